@@ -1,6 +1,9 @@
 use anyhow::Result;
 use clap::Parser;
-use cli::{tui::handle_sql_command, Cli, Commands, MigrateArgs, SqlArgs};
+use cli::{
+    tui::{handle_migrate_command, handle_sql_command},
+    Cli, Commands, SqlArgs,
+};
 use common::util::trace::initialize_tracing;
 use compile::parser::parse;
 use network::client::start_client;
@@ -80,8 +83,8 @@ async fn async_main() -> Result<ExitCode> {
                 start_server(args).await;
             }
             Commands::Migrate(args) => {
-                info!("Handling database migration");
-                handle_migration(args);
+                info!(migrations_dir = ?args.migrations_dir(), action = ?args.action(), "Handling database migration");
+                handle_migrate_command(args).await?;
             }
             Commands::Client(args) => {
                 info!("Starting client");
@@ -92,8 +95,3 @@ async fn async_main() -> Result<ExitCode> {
 
     Ok(ExitCode::SUCCESS)
 }
-
-fn handle_migration(args: &MigrateArgs) {
-    info!(migrations_dir = ?args.migrations_dir(), action = ?args.action(), "Processing migrations");
-    // TODO: Implement migrations
-}
@@ -0,0 +1,72 @@
+//! # Page Guards
+//!
+//! A page guard holds the latch of the buffer pool frame a page resides in for as long as it
+//! lives: shared for a [`ReadPageGuard`] and exclusive for a [`WritePageGuard`]. Each frame
+//! has its own latch, so guards over different pages never contend, while guards over the
+//! same page serialize writers against every other access.
+//!
+//! Guards own their latch, so they can be held (and sent to other tasks) after the
+//! [`BufferPoolManager`](crate::BufferPoolManager) they were taken from is released.
+
+use common::PageId;
+use parking_lot::{lock_api::ArcRwLockReadGuard, lock_api::ArcRwLockWriteGuard, RawRwLock};
+use std::ops::{Deref, DerefMut};
+use storage::page::Page;
+
+/// Shared access to a page resident in the buffer pool.
+pub struct ReadPageGuard {
+    page_id: PageId,
+    latch: ArcRwLockReadGuard<RawRwLock, Page>,
+}
+
+impl ReadPageGuard {
+    pub(crate) fn new(page_id: PageId, latch: ArcRwLockReadGuard<RawRwLock, Page>) -> Self {
+        ReadPageGuard { page_id, latch }
+    }
+
+    pub fn page_id(&self) -> PageId {
+        self.page_id
+    }
+}
+
+impl Deref for ReadPageGuard {
+    type Target = Page;
+
+    fn deref(&self) -> &Page {
+        &self.latch
+    }
+}
+
+/// Exclusive access to a page resident in the buffer pool.
+///
+/// Changes made through the guard bypass the write-ahead log, so callers that modify the
+/// page must log the change and mark the page dirty themselves (as
+/// [`BufferPoolManager::write_data`](crate::BufferPoolManager::write_data) does).
+pub struct WritePageGuard {
+    page_id: PageId,
+    latch: ArcRwLockWriteGuard<RawRwLock, Page>,
+}
+
+impl WritePageGuard {
+    pub(crate) fn new(page_id: PageId, latch: ArcRwLockWriteGuard<RawRwLock, Page>) -> Self {
+        WritePageGuard { page_id, latch }
+    }
+
+    pub fn page_id(&self) -> PageId {
+        self.page_id
+    }
+}
+
+impl Deref for WritePageGuard {
+    type Target = Page;
+
+    fn deref(&self) -> &Page {
+        &self.latch
+    }
+}
+
+impl DerefMut for WritePageGuard {
+    fn deref_mut(&mut self) -> &mut Page {
+        &mut self.latch
+    }
+}
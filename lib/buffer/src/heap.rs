@@ -0,0 +1,276 @@
+//! # Table Heaps
+//!
+//! A [`TableHeap`] stores the records of a table (e.g. its encoded rows) in
+//! [`SlottedPage`]s held in the buffer pool, appending to its last page until it is full and
+//! then to a newly allocated page. Each page starts with the `u32` id of the next page of the
+//! heap (`u32::MAX` on the last page), followed by the slotted page, so that its owner (e.g.
+//! the catalog) only has to remember the first page to reopen the heap (see
+//! [`TableHeap::open`]).
+
+use crate::BufferPoolManager;
+use anyhow::Result;
+use common::{rid::RID, PageId, USABLE_PAGE_SIZE};
+use storage::slotted_page::SlottedPage;
+use thiserror::Error;
+use tracing::debug;
+
+/// The size of the next page id at the start of each heap page.
+const PAGE_HEADER_SIZE: usize = 4;
+
+/// The size of the slotted page following the header of each heap page.
+const SLOTTED_PAGE_SIZE: usize = USABLE_PAGE_SIZE - PAGE_HEADER_SIZE;
+
+/// The next page id of the last page of a heap.
+const NO_NEXT_PAGE: u32 = u32::MAX;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TableHeapError {
+    #[error("Record of {0} bytes exceeds the {max} bytes a heap page holds", max = TableHeap::max_record_len())]
+    RecordTooLarge(usize),
+}
+
+/// The pages holding the records of a table, in insertion order.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TableHeap {
+    pages: Vec<PageId>,
+}
+
+impl TableHeap {
+    /// Creates a heap of the records in the given pages.
+    pub fn new(pages: Vec<PageId>) -> Self {
+        Self { pages }
+    }
+
+    /// Reopens the heap starting at `first_page`, following the links between its pages.
+    pub async fn open(bpm: &mut BufferPoolManager, first_page: PageId) -> Result<Self> {
+        let mut pages = Vec::new();
+        let mut next_page = Some(first_page);
+        while let Some(page_id) = next_page {
+            if pages.contains(&page_id) {
+                anyhow::bail!("Heap page {} links back to an earlier page", page_id);
+            }
+            pages.push(page_id);
+            let (next, _) = Self::fetch(bpm, page_id).await?;
+            bpm.unpin_page(page_id, false)?;
+            next_page = next;
+        }
+        debug!("Opened heap of {} pages", pages.len());
+        Ok(Self { pages })
+    }
+
+    /// Returns the pages of the heap, in the order they were allocated.
+    pub fn pages(&self) -> &[PageId] {
+        &self.pages
+    }
+
+    /// Returns the length of the largest record a heap can hold.
+    pub fn max_record_len() -> usize {
+        SlottedPage::max_record_len(SLOTTED_PAGE_SIZE)
+    }
+
+    /// Fetches a page of the heap, pinning it, as the id of the next page (if any) and a
+    /// slotted page.
+    async fn fetch(
+        bpm: &mut BufferPoolManager,
+        page_id: PageId,
+    ) -> Result<(Option<PageId>, SlottedPage)> {
+        let page = bpm
+            .fetch_page(page_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Heap page {} could not be fetched", page_id))?;
+        let data = &page.data()[..USABLE_PAGE_SIZE.min(page.data().len())];
+        let next = data
+            .get(..PAGE_HEADER_SIZE)
+            .map(|next| u32::from_be_bytes(next.try_into().expect("header is 4 bytes")))
+            .filter(|&next| next != NO_NEXT_PAGE)
+            .map(PageId::from);
+        let data = data.get(PAGE_HEADER_SIZE..).unwrap_or_default().to_vec();
+        match SlottedPage::from_bytes(data) {
+            Ok(page) => Ok((next, page)),
+            Err(e) => {
+                bpm.unpin_page(page_id, false)?;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Appends records to the heap, returning where each one was stored. Either all records
+    /// are inserted or, if any of them can't be, none is: every record is placed before any
+    /// page is written, and the pages allocated for them are released on failure.
+    pub async fn insert_records(
+        &mut self,
+        bpm: &mut BufferPoolManager,
+        records: &[Vec<u8>],
+    ) -> Result<Vec<RID>> {
+        if let Some(record) = records
+            .iter()
+            .find(|record| record.len() > Self::max_record_len())
+        {
+            return Err(TableHeapError::RecordTooLarge(record.len()).into());
+        }
+        if records.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // The pinned pages the records are placed in, the first of which may be the last page
+        // of the heap
+        let mut pinned = Vec::new();
+        if let Some(&last) = self.pages.last() {
+            pinned.push((last, Self::fetch(bpm, last).await?.1));
+        }
+        let placed = self.place_records(bpm, records, &mut pinned).await;
+
+        let appended = usize::from(!self.pages.is_empty());
+        let written = match placed {
+            Ok(rids) => Self::write_pages(bpm, &pinned).await.map(|_| rids),
+            Err(e) => Err(e),
+        };
+        for (page_id, _) in &pinned {
+            bpm.unpin_page(*page_id, written.is_ok())?;
+        }
+        match written {
+            Ok(rids) => {
+                let new_pages = pinned.iter().skip(appended).map(|(page_id, _)| *page_id);
+                self.pages.extend(new_pages);
+                debug!("Inserted {} records into the heap", rids.len());
+                Ok(rids)
+            }
+            Err(e) => {
+                for (page_id, _) in pinned.iter().skip(appended) {
+                    bpm.delete_page(*page_id).await?;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Adds the records to the last of the pinned pages, allocating (and pinning) new pages
+    /// as they fill up.
+    async fn place_records(
+        &self,
+        bpm: &mut BufferPoolManager,
+        records: &[Vec<u8>],
+        pinned: &mut Vec<(PageId, SlottedPage)>,
+    ) -> Result<Vec<RID>> {
+        let mut rids = Vec::with_capacity(records.len());
+        for record in records {
+            if !matches!(pinned.last(), Some((_, page)) if page.fits(record.len())) {
+                let (page_id, _) = bpm.new_page().await?;
+                pinned.push((page_id, SlottedPage::new(SLOTTED_PAGE_SIZE)));
+            }
+            let (page_id, page) = pinned.last_mut().expect("a page was just pinned");
+            let slot = page.try_add_record(record)?;
+            rids.push(RID::new(*page_id, slot as u32));
+        }
+        Ok(rids)
+    }
+
+    /// Returns the records of the heap, page by page in slot order.
+    pub async fn scan(&self, bpm: &mut BufferPoolManager) -> Result<Vec<Vec<u8>>> {
+        let mut records = Vec::new();
+        for &page_id in &self.pages {
+            let (_, page) = Self::fetch(bpm, page_id).await?;
+            records.extend(page.records().map(<[u8]>::to_vec));
+            bpm.unpin_page(page_id, false)?;
+        }
+        Ok(records)
+    }
+
+    /// Deletes the pages of the heap (e.g. once its table is dropped), so that they can be
+    /// reused by later allocations.
+    pub async fn free(self, bpm: &mut BufferPoolManager) -> Result<()> {
+        for page_id in self.pages {
+            bpm.delete_page(page_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Writes the pinned pages, each linking to the one after it. The first of them is either
+    /// the first page of the heap or its last page, which linked to none.
+    async fn write_pages(
+        bpm: &mut BufferPoolManager,
+        pages: &[(PageId, SlottedPage)],
+    ) -> Result<()> {
+        for (index, (page_id, page)) in pages.iter().enumerate() {
+            let next = pages
+                .get(index + 1)
+                .map_or(NO_NEXT_PAGE, |(next, _)| next.0);
+            let mut data = next.to_be_bytes().to_vec();
+            data.extend_from_slice(page.as_bytes());
+            bpm.write_data(*page_id, &data).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ReplacementPolicy;
+    use std::sync::Arc;
+    use storage::disk::{DiskManager, IN_MEMORY_PATH};
+
+    fn setup(pool_size: usize) -> BufferPoolManager {
+        let disk_manager = Arc::new(DiskManager::new(IN_MEMORY_PATH).unwrap());
+        BufferPoolManager::new_with_size(ReplacementPolicy::LRU, disk_manager, pool_size).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_records_spill_into_new_pages() {
+        let mut bpm = setup(10);
+        let mut heap = TableHeap::default();
+        let record = vec![1; TableHeap::max_record_len() / 2];
+
+        let rids = heap
+            .insert_records(&mut bpm, &[record.clone(), record.clone()])
+            .await
+            .unwrap();
+        let rids = [
+            rids,
+            heap.insert_records(&mut bpm, &[record]).await.unwrap(),
+        ]
+        .concat();
+
+        assert_eq!(heap.pages().len(), 3);
+        let pages = rids
+            .iter()
+            .map(|rid| rid.page_id.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(pages, heap.pages());
+        assert!(rids.iter().all(|rid| rid.slot_num == 0));
+        assert_eq!(heap.scan(&mut bpm).await.unwrap().len(), 3);
+
+        // The heap is found again from its first page
+        let reopened = TableHeap::open(&mut bpm, heap.pages()[0]).await.unwrap();
+        assert_eq!(reopened, heap);
+        // Once freed, its pages are reused
+        reopened.free(&mut bpm).await.unwrap();
+        let (page_id, _) = bpm.new_page().await.unwrap();
+        assert!(heap.pages().contains(&page_id));
+    }
+
+    #[tokio::test]
+    async fn test_failed_insert_leaves_the_heap_untouched() {
+        let mut bpm = setup(2);
+        let mut heap = TableHeap::default();
+        heap.insert_records(&mut bpm, &[vec![1; 8]]).await.unwrap();
+        let page = heap.pages()[0];
+        let before = bpm.read_data(page).await.unwrap();
+
+        // With the other frame pinned, no page can be allocated for the second record
+        let record = vec![2; TableHeap::max_record_len()];
+        let _pinned = bpm.new_page().await.unwrap();
+        assert!(heap
+            .insert_records(&mut bpm, &[vec![3; 8], record])
+            .await
+            .is_err());
+        assert_eq!(heap.pages(), [page]);
+        assert_eq!(bpm.read_data(page).await.unwrap(), before);
+
+        let err = heap
+            .insert_records(&mut bpm, &[vec![0; TableHeap::max_record_len() + 1]])
+            .await
+            .unwrap_err();
+        assert!(err.is::<TableHeapError>());
+    }
+}
@@ -1,8 +1,12 @@
 #![allow(dead_code)]
 
 mod alloc;
+pub mod guard;
+pub mod heap;
 pub mod manager;
 pub mod replacer;
 
+pub use guard::{ReadPageGuard, WritePageGuard};
+pub use heap::{TableHeap, TableHeapError};
 pub use manager::*;
 pub use replacer::*;
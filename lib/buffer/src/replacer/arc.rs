@@ -0,0 +1,389 @@
+//! # ARC (Adaptive Replacement Cache) Replacer
+//!
+//! [`ARCReplacer`] balances recency and frequency by splitting resident frames
+//! into two lists: `recent` (seen once) and `frequent` (seen at least twice).
+//! Each list is shadowed by a ghost list remembering the pages it recently evicted.
+//! A hit in a ghost list means that list was evicted too eagerly, so the target
+//! size of the `recent` list is adjusted towards it. This makes the policy behave
+//! like LRU on recency-heavy workloads and like LFU on frequency-heavy ones, while
+//! one-off scans only ever churn the `recent` list and leave the working set alone.
+//!
+//! Ghost lists are keyed by page rather than by frame, since the buffer pool reuses a
+//! frame for another page as soon as it is evicted. Accesses recorded without a page,
+//! through [`ARCReplacer::record_access`], use the frame id as the page id.
+
+use crate::replacer::{Replacer, ReplacerStats};
+use common::{FrameId, PageId};
+use lru::LruCache;
+use std::{collections::HashMap, fmt, time::Instant};
+use tracing::{debug, info, warn};
+use typed_builder::TypedBuilder;
+
+/// `ARCReplacer` implements the Adaptive Replacement Cache policy.
+///
+/// Resident frames store whether they are evictable; ghost lists only store page ids.
+#[derive(Debug, TypedBuilder)]
+pub struct ARCReplacer {
+    capacity: usize,
+    // Target size of the `recent` list, adapted on ghost hits.
+    target_recent_size: usize,
+    // Frames accessed once since they entered the cache (T1).
+    recent: LruCache<FrameId, bool>,
+    // Frames accessed at least twice since they entered the cache (T2).
+    frequent: LruCache<FrameId, bool>,
+    // The page held by each resident frame.
+    pages: HashMap<FrameId, PageId>,
+    // Pages recently evicted from `recent` (B1).
+    recent_ghosts: LruCache<PageId, ()>,
+    // Pages recently evicted from `frequent` (B2).
+    frequent_ghosts: LruCache<PageId, ()>,
+    stats: ReplacerStats,
+}
+
+impl ARCReplacer {
+    /// Constructs a new `ARCReplacer` tracking at most `capacity` resident frames.
+    pub fn new(capacity: usize) -> Self {
+        info!("Initializing ARC Replacer with capacity {}", capacity);
+        ARCReplacer::builder()
+            .capacity(capacity.max(1))
+            .target_recent_size(0)
+            .recent(LruCache::unbounded())
+            .frequent(LruCache::unbounded())
+            .pages(HashMap::new())
+            .recent_ghosts(LruCache::unbounded())
+            .frequent_ghosts(LruCache::unbounded())
+            .stats(ReplacerStats::new())
+            .build()
+    }
+
+    /// Records an access to a frame, marking it as non-evictable. The frame id doubles as
+    /// the id of the page it holds.
+    pub fn record_access(&mut self, frame_id: FrameId) {
+        self.record_page_access(frame_id, PageId::from(frame_id.0))
+    }
+
+    /// Records an access to a frame holding `page_id`, marking it as non-evictable.
+    ///
+    /// Resident frames and ghost hits are promoted to the `frequent` list; ghost hits
+    /// also shift the target split towards the list the page was evicted from.
+    pub fn record_page_access(&mut self, frame_id: FrameId, page_id: PageId) {
+        let start = Instant::now();
+
+        self.pages.insert(frame_id, page_id);
+        if self.recent.pop(&frame_id).is_some() || self.frequent.contains(&frame_id) {
+            debug!(frame_id = ?frame_id, "ARC hit, promoting to frequent list");
+            self.frequent.put(frame_id, false);
+            self.stats.increment_cache_hits();
+        } else if self.recent_ghosts.pop(&page_id).is_some() {
+            let delta = (self.frequent_ghosts.len() / (self.recent_ghosts.len() + 1)).max(1);
+            self.target_recent_size = (self.target_recent_size + delta).min(self.capacity);
+            debug!(page_id = ?page_id, target = self.target_recent_size, "ARC recent ghost hit");
+            self.frequent.put(frame_id, false);
+            self.stats.increment_cache_misses();
+        } else if self.frequent_ghosts.pop(&page_id).is_some() {
+            let delta = (self.recent_ghosts.len() / (self.frequent_ghosts.len() + 1)).max(1);
+            self.target_recent_size = self.target_recent_size.saturating_sub(delta);
+            debug!(page_id = ?page_id, target = self.target_recent_size, "ARC frequent ghost hit");
+            self.frequent.put(frame_id, false);
+            self.stats.increment_cache_misses();
+        } else {
+            debug!(frame_id = ?frame_id, "ARC miss, adding to recent list");
+            self.trim_ghosts();
+            self.recent.put(frame_id, false);
+            self.stats.increment_cache_misses();
+        }
+
+        self.stats
+            .set_current_cache_size(self.recent.len() + self.frequent.len());
+        self.stats.increment_requests();
+        self.stats.update_latency(start.elapsed());
+    }
+
+    /// Marks a resident frame as evictable or non-evictable.
+    pub fn set_evictable(&mut self, frame_id: FrameId, evictable: bool) {
+        if let Some(flag) = self.recent.peek_mut(&frame_id) {
+            *flag = evictable;
+        } else if let Some(flag) = self.frequent.peek_mut(&frame_id) {
+            *flag = evictable;
+        }
+    }
+
+    /// Evicts an evictable frame, preferring the `recent` list while it exceeds its
+    /// target size. The page of the evicted frame is remembered in the matching ghost list.
+    pub fn evict(&mut self) -> Option<FrameId> {
+        self.evict_skipping(|_| false)
+    }
+
+    /// Like [`ARCReplacer::evict`], but only evicts a frame for which `skip` returns `true`
+    /// if every other evictable frame is skipped as well.
+    pub fn evict_skipping(&mut self, skip: impl Fn(FrameId) -> bool) -> Option<FrameId> {
+        let start = Instant::now();
+
+        let prefer_recent = self.recent.len() > self.target_recent_size;
+        let evicted = self
+            .evict_preferred(prefer_recent, &skip)
+            .or_else(|| self.evict_preferred(prefer_recent, &|_| false));
+
+        match evicted {
+            Some(frame_id) => {
+                debug!(frame_id = ?frame_id, "Evicted frame from ARC Replacer");
+                self.stats.increment_cache_evictions();
+            }
+            None => warn!("No frame evicted from ARC Replacer: all frames are in use"),
+        }
+
+        self.stats.update_latency(start.elapsed());
+        evicted
+    }
+
+    /// Stops tracking a frame, without remembering its page in the ghost lists.
+    pub fn remove(&mut self, frame_id: FrameId) {
+        self.recent.pop(&frame_id);
+        self.frequent.pop(&frame_id);
+        self.pages.remove(&frame_id);
+        self.stats
+            .set_current_cache_size(self.recent.len() + self.frequent.len());
+    }
+
+    /// Returns the number of evictable resident frames.
+    pub fn size(&self) -> usize {
+        self.recent
+            .iter()
+            .chain(self.frequent.iter())
+            .filter(|(_, &evictable)| evictable)
+            .count()
+    }
+
+    /// Returns the current target size of the `recent` list.
+    pub fn target_recent_size(&self) -> usize {
+        self.target_recent_size
+    }
+
+    /// Returns whether a frame is currently resident in the replacer.
+    pub fn contains(&self, frame_id: FrameId) -> bool {
+        self.recent.contains(&frame_id) || self.frequent.contains(&frame_id)
+    }
+
+    /// Returns the statistics collected by the replacer.
+    pub fn stats(&self) -> &ReplacerStats {
+        &self.stats
+    }
+
+    fn evict_preferred(
+        &mut self,
+        prefer_recent: bool,
+        skip: &dyn Fn(FrameId) -> bool,
+    ) -> Option<FrameId> {
+        if prefer_recent {
+            self.evict_recent(skip)
+                .or_else(|| self.evict_frequent(skip))
+        } else {
+            self.evict_frequent(skip)
+                .or_else(|| self.evict_recent(skip))
+        }
+    }
+
+    fn evict_recent(&mut self, skip: &dyn Fn(FrameId) -> bool) -> Option<FrameId> {
+        let frame_id = Self::lru_evictable(&self.recent, skip)?;
+        self.recent.pop(&frame_id);
+        if let Some(page_id) = self.pages.remove(&frame_id) {
+            self.recent_ghosts.put(page_id, ());
+        }
+        Some(frame_id)
+    }
+
+    fn evict_frequent(&mut self, skip: &dyn Fn(FrameId) -> bool) -> Option<FrameId> {
+        let frame_id = Self::lru_evictable(&self.frequent, skip)?;
+        self.frequent.pop(&frame_id);
+        if let Some(page_id) = self.pages.remove(&frame_id) {
+            self.frequent_ghosts.put(page_id, ());
+        }
+        Some(frame_id)
+    }
+
+    fn lru_evictable(
+        list: &LruCache<FrameId, bool>,
+        skip: &dyn Fn(FrameId) -> bool,
+    ) -> Option<FrameId> {
+        list.iter()
+            .rev()
+            .find(|(&frame_id, &evictable)| evictable && !skip(frame_id))
+            .map(|(&frame_id, _)| frame_id)
+    }
+
+    /// Keeps the ghost lists bounded so that `recent + recent_ghosts` stays within
+    /// the capacity and the whole directory stays within twice the capacity.
+    fn trim_ghosts(&mut self) {
+        while !self.recent_ghosts.is_empty()
+            && self.recent.len() + self.recent_ghosts.len() >= self.capacity
+        {
+            self.recent_ghosts.pop_lru();
+        }
+
+        let resident = self.recent.len() + self.frequent.len();
+        while !self.frequent_ghosts.is_empty()
+            && resident + self.recent_ghosts.len() + self.frequent_ghosts.len() >= 2 * self.capacity
+        {
+            self.frequent_ghosts.pop_lru();
+        }
+    }
+}
+
+impl Replacer for ARCReplacer {
+    fn record_access(&mut self, frame_id: FrameId) {
+        ARCReplacer::record_access(self, frame_id)
+    }
+
+    fn record_page_access(&mut self, frame_id: FrameId, page_id: PageId) {
+        ARCReplacer::record_page_access(self, frame_id, page_id)
+    }
+
+    fn set_evictable(&mut self, frame_id: FrameId, evictable: bool) {
+        ARCReplacer::set_evictable(self, frame_id, evictable)
+    }
+
+    fn evict_skipping(&mut self, skip: &dyn Fn(FrameId) -> bool) -> Option<FrameId> {
+        ARCReplacer::evict_skipping(self, skip)
+    }
+
+    fn remove(&mut self, frame_id: FrameId) {
+        ARCReplacer::remove(self, frame_id)
+    }
+
+    fn size(&self) -> usize {
+        ARCReplacer::size(self)
+    }
+
+    fn get_statistics(&self) -> ReplacerStats {
+        self.stats.clone()
+    }
+}
+
+impl fmt::Display for ARCReplacer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "ARCReplacer (capacity: {}, target recent size: {})",
+            self.capacity, self.target_recent_size
+        )?;
+        writeln!(
+            f,
+            "Recent: {}, Frequent: {}, Recent ghosts: {}, Frequent ghosts: {}",
+            self.recent.len(),
+            self.frequent.len(),
+            self.recent_ghosts.len(),
+            self.frequent_ghosts.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Simulates a cache of `capacity` frames where ids double as page ids, evicting
+    /// through the replacer on a miss once the cache is full.
+    fn access(replacer: &mut ARCReplacer, capacity: usize, frame_id: FrameId) {
+        if !replacer.contains(frame_id)
+            && replacer.recent.len() + replacer.frequent.len() >= capacity
+        {
+            replacer.evict().expect("a frame should be evictable");
+        }
+        replacer.record_access(frame_id);
+        replacer.set_evictable(frame_id, true);
+    }
+
+    #[test]
+    fn test_new_replacer() {
+        let replacer = ARCReplacer::new(4);
+        assert_eq!(replacer.size(), 0);
+        assert_eq!(replacer.target_recent_size(), 0);
+    }
+
+    #[test]
+    fn test_pinned_frames_are_not_victims() {
+        let mut replacer = ARCReplacer::new(2);
+        replacer.record_access(FrameId::new(1));
+        replacer.record_access(FrameId::new(2));
+        assert_eq!(replacer.size(), 0);
+        assert_eq!(replacer.evict(), None);
+
+        replacer.set_evictable(FrameId::new(2), true);
+        assert_eq!(replacer.size(), 1);
+        assert_eq!(replacer.evict(), Some(FrameId::new(2)));
+        assert_eq!(replacer.evict(), None);
+    }
+
+    #[test]
+    fn test_scan_does_not_evict_working_set() {
+        let capacity = 4;
+        let mut replacer = ARCReplacer::new(capacity);
+        let working_set = [FrameId::new(0), FrameId::new(1)];
+
+        for _ in 0..2 {
+            for &frame_id in &working_set {
+                access(&mut replacer, capacity, frame_id);
+            }
+        }
+
+        // A long one-off scan only churns the recent list.
+        for id in 100..200 {
+            access(&mut replacer, capacity, FrameId::new(id));
+        }
+
+        for &frame_id in &working_set {
+            assert!(
+                replacer.contains(frame_id),
+                "Working set frame {:?} should survive the scan",
+                frame_id
+            );
+        }
+    }
+
+    #[test]
+    fn test_ghosts_are_keyed_by_page() {
+        fn access(replacer: &mut ARCReplacer, frame_id: u32, page_id: u32) {
+            replacer.record_page_access(FrameId::new(frame_id), PageId::from(page_id));
+            replacer.set_evictable(FrameId::new(frame_id), true);
+        }
+        let mut replacer = ARCReplacer::new(4);
+        access(&mut replacer, 0, 10);
+        access(&mut replacer, 1, 11);
+        assert_eq!(replacer.evict(), Some(FrameId::new(0)));
+
+        // The evicted frame is reused for another page, which is a plain miss
+        access(&mut replacer, 0, 12);
+        assert_eq!(replacer.target_recent_size(), 0);
+        assert!(replacer.recent.contains(&FrameId::new(0)));
+
+        // The evicted page coming back, in whichever frame, is a ghost hit
+        assert_eq!(replacer.evict(), Some(FrameId::new(1)));
+        access(&mut replacer, 1, 10);
+        assert_eq!(replacer.target_recent_size(), 1);
+        assert!(replacer.frequent.contains(&FrameId::new(1)));
+    }
+
+    #[test]
+    fn test_ghost_hits_adapt_target_split() {
+        let capacity = 4;
+        let mut replacer = ARCReplacer::new(capacity);
+
+        for id in [1, 1, 2, 3, 4, 5] {
+            access(&mut replacer, capacity, FrameId::new(id));
+        }
+        assert!(!replacer.contains(FrameId::new(2)));
+
+        // Re-accessing an id evicted from the recent list grows its target size.
+        access(&mut replacer, capacity, FrameId::new(2));
+        assert_eq!(replacer.target_recent_size(), 1);
+        assert!(replacer.frequent.contains(&FrameId::new(2)));
+
+        // With the recent list pinned, the victim comes from the frequent list,
+        // and hitting its ghost shrinks the target again.
+        replacer.set_evictable(FrameId::new(4), false);
+        replacer.set_evictable(FrameId::new(5), false);
+        assert_eq!(replacer.evict(), Some(FrameId::new(1)));
+        access(&mut replacer, capacity, FrameId::new(1));
+        assert_eq!(replacer.target_recent_size(), 0);
+    }
+}
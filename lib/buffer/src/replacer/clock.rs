@@ -0,0 +1,279 @@
+//! # Clock (Second-Chance) Cache Replacer
+//!
+//! `ClockReplacer` approximates LRU at a fraction of its bookkeeping: instead of keeping its
+//! frames in access order, it keeps them in a circular buffer with a reference bit each, which
+//! is set whenever a frame is accessed, pinned or unpinned. To find a victim, the clock hand
+//! sweeps the buffer, clearing the reference bits of the evictable frames it passes and evicting
+//! the first one whose bit is already clear. A frame referenced since the hand last passed it is
+//! thus given a second chance, and only evicted once the hand comes back around to it.
+
+use crate::replacer::{Replacer, ReplacerStats};
+use common::FrameId;
+use std::{collections::HashMap, fmt};
+use tracing::{debug, info, warn};
+use typed_builder::TypedBuilder;
+
+/// A frame in the circular buffer of the clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ClockEntry {
+    frame_id: FrameId,
+    referenced: bool,
+    evictable: bool,
+}
+
+/// `ClockReplacer` implements the clock, or second-chance, cache replacement policy.
+///
+/// Frames are placed in the slots of a circular buffer as they are first tracked, and keep
+/// their slot until they are evicted or removed. Eviction advances the hand from where it
+/// last stopped, so frames are considered in slot order rather than in access order.
+#[derive(Debug, TypedBuilder)]
+pub struct ClockReplacer {
+    // The circular buffer of frames, in which removed frames leave empty slots.
+    slots: Vec<Option<ClockEntry>>,
+    // The slot of each tracked frame.
+    positions: HashMap<FrameId, usize>,
+    // Empty slots, reused before the buffer grows.
+    free_slots: Vec<usize>,
+    // The slot the next sweep starts from.
+    hand: usize,
+    // Statistical data for cache operations.
+    stats: ReplacerStats,
+}
+
+impl ClockReplacer {
+    /// Constructs a new `ClockReplacer` with room for `capacity` frames, growing beyond it
+    /// if more frames are tracked.
+    pub fn new(capacity: usize) -> Self {
+        info!("Initializing Clock Replacer with capacity {}", capacity);
+        ClockReplacer::builder()
+            .slots(Vec::with_capacity(capacity))
+            .positions(HashMap::with_capacity(capacity))
+            .free_slots(Vec::new())
+            .hand(0)
+            .stats(ReplacerStats::new())
+            .build()
+    }
+
+    /// Returns the entry of a tracked frame, placing the frame in a slot if it is new.
+    fn entry(&mut self, frame_id: FrameId) -> &mut ClockEntry {
+        let slot = match self.positions.get(&frame_id) {
+            Some(&slot) => {
+                self.stats.increment_cache_hits();
+                slot
+            }
+            None => {
+                self.stats.increment_cache_misses();
+                let entry = Some(ClockEntry {
+                    frame_id,
+                    referenced: false,
+                    evictable: false,
+                });
+                let slot = match self.free_slots.pop() {
+                    Some(slot) => {
+                        self.slots[slot] = entry;
+                        slot
+                    }
+                    None => {
+                        self.slots.push(entry);
+                        self.slots.len() - 1
+                    }
+                };
+                self.positions.insert(frame_id, slot);
+                self.stats.set_current_cache_size(self.positions.len());
+                slot
+            }
+        };
+        self.slots[slot]
+            .as_mut()
+            .expect("tracked frames have a slot")
+    }
+
+    /// Records an access to a frame, setting its reference bit and pinning it.
+    pub fn record_access(&mut self, frame_id: FrameId) {
+        let entry = self.entry(frame_id);
+        entry.referenced = true;
+        entry.evictable = false;
+        self.stats.increment_requests();
+        debug!(frame_id = ?frame_id, "Recorded access in Clock Replacer");
+    }
+
+    /// Marks a frame as evictable (unpinned) or non-evictable (pinned), tracking it if it is
+    /// new. Either way, its reference bit is set.
+    pub fn set_evictable(&mut self, frame_id: FrameId, evictable: bool) {
+        let entry = self.entry(frame_id);
+        entry.referenced = true;
+        entry.evictable = evictable;
+    }
+
+    /// Evicts the first frame the clock hand reaches that is evictable and unreferenced.
+    pub fn evict(&mut self) -> Option<FrameId> {
+        self.evict_skipping(|_| false)
+    }
+
+    /// Evicts the first evictable, unreferenced frame the clock hand reaches among those for
+    /// which `skip` returns `false`, falling back to the skipped frames if there are no
+    /// others. The hand passes over skipped frames without clearing their reference bits.
+    pub fn evict_skipping(&mut self, skip: impl Fn(FrameId) -> bool) -> Option<FrameId> {
+        let evicted = self.sweep(&skip).or_else(|| self.sweep(&|_| false));
+        match evicted {
+            Some(frame_id) => {
+                self.remove(frame_id);
+                self.stats.increment_cache_evictions();
+                debug!(frame_id = ?frame_id, "Evicted frame from Clock Replacer");
+            }
+            None => warn!("No evictable frames available for eviction"),
+        }
+        evicted
+    }
+
+    /// Advances the hand until it reaches a victim, for at most two turns of the clock: by
+    /// then, the reference bit of every candidate has been cleared.
+    fn sweep(&mut self, skip: &dyn Fn(FrameId) -> bool) -> Option<FrameId> {
+        let len = self.slots.len();
+        for _ in 0..2 * len {
+            let slot = self.hand;
+            self.hand = (self.hand + 1) % len;
+            match &mut self.slots[slot] {
+                Some(entry) if entry.evictable && !skip(entry.frame_id) => {
+                    if !entry.referenced {
+                        return Some(entry.frame_id);
+                    }
+                    // The frame's second chance
+                    entry.referenced = false;
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Stops tracking a frame, leaving its slot empty.
+    pub fn remove(&mut self, frame_id: FrameId) {
+        if let Some(slot) = self.positions.remove(&frame_id) {
+            self.slots[slot] = None;
+            self.free_slots.push(slot);
+            self.stats.set_current_cache_size(self.positions.len());
+        }
+    }
+
+    /// Returns the number of evictable frames in the replacer.
+    pub fn size(&self) -> usize {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|entry| entry.evictable)
+            .count()
+    }
+}
+
+impl Replacer for ClockReplacer {
+    fn record_access(&mut self, frame_id: FrameId) {
+        ClockReplacer::record_access(self, frame_id)
+    }
+
+    fn set_evictable(&mut self, frame_id: FrameId, evictable: bool) {
+        ClockReplacer::set_evictable(self, frame_id, evictable)
+    }
+
+    fn evict_skipping(&mut self, skip: &dyn Fn(FrameId) -> bool) -> Option<FrameId> {
+        ClockReplacer::evict_skipping(self, skip)
+    }
+
+    fn remove(&mut self, frame_id: FrameId) {
+        ClockReplacer::remove(self, frame_id)
+    }
+
+    fn size(&self) -> usize {
+        ClockReplacer::size(self)
+    }
+
+    fn get_statistics(&self) -> ReplacerStats {
+        self.stats.clone()
+    }
+}
+
+impl fmt::Display for ClockReplacer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "ClockReplacer (size: {}, hand: {})",
+            self.positions.len(),
+            self.hand
+        )?;
+        for (slot, entry) in self.slots.iter().enumerate() {
+            if let Some(entry) = entry {
+                writeln!(
+                    f,
+                    "Slot {}: Frame ID: {:?}, Referenced: {}, Evictable: {}",
+                    slot, entry.frame_id, entry.referenced, entry.evictable
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replacer::ReplacementPolicy;
+
+    /// Accesses and unpins frames, as the buffer pool does when a page is fetched and released.
+    fn touch(replacer: &mut dyn Replacer, frame_ids: &[u32]) {
+        for &frame_id in frame_ids {
+            replacer.record_access(FrameId::new(frame_id));
+            replacer.set_evictable(FrameId::new(frame_id), true);
+        }
+    }
+
+    #[test]
+    fn test_referenced_frame_is_given_a_second_chance() {
+        let mut replacer = ClockReplacer::new(3);
+        touch(&mut replacer, &[1, 2, 3]);
+        // Every frame is referenced, so the hand clears them all and comes back to the first
+        assert_eq!(replacer.evict(), Some(FrameId::new(1)));
+
+        // Frame 2 is referenced again, so frame 3 is evicted before it
+        touch(&mut replacer, &[2]);
+        assert_eq!(replacer.evict(), Some(FrameId::new(3)));
+        // Its reference bit was cleared by the hand, so it goes next
+        assert_eq!(replacer.evict(), Some(FrameId::new(2)));
+        assert_eq!(replacer.evict(), None);
+    }
+
+    #[test]
+    fn test_clock_order_differs_from_lru_order() {
+        let victim = |policy: ReplacementPolicy| {
+            let mut replacer = policy.replacer(3);
+            touch(replacer.as_mut(), &[1, 2, 3]);
+            assert_eq!(replacer.evict(), Some(FrameId::new(1)), "{:?}", policy);
+            // Frame 4 takes the slot of frame 1, behind the hand, and frames 2 and 3 are
+            // referenced again after it
+            touch(replacer.as_mut(), &[4, 2, 3]);
+            replacer.evict()
+        };
+
+        // Frame 4 is the least recently used, but the hand reaches frame 2 again first
+        assert_eq!(victim(ReplacementPolicy::LRU), Some(FrameId::new(4)));
+        assert_eq!(victim(ReplacementPolicy::Clock), Some(FrameId::new(2)));
+    }
+
+    #[test]
+    fn test_pinned_and_skipped_frames_are_passed_over() {
+        let mut replacer: Box<dyn Replacer> = Box::new(ClockReplacer::new(3));
+        touch(replacer.as_mut(), &[1, 2, 3]);
+        replacer.record_access(FrameId::new(1));
+        assert_eq!(replacer.size(), 2);
+
+        // Frame 2 is skipped as long as frame 3 can be evicted instead
+        let skip_2 = |frame_id: FrameId| frame_id == FrameId::new(2);
+        assert_eq!(replacer.evict_skipping(&skip_2), Some(FrameId::new(3)));
+        assert_eq!(replacer.evict_skipping(&skip_2), Some(FrameId::new(2)));
+        assert_eq!(replacer.evict(), None);
+
+        replacer.remove(FrameId::new(1));
+        assert_eq!(replacer.size(), 0);
+        touch(replacer.as_mut(), &[5]);
+        assert_eq!(replacer.evict(), Some(FrameId::new(5)));
+    }
+}
@@ -12,13 +12,13 @@
 //! This could be applied in database buffering, file system caches, or other areas
 //! where caching is utilized.
 
-use crate::ReplacerStats;
+use crate::replacer::{Replacer, ReplacerStats};
 use common::FrameId;
 use getset::{Getters, Setters};
 use parking_lot::RwLock;
 use std::{
     cmp::{Ordering, Reverse},
-    collections::{BinaryHeap, HashMap},
+    collections::{BinaryHeap, HashMap, HashSet},
     fmt,
     sync::Arc,
     time::Instant,
@@ -58,6 +58,7 @@ impl PartialOrd for LFUEntry {
 pub struct LFUReplacer {
     cache: Arc<RwLock<HashMap<FrameId, usize>>>, // Stores frame access frequencies
     priority_queue: BinaryHeap<Reverse<LFUEntry>>, // Min-heap based on frequency
+    pinned: HashSet<FrameId>, // Frames that are tracked but currently not evictable
     stats: ReplacerStats,
 }
 
@@ -67,6 +68,7 @@ impl LFUReplacer {
         LFUReplacer::builder()
             .cache(Arc::new(RwLock::new(HashMap::new())))
             .priority_queue(BinaryHeap::new())
+            .pinned(HashSet::new())
             .stats(ReplacerStats::new())
             .build()
     }
@@ -91,24 +93,62 @@ impl LFUReplacer {
     /// Evicts the least frequently used frame from the cache.
     /// Returns `Some(frame_id)` if a frame is evicted, or `None` if no frame can be evicted.
     pub fn evict(&mut self) -> Option<FrameId> {
+        self.evict_skipping(|_| false)
+    }
+
+    /// Evicts the least frequently used evictable frame for which `skip` returns `false`,
+    /// falling back to the skipped frames if there are no others.
+    pub fn evict_skipping(&mut self, skip: impl Fn(FrameId) -> bool) -> Option<FrameId> {
+        let mut cache = self.cache.write();
+
+        // Entries superseded by a later access are dropped, while the entries of frames that
+        // cannot be evicted right now are pushed back once the search is over.
+        let mut retained = Vec::new();
+        let mut fallback = None;
+        let mut victim = None;
         while let Some(Reverse(entry)) = self.priority_queue.pop() {
-            let mut cache = self.cache.write();
-            if let Some(&current_frequency) = cache.get(&entry.frame_id) {
-                if current_frequency == entry.frequency {
-                    cache.remove(&entry.frame_id);
-                    debug!(frame_id = ?entry.frame_id, "Evicted frame from LFU Replacer");
-                    return Some(entry.frame_id);
+            if cache.get(&entry.frame_id) != Some(&entry.frequency) {
+                continue;
+            }
+            if self.pinned.contains(&entry.frame_id) {
+                retained.push(entry);
+            } else if skip(entry.frame_id) {
+                match fallback {
+                    None => fallback = Some(entry),
+                    Some(_) => retained.push(entry),
                 }
+            } else {
+                victim = Some(entry);
+                break;
             }
         }
 
-        warn!("No frame evicted from LFU Replacer: all frames are in use");
-        None
+        let victim = match victim {
+            Some(entry) => {
+                retained.extend(fallback);
+                Some(entry)
+            }
+            None => fallback,
+        };
+        self.priority_queue
+            .extend(retained.into_iter().map(Reverse));
+
+        match victim {
+            Some(entry) => {
+                cache.remove(&entry.frame_id);
+                debug!(frame_id = ?entry.frame_id, "Evicted frame from LFU Replacer");
+                Some(entry.frame_id)
+            }
+            None => {
+                warn!("No frame evicted from LFU Replacer: all frames are in use");
+                None
+            }
+        }
     }
 
     /// Returns the number of evictable frames that are currently in the replacer.
     pub fn size(&self) -> usize {
-        self.cache.read().len()
+        self.cache.read().len() - self.pinned.len()
     }
 
     /// Adds multiple frames to the replacer, marking them as either evictable or non-evictable.
@@ -116,10 +156,7 @@ impl LFUReplacer {
         for frame_id in frame_ids.clone() {
             // Record the access for each frame
             self.record_access(frame_id);
-            if evictable {
-                // If the frame is evictable, adjust its status in the cache
-                self.set_evictable(frame_id);
-            }
+            self.set_evictable(frame_id, evictable);
         }
         debug!("Bulk added {} frames to LFU Replacer", frame_ids.len());
     }
@@ -141,10 +178,22 @@ impl LFUReplacer {
         evicted_frames
     }
 
-    /// Marks a frame as evictable in the cache.
-    fn set_evictable(&mut self, frame_id: FrameId) {
-        let mut cache = self.cache.write();
-        cache.insert(frame_id, 1); // Set a default frequency for new evictable frames
+    /// Marks a frame as evictable or non-evictable, tracking it if it is new.
+    pub fn set_evictable(&mut self, frame_id: FrameId, evictable: bool) {
+        if !self.cache.read().contains_key(&frame_id) {
+            self.record_access(frame_id);
+        }
+        if evictable {
+            self.pinned.remove(&frame_id);
+        } else {
+            self.pinned.insert(frame_id);
+        }
+    }
+
+    /// Stops tracking a frame.
+    pub fn remove(&mut self, frame_id: FrameId) {
+        self.cache.write().remove(&frame_id);
+        self.pinned.remove(&frame_id);
     }
 
     pub fn get_statistics(&self) -> ReplacerStats {
@@ -152,6 +201,33 @@ impl LFUReplacer {
     }
 }
 
+impl Replacer for LFUReplacer {
+    fn record_access(&mut self, frame_id: FrameId) {
+        LFUReplacer::record_access(self, frame_id);
+        self.pinned.insert(frame_id);
+    }
+
+    fn set_evictable(&mut self, frame_id: FrameId, evictable: bool) {
+        LFUReplacer::set_evictable(self, frame_id, evictable)
+    }
+
+    fn evict_skipping(&mut self, skip: &dyn Fn(FrameId) -> bool) -> Option<FrameId> {
+        LFUReplacer::evict_skipping(self, skip)
+    }
+
+    fn remove(&mut self, frame_id: FrameId) {
+        LFUReplacer::remove(self, frame_id)
+    }
+
+    fn size(&self) -> usize {
+        LFUReplacer::size(self)
+    }
+
+    fn get_statistics(&self) -> ReplacerStats {
+        LFUReplacer::get_statistics(self)
+    }
+}
+
 impl fmt::Display for LFUReplacer {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let cache = self.cache.read();
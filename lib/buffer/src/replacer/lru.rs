@@ -1,4 +1,4 @@
-use crate::replacer::ReplacerStats;
+use crate::replacer::{Replacer, ReplacerStats};
 use common::{FrameId, BUFFER_POOL_SIZE};
 use core::fmt;
 use lru::LruCache;
@@ -70,7 +70,7 @@ impl LRUReplacer {
 
         {
             let mut cache = self.cache.write();
-            match cache.pop(&frame_id) {
+            match cache.put(frame_id, false) {
                 Some(_) => {
                     debug!(
                         "Frame {:?} found in cache, updating LRU position.",
@@ -80,7 +80,6 @@ impl LRUReplacer {
                 }
                 None => {
                     debug!("Frame {:?} not found, adding to cache.", frame_id);
-                    cache.put(frame_id, false);
                     self.stats.increment_cache_misses();
                 }
             }
@@ -92,17 +91,36 @@ impl LRUReplacer {
     }
 
     pub fn evict(&mut self) -> Option<FrameId> {
+        self.evict_skipping(|_| false)
+    }
+
+    /// Evicts the least recently used evictable frame for which `skip` returns `false`.
+    /// If every evictable frame is skipped, falls back to plain LRU order so that
+    /// eviction only fails when no frame is evictable at all.
+    ///
+    /// Used by the buffer pool to retain hot pages for as long as a cold frame is available.
+    pub fn evict_skipping(&mut self, skip: impl Fn(FrameId) -> bool) -> Option<FrameId> {
         let start = Instant::now();
         let mut cache = self.cache.write();
-        let evicted_frame =
+
+        // Walk from the least recently used end and only remove the first evictable
+        // frame, so that pinned frames keep their position when no victim is found.
+        let evictable = || {
             cache
-                .pop_lru()
-                .filter(|&(_, evictable)| evictable)
-                .map(|(frame_id, _)| {
-                    debug!("Evicting frame {:?}", frame_id);
-                    self.stats.increment_cache_evictions();
-                    frame_id
-                });
+                .iter()
+                .rev()
+                .filter(|(_, &evictable)| evictable)
+                .map(|(&frame_id, _)| frame_id)
+        };
+        let evicted_frame = evictable()
+            .find(|&frame_id| !skip(frame_id))
+            .or_else(|| evictable().next());
+
+        if let Some(frame_id) = evicted_frame {
+            cache.pop(&frame_id);
+            debug!("Evicting frame {:?}", frame_id);
+            self.stats.increment_cache_evictions();
+        }
 
         if evicted_frame.is_none() {
             warn!("No evictable frames available for eviction");
@@ -187,11 +205,46 @@ impl LRUReplacer {
         self.stats.update_latency(start_time.elapsed());
     }
 
+    /// Stops tracking a frame entirely, e.g. once its page has been deleted from the pool.
+    pub fn remove(&mut self, frame_id: FrameId) {
+        let mut cache = self.cache.write();
+        if cache.pop(&frame_id).is_some() {
+            debug!("Removed frame {:?} from the replacer", frame_id);
+        }
+        self.stats.set_current_cache_size(cache.len());
+    }
+
     pub fn get_statistics(&self) -> ReplacerStats {
         self.stats.clone()
     }
 }
 
+impl Replacer for LRUReplacer {
+    fn record_access(&mut self, frame_id: FrameId) {
+        LRUReplacer::record_access(self, frame_id)
+    }
+
+    fn set_evictable(&mut self, frame_id: FrameId, evictable: bool) {
+        LRUReplacer::set_evictable(self, frame_id, evictable)
+    }
+
+    fn evict_skipping(&mut self, skip: &dyn Fn(FrameId) -> bool) -> Option<FrameId> {
+        LRUReplacer::evict_skipping(self, skip)
+    }
+
+    fn remove(&mut self, frame_id: FrameId) {
+        LRUReplacer::remove(self, frame_id)
+    }
+
+    fn size(&self) -> usize {
+        LRUReplacer::size(self)
+    }
+
+    fn get_statistics(&self) -> ReplacerStats {
+        LRUReplacer::get_statistics(self)
+    }
+}
+
 impl fmt::Display for LRUReplacer {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "LRUReplacer (size: {})\n", self.size())?;
@@ -277,6 +330,35 @@ mod tests {
         assert_eq!(replacer.evict(), Some(FrameId(3)));
     }
 
+    #[test]
+    fn test_evict_skipping() {
+        let mut replacer = LRUReplacer::new(3);
+        for frame_id in [FrameId(1), FrameId(2), FrameId(3)] {
+            replacer.record_access(frame_id);
+            replacer.set_evictable(frame_id, true);
+        }
+
+        // Frame 1 is skipped while another evictable frame exists
+        let skip_one = |frame_id: FrameId| frame_id == FrameId(1);
+        assert_eq!(replacer.evict_skipping(skip_one), Some(FrameId(2)));
+        assert_eq!(replacer.evict_skipping(skip_one), Some(FrameId(3)));
+
+        // Falls back to LRU order once only skipped frames remain
+        assert_eq!(replacer.evict_skipping(skip_one), Some(FrameId(1)));
+        assert_eq!(replacer.evict_skipping(skip_one), None);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut replacer = LRUReplacer::new(2);
+        replacer.record_access(FrameId(1));
+        replacer.set_evictable(FrameId(1), true);
+
+        replacer.remove(FrameId(1));
+        assert_eq!(replacer.size(), 0);
+        assert_eq!(replacer.evict(), None);
+    }
+
     #[test]
     fn test_evict() {
         let mut replacer = LRUReplacer::new(2);
@@ -1,3 +1,4 @@
+use crate::replacer::{Replacer, ReplacerStats};
 use common::FrameId;
 use parking_lot::Mutex;
 use std::{
@@ -7,6 +8,7 @@ use std::{
 };
 use tracing::{debug, error, field::debug, info, warn};
 
+#[derive(Debug)]
 pub struct LRUKReplacer {
     replacer_size: usize,
     k: usize,
@@ -54,6 +56,7 @@ impl fmt::Display for LRUKReplacer {
     }
 }
 
+#[derive(Debug)]
 struct AccessInfo {
     curr_size: usize,
     k: usize,
@@ -66,41 +69,41 @@ struct AccessInfo {
 }
 
 impl AccessInfo {
-    fn try_evict(&mut self) -> Option<FrameId> {
-        // Try to evict from history list first
-        if let Some(frame_id) = self
-            .history_list
+    fn try_evict(&mut self, skip: &dyn Fn(FrameId) -> bool) -> Option<FrameId> {
+        let frame_id = self
+            .find_victim(skip)
+            .or_else(|| self.find_victim(&|_| false))?;
+
+        self.history_list.retain(|&x| x != frame_id);
+        self.history_map.remove(&frame_id);
+        self.cache_list.retain(|&x| x != frame_id);
+        self.cache_map.remove(&frame_id);
+        self.access_count.remove(&frame_id);
+        self.is_evictable.remove(&frame_id);
+        self.curr_size -= 1;
+        Some(frame_id)
+    }
+
+    /// Finds the evictable frame to evict among those not skipped: frames with fewer than
+    /// `k` accesses in the history list go first, then the cache list in LRU order.
+    fn find_victim(&self, skip: &dyn Fn(FrameId) -> bool) -> Option<FrameId> {
+        let evictable = |frame: FrameId| {
+            self.is_evictable.get(&frame).copied().unwrap_or(false) && !skip(frame)
+        };
+
+        self.history_list
             .iter()
             .rev()
             .find(|&&frame| {
-                self.is_evictable.get(&frame).copied().unwrap_or(false)
-                    && self.access_count.get(&frame).copied().unwrap_or(0) < self.k
+                evictable(frame) && self.access_count.get(&frame).copied().unwrap_or(0) < self.k
+            })
+            .or_else(|| {
+                self.cache_list
+                    .iter()
+                    .rev()
+                    .find(|&&frame| evictable(frame))
             })
             .copied()
-        {
-            self.history_list.retain(|&x| x != frame_id);
-            self.access_count.remove(&frame_id);
-            self.is_evictable.remove(&frame_id);
-            self.curr_size -= 1;
-            return Some(frame_id);
-        }
-
-        // Then try to evict from cache list
-        if let Some(frame_id) = self
-            .cache_list
-            .iter()
-            .rev()
-            .find(|&&frame| self.is_evictable.get(&frame).copied().unwrap_or(false))
-            .copied()
-        {
-            self.cache_list.retain(|&x| x != frame_id);
-            self.access_count.remove(&frame_id);
-            self.is_evictable.remove(&frame_id);
-            self.curr_size -= 1;
-            return Some(frame_id);
-        }
-
-        None
     }
 
     // fn try_evict_from_history_list(&mut self) -> Option<FrameId> {
@@ -158,14 +161,13 @@ impl AccessInfo {
 
     fn update_cache(&mut self, frame_id: FrameId) {
         if self.access_count.get(&frame_id).copied().unwrap_or(0) > self.k {
-            if let Some(&position) = self.cache_map.get(&frame_id) {
-                self.cache_list.remove(position);
-            }
-        } else if self.access_count.get(&frame_id).copied().unwrap_or(0) == self.k {
-            if let Some(&position) = self.history_map.get(&frame_id) {
-                self.history_list.remove(position);
-                self.history_map.remove(&frame_id);
+            if self.cache_map.contains_key(&frame_id) {
+                self.cache_list.retain(|&x| x != frame_id);
             }
+        } else if self.access_count.get(&frame_id).copied().unwrap_or(0) == self.k
+            && self.history_map.remove(&frame_id).is_some()
+        {
+            self.history_list.retain(|&x| x != frame_id);
         }
 
         self.move_to_cache(frame_id);
@@ -210,11 +212,17 @@ impl LRUKReplacer {
     //     accesses.try_evict_from_cache_list()
     // }
     pub fn evict(&self) -> Option<FrameId> {
+        self.evict_skipping(|_| false)
+    }
+
+    /// Evicts the frame with the largest backward k-distance among the evictable frames for
+    /// which `skip` returns `false`, falling back to the skipped frames if there are no others.
+    pub fn evict_skipping(&self, skip: impl Fn(FrameId) -> bool) -> Option<FrameId> {
         let mut accesses = self.accesses.lock();
         if accesses.curr_size == 0 {
             return None;
         }
-        accesses.try_evict()
+        accesses.try_evict(&skip)
     }
 
     pub fn record_access(&self, frame_id: FrameId) {
@@ -291,7 +299,14 @@ impl LRUKReplacer {
             accesses.cache_map.remove(&frame_id);
         }
 
-        accesses.curr_size -= 1;
+        if accesses
+            .is_evictable
+            .get(&frame_id)
+            .copied()
+            .unwrap_or(false)
+        {
+            accesses.curr_size -= 1;
+        }
         accesses.access_count.get_mut(&frame_id).map(|v| *v = 0);
         accesses.is_evictable.get_mut(&frame_id).map(|v| *v = false);
     }
@@ -301,6 +316,34 @@ impl LRUKReplacer {
     }
 }
 
+impl Replacer for LRUKReplacer {
+    fn record_access(&mut self, frame_id: FrameId) {
+        LRUKReplacer::record_access(self, frame_id);
+        LRUKReplacer::set_evictable(self, frame_id, false);
+    }
+
+    fn set_evictable(&mut self, frame_id: FrameId, evictable: bool) {
+        LRUKReplacer::set_evictable(self, frame_id, evictable)
+    }
+
+    fn evict_skipping(&mut self, skip: &dyn Fn(FrameId) -> bool) -> Option<FrameId> {
+        LRUKReplacer::evict_skipping(self, skip)
+    }
+
+    fn remove(&mut self, frame_id: FrameId) {
+        LRUKReplacer::remove(self, frame_id)
+    }
+
+    fn size(&self) -> usize {
+        LRUKReplacer::size(self)
+    }
+
+    fn get_statistics(&self) -> ReplacerStats {
+        // LRU-K does not collect cache statistics
+        ReplacerStats::new()
+    }
+}
+
 #[cfg(test)]
 mod lru_k_tests {
     use super::*;
@@ -367,36 +410,35 @@ mod lru_k_tests {
         // eprintln!("Buffer state: {}", lru_replacer);
         assert_eq!(lru_replacer.size(), 4);
 
-        // TODO: This test is failing. Should be evicting 3, but evicting 1 instead.
         // Continue looking for victims. We expect 3 to be evicted next.
-        // assert_eq!(lru_replacer.evict(), Some(FrameId::from(3)));
-        // assert_eq!(lru_replacer.size(), 3);
-
-        // // Set 6 to be evictable. 6 Should be evicted next since it has max backward k-dist.
-        // lru_replacer.set_evictable(FrameId::from(6), true);
-        // assert_eq!(lru_replacer.size(), 4);
-        // assert_eq!(lru_replacer.evict(), Some(FrameId::from(6)));
-        // assert_eq!(lru_replacer.size(), 3);
-
-        // // Now we have [1,5,4]. Continue looking for victims.
-        // lru_replacer.set_evictable(FrameId::from(1), false);
-        // assert_eq!(lru_replacer.size(), 2);
-        // assert_eq!(lru_replacer.evict(), Some(FrameId::from(5)));
-        // assert_eq!(lru_replacer.size(), 1);
-
-        // // Update access history for 1. Now we have [4,1]. Next victim is 4.
-        // lru_replacer.record_access(FrameId::from(1));
-        // lru_replacer.record_access(FrameId::from(1));
-        // lru_replacer.set_evictable(FrameId::from(1), true);
-        // assert_eq!(lru_replacer.size(), 2);
-        // assert_eq!(lru_replacer.evict(), Some(FrameId::from(4)));
-
-        // assert_eq!(lru_replacer.size(), 1);
-        // assert_eq!(lru_replacer.evict(), Some(FrameId::from(1)));
-        // assert_eq!(lru_replacer.size(), 0);
-
-        // // This operation should not modify size
-        // assert_eq!(lru_replacer.evict(), None);
-        // assert_eq!(lru_replacer.size(), 0);
+        assert_eq!(lru_replacer.evict(), Some(FrameId::from(3)));
+        assert_eq!(lru_replacer.size(), 3);
+
+        // Set 6 to be evictable. 6 Should be evicted next since it has max backward k-dist.
+        lru_replacer.set_evictable(FrameId::from(6), true);
+        assert_eq!(lru_replacer.size(), 4);
+        assert_eq!(lru_replacer.evict(), Some(FrameId::from(6)));
+        assert_eq!(lru_replacer.size(), 3);
+
+        // Now we have [1,5,4]. Continue looking for victims.
+        lru_replacer.set_evictable(FrameId::from(1), false);
+        assert_eq!(lru_replacer.size(), 2);
+        assert_eq!(lru_replacer.evict(), Some(FrameId::from(5)));
+        assert_eq!(lru_replacer.size(), 1);
+
+        // Update access history for 1. Now we have [4,1]. Next victim is 4.
+        lru_replacer.record_access(FrameId::from(1));
+        lru_replacer.record_access(FrameId::from(1));
+        lru_replacer.set_evictable(FrameId::from(1), true);
+        assert_eq!(lru_replacer.size(), 2);
+        assert_eq!(lru_replacer.evict(), Some(FrameId::from(4)));
+
+        assert_eq!(lru_replacer.size(), 1);
+        assert_eq!(lru_replacer.evict(), Some(FrameId::from(1)));
+        assert_eq!(lru_replacer.size(), 0);
+
+        // This operation should not modify size
+        assert_eq!(lru_replacer.evict(), None);
+        assert_eq!(lru_replacer.size(), 0);
     }
 }
@@ -4,19 +4,27 @@ use std::{
     time::Duration,
 };
 
-use common::FrameId;
+use common::{FrameId, PageId};
 
+mod arc;
+mod clock;
 mod lfu;
 mod lru;
 mod lru_k;
 mod mru;
 
+pub use arc::ARCReplacer;
+pub use clock::ClockReplacer;
 pub use lfu::LFUReplacer;
 pub use lru::LRUReplacer;
+pub use lru_k::LRUKReplacer;
 pub use mru::MRUReplacer;
 use parking_lot::RwLock;
 use typed_builder::TypedBuilder;
 
+/// The number of recent accesses the [`ReplacementPolicy::LRUK`] policy ranks frames by.
+pub const LRU_K: usize = 2;
+
 /// Policy for cache replacement
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ReplacementPolicy {
@@ -28,6 +36,10 @@ pub enum ReplacementPolicy {
     LFU,
     /// LRU-K (Least Recently Used K)
     LRUK,
+    /// Adaptive Replacement Cache (balances recency and frequency)
+    Adaptive,
+    /// Clock (second-chance approximation of LRU)
+    Clock,
 }
 
 /// `ReplacerStats` holds statistical data for cache operations within an LRU Replacer.
@@ -274,24 +286,54 @@ impl fmt::Display for ReplacerStats {
     }
 }
 
-pub trait Replacer {
-    /// Remove the victim frame as defined by the replacement policy.
-    /// Returns `Option<FrameId>`
-    /// `Some(frame_id)` if a victim frame was found, `None` otherwise.
-    fn victim(&mut self) -> Option<FrameId>;
+/// The interface the buffer pool evicts frames through, letting it hold any replacement
+/// policy as a `Box<dyn Replacer>`.
+///
+/// A frame is tracked from its first recorded access. Accessed frames are pinned, i.e.
+/// non-evictable, until they are marked evictable again.
+pub trait Replacer: fmt::Debug + fmt::Display + Send + Sync {
+    /// Records an access to a frame, tracking it if it is new and marking it as non-evictable.
+    fn record_access(&mut self, frame_id: FrameId);
+
+    /// Records an access to a frame holding `page_id`. Policies that remember evicted pages
+    /// key them by page, since a frame is reused for another page as soon as it is evicted.
+    fn record_page_access(&mut self, frame_id: FrameId, _page_id: PageId) {
+        self.record_access(frame_id)
+    }
+
+    /// Marks a tracked frame as evictable or non-evictable.
+    fn set_evictable(&mut self, frame_id: FrameId, evictable: bool);
+
+    /// Evicts the victim frame of the replacement policy among the evictable frames for which
+    /// `skip` returns `false`, falling back to the skipped ones if there are no others.
+    /// Returns `None` only if no frame is evictable.
+    fn evict_skipping(&mut self, skip: &dyn Fn(FrameId) -> bool) -> Option<FrameId>;
 
-    /// Pins a frame, indicating that it should not be victimized until it is unpinned.
-    fn pin(&mut self, frame_id: FrameId);
+    /// Evicts the victim frame of the replacement policy, if any frame is evictable.
+    fn evict(&mut self) -> Option<FrameId> {
+        self.evict_skipping(&|_| false)
+    }
 
-    /// Unpins a frame, indicating that it can now be victimized.
-    fn unpin(&mut self, frame_id: FrameId);
+    /// Stops tracking a frame entirely, e.g. once its page has been deleted from the pool.
+    fn remove(&mut self, frame_id: FrameId);
 
-    /// Returns the number of elements in the replacer that can be victimized.
+    /// Returns the number of evictable frames.
     fn size(&self) -> usize;
+
+    /// Returns a snapshot of the replacer's cache statistics.
+    fn get_statistics(&self) -> ReplacerStats;
 }
 
-// trait Replacer {
-//     fn record_access(&mut self, frame_id: FrameId);
-//     fn evict(&mut self) -> Option<FrameId>;
-//     // Other common methods...
-// }
+impl ReplacementPolicy {
+    /// Creates a replacer implementing the policy for a pool of `capacity` frames.
+    pub fn replacer(&self, capacity: usize) -> Box<dyn Replacer> {
+        match self {
+            ReplacementPolicy::LRU => Box::new(LRUReplacer::new(capacity)),
+            ReplacementPolicy::MRU => Box::new(MRUReplacer::new()),
+            ReplacementPolicy::LFU => Box::new(LFUReplacer::new()),
+            ReplacementPolicy::LRUK => Box::new(LRUKReplacer::new(capacity, LRU_K)),
+            ReplacementPolicy::Adaptive => Box::new(ARCReplacer::new(capacity)),
+            ReplacementPolicy::Clock => Box::new(ClockReplacer::new(capacity)),
+        }
+    }
+}
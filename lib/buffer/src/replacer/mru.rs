@@ -5,10 +5,15 @@
 //! accessed, which is the opposite of the LRU strategy. This strategy is used in scenarios
 //! where the most recently used items are less likely to be needed again soon.
 
-use crate::ReplacerStats;
+use crate::replacer::{Replacer, ReplacerStats};
 use common::FrameId;
 use parking_lot::RwLock;
-use std::{collections::HashMap, fmt, sync::Arc, time::Instant};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::Arc,
+    time::Instant,
+};
 use tracing::{debug, info};
 use typed_builder::TypedBuilder;
 
@@ -29,6 +34,8 @@ struct MRUEntry {
 pub struct MRUReplacer {
     // Stores frame access times.
     cache: Arc<RwLock<HashMap<FrameId, Instant>>>,
+    // Frames that are tracked but currently not evictable.
+    pinned: HashSet<FrameId>,
     // Statistical data for cache operations.
     stats: ReplacerStats,
 }
@@ -39,6 +46,7 @@ impl MRUReplacer {
         info!("Initializing MRU Replacer");
         MRUReplacer::builder()
             .cache(Arc::new(RwLock::new(HashMap::new())))
+            .pinned(HashSet::new())
             .stats(ReplacerStats::new())
             .build()
     }
@@ -53,19 +61,51 @@ impl MRUReplacer {
     /// Evicts the most recently used frame from the cache.
     /// Returns `Some(frame_id)` if a frame is evicted, or `None` if no frame can be evicted.
     pub fn evict(&mut self) -> Option<FrameId> {
+        self.evict_skipping(|_| false)
+    }
+
+    /// Evicts the most recently used evictable frame for which `skip` returns `false`,
+    /// falling back to the skipped frames if there are no others.
+    pub fn evict_skipping(&mut self, skip: impl Fn(FrameId) -> bool) -> Option<FrameId> {
         let mut cache = self.cache.write();
-        if let Some((&frame_id, &_most_recent)) = cache.iter().max_by_key(|&(_, &time)| time) {
+        let most_recent = |skip: &dyn Fn(FrameId) -> bool| {
+            cache
+                .iter()
+                .filter(|(frame_id, _)| !self.pinned.contains(*frame_id) && !skip(**frame_id))
+                .max_by_key(|&(_, &time)| time)
+                .map(|(&frame_id, _)| frame_id)
+        };
+
+        let evicted = most_recent(&skip).or_else(|| most_recent(&|_| false));
+        if let Some(frame_id) = evicted {
             cache.remove(&frame_id);
             debug!(frame_id = ?frame_id, "Evicted frame from MRU Replacer");
-            return Some(frame_id);
         }
+        evicted
+    }
+
+    /// Marks a frame as evictable or non-evictable, tracking it if it is new.
+    pub fn set_evictable(&mut self, frame_id: FrameId, evictable: bool) {
+        self.cache
+            .write()
+            .entry(frame_id)
+            .or_insert_with(Instant::now);
+        if evictable {
+            self.pinned.remove(&frame_id);
+        } else {
+            self.pinned.insert(frame_id);
+        }
+    }
 
-        None
+    /// Stops tracking a frame.
+    pub fn remove(&mut self, frame_id: FrameId) {
+        self.cache.write().remove(&frame_id);
+        self.pinned.remove(&frame_id);
     }
 
-    /// Returns the number of frames in the replacer.
+    /// Returns the number of evictable frames in the replacer.
     pub fn size(&self) -> usize {
-        self.cache.read().len()
+        self.cache.read().len() - self.pinned.len()
     }
 
     /// Adds multiple frames to the replacer and marks them as evictable.
@@ -104,6 +144,33 @@ impl MRUReplacer {
     }
 }
 
+impl Replacer for MRUReplacer {
+    fn record_access(&mut self, frame_id: FrameId) {
+        MRUReplacer::record_access(self, frame_id);
+        self.pinned.insert(frame_id);
+    }
+
+    fn set_evictable(&mut self, frame_id: FrameId, evictable: bool) {
+        MRUReplacer::set_evictable(self, frame_id, evictable)
+    }
+
+    fn evict_skipping(&mut self, skip: &dyn Fn(FrameId) -> bool) -> Option<FrameId> {
+        MRUReplacer::evict_skipping(self, skip)
+    }
+
+    fn remove(&mut self, frame_id: FrameId) {
+        MRUReplacer::remove(self, frame_id)
+    }
+
+    fn size(&self) -> usize {
+        MRUReplacer::size(self)
+    }
+
+    fn get_statistics(&self) -> ReplacerStats {
+        self.stats.clone()
+    }
+}
+
 impl fmt::Display for MRUReplacer {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let cache = self.cache.read();
@@ -122,6 +189,7 @@ impl fmt::Display for MRUReplacer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::replacer::ReplacementPolicy;
     use std::thread;
     use std::time::Duration;
 
@@ -173,5 +241,39 @@ mod tests {
         assert!(display_string.contains("Frame ID: FrameId(1), Last Accessed:"));
     }
 
+    #[test]
+    fn test_policy_evicts_most_recent_unpinned_frame() {
+        let victim = |policy: ReplacementPolicy| {
+            let mut replacer = policy.replacer(4);
+            for frame_id in 1..=3 {
+                replacer.record_access(FrameId::new(frame_id));
+                replacer.set_evictable(FrameId::new(frame_id), true);
+                thread::sleep(Duration::from_millis(10)); // Ensuring a time difference
+            }
+            replacer.evict()
+        };
+
+        assert_eq!(victim(ReplacementPolicy::MRU), Some(FrameId::new(3)));
+        assert_eq!(victim(ReplacementPolicy::LRU), Some(FrameId::new(1)));
+    }
+
+    #[test]
+    fn test_pinned_frames_are_not_evicted() {
+        let mut replacer: Box<dyn Replacer> = Box::new(MRUReplacer::new());
+        for frame_id in 1..=3 {
+            replacer.record_access(FrameId::new(frame_id));
+            thread::sleep(Duration::from_millis(10)); // Ensuring a time difference
+        }
+        assert_eq!(replacer.size(), 0);
+        assert_eq!(replacer.evict(), None);
+
+        replacer.set_evictable(FrameId::new(1), true);
+        replacer.set_evictable(FrameId::new(2), true);
+        assert_eq!(replacer.size(), 2);
+        assert_eq!(replacer.evict(), Some(FrameId::new(2)));
+        assert_eq!(replacer.evict(), Some(FrameId::new(1)));
+        assert_eq!(replacer.evict(), None);
+    }
+
     // Additional test cases...
 }
@@ -0,0 +1,311 @@
+//! # Catalog
+//!
+//! The [`Catalog`] keeps track of the tables of the database by name: the tables created by
+//! `CREATE TABLE`, which queries are planned against and whose rows the execution layer reads
+//! and writes. The schemas of its tables, along with the first page of the heaps holding their
+//! rows, can be encoded (see [`Catalog::encode`]) to be persisted in the system catalog page.
+//! The other heap pages are linked from the first one, so the size of the catalog doesn't grow
+//! with the tables.
+
+use crate::schema::{Schema, SchemaRef};
+use crate::table::{DatabaseError, Table};
+use dashmap::{mapref::entry::Entry, DashMap};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::debug;
+
+/// A reference-counted [`Catalog`] handle that can be shared across threads.
+pub type CatalogRef = Arc<Catalog>;
+
+/// How a [`Table`] is encoded by [`Catalog::encode`].
+#[derive(Serialize, Deserialize)]
+struct EncodedTable {
+    schema: Schema,
+    first_heap_page: Option<u32>,
+}
+
+/// [`Catalog`] keeps track of the tables of a database, by name.
+#[derive(Debug, Default)]
+pub struct Catalog {
+    tables: DashMap<String, Arc<Table>>,
+}
+
+impl Catalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a table named `name` with the given schema, failing if a table of the same
+    /// name already exists.
+    pub fn create_table(&self, name: &str, schema: Schema) -> Result<Arc<Table>, DatabaseError> {
+        let table = Arc::new(Table::new(name, schema));
+        self.insert_table(Arc::clone(&table))?;
+        Ok(table)
+    }
+
+    /// Adds a table (e.g. one decoded from the catalog page, or one whose drop is undone),
+    /// failing if a table of the same name already exists.
+    pub fn insert_table(&self, table: Arc<Table>) -> Result<(), DatabaseError> {
+        match self.tables.entry(table.name().clone()) {
+            Entry::Occupied(_) => Err(DatabaseError::TableAlreadyExists(table.name().clone())),
+            Entry::Vacant(entry) => {
+                debug!("Creating table `{}`", table.name());
+                entry.insert(table);
+                Ok(())
+            }
+        }
+    }
+
+    /// Registers a table, replacing any table previously registered under the same name.
+    pub fn register_table(&self, name: &str, schema: SchemaRef) {
+        debug!("Registering table `{}` in the catalog", name);
+        self.tables
+            .insert(name.to_string(), Arc::new(Table::new(name, schema)));
+    }
+
+    /// Returns the table named `name`.
+    pub fn get_table(&self, name: &str) -> Option<Arc<Table>> {
+        self.tables.get(name).map(|table| Arc::clone(table.value()))
+    }
+
+    /// Drops the table named `name`, returning it.
+    pub fn drop_table(&self, name: &str) -> Result<Arc<Table>, DatabaseError> {
+        debug!("Dropping table `{}`", name);
+        self.tables
+            .remove(name)
+            .map(|(_, table)| table)
+            .ok_or_else(|| DatabaseError::TableNotFound(name.to_string()))
+    }
+
+    /// Returns the schema of the table named `name`.
+    pub fn table_schema(&self, name: &str) -> Option<SchemaRef> {
+        self.tables
+            .get(name)
+            .map(|table| Arc::clone(table.value().schema()))
+    }
+
+    /// Returns the names of all tables, in alphabetical order.
+    pub fn table_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.tables.iter().map(|t| t.key().clone()).collect();
+        names.sort();
+        names
+    }
+
+    /// Returns the names of the tables matching the SQL `LIKE` pattern `pattern`, in
+    /// alphabetical order.
+    pub fn table_names_like(&self, pattern: &str) -> Vec<String> {
+        self.table_names()
+            .into_iter()
+            .filter(|name| like(name, pattern))
+            .collect()
+    }
+
+    /// Encodes the schemas and first heap pages of all tables, by name, as the big-endian
+    /// length of their JSON followed by the JSON itself.
+    pub fn encode(&self) -> Result<Vec<u8>, DatabaseError> {
+        let tables = self
+            .tables
+            .iter()
+            .map(|table| {
+                let encoded = EncodedTable {
+                    schema: table.schema().as_ref().clone(),
+                    first_heap_page: table.heap_pages().first().copied(),
+                };
+                (table.key().clone(), encoded)
+            })
+            .collect::<BTreeMap<_, _>>();
+        let json = serde_json::to_vec(&tables)
+            .map_err(|e| DatabaseError::InvalidEncoding(e.to_string()))?;
+        let len = u32::try_from(json.len())
+            .map_err(|_| DatabaseError::InvalidEncoding("catalog too large".to_string()))?;
+
+        let mut bytes = len.to_be_bytes().to_vec();
+        bytes.extend(json);
+        Ok(bytes)
+    }
+
+    /// Decodes a catalog encoded by [`Catalog::encode`], creating its tables (without
+    /// indexes). The heap pages of each table are only its first one, from which the owner of
+    /// the heaps follows the links to the others. Trailing bytes (e.g. the rest of the page)
+    /// are ignored, and a length of zero (e.g. an all-zero page) decodes to a catalog without
+    /// tables.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DatabaseError> {
+        let invalid = |reason: &str| DatabaseError::InvalidEncoding(reason.to_string());
+        let len = bytes.get(..4).ok_or_else(|| invalid("missing length"))?;
+        let len = u32::from_be_bytes(len.try_into().expect("length is 4 bytes")) as usize;
+
+        let catalog = Self::new();
+        if len == 0 {
+            return Ok(catalog);
+        }
+        let json = bytes
+            .get(4..4 + len)
+            .ok_or_else(|| invalid("truncated tables"))?;
+        let tables: BTreeMap<String, EncodedTable> =
+            serde_json::from_slice(json).map_err(|e| invalid(&e.to_string()))?;
+        for (name, table) in tables {
+            catalog
+                .create_table(&name, table.schema)?
+                .set_heap_pages(table.first_heap_page.into_iter().collect());
+        }
+        Ok(catalog)
+    }
+}
+
+/// Matches `text` against a SQL `LIKE` pattern, where `%` matches any sequence of
+/// characters (including none) and `_` matches exactly one character.
+pub fn like(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+
+    // Positions to resume from when the characters after the last `%` fail to match
+    let (mut t, mut p) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('%') => {
+                backtrack = Some((t, p));
+                p += 1;
+            }
+            Some(&c) if c == '_' || c == text[t] => {
+                t += 1;
+                p += 1;
+            }
+            _ => match backtrack {
+                // Let the last `%` swallow one more character
+                Some((last_t, last_p)) => {
+                    backtrack = Some((last_t + 1, last_p));
+                    t = last_t + 1;
+                    p = last_p + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '%')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Column;
+    use pretty_assertions_sorted::assert_eq;
+    use ty::DataTypeKind;
+
+    fn users_schema() -> Schema {
+        Schema::new(vec![
+            Column::new_fixed("id", DataTypeKind::Integer).unwrap(),
+            Column::new_varlen_with_offset("name", DataTypeKind::VarChar, 255, 4).unwrap(),
+        ])
+    }
+
+    #[test]
+    fn test_like_wildcards() {
+        assert!(like("users", "users"));
+        assert!(like("users", "user%"));
+        assert!(like("users", "%s"));
+        assert!(like("users", "u_e%"));
+        assert!(like("users", "%%"));
+        assert!(like("", "%"));
+        assert!(like("user_roles", "%_roles"));
+        assert!(!like("users", "user"));
+        assert!(!like("users", "_sers_"));
+        assert!(!like("orders", "user%"));
+        assert!(!like("", "_"));
+    }
+
+    #[test]
+    fn test_table_names_are_listed_and_filtered() {
+        let catalog = Catalog::new();
+        let schema = Arc::new(Schema::new(vec![Column::new_fixed(
+            "id",
+            DataTypeKind::Integer,
+        )
+        .unwrap()]));
+        for name in ["users", "orders", "user_roles"] {
+            catalog.register_table(name, Arc::clone(&schema));
+        }
+
+        assert_eq!(catalog.table_names(), ["orders", "user_roles", "users"]);
+        assert_eq!(catalog.table_names_like("user%"), ["user_roles", "users"]);
+        assert_eq!(catalog.table_schema("orders"), Some(Arc::clone(&schema)));
+        assert_eq!(catalog.table_schema("missing"), None);
+
+        assert_eq!(catalog.drop_table("orders").unwrap().schema(), &schema);
+        assert_eq!(catalog.table_names(), ["user_roles", "users"]);
+        assert!(catalog.drop_table("orders").is_err());
+    }
+
+    #[test]
+    fn test_tables_are_created_found_and_dropped() {
+        let catalog = Catalog::new();
+        let users = catalog.create_table("users", users_schema()).unwrap();
+        catalog.create_table("orders", Schema::default()).unwrap();
+
+        assert_eq!(users.name(), "users");
+        assert_eq!(users.schema().get_col_idx("name").unwrap(), 1);
+        assert!(users.indexes().is_empty());
+        assert_eq!(catalog.table_names(), ["orders", "users"]);
+
+        let found = catalog.get_table("users").unwrap();
+        assert!(Arc::ptr_eq(&found, &users));
+        assert!(catalog.get_table("missing").is_none());
+
+        assert!(Arc::ptr_eq(&catalog.drop_table("users").unwrap(), &users));
+        assert!(catalog.get_table("users").is_none());
+        assert_eq!(catalog.table_names(), ["orders"]);
+        assert_eq!(
+            catalog.drop_table("users").unwrap_err(),
+            DatabaseError::TableNotFound("users".to_string())
+        );
+
+        // A dropped table can be put back as it was
+        catalog.insert_table(Arc::clone(&users)).unwrap();
+        assert!(Arc::ptr_eq(&catalog.get_table("users").unwrap(), &users));
+    }
+
+    #[test]
+    fn test_schemas_survive_encoding() {
+        let catalog = Catalog::new();
+        let users = catalog.create_table("users", users_schema()).unwrap();
+        users.set_heap_pages(vec![3, 5]);
+        catalog.create_table("orders", Schema::default()).unwrap();
+
+        let mut page = catalog.encode().unwrap();
+        page.resize(4096, 0);
+        let decoded = Catalog::decode(&page).unwrap();
+        assert_eq!(decoded.table_names(), ["orders", "users"]);
+        assert_eq!(
+            decoded.table_schema("users").unwrap().as_ref(),
+            &users_schema()
+        );
+        assert_eq!(decoded.get_table("users").unwrap().heap_pages(), [3]);
+        assert!(decoded.get_table("orders").unwrap().heap_pages().is_empty());
+
+        assert!(Catalog::decode(&[0; 4096])
+            .unwrap()
+            .table_names()
+            .is_empty());
+        assert!(matches!(
+            Catalog::decode(&page[..10]),
+            Err(DatabaseError::InvalidEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_create_is_an_error() {
+        let catalog = Catalog::new();
+        catalog.create_table("users", users_schema()).unwrap();
+
+        assert_eq!(
+            catalog
+                .create_table("users", Schema::default())
+                .unwrap_err(),
+            DatabaseError::TableAlreadyExists("users".to_string())
+        );
+        // The original table is left in place
+        assert_eq!(catalog.table_schema("users").unwrap().columns().len(), 2);
+    }
+}
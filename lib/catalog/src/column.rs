@@ -32,10 +32,11 @@
 use anyhow::Result;
 use getset::{Getters, Setters};
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::fmt;
 use thiserror::Error;
 use tracing::warn;
-use ty::DataTypeKind;
+use ty::{Collation, DataType, DataTypeKind, TypeError, Value, VarChar, MAX_DECIMAL_SCALE};
 use typed_builder::TypedBuilder;
 
 #[derive(Error, Debug)]
@@ -44,6 +45,8 @@ pub enum ColumnError {
     InvalidType,
     #[error("Invalid length for this operation")]
     InvalidLength,
+    #[error("Invalid precision or scale for this operation")]
+    InvalidPrecision,
     // ...
 }
 
@@ -54,10 +57,31 @@ pub enum ColumnLength {
     Variable(u32),
 }
 
+/// The precision (the total number of significant digits) and scale (the number of digits
+/// after the decimal point) of a `NUMERIC(precision, scale)` column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct DecimalPrecision {
+    pub precision: u32,
+    pub scale: u32,
+}
+
+/// What a `VARCHAR(n)` column does with values longer than `n` characters.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum LengthPolicy {
+    /// Reject the value, as the SQL standard requires
+    #[default]
+    Error,
+    /// Store the first `n` characters of the value
+    Truncate,
+}
+
 /// Represents a column in a database table.
 ///
 /// A `Column` is characterized by its name, data type, length, and an offset in the table.
-/// The length can be fixed or variable, depending on the data type.
+/// The length can be fixed or variable, depending on the data type. Columns are nullable and
+/// unconstrained unless marked otherwise (see [`Column::validate`]).
 ///
 /// ```ignore
 /// +--------------+--------------+--------------+--------------+
@@ -86,18 +110,7 @@ pub enum ColumnLength {
 ///     .build();
 /// ```
 #[derive(
-    Debug,
-    Clone,
-    PartialEq,
-    Eq,
-    PartialOrd,
-    Ord,
-    Hash,
-    Serialize,
-    Deserialize,
-    TypedBuilder,
-    Getters,
-    Setters,
+    Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, TypedBuilder, Getters, Setters,
 )]
 #[getset(get = "pub")]
 pub struct Column {
@@ -105,6 +118,89 @@ pub struct Column {
     column_type: DataTypeKind,
     length: ColumnLength,
     column_offset: u32,
+    #[builder(default)]
+    decimal_precision: Option<DecimalPrecision>,
+    /// Whether the column accepts `NULL`s (`NOT NULL` if unset)
+    #[builder(default = true)]
+    #[getset(set = "pub")]
+    nullable: bool,
+    /// Whether no two rows may hold the same value in the column (`UNIQUE`)
+    #[builder(default)]
+    #[getset(set = "pub")]
+    unique: bool,
+    /// Whether the column is (part of) the primary key of its table, which makes it both
+    /// `NOT NULL` and `UNIQUE`
+    #[builder(default)]
+    #[getset(set = "pub")]
+    primary_key: bool,
+    /// The value of the column in rows inserted without one (`DEFAULT`)
+    #[builder(default)]
+    #[getset(set = "pub")]
+    default: Option<DataType>,
+    /// How the text values of the column are ordered (`COLLATE`)
+    #[builder(default)]
+    #[getset(set = "pub")]
+    collation: Collation,
+    /// What to do with values longer than the length of a `VARCHAR` column
+    #[builder(default)]
+    #[getset(set = "pub")]
+    length_policy: LengthPolicy,
+}
+
+/// Columns are ordered field by field, in the order they are declared in.
+impl Ord for Column {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (
+            &self.column_name,
+            &self.column_type,
+            &self.length,
+            self.column_offset,
+            self.decimal_precision,
+            self.nullable,
+            self.unique,
+            self.primary_key,
+        )
+            .cmp(&(
+                &other.column_name,
+                &other.column_type,
+                &other.length,
+                other.column_offset,
+                other.decimal_precision,
+                other.nullable,
+                other.unique,
+                other.primary_key,
+            ))
+            .then_with(|| match (&self.default, &other.default) {
+                (Some(a), Some(b)) => cmp_defaults(a, b),
+                (a, b) => a.is_some().cmp(&b.is_some()),
+            })
+            .then_with(|| self.collation.cmp(&other.collation))
+            .then_with(|| self.length_policy.cmp(&other.length_policy))
+    }
+}
+
+impl PartialOrd for Column {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Orders the defaults of columns, which unlike their other fields have no total order of
+/// their own: values of different types are ordered by their kind (serial values as the
+/// integers they are), and values of the same type that don't compare by their text.
+fn cmp_defaults(a: &DataType, b: &DataType) -> Ordering {
+    a.partial_cmp(b).unwrap_or_else(|| {
+        let key = |value: &DataType| {
+            let kind = match value.data_type_kind() {
+                DataTypeKind::SmallSerial => DataTypeKind::SmallInt,
+                DataTypeKind::Serial => DataTypeKind::Integer,
+                DataTypeKind::BigSerial => DataTypeKind::BigInt,
+                kind => kind,
+            };
+            (kind, value.to_string())
+        };
+        key(a).cmp(&key(b))
+    })
 }
 
 impl Column {
@@ -217,6 +313,88 @@ impl Column {
             .build())
     }
 
+    /// Creates a new `NUMERIC(precision, scale)` column, whose values are rounded to `scale`
+    /// decimal places on insert (see [`Column::coerce_value`]).
+    pub fn new_decimal(column_name: &str, precision: u32, scale: u32) -> Result<Self, ColumnError> {
+        if precision == 0 || precision > MAX_DECIMAL_SCALE || scale > precision {
+            warn!(
+                "Invalid precision or scale for this operation. Expected 0 <= scale <= precision <= {}, but found: NUMERIC({}, {})",
+                MAX_DECIMAL_SCALE, precision, scale
+            );
+            return Err(ColumnError::InvalidPrecision);
+        }
+
+        Ok(Column::builder()
+            .column_name(column_name.to_string())
+            .column_type(DataTypeKind::Decimal)
+            .length(ColumnLength::Fixed(8))
+            .column_offset(0)
+            .decimal_precision(Some(DecimalPrecision { precision, scale }))
+            .build())
+    }
+
+    /// Coerces a value inserted into the column to the column's precision and scale, if it
+    /// has any. Other values are returned as is.
+    pub fn coerce_value(&self, value: &Value) -> Result<Value, TypeError> {
+        match self.decimal_precision {
+            Some(DecimalPrecision { precision, scale }) => value.coerce_decimal(precision, scale),
+            None => Ok(value.clone()),
+        }
+    }
+
+    /// Validates a value inserted into the column, returning the value to store.
+    ///
+    /// A `NULL` (as given for a column missing from an insert) is replaced with the column's
+    /// default if it has one, and rejected if the column is `NOT NULL` otherwise. Values of
+    /// another type are coerced to the column's type (see [`DataType::coerce_to`]), decimals
+    /// to the column's precision and scale, and text longer than the length of a `VARCHAR`
+    /// column is rejected or truncated according to its [`LengthPolicy`].
+    pub fn validate(&self, value: &DataType) -> Result<DataType, TypeError> {
+        let value = match (value, &self.default) {
+            (DataType::Null, Some(default)) => default,
+            _ => value,
+        };
+
+        if let DataType::Null = value {
+            if !self.is_nullable() {
+                return Err(TypeError::NullViolation {
+                    column: self.column_name.clone(),
+                });
+            }
+            return Ok(DataType::Null);
+        }
+
+        let value = if value.data_type_kind() == self.column_type {
+            value.clone()
+        } else {
+            value.coerce_to(&self.column_type)?
+        };
+        match (value, self.decimal_precision, &self.length) {
+            (value, Some(DecimalPrecision { precision, scale }), _) => {
+                value.coerce_decimal(precision, scale)
+            }
+            (DataType::VarChar(val), None, ColumnLength::Variable(max_len)) => {
+                let max_len = *max_len as usize;
+                match self.length_policy {
+                    LengthPolicy::Error => VarChar::bounded(val, max_len),
+                    LengthPolicy::Truncate => Ok(VarChar::truncated(val, max_len)),
+                }
+            }
+            (value, _, _) => Ok(value),
+        }
+    }
+
+    /// Orders two values of the column, comparing text under the column's collation (e.g. to
+    /// sort rows by the column in `ORDER BY`).
+    pub fn compare_values(&self, a: &DataType, b: &DataType) -> Option<Ordering> {
+        a.compare_text(b, self.collation)
+    }
+
+    /// Returns `true` iff the column accepts `NULL`s, which primary key columns never do.
+    pub fn is_nullable(&self) -> bool {
+        self.nullable && !self.primary_key
+    }
+
     /// Returns `true` iff the column is fixed-length, `false` otherwise.
     pub fn is_inlined(&self) -> bool {
         match self.length {
@@ -239,6 +417,7 @@ impl fmt::Display for Column {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ty::DataType;
 
     #[test]
     fn test_create_fixed_column() {
@@ -292,6 +471,154 @@ mod tests {
         assert!(column.is_err());
     }
 
+    #[test]
+    fn test_decimal_column_rounds_values_to_its_scale() {
+        let column = Column::new_decimal("price", 5, 2).unwrap();
+        assert_eq!(column.column_type(), &DataTypeKind::Decimal);
+        assert_eq!(
+            column.decimal_precision(),
+            &Some(DecimalPrecision {
+                precision: 5,
+                scale: 2
+            })
+        );
+
+        let decimal = |val: &str| Value::new(DataType::Decimal(val.parse().unwrap()));
+        assert_eq!(
+            column.coerce_value(&decimal("3.145")).unwrap(),
+            decimal("3.15")
+        );
+        assert!(column.coerce_value(&decimal("1234.5")).is_err());
+        assert!(Column::new_decimal("price", 2, 3).is_err());
+    }
+
+    #[test]
+    fn test_null_into_not_null_column_is_rejected() {
+        let mut column = Column::new_fixed("id", DataTypeKind::Integer).unwrap();
+        assert_eq!(column.validate(&DataType::Null).unwrap(), DataType::Null);
+
+        column.set_nullable(false);
+        assert!(matches!(
+            column.validate(&DataType::Null),
+            Err(TypeError::NullViolation { column }) if column == "id"
+        ));
+
+        // Primary keys are implicitly `NOT NULL`
+        let mut column = Column::new_fixed("id", DataTypeKind::Integer).unwrap();
+        column.set_primary_key(true);
+        assert!(!column.is_nullable());
+        assert!(column.validate(&DataType::Null).is_err());
+    }
+
+    #[test]
+    fn test_missing_value_uses_the_default() {
+        let mut column = Column::new_varlen("name", DataTypeKind::VarChar, 255).unwrap();
+        column
+            .set_nullable(false)
+            .set_default(Some(DataType::Text("anonymous".to_string())));
+
+        // The default is coerced to the column's type like any other value
+        assert_eq!(
+            column.validate(&DataType::Null).unwrap(),
+            DataType::VarChar("anonymous".to_string())
+        );
+        assert_eq!(
+            column
+                .validate(&DataType::VarChar("ada".to_string()))
+                .unwrap(),
+            DataType::VarChar("ada".to_string())
+        );
+    }
+
+    #[test]
+    fn test_mismatched_values_are_coerced_or_rejected() {
+        let column = Column::new_fixed("id", DataTypeKind::Integer).unwrap();
+        assert_eq!(
+            column.validate(&DataType::SmallInt(7)).unwrap(),
+            DataType::Integer(7)
+        );
+        assert_eq!(
+            column.validate(&DataType::Text("42".to_string())).unwrap(),
+            DataType::Integer(42)
+        );
+        assert!(matches!(
+            column.validate(&DataType::Text("forty-two".to_string())),
+            Err(TypeError::InvalidCast { .. })
+        ));
+        assert!(matches!(
+            column.validate(&DataType::Blob(vec![1])),
+            Err(TypeError::IncompatibleType { .. })
+        ));
+
+        let column = Column::new_decimal("price", 5, 2).unwrap();
+        assert_eq!(
+            column
+                .validate(&DataType::Decimal("3.145".parse().unwrap()))
+                .unwrap(),
+            DataType::Decimal("3.15".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_varchar_length_is_enforced() {
+        let mut column = Column::new_varlen("name", DataTypeKind::VarChar, 5).unwrap();
+        let varchar = |s: &str| DataType::VarChar(s.to_string());
+
+        assert_eq!(
+            column.validate(&varchar("hello")).unwrap(),
+            varchar("hello")
+        );
+        assert!(matches!(
+            column.validate(&varchar("hello!")),
+            Err(TypeError::StringTooLong {
+                max_len: 5,
+                found: 6
+            })
+        ));
+        // Text is coerced before its length is checked
+        assert!(column
+            .validate(&DataType::Text("hello!".to_string()))
+            .is_err());
+        // The length is counted in characters, not bytes
+        assert_eq!(
+            column.validate(&varchar("crème")).unwrap(),
+            varchar("crème")
+        );
+
+        column.set_length_policy(LengthPolicy::Truncate);
+        assert_eq!(
+            column.validate(&varchar("hello!")).unwrap(),
+            varchar("hello")
+        );
+        assert_eq!(
+            column
+                .validate(&DataType::Text("crème brûlée".to_string()))
+                .unwrap(),
+            varchar("crème")
+        );
+        assert_eq!(
+            column.validate(&varchar("日本語の本")).unwrap(),
+            varchar("日本語の本")
+        );
+        assert_eq!(
+            column.validate(&varchar("日本語の本です")).unwrap(),
+            varchar("日本語の本")
+        );
+    }
+
+    #[test]
+    fn test_values_are_ordered_under_the_column_collation() {
+        let mut column = Column::new_varlen("name", DataTypeKind::VarChar, 255).unwrap();
+        let (a, b) = (
+            DataType::VarChar("a".to_string()),
+            DataType::VarChar("B".to_string()),
+        );
+        assert_eq!(column.compare_values(&a, &b), Some(Ordering::Greater));
+
+        column.set_collation(Collation::CaseInsensitive);
+        assert_eq!(column.compare_values(&a, &b), Some(Ordering::Less));
+    }
+
     #[test]
     fn test_invalid_varchar_column_with_offset() {
         let column = Column::new_varlen_with_offset("name", DataTypeKind::VarChar, 0, 4);
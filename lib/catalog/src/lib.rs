@@ -1,26 +1,9 @@
-use dashmap::DashMap;
-use std::sync::Arc;
-
+pub mod catalog;
 pub mod column;
 pub mod schema;
+pub mod table;
 
+pub use catalog::{Catalog, CatalogRef};
 pub use column::*;
-
-#[derive(Debug)]
-pub struct Table {
-    name: String,
-    schema: Schema,
-    indexes: Vec<Index>,
-}
-
-#[derive(Debug)]
-pub struct Schema {
-    columns: Vec<Column>,
-}
-
-#[derive(Debug)]
-pub struct Index {
-    // TODO: Implement Index
-    name: String,
-    columns: Vec<Column>,
-}
+pub use schema::{Schema, SchemaRef};
+pub use table::{DatabaseError, Index, Table};
@@ -112,6 +112,14 @@ impl Schema {
             .ok_or(SchemaError::ColumnIndexOutOfBounds.into())
     }
 
+    /// Returns the columns making up the primary key, in column order.
+    pub fn primary_key_columns(&self) -> Vec<&Column> {
+        self.columns
+            .iter()
+            .filter(|column| *column.primary_key())
+            .collect()
+    }
+
     pub fn get_col_idx(&self, col_name: &str) -> Result<usize> {
         let mut col_idx = 0;
         for col in &self.columns {
@@ -221,6 +229,24 @@ mod tests {
         assert_eq!(col2.length(), &ColumnLength::Fixed(4));
     }
 
+    #[test]
+    fn test_primary_key_columns() {
+        let mut id = Column::new_fixed("id", DataTypeKind::Integer).unwrap();
+        id.set_primary_key(true);
+        let schema = Schema::new(vec![
+            id,
+            Column::new_varlen_with_offset("name", DataTypeKind::VarChar, 255, 4).unwrap(),
+        ]);
+
+        let primary_key: Vec<_> = schema
+            .primary_key_columns()
+            .iter()
+            .map(|column| column.column_name().as_str())
+            .collect();
+        assert_eq!(primary_key, ["id"]);
+        assert!(Schema::default().primary_key_columns().is_empty());
+    }
+
     #[test]
     fn test_get_column_invalid_index() {
         let schema = Schema::new(vec![/* ... */]);
@@ -0,0 +1,76 @@
+//! # Tables
+//!
+//! A [`Table`] of the [`Catalog`] holds the schema of its rows, its indexes and the pages of
+//! the heap its rows are stored in, so that the execution layer can resolve the tables named
+//! in queries (e.g. in `FROM <table>`) to their schemas and rows.
+//!
+//! [`Catalog`]: crate::Catalog
+
+use crate::{schema::SchemaRef, Column};
+use getset::Getters;
+use std::sync::RwLock;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DatabaseError {
+    #[error("Table `{0}` already exists")]
+    TableAlreadyExists(String),
+    #[error("Table `{0}` does not exist")]
+    TableNotFound(String),
+    #[error("Invalid encoded catalog: {0}")]
+    InvalidEncoding(String),
+}
+
+/// A table of the [`Catalog`](crate::Catalog), along with the schema of the rows it holds,
+/// its indexes and the pages of the heap its rows are stored in.
+#[derive(Debug, Getters)]
+#[getset(get = "pub")]
+pub struct Table {
+    name: String,
+    schema: SchemaRef,
+    indexes: Vec<Index>,
+    #[getset(skip)]
+    heap_pages: RwLock<Vec<u32>>,
+}
+
+impl Table {
+    /// Creates a table without any indexes or rows.
+    pub fn new(name: &str, schema: impl Into<SchemaRef>) -> Self {
+        Self {
+            name: name.to_string(),
+            schema: schema.into(),
+            indexes: Vec::new(),
+            heap_pages: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Returns the ids of the pages of the heap the rows of the table are stored in, in the
+    /// order they were allocated.
+    pub fn heap_pages(&self) -> Vec<u32> {
+        self.heap_pages.read().unwrap().clone()
+    }
+
+    /// Replaces the pages of the heap the rows of the table are stored in (e.g. after rows
+    /// were inserted into a new page).
+    pub fn set_heap_pages(&self, pages: Vec<u32>) {
+        *self.heap_pages.write().unwrap() = pages;
+    }
+}
+
+/// An index over some of the columns of a [`Table`].
+#[derive(Debug, Getters)]
+#[getset(get = "pub")]
+pub struct Index {
+    // TODO: Implement Index
+    name: String,
+    columns: Vec<Column>,
+}
+
+impl Index {
+    pub fn new(name: &str, columns: Vec<Column>) -> Self {
+        Self {
+            name: name.to_string(),
+            columns,
+        }
+    }
+}
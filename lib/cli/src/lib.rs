@@ -53,7 +53,7 @@ pub struct ServeArgs {
     #[arg(short, long)]
     #[getset(get = "pub")]
     verbose: bool,
-    /// Specify the network protocol (TCP or UDP) for database connections
+    /// Specify the network protocol (TCP, UDP or WebSocket) for database connections
     #[arg(short = 's', long, default_value_t = NetworkProtocol::TCP)]
     #[getset(get = "pub")]
     protocol: NetworkProtocol,
@@ -62,6 +62,11 @@ pub struct ServeArgs {
     #[getset(get = "pub")]
     metrics: bool,
 
+    /// Port to host the metrics server on (the following ports are tried if it is taken)
+    #[arg(long, default_value_t = common::METRICS_PORT)]
+    #[getset(get = "pub")]
+    metrics_port: u16,
+
     /// Maximum number of concurrent transactions to allow
     #[arg(short = 't', long, default_value_t = 20)]
     #[getset(get = "pub")]
@@ -71,12 +76,30 @@ pub struct ServeArgs {
     #[arg(short = 'c', long, default_value_t = 20)]
     #[getset(get = "pub")]
     max_connections: usize,
+
+    /// Abort queries running longer than N milliseconds (disabled if unset)
+    #[arg(long, value_name = "MILLISECONDS")]
+    #[getset(get = "pub")]
+    statement_timeout_ms: Option<u64>,
+
+    /// Maximum size of a (possibly chunked) client message, in bytes
+    #[arg(long, value_name = "BYTES", default_value_t = common::MAX_MESSAGE_LENGTH)]
+    #[getset(get = "pub")]
+    max_message_length: usize,
+
+    /// Require clients to authenticate (with a password or a token) before querying
+    #[arg(long)]
+    #[getset(get = "pub")]
+    auth: bool,
 }
 
 #[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum NetworkProtocol {
     TCP,
     UDP,
+    /// The TCP protocol, framed in WebSocket messages (e.g. for browsers)
+    #[value(name = "ws")]
+    WebSocket,
 }
 
 impl fmt::Display for NetworkProtocol {
@@ -84,6 +107,7 @@ impl fmt::Display for NetworkProtocol {
         match self {
             NetworkProtocol::TCP => write!(f, "tcp"),
             NetworkProtocol::UDP => write!(f, "udp"),
+            NetworkProtocol::WebSocket => write!(f, "ws"),
         }
     }
 }
@@ -113,6 +137,9 @@ pub struct MigrateArgs {
     /// Specify the migration action (up, down, status)
     #[arg(short, long)]
     action: MigrationAction,
+    /// Path to the database file to migrate
+    #[arg(short, long)]
+    db_path: String,
 }
 
 #[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
@@ -121,3 +148,59 @@ pub enum MigrationAction {
     Down,
     Status,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serve_metrics_port_flag() {
+        let serve_args = |args: &[&str]| match Cli::try_parse_from(args).unwrap().command {
+            Some(Commands::Serve(args)) => args,
+            command => panic!("Expected the serve command, got {:?}", command),
+        };
+
+        assert_eq!(
+            *serve_args(&["r2db2", "serve"]).metrics_port(),
+            common::METRICS_PORT
+        );
+        assert_eq!(
+            *serve_args(&["r2db2", "serve", "--metrics-port", "9100"]).metrics_port(),
+            9100
+        );
+        assert!(Cli::try_parse_from(["r2db2", "serve", "--metrics-port", "http"]).is_err());
+    }
+
+    #[test]
+    fn test_serve_protocol_flag_defaults_to_tcp() {
+        let protocol = |args: &[&str]| match Cli::try_parse_from(args).unwrap().command {
+            Some(Commands::Serve(args)) => *args.protocol(),
+            command => panic!("Expected the serve command, got {:?}", command),
+        };
+
+        assert_eq!(protocol(&["r2db2", "serve"]), NetworkProtocol::TCP);
+        assert_eq!(
+            protocol(&["r2db2", "serve", "--protocol", "ws"]),
+            NetworkProtocol::WebSocket
+        );
+    }
+
+    #[test]
+    fn test_migrate_requires_a_database_path() {
+        let migrate = [
+            "r2db2",
+            "migrate",
+            "--migrations-dir",
+            "migrations",
+            "--action",
+            "up",
+        ];
+        assert!(Cli::try_parse_from(migrate).is_err());
+
+        let args = Cli::try_parse_from([&migrate[..], &["--db-path", "app.db"]].concat()).unwrap();
+        match args.command {
+            Some(Commands::Migrate(args)) => assert_eq!(args.db_path(), "app.db"),
+            command => panic!("Expected the migrate command, got {:?}", command),
+        }
+    }
+}
@@ -1,8 +1,8 @@
-use std::sync::Arc;
+use std::{fs, path::Path, sync::Arc};
 
-use crate::SqlArgs;
-use anyhow::Result;
-use driver::{shell::Shell, Driver};
+use crate::{MigrateArgs, MigrationAction, SqlArgs};
+use anyhow::{Context, Result};
+use driver::{migrate::Migrator, shell::Shell, Driver};
 use tracing::info;
 
 pub async fn handle_sql_command(args: &SqlArgs) -> Result<()> {
@@ -10,13 +10,23 @@ pub async fn handle_sql_command(args: &SqlArgs) -> Result<()> {
         .db_path()
         .clone()
         .unwrap_or_else(|| "test.db".to_owned());
-    let driver = Driver::new(&db_path).expect("Failed to create driver");
+    let driver = Driver::new(&db_path)
+        .await
+        .expect("Failed to create driver");
 
     if let Some(command) = args.command() {
-        info!("Executing SQL command");
-        driver.process_sql_command(command).await;
+        // The command is either SQL or the path of a script to execute statement by statement
+        if Path::new(command).is_file() {
+            info!("Executing SQL script {}", command);
+            let script = fs::read_to_string(command)
+                .with_context(|| format!("Failed to read SQL script {}", command))?;
+            driver.process_sql_command(&script).await;
+        } else {
+            info!("Executing SQL command");
+            driver.process_sql_command(command).await;
+        }
 
-        return Ok(());
+        return driver.checkpoint().await;
     }
 
     // Start shell
@@ -27,3 +37,73 @@ pub async fn handle_sql_command(args: &SqlArgs) -> Result<()> {
 
     Ok(())
 }
+
+pub async fn handle_migrate_command(args: &MigrateArgs) -> Result<()> {
+    let driver = Driver::new(args.db_path()).await?;
+    let migrator = Migrator::new(&driver, args.migrations_dir());
+
+    match args.action() {
+        MigrationAction::Up => {
+            let applied = migrator.up().await?;
+            println!("Applied {} migration(s): {:?}", applied.len(), applied);
+        }
+        MigrationAction::Down => match migrator.down().await? {
+            Some(version) => println!("Rolled back migration {}", version),
+            None => println!("No migration to roll back"),
+        },
+        MigrationAction::Status => {
+            for status in migrator.status().await? {
+                println!(
+                    "{:>8}  {:<32}  {}",
+                    status.version,
+                    status.name,
+                    if status.applied { "applied" } else { "pending" }
+                );
+            }
+        }
+    }
+
+    driver.checkpoint().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_command_executes_a_script_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let db_path = db_path.to_str().unwrap().to_string();
+        let script_path = temp_dir.path().join("seed.sql");
+        fs::write(
+            &script_path,
+            "-- Seed the notes
+             CREATE TABLE notes (id INTEGER, body VARCHAR(16));
+             INSERT INTO notes VALUES (1, 'first; of two');
+             INSERT INTO notes VALUES (2, 'second');
+            ",
+        )
+        .unwrap();
+
+        let args = SqlArgs::builder()
+            .command(Some(script_path.to_str().unwrap().to_string()))
+            .db_path(Some(db_path.clone()))
+            .build();
+        handle_sql_command(&args).await.unwrap();
+
+        let driver = Driver::new(&db_path).await.unwrap();
+        let mut rows = Vec::new();
+        driver
+            .execute_script("SELECT body FROM notes", true, |result| {
+                rows = result.as_ref().unwrap().rows().clone();
+            })
+            .await
+            .unwrap();
+        let bodies = rows
+            .iter()
+            .map(|row| row[0].to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(bodies, ["first; of two", "second"]);
+    }
+}
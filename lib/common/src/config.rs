@@ -6,7 +6,7 @@ use config::{ConfigBuilder, Environment, File, FileFormat};
 use getset::{Getters, Setters};
 use serde::{Deserialize, Serialize};
 use shrinkwraprs::Shrinkwrap;
-use std::{fmt, time::Duration};
+use std::{fmt, str::FromStr, time::Duration};
 use thiserror::Error;
 use typed_builder::TypedBuilder;
 use url::Url;
@@ -18,10 +18,35 @@ pub const DEFAULT_METRICS_SAMPLE_RATE: u32 = 100;
 /// are the unit of data transfer between disk and memory.
 pub const PAGE_SIZE: usize = 4096;
 
+/// The number of bytes at the end of every on-disk page reserved for its CRC32 checksum.
+pub const PAGE_CHECKSUM_SIZE: usize = 4;
+
+/// The number of bytes of every on-disk page, just ahead of its checksum, reserved for its header:
+/// a magic number telling written pages from never-written ones, the kind of the page and its LSN.
+pub const PAGE_HEADER_SIZE: usize = 16;
+
+/// The number of bytes of a page available to callers. The trailing [`PAGE_HEADER_SIZE`] and
+/// [`PAGE_CHECKSUM_SIZE`] bytes are overwritten with the page header and checksum when the page
+/// is written to disk.
+pub const USABLE_PAGE_SIZE: usize = PAGE_SIZE - PAGE_HEADER_SIZE - PAGE_CHECKSUM_SIZE;
+
 /// The size of the buffer pool (in frames). Specifies the number of pages that can be held in
 /// memory at any given time. The buffer pool is the primary mechanism for storing pages in memory.
 pub const BUFFER_POOL_SIZE: usize = 100;
 
+/// The number of accesses within [`HOT_PAGE_ACCESS_WINDOW`] after which a page is automatically
+/// promoted to the hot partition of the buffer pool. Hot pages are preferentially retained during
+/// eviction.
+pub const HOT_PAGE_ACCESS_THRESHOLD: usize = 8;
+
+/// The window over which page accesses are counted for hot page promotion.
+pub const HOT_PAGE_ACCESS_WINDOW: Duration = Duration::from_secs(1);
+
+/// The fraction of buffer pool frames reserved for hot pages. Up to this many resident hot
+/// pages are protected from eviction while cold pages are evictable; hot pages beyond the
+/// reserve compete with cold pages under the base replacement policy.
+pub const HOT_PARTITION_FRACTION: f64 = 0.5;
+
 /// The maximum number of concurrent transactions. Sets an upper limit on the number of transactions
 /// that can be processed concurrently by the DBMS. This is used to initialize the scheduler.
 /// Transactions beyond this limit will be blocked until a transaction completes.
@@ -29,6 +54,18 @@ pub const MAX_TRANSACTIONS: usize = 100;
 
 pub const TCP_PORT: u16 = 2345;
 pub const UDP_PORT: u16 = 2346;
+pub const WS_PORT: u16 = 2347;
+
+/// The port the metrics server tries first. If it is taken, the following ports are tried.
+pub const METRICS_PORT: u16 = 8080;
+
+/// The default upper bound (in bytes) on the payload of a message reassembled from chunked
+/// protocol frames. Larger messages are rejected and the connection is closed.
+pub const MAX_MESSAGE_LENGTH: usize = 16 * 1024 * 1024;
+
+/// The default number of result rows the server coalesces into a single write to the socket.
+/// Rows are written as soon as they are produced; only rows that are ready at once share a write.
+pub const ROW_FLUSH_BATCH_SIZE: usize = 64;
 
 /// Unique identifier for a frame. Frames are identified by a monotonically increasing integer
 /// and are the unit of storage in the buffer pool.
@@ -96,6 +133,39 @@ impl fmt::Display for FrameId {
     }
 }
 
+impl FromStr for FrameId {
+    type Err = ParseIdError;
+
+    /// Parses a frame id from either a bare number (`5`) or its display form (`FrameId(5)`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_id("FrameId", s).map(Self)
+    }
+}
+
+/// Error returned when parsing a [`PageId`] or [`FrameId`] from a string fails.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("Invalid {kind} `{input}`: expected a number or `{kind}(<number>)`")]
+pub struct ParseIdError {
+    kind: &'static str,
+    input: String,
+}
+
+/// Parses an id written as a bare number or wrapped in its type name (e.g. `PageId(5)`), as
+/// the ids are displayed.
+fn parse_id(kind: &'static str, input: &str) -> Result<u32, ParseIdError> {
+    let trimmed = input.trim();
+    let number = trimmed
+        .strip_prefix(kind)
+        .and_then(|rest| rest.strip_prefix('('))
+        .and_then(|rest| rest.strip_suffix(')'))
+        .unwrap_or(trimmed);
+
+    number.trim().parse().map_err(|_| ParseIdError {
+        kind,
+        input: input.to_string(),
+    })
+}
+
 /// Unique identifier for a page. Pages are identified by a tuple of (file_id, page_number).
 #[derive(
     Debug,
@@ -113,6 +183,10 @@ impl fmt::Display for FrameId {
 )]
 pub struct PageId(pub u32);
 
+/// The page holding the system catalog. Reserved at the start of every database file so it can be
+/// located (and prefetched) without consulting any other metadata.
+pub const CATALOG_PAGE_ID: PageId = PageId(0);
+
 impl PageId {
     pub fn new(page_id: u32) -> Self {
         Self(page_id)
@@ -139,6 +213,15 @@ impl fmt::Display for PageId {
     }
 }
 
+impl FromStr for PageId {
+    type Err = ParseIdError;
+
+    /// Parses a page id from either a bare number (`5`) or its display form (`PageId(5)`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_id("PageId", s).map(Self)
+    }
+}
+
 impl From<PageId> for u32 {
     fn from(page_id: PageId) -> Self {
         page_id.0
@@ -428,3 +511,51 @@ mod connection_string_tests {
         assert!(config_result.is_err());
     }
 }
+
+#[cfg(test)]
+mod id_tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_parse_from_numbers_and_their_display_form() {
+        assert_eq!("5".parse::<PageId>(), Ok(PageId::from(5)));
+        assert_eq!(" 42 ".parse::<PageId>(), Ok(PageId::from(42)));
+        assert_eq!("5".parse::<FrameId>(), Ok(FrameId::from(5)));
+
+        let page_id = PageId::from(7);
+        assert_eq!(page_id.to_string().parse::<PageId>(), Ok(page_id));
+        let frame_id = FrameId::from(9);
+        assert_eq!(frame_id.to_string().parse::<FrameId>(), Ok(frame_id));
+    }
+
+    #[test]
+    fn test_invalid_ids_are_rejected() {
+        for input in ["", "-1", "five", "4294967296", "FrameId(5)", "PageId(5"] {
+            let err = input.parse::<PageId>().unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "Invalid PageId `{}`: expected a number or `PageId(<number>)`",
+                    input
+                )
+            );
+        }
+        assert!("PageId(5)".parse::<FrameId>().is_err());
+    }
+
+    #[test]
+    fn test_ids_round_trip_through_serde() {
+        let page_id = PageId::from(5);
+        let json = serde_json::to_string(&page_id).unwrap();
+        assert_eq!(json, "5");
+        assert_eq!(serde_json::from_str::<PageId>(&json).unwrap(), page_id);
+
+        let frame_ids = vec![FrameId::from(1), FrameId::from(2)];
+        let json = serde_json::to_string(&frame_ids).unwrap();
+        assert_eq!(json, "[1,2]");
+        assert_eq!(
+            serde_json::from_str::<Vec<FrameId>>(&json).unwrap(),
+            frame_ids
+        );
+    }
+}
@@ -0,0 +1,127 @@
+/// `ByteWriter` builds a byte buffer out of primitives, writing every number in big-endian
+/// (network) byte order so that encoders don't have to spell out the endianness themselves.
+///
+/// ```
+/// use common::util::bytes::ByteWriter;
+///
+/// let mut writer = ByteWriter::new();
+/// writer.put_i16(1).put_bytes(b"ab");
+/// assert_eq!(writer.into_vec(), vec![0, 1, b'a', b'b']);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ByteWriter {
+    buf: Vec<u8>,
+}
+
+impl ByteWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        ByteWriter {
+            buf: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn put_u8(&mut self, val: u8) -> &mut Self {
+        self.buf.push(val);
+        self
+    }
+
+    pub fn put_i16(&mut self, val: i16) -> &mut Self {
+        self.put_bytes(&val.to_be_bytes())
+    }
+
+    pub fn put_i32(&mut self, val: i32) -> &mut Self {
+        self.put_bytes(&val.to_be_bytes())
+    }
+
+    pub fn put_u32(&mut self, val: u32) -> &mut Self {
+        self.put_bytes(&val.to_be_bytes())
+    }
+
+    pub fn put_i64(&mut self, val: i64) -> &mut Self {
+        self.put_bytes(&val.to_be_bytes())
+    }
+
+    pub fn put_f32(&mut self, val: f32) -> &mut Self {
+        self.put_bytes(&val.to_be_bytes())
+    }
+
+    pub fn put_f64(&mut self, val: f64) -> &mut Self {
+        self.put_bytes(&val.to_be_bytes())
+    }
+
+    /// Appends raw bytes as they are.
+    pub fn put_bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(bytes);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Returns the bytes written so far.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Consumes the writer, returning the bytes written.
+    pub fn into_vec(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_primitives_match_manual_encoding() {
+        assert_eq!(ByteWriter::new().put_u8(0xab).as_bytes(), &[0xab]);
+        assert_eq!(
+            ByteWriter::new().put_i16(-2).as_bytes(),
+            &(-2i16).to_be_bytes()
+        );
+        assert_eq!(
+            ByteWriter::new().put_i32(-123_456).as_bytes(),
+            &(-123_456i32).to_be_bytes()
+        );
+        assert_eq!(
+            ByteWriter::new().put_u32(0xdead_beef).as_bytes(),
+            &0xdead_beefu32.to_be_bytes()
+        );
+        assert_eq!(
+            ByteWriter::new().put_i64(i64::MIN).as_bytes(),
+            &i64::MIN.to_be_bytes()
+        );
+        assert_eq!(
+            ByteWriter::new().put_f32(1.5).as_bytes(),
+            &1.5f32.to_be_bytes()
+        );
+        assert_eq!(
+            ByteWriter::new().put_f64(-0.1).as_bytes(),
+            &(-0.1f64).to_be_bytes()
+        );
+        assert_eq!(ByteWriter::new().put_bytes(b"xyz").as_bytes(), b"xyz");
+    }
+
+    #[test]
+    fn test_writes_are_appended_in_order() {
+        let mut writer = ByteWriter::with_capacity(24);
+        writer.put_f64(1.0).put_f64(2.0).put_f64(3.0);
+        assert_eq!(writer.len(), 24);
+
+        let mut manual = Vec::new();
+        manual.append(&mut 1.0f64.to_be_bytes().to_vec());
+        manual.append(&mut 2.0f64.to_be_bytes().to_vec());
+        manual.append(&mut 3.0f64.to_be_bytes().to_vec());
+        assert_eq!(writer.into_vec(), manual);
+    }
+}
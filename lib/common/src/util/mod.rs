@@ -1,2 +1,3 @@
+pub mod bytes;
 pub mod time;
 pub mod trace;
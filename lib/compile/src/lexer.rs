@@ -1,5 +1,6 @@
 use crate::diagnostics::{CompileError, LocatableError, SyntaxError};
-use logos::{Lexer, Logos};
+use chrono::{NaiveDate, NaiveDateTime};
+use logos::{FilterResult, Lexer, Logos};
 use thiserror::Error;
 
 #[derive(Debug, Default, Error, PartialEq, Clone)]
@@ -13,6 +14,12 @@ pub enum LexerError {
     ParseFloat,
     #[error("Unterminated string literal")]
     UnterminatedString,
+    #[error("Unterminated quoted identifier")]
+    UnterminatedQuotedIdent,
+    #[error("Unterminated block comment")]
+    UnterminatedBlockComment,
+    #[error("Invalid date or timestamp literal")]
+    InvalidDate,
 }
 
 impl From<std::num::ParseIntError> for LexerError {
@@ -33,10 +40,82 @@ fn ident(lex: &mut Lexer<TokenKind>) -> Option<String> {
     Some(ident)
 }
 
-fn string(lex: &mut Lexer<TokenKind>) -> Option<String> {
+/// Lexes a string literal whose opening quote was just lexed, unescaping doubled quotes
+/// (`'O''Brien'`).
+///
+/// A literal missing its closing quote is assumed to end with its line: lexing resumes on the
+/// next line instead of consuming the rest of the input, so that the errors of the following
+/// statements are reported as well.
+fn string(lex: &mut Lexer<TokenKind>) -> Result<String, LexerError> {
+    let remainder = lex.remainder();
+    let mut chars = remainder.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != '\'' {
+            continue;
+        }
+        if chars.next_if(|(_, c)| *c == '\'').is_none() {
+            lex.bump(i + 1);
+            return Ok(remainder[..i].replace("''", "'"));
+        }
+    }
+
+    lex.bump(remainder.find('\n').unwrap_or(remainder.len()));
+    Err(LexerError::UnterminatedString)
+}
+
+/// Strips the outer quotes of a quoted identifier, unescaping doubled quotes.
+fn quoted_ident(lex: &mut Lexer<TokenKind>) -> Option<String> {
     let slice = lex.slice();
-    let string: String = slice[1..slice.len() - 1].parse().ok()?;
-    Some(string)
+    Some(slice[1..slice.len() - 1].replace("\"\"", "\""))
+}
+
+/// Skips a block comment whose opening `/*` was just lexed. Block comments nest, so the
+/// comment only ends once every nested `/*` has been closed.
+fn block_comment(lex: &mut Lexer<TokenKind>) -> FilterResult<(), LexerError> {
+    let remainder = lex.remainder().as_bytes();
+    let mut depth = 1;
+    let mut i = 0;
+    while i < remainder.len() {
+        match remainder.get(i..i + 2) {
+            Some(b"/*") => {
+                depth += 1;
+                i += 2;
+            }
+            Some(b"*/") => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    lex.bump(i);
+                    return FilterResult::Skip;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    lex.bump(remainder.len());
+    FilterResult::Error(LexerError::UnterminatedBlockComment)
+}
+
+/// Returns the quoted part of a literal, without the type of a typed literal (`DATE '...'`).
+fn quoted(slice: &str) -> &str {
+    let start = slice.find('\'').unwrap_or(0);
+    &slice[start + 1..slice.len() - 1]
+}
+
+fn date(lex: &mut Lexer<TokenKind>) -> Result<NaiveDate, LexerError> {
+    NaiveDate::parse_from_str(quoted(lex.slice()), "%Y-%m-%d").map_err(|_| LexerError::InvalidDate)
+}
+
+fn timestamp(lex: &mut Lexer<TokenKind>) -> Result<NaiveDateTime, LexerError> {
+    let literal = quoted(lex.slice());
+    NaiveDateTime::parse_from_str(&literal.replacen('T', " ", 1), "%Y-%m-%d %H:%M:%S%.f")
+        // `TIMESTAMP '2023-01-01'` is midnight of that day
+        .or_else(|_| {
+            NaiveDate::parse_from_str(literal, "%Y-%m-%d")
+                .map(|date| date.and_time(Default::default()))
+        })
+        .map_err(|_| LexerError::InvalidDate)
 }
 
 #[derive(Logos, Debug, PartialEq, Clone)]
@@ -44,6 +123,7 @@ fn string(lex: &mut Lexer<TokenKind>) -> Option<String> {
 enum TokenKind {
     #[regex(r"[ \n\t\f]+", logos::skip)]
     #[regex(r"--[^\n]*", logos::skip)]
+    #[token("/*", block_comment)]
     Ignored,
 
     #[token("SELECT", ignore(ascii_case))]
@@ -165,8 +245,20 @@ enum TokenKind {
     False,
     #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*", ident)]
     Ident(String),
-    #[regex(r"'[^']*'", string)]
+    #[regex(r#""([^"]|"")*""#, quoted_ident)]
+    QuotedIdent(String),
+    #[token("'", string)]
     String(String),
+    #[regex(r"'[0-9]{4}-[0-9]{2}-[0-9]{2}'", date, priority = 10)]
+    #[regex(r"DATE[ \n\t\f]+'[^']*'", date, ignore(ascii_case))]
+    DateLiteral(NaiveDate),
+    #[regex(
+        r"'[0-9]{4}-[0-9]{2}-[0-9]{2}[ T][0-9]{2}:[0-9]{2}:[0-9]{2}(\.[0-9]+)?'",
+        timestamp,
+        priority = 10
+    )]
+    #[regex(r"TIMESTAMP[ \n\t\f]+'[^']*'", timestamp, ignore(ascii_case))]
+    TimestampLiteral(NaiveDateTime),
 
     #[token("=")]
     Eq,
@@ -189,6 +281,12 @@ enum TokenKind {
     Star,
     #[token("/")]
     Slash,
+    #[token("%")]
+    Percent,
+    #[token("||")]
+    Concat,
+    #[token("::")]
+    DoubleColon,
 
     #[token(",")]
     Comma,
@@ -198,6 +296,10 @@ enum TokenKind {
     LParen,
     #[token(")")]
     RParen,
+    #[token("[")]
+    LBracket,
+    #[token("]")]
+    RBracket,
     #[token(".")]
     Dot,
     #[token("->")]
@@ -205,10 +307,10 @@ enum TokenKind {
     #[token("?")]
     Question,
 
-    #[regex(r"'[^']*", |_| {
-        Err(LexerError::UnterminatedString)
+    #[regex(r#""([^"]|"")*"#, |_| {
+        Err(LexerError::UnterminatedQuotedIdent)
     })]
-    UnterminatedString,
+    UnterminatedQuotedIdent,
 }
 
 // Basic SQL Queries
@@ -298,6 +400,83 @@ mod basic_queries {
         );
     }
 
+    #[test]
+    fn test_quoted_identifiers() {
+        let lexer = TokenKind::lexer(r#"SELECT "order", "First Name" FROM "user";"#);
+        let tokens = lexer.spanned().collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            &[
+                (Ok(Select), 0..6),
+                (Ok(QuotedIdent("order".to_string())), 7..14),
+                (Ok(Comma), 14..15),
+                (Ok(QuotedIdent("First Name".to_string())), 16..28),
+                (Ok(From), 29..33),
+                (Ok(QuotedIdent("user".to_string())), 34..40),
+                (Ok(Semi), 40..41),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_escaped_quotes() {
+        let lexer = TokenKind::lexer(r#"'O''Brien' '''' "say ""hi""""#);
+        let tokens = lexer.spanned().collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            &[
+                (Ok(String("O'Brien".to_string())), 0..10),
+                (Ok(String("'".to_string())), 11..15),
+                (Ok(QuotedIdent(r#"say "hi""#.to_string())), 16..28),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_operators() {
+        let lexer = TokenKind::lexer("a || b x % 2 val::INTEGER arr[1]");
+        let tokens = lexer.spanned().collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            &[
+                (Ok(Ident("a".to_string())), 0..1),
+                (Ok(Concat), 2..4),
+                (Ok(Ident("b".to_string())), 5..6),
+                (Ok(Ident("x".to_string())), 7..8),
+                (Ok(Percent), 9..10),
+                (Ok(Integer(2)), 11..12),
+                (Ok(Ident("val".to_string())), 13..16),
+                (Ok(DoubleColon), 16..18),
+                (Ok(Ident("INTEGER".to_string())), 18..25),
+                (Ok(Ident("arr".to_string())), 26..29),
+                (Ok(LBracket), 29..30),
+                (Ok(Integer(1)), 30..31),
+                (Ok(RBracket), 31..32),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_block_comments() {
+        let lexer = TokenKind::lexer(
+            "SELECT /* the id\n   of every user */ id /* nested /* comments */ end */ FROM users",
+        );
+        let tokens = lexer.spanned().collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            &[
+                (Ok(Select), 0..6),
+                (Ok(Ident("id".to_string())), 37..39),
+                (Ok(From), 72..76),
+                (Ok(Ident("users".to_string())), 77..82),
+            ],
+        );
+    }
+
     #[test]
     fn test_insert() {
         let lexer =
@@ -363,11 +542,50 @@ mod basic_queries {
                 (Ok(Where), 17..22),
                 (Ok(Ident("created_at".to_string())), 23..33),
                 (Ok(Lt), 34..35),
-                (Ok(String("2023-01-01".to_string())), 36..48), // TODO: enhance lexer to support dates
+                (
+                    Ok(DateLiteral(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap())),
+                    36..48
+                ),
                 (Ok(Semi), 48..49),
             ],
         );
     }
+
+    #[test]
+    fn test_date_and_timestamp_literals() {
+        let lexer = TokenKind::lexer(
+            "'2024-02-29' '2023-01-01 12:30:45' '2023-01-01T12:30:45.5' date '2023-06-01' \
+             TIMESTAMP '2023-06-01'",
+        );
+        let tokens = lexer.spanned().collect::<Vec<_>>();
+
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(
+            tokens,
+            &[
+                (Ok(DateLiteral(date(2024, 2, 29))), 0..12),
+                (
+                    Ok(TimestampLiteral(
+                        date(2023, 1, 1).and_hms_opt(12, 30, 45).unwrap()
+                    )),
+                    13..34
+                ),
+                (
+                    Ok(TimestampLiteral(
+                        date(2023, 1, 1).and_hms_milli_opt(12, 30, 45, 500).unwrap()
+                    )),
+                    35..58
+                ),
+                (Ok(DateLiteral(date(2023, 6, 1))), 59..76),
+                (
+                    Ok(TimestampLiteral(
+                        date(2023, 6, 1).and_hms_opt(0, 0, 0).unwrap()
+                    )),
+                    77..99
+                ),
+            ],
+        );
+    }
 }
 
 #[cfg(test)]
@@ -756,6 +974,22 @@ mod error_cases {
     use pretty_assertions_sorted::assert_eq;
     use TokenKind::*;
 
+    #[test]
+    fn test_invalid_date() {
+        let lexer = TokenKind::lexer("'2023-13-45' DATE 'yesterday' 'next week'");
+
+        let tokens = lexer.spanned().collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            &[
+                (Err(LexerError::InvalidDate), 0..12),
+                (Err(LexerError::InvalidDate), 13..29),
+                (Ok(String("next week".to_string())), 30..41),
+            ],
+        );
+    }
+
     #[test]
     fn test_unterminated_string() {
         let lexer = TokenKind::lexer("'This is an unterminated string");
@@ -765,6 +999,63 @@ mod error_cases {
         assert_eq!(tokens, &[(Err(LexerError::UnterminatedString), 0..31)],);
     }
 
+    #[test]
+    fn test_unterminated_escaped_string() {
+        let lexer = TokenKind::lexer(r#"'O''Brien''s "name"#);
+
+        let tokens = lexer.spanned().collect::<Vec<_>>();
+
+        assert_eq!(tokens, &[(Err(LexerError::UnterminatedString), 0..18)],);
+    }
+
+    #[test]
+    fn test_lexing_resumes_after_the_line_of_an_unterminated_string() {
+        let lexer = TokenKind::lexer("SELECT 'oops;\nSELECT 1;");
+
+        let tokens = lexer.spanned().collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            &[
+                (Ok(Select), 0..6),
+                (Err(LexerError::UnterminatedString), 7..13),
+                (Ok(Select), 14..20),
+                (Ok(Integer(1)), 21..22),
+                (Ok(Semi), 22..23),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_unterminated_quoted_identifier() {
+        let lexer = TokenKind::lexer(r#"SELECT "name"#);
+
+        let tokens = lexer.spanned().collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            &[
+                (Ok(Select), 0..6),
+                (Err(LexerError::UnterminatedQuotedIdent), 7..12),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let lexer = TokenKind::lexer("SELECT /* never /* closed */");
+
+        let tokens = lexer.spanned().collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            &[
+                (Ok(Select), 0..6),
+                (Err(LexerError::UnterminatedBlockComment), 7..28),
+            ],
+        );
+    }
+
     #[test]
     fn test_unexpected_token() {
         let lexer = TokenKind::lexer("SELECT * FROM @");
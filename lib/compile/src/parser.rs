@@ -1,9 +1,14 @@
-use crate::diagnostics::{report_errors, CompileError, LocatableResult, SyntaxError};
+use crate::diagnostics::{report_errors, CompileError, LocatableResult, Span, SyntaxError};
 use anyhow::Result;
 pub use sqlparser::ast::*;
 use sqlparser::dialect::{self, Dialect, PostgreSqlDialect};
 use sqlparser::parser::Parser;
 pub use sqlparser::parser::ParserError;
+use sqlparser::tokenizer::{Location, Token, Tokenizer};
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::CharIndices;
+use thiserror::Error;
 
 // #[derive(Debug)]
 // pub enum SyntaxError {
@@ -12,10 +17,196 @@ pub use sqlparser::parser::ParserError;
 //     MissingFromClause,
 // }
 
+/// The error a SQL string fails to parse with, locating the token the parser did not expect.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("syntax error {} at position {}: {message}", near(.token), .span.start + 1)]
+pub struct ParseError {
+    message: String,
+    token: Option<String>,
+    span: Span,
+    expected: Vec<String>,
+}
+
+fn near(token: &Option<String>) -> String {
+    match token {
+        Some(token) => format!("near '{}'", token),
+        None => "at end of input".to_string(),
+    }
+}
+
+impl ParseError {
+    /// The message the parser failed with.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The unexpected token, or `None` if the input ended unexpectedly.
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    /// The byte range of the unexpected token in the parsed string.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// What the parser expected to find instead of the unexpected token (empty if unknown).
+    pub fn expected(&self) -> &[String] {
+        &self.expected
+    }
+
+    fn from_parser_error(sql: &str, error: ParserError) -> Self {
+        let message = match error {
+            ParserError::ParserError(message) | ParserError::TokenizerError(message) => message,
+            ParserError::RecursionLimitExceeded => {
+                return ParseError {
+                    message: error.to_string(),
+                    token: None,
+                    span: 0..sql.len(),
+                    expected: Vec::new(),
+                }
+            }
+        };
+
+        // Messages end with the location of the offending token, unless the input ended
+        let (message, start) = match message.rsplit_once(" at Line: ") {
+            Some((message, location)) => (message.to_string(), byte_offset(sql, location)),
+            None => (message, None),
+        };
+
+        // Most messages read "Expected <expected>, found: <token>"
+        let (expected, found) = match message
+            .strip_prefix("Expected ")
+            .and_then(|rest| rest.rsplit_once(", found: "))
+        {
+            Some(("one of", found)) => (Vec::new(), Some(found)),
+            Some((expected, found)) => (
+                expected
+                    .strip_prefix("one of ")
+                    .map(|alternatives| alternatives.split(" or ").map(String::from).collect())
+                    .unwrap_or_else(|| vec![expected.to_string()]),
+                Some(found),
+            ),
+            None => (Vec::new(), None),
+        };
+
+        let token = found.filter(|found| *found != "EOF").map(String::from);
+        let span = match (start, &token) {
+            (Some(start), Some(token)) => start..(start + token.len()).min(sql.len()),
+            (Some(start), None) => start..start,
+            (None, _) => sql.len()..sql.len(),
+        };
+
+        ParseError {
+            message,
+            token,
+            span,
+            expected,
+        }
+    }
+}
+
+/// Converts a `"<line>, Column <column>"` location (both 1-based, columns counted in
+/// characters) into a byte offset into `sql`.
+fn byte_offset(sql: &str, location: &str) -> Option<usize> {
+    let (line, column) = location.split_once(", Column ")?;
+    let (line, column) = (line.parse::<usize>().ok()?, column.parse::<usize>().ok()?);
+
+    let line_start = if line <= 1 {
+        0
+    } else {
+        sql.match_indices('\n').nth(line - 2)?.0 + 1
+    };
+    sql[line_start..]
+        .char_indices()
+        .map(|(offset, _)| line_start + offset)
+        .chain(std::iter::once(sql.len()))
+        .nth(column.checked_sub(1)?)
+}
+
 /// Parse the SQL string and return a list of SQL statements.
-pub fn parse_sql(sql: &str) -> Result<Vec<Statement>, ParserError> {
+pub fn parse_sql(sql: &str) -> Result<Vec<Statement>, ParseError> {
     let dialect = PostgreSqlDialect {};
-    Parser::parse_sql(&dialect, sql)
+    Parser::parse_sql(&dialect, sql).map_err(|e| ParseError::from_parser_error(sql, e))
+}
+
+/// Splits a script into its statements at the semicolons outside of string literals, quoted
+/// identifiers and comments, dropping the whitespace and comments between statements as well
+/// as empty statements (e.g. `;;`).
+///
+/// A script that can't be tokenized (e.g. with an unterminated string literal) is returned as
+/// a single statement, so that executing it reports the error.
+pub fn split_statements(script: &str) -> Vec<String> {
+    let dialect = PostgreSqlDialect {};
+    let Ok(tokens) = Tokenizer::new(&dialect, script).tokenize_with_location() else {
+        let script = script.trim();
+        return match script.is_empty() {
+            true => Vec::new(),
+            false => vec![script.to_string()],
+        };
+    };
+
+    let mut cursor = Cursor::new(script);
+    let mut statements = Vec::new();
+    // The byte range of the statement being split, up to the end of its last token so far
+    let mut statement: Option<Range<usize>> = None;
+    // Whether the previous token is part of the statement, so that it ends where this one starts
+    let mut in_statement = false;
+    for token in &tokens {
+        let start = cursor.offset(token.location);
+        if let (true, Some(range)) = (in_statement, &mut statement) {
+            range.end = start;
+        }
+        in_statement = false;
+
+        match token.token {
+            Token::SemiColon => statements.extend(statement.take().map(|r| script[r].to_string())),
+            Token::Whitespace(_) | Token::EOF => {}
+            _ => {
+                statement.get_or_insert(start..start);
+                in_statement = true;
+            }
+        }
+    }
+    if let (true, Some(range)) = (in_statement, &mut statement) {
+        range.end = script.len();
+    }
+    statements.extend(statement.map(|r| script[r].to_string()));
+    statements
+}
+
+/// Converts the locations of the tokens of a string, in order, into byte offsets.
+struct Cursor<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    len: usize,
+    line: u64,
+    column: u64,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(s: &'a str) -> Self {
+        Cursor {
+            chars: s.char_indices().peekable(),
+            len: s.len(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    /// Returns the byte offset of a location at or after the previous one.
+    fn offset(&mut self, location: Location) -> usize {
+        while (self.line, self.column) < (location.line, location.column) {
+            match self.chars.next() {
+                Some((_, '\n')) => {
+                    self.line += 1;
+                    self.column = 1;
+                }
+                Some(_) => self.column += 1,
+                None => break,
+            }
+        }
+        self.chars.peek().map_or(self.len, |(offset, _)| *offset)
+    }
 }
 
 pub fn parse(source: &str) -> LocatableResult<Vec<Statement>> {
@@ -53,3 +244,77 @@ pub fn parse(source: &str) -> LocatableResult<Vec<Statement>> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_from_reports_the_unexpected_token() {
+        let error = parse_sql("SELECT * users").unwrap_err();
+        assert_eq!(error.token(), Some("users"));
+        assert_eq!(error.span(), &(9..14));
+        assert_eq!(error.expected(), ["end of statement"]);
+        assert_eq!(
+            error.to_string(),
+            "syntax error near 'users' at position 10: Expected end of statement, found: users"
+        );
+    }
+
+    #[test]
+    fn test_unbalanced_paren_reports_the_unexpected_token() {
+        let error = parse_sql("SELECT (1 + 2 FROM t").unwrap_err();
+        assert_eq!(error.token(), Some("FROM"));
+        assert_eq!(error.span(), &(14..18));
+        assert_eq!(error.expected(), [")"]);
+
+        // An unclosed paren at the end of the input
+        let sql = "SELECT *\nFROM t\nWHERE (a = 1";
+        let error = parse_sql(sql).unwrap_err();
+        assert_eq!(error.token(), None);
+        assert_eq!(error.span(), &(sql.len()..sql.len()));
+        assert!(error
+            .to_string()
+            .starts_with("syntax error at end of input"));
+    }
+
+    #[test]
+    fn test_statements_are_split_at_semicolons() {
+        assert_eq!(
+            split_statements(
+                "CREATE TABLE t (a INT);\nINSERT INTO t VALUES (1);\n\nSELECT * FROM t"
+            ),
+            [
+                "CREATE TABLE t (a INT)",
+                "INSERT INTO t VALUES (1)",
+                "SELECT * FROM t"
+            ]
+        );
+        assert!(split_statements(" ;; -- nothing\n").is_empty());
+    }
+
+    #[test]
+    fn test_statements_are_split_outside_of_literals_and_comments() {
+        assert_eq!(
+            split_statements("SELECT 'a;b'; -- no; split\nSELECT /* nor; here */ \"c;d\";;\n"),
+            ["SELECT 'a;b'", "SELECT /* nor; here */ \"c;d\""]
+        );
+        // Locations are counted in characters, not bytes
+        assert_eq!(
+            split_statements("SELECT 'é;è';\r\nSELECT 'ü'"),
+            ["SELECT 'é;è'", "SELECT 'ü'"]
+        );
+        // An unterminated literal leaves the script whole
+        assert_eq!(
+            split_statements("SELECT 1; SELECT 'a;"),
+            ["SELECT 1; SELECT 'a;"]
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_errors_are_located() {
+        let sql = "SELECT 1;\nSELECT 'abc";
+        let error = parse_sql(sql).unwrap_err();
+        assert_eq!(error.span().start, 17);
+    }
+}
@@ -1,18 +1,71 @@
 #![allow(dead_code)]
-use anyhow::Result;
-use buffer::{BufferPoolManager, ReplacementPolicy};
-use execution::QueryEngine;
+use anyhow::{Context, Result};
+use buffer::{BufferPoolManager, BufferPoolManagerRef, ReplacementPolicy, TableHeap};
+use catalog::Catalog;
+use common::{PageId, CATALOG_PAGE_ID, USABLE_PAGE_SIZE};
+use execution::{is_cancelled, PreparedStatement, QueryEngine};
 use std::{
+    collections::HashMap,
+    future::Future,
     io::{self, Write},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 use storage::disk::DiskManager;
-use tracing::{error, info, instrument, trace};
+use thiserror::Error;
+use tokio::sync::{Mutex, RwLock, RwLockReadGuard};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, trace, warn};
+use ty::DataType;
 use typed_builder::TypedBuilder;
 
+pub mod migrate;
 pub mod shell;
 
+pub use catalog::DatabaseError;
+pub use compile::parser::ParseError;
+pub use execution::{BoundStatement, QueryCancelled, QueryResult, RowSender};
+
+/// The error a SQL command fails with when it runs longer than its statement timeout.
+#[derive(Error, Debug)]
+#[error("Canceling statement due to statement timeout ({0:?})")]
+pub struct StatementTimeout(pub Duration);
+
+/// The error `CREATE TABLE` fails with when the encoded catalog would no longer fit in the
+/// catalog page.
+#[derive(Error, Debug)]
+#[error("The catalog of {0} bytes exceeds the {USABLE_PAGE_SIZE} bytes of the catalog page")]
+pub struct CatalogTooLarge(pub usize);
+
+/// The statements a client session (e.g. a connection of the server) prepared, by name.
+/// Statements are only visible to the session that prepared them, and must be released with
+/// [`Driver::deallocate_all`] when the session ends.
+#[derive(Debug, Default)]
+pub struct PreparedStatements {
+    statements: HashMap<String, PreparedStatement>,
+}
+
+impl PreparedStatements {
+    fn get(&self, name: &str) -> Result<&PreparedStatement> {
+        Ok(self
+            .statements
+            .get(name)
+            .ok_or_else(|| PreparedStatementError::DoesNotExist(name.to_string()))?)
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PreparedStatementError {
+    #[error("Prepared statement \"{0}\" already exists")]
+    AlreadyExists(String),
+
+    #[error("Prepared statement \"{0}\" does not exist")]
+    DoesNotExist(String),
+}
+
 #[instrument]
 async fn parse_query(query: &str) -> Ast {
     // Simulate query parsing
@@ -70,31 +123,59 @@ struct PhysicalPlan;
 pub type DriverRef = Arc<Driver>;
 #[derive(Debug, TypedBuilder)]
 pub struct Driver {
-    buffer_pool_manager: Arc<BufferPoolManager>,
+    buffer_pool_manager: BufferPoolManagerRef,
     disk_manager: Arc<DiskManager>,
     query_engine: QueryEngine,
+    /// Whether byte-oriented values are rendered as hex in result sets
+    #[builder(default)]
+    binary_output: AtomicBool,
+    /// Held exclusively by [`Driver::atomically`], and shared by the commands of every
+    /// other session
+    #[builder(default)]
+    exclusive: RwLock<()>,
+}
+
+tokio::task_local! {
+    /// Set while the commands of [`Driver::atomically`] run, which already hold the
+    /// exclusive lock
+    static ATOMICALLY: ();
 }
 
 impl Driver {
     // Create a new driver for a database stored in the specified path
-    pub fn new(path: &str) -> Result<Self> {
+    pub async fn new(path: &str) -> Result<Self> {
         trace!("Starting driver initialization");
         let disk_start = Instant::now();
         let disk_manager = Arc::new(DiskManager::new(path)?);
         info!("Disk manager initialized in {:?}", disk_start.elapsed());
 
         let buffer_start = Instant::now();
-        let buffer_pool_manager = Arc::new(BufferPoolManager::new_with_size(
-            ReplacementPolicy::LRU,
-            disk_manager.clone(),
-            100,
-        ));
+        let buffer_pool_manager =
+            BufferPoolManager::new_with_size(ReplacementPolicy::LRU, disk_manager.clone(), 100)?;
         info!(
             "Buffer pool manager initialized in {:?}",
             buffer_start.elapsed()
         );
 
+        // Redo changes that were logged but never written back before the last shutdown
+        let recovery_start = Instant::now();
+        let recovered = buffer_pool_manager.recover()?;
+        info!(
+            "Recovered {} log records in {:?}",
+            recovered,
+            recovery_start.elapsed()
+        );
+        let buffer_pool_manager = Arc::new(Mutex::new(buffer_pool_manager));
+
         let query_engine = QueryEngine::new();
+        query_engine.set_buffer_pool(Arc::clone(&buffer_pool_manager));
+        let catalog_start = Instant::now();
+        Self::load_catalog(&disk_manager, &buffer_pool_manager, &query_engine).await?;
+        info!(
+            "Loaded {} tables from the catalog in {:?}",
+            query_engine.catalog().table_names().len(),
+            catalog_start.elapsed()
+        );
 
         Ok(Driver::builder()
             .buffer_pool_manager(buffer_pool_manager)
@@ -103,12 +184,389 @@ impl Driver {
             .build())
     }
 
-    /// Process a SQL command
-    pub async fn process_sql_command(&self, command: &String) {
-        match self.query_engine.execute_query(&command).await {
-            Ok(_) => info!("Query executed successfully"),
-            Err(e) => error!("Failed to execute query: {:?}", e),
+    /// Registers the tables stored in the catalog page with the query engine, and makes the
+    /// engine write the catalog back to that page whenever `CREATE TABLE` or `DROP TABLE`
+    /// changes it. The catalog only records the first page of each heap, so the others are
+    /// found by following the links between them.
+    async fn load_catalog(
+        disk_manager: &Arc<DiskManager>,
+        buffer_pool_manager: &BufferPoolManagerRef,
+        query_engine: &QueryEngine,
+    ) -> Result<()> {
+        // Reserve the catalog page, so that no other page is ever allocated in its place
+        if disk_manager.num_pages() == 0 {
+            let page_id = disk_manager.allocate_page()?;
+            debug_assert_eq!(PageId(page_id), CATALOG_PAGE_ID);
+        }
+
+        let catalog_page = disk_manager.read_data(CATALOG_PAGE_ID.0)?;
+        let catalog = Self::decode_catalog(&catalog_page, buffer_pool_manager).await?;
+        query_engine.restore_tables(&catalog)?;
+
+        let disk_manager = Arc::clone(disk_manager);
+        query_engine.set_catalog_persister(move |catalog| {
+            let bytes = catalog.encode()?;
+            if bytes.len() > USABLE_PAGE_SIZE {
+                return Err(CatalogTooLarge(bytes.len()).into());
+            }
+            disk_manager.write_data(CATALOG_PAGE_ID.0, &bytes)?;
+            Ok(())
+        });
+        Ok(())
+    }
+
+    /// Decodes the catalog stored in `catalog_page`, finding the pages of each heap.
+    async fn decode_catalog(
+        catalog_page: &[u8],
+        buffer_pool_manager: &BufferPoolManagerRef,
+    ) -> Result<Catalog> {
+        let catalog = Catalog::decode(catalog_page)?;
+        let mut bpm = buffer_pool_manager.lock().await;
+        for name in catalog.table_names() {
+            let Some(table) = catalog.get_table(&name) else {
+                continue;
+            };
+            if let Some(&first_page) = table.heap_pages().first() {
+                let heap = TableHeap::open(&mut bpm, PageId(first_page)).await?;
+                table.set_heap_pages(heap.pages().iter().map(|page_id| page_id.0).collect());
+            }
+        }
+        Ok(catalog)
+    }
+
+    /// Writes every dirty page of the buffer pool to disk and checkpoints the write-ahead
+    /// log, so that nothing needs to be recovered on the next start.
+    pub async fn checkpoint(&self) -> Result<()> {
+        let _shared = self.shared().await;
+        let lsn = self.buffer_pool_manager.lock().await.checkpoint().await?;
+        info!("Checkpointed the buffer pool at LSN {}", lsn);
+        Ok(())
+    }
+
+    /// Returns the engine executing SQL commands.
+    pub fn query_engine(&self) -> &QueryEngine {
+        &self.query_engine
+    }
+
+    /// Returns the disk manager backing the database file.
+    pub fn disk_manager(&self) -> &Arc<DiskManager> {
+        &self.disk_manager
+    }
+
+    /// Sets whether byte-oriented values (e.g. `BLOB`s) are rendered as hex in the result
+    /// sets printed by [`Driver::process_sql_command`].
+    pub fn set_binary_output(&self, enabled: bool) {
+        self.binary_output.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns whether byte-oriented values are rendered as hex in printed result sets.
+    pub fn binary_output(&self) -> bool {
+        self.binary_output.load(Ordering::Relaxed)
+    }
+
+    /// Runs `commands`, undoing every change they made to the database if they fail.
+    ///
+    /// The changes are undone through a savepoint of the buffer pool, which records every
+    /// change made to the pool, so the commands of other sessions wait until `commands`
+    /// completes. The changes are only undone in memory: pages written back while `commands`
+    /// runs, like the catalog, are already on disk if the process crashes before it completes.
+    pub async fn atomically<T>(&self, commands: impl Future<Output = Result<T>>) -> Result<T> {
+        let _exclusive = self.exclusive.write().await;
+        // The catalog is written to its page directly rather than through the buffer pool
+        let catalog_page = self.disk_manager.read_data(CATALOG_PAGE_ID.0)?;
+        self.buffer_pool_manager.lock().await.set_savepoint()?;
+
+        // Boxed to keep the commands, which can be large futures, off the stack
+        match Box::pin(ATOMICALLY.scope((), commands)).await {
+            Ok(value) => {
+                self.buffer_pool_manager
+                    .lock()
+                    .await
+                    .release_savepoint()
+                    .await?;
+                Ok(value)
+            }
+            Err(e) => {
+                self.rollback(&catalog_page)
+                    .await
+                    .context("Failed to undo the changes of failed commands")?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Rolls the buffer pool back to its savepoint and the catalog back to `catalog_page`.
+    /// The tables of the engine are only replaced once the catalog has been restored.
+    async fn rollback(&self, catalog_page: &[u8]) -> Result<()> {
+        self.buffer_pool_manager
+            .lock()
+            .await
+            .rollback_to_savepoint()
+            .await?;
+        let catalog = Self::decode_catalog(catalog_page, &self.buffer_pool_manager).await?;
+        self.disk_manager
+            .write_data(CATALOG_PAGE_ID.0, catalog_page)?;
+        self.query_engine.replace_tables(&catalog)?;
+        Ok(())
+    }
+
+    /// Waits for the commands of [`Driver::atomically`] in any other session to complete, and
+    /// holds them back while the returned guard is alive.
+    async fn shared(&self) -> Option<RwLockReadGuard<'_, ()>> {
+        match ATOMICALLY.try_with(|_| ()) {
+            Ok(()) => None,
+            Err(_) => Some(self.exclusive.read().await),
+        }
+    }
+
+    /// Returns the names of all tables in the catalog, in alphabetical order.
+    pub fn list_tables(&self) -> Vec<String> {
+        self.query_engine.catalog().table_names()
+    }
+
+    /// Returns the names of the tables in the catalog matching the SQL `LIKE` pattern
+    /// `pattern` (`%` matches any sequence of characters, `_` any single character).
+    pub fn list_tables_like(&self, pattern: &str) -> Vec<String> {
+        self.query_engine.catalog().table_names_like(pattern)
+    }
+
+    /// Executes a SQL command that can be aborted by cancelling `token`, in which case it
+    /// fails with [`QueryCancelled`].
+    pub async fn execute_sql_command(
+        &self,
+        command: &str,
+        token: &CancellationToken,
+    ) -> Result<QueryResult> {
+        self.run_sql_command(command, token, None).await
+    }
+
+    /// Like [`Driver::execute_sql_command`], but sends every row to `rows` as soon as it is
+    /// produced, returning a result that only names the columns.
+    pub async fn execute_sql_command_streaming(
+        &self,
+        command: &str,
+        token: &CancellationToken,
+        rows: &RowSender,
+    ) -> Result<QueryResult> {
+        self.run_sql_command(command, token, Some(rows)).await
+    }
+
+    async fn run_sql_command(
+        &self,
+        command: &str,
+        token: &CancellationToken,
+        rows: Option<&RowSender>,
+    ) -> Result<QueryResult> {
+        let _shared = self.shared().await;
+        let result = match rows {
+            Some(rows) => {
+                self.query_engine
+                    .execute_query_streaming(command, token, rows)
+                    .await
+            }
+            None => {
+                self.query_engine
+                    .execute_query_with_cancellation(command, token)
+                    .await
+            }
+        };
+        result.map_err(|e| {
+            if is_cancelled(&e) {
+                QueryCancelled.into()
+            } else {
+                e.into()
+            }
+        })
+    }
+
+    /// Like [`Driver::execute_sql_command`], but cancels `token` once the command has run for
+    /// `timeout`, failing with [`StatementTimeout`].
+    pub async fn execute_sql_command_with_timeout(
+        &self,
+        command: &str,
+        token: &CancellationToken,
+        timeout: Duration,
+    ) -> Result<QueryResult> {
+        Self::with_statement_timeout(self.execute_sql_command(command, token), token, timeout).await
+    }
+
+    /// Runs the execution of a command, cancelling `token` once it has run for `timeout`, in
+    /// which case it fails with [`StatementTimeout`].
+    pub async fn with_statement_timeout<T>(
+        execution: impl Future<Output = Result<T>>,
+        token: &CancellationToken,
+        timeout: Duration,
+    ) -> Result<T> {
+        tokio::pin!(execution);
+
+        tokio::select! {
+            result = &mut execution => result,
+            _ = tokio::time::sleep(timeout) => {
+                warn!("Aborting command that exceeded the statement timeout of {:?}", timeout);
+                token.cancel();
+                // Wait for the command to observe the cancellation and release its resources
+                let _ = execution.await;
+                Err(StatementTimeout(timeout).into())
+            }
+        }
+    }
+
+    /// Plans `sql` once and stores it under `name` in `statements`, to be executed any number
+    /// of times by [`Driver::execute_prepared`].
+    pub async fn prepare(
+        &self,
+        statements: &mut PreparedStatements,
+        name: &str,
+        sql: &str,
+    ) -> Result<()> {
+        if statements.statements.contains_key(name) {
+            return Err(PreparedStatementError::AlreadyExists(name.to_string()).into());
+        }
+
+        let statement = self.query_engine.prepare(sql).await?;
+        debug!("Prepared statement \"{}\": {}", name, sql);
+        statements.statements.insert(name.to_string(), statement);
+        Ok(())
+    }
+
+    /// Executes the statement prepared under `name`, binding `params` to its parameters
+    /// (`$1`, `$2`, ...) in order.
+    pub async fn execute_prepared(
+        &self,
+        statements: &PreparedStatements,
+        name: &str,
+        params: Vec<DataType>,
+    ) -> Result<QueryResult> {
+        let statement = statements.get(name)?;
+        let _shared = self.shared().await;
+        Ok(self
+            .query_engine
+            .execute_prepared(statement, &params)
+            .await?)
+    }
+
+    /// Returns the names of the types the parameters of the statement prepared under `name`
+    /// were inferred to have, in order (`unknown` for parameters of no inferred type).
+    pub fn parameter_types(
+        &self,
+        statements: &PreparedStatements,
+        name: &str,
+    ) -> Result<Vec<String>> {
+        let statement = statements.get(name)?;
+        Ok(statement
+            .parameter_types()?
+            .into_iter()
+            .map(|data_type| data_type.map_or("unknown".to_string(), |ty| ty.to_string()))
+            .collect())
+    }
+
+    /// Binds `params` to the parameters of the statement prepared under `name`, to be executed
+    /// any number of times by [`Driver::execute_bound`].
+    pub fn bind(
+        &self,
+        statements: &PreparedStatements,
+        name: &str,
+        params: &[DataType],
+    ) -> Result<BoundStatement> {
+        let statement = statements.get(name)?;
+        Ok(self.query_engine.bind(statement, params)?)
+    }
+
+    /// Executes a statement bound by [`Driver::bind`].
+    pub async fn execute_bound(&self, statement: &BoundStatement) -> Result<QueryResult> {
+        let _shared = self.shared().await;
+        Ok(self.query_engine.execute_bound(statement).await?)
+    }
+
+    /// Like [`Driver::execute_bound`], but can be aborted by cancelling `token`, in which case
+    /// it fails with [`QueryCancelled`], and sends every row to `rows` as soon as it is
+    /// produced (see [`Driver::execute_sql_command_streaming`]).
+    pub async fn execute_bound_streaming(
+        &self,
+        statement: &BoundStatement,
+        token: &CancellationToken,
+        rows: &RowSender,
+    ) -> Result<QueryResult> {
+        let _shared = self.shared().await;
+        let result = self
+            .query_engine
+            .execute_bound_streaming(statement, token, rows)
+            .await;
+        result.map_err(|e| {
+            if is_cancelled(&e) {
+                QueryCancelled.into()
+            } else {
+                e.into()
+            }
+        })
+    }
+
+    /// Plans `sql` without executing it, returning its logical plan as an indented tree with
+    /// a line per plan node.
+    pub async fn explain(&self, sql: &str) -> Result<String> {
+        let statement = self.query_engine.prepare(sql).await?;
+        let plan = statement.plan().display_indent().to_string();
+        self.query_engine.deallocate(&statement)?;
+        Ok(plan)
+    }
+
+    /// Removes the statement prepared under `name`.
+    pub fn deallocate(&self, statements: &mut PreparedStatements, name: &str) -> Result<()> {
+        let statement = statements
+            .statements
+            .remove(name)
+            .ok_or_else(|| PreparedStatementError::DoesNotExist(name.to_string()))?;
+        Ok(self.query_engine.deallocate(&statement)?)
+    }
+
+    /// Removes every statement of a session, e.g. once its client disconnected.
+    pub fn deallocate_all(&self, statements: &mut PreparedStatements) -> Result<()> {
+        for (_, statement) in statements.statements.drain() {
+            self.query_engine.deallocate(&statement)?;
+        }
+        Ok(())
+    }
+
+    /// Executes the statements of a script (see [`compile::parser::split_statements`]) in
+    /// order, handing the result of each one to `on_result`. When the script has several
+    /// statements, failures name the (1-based) number of the failing statement.
+    ///
+    /// With `bail_on_error`, the script stops at the first failing statement, which it fails
+    /// with. Otherwise every statement is executed and failures are only handed to `on_result`.
+    pub async fn execute_script(
+        &self,
+        script: &str,
+        bail_on_error: bool,
+        mut on_result: impl FnMut(&Result<QueryResult>),
+    ) -> Result<()> {
+        let statements = compile::parser::split_statements(script);
+        let token = CancellationToken::new();
+        for (i, statement) in statements.iter().enumerate() {
+            let mut result = self.execute_sql_command(statement, &token).await;
+            if statements.len() > 1 {
+                result = result
+                    .with_context(|| format!("Statement {} of {} failed", i + 1, statements.len()));
+            }
+            on_result(&result);
+            if bail_on_error {
+                result?;
+            }
         }
+        Ok(())
+    }
+
+    /// Process the statements of a SQL command in order, printing the result set of each one
+    pub async fn process_sql_command(&self, command: &str) {
+        let binary_output = self.binary_output();
+        let _ = self
+            .execute_script(command, false, |result| match result {
+                Ok(result) => {
+                    shell::print_query_result(result, binary_output);
+                    info!("Query executed successfully");
+                }
+                Err(e) => error!("Failed to execute query: {:?}", e),
+            })
+            .await;
     }
 
     pub async fn start_shell(&self) {
@@ -132,3 +590,251 @@ impl Driver {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_created_tables_survive_reopening_the_database() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let db_path = db_path.to_str().unwrap();
+        let token = CancellationToken::new();
+
+        let driver = Driver::new(db_path).await.unwrap();
+        for sql in [
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name VARCHAR(64) NOT NULL)",
+            "CREATE TABLE orders (id INTEGER, user_id INTEGER)",
+            "DROP TABLE orders",
+            "INSERT INTO users VALUES (1, 'ada')",
+        ] {
+            driver.execute_sql_command(sql, &token).await.unwrap();
+        }
+        // Enough rows to spill into pages the catalog doesn't record
+        let values = (2..300)
+            .map(|id| format!("({}, 'user {}')", id, id))
+            .collect::<Vec<_>>()
+            .join(", ");
+        driver
+            .execute_sql_command(&format!("INSERT INTO users VALUES {}", values), &token)
+            .await
+            .unwrap();
+        let heap_pages = driver
+            .query_engine()
+            .catalog()
+            .get_table("users")
+            .unwrap()
+            .heap_pages();
+        assert!(heap_pages.len() > 1);
+        drop(driver);
+
+        let driver = Driver::new(db_path).await.unwrap();
+        assert_eq!(driver.list_tables(), ["users"]);
+        let users = driver.query_engine().catalog().get_table("users").unwrap();
+        assert_eq!(users.heap_pages(), heap_pages);
+        let schema = driver
+            .query_engine()
+            .catalog()
+            .table_schema("users")
+            .unwrap();
+        assert_eq!(schema.columns()[1].column_name(), "name");
+        assert!(!schema.columns()[1].is_nullable());
+        let result = driver
+            .execute_sql_command("SELECT name FROM users WHERE id = 1", &token)
+            .await
+            .unwrap();
+        assert_eq!(result.rows(), &[vec![DataType::VarChar("ada".to_string())]]);
+        let result = driver
+            .execute_sql_command("SELECT name FROM users", &token)
+            .await
+            .unwrap();
+        assert_eq!(result.rows().len(), 299);
+    }
+
+    #[tokio::test]
+    async fn test_other_sessions_wait_for_atomic_commands() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Arc::new(Driver::new(db_path.to_str().unwrap()).await.unwrap());
+        let token = CancellationToken::new();
+        driver
+            .execute_sql_command("CREATE TABLE users (id INTEGER)", &token)
+            .await
+            .unwrap();
+
+        let mut other_session = None;
+        let result: Result<()> = driver
+            .atomically(async {
+                driver
+                    .execute_sql_command("INSERT INTO users VALUES (1)", &token)
+                    .await?;
+                let other = tokio::spawn({
+                    let driver = Arc::clone(&driver);
+                    async move {
+                        let token = CancellationToken::new();
+                        driver
+                            .execute_sql_command("INSERT INTO users VALUES (2)", &token)
+                            .await
+                            .map(|_| ())
+                    }
+                });
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                assert!(!other.is_finished());
+                other_session = Some(other);
+                anyhow::bail!("the commands fail")
+            })
+            .await;
+        assert!(result.is_err());
+
+        // The insert of the other session ran after the rollback, so it was kept
+        other_session.unwrap().await.unwrap().unwrap();
+        let users = driver
+            .execute_sql_command("SELECT id FROM users", &token)
+            .await
+            .unwrap();
+        assert_eq!(users.rows(), &[vec![DataType::Integer(2)]]);
+    }
+
+    #[tokio::test]
+    async fn test_script_statements_are_executed_in_order() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Driver::new(db_path.to_str().unwrap()).await.unwrap();
+
+        let mut results = Vec::new();
+        driver
+            .execute_script(
+                "CREATE TABLE notes (body VARCHAR(16));
+                 INSERT INTO notes VALUES ('a;b');
+                 SELECT body FROM notes",
+                true,
+                |result| results.push(result.as_ref().map(|r| r.rows().clone()).unwrap()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[2], [vec![DataType::VarChar("a;b".to_string())]]);
+
+        // The failing statement is named, and only stops the script with `bail_on_error`
+        let script = "SELECT 1; SELECT * FROM missing; SELECT 3";
+        let mut failures = Vec::new();
+        let mut executed = 0;
+        let mut on_result = |result: &Result<QueryResult>| {
+            executed += 1;
+            failures.extend(result.as_ref().err().map(|e| e.to_string()));
+        };
+        driver
+            .execute_script(script, false, &mut on_result)
+            .await
+            .unwrap();
+        let err = driver
+            .execute_script(script, true, &mut on_result)
+            .await
+            .unwrap_err();
+        assert_eq!(executed, 3 + 2);
+        assert_eq!(failures, ["Statement 2 of 3 failed"; 2]);
+        assert_eq!(err.to_string(), "Statement 2 of 3 failed");
+    }
+
+    #[tokio::test]
+    async fn test_prepared_statement_is_executed_with_parameters() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("users.csv");
+        std::fs::write(&csv_path, "id,name\n1,ada\n2,grace\n3,barbara\n").unwrap();
+
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Driver::new(db_path.to_str().unwrap()).await.unwrap();
+        let mut statements = PreparedStatements::default();
+        let sql = format!("SELECT name FROM {} WHERE id > $1", csv_path.display());
+        driver
+            .prepare(&mut statements, "older_than", &sql)
+            .await
+            .unwrap();
+        let planned = driver.query_engine().statements_planned();
+
+        let names = |result: QueryResult| {
+            result
+                .rows()
+                .iter()
+                .map(|row| row[0].to_string())
+                .collect::<Vec<_>>()
+        };
+        let result = driver
+            .execute_prepared(&statements, "older_than", vec![DataType::Integer(1)])
+            .await
+            .unwrap();
+        assert_eq!(names(result), ["grace", "barbara"]);
+        let result = driver
+            .execute_prepared(&statements, "older_than", vec![DataType::BigInt(2)])
+            .await
+            .unwrap();
+        assert_eq!(names(result), ["barbara"]);
+        // Both executions reused the plan of the prepared statement
+        assert_eq!(driver.query_engine().statements_planned(), planned);
+
+        let err = driver
+            .prepare(&mut statements, "older_than", &sql)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<PreparedStatementError>(),
+            Some(&PreparedStatementError::AlreadyExists(
+                "older_than".to_string()
+            ))
+        );
+
+        // Statements are only visible to the session that prepared them
+        let mut other_statements = PreparedStatements::default();
+        driver
+            .prepare(&mut other_statements, "older_than", &sql)
+            .await
+            .unwrap();
+        driver.deallocate_all(&mut other_statements).unwrap();
+
+        driver.deallocate(&mut statements, "older_than").unwrap();
+        let err = driver
+            .execute_prepared(&statements, "older_than", vec![DataType::Integer(1)])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<PreparedStatementError>(),
+            Some(&PreparedStatementError::DoesNotExist(
+                "older_than".to_string()
+            ))
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_slow_command_is_aborted_by_statement_timeout() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("numbers.csv");
+        let rows = (0..100_000).map(|i| i.to_string()).collect::<Vec<_>>();
+        std::fs::write(&csv_path, format!("n\n{}\n", rows.join("\n"))).unwrap();
+
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Driver::new(db_path.to_str().unwrap()).await.unwrap();
+        // Takes a tenth of a second per batch, so scanning the whole file takes over a second
+        driver.query_engine().register_udf(
+            "crawl",
+            vec![arrow::datatypes::DataType::Int64],
+            arrow::datatypes::DataType::Int64,
+            |args| {
+                std::thread::sleep(Duration::from_millis(100));
+                Ok(args[0].clone())
+            },
+        );
+
+        let sql = format!("SELECT crawl(n) FROM {}", csv_path.display());
+        let token = CancellationToken::new();
+        let started_at = Instant::now();
+        let err = driver
+            .execute_sql_command_with_timeout(&sql, &token, Duration::from_millis(10))
+            .await
+            .unwrap_err();
+
+        assert!(err.is::<StatementTimeout>(), "unexpected error: {}", err);
+        assert!(token.is_cancelled());
+        assert!(started_at.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,478 @@
+//! # Migrations
+//!
+//! Migrations are numbered SQL scripts in a directory, applied to the database in order of
+//! their version. A migration is made of an up script named `<version>_<name>.up.sql` (or
+//! just `<version>_<name>.sql`) and an optional down script named `<version>_<name>.down.sql`
+//! that reverts it. The versions applied so far are tracked in the `schema_migrations` table
+//! of the database, which records every time a migration is applied or rolled back.
+//!
+//! Every statement of a script is parsed before any of them is executed, so that a script
+//! with a malformed statement aborts before running anything. A script then runs atomically
+//! along with recording its version (see [`Driver::atomically`]): if a statement fails,
+//! the changes of the statements before it are undone and the version is not recorded.
+
+use crate::Driver;
+use anyhow::{Context, Result};
+use compile::parser::split_statements;
+use execution::parse_error;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+use ty::DataType;
+
+/// The table recording the version and name of every migration applied or rolled back, in
+/// the order they were. A migration is applied if the latest row of its version says so.
+pub const SCHEMA_MIGRATIONS_TABLE: &str = "schema_migrations";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum MigrationError {
+    #[error("Invalid migration file name \"{0}\", expected <version>_<name>.up.sql")]
+    InvalidFileName(String),
+
+    #[error("Migration {0} is defined more than once")]
+    DuplicateVersion(u64),
+
+    #[error("Migration {0} has no up script")]
+    MissingUpScript(u64),
+
+    #[error("Migration {0} has been applied but is missing from the migrations directory")]
+    MissingMigration(u64),
+
+    #[error("Migration {0} has no down script and cannot be rolled back")]
+    Irreversible(u64),
+
+    #[error("Invalid row in the schema_migrations table: {0:?}")]
+    InvalidHistory(Vec<DataType>),
+}
+
+/// A migration read from the migrations directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    pub version: u64,
+    pub name: String,
+    pub up: String,
+    pub down: Option<String>,
+}
+
+/// Whether a migration of the migrations directory has been applied to the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub version: u64,
+    pub name: String,
+    pub applied: bool,
+}
+
+/// Applies and rolls back the migrations of a directory against the database of a driver.
+pub struct Migrator<'a> {
+    driver: &'a Driver,
+    migrations_dir: PathBuf,
+}
+
+impl<'a> Migrator<'a> {
+    pub fn new(driver: &'a Driver, migrations_dir: impl Into<PathBuf>) -> Self {
+        Migrator {
+            driver,
+            migrations_dir: migrations_dir.into(),
+        }
+    }
+
+    /// Applies every pending migration in order of version, returning the versions applied.
+    /// Stops at the first migration that fails, leaving the later ones pending.
+    pub async fn up(&self) -> Result<Vec<u64>> {
+        let applied = self.applied_migrations().await?;
+        let mut versions = Vec::new();
+        for migration in self.migrations()?.into_values() {
+            if applied.contains_key(&migration.version) {
+                continue;
+            }
+
+            info!(
+                "Applying migration {} ({})",
+                migration.version, migration.name
+            );
+            self.driver
+                .atomically(async {
+                    self.run_script(&migration.up).await?;
+                    self.record(migration.version, &migration.name, true).await
+                })
+                .await
+                .with_context(|| format!("Failed to apply migration {}", migration.version))?;
+            versions.push(migration.version);
+        }
+        Ok(versions)
+    }
+
+    /// Rolls back the latest applied migration, returning its version, or `None` if no
+    /// migration has been applied.
+    pub async fn down(&self) -> Result<Option<u64>> {
+        let Some((version, _)) = self.applied_migrations().await?.pop_last() else {
+            return Ok(None);
+        };
+        let migration = self
+            .migrations()?
+            .remove(&version)
+            .ok_or(MigrationError::MissingMigration(version))?;
+        let down = migration
+            .down
+            .as_ref()
+            .ok_or(MigrationError::Irreversible(version))?;
+
+        info!("Rolling back migration {} ({})", version, migration.name);
+        self.driver
+            .atomically(async {
+                self.run_script(down).await?;
+                self.record(version, &migration.name, false).await
+            })
+            .await
+            .with_context(|| format!("Failed to roll back migration {}", version))?;
+        Ok(Some(version))
+    }
+
+    /// Returns every migration of the migrations directory in order of version, along with
+    /// whether it has been applied.
+    pub async fn status(&self) -> Result<Vec<MigrationStatus>> {
+        let applied = self.applied_migrations().await?;
+        Ok(self
+            .migrations()?
+            .into_values()
+            .map(|migration| MigrationStatus {
+                applied: applied.contains_key(&migration.version),
+                version: migration.version,
+                name: migration.name,
+            })
+            .collect())
+    }
+
+    /// Reads the migrations of the migrations directory, by version.
+    pub fn migrations(&self) -> Result<BTreeMap<u64, Migration>> {
+        let mut ups = BTreeMap::new();
+        let mut downs = BTreeMap::new();
+        for entry in fs::read_dir(&self.migrations_dir).with_context(|| {
+            format!(
+                "Failed to read migrations directory {}",
+                self.migrations_dir.display()
+            )
+        })? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("sql") {
+                continue;
+            }
+
+            let (version, name, is_down) = parse_file_name(&path)?;
+            let scripts = if is_down { &mut downs } else { &mut ups };
+            let script = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read migration {}", path.display()))?;
+            if scripts.insert(version, (name, script)).is_some() {
+                return Err(MigrationError::DuplicateVersion(version).into());
+            }
+        }
+
+        if let Some(&version) = downs.keys().find(|version| !ups.contains_key(version)) {
+            return Err(MigrationError::MissingUpScript(version).into());
+        }
+
+        Ok(ups
+            .into_iter()
+            .map(|(version, (name, up))| {
+                let down = downs.remove(&version).map(|(_, down)| down);
+                (
+                    version,
+                    Migration {
+                        version,
+                        name,
+                        up,
+                        down,
+                    },
+                )
+            })
+            .collect())
+    }
+
+    /// Returns the names of the migrations applied to the database, by version, as recorded
+    /// in the [`SCHEMA_MIGRATIONS_TABLE`].
+    pub async fn applied_migrations(&self) -> Result<BTreeMap<u64, String>> {
+        let mut applied = BTreeMap::new();
+        if !self
+            .driver
+            .list_tables()
+            .iter()
+            .any(|table| table == SCHEMA_MIGRATIONS_TABLE)
+        {
+            return Ok(applied);
+        }
+
+        let history = self
+            .driver
+            .execute_sql_command(
+                &format!(
+                    "SELECT version, name, applied FROM {}",
+                    SCHEMA_MIGRATIONS_TABLE
+                ),
+                &CancellationToken::new(),
+            )
+            .await?;
+        // Rows are scanned in the order they were inserted, so later rows override earlier ones
+        for row in history.rows() {
+            match row.as_slice() {
+                [DataType::BigInt(version), name, DataType::Boolean(true)] => {
+                    applied.insert(*version as u64, name.to_string());
+                }
+                [DataType::BigInt(version), _, DataType::Boolean(false)] => {
+                    applied.remove(&(*version as u64));
+                }
+                row => return Err(MigrationError::InvalidHistory(row.to_vec()).into()),
+            }
+        }
+        Ok(applied)
+    }
+
+    /// Records that a migration was applied, or rolled back, in the
+    /// [`SCHEMA_MIGRATIONS_TABLE`], creating the table on first use.
+    async fn record(&self, version: u64, name: &str, applied: bool) -> Result<()> {
+        let token = CancellationToken::new();
+        self.driver
+            .execute_sql_command(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS {} \
+                     (version BIGINT NOT NULL, name VARCHAR(255) NOT NULL, applied BOOLEAN NOT NULL)",
+                    SCHEMA_MIGRATIONS_TABLE
+                ),
+                &token,
+            )
+            .await?;
+        self.driver
+            .execute_sql_command(
+                &format!(
+                    "INSERT INTO {} VALUES ({}, '{}', {})",
+                    SCHEMA_MIGRATIONS_TABLE,
+                    version,
+                    name.replace('\'', "''"),
+                    applied
+                ),
+                &token,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Parses every statement of `script`, then executes them in order.
+    async fn run_script(&self, script: &str) -> Result<()> {
+        let statements = split_statements(script);
+        let engine = self.driver.query_engine();
+        for statement in &statements {
+            match engine.prepare(statement).await {
+                Ok(prepared) => engine.deallocate(&prepared)?,
+                // Statements that can't be planned ahead (e.g. inserts, or queries of tables
+                // created by earlier statements) fail as they are executed instead
+                Err(e) if parse_error(&e).is_none() => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let token = CancellationToken::new();
+        for statement in &statements {
+            debug!("Executing migration statement: {}", statement);
+            self.driver.execute_sql_command(statement, &token).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits a migration file name into its version, name and whether it is a down script.
+fn parse_file_name(path: &Path) -> Result<(u64, String, bool)> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let invalid = || MigrationError::InvalidFileName(file_name.clone());
+
+    let stem = file_name.strip_suffix(".sql").ok_or_else(invalid)?;
+    let (stem, is_down) = match stem.strip_suffix(".down") {
+        Some(stem) => (stem, true),
+        None => (stem.strip_suffix(".up").unwrap_or(stem), false),
+    };
+    let (version, name) = stem.split_once('_').ok_or_else(invalid)?;
+    let version = version.parse().map_err(|_| invalid())?;
+    Ok((version, name.to_string(), is_down))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    /// Creates a driver with a `tick` function counting the rows it is called on, and a
+    /// migrations directory with a file of numbers to call it on.
+    async fn setup() -> (tempfile::TempDir, Driver, Arc<AtomicUsize>, String) {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("numbers.csv");
+        fs::write(&csv_path, "n\n1\n2\n").unwrap();
+        fs::create_dir(temp_dir.path().join("migrations")).unwrap();
+
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Driver::new(db_path.to_str().unwrap()).await.unwrap();
+        let ticks = Arc::new(AtomicUsize::new(0));
+        driver.query_engine().register_udf(
+            "tick",
+            vec![arrow::datatypes::DataType::Int64],
+            arrow::datatypes::DataType::Int64,
+            {
+                let ticks = ticks.clone();
+                move |args| {
+                    ticks.fetch_add(args[0].len(), Ordering::Relaxed);
+                    Ok(args[0].clone())
+                }
+            },
+        );
+
+        let query = format!("SELECT tick(n) FROM {}", csv_path.display());
+        (temp_dir, driver, ticks, query)
+    }
+
+    fn write_migration(temp_dir: &tempfile::TempDir, file_name: &str, script: &str) {
+        fs::write(temp_dir.path().join("migrations").join(file_name), script).unwrap();
+    }
+
+    async fn applied(migrator: &Migrator<'_>) -> Vec<(u64, bool)> {
+        migrator
+            .status()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|status| (status.version, status.applied))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_migrations_are_applied_and_rolled_back_in_order() {
+        let (temp_dir, driver, ticks, query) = setup().await;
+        write_migration(&temp_dir, "001_first.up.sql", &format!("{};", query));
+        write_migration(&temp_dir, "001_first.down.sql", &format!("{};", query));
+        write_migration(
+            &temp_dir,
+            "002_second.up.sql",
+            &format!("-- Runs twice; once here\n{};\n{};\n", query, query),
+        );
+        write_migration(&temp_dir, "002_second.down.sql", &query);
+        let migrator = Migrator::new(&driver, temp_dir.path().join("migrations"));
+
+        assert_eq!(applied(&migrator).await, [(1, false), (2, false)]);
+        assert_eq!(migrator.up().await.unwrap(), [1, 2]);
+        assert_eq!(ticks.load(Ordering::Relaxed), 6);
+        assert_eq!(applied(&migrator).await, [(1, true), (2, true)]);
+        assert!(driver
+            .list_tables()
+            .contains(&"schema_migrations".to_string()));
+
+        // Applied migrations are not applied again
+        assert!(migrator.up().await.unwrap().is_empty());
+        assert_eq!(ticks.load(Ordering::Relaxed), 6);
+
+        assert_eq!(migrator.down().await.unwrap(), Some(2));
+        assert_eq!(ticks.load(Ordering::Relaxed), 8);
+        assert_eq!(applied(&migrator).await, [(1, true), (2, false)]);
+
+        assert_eq!(migrator.down().await.unwrap(), Some(1));
+        assert_eq!(migrator.down().await.unwrap(), None);
+        assert_eq!(applied(&migrator).await, [(1, false), (2, false)]);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_migration_aborts_without_being_recorded() {
+        let (temp_dir, driver, ticks, query) = setup().await;
+        write_migration(&temp_dir, "1_first.sql", &query);
+        write_migration(
+            &temp_dir,
+            "2_broken.sql",
+            &format!("{};\nSELEC * FORM users;", query),
+        );
+        write_migration(&temp_dir, "3_third.sql", &query);
+        let migrator = Migrator::new(&driver, temp_dir.path().join("migrations"));
+
+        assert!(migrator.up().await.is_err());
+        // The statement before the malformed one was never executed
+        assert_eq!(ticks.load(Ordering::Relaxed), 2);
+        assert_eq!(
+            applied(&migrator).await,
+            [(1, true), (2, false), (3, false)]
+        );
+
+        // Without a down script the first migration cannot be rolled back
+        let err = migrator.down().await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<MigrationError>(),
+            Some(&MigrationError::Irreversible(1))
+        );
+        assert_eq!(
+            applied(&migrator).await,
+            [(1, true), (2, false), (3, false)]
+        );
+    }
+
+    async fn user_count(driver: &Driver) -> usize {
+        driver
+            .execute_sql_command("SELECT id FROM users", &CancellationToken::new())
+            .await
+            .unwrap()
+            .row_count()
+    }
+
+    #[tokio::test]
+    async fn test_failed_migration_is_undone_and_history_survives_reopening() {
+        let (temp_dir, driver, _, _) = setup().await;
+        write_migration(
+            &temp_dir,
+            "1_users.sql",
+            "CREATE TABLE users (id INTEGER NOT NULL);\nINSERT INTO users VALUES (1);",
+        );
+        // The last insert fails once the others have been executed
+        write_migration(
+            &temp_dir,
+            "2_notes.sql",
+            "CREATE TABLE notes (id INTEGER);
+             INSERT INTO users VALUES (2);
+             INSERT INTO users VALUES (NULL);",
+        );
+        let migrator = Migrator::new(&driver, temp_dir.path().join("migrations"));
+        assert!(migrator.up().await.is_err());
+
+        // Nothing the failed migration did is left behind
+        assert_eq!(driver.list_tables(), ["schema_migrations", "users"]);
+        assert_eq!(user_count(&driver).await, 1);
+        assert_eq!(applied(&migrator).await, [(1, true), (2, false)]);
+
+        // The history is stored in the database, along with the tables
+        drop(migrator);
+        drop(driver);
+        let driver = Driver::new(temp_dir.path().join("test.db").to_str().unwrap())
+            .await
+            .unwrap();
+        let migrator = Migrator::new(&driver, temp_dir.path().join("migrations"));
+        assert_eq!(applied(&migrator).await, [(1, true), (2, false)]);
+        assert_eq!(user_count(&driver).await, 1);
+    }
+
+    #[test]
+    fn test_file_names_are_parsed() {
+        let parse = |name: &str| parse_file_name(Path::new(name)).ok();
+        assert_eq!(
+            parse("001_create_users.up.sql"),
+            Some((1, "create_users".to_string(), false))
+        );
+        assert_eq!(
+            parse("20240101_add_index.down.sql"),
+            Some((20240101, "add_index".to_string(), true))
+        );
+        assert_eq!(parse("7_seed.sql"), Some((7, "seed".to_string(), false)));
+        assert_eq!(parse("create_users.sql"), None);
+        assert_eq!(parse("v1_create_users.sql"), None);
+    }
+}
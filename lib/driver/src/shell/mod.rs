@@ -1,13 +1,20 @@
 use self::{highlighter::SqlHighlighter, prompt::SqlPrompt};
-use crate::DriverRef;
+use crate::{DriverRef, QueryResult};
 use anyhow::Result;
 use nu_ansi_term::{Color, Style};
+pub use output::{render_query_result, OutputMode};
 use owo_colors::OwoColorize;
 use prettytable::{row, Table};
 use reedline::{DefaultHinter, DefaultPrompt, FileBackedHistory, Reedline, Signal};
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+use tracing::{error, info};
 use typed_builder::TypedBuilder;
 
 mod highlighter;
+mod output;
 mod prompt;
 
 #[derive(TypedBuilder)]
@@ -16,6 +23,10 @@ pub struct Shell {
     prompt: SqlPrompt,
     line_editor: Reedline,
     bail_on_error: bool,
+    /// Whether the run time of every statement is reported after its result set
+    timer: bool,
+    output_mode: OutputMode,
+    headers: bool,
 }
 
 impl Shell {
@@ -37,6 +48,9 @@ impl Shell {
             .prompt(prompt)
             .line_editor(line_editor)
             .bail_on_error(false)
+            .timer(false)
+            .output_mode(OutputMode::default())
+            .headers(true)
             .build()
     }
 
@@ -51,7 +65,7 @@ impl Shell {
                         continue;
                     }
 
-                    if let Err(e) = self.process_command(buffer.trim()).await {
+                    if let Err(e) = self.process_command(buffer.trim(), &mut io::stdout()).await {
                         if self.bail_on_error {
                             return Err(e);
                         }
@@ -66,16 +80,76 @@ impl Shell {
         Ok(())
     }
 
-    async fn process_command(&mut self, command: &str) -> Result<()> {
-        if command.starts_with('.') {
-            self.handle_dot_command(command)?;
-        } else {
-            self.driver.process_sql_command(&command.to_string()).await;
+    async fn process_command(&mut self, command: &str, out: &mut impl Write) -> Result<()> {
+        match command.split_once(char::is_whitespace) {
+            Some((".explain", query)) => self.explain(query.trim(), out).await?,
+            _ if command.starts_with('.') => self.handle_dot_command(command)?,
+            _ => self.process_sql_command(command, out).await?,
         }
 
         Ok(())
     }
 
+    /// Executes the statements of a SQL command in order, writing the result set of each one
+    /// to `out` in the current output mode, followed by its run time with `.timer on`. With
+    /// `.bail on`, the first failing statement stops the command and fails it.
+    async fn process_sql_command(&self, command: &str, out: &mut impl Write) -> Result<()> {
+        let mut written = Ok(());
+        let mut start = Instant::now();
+        let executed = self
+            .driver
+            .execute_script(command, self.bail_on_error, |result| {
+                let elapsed = start.elapsed();
+                if written.is_ok() {
+                    written = self.write_statement_result(out, result, elapsed);
+                }
+                start = Instant::now();
+            })
+            .await;
+
+        written?;
+        executed
+    }
+
+    fn write_statement_result(
+        &self,
+        out: &mut impl Write,
+        result: &Result<QueryResult>,
+        elapsed: Duration,
+    ) -> io::Result<()> {
+        match result {
+            Ok(result) => {
+                write!(out, "{}", self.render_query_result(result))?;
+                info!("Query executed successfully");
+            }
+            Err(e) => error!("Failed to execute query: {:?}", e),
+        }
+
+        if self.timer {
+            let rows = result.as_ref().ok().map(|result| result.rows().len());
+            writeln!(out, "{}", run_time(elapsed, rows))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the logical plan of a query to `out` without executing it.
+    async fn explain(&self, query: &str, out: &mut impl Write) -> io::Result<()> {
+        match self.driver.explain(query).await {
+            Ok(plan) => writeln!(out, "{}", plan)?,
+            Err(e) => error!("Failed to explain query: {:?}", e),
+        }
+        Ok(())
+    }
+
+    fn render_query_result(&self, result: &QueryResult) -> String {
+        render_query_result(
+            result,
+            self.output_mode,
+            self.headers,
+            self.driver.binary_output(),
+        )
+    }
+
     fn handle_dot_command(&mut self, command: &str) -> Result<()> {
         match command.split_whitespace().collect::<Vec<&str>>().as_slice() {
             [".bail"] => {
@@ -101,20 +175,74 @@ impl Shell {
                 self.bail_on_error = false;
                 Ok(())
             }
+            [".binary"] => {
+                println!(
+                    "{}",
+                    format!(
+                        "Binary output mode is {}",
+                        if self.driver.binary_output() {
+                            "on".green().to_string()
+                        } else {
+                            "off".red().to_string()
+                        }
+                    )
+                    .purple()
+                );
+                Ok(())
+            }
             [".binary", "on"] => {
-                todo!("Add binary output");
-                // self.driver.set_binary_output(true);
-                // Ok(())
+                self.driver.set_binary_output(true);
+                Ok(())
             }
             [".binary", "off"] => {
-                todo!("Add binary output");
-                // self.driver.set_binary_output(false);
-                // Ok(())
+                self.driver.set_binary_output(false);
+                Ok(())
             }
             [".exit"] => {
                 println!("Goodbye!");
                 std::process::exit(0);
             }
+            [".headers"] => {
+                println!(
+                    "{}",
+                    format!(
+                        "Headers are {}",
+                        if self.headers {
+                            "on".green().to_string()
+                        } else {
+                            "off".red().to_string()
+                        }
+                    )
+                    .purple()
+                );
+                Ok(())
+            }
+            [".headers", "on"] => {
+                self.headers = true;
+                Ok(())
+            }
+            [".headers", "off"] => {
+                self.headers = false;
+                Ok(())
+            }
+            [".mode"] => {
+                println!(
+                    "{}",
+                    format!("Output mode is {}", self.output_mode.green()).purple()
+                );
+                Ok(())
+            }
+            [".mode", mode] => {
+                match mode.parse() {
+                    Ok(mode) => self.output_mode = mode,
+                    Err(e) => println!("{}", e.red()),
+                }
+                Ok(())
+            }
+            [".explain"] => {
+                println!("{}", "Usage: .explain <query>".red());
+                Ok(())
+            }
             [".exit", code] => {
                 println!("Goodbye!");
                 std::process::exit(code.parse::<i32>().unwrap_or(0));
@@ -127,15 +255,36 @@ impl Shell {
                 println!("Goodbye!");
                 std::process::exit(0);
             }
+            [".timer"] => {
+                println!(
+                    "{}",
+                    format!(
+                        "Timer is {}",
+                        if self.timer {
+                            "on".green().to_string()
+                        } else {
+                            "off".red().to_string()
+                        }
+                    )
+                    .purple()
+                );
+                Ok(())
+            }
+            [".timer", "on"] => {
+                self.timer = true;
+                Ok(())
+            }
+            [".timer", "off"] => {
+                self.timer = false;
+                Ok(())
+            }
             [".tables"] => {
-                todo!("Add table listing");
-                // self.driver.show_tables();
-                // Ok(())
+                tables_table(&self.driver.list_tables()).printstd();
+                Ok(())
             }
             [".tables", table] => {
-                todo!("Add table listing");
-                // self.driver.show_tables_like(table);
-                // Ok(())
+                tables_table(&self.driver.list_tables_like(table)).printstd();
+                Ok(())
             }
             [".vfslist"] => {
                 todo!("Add VFS listing");
@@ -206,14 +355,193 @@ impl Shell {
             ".exit [CODE]",
             "Exit this program with return-code [CODE]"
         ]);
+        table.add_row(row![
+            ".explain QUERY",
+            "Show the logical plan of QUERY without running it"
+        ]);
+        table.add_row(row![".headers on|off", "Turn display of headers on or off"]);
         table.add_row(row![".help", "Show this help information"]);
+        table.add_row(row![".mode MODE", "Set output mode (table, csv or json)"]);
         table.add_row(row![".quit", "Exit this program (with return-code 0)"]);
         table.add_row(row![
             ".tables [TABLE]",
             "List names of tables matching LIKE pattern [TABLE]"
         ]);
+        table.add_row(row![
+            ".timer on|off",
+            "Turn the timing of statements on or off"
+        ]);
         table.add_row(row![".vfslist", "List all available VFSes"]);
 
         table.printstd();
     }
 }
+
+/// Builds the table listing the names of tables for the `.tables` dot command.
+fn tables_table(names: &[String]) -> Table {
+    let mut table = Table::new();
+    table.set_format(*prettytable::format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(row!["Table"]);
+
+    for name in names {
+        table.add_row(row![name]);
+    }
+
+    table
+}
+
+/// Formats the run time of a statement reported with `.timer on`, with the number of rows it
+/// returned if it succeeded.
+fn run_time(elapsed: Duration, rows: Option<usize>) -> String {
+    match rows {
+        Some(rows) => format!(
+            "Run Time: real {:.3}s, rows: {}",
+            elapsed.as_secs_f64(),
+            rows
+        ),
+        None => format!("Run Time: real {:.3}s", elapsed.as_secs_f64()),
+    }
+}
+
+/// Prints the rows of a query result as a table titled with its column names. With
+/// `binary_output`, byte-oriented values are rendered as hex.
+pub fn print_query_result(result: &QueryResult, binary_output: bool) {
+    print!(
+        "{}",
+        render_query_result(result, OutputMode::Table, true, binary_output)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Driver;
+    use catalog::{schema::Schema, Column};
+    use output::result_table;
+    use std::sync::Arc;
+    use ty::{DataType, DataTypeKind};
+
+    async fn driver_with_tables(names: &[&str]) -> (tempfile::TempDir, Driver) {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Driver::new(db_path.to_str().unwrap()).await.unwrap();
+
+        let schema = Arc::new(Schema::new(vec![Column::new_fixed(
+            "id",
+            DataTypeKind::Integer,
+        )
+        .unwrap()]));
+        for name in names {
+            driver
+                .query_engine()
+                .register_table_schema(name, Arc::clone(&schema));
+        }
+        (temp_dir, driver)
+    }
+
+    fn shell(driver: Driver) -> Shell {
+        Shell::builder()
+            .driver(Arc::new(driver))
+            .prompt(SqlPrompt::default())
+            .line_editor(Reedline::create())
+            .bail_on_error(false)
+            .timer(false)
+            .output_mode(OutputMode::Csv)
+            .headers(false)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_timer_reports_the_run_time_of_every_statement() {
+        let (_temp_dir, driver) = driver_with_tables(&[]).await;
+        let mut shell = shell(driver);
+        async fn run(shell: &Shell) -> String {
+            let mut out = Vec::new();
+            shell
+                .process_sql_command("SELECT 1; SELECT * FROM missing", &mut out)
+                .await
+                .unwrap();
+            String::from_utf8(out).unwrap()
+        }
+
+        assert!(!run(&shell).await.contains("Run Time"));
+
+        shell.handle_dot_command(".timer on").unwrap();
+        let output = run(&shell).await;
+        let timings = output
+            .lines()
+            .filter(|line| line.starts_with("Run Time: real "))
+            .collect::<Vec<_>>();
+        assert_eq!(timings.len(), 2, "{}", output);
+        assert!(timings[0].ends_with("s, rows: 1"), "{}", output);
+        // A failed statement is timed too, but returned no rows
+        assert!(timings[1].ends_with('s'), "{}", output);
+
+        shell.handle_dot_command(".timer off").unwrap();
+        assert!(!run(&shell).await.contains("Run Time"));
+    }
+
+    #[test]
+    fn test_run_time_is_reported_in_seconds() {
+        assert_eq!(
+            run_time(Duration::from_micros(13_400), Some(3)),
+            "Run Time: real 0.013s, rows: 3"
+        );
+        assert_eq!(
+            run_time(Duration::from_secs(2), None),
+            "Run Time: real 2.000s"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tables_lists_every_table() {
+        let (_temp_dir, driver) = driver_with_tables(&["users", "orders"]).await;
+
+        let listing = tables_table(&driver.list_tables()).to_string();
+        assert!(listing.contains("users"), "{}", listing);
+        assert!(listing.contains("orders"), "{}", listing);
+    }
+
+    #[tokio::test]
+    async fn test_binary_output_renders_blobs_as_hex() {
+        let (_temp_dir, driver) = driver_with_tables(&[]).await;
+        let result = QueryResult::new(
+            vec!["data".to_string()],
+            vec![vec![DataType::Blob(vec![0x01, 0xab, 0xff])]],
+        );
+        let rendered = || result_table(&result, true, driver.binary_output()).to_string();
+
+        assert!(!driver.binary_output());
+        assert!(rendered().contains("[1, 171, 255]"), "{}", rendered());
+
+        driver.set_binary_output(true);
+        assert!(rendered().contains("\\x01abff"), "{}", rendered());
+
+        driver.set_binary_output(false);
+        assert!(rendered().contains("[1, 171, 255]"), "{}", rendered());
+    }
+
+    #[tokio::test]
+    async fn test_explain_prints_the_plan_without_executing() {
+        let (_temp_dir, driver) = driver_with_tables(&["t"]).await;
+        let mut shell = shell(driver);
+        async fn run(shell: &mut Shell, command: &str) -> String {
+            let mut out = Vec::new();
+            shell.process_command(command, &mut out).await.unwrap();
+            String::from_utf8(out).unwrap()
+        }
+
+        let output = run(&mut shell, ".explain SELECT * FROM t").await;
+        assert!(output.contains("TableScan: t"), "{}", output);
+        assert_eq!(run(&mut shell, ".explain SELECT * FROM missing").await, "");
+    }
+
+    #[tokio::test]
+    async fn test_tables_filters_by_like_pattern() {
+        let (_temp_dir, driver) = driver_with_tables(&["users", "user_roles", "orders"]).await;
+
+        assert_eq!(driver.list_tables_like("user%"), ["user_roles", "users"]);
+        assert_eq!(driver.list_tables_like("_rders"), ["orders"]);
+        assert!(driver.list_tables_like("accounts").is_empty());
+    }
+}
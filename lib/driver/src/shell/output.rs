@@ -0,0 +1,248 @@
+//! # Output Modes
+//!
+//! Renders the result sets of queries for the shell, either as a table for reading or as
+//! CSV (per RFC 4180) or JSON for scripting and piping. The mode is chosen with the `.mode`
+//! dot command, and whether column names are included with `.headers`.
+
+use crate::QueryResult;
+use core::fmt;
+use prettytable::{Row, Table};
+use std::str::FromStr;
+use ty::DataType;
+
+/// How the result sets of queries are rendered.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// A table titled with the column names, followed by the number of rows
+    #[default]
+    Table,
+    /// A line of comma-separated fields per row, after a line of column names
+    Csv,
+    /// An array with an object per row, keyed by column name
+    Json,
+}
+
+impl FromStr for OutputMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(OutputMode::Table),
+            "csv" => Ok(OutputMode::Csv),
+            "json" => Ok(OutputMode::Json),
+            _ => Err(format!("Unknown output mode `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for OutputMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputMode::Table => write!(f, "table"),
+            OutputMode::Csv => write!(f, "csv"),
+            OutputMode::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// Renders a result set in the given mode, including the column names if `headers` is set
+/// (JSON objects are always keyed by column name). With `binary_output`, byte-oriented
+/// values are rendered as hex.
+pub fn render_query_result(
+    result: &QueryResult,
+    mode: OutputMode,
+    headers: bool,
+    binary_output: bool,
+) -> String {
+    match mode {
+        OutputMode::Table => format!(
+            "{}({} rows)\n",
+            result_table(result, headers, binary_output),
+            result.row_count()
+        ),
+        OutputMode::Csv => result_csv(result, headers, binary_output),
+        OutputMode::Json => result_json(result, binary_output),
+    }
+}
+
+pub(super) fn result_table(result: &QueryResult, headers: bool, binary_output: bool) -> Table {
+    let mut table = Table::new();
+    table.set_format(*prettytable::format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    if headers {
+        table.set_titles(Row::from(result.columns()));
+    }
+
+    for row in result.rows() {
+        table.add_row(Row::from(
+            row.iter().map(|value| format_value(value, binary_output)),
+        ));
+    }
+
+    table
+}
+
+/// Renders a result set as CSV records terminated by CRLF. `NULL`s are rendered as empty
+/// fields.
+fn result_csv(result: &QueryResult, headers: bool, binary_output: bool) -> String {
+    let mut csv = String::new();
+    let mut push_record = |fields: Vec<String>| {
+        let fields: Vec<_> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push_str("\r\n");
+    };
+
+    if headers {
+        push_record(result.columns().clone());
+    }
+    for row in result.rows() {
+        push_record(
+            row.iter()
+                .map(|value| match value {
+                    DataType::Null => String::new(),
+                    value => format_value(value, binary_output),
+                })
+                .collect(),
+        );
+    }
+    csv
+}
+
+/// Quotes a CSV field if it contains a comma, a quote or a line break, doubling its quotes.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Renders a result set as a JSON array of objects whose keys follow the column order.
+fn result_json(result: &QueryResult, binary_output: bool) -> String {
+    let objects: Vec<String> = result
+        .rows()
+        .iter()
+        .map(|row| {
+            let members: Vec<String> = result
+                .columns()
+                .iter()
+                .zip(row)
+                .map(|(column, value)| {
+                    format!(
+                        "{}:{}",
+                        serde_json::Value::from(column.as_str()),
+                        json_value(value, binary_output)
+                    )
+                })
+                .collect();
+            format!("{{{}}}", members.join(","))
+        })
+        .collect();
+    format!("[{}]\n", objects.join(",\n"))
+}
+
+/// Maps a value to the closest JSON type: numbers (other than NaN and infinities) and
+/// booleans map to themselves, `NULL` to `null`, JSON values are embedded as is and
+/// everything else is rendered as a string.
+fn json_value(value: &DataType, binary_output: bool) -> serde_json::Value {
+    let float = |val: f64| {
+        serde_json::Number::from_f64(val)
+            .map(serde_json::Value::Number)
+            .unwrap_or_else(|| serde_json::Value::String(val.to_string()))
+    };
+
+    match value {
+        DataType::Null => serde_json::Value::Null,
+        DataType::Boolean(val) => (*val).into(),
+        DataType::SmallInt(val) | DataType::SmallSerial(val) => (*val).into(),
+        DataType::Integer(val) | DataType::Serial(val) => (*val).into(),
+        DataType::BigInt(val) | DataType::BigSerial(val) => (*val).into(),
+        DataType::Real(val) => float(*val as f64),
+        DataType::DoublePrecision(val) | DataType::Float(val) => float(*val),
+        // Kept exact rather than rounded to the nearest float
+        DataType::Decimal(val) => serde_json::Number::from_str(&val.to_string())
+            .map(serde_json::Value::Number)
+            .unwrap_or_else(|_| val.to_string().into()),
+        DataType::Json(val) => val.clone(),
+        value => format_value(value, binary_output).into(),
+    }
+}
+
+/// Renders a value for display, with `BLOB`s in the `\x`-prefixed hex format of `bytea`
+/// output if `binary_output` is set.
+pub(super) fn format_value(value: &DataType, binary_output: bool) -> String {
+    match value {
+        DataType::Blob(bytes) if binary_output => {
+            let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            format!("\\x{}", hex)
+        }
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A result set with a comma embedded in a value and a `NULL`.
+    fn result() -> QueryResult {
+        QueryResult::new(
+            vec!["id".to_string(), "name".to_string()],
+            vec![
+                vec![
+                    DataType::Integer(1),
+                    DataType::Text("Lovelace, Ada".to_string()),
+                ],
+                vec![DataType::Integer(2), DataType::Null],
+            ],
+        )
+    }
+
+    #[test]
+    fn test_csv_output_quotes_fields() {
+        assert_eq!(
+            render_query_result(&result(), OutputMode::Csv, true, false),
+            "id,name\r\n1,\"Lovelace, Ada\"\r\n2,\r\n"
+        );
+        assert_eq!(
+            render_query_result(&result(), OutputMode::Csv, false, false),
+            "1,\"Lovelace, Ada\"\r\n2,\r\n"
+        );
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_json_output_maps_values_to_json_types() {
+        let json = render_query_result(&result(), OutputMode::Json, true, false);
+        assert_eq!(
+            json,
+            "[{\"id\":1,\"name\":\"Lovelace, Ada\"},\n{\"id\":2,\"name\":null}]\n"
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            serde_json::json!([
+                {"id": 1, "name": "Lovelace, Ada"},
+                {"id": 2, "name": null},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_table_output_lists_rows() {
+        let table = render_query_result(&result(), OutputMode::Table, true, false);
+        assert!(table.contains("| Lovelace, Ada |"), "{}", table);
+        assert!(table.contains("name"), "{}", table);
+        assert!(table.ends_with("(2 rows)\n"), "{}", table);
+
+        let table = render_query_result(&result(), OutputMode::Table, false, false);
+        assert!(!table.contains("name"), "{}", table);
+    }
+
+    #[test]
+    fn test_output_modes_are_parsed() {
+        for mode in [OutputMode::Table, OutputMode::Csv, OutputMode::Json] {
+            assert_eq!(mode.to_string().parse::<OutputMode>(), Ok(mode));
+        }
+        assert!("html".parse::<OutputMode>().is_err());
+    }
+}
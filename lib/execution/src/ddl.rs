@@ -0,0 +1,484 @@
+//! # Data Definition
+//!
+//! Executes `CREATE TABLE` and `DROP TABLE`, creating and removing tables in the [`Catalog`]
+//! of the engine, which queries are planned against. After every change, the tables are
+//! handed to the engine's [`CatalogPersister`], if it has one; a change
+//! that fails to persist is undone. Once a drop is persisted, the heap pages of the dropped
+//! tables are freed.
+//!
+//! Column types are parsed by [`DataTypeKind::from_sql`]. Columns may be declared `NULL`, `NOT
+//! NULL`, `UNIQUE`, `PRIMARY KEY` or with a `DEFAULT` (evaluated once, when the table is
+//! created), and tables may declare `UNIQUE` and `PRIMARY KEY` constraints over their columns.
+//! Text columns may declare how their values are ordered with `COLLATE` (see
+//! [`Collation::from_name`]).
+
+use crate::{eval, QueryEngine, QueryResult};
+use buffer::TableHeap;
+use catalog::{Catalog, Column, ColumnLength, Schema, Table};
+use common::PageId;
+use compile::parser::{
+    ColumnDef, ColumnOption, DataType as SqlDataType, ExactNumberInfo, ObjectName, ObjectType,
+    Statement, TableConstraint,
+};
+use datafusion_common::{DataFusionError, Result};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use std::{error::Error, sync::Arc};
+use tracing::{debug, error, info};
+use ty::{Collation, DataType, DataTypeKind, TypeError};
+
+/// Persists the tables of a [`Catalog`] after `CREATE TABLE` or `DROP TABLE` changes them.
+pub type CatalogPersister =
+    Box<dyn Fn(&Catalog) -> Result<(), Box<dyn Error + Send + Sync>> + Send + Sync>;
+
+/// The length of the variable-length columns declared without one (e.g. `TEXT`, or `VARCHAR`
+/// without a length, which holds at most this many characters).
+pub const DEFAULT_VARIABLE_LENGTH: u32 = 255;
+
+fn not_implemented<T>(what: impl std::fmt::Display) -> Result<T> {
+    Err(DataFusionError::NotImplemented(what.to_string()))
+}
+
+fn external(error: impl std::error::Error + Send + Sync + 'static) -> DataFusionError {
+    DataFusionError::External(Box::new(error))
+}
+
+/// Executes a `CREATE TABLE` or `DROP TABLE` statement.
+pub(crate) async fn execute(engine: &QueryEngine, statement: &Statement) -> Result<QueryResult> {
+    match statement {
+        Statement::CreateTable {
+            or_replace: false,
+            temporary: false,
+            external: false,
+            if_not_exists,
+            name,
+            columns,
+            constraints,
+            query: None,
+            like: None,
+            clone: None,
+            ..
+        } => create_table(engine, name, columns, constraints, *if_not_exists),
+        Statement::Drop {
+            object_type: ObjectType::Table,
+            if_exists,
+            names,
+            ..
+        } => drop_tables(engine, names, *if_exists).await,
+        statement => not_implemented(format!("Unsupported statement: {}", statement)),
+    }?;
+    Ok(QueryResult::new(Vec::new(), Vec::new()))
+}
+
+fn create_table(
+    engine: &QueryEngine,
+    name: &ObjectName,
+    columns: &[ColumnDef],
+    constraints: &[TableConstraint],
+    if_not_exists: bool,
+) -> Result<()> {
+    let name = name.to_string();
+    if if_not_exists && engine.catalog.get_table(&name).is_some() {
+        debug!("Table `{}` already exists, skipping", name);
+        return Ok(());
+    }
+
+    let schema = table_schema(columns, constraints)?;
+    engine
+        .catalog
+        .create_table(&name, schema)
+        .map_err(external)?;
+    if let Err(e) = persist(engine) {
+        engine.catalog.drop_table(&name).map_err(external)?;
+        return Err(e);
+    }
+    info!("Created table `{}`", name);
+    Ok(())
+}
+
+/// Drops the named tables. Unless `if_exists`, every table must exist, and none is dropped
+/// otherwise.
+async fn drop_tables(engine: &QueryEngine, names: &[ObjectName], if_exists: bool) -> Result<()> {
+    // Tables are checked as they are dropped, so that one dropped concurrently by another
+    // statement can't be dropped twice
+    let mut dropped = Vec::with_capacity(names.len());
+    for name in names.iter().map(ToString::to_string) {
+        match engine.catalog.drop_table(&name) {
+            Ok(table) => dropped.push(table),
+            Err(_) if if_exists => debug!("Table `{}` does not exist, skipping", name),
+            Err(e) => {
+                restore_tables(engine, dropped)?;
+                return Err(external(e));
+            }
+        }
+    }
+
+    if let Err(e) = persist(engine) {
+        restore_tables(engine, dropped)?;
+        return Err(e);
+    }
+    for table in &dropped {
+        info!("Dropped table `{}`", table.name());
+    }
+
+    // Inserts check that their table still exists once they hold the pool, so none can
+    // append to the heaps from here on
+    let buffer_pool = engine.buffer_pool.read().unwrap().clone();
+    if let Some(buffer_pool) = buffer_pool {
+        let mut bpm = buffer_pool.lock().await;
+        for table in dropped {
+            let heap = TableHeap::new(table.heap_pages().into_iter().map(PageId::from).collect());
+            // The drop is already persisted, so a failure only leaks the pages
+            if let Err(e) = heap.free(&mut bpm).await {
+                error!("Failed to free the heap of `{}`: {}", table.name(), e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Puts back the tables dropped by a statement that failed, along with their rows.
+fn restore_tables(engine: &QueryEngine, tables: Vec<Arc<Table>>) -> Result<()> {
+    for table in tables {
+        engine.catalog.insert_table(table).map_err(external)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn persist(engine: &QueryEngine) -> Result<()> {
+    match engine.catalog_persister.read().unwrap().as_ref() {
+        Some(persister) => persister(&engine.catalog).map_err(DataFusionError::External),
+        None => Ok(()),
+    }
+}
+
+/// Builds the schema of a table from its column definitions and table constraints, laying
+/// the columns out one after the other.
+fn table_schema(columns: &[ColumnDef], constraints: &[TableConstraint]) -> Result<Schema> {
+    let mut offset = 0;
+    let mut table_columns: Vec<Column> = Vec::with_capacity(columns.len());
+    for def in columns {
+        if table_columns
+            .iter()
+            .any(|column| column.column_name() == &def.name.value)
+        {
+            return Err(DataFusionError::Plan(format!(
+                "Column `{}` specified more than once",
+                def.name
+            )));
+        }
+        let column = column(def, offset)?;
+        offset += match column.length() {
+            ColumnLength::Fixed(len) | ColumnLength::Variable(len) => len,
+        };
+        table_columns.push(column);
+    }
+
+    for constraint in constraints {
+        let TableConstraint::Unique {
+            columns,
+            is_primary,
+            ..
+        } = constraint
+        else {
+            return not_implemented(format!("Unsupported table constraint: {}", constraint));
+        };
+        for name in columns {
+            let column = table_columns
+                .iter_mut()
+                .find(|column| column.column_name() == &name.value)
+                .ok_or_else(|| {
+                    DataFusionError::Plan(format!(
+                        "Column `{}` named in constraint does not exist",
+                        name
+                    ))
+                })?;
+            if *is_primary {
+                column.set_primary_key(true);
+            } else if columns.len() == 1 {
+                column.set_unique(true);
+            } else {
+                return not_implemented("UNIQUE constraints over more than one column");
+            }
+        }
+    }
+    Ok(Schema::new(table_columns))
+}
+
+/// Validates a value assigned to a column (by `INSERT`, or as its `DEFAULT`) with
+/// [`Column::validate`], first converting numeric values to the numeric type of the column:
+/// numeric literals are evaluated to `BIGINT`s and `DOUBLE PRECISION`s, which
+/// [`DataType::coerce_to`] doesn't narrow, and `NUMERIC` columns only hold `Decimal`s.
+pub(crate) fn assign(column: &Column, value: DataType) -> Result<DataType, TypeError> {
+    let overflow = |data_type: &str| TypeError::OverflowError {
+        data_type: data_type.to_string(),
+    };
+    let integer = match value {
+        DataType::SmallInt(val) | DataType::SmallSerial(val) => Some(val as i64),
+        DataType::Integer(val) | DataType::Serial(val) => Some(val as i64),
+        DataType::BigInt(val) | DataType::BigSerial(val) => Some(val),
+        _ => None,
+    };
+    let float = match value {
+        DataType::Real(val) => Some(val as f64),
+        DataType::DoublePrecision(val) | DataType::Float(val) => Some(val),
+        DataType::Decimal(val) => val.to_f64(),
+        _ => integer.map(|val| val as f64),
+    };
+
+    let value = match (column.column_type(), integer, float) {
+        (DataTypeKind::SmallInt, Some(val), _) => i16::try_from(val)
+            .map(DataType::SmallInt)
+            .map_err(|_| overflow("SMALLINT"))?,
+        (DataTypeKind::Integer, Some(val), _) => i32::try_from(val)
+            .map(DataType::Integer)
+            .map_err(|_| overflow("INTEGER"))?,
+        (DataTypeKind::BigInt, Some(val), _) => DataType::BigInt(val),
+        (DataTypeKind::Decimal, Some(val), _) => DataType::Decimal(Decimal::from(val)),
+        (DataTypeKind::Decimal, None, Some(val)) if !matches!(value, DataType::Decimal(_)) => {
+            DataType::Decimal(Decimal::try_from(val).map_err(|_| overflow("DECIMAL"))?)
+        }
+        (DataTypeKind::Real, _, Some(val)) => DataType::Real(val as f32),
+        (DataTypeKind::DoublePrecision, _, Some(val)) => DataType::DoublePrecision(val),
+        (DataTypeKind::Float, _, Some(val)) => DataType::Float(val),
+        _ => value,
+    };
+    column.validate(&value)
+}
+
+/// Builds a column from its definition, placed at `offset` in the rows of its table.
+fn column(def: &ColumnDef, offset: u32) -> Result<Column> {
+    let (kind, length) = DataTypeKind::from_sql(&def.data_type.to_string()).map_err(external)?;
+    let decimal_precision = match &def.data_type {
+        SqlDataType::Decimal(ExactNumberInfo::PrecisionAndScale(precision, scale))
+        | SqlDataType::Numeric(ExactNumberInfo::PrecisionAndScale(precision, scale)) => {
+            Some((*precision, *scale))
+        }
+        SqlDataType::Decimal(ExactNumberInfo::Precision(precision))
+        | SqlDataType::Numeric(ExactNumberInfo::Precision(precision)) => Some((*precision, 0)),
+        _ => None,
+    };
+
+    let name = def.name.value.as_str();
+    let mut column = match decimal_precision {
+        Some((precision, scale)) => {
+            let column = Column::new_decimal(
+                name,
+                u32::try_from(precision).unwrap_or(u32::MAX),
+                u32::try_from(scale).unwrap_or(u32::MAX),
+            )
+            .map_err(|e| DataFusionError::Plan(format!("{}: {}", def, e)))?;
+            Column::builder()
+                .column_name(name.to_string())
+                .column_type(kind.clone())
+                .length(column.length().clone())
+                .column_offset(offset)
+                .decimal_precision(*column.decimal_precision())
+                .build()
+        }
+        None => {
+            let length = match (kind.metadata().size(), length) {
+                (_, Some(0)) => {
+                    return Err(DataFusionError::Plan(format!(
+                        "Length of column `{}` must be positive",
+                        name
+                    )))
+                }
+                (_, Some(length)) => {
+                    ColumnLength::Variable(u32::try_from(length).unwrap_or(u32::MAX))
+                }
+                (Some(size), None) => ColumnLength::Fixed(size as u32),
+                (None, None) => ColumnLength::Variable(DEFAULT_VARIABLE_LENGTH),
+            };
+            Column::builder()
+                .column_name(name.to_string())
+                .column_type(kind.clone())
+                .length(length)
+                .column_offset(offset)
+                .build()
+        }
+    };
+
+    for option in &def.options {
+        match &option.option {
+            ColumnOption::Null => column.set_nullable(true),
+            ColumnOption::NotNull => column.set_nullable(false),
+            ColumnOption::Unique { is_primary: true } => column.set_primary_key(true),
+            ColumnOption::Unique { is_primary: false } => column.set_unique(true),
+            ColumnOption::Default(expr) => {
+                let value = eval::evaluate(expr).map_err(external)?;
+                let value = match value {
+                    DataType::Null => value,
+                    value => assign(&column, value).map_err(external)?,
+                };
+                column.set_default(Some(value))
+            }
+            option => return not_implemented(format!("Unsupported column option: {}", option)),
+        };
+    }
+    if let Some(collation) = &def.collation {
+        if !matches!(kind, DataTypeKind::Text | DataTypeKind::VarChar) {
+            return Err(DataFusionError::Plan(format!(
+                "Collation `{}` applies to text columns only, but `{}` is {}",
+                collation,
+                name,
+                kind.metadata().name()
+            )));
+        }
+        let collation_name = collation.0.last().map_or("", |ident| ident.value.as_str());
+        let collation = Collation::from_name(collation_name)
+            .ok_or_else(|| DataFusionError::Plan(format!("Unknown collation `{}`", collation)))?;
+        column.set_collation(collation);
+    }
+    Ok(column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use catalog::DatabaseError;
+
+    fn database_error(error: &DataFusionError) -> Option<&DatabaseError> {
+        match error {
+            DataFusionError::External(e) => e.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tables_are_created_and_dropped() {
+        let engine = QueryEngine::new();
+        engine
+            .execute_query(
+                "CREATE TABLE users (
+                    id INTEGER PRIMARY KEY,
+                    name VARCHAR(64) NOT NULL,
+                    email TEXT UNIQUE,
+                    balance NUMERIC(10, 2) DEFAULT 0
+                )",
+            )
+            .await
+            .unwrap();
+
+        let schema = engine.catalog().table_schema("users").unwrap();
+        let columns = schema.columns();
+        assert_eq!(
+            columns
+                .iter()
+                .map(|column| column.column_name().as_str())
+                .collect::<Vec<_>>(),
+            ["id", "name", "email", "balance"]
+        );
+        assert!(*columns[0].primary_key() && !columns[0].is_nullable());
+        assert_eq!(columns[1].column_type(), &DataTypeKind::VarChar);
+        assert_eq!(columns[1].length(), &ColumnLength::Variable(64));
+        assert_eq!(columns[1].column_offset(), &4);
+        assert!(!columns[1].is_nullable());
+        assert!(*columns[2].unique() && columns[2].is_nullable());
+        assert_eq!(columns[3].decimal_precision().unwrap().scale, 2);
+        assert_eq!(
+            columns[3].default(),
+            &Some(DataType::Decimal("0.00".parse().unwrap()))
+        );
+        assert_eq!(engine.catalog().table_names(), ["users"]);
+
+        engine.execute_query("DROP TABLE users").await.unwrap();
+        assert!(engine.catalog().table_schema("users").is_none());
+        assert!(engine.catalog().table_names().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_if_not_exists_and_if_exists_are_idempotent() {
+        let engine = QueryEngine::new();
+        let create = "CREATE TABLE t (id BIGINT)";
+        engine.execute_query(create).await.unwrap();
+
+        let err = engine.execute_query(create).await.unwrap_err();
+        assert_eq!(
+            database_error(&err),
+            Some(&DatabaseError::TableAlreadyExists("t".to_string()))
+        );
+        // The existing table is kept as it is
+        engine
+            .execute_query("CREATE TABLE IF NOT EXISTS t (name TEXT)")
+            .await
+            .unwrap();
+        let schema = engine.catalog().table_schema("t").unwrap();
+        assert_eq!(schema.columns()[0].column_name(), "id");
+
+        engine.execute_query("DROP TABLE t").await.unwrap();
+        let err = engine.execute_query("DROP TABLE t").await.unwrap_err();
+        assert_eq!(
+            database_error(&err),
+            Some(&DatabaseError::TableNotFound("t".to_string()))
+        );
+        engine
+            .execute_query("DROP TABLE IF EXISTS t")
+            .await
+            .unwrap();
+
+        // A missing table fails the whole statement, whatever its position
+        engine.execute_query(create).await.unwrap();
+        let err = engine
+            .execute_query("DROP TABLE t, missing")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            database_error(&err),
+            Some(&DatabaseError::TableNotFound("missing".to_string()))
+        );
+        assert_eq!(engine.catalog().table_names(), ["t"]);
+        assert!(engine.catalog().table_schema("t").is_some());
+        engine
+            .execute_query("DROP TABLE IF EXISTS missing, t")
+            .await
+            .unwrap();
+        assert!(engine.catalog().table_names().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_persistence_undoes_the_change() {
+        let engine = QueryEngine::new();
+        engine
+            .execute_query("CREATE TABLE kept (id INT)")
+            .await
+            .unwrap();
+        engine.set_catalog_persister(|_| Err("disk full".into()));
+
+        assert!(engine
+            .execute_query("CREATE TABLE t (id INT)")
+            .await
+            .is_err());
+        assert!(engine.execute_query("DROP TABLE kept").await.is_err());
+        assert_eq!(engine.catalog().table_names(), ["kept"]);
+    }
+
+    #[test]
+    fn test_invalid_definitions_are_rejected() {
+        let schema = |sql: &str| {
+            let ast = compile::parser::parse_sql(sql).unwrap();
+            let Statement::CreateTable {
+                columns,
+                constraints,
+                ..
+            } = &ast[0]
+            else {
+                unreachable!()
+            };
+            table_schema(columns, constraints)
+        };
+
+        assert!(schema("CREATE TABLE t (a INT, a TEXT)").is_err());
+        assert!(schema("CREATE TABLE t (a WIDGET)").is_err());
+        assert!(schema("CREATE TABLE t (a INT, PRIMARY KEY (b))").is_err());
+        assert!(schema("CREATE TABLE t (a INT COLLATE nocase)").is_err());
+        assert!(schema("CREATE TABLE t (a TEXT COLLATE klingon)").is_err());
+        let collated = schema("CREATE TABLE t (a TEXT COLLATE nocase, b TEXT)").unwrap();
+        assert_eq!(
+            collated.columns()[0].collation(),
+            &Collation::CaseInsensitive
+        );
+        assert_eq!(collated.columns()[1].collation(), &Collation::Binary);
+        let schema = schema("CREATE TABLE t (a INT, b INT, PRIMARY KEY (a, b))").unwrap();
+        assert_eq!(schema.primary_key_columns().len(), 2);
+    }
+}
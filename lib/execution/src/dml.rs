@@ -0,0 +1,332 @@
+//! # Data Manipulation
+//!
+//! Executes `INSERT INTO <table> [(<columns>)] VALUES (...), ...` against the tables created by
+//! `CREATE TABLE`. Every value is coerced to the type of its column and checked against the
+//! column's constraints by [`Column::validate`], which also fills in the defaults of the
+//! columns left out (NULL for those without one). The rows are then encoded (see
+//! [`encode_row`]) and appended to the [`TableHeap`] of the table, in the buffer pool of the
+//! engine.
+//!
+//! The rows of a statement are inserted atomically: none is stored unless all of them are
+//! valid and fit in the heap, and the first page of a table's heap is only kept once the
+//! catalog recording it is persisted.
+
+use crate::{ddl, eval, QueryEngine, QueryResult};
+use buffer::TableHeap;
+use catalog::{Column, DatabaseError};
+use common::PageId;
+use compile::parser::{Expr, Ident, ObjectName, Query, SetExpr, Statement};
+use datafusion_common::{DataFusionError, Result};
+use std::sync::Arc;
+use storage::table::row::encode_row;
+use tracing::info;
+use ty::DataType;
+
+fn external(error: impl std::error::Error + Send + Sync + 'static) -> DataFusionError {
+    DataFusionError::External(Box::new(error))
+}
+
+/// Executes an `INSERT` statement.
+pub(crate) async fn execute(engine: &QueryEngine, statement: &Statement) -> Result<QueryResult> {
+    match statement {
+        Statement::Insert {
+            or: None,
+            ignore: false,
+            table_name,
+            columns,
+            overwrite: false,
+            source: Some(source),
+            partitioned: None,
+            after_columns,
+            on: None,
+            returning: None,
+            ..
+        } if after_columns.is_empty() => insert(engine, table_name, columns, source).await,
+        statement => Err(DataFusionError::NotImplemented(format!(
+            "Unsupported statement: {}",
+            statement
+        ))),
+    }
+}
+
+async fn insert(
+    engine: &QueryEngine,
+    table_name: &ObjectName,
+    columns: &[Ident],
+    source: &Query,
+) -> Result<QueryResult> {
+    let name = table_name.to_string();
+    let table = engine
+        .catalog
+        .get_table(&name)
+        .ok_or_else(|| external(DatabaseError::TableNotFound(name.clone())))?;
+    let SetExpr::Values(values) = source.body.as_ref() else {
+        return Err(DataFusionError::NotImplemented(format!(
+            "INSERT from a query: {}",
+            source
+        )));
+    };
+
+    let schema = table.schema();
+    let targets = target_columns(schema.columns(), columns)?;
+    let records = values
+        .rows
+        .iter()
+        .map(|row| {
+            let row = row_values(engine, schema.columns(), &targets, row)?;
+            encode_row(&row).map_err(external)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let buffer_pool = engine.buffer_pool.read().unwrap().clone();
+    let Some(buffer_pool) = buffer_pool else {
+        return Err(DataFusionError::Execution(format!(
+            "Cannot insert into `{}` without a buffer pool to store its rows in",
+            name
+        )));
+    };
+    // The pool stays locked until the heap pages of the table are updated, so that concurrent
+    // inserts into the same table append to the same heap
+    let mut bpm = buffer_pool.lock().await;
+    // The pages of a table dropped while waiting for the pool have been freed
+    if !engine
+        .catalog
+        .get_table(&name)
+        .is_some_and(|current| Arc::ptr_eq(&current, &table))
+    {
+        return Err(external(DatabaseError::TableNotFound(name)));
+    }
+    let pages = table.heap_pages();
+    let mut heap = TableHeap::new(pages.iter().copied().map(PageId::from).collect());
+    let rids = heap
+        .insert_records(&mut bpm, &records)
+        .await
+        .map_err(|e| DataFusionError::External(e.into()))?;
+    // The statement's page writes are forced to the log together, before the catalog can link
+    // to the pages they filled
+    bpm.flush_log()
+        .map_err(|e| DataFusionError::External(e.into()))?;
+    if heap.pages().len() > pages.len() {
+        table.set_heap_pages(heap.pages().iter().map(|page_id| page_id.0).collect());
+        // Later pages are linked from the first one, which is all the catalog records
+        if pages.is_empty() {
+            if let Err(e) = ddl::persist(engine) {
+                table.set_heap_pages(pages);
+                heap.free(&mut bpm)
+                    .await
+                    .map_err(|e| DataFusionError::External(e.into()))?;
+                return Err(e);
+            }
+        }
+    }
+    drop(bpm);
+
+    info!("Inserted {} rows into `{}`", rids.len(), name);
+    Ok(QueryResult::new(Vec::new(), Vec::new())
+        .with_command_tag(format!("INSERT 0 {}", rids.len())))
+}
+
+/// Returns the index, in the schema, of each column named in the statement, or of every
+/// column if none is named.
+fn target_columns(schema: &[Column], columns: &[Ident]) -> Result<Vec<usize>> {
+    if columns.is_empty() {
+        return Ok((0..schema.len()).collect());
+    }
+
+    let mut targets = Vec::with_capacity(columns.len());
+    for ident in columns {
+        let index = schema
+            .iter()
+            .position(|column| column.column_name() == &ident.value)
+            .ok_or_else(|| {
+                DataFusionError::Plan(format!("Column `{}` does not exist", ident.value))
+            })?;
+        if targets.contains(&index) {
+            return Err(DataFusionError::Plan(format!(
+                "Column `{}` specified more than once",
+                ident.value
+            )));
+        }
+        targets.push(index);
+    }
+    Ok(targets)
+}
+
+/// Evaluates the values of a row of `VALUES`, returning the value of every column of the
+/// table, in schema order.
+fn row_values(
+    engine: &QueryEngine,
+    schema: &[Column],
+    targets: &[usize],
+    row: &[Expr],
+) -> Result<Vec<DataType>> {
+    if row.len() != targets.len() {
+        return Err(DataFusionError::Plan(format!(
+            "INSERT has {} target columns but {} values",
+            targets.len(),
+            row.len()
+        )));
+    }
+
+    let mut values = vec![DataType::Null; schema.len()];
+    let functions = engine.functions.read().unwrap();
+    for (&index, expr) in targets.iter().zip(row) {
+        values[index] = eval::evaluate_with(expr, &functions).map_err(external)?;
+    }
+    schema
+        .iter()
+        .zip(values)
+        .map(|(column, value)| ddl::assign(column, value).map_err(external))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use buffer::{BufferPoolManager, ReplacementPolicy};
+    use std::sync::Arc;
+    use storage::disk::{DiskManager, IN_MEMORY_PATH};
+    use ty::TypeError;
+
+    async fn engine_with_table() -> QueryEngine {
+        let disk_manager = Arc::new(DiskManager::new(IN_MEMORY_PATH).unwrap());
+        let bpm =
+            BufferPoolManager::new_with_size(ReplacementPolicy::LRU, disk_manager, 10).unwrap();
+        let engine = QueryEngine::new();
+        engine.set_buffer_pool(Arc::new(tokio::sync::Mutex::new(bpm)));
+        engine
+            .execute_query(
+                "CREATE TABLE users (
+                    id INTEGER NOT NULL,
+                    name VARCHAR(8),
+                    active BOOLEAN DEFAULT true
+                )",
+            )
+            .await
+            .unwrap();
+        engine
+    }
+
+    fn type_error(e: &DataFusionError) -> Option<&TypeError> {
+        match e {
+            DataFusionError::External(e) => e.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rows_are_inserted_into_the_heap() {
+        let engine = engine_with_table().await;
+        let result = engine
+            .execute_query("INSERT INTO users VALUES (1, 'ada', false)")
+            .await
+            .unwrap();
+        assert_eq!(result.command_tag().as_deref(), Some("INSERT 0 1"));
+
+        let result = engine
+            .execute_query("INSERT INTO users (name, id) VALUES ('grace', 2), (NULL, 3)")
+            .await
+            .unwrap();
+        assert_eq!(result.command_tag().as_deref(), Some("INSERT 0 2"));
+        let table = engine.catalog().get_table("users").unwrap();
+        assert_eq!(table.heap_pages().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_values_are_coerced_or_rejected() {
+        let engine = engine_with_table().await;
+        // Text holding a number is coerced to the INTEGER column
+        engine
+            .execute_query("INSERT INTO users (id) VALUES ('42')")
+            .await
+            .unwrap();
+
+        let err = engine
+            .execute_query("INSERT INTO users (id) VALUES ('forty-two')")
+            .await
+            .unwrap_err();
+        assert!(type_error(&err).is_some(), "unexpected error: {}", err);
+        let err = engine
+            .execute_query("INSERT INTO users (id) VALUES (3000000000)")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            type_error(&err),
+            Some(TypeError::OverflowError { .. })
+        ));
+        let err = engine
+            .execute_query("INSERT INTO users (id, name) VALUES (1, 'much too long')")
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(type_error(&err), Some(TypeError::StringTooLong { .. })),
+            "unexpected error: {}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_not_null_column_fails_the_whole_statement() {
+        let engine = engine_with_table().await;
+        let err = engine
+            .execute_query("INSERT INTO users (id, name) VALUES (1, 'ada'), (NULL, 'grace')")
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(type_error(&err), Some(TypeError::NullViolation { column }) if column == "id"),
+            "unexpected error: {}",
+            err
+        );
+        let err = engine
+            .execute_query("INSERT INTO users (name) VALUES ('ada')")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            type_error(&err),
+            Some(TypeError::NullViolation { .. })
+        ));
+
+        // Neither statement stored any row
+        let table = engine.catalog().get_table("users").unwrap();
+        assert!(table.heap_pages().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_persistence_releases_the_first_heap_page() {
+        let engine = engine_with_table().await;
+        engine.set_catalog_persister(|_| Err("disk full".into()));
+
+        assert!(engine
+            .execute_query("INSERT INTO users VALUES (1, 'ada', false)")
+            .await
+            .is_err());
+        let table = engine.catalog().get_table("users").unwrap();
+        assert!(table.heap_pages().is_empty());
+
+        // The page allocated for the row was freed and reused by the next insert, so the next
+        // page allocated follows it
+        engine.set_catalog_persister(|_| Ok(()));
+        engine
+            .execute_query("INSERT INTO users VALUES (1, 'ada', false)")
+            .await
+            .unwrap();
+        let buffer_pool = engine.buffer_pool.read().unwrap().clone().unwrap();
+        let (page_id, _) = buffer_pool.lock().await.new_page().await.unwrap();
+        assert_eq!(table.heap_pages(), [page_id.0 - 1]);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_tables_free_their_heap_pages() {
+        let engine = engine_with_table().await;
+        engine
+            .execute_query("INSERT INTO users VALUES (1, 'ada', false)")
+            .await
+            .unwrap();
+        let pages = engine.catalog().get_table("users").unwrap().heap_pages();
+        engine.execute_query("DROP TABLE users").await.unwrap();
+
+        let buffer_pool = engine.buffer_pool.read().unwrap().clone().unwrap();
+        let (page_id, _) = buffer_pool.lock().await.new_page().await.unwrap();
+        assert_eq!(pages, [page_id.0]);
+    }
+}
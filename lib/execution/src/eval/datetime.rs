@@ -0,0 +1,162 @@
+//! Date and time functions over `DATETIME`s, which may also be given as text (e.g.
+//! `'2024-03-15 10:30:00'`), and `TIMESTAMPTZ`s, whose fields are those of their UTC time. A
+//! NULL argument yields NULL.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
+use ty::{DataType, DataTypeKind, TypeError};
+
+fn invalid_unit(function: &str, unit: &str) -> TypeError {
+    TypeError::InvalidArgument {
+        function: function.to_string(),
+        reason: format!("unit \"{}\" not recognized", unit),
+    }
+}
+
+/// Returns the timestamp of a value, or `None` for NULL.
+fn datetime(value: &DataType) -> Result<Option<NaiveDateTime>, TypeError> {
+    match value {
+        DataType::Null => Ok(None),
+        DataType::DateTime(val) => Ok(Some(*val)),
+        DataType::TimestampTz(val) => Ok(Some(val.naive_utc())),
+        DataType::Text(_) | DataType::VarChar(_) => {
+            match value.coerce_to(&DataTypeKind::DateTime)? {
+                DataType::DateTime(val) => Ok(Some(val)),
+                _ => unreachable!("Coercion to DATETIME yields a DATETIME"),
+            }
+        }
+        value => Err(TypeError::IncompatibleType {
+            expected: "DATETIME".to_string(),
+            found: value.data_type_kind().metadata().name().to_string(),
+        }),
+    }
+}
+
+fn midnight(date: NaiveDate) -> NaiveDateTime {
+    date.and_time(NaiveTime::default())
+}
+
+/// Returns the current timestamp, as a `TIMESTAMPTZ`.
+pub fn now() -> DataType {
+    DataType::TimestampTz(Utc::now())
+}
+
+/// Truncates a timestamp to the start of the `second`, `minute`, `hour`, `day`, `week` (which
+/// starts on Monday), `month`, `quarter` or `year` it falls in, like
+/// `DATE_TRUNC('day', ts)`. A `TIMESTAMPTZ` stays one.
+pub fn date_trunc(unit: &DataType, value: &DataType) -> Result<DataType, TypeError> {
+    let unit = match unit {
+        DataType::Null => return Ok(DataType::Null),
+        DataType::Text(s) | DataType::VarChar(s) => s.to_lowercase(),
+        unit => {
+            return Err(TypeError::IncompatibleType {
+                expected: "TEXT".to_string(),
+                found: unit.data_type_kind().metadata().name().to_string(),
+            })
+        }
+    };
+    let Some(datetime) = datetime(value)? else {
+        return Ok(DataType::Null);
+    };
+
+    let date = datetime.date();
+    let first_of_month = |month: u32| NaiveDate::from_ymd_opt(date.year(), month, 1).unwrap();
+    let truncated = match unit.as_str() {
+        "second" => datetime.with_nanosecond(0).unwrap(),
+        "minute" => date
+            .and_hms_opt(datetime.hour(), datetime.minute(), 0)
+            .unwrap(),
+        "hour" => date.and_hms_opt(datetime.hour(), 0, 0).unwrap(),
+        "day" => midnight(date),
+        "week" => {
+            let days = date.weekday().num_days_from_monday();
+            midnight(date - Duration::days(days as i64))
+        }
+        "month" => midnight(first_of_month(date.month())),
+        "quarter" => midnight(first_of_month(date.month0() / 3 * 3 + 1)),
+        "year" => midnight(first_of_month(1)),
+        unit => return Err(invalid_unit("DATE_TRUNC", unit)),
+    };
+    match value {
+        DataType::TimestampTz(_) => DataType::DateTime(truncated).assume_utc(),
+        _ => Ok(DataType::DateTime(truncated)),
+    }
+}
+
+/// Returns a field of a timestamp as an `INTEGER`, like `EXTRACT(field FROM ts)`. The field is
+/// one of `YEAR`, `QUARTER`, `MONTH`, `WEEK` (of the ISO 8601 year), `DAY`, `DOW` (the day of
+/// the week, from 0 for Sunday), `DOY` (the day of the year), `HOUR`, `MINUTE` or `SECOND`
+/// (truncated to a whole second), or `EPOCH`, the number of seconds since 1970 as a `BIGINT`.
+pub fn extract(field: &str, value: &DataType) -> Result<DataType, TypeError> {
+    let Some(datetime) = datetime(value)? else {
+        return Ok(DataType::Null);
+    };
+
+    let field = field.to_uppercase();
+    let value = match field.as_str() {
+        "YEAR" => datetime.year(),
+        "QUARTER" => (datetime.month0() / 3 + 1) as i32,
+        "MONTH" => datetime.month() as i32,
+        "WEEK" => datetime.iso_week().week() as i32,
+        "DAY" => datetime.day() as i32,
+        "DOW" => datetime.weekday().num_days_from_sunday() as i32,
+        "DOY" => datetime.ordinal() as i32,
+        "HOUR" => datetime.hour() as i32,
+        "MINUTE" => datetime.minute() as i32,
+        "SECOND" => datetime.second() as i32,
+        "EPOCH" => return Ok(DataType::BigInt(datetime.timestamp())),
+        field => return Err(invalid_unit("EXTRACT", field)),
+    };
+    Ok(DataType::Integer(value))
+}
+
+/// Returns a field of a timestamp, named by a text value, like `DATE_PART('year', ts)` (see
+/// [`extract`]).
+pub fn date_part(field: &DataType, value: &DataType) -> Result<DataType, TypeError> {
+    match field {
+        DataType::Null => Ok(DataType::Null),
+        DataType::Text(field) | DataType::VarChar(field) => extract(field, value),
+        field => Err(TypeError::IncompatibleType {
+            expected: "TEXT".to_string(),
+            found: field.data_type_kind().metadata().name().to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date_trunc_units() {
+        let datetime = |s: &str| DataType::Text(s.to_string()).coerce_to(&DataTypeKind::DateTime);
+        let trunc = |unit: &str| {
+            date_trunc(
+                &DataType::Text(unit.to_string()),
+                &datetime("2024-05-16 10:30:45.5").unwrap(),
+            )
+        };
+
+        assert_eq!(trunc("second"), datetime("2024-05-16 10:30:45"));
+        assert_eq!(trunc("minute"), datetime("2024-05-16 10:30:00"));
+        assert_eq!(trunc("HOUR"), datetime("2024-05-16 10:00:00"));
+        // 2024-05-16 is a Thursday
+        assert_eq!(trunc("week"), datetime("2024-05-13"));
+        assert_eq!(trunc("month"), datetime("2024-05-01"));
+        assert_eq!(trunc("quarter"), datetime("2024-04-01"));
+        assert_eq!(trunc("year"), datetime("2024-01-01"));
+        assert_eq!(
+            date_trunc(
+                &DataType::Text("day".to_string()),
+                &datetime("2024-05-16 10:30:45")
+                    .unwrap()
+                    .assume_utc()
+                    .unwrap()
+            ),
+            datetime("2024-05-16").unwrap().assume_utc()
+        );
+        assert!(matches!(
+            trunc("fortnight"),
+            Err(TypeError::InvalidArgument { .. })
+        ));
+    }
+}
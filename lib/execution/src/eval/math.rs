@@ -0,0 +1,281 @@
+//! Numeric functions. They accept values of every numeric type and, unless noted otherwise,
+//! return a value of the type they were given (of the plain integer type for serials). A NULL
+//! argument yields NULL.
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, MathematicalOps, RoundingStrategy};
+use ty::{DataType, TypeError};
+
+fn not_numeric(value: &DataType) -> TypeError {
+    TypeError::IncompatibleType {
+        expected: "numeric".to_string(),
+        found: value.data_type_kind().metadata().name().to_string(),
+    }
+}
+
+fn overflow(data_type: &str) -> TypeError {
+    TypeError::OverflowError {
+        data_type: data_type.to_string(),
+    }
+}
+
+fn invalid_argument(function: &str, reason: &str) -> TypeError {
+    TypeError::InvalidArgument {
+        function: function.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+/// Returns the value of an integer and the width of its type in bits.
+fn integer_parts(value: &DataType) -> Option<(i64, u32)> {
+    match value {
+        DataType::SmallInt(val) | DataType::SmallSerial(val) => Some((*val as i64, 16)),
+        DataType::Integer(val) | DataType::Serial(val) => Some((*val as i64, 32)),
+        DataType::BigInt(val) | DataType::BigSerial(val) => Some((*val, 64)),
+        _ => None,
+    }
+}
+
+/// Creates an integer of the type `width` bits wide, failing if `val` doesn't fit in it.
+fn integer(val: i64, width: u32) -> Result<DataType, TypeError> {
+    match width {
+        16 => i16::try_from(val)
+            .map(DataType::SmallInt)
+            .map_err(|_| overflow("SMALLINT")),
+        32 => i32::try_from(val)
+            .map(DataType::Integer)
+            .map_err(|_| overflow("INTEGER")),
+        _ => Ok(DataType::BigInt(val)),
+    }
+}
+
+fn to_f64(value: &DataType) -> f64 {
+    match value {
+        DataType::Real(val) => *val as f64,
+        DataType::DoublePrecision(val) | DataType::Float(val) => *val,
+        DataType::Decimal(val) => val.to_f64().unwrap_or(f64::NAN),
+        _ => integer_parts(value).map_or(f64::NAN, |(val, _)| val as f64),
+    }
+}
+
+fn to_decimal(value: &DataType) -> Decimal {
+    match value {
+        DataType::Decimal(val) => *val,
+        _ => integer_parts(value).map_or(Decimal::ZERO, |(val, _)| Decimal::from(val)),
+    }
+}
+
+/// Wraps the floating-point result of an operation on `a` and `b` in the type `checked_div`
+/// divides them into: `REAL` for two `REAL`s, `FLOAT` if either is one, and `DOUBLE PRECISION`
+/// otherwise.
+fn floating(a: &DataType, b: &DataType, val: f64) -> DataType {
+    match (a, b) {
+        (DataType::Real(_), DataType::Real(_)) => DataType::Real(val as f32),
+        (DataType::Float(_), _) | (_, DataType::Float(_)) => DataType::Float(val),
+        _ => DataType::DoublePrecision(val),
+    }
+}
+
+pub fn abs(value: &DataType) -> Result<DataType, TypeError> {
+    match value {
+        DataType::Null => Ok(DataType::Null),
+        DataType::Decimal(val) => Ok(DataType::Decimal(val.abs())),
+        DataType::Real(val) => Ok(DataType::Real(val.abs())),
+        DataType::DoublePrecision(val) => Ok(DataType::DoublePrecision(val.abs())),
+        DataType::Float(val) => Ok(DataType::Float(val.abs())),
+        value => match integer_parts(value) {
+            Some((val, width)) => {
+                integer(val.checked_abs().ok_or_else(|| overflow("BIGINT"))?, width)
+            }
+            None => Err(not_numeric(value)),
+        },
+    }
+}
+
+/// Rounds a value to `digits` decimal places (0 by default), or to a multiple of
+/// `10^-digits` for a negative `digits`. Midpoints are rounded away from zero.
+pub fn round(value: &DataType, digits: Option<&DataType>) -> Result<DataType, TypeError> {
+    let digits = match digits {
+        None => 0,
+        Some(DataType::Null) => return Ok(DataType::Null),
+        Some(digits) => match integer_parts(digits) {
+            Some((digits, _)) => digits.clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+            None => {
+                return Err(TypeError::IncompatibleType {
+                    expected: "INTEGER".to_string(),
+                    found: digits.data_type_kind().metadata().name().to_string(),
+                })
+            }
+        },
+    };
+
+    match value {
+        DataType::Null => Ok(DataType::Null),
+        DataType::Decimal(val) if digits >= 0 => Ok(DataType::Decimal(
+            val.round_dp_with_strategy(digits as u32, RoundingStrategy::MidpointAwayFromZero),
+        )),
+        DataType::Decimal(val) => {
+            let rounded = Decimal::TEN
+                .checked_powi(-(digits as i64))
+                .and_then(|factor| {
+                    (val / factor)
+                        .round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero)
+                        .checked_mul(factor)
+                });
+            // Rounding to more digits than a `Decimal` holds leaves nothing
+            Ok(DataType::Decimal(rounded.unwrap_or(Decimal::ZERO)))
+        }
+        DataType::Real(_) | DataType::DoublePrecision(_) | DataType::Float(_) => {
+            let factor = 10f64.powi(digits);
+            let rounded = (to_f64(value) * factor).round() / factor;
+            Ok(floating(value, value, rounded))
+        }
+        value => match integer_parts(value) {
+            // Integers have no decimal places to round
+            Some((val, width)) if digits >= 0 => integer(val, width),
+            Some((val, width)) => {
+                let Some(factor) = 10i64.checked_pow(digits.unsigned_abs()) else {
+                    return integer(0, width);
+                };
+                let remainder = val % factor;
+                let mut rounded = val - remainder;
+                if remainder.unsigned_abs() * 2 >= factor.unsigned_abs() {
+                    rounded = rounded
+                        .checked_add(factor * val.signum())
+                        .ok_or_else(|| overflow("BIGINT"))?;
+                }
+                integer(rounded, width)
+            }
+            None => Err(not_numeric(value)),
+        },
+    }
+}
+
+/// Returns the smallest integral value not less than a value.
+pub fn ceil(value: &DataType) -> Result<DataType, TypeError> {
+    match value {
+        DataType::Null => Ok(DataType::Null),
+        DataType::Decimal(val) => Ok(DataType::Decimal(val.ceil())),
+        DataType::Real(val) => Ok(DataType::Real(val.ceil())),
+        DataType::DoublePrecision(val) => Ok(DataType::DoublePrecision(val.ceil())),
+        DataType::Float(val) => Ok(DataType::Float(val.ceil())),
+        value => match integer_parts(value) {
+            Some((val, width)) => integer(val, width),
+            None => Err(not_numeric(value)),
+        },
+    }
+}
+
+/// Returns the largest integral value not greater than a value.
+pub fn floor(value: &DataType) -> Result<DataType, TypeError> {
+    match value {
+        DataType::Null => Ok(DataType::Null),
+        DataType::Decimal(val) => Ok(DataType::Decimal(val.floor())),
+        DataType::Real(val) => Ok(DataType::Real(val.floor())),
+        DataType::DoublePrecision(val) => Ok(DataType::DoublePrecision(val.floor())),
+        DataType::Float(val) => Ok(DataType::Float(val.floor())),
+        value => match integer_parts(value) {
+            Some((val, width)) => integer(val, width),
+            None => Err(not_numeric(value)),
+        },
+    }
+}
+
+/// Returns the remainder of dividing `a` by `b`, which has the sign of `a`. The operands are
+/// widened to a common type as for [`DataType::checked_div`], and a zero divisor fails with
+/// [`TypeError::DivisionByZero`] unless one of them is floating-point.
+pub fn modulo(a: &DataType, b: &DataType) -> Result<DataType, TypeError> {
+    match (a, b) {
+        (DataType::Null, _) | (_, DataType::Null) => Ok(DataType::Null),
+        (a, _) if !a.is_numeric() => Err(not_numeric(a)),
+        (_, b) if !b.is_numeric() => Err(not_numeric(b)),
+        (a, b) if a.is_floating() || b.is_floating() => Ok(floating(a, b, to_f64(a) % to_f64(b))),
+        (a, b) => match (integer_parts(a), integer_parts(b)) {
+            (Some(_), Some((0, _))) => Err(TypeError::DivisionByZero),
+            // Only `i64::MIN % -1` overflows, and its remainder is 0
+            (Some((a, a_width)), Some((b, b_width))) => {
+                integer(a.checked_rem(b).unwrap_or(0), a_width.max(b_width))
+            }
+            // At least one of the operands is a `Decimal`
+            _ => {
+                let (a, b) = (to_decimal(a), to_decimal(b));
+                if b.is_zero() {
+                    return Err(TypeError::DivisionByZero);
+                }
+                a.checked_rem(b)
+                    .map(DataType::Decimal)
+                    .ok_or_else(|| overflow("DECIMAL"))
+            }
+        },
+    }
+}
+
+/// Raises `base` to the power `exponent`. Like in PostgreSQL, the result is a `Decimal` if
+/// either operand is one (and neither is floating-point), and floating-point otherwise, even
+/// for two integers.
+pub fn power(base: &DataType, exponent: &DataType) -> Result<DataType, TypeError> {
+    match (base, exponent) {
+        (DataType::Null, _) | (_, DataType::Null) => return Ok(DataType::Null),
+        (a, _) if !a.is_numeric() => return Err(not_numeric(a)),
+        (_, b) if !b.is_numeric() => return Err(not_numeric(b)),
+        _ => {}
+    }
+
+    let (base_f64, exponent_f64) = (to_f64(base), to_f64(exponent));
+    if base_f64 == 0.0 && exponent_f64 < 0.0 {
+        return Err(invalid_argument(
+            "POWER",
+            "zero raised to a negative power is undefined",
+        ));
+    }
+    if base_f64 < 0.0 && exponent_f64.fract() != 0.0 {
+        return Err(invalid_argument(
+            "POWER",
+            "a negative number raised to a non-integer power yields a complex result",
+        ));
+    }
+
+    let decimal = matches!(base, DataType::Decimal(_)) || matches!(exponent, DataType::Decimal(_));
+    if decimal && !base.is_floating() && !exponent.is_floating() {
+        return to_decimal(base)
+            .checked_powd(to_decimal(exponent))
+            .map(DataType::Decimal)
+            .ok_or_else(|| overflow("DECIMAL"));
+    }
+    Ok(floating(base, exponent, base_f64.powf(exponent_f64)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(val: &str) -> DataType {
+        DataType::Decimal(val.parse().unwrap())
+    }
+
+    #[test]
+    fn test_round_to_negative_digits() {
+        let digits = DataType::BigInt(-2);
+        assert_eq!(
+            round(&DataType::Integer(1250), Some(&digits)).unwrap(),
+            DataType::Integer(1300)
+        );
+        assert_eq!(
+            round(&DataType::Integer(-1249), Some(&digits)).unwrap(),
+            DataType::Integer(-1200)
+        );
+        assert_eq!(
+            round(&decimal("1250.5"), Some(&digits)).unwrap(),
+            decimal("1300")
+        );
+        assert_eq!(
+            round(&DataType::DoublePrecision(1249.0), Some(&digits)).unwrap(),
+            DataType::DoublePrecision(1200.0)
+        );
+        assert_eq!(
+            round(&DataType::SmallInt(5), Some(&DataType::BigInt(-30))).unwrap(),
+            DataType::SmallInt(0)
+        );
+        assert!(round(&DataType::SmallInt(32_700), Some(&DataType::BigInt(-3))).is_err());
+    }
+}
@@ -0,0 +1,638 @@
+//! # Expression Evaluation
+//!
+//! Evaluates SQL expressions that don't read any table (e.g. `COALESCE(NULL, 3)`) to values of
+//! the type system. Queries without a `FROM` clause are answered this way rather than planned.
+//!
+//! `CASE` evaluates its conditions in order, to the result of the first that holds. Every
+//! result is evaluated, as the results must be compatible with each other whichever is chosen.
+//!
+//! Functions are looked up in a [`FunctionRegistry`], including those with a syntax of their
+//! own (e.g. `TRIM(LEADING 'x' FROM s)` calls `LTRIM(s, 'x')`).
+//!
+//! Integer literals evaluate to `BIGINT`s and other numbers to `DOUBLE PRECISION`s, like the
+//! `Int64` and `Float64` literals of planned queries.
+
+mod datetime;
+mod math;
+mod registry;
+mod string;
+
+pub use registry::{Arity, FunctionRegistry, ScalarFunction};
+
+use crate::QueryResult;
+use compile::parser::{
+    BinaryOperator, DateTimeField, Expr as SqlExpr, Function, FunctionArg, FunctionArgExpr, Query,
+    SelectItem, SetExpr, TrimWhereField, UnaryOperator, Value,
+};
+use std::cmp::Ordering;
+use thiserror::Error;
+use ty::{DataType, DataTypeKind, TypeCheck, TypeError};
+
+#[derive(Error, Debug)]
+pub enum EvalError {
+    #[error(transparent)]
+    TypeError(#[from] TypeError),
+    #[error("Function {0} does not exist")]
+    UnknownFunction(String),
+    #[error("{function} takes {expected} arguments, but was given {found}")]
+    ArgumentCount {
+        function: String,
+        expected: String,
+        found: usize,
+    },
+    #[error("Unsupported expression: {0}")]
+    Unsupported(String),
+}
+
+/// Evaluates a query that doesn't select from any table to its single row, returning `None`
+/// for any other query.
+pub(crate) fn evaluate_query(
+    query: &Query,
+    functions: &FunctionRegistry,
+) -> Option<Result<QueryResult, EvalError>> {
+    let SetExpr::Select(select) = query.body.as_ref() else {
+        return None;
+    };
+    if !select.from.is_empty() {
+        return None;
+    }
+    if select.selection.is_some() || select.having.is_some() {
+        return Some(Err(EvalError::Unsupported(select.to_string())));
+    }
+
+    let mut columns = Vec::with_capacity(select.projection.len());
+    let mut row = Vec::with_capacity(select.projection.len());
+    for item in &select.projection {
+        let (name, expr) = match item {
+            SelectItem::UnnamedExpr(expr) => (expr.to_string(), expr),
+            SelectItem::ExprWithAlias { expr, alias } => (alias.value.clone(), expr),
+            item => return Some(Err(EvalError::Unsupported(item.to_string()))),
+        };
+        match evaluate_with(expr, functions) {
+            Ok(value) => row.push(value),
+            Err(e) => return Some(Err(e)),
+        }
+        columns.push(name);
+    }
+    Some(Ok(QueryResult::new(columns, vec![row])))
+}
+
+/// Evaluates an expression that doesn't refer to any column, calling the built-in functions.
+pub fn evaluate(expr: &SqlExpr) -> Result<DataType, EvalError> {
+    evaluate_with(expr, FunctionRegistry::builtins())
+}
+
+/// Evaluates an expression that doesn't refer to any column, calling the functions of
+/// `functions`.
+pub fn evaluate_with(expr: &SqlExpr, functions: &FunctionRegistry) -> Result<DataType, EvalError> {
+    let evaluate = |expr: &SqlExpr| evaluate_with(expr, functions);
+    match expr {
+        SqlExpr::Value(value) => literal(value),
+        SqlExpr::TypedString { data_type, value } => {
+            let (kind, _) = DataTypeKind::from_sql(&data_type.to_string())?;
+            Ok(DataType::Text(value.clone()).coerce_to(&kind)?)
+        }
+        SqlExpr::Nested(expr) => evaluate(expr),
+        SqlExpr::Function(function) => call(function, functions),
+        SqlExpr::UnaryOp {
+            op: UnaryOperator::Minus,
+            expr,
+        } => negate(&evaluate(expr)?),
+        SqlExpr::UnaryOp {
+            op: UnaryOperator::Plus,
+            expr,
+        } => evaluate(expr),
+        SqlExpr::BinaryOp { left, op, right } => compare(op, &evaluate(left)?, &evaluate(right)?),
+        SqlExpr::Case {
+            operand,
+            conditions,
+            results,
+            else_result,
+        } => case(
+            operand.as_deref(),
+            conditions,
+            results,
+            else_result.as_deref(),
+            functions,
+        ),
+        SqlExpr::Ceil {
+            expr,
+            field: DateTimeField::NoDateTime,
+        } => functions.call("CEIL", &[evaluate(expr)?]),
+        SqlExpr::Floor {
+            expr,
+            field: DateTimeField::NoDateTime,
+        } => functions.call("FLOOR", &[evaluate(expr)?]),
+        SqlExpr::Extract { field, expr } => functions.call(
+            "DATE_PART",
+            &[DataType::Text(field.to_string()), evaluate(expr)?],
+        ),
+        SqlExpr::Substring {
+            expr,
+            substring_from,
+            substring_for,
+            ..
+        } => {
+            let mut args = vec![evaluate(expr)?];
+            match (substring_from, substring_for) {
+                (Some(from), len) => {
+                    args.push(evaluate(from)?);
+                    args.extend(len.as_deref().map(evaluate).transpose()?);
+                }
+                // Substrings start at the first character by default
+                (None, Some(len)) => args.extend([DataType::BigInt(1), evaluate(len)?]),
+                (None, None) => {}
+            }
+            functions.call("SUBSTRING", &args)
+        }
+        SqlExpr::Trim {
+            expr,
+            trim_where,
+            trim_what,
+            trim_characters: None,
+        } => {
+            let function = match trim_where {
+                None | Some(TrimWhereField::Both) => "BTRIM",
+                Some(TrimWhereField::Leading) => "LTRIM",
+                Some(TrimWhereField::Trailing) => "RTRIM",
+            };
+            let mut args = vec![evaluate(expr)?];
+            args.extend(trim_what.as_deref().map(evaluate).transpose()?);
+            functions.call(function, &args)
+        }
+        expr => Err(EvalError::Unsupported(expr.to_string())),
+    }
+}
+
+fn literal(value: &Value) -> Result<DataType, EvalError> {
+    Ok(match value {
+        Value::Number(n, _) => match n.parse::<i64>() {
+            Ok(n) => DataType::BigInt(n),
+            Err(_) => DataType::DoublePrecision(
+                n.parse()
+                    .map_err(|_| EvalError::Unsupported(format!("Invalid number: {}", n)))?,
+            ),
+        },
+        Value::SingleQuotedString(s) => DataType::Text(s.clone()),
+        Value::Boolean(b) => DataType::Boolean(*b),
+        Value::Null => DataType::Null,
+        value => return Err(EvalError::Unsupported(value.to_string())),
+    })
+}
+
+fn call(function: &Function, functions: &FunctionRegistry) -> Result<DataType, EvalError> {
+    let args = function
+        .args
+        .iter()
+        .map(|arg| match arg {
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => evaluate_with(expr, functions),
+            arg => Err(EvalError::Unsupported(arg.to_string())),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    functions.call(&function.name.to_string(), &args)
+}
+
+fn negate(value: &DataType) -> Result<DataType, EvalError> {
+    let overflow = || TypeError::OverflowError {
+        data_type: value.data_type_kind().metadata().name().to_string(),
+    };
+    Ok(match value {
+        DataType::Null => DataType::Null,
+        DataType::SmallInt(val) => DataType::SmallInt(val.checked_neg().ok_or_else(overflow)?),
+        DataType::Integer(val) => DataType::Integer(val.checked_neg().ok_or_else(overflow)?),
+        DataType::BigInt(val) => DataType::BigInt(val.checked_neg().ok_or_else(overflow)?),
+        DataType::Decimal(val) => DataType::Decimal(-*val),
+        DataType::Real(val) => DataType::Real(-val),
+        DataType::DoublePrecision(val) => DataType::DoublePrecision(-val),
+        DataType::Float(val) => DataType::Float(-val),
+        value => {
+            return Err(TypeError::IncompatibleType {
+                expected: "numeric".to_string(),
+                found: value.data_type_kind().metadata().name().to_string(),
+            }
+            .into())
+        }
+    })
+}
+
+/// Compares two values, to NULL if either is NULL.
+pub(crate) fn compare(
+    op: &BinaryOperator,
+    a: &DataType,
+    b: &DataType,
+) -> Result<DataType, EvalError> {
+    compare_with(op, a, b, |_, _| None)
+}
+
+/// Compares two values like [`compare`], except that `order` orders them first (e.g. text
+/// under the collation of a column), falling back to the usual order where it can't.
+pub(crate) fn compare_with(
+    op: &BinaryOperator,
+    a: &DataType,
+    b: &DataType,
+    order: impl Fn(&DataType, &DataType) -> Option<Ordering>,
+) -> Result<DataType, EvalError> {
+    if matches!(a, DataType::Null) || matches!(b, DataType::Null) {
+        return Ok(DataType::Null);
+    }
+    check_compatible(&[a, b])?;
+    let ordering = || {
+        order(a, b)
+            .or_else(|| compare_values(a, b))
+            .ok_or_else(|| TypeError::IncompatibleType {
+                expected: a.data_type_kind().metadata().name().to_string(),
+                found: b.data_type_kind().metadata().name().to_string(),
+            })
+    };
+    Ok(DataType::Boolean(match op {
+        BinaryOperator::Eq => values_equal(a, b),
+        BinaryOperator::NotEq => !values_equal(a, b),
+        BinaryOperator::Lt => ordering()? == Ordering::Less,
+        BinaryOperator::LtEq => ordering()? != Ordering::Greater,
+        BinaryOperator::Gt => ordering()? == Ordering::Greater,
+        BinaryOperator::GtEq => ordering()? != Ordering::Less,
+        op => return Err(EvalError::Unsupported(format!("{} {} {}", a, op, b))),
+    }))
+}
+
+/// Evaluates a searched `CASE` (without an operand), whose conditions must be booleans, or a
+/// simple `CASE`, whose conditions are values compared with the operand.
+fn case(
+    operand: Option<&SqlExpr>,
+    conditions: &[SqlExpr],
+    results: &[SqlExpr],
+    else_result: Option<&SqlExpr>,
+    functions: &FunctionRegistry,
+) -> Result<DataType, EvalError> {
+    let evaluate = |expr: &SqlExpr| evaluate_with(expr, functions);
+    let mut values = results
+        .iter()
+        .map(evaluate)
+        .collect::<Result<Vec<_>, _>>()?;
+    let else_value = else_result.map_or(Ok(DataType::Null), evaluate)?;
+    check_compatible(&values.iter().chain([&else_value]).collect::<Vec<_>>())?;
+
+    let operand = operand.map(evaluate).transpose()?;
+    for (i, condition) in conditions.iter().enumerate() {
+        let condition = evaluate(condition)?;
+        let holds = match &operand {
+            // A NULL operand matches no value, not even NULL
+            Some(operand) => match compare(&BinaryOperator::Eq, operand, &condition)? {
+                DataType::Boolean(equal) => equal,
+                _ => false,
+            },
+            None => match condition {
+                DataType::Boolean(holds) => holds,
+                DataType::Null => false,
+                condition => {
+                    return Err(TypeError::IncompatibleType {
+                        expected: "BOOLEAN".to_string(),
+                        found: condition.data_type_kind().metadata().name().to_string(),
+                    }
+                    .into())
+                }
+            },
+        };
+        if holds {
+            return Ok(values.swap_remove(i));
+        }
+    }
+    Ok(else_value)
+}
+
+/// Fails unless every argument is compatible with every other.
+fn check_compatible(args: &[&DataType]) -> Result<(), TypeError> {
+    for (i, a) in args.iter().enumerate() {
+        if let Some(b) = args[i + 1..].iter().find(|b| !a.is_compatible_with(b)) {
+            return Err(TypeError::IncompatibleType {
+                expected: a.data_type_kind().metadata().name().to_string(),
+                found: b.data_type_kind().metadata().name().to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether two (compatible) values are equal, widening integers of different types.
+fn values_equal(a: &DataType, b: &DataType) -> bool {
+    a == b
+        || b.coerce_to(&a.data_type_kind()).is_ok_and(|b| *a == b)
+        || a.coerce_to(&b.data_type_kind()).is_ok_and(|a| a == *b)
+}
+
+/// Orders two (compatible) values, widening integers of different types.
+fn compare_values(a: &DataType, b: &DataType) -> Option<Ordering> {
+    a.partial_cmp(b)
+        .or_else(|| a.partial_cmp(&b.coerce_to(&a.data_type_kind()).ok()?))
+        .or_else(|| a.coerce_to(&b.data_type_kind()).ok()?.partial_cmp(b))
+}
+
+/// Returns the first argument that isn't NULL, or NULL if they all are.
+pub fn coalesce(args: &[DataType]) -> Result<DataType, TypeError> {
+    check_compatible(&args.iter().collect::<Vec<_>>())?;
+    Ok(args
+        .iter()
+        .find(|arg| !matches!(arg, DataType::Null))
+        .cloned()
+        .unwrap_or(DataType::Null))
+}
+
+/// Returns NULL if `a` equals `b`, and `a` otherwise.
+pub fn nullif(a: &DataType, b: &DataType) -> Result<DataType, TypeError> {
+    check_compatible(&[a, b])?;
+    if matches!(b, DataType::Null) || !values_equal(a, b) {
+        Ok(a.clone())
+    } else {
+        Ok(DataType::Null)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use compile::parser::{parse_sql, Statement};
+
+    fn eval(sql: &str) -> Result<DataType, EvalError> {
+        let ast = parse_sql(&format!("SELECT {}", sql)).unwrap();
+        let [Statement::Query(query)] = ast.as_slice() else {
+            panic!("Not a query: {}", sql);
+        };
+        let result = evaluate_query(query, FunctionRegistry::builtins()).unwrap()?;
+        Ok(result.rows()[0][0].clone())
+    }
+
+    #[test]
+    fn test_coalesce_returns_the_first_non_null_argument() {
+        assert_eq!(
+            eval("COALESCE(NULL, NULL, 3)").unwrap(),
+            DataType::BigInt(3)
+        );
+        assert_eq!(eval("COALESCE(NULL, NULL)").unwrap(), DataType::Null);
+    }
+
+    #[test]
+    fn test_nullif_returns_null_for_equal_arguments() {
+        assert_eq!(eval("NULLIF(5, 5)").unwrap(), DataType::Null);
+        assert_eq!(eval("NULLIF(5, 6)").unwrap(), DataType::BigInt(5));
+        assert_eq!(
+            nullif(&DataType::Integer(5), &DataType::BigInt(5)).unwrap(),
+            DataType::Null
+        );
+    }
+
+    #[test]
+    fn test_searched_case_selects_the_first_branch_that_holds() {
+        assert_eq!(
+            eval("CASE WHEN 1 > 2 THEN 'a' WHEN 2 > 1 THEN 'b' WHEN 3 > 1 THEN 'c' END").unwrap(),
+            DataType::Text("b".to_string())
+        );
+    }
+
+    #[test]
+    fn test_simple_case_compares_the_operand_with_each_value() {
+        assert_eq!(
+            eval("CASE 2 WHEN 1 THEN 'one' WHEN 2 THEN 'two' END").unwrap(),
+            DataType::Text("two".to_string())
+        );
+        assert_eq!(
+            eval("CASE NULL WHEN NULL THEN 'null' END").unwrap(),
+            DataType::Null
+        );
+    }
+
+    #[test]
+    fn test_case_falls_through_to_else() {
+        assert_eq!(
+            eval("CASE WHEN 1 = 2 THEN 10 ELSE 20 END").unwrap(),
+            DataType::BigInt(20)
+        );
+        assert_eq!(eval("CASE WHEN NULL THEN 10 END").unwrap(), DataType::Null);
+        assert!(matches!(
+            eval("CASE WHEN 1 = 1 THEN 10 ELSE 'twenty' END"),
+            Err(EvalError::TypeError(TypeError::IncompatibleType { .. }))
+        ));
+        assert!(matches!(
+            eval("CASE WHEN 1 THEN 10 END"),
+            Err(EvalError::TypeError(TypeError::IncompatibleType { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_string_functions() {
+        let text = |s: &str| DataType::Text(s.to_string());
+        assert_eq!(eval("UPPER('abc')").unwrap(), text("ABC"));
+        assert_eq!(eval("lower('ÀBC')").unwrap(), text("àbc"));
+        assert_eq!(eval("LENGTH('crème')").unwrap(), DataType::Integer(5));
+        assert_eq!(eval("SUBSTRING('database' FROM 5)").unwrap(), text("base"));
+        assert_eq!(
+            eval("SUBSTRING('database' FROM 0 FOR 3)").unwrap(),
+            text("da")
+        );
+        assert_eq!(eval("SUBSTRING('database', 2, 3)").unwrap(), text("ata"));
+        assert_eq!(eval("TRIM('  padded  ')").unwrap(), text("padded"));
+        assert_eq!(
+            eval("TRIM(LEADING 'x' FROM 'xxpaddedxx')").unwrap(),
+            text("paddedxx")
+        );
+        assert_eq!(
+            eval("TRIM(TRAILING 'xy' FROM 'xxpaddedyx')").unwrap(),
+            text("xxpadded")
+        );
+        assert_eq!(eval("CONCAT('a', 'b', 1, TRUE)").unwrap(), text("ab1true"));
+
+        assert!(matches!(
+            eval("UPPER(1)"),
+            Err(EvalError::TypeError(TypeError::IncompatibleType { .. }))
+        ));
+        assert!(matches!(
+            eval("SUBSTRING('database' FROM 1 FOR -1)"),
+            Err(EvalError::TypeError(TypeError::InvalidArgument { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_string_functions_propagate_null() {
+        for sql in [
+            "UPPER(NULL)",
+            "LOWER(NULL)",
+            "LENGTH(NULL)",
+            "SUBSTRING(NULL FROM 1 FOR 2)",
+            "SUBSTRING('database' FROM NULL)",
+            "SUBSTRING('database' FROM 1 FOR NULL)",
+            "TRIM(NULL)",
+            "TRIM(BOTH NULL FROM 'x')",
+            "CONCAT('a', NULL, 'b')",
+        ] {
+            assert_eq!(eval(sql).unwrap(), DataType::Null, "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_math_functions() {
+        let decimal = |val: &str| DataType::Decimal(val.parse().unwrap());
+        assert_eq!(eval("ABS(-5)").unwrap(), DataType::BigInt(5));
+        assert_eq!(eval("ABS(-2.5)").unwrap(), DataType::DoublePrecision(2.5));
+        assert_eq!(math::abs(&decimal("-2.50")).unwrap(), decimal("2.50"));
+        assert!(matches!(
+            math::abs(&DataType::SmallInt(i16::MIN)),
+            Err(TypeError::OverflowError { .. })
+        ));
+
+        assert_eq!(eval("ROUND(7)").unwrap(), DataType::BigInt(7));
+        assert_eq!(eval("ROUND(2.5)").unwrap(), DataType::DoublePrecision(3.0));
+        assert_eq!(
+            eval("ROUND(-2.5)").unwrap(),
+            DataType::DoublePrecision(-3.0)
+        );
+        assert_eq!(
+            eval("ROUND(2.71828, 2)").unwrap(),
+            DataType::DoublePrecision(2.72)
+        );
+        // Rounding a `Decimal` keeps it a `Decimal`
+        assert_eq!(
+            math::round(&decimal("3.145"), Some(&DataType::Integer(2))).unwrap(),
+            decimal("3.15")
+        );
+        assert_eq!(math::round(&decimal("-0.5"), None).unwrap(), decimal("-1"));
+
+        assert_eq!(eval("CEIL(4)").unwrap(), DataType::BigInt(4));
+        assert_eq!(eval("CEIL(4.2)").unwrap(), DataType::DoublePrecision(5.0));
+        assert_eq!(
+            eval("CEILING(-4.2)").unwrap(),
+            DataType::DoublePrecision(-4.0)
+        );
+        assert_eq!(
+            eval("FLOOR(-4.2)").unwrap(),
+            DataType::DoublePrecision(-5.0)
+        );
+        assert_eq!(math::ceil(&decimal("1.01")).unwrap(), decimal("2"));
+        assert_eq!(math::floor(&decimal("-1.01")).unwrap(), decimal("-2"));
+
+        assert_eq!(eval("MOD(10, 3)").unwrap(), DataType::BigInt(1));
+        assert_eq!(eval("MOD(-10, 3)").unwrap(), DataType::BigInt(-1));
+        assert_eq!(eval("MOD(5.5, 2)").unwrap(), DataType::DoublePrecision(1.5));
+        assert_eq!(
+            math::modulo(&decimal("5.5"), &DataType::Integer(2)).unwrap(),
+            decimal("1.5")
+        );
+        assert_eq!(
+            math::modulo(&DataType::SmallInt(7), &DataType::Integer(4)).unwrap(),
+            DataType::Integer(3)
+        );
+        assert!(matches!(
+            eval("MOD(1, 0)"),
+            Err(EvalError::TypeError(TypeError::DivisionByZero))
+        ));
+
+        // Integer powers are floating-point, as negative exponents need not yield integers
+        assert_eq!(
+            eval("POWER(2, 10)").unwrap(),
+            DataType::DoublePrecision(1024.0)
+        );
+        assert_eq!(
+            eval("POWER(2, -1)").unwrap(),
+            DataType::DoublePrecision(0.5)
+        );
+        assert_eq!(
+            eval("POWER(9, 0.5)").unwrap(),
+            DataType::DoublePrecision(3.0)
+        );
+        assert_eq!(
+            math::power(&decimal("1.5"), &DataType::Integer(2)).unwrap(),
+            decimal("2.25")
+        );
+        assert!(matches!(
+            eval("POWER(0, -1)"),
+            Err(EvalError::TypeError(TypeError::InvalidArgument { .. }))
+        ));
+        assert!(matches!(
+            eval("POWER(-8, 0.5)"),
+            Err(EvalError::TypeError(TypeError::InvalidArgument { .. }))
+        ));
+        assert!(matches!(
+            eval("ABS('five')"),
+            Err(EvalError::TypeError(TypeError::IncompatibleType { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_math_functions_propagate_null() {
+        for sql in [
+            "ABS(NULL)",
+            "ROUND(NULL)",
+            "ROUND(1.5, NULL)",
+            "CEIL(NULL)",
+            "FLOOR(NULL)",
+            "MOD(NULL, 2)",
+            "MOD(2, NULL)",
+            "POWER(NULL, 2)",
+            "POWER(2, NULL)",
+        ] {
+            assert_eq!(eval(sql).unwrap(), DataType::Null, "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_date_trunc_zeroes_the_time_of_day() {
+        let datetime = |s: &str| {
+            DataType::Text(s.to_string())
+                .coerce_to(&DataTypeKind::DateTime)
+                .unwrap()
+        };
+        assert_eq!(
+            eval("DATE_TRUNC('day', TIMESTAMP '2024-03-15 10:30:45')").unwrap(),
+            datetime("2024-03-15 00:00:00")
+        );
+        assert_eq!(
+            eval("DATE_TRUNC('day', '2024-03-15 23:59:59')").unwrap(),
+            datetime("2024-03-15 00:00:00")
+        );
+        assert_eq!(eval("DATE_TRUNC('day', NULL)").unwrap(), DataType::Null);
+    }
+
+    #[test]
+    fn test_extract_returns_the_field_as_an_integer() {
+        let extract = |field: &str| {
+            eval(&format!(
+                "EXTRACT({} FROM TIMESTAMP '2024-03-15 10:30:45')",
+                field
+            ))
+        };
+        assert_eq!(extract("YEAR").unwrap(), DataType::Integer(2024));
+        assert_eq!(extract("MONTH").unwrap(), DataType::Integer(3));
+        assert_eq!(extract("DAY").unwrap(), DataType::Integer(15));
+        assert_eq!(extract("HOUR").unwrap(), DataType::Integer(10));
+        assert_eq!(extract("DOW").unwrap(), DataType::Integer(5));
+        assert_eq!(eval("EXTRACT(YEAR FROM NULL)").unwrap(), DataType::Null);
+        assert!(matches!(
+            eval("EXTRACT(YEAR FROM 2024)"),
+            Err(EvalError::TypeError(TypeError::IncompatibleType { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_now_returns_the_current_timestamp() {
+        let DataType::TimestampTz(now) = eval("NOW()").unwrap() else {
+            panic!("NOW() is not a timestamp");
+        };
+        let elapsed = chrono::Utc::now() - now;
+        assert!(elapsed >= chrono::Duration::zero() && elapsed < chrono::Duration::minutes(1));
+        assert!(matches!(
+            eval("CURRENT_TIMESTAMP").unwrap(),
+            DataType::TimestampTz(_)
+        ));
+        assert!(eval("NOW(1)").is_err());
+    }
+
+    #[test]
+    fn test_incompatible_arguments_are_rejected() {
+        assert!(matches!(
+            eval("COALESCE(NULL, 1, 'one')"),
+            Err(EvalError::TypeError(TypeError::IncompatibleType { .. }))
+        ));
+        assert!(matches!(
+            eval("NULLIF(1)"),
+            Err(EvalError::ArgumentCount { found: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,199 @@
+//! The scalar functions expressions can call, by name. Besides the built-in functions, any
+//! function over values of the type system can be registered (e.g. with
+//! [`QueryEngine::register_function`](crate::QueryEngine::register_function)).
+
+use super::{coalesce, datetime, math, nullif, string, EvalError};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+use string::TrimSide;
+use ty::{DataType, TypeError};
+
+/// The implementation of a scalar function, called with as many arguments as its [`Arity`]
+/// allows.
+pub type ScalarFunction = Box<dyn Fn(&[DataType]) -> Result<DataType, TypeError> + Send + Sync>;
+
+/// The number of arguments a function takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exact(usize),
+    /// Between the two numbers, inclusive
+    Range(usize, usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    /// Returns whether a function of this arity can be called with `count` arguments.
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Exact(n) => count == n,
+            Arity::Range(min, max) => (min..=max).contains(&count),
+            Arity::AtLeast(min) => count >= min,
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Arity::Exact(n) => write!(f, "{}", n),
+            Arity::Range(min, max) if max == min + 1 => write!(f, "{} or {}", min, max),
+            Arity::Range(min, max) => write!(f, "{} to {}", min, max),
+            Arity::AtLeast(min) => write!(f, "at least {}", min),
+        }
+    }
+}
+
+struct RegisteredFunction {
+    arity: Arity,
+    function: ScalarFunction,
+}
+
+/// Maps (case-insensitive) function names to their implementations.
+#[derive(Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, RegisteredFunction>,
+}
+
+impl fmt::Debug for FunctionRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names = self.functions.keys().collect::<Vec<_>>();
+        names.sort();
+        f.debug_struct("FunctionRegistry")
+            .field("functions", &names)
+            .finish()
+    }
+}
+
+impl FunctionRegistry {
+    /// Creates a registry without any functions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a registry of the built-in functions.
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry
+            .register("UPPER", Arity::Exact(1), unary(string::upper))
+            .register("LOWER", Arity::Exact(1), unary(string::lower))
+            .register("LENGTH", Arity::Exact(1), unary(string::length))
+            .register("CHAR_LENGTH", Arity::Exact(1), unary(string::length))
+            .register("CHARACTER_LENGTH", Arity::Exact(1), unary(string::length))
+            .register("SUBSTRING", Arity::Range(1, 3), |args| {
+                string::substring(&args[0], args.get(1), args.get(2))
+            })
+            .register("BTRIM", Arity::Range(1, 2), trim(TrimSide::Both))
+            .register("LTRIM", Arity::Range(1, 2), trim(TrimSide::Leading))
+            .register("RTRIM", Arity::Range(1, 2), trim(TrimSide::Trailing))
+            .register("CONCAT", Arity::AtLeast(0), string::concat)
+            .register("ABS", Arity::Exact(1), unary(math::abs))
+            .register("CEIL", Arity::Exact(1), unary(math::ceil))
+            .register("CEILING", Arity::Exact(1), unary(math::ceil))
+            .register("FLOOR", Arity::Exact(1), unary(math::floor))
+            .register("ROUND", Arity::Range(1, 2), |args| {
+                math::round(&args[0], args.get(1))
+            })
+            .register("MOD", Arity::Exact(2), binary(math::modulo))
+            .register("POWER", Arity::Exact(2), binary(math::power))
+            .register("POW", Arity::Exact(2), binary(math::power))
+            .register("NOW", Arity::Exact(0), |_| Ok(datetime::now()))
+            .register(
+                "CURRENT_TIMESTAMP",
+                Arity::Exact(0),
+                |_| Ok(datetime::now()),
+            )
+            .register("DATE_TRUNC", Arity::Exact(2), binary(datetime::date_trunc))
+            .register("DATE_PART", Arity::Exact(2), binary(datetime::date_part))
+            .register("COALESCE", Arity::AtLeast(1), coalesce)
+            .register("NULLIF", Arity::Exact(2), binary(nullif));
+        registry
+    }
+
+    /// Returns the registry of the built-in functions, shared by all evaluations that don't
+    /// use registries of their own.
+    pub fn builtins() -> &'static FunctionRegistry {
+        static BUILTINS: OnceLock<FunctionRegistry> = OnceLock::new();
+        BUILTINS.get_or_init(FunctionRegistry::with_builtins)
+    }
+
+    /// Registers a function, replacing any function of the same name.
+    pub fn register<F>(&mut self, name: &str, arity: Arity, function: F) -> &mut Self
+    where
+        F: Fn(&[DataType]) -> Result<DataType, TypeError> + Send + Sync + 'static,
+    {
+        self.functions.insert(
+            name.to_uppercase(),
+            RegisteredFunction {
+                arity,
+                function: Box::new(function),
+            },
+        );
+        self
+    }
+
+    /// Returns the arity of a function, or `None` if there is no function of that name.
+    pub fn arity(&self, name: &str) -> Option<Arity> {
+        self.functions
+            .get(&name.to_uppercase())
+            .map(|registered| registered.arity)
+    }
+
+    /// Calls a function, failing if there is no function of that name or if it doesn't take
+    /// as many arguments as it is given.
+    pub fn call(&self, name: &str, args: &[DataType]) -> Result<DataType, EvalError> {
+        let name = name.to_uppercase();
+        let Some(registered) = self.functions.get(&name) else {
+            return Err(EvalError::UnknownFunction(name));
+        };
+        if !registered.arity.accepts(args.len()) {
+            return Err(EvalError::ArgumentCount {
+                function: name,
+                expected: registered.arity.to_string(),
+                found: args.len(),
+            });
+        }
+        Ok((registered.function)(args)?)
+    }
+}
+
+fn unary(
+    f: fn(&DataType) -> Result<DataType, TypeError>,
+) -> impl Fn(&[DataType]) -> Result<DataType, TypeError> {
+    move |args| f(&args[0])
+}
+
+fn binary(
+    f: fn(&DataType, &DataType) -> Result<DataType, TypeError>,
+) -> impl Fn(&[DataType]) -> Result<DataType, TypeError> {
+    move |args| f(&args[0], &args[1])
+}
+
+fn trim(side: TrimSide) -> impl Fn(&[DataType]) -> Result<DataType, TypeError> {
+    move |args| string::trim(&args[0], side, args.get(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arity_is_checked_before_calling() {
+        let mut registry = FunctionRegistry::new();
+        registry.register("first", Arity::Range(1, 2), |args| Ok(args[0].clone()));
+
+        assert_eq!(registry.arity("FIRST"), Some(Arity::Range(1, 2)));
+        assert_eq!(
+            registry.call("First", &[DataType::Integer(1)]).unwrap(),
+            DataType::Integer(1)
+        );
+        assert!(matches!(
+            registry.call("first", &[]),
+            Err(EvalError::ArgumentCount { found: 0, ref expected, .. }) if expected == "1 or 2"
+        ));
+        assert!(matches!(
+            registry.call("second", &[]),
+            Err(EvalError::UnknownFunction(name)) if name == "SECOND"
+        ));
+    }
+}
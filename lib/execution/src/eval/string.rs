@@ -0,0 +1,171 @@
+//! Text functions. They accept `TEXT` and `VARCHAR` values alike and, where they return
+//! text, return it as the type they were given. A NULL argument yields NULL.
+
+use ty::{DataType, TypeError};
+
+/// The characters `TRIM` removes unless told otherwise.
+const DEFAULT_TRIM_CHARACTERS: &str = " ";
+
+/// Which ends of a string `TRIM` removes characters from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrimSide {
+    Both,
+    Leading,
+    Trailing,
+}
+
+fn not_textual(value: &DataType) -> TypeError {
+    TypeError::IncompatibleType {
+        expected: "TEXT".to_string(),
+        found: value.data_type_kind().metadata().name().to_string(),
+    }
+}
+
+/// Applies `f` to the text of a value, keeping its type, or passes NULL through.
+fn map_text(value: &DataType, f: impl FnOnce(&str) -> String) -> Result<DataType, TypeError> {
+    match value {
+        DataType::Null => Ok(DataType::Null),
+        DataType::Text(s) => Ok(DataType::Text(f(s))),
+        DataType::VarChar(s) => Ok(DataType::VarChar(f(s))),
+        value => Err(not_textual(value)),
+    }
+}
+
+fn integer(value: &DataType) -> Result<i64, TypeError> {
+    match value {
+        DataType::SmallInt(val) | DataType::SmallSerial(val) => Ok(*val as i64),
+        DataType::Integer(val) | DataType::Serial(val) => Ok(*val as i64),
+        DataType::BigInt(val) | DataType::BigSerial(val) => Ok(*val),
+        value => Err(TypeError::IncompatibleType {
+            expected: "INTEGER".to_string(),
+            found: value.data_type_kind().metadata().name().to_string(),
+        }),
+    }
+}
+
+pub fn upper(value: &DataType) -> Result<DataType, TypeError> {
+    map_text(value, str::to_uppercase)
+}
+
+pub fn lower(value: &DataType) -> Result<DataType, TypeError> {
+    map_text(value, str::to_lowercase)
+}
+
+/// Returns the number of characters (not bytes) of a string, as an `INTEGER`.
+pub fn length(value: &DataType) -> Result<DataType, TypeError> {
+    match value {
+        DataType::Null => Ok(DataType::Null),
+        DataType::Text(s) | DataType::VarChar(s) => Ok(DataType::Integer(
+            i32::try_from(s.chars().count()).map_err(|_| TypeError::OverflowError {
+                data_type: "INTEGER".to_string(),
+            })?,
+        )),
+        value => Err(not_textual(value)),
+    }
+}
+
+/// Returns the characters of a string from the (1-based) position `from`, up to `len` of
+/// them, like `SUBSTRING(s FROM from FOR len)`.
+///
+/// As in PostgreSQL, the range may start before the first character, in which case only the
+/// part of it within the string is returned, and a negative length is an error.
+pub fn substring(
+    value: &DataType,
+    from: Option<&DataType>,
+    len: Option<&DataType>,
+) -> Result<DataType, TypeError> {
+    if [from, len]
+        .into_iter()
+        .flatten()
+        .any(|arg| matches!(arg, DataType::Null))
+    {
+        return Ok(DataType::Null);
+    }
+    let from = from.map(integer).transpose()?.unwrap_or(1);
+    let end = match len.map(integer).transpose()? {
+        Some(len) if len < 0 => {
+            return Err(TypeError::InvalidArgument {
+                function: "SUBSTRING".to_string(),
+                reason: format!("negative length {}", len),
+            })
+        }
+        Some(len) => Some(from.saturating_add(len)),
+        None => None,
+    };
+
+    let skip = usize::try_from(from.saturating_sub(1)).unwrap_or(0);
+    let take = end.map_or(usize::MAX, |end| {
+        usize::try_from(end - from.max(1)).unwrap_or(0)
+    });
+    map_text(value, |s| s.chars().skip(skip).take(take).collect())
+}
+
+/// Removes the longest run of characters in `characters` (spaces by default) from the given
+/// side(s) of a string.
+pub fn trim(
+    value: &DataType,
+    side: TrimSide,
+    characters: Option<&DataType>,
+) -> Result<DataType, TypeError> {
+    let characters = match characters {
+        None => DEFAULT_TRIM_CHARACTERS,
+        Some(DataType::Null) => return Ok(DataType::Null),
+        Some(DataType::Text(s) | DataType::VarChar(s)) => s.as_str(),
+        Some(value) => return Err(not_textual(value)),
+    };
+    let trimmed = |c: char| characters.contains(c);
+    map_text(value, |s| {
+        match side {
+            TrimSide::Both => s.trim_matches(trimmed),
+            TrimSide::Leading => s.trim_start_matches(trimmed),
+            TrimSide::Trailing => s.trim_end_matches(trimmed),
+        }
+        .to_string()
+    })
+}
+
+/// Concatenates the text of its arguments, which may be of any type, into a `TEXT`. Like the
+/// `||` operator (and unlike PostgreSQL's `CONCAT`), a NULL argument makes the result NULL.
+pub fn concat(args: &[DataType]) -> Result<DataType, TypeError> {
+    let mut result = String::new();
+    for arg in args {
+        match arg {
+            DataType::Null => return Ok(DataType::Null),
+            DataType::Text(s) | DataType::VarChar(s) => result.push_str(s),
+            arg => result.push_str(&arg.to_string()),
+        }
+    }
+    Ok(DataType::Text(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> DataType {
+        DataType::Text(s.to_string())
+    }
+
+    #[test]
+    fn test_substring_boundaries() {
+        let sub = |s: &str, from: Option<i64>, len: Option<i64>| {
+            let from = from.map(DataType::BigInt);
+            let len = len.map(DataType::BigInt);
+            substring(&text(s), from.as_ref(), len.as_ref())
+        };
+
+        assert_eq!(sub("database", Some(5), None).unwrap(), text("base"));
+        assert_eq!(sub("database", Some(1), Some(4)).unwrap(), text("data"));
+        // Positions before the start of the string count towards the length
+        assert_eq!(sub("database", Some(-1), Some(4)).unwrap(), text("da"));
+        assert_eq!(sub("database", Some(0), None).unwrap(), text("database"));
+        assert_eq!(sub("database", Some(7), Some(10)).unwrap(), text("se"));
+        assert_eq!(sub("database", Some(20), Some(2)).unwrap(), text(""));
+        assert_eq!(sub("database", Some(3), Some(0)).unwrap(), text(""));
+        assert_eq!(sub("database", None, Some(2)).unwrap(), text("da"));
+        assert!(sub("database", Some(1), Some(-1)).is_err());
+
+        // Positions count characters, not bytes
+        assert_eq!(sub("crème brûlée", Some(3), Some(3)).unwrap(), text("ème"));
+    }
+}
@@ -1,13 +1,68 @@
+mod ddl;
+mod dml;
+mod eval;
 mod experimental;
+mod planner;
+mod result;
+mod scan;
+mod subquery;
 
-use compile::parser::{parse_sql, Statement};
+use buffer::BufferPoolManagerRef;
+use catalog::{schema::SchemaRef, Catalog};
+use compile::parser::{parse_sql, ParseError, Statement};
 use datafusion_expr::LogicalPlan;
 use regex::Regex;
 // use datafusion::datasource::file_format::file_compression_type::FileCompressionType;
+use arrow::{array::ArrayRef, datatypes::DataType};
 use core::fmt;
+use datafusion::physical_expr::functions::make_scalar_function;
+use datafusion::physical_plan::{display::DisplayableExecutionPlan, ExecutionPlan};
 use datafusion::prelude::*;
-use datafusion_common::{plan_err, DataFusionError, Result, ScalarValue};
-use tracing::{debug, instrument, trace};
+use datafusion_common::{DataFusionError, Result, ScalarValue};
+use datafusion_expr::Volatility;
+use futures::{future::BoxFuture, StreamExt};
+use std::{
+    error::Error,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    time::Instant,
+};
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, instrument, trace};
+
+pub use ddl::{CatalogPersister, DEFAULT_VARIABLE_LENGTH};
+pub use eval::{evaluate, evaluate_with, Arity, EvalError, FunctionRegistry, ScalarFunction};
+pub use result::QueryResult;
+pub use subquery::{is_cardinality_violation, CardinalityViolation};
+
+/// The sending half of a channel that receives the rows of a query as they are produced.
+pub type RowSender = mpsc::Sender<Vec<ty::DataType>>;
+
+/// Source of the suffixes that give every external query its own table name.
+static NEXT_EXTERNAL_TABLE_ID: AtomicU64 = AtomicU64::new(1);
+
+/// The error a query fails with when it is aborted through its [`CancellationToken`].
+#[derive(Error, Debug)]
+#[error("Query cancelled")]
+pub struct QueryCancelled;
+
+/// Returns whether `error` reports a query aborted through its [`CancellationToken`].
+pub fn is_cancelled(error: &DataFusionError) -> bool {
+    matches!(error, DataFusionError::External(e) if e.is::<QueryCancelled>())
+}
+
+/// Returns the syntax error `error` reports, if the query failed to parse.
+pub fn parse_error(error: &DataFusionError) -> Option<&ParseError> {
+    match error {
+        DataFusionError::External(e) => e.downcast_ref(),
+        _ => None,
+    }
+}
 
 pub enum ExternalDataSource {
     CSV,
@@ -15,8 +70,69 @@ pub enum ExternalDataSource {
     ORC,
 }
 
+/// A statement planned once by [`QueryEngine::prepare`], which can be executed any number of
+/// times with different parameters by [`QueryEngine::execute_prepared`].
+#[derive(Debug)]
+pub struct PreparedStatement {
+    plan: LogicalPlan,
+    // The table the external file read by the statement is registered as until deallocation
+    external_table: Option<String>,
+}
+
+impl PreparedStatement {
+    /// Returns the plan of the statement, with its parameters still unbound.
+    pub fn plan(&self) -> &LogicalPlan {
+        &self.plan
+    }
+
+    /// Returns the types the parameters of the statement (`$1`, `$2`, ...) were inferred to
+    /// have, in order. Parameters whose type couldn't be inferred are `None`.
+    pub fn parameter_types(&self) -> Result<Vec<Option<DataType>>> {
+        let mut types = self
+            .plan
+            .get_parameter_types()?
+            .into_iter()
+            .map(|(id, data_type)| {
+                let index = id[1..].parse::<usize>().map_err(|_| {
+                    DataFusionError::Plan(format!("Invalid parameter placeholder: {}", id))
+                })?;
+                Ok((index, data_type))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        types.sort_by_key(|(index, _)| *index);
+        Ok(types.into_iter().map(|(_, data_type)| data_type).collect())
+    }
+}
+
+/// A prepared statement whose parameters were bound by [`QueryEngine::bind`], ready to be
+/// executed by [`QueryEngine::execute_bound`].
+#[derive(Debug, Clone)]
+pub struct BoundStatement {
+    plan: LogicalPlan,
+    // Whether the statement reads an external file, which stays registered until the
+    // prepared statement is deallocated
+    external: bool,
+}
+
+impl BoundStatement {
+    /// Returns the plan of the statement, with its parameters substituted by their values.
+    pub fn plan(&self) -> &LogicalPlan {
+        &self.plan
+    }
+}
+
 pub struct QueryEngine {
     context: SessionContext,
+    // The tables created by `CREATE TABLE`, which queries are planned against
+    catalog: Catalog,
+    // Persists the tables of the catalog after DDL changes them
+    catalog_persister: RwLock<Option<CatalogPersister>>,
+    // Holds the heap pages the rows of the tables of the catalog are stored in
+    buffer_pool: RwLock<Option<BufferPoolManagerRef>>,
+    // Number of statements parsed and planned so far
+    statements_planned: AtomicU64,
+    // The functions queries without a `FROM` clause can call
+    functions: RwLock<FunctionRegistry>,
     // TODO: Add other fields as necessary,
     // buffer manager, storage layer, etc.
 }
@@ -31,18 +147,316 @@ impl QueryEngine {
     pub fn new() -> Self {
         QueryEngine {
             context: SessionContext::new(),
+            catalog: Catalog::new(),
+            catalog_persister: RwLock::new(None),
+            buffer_pool: RwLock::new(None),
+            statements_planned: AtomicU64::new(0),
+            functions: RwLock::new(FunctionRegistry::with_builtins()),
             // Initialize other components
         }
     }
 
-    pub async fn execute_query(&self, sql: &str) -> Result<()> {
+    /// Registers a user-defined scalar function that can be called by `name` in SQL.
+    ///
+    /// `fun` receives one array per argument (all of the same length) and must return an
+    /// array of `return_type` with one value per input row. The function is assumed to be
+    /// deterministic, so calls with constant arguments may be folded during planning.
+    pub fn register_udf<F>(
+        &self,
+        name: &str,
+        input_types: Vec<DataType>,
+        return_type: DataType,
+        fun: F,
+    ) where
+        F: Fn(&[ArrayRef]) -> Result<ArrayRef> + Send + Sync + 'static,
+    {
+        debug!("Registering scalar function `{}`", name);
+        self.context.register_udf(create_udf(
+            name,
+            input_types,
+            Arc::new(return_type),
+            Volatility::Immutable,
+            make_scalar_function(fun),
+        ));
+    }
+
+    /// Registers a scalar function over values of the type system that can be called by
+    /// `name` in queries without a `FROM` clause, replacing any function of that name
+    /// (built-in or not).
+    pub fn register_function<F>(&self, name: &str, arity: Arity, fun: F)
+    where
+        F: Fn(&[ty::DataType]) -> std::result::Result<ty::DataType, ty::TypeError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        debug!("Registering function `{}`", name);
+        self.functions.write().unwrap().register(name, arity, fun);
+    }
+
+    /// Registers the schema of a catalog table, so that queries reading from `name` can be
+    /// planned.
+    pub fn register_table_schema(&self, name: &str, schema: SchemaRef) {
+        self.catalog.register_table(name, schema);
+    }
+
+    /// Returns the catalog of the tables created by `CREATE TABLE`, which queries are planned
+    /// against.
+    pub fn catalog(&self) -> &Catalog {
+        &self.catalog
+    }
+
+    /// Sets the function persisting the tables of the catalog whenever `CREATE TABLE` or
+    /// `DROP TABLE` changes them. A statement whose change fails to persist fails, and its
+    /// change is undone.
+    pub fn set_catalog_persister<F>(&self, persister: F)
+    where
+        F: Fn(&Catalog) -> Result<(), Box<dyn Error + Send + Sync>> + Send + Sync + 'static,
+    {
+        *self.catalog_persister.write().unwrap() = Some(Box::new(persister));
+    }
+
+    /// Sets the buffer pool holding the heap pages the rows of the tables created by `CREATE
+    /// TABLE` are stored in. Without one, rows can't be inserted into those tables.
+    pub fn set_buffer_pool(&self, buffer_pool: BufferPoolManagerRef) {
+        *self.buffer_pool.write().unwrap() = Some(buffer_pool);
+    }
+
+    /// Adds the tables of a catalog (e.g. one decoded from the system catalog page) as if
+    /// they had been created by `CREATE TABLE`, without persisting them again.
+    pub fn restore_tables(&self, catalog: &Catalog) -> Result<()> {
+        for name in catalog.table_names() {
+            let Some(table) = catalog.get_table(&name) else {
+                continue;
+            };
+            self.catalog
+                .insert_table(table)
+                .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the tables of the engine with those of a catalog (e.g. one restored from the
+    /// system catalog page once changes to it have been undone), without persisting them.
+    pub fn replace_tables(&self, catalog: &Catalog) -> Result<()> {
+        for name in self.catalog.table_names() {
+            self.catalog
+                .drop_table(&name)
+                .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        }
+        self.restore_tables(catalog)
+    }
+
+    /// Returns the number of statements the engine has parsed and planned so far.
+    pub fn statements_planned(&self) -> u64 {
+        self.statements_planned.load(Ordering::Relaxed)
+    }
+
+    /// Plans a query with parameters (`$1`, `$2`, ...) without executing it. The external
+    /// file a query reads from stays registered until the statement is deallocated.
+    pub async fn prepare(&self, sql: &str) -> Result<PreparedStatement> {
+        self.statements_planned.fetch_add(1, Ordering::Relaxed);
+        if !self.is_external_datasource(sql) {
+            let plan = self.create_logical_plan(&Self::parse(sql)?)?;
+            return Ok(PreparedStatement {
+                plan,
+                external_table: None,
+            });
+        }
+
+        let (rewritten_query, table_name, file_path, format) = self.rewrite_query(sql)?;
+        self.register_external_table(&table_name, &file_path, format)
+            .await?;
+        match self
+            .context
+            .state()
+            .create_logical_plan(&rewritten_query)
+            .await
+        {
+            Ok(plan) => Ok(PreparedStatement {
+                plan,
+                external_table: Some(table_name),
+            }),
+            Err(e) => {
+                self.context.deregister_table(table_name.as_str())?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Executes a prepared statement, binding `params` to its parameters in order.
+    pub async fn execute_prepared(
+        &self,
+        statement: &PreparedStatement,
+        params: &[ty::DataType],
+    ) -> Result<QueryResult> {
+        self.execute_bound(&self.bind(statement, params)?).await
+    }
+
+    /// Binds `params` to the parameters of a prepared statement in order, casting each value
+    /// to the type its parameter was inferred to have. Fails unless exactly one value is
+    /// given per parameter.
+    pub fn bind(
+        &self,
+        statement: &PreparedStatement,
+        params: &[ty::DataType],
+    ) -> Result<BoundStatement> {
+        let types = statement.parameter_types()?;
+        if params.len() != types.len() {
+            return Err(DataFusionError::Plan(format!(
+                "{} parameters were bound, but the prepared statement requires {}",
+                params.len(),
+                types.len()
+            )));
+        }
+
+        let values = params
+            .iter()
+            .zip(types)
+            .map(|(param, data_type)| {
+                let value = result::scalar_value(param);
+                match data_type {
+                    Some(data_type) if value.data_type() != data_type => {
+                        let array = arrow::compute::cast(&value.to_array()?, &data_type)?;
+                        ScalarValue::try_from_array(&array, 0)
+                    }
+                    _ => Ok(value),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(BoundStatement {
+            plan: statement.plan.clone().with_param_values(values)?,
+            external: statement.external_table.is_some(),
+        })
+    }
+
+    /// Executes a statement bound by [`QueryEngine::bind`].
+    pub async fn execute_bound(&self, statement: &BoundStatement) -> Result<QueryResult> {
+        self.run_bound(statement, &CancellationToken::new(), None)
+            .await
+    }
+
+    /// Like [`QueryEngine::execute_bound`], but can be aborted by cancelling `token` and sends
+    /// every row to `rows` as soon as it is produced (see
+    /// [`QueryEngine::execute_query_streaming`]).
+    pub async fn execute_bound_streaming(
+        &self,
+        statement: &BoundStatement,
+        token: &CancellationToken,
+        rows: &RowSender,
+    ) -> Result<QueryResult> {
+        self.run_bound(statement, token, Some(rows)).await
+    }
+
+    async fn run_bound(
+        &self,
+        statement: &BoundStatement,
+        token: &CancellationToken,
+        rows: Option<&RowSender>,
+    ) -> Result<QueryResult> {
+        if statement.external {
+            let df = Self::cancellable(
+                self.context.execute_logical_plan(statement.plan.clone()),
+                token,
+            )
+            .await?;
+            Self::collect(df, token, rows).await
+        } else {
+            let plan = self.optimize_plan(&statement.plan)?;
+            let result = Self::cancellable(self.execute_optimized_plan(&plan), token).await?;
+            Self::send_result(result, token, rows).await
+        }
+    }
+
+    /// Releases the resources held by a prepared statement.
+    pub fn deallocate(&self, statement: &PreparedStatement) -> Result<()> {
+        if let Some(table_name) = &statement.external_table {
+            self.context.deregister_table(table_name.as_str())?;
+        }
+        Ok(())
+    }
+
+    pub async fn execute_query(&self, sql: &str) -> Result<QueryResult> {
+        self.execute_query_with_cancellation(sql, &CancellationToken::new())
+            .await
+    }
+
+    /// Executes a query that can be aborted by cancelling `token`. The token is checked
+    /// whenever execution awaits (e.g. between record batches), and a cancelled query fails
+    /// with [`QueryCancelled`] (see [`is_cancelled`]).
+    pub async fn execute_query_with_cancellation(
+        &self,
+        sql: &str,
+        token: &CancellationToken,
+    ) -> Result<QueryResult> {
+        self.execute(sql, token, None).await
+    }
+
+    /// Like [`QueryEngine::execute_query_with_cancellation`], but sends every row to `rows` as
+    /// soon as it is produced rather than collecting it, so the result returned only names the
+    /// columns. Execution waits for the receiver whenever the channel is full.
+    pub async fn execute_query_streaming(
+        &self,
+        sql: &str,
+        token: &CancellationToken,
+        rows: &RowSender,
+    ) -> Result<QueryResult> {
+        self.execute(sql, token, Some(rows)).await
+    }
+
+    async fn execute(
+        &self,
+        sql: &str,
+        token: &CancellationToken,
+        rows: Option<&RowSender>,
+    ) -> Result<QueryResult> {
         // Determine the type of query (internal database table or external file (CSV, Parquet, etc.))
         if self.is_external_datasource(sql) {
             // Delegate to DataFusion engine
-            self.execute_external_datasource_query(sql).await
-        } else {
-            // Handle with custom query execution
-            self.execute_database_query(sql).await
+            return self
+                .execute_external_datasource_query(sql, token, rows)
+                .await;
+        }
+
+        // Handle with custom query execution
+        let result = Self::cancellable(self.execute_database_query(sql), token).await?;
+        Self::send_result(result, token, rows).await
+    }
+
+    /// Sends the rows of a result collected before it is returned to `rows`, if the query
+    /// streams its rows, returning the result without them.
+    async fn send_result(
+        result: QueryResult,
+        token: &CancellationToken,
+        rows: Option<&RowSender>,
+    ) -> Result<QueryResult> {
+        match rows {
+            Some(sender) => {
+                for row in result.rows() {
+                    Self::send_row(sender, row.clone(), token).await?;
+                }
+                let streamed = QueryResult::new(result.columns().clone(), Vec::new());
+                Ok(match result.command_tag() {
+                    Some(tag) => streamed.with_command_tag(tag.clone()),
+                    None => streamed,
+                })
+            }
+            None => Ok(result),
+        }
+    }
+
+    /// Runs `future` unless `token` is cancelled first, in which case the future is dropped.
+    async fn cancellable<T>(
+        future: impl Future<Output = Result<T>>,
+        token: &CancellationToken,
+    ) -> Result<T> {
+        tokio::select! {
+            biased;
+            _ = token.cancelled() => {
+                info!("Query cancelled");
+                Err(DataFusionError::External(Box::new(QueryCancelled)))
+            }
+            result = future => result,
         }
     }
 
@@ -53,33 +467,103 @@ impl QueryEngine {
         sql.contains(".csv") || sql.contains(".parquet") || sql.contains(".json")
     }
 
-    async fn execute_external_datasource_query(&self, query: &str) -> Result<()> {
-        let (rewritten_query, file_path, format) = self.rewrite_query(query)?;
+    async fn execute_external_datasource_query(
+        &self,
+        query: &str,
+        token: &CancellationToken,
+        rows: Option<&RowSender>,
+    ) -> Result<QueryResult> {
+        let (rewritten_query, table_name, file_path, format) = self.rewrite_query(query)?;
+        self.register_external_table(&table_name, &file_path, format)
+            .await?;
 
+        self.statements_planned.fetch_add(1, Ordering::Relaxed);
+        let result = async {
+            let df = Self::cancellable(self.context.sql(&rewritten_query), token).await?;
+            Self::collect(df, token, rows).await
+        }
+        .await;
+
+        // The table only lives for this query, so later queries can read the same file
+        self.context.deregister_table(table_name.as_str())?;
+        result
+    }
+
+    async fn register_external_table(
+        &self,
+        table_name: &str,
+        file_path: &str,
+        format: ExternalDataSource,
+    ) -> Result<()> {
         match format {
             ExternalDataSource::CSV => {
                 self.context
-                    .register_csv("csv_table", &file_path, CsvReadOptions::new())
-                    .await?;
+                    .register_csv(table_name, file_path, CsvReadOptions::new())
+                    .await
             }
             ExternalDataSource::Parquet => {
                 self.context
-                    .register_parquet("parquet_table", &file_path, ParquetReadOptions::default())
-                    .await?;
+                    .register_parquet(table_name, file_path, ParquetReadOptions::default())
+                    .await
             }
             ExternalDataSource::ORC => {
                 // Register ORC file when supported
                 todo!();
             }
         }
+    }
 
-        let df = self.context.sql(&rewritten_query).await?;
-        df.show().await?;
+    /// Executes `df`, collecting the rows it produces unless `token` is cancelled first. Given
+    /// a sender, the rows of every batch are sent as soon as the batch is produced instead.
+    async fn collect(
+        df: DataFrame,
+        token: &CancellationToken,
+        rows: Option<&RowSender>,
+    ) -> Result<QueryResult> {
+        let mut stream = Self::cancellable(df.execute_stream(), token).await?;
 
-        Ok(())
+        // Dropping the stream on cancellation aborts the operators still running
+        let mut batches = Vec::new();
+        while let Some(batch) =
+            Self::cancellable(async { stream.next().await.transpose() }, token).await?
+        {
+            match rows {
+                Some(sender) => {
+                    let result = QueryResult::from_batches(&stream.schema(), &[batch])?;
+                    for row in result.rows() {
+                        Self::send_row(sender, row.clone(), token).await?;
+                    }
+                }
+                None => batches.push(batch),
+            }
+            // Let other tasks (e.g. the handler of a cancel request) run between batches
+            tokio::task::yield_now().await;
+        }
+        QueryResult::from_batches(&stream.schema(), &batches)
     }
 
-    fn rewrite_query(&self, query: &str) -> Result<(String, String, ExternalDataSource)> {
+    /// Sends a row to the receiver of a streaming query, waiting while the channel is full.
+    /// A query whose receiver went away (e.g. with the client's connection) is cancelled.
+    async fn send_row(
+        sender: &RowSender,
+        row: Vec<ty::DataType>,
+        token: &CancellationToken,
+    ) -> Result<()> {
+        Self::cancellable(
+            async {
+                sender
+                    .send(row)
+                    .await
+                    .map_err(|_| DataFusionError::External(Box::new(QueryCancelled)))
+            },
+            token,
+        )
+        .await
+    }
+
+    /// Rewrites the file path in `query` to a table name unique to this query, returning
+    /// the rewritten query, the table name, the file path and its format.
+    fn rewrite_query(&self, query: &str) -> Result<(String, String, String, ExternalDataSource)> {
         // Regex for unquoted file path
         let unquoted_re = Regex::new(r"FROM\s+([^\s']+\.csv|parquet|orc)")
             .expect("Invalid regex for unquoted path");
@@ -111,14 +595,13 @@ impl QueryEngine {
             )));
         };
 
-        let table_name = match file_ext {
-            "csv" => "csv_table",
-            "parquet" => "parquet_table",
-            "orc" => "orc_table",
-            _ => unreachable!(),
-        };
+        let table_name = format!(
+            "{}_table_{}",
+            file_ext,
+            NEXT_EXTERNAL_TABLE_ID.fetch_add(1, Ordering::Relaxed)
+        );
 
-        let rewritten_query = query.replace(file_path, table_name);
+        let rewritten_query = query.replace(file_path, &table_name);
 
         debug!("Rewritten query: {}", rewritten_query);
         debug!("File path: {}", file_path);
@@ -130,28 +613,550 @@ impl QueryEngine {
             _ => unreachable!(),
         };
 
-        Ok((rewritten_query, file_path.to_string(), format))
+        Ok((rewritten_query, table_name, file_path.to_string(), format))
+    }
+
+    /// Parses `sql`, failing with the [`ParseError`] locating the syntax error (see
+    /// [`parse_error`]).
+    fn parse(sql: &str) -> Result<Vec<Statement>> {
+        parse_sql(sql).map_err(|e| DataFusionError::External(Box::new(e)))
     }
 
-    async fn execute_database_query(&self, sql: &str) -> Result<()> {
-        let ast = parse_sql(sql)?;
+    async fn execute_database_query(&self, sql: &str) -> Result<QueryResult> {
+        self.statements_planned.fetch_add(1, Ordering::Relaxed);
+        let ast = Self::parse(sql)?;
+        if let [Statement::Explain {
+            analyze, statement, ..
+        }] = ast.as_slice()
+        {
+            return self.explain(statement, *analyze).await;
+        }
+        if let [statement @ (Statement::CreateTable { .. } | Statement::Drop { .. })] =
+            ast.as_slice()
+        {
+            return ddl::execute(self, statement).await;
+        }
+        // Inserts into other tables (e.g. registered files) are left to DataFusion
+        if let [statement @ Statement::Insert { table_name, .. }] = ast.as_slice() {
+            if self.catalog.get_table(&table_name.to_string()).is_some() {
+                return dml::execute(self, statement).await;
+            }
+        }
+        // Queries of other tables (e.g. registered files) are left to DataFusion
+        if let [Statement::Query(query)] = ast.as_slice() {
+            if let Some(table_name) = scan::scanned_table(query) {
+                if self.catalog.get_table(&table_name.to_string()).is_some() {
+                    match scan::execute(self, query).await {
+                        // Queries scans can't run (e.g. with subqueries) are planned instead
+                        Err(DataFusionError::NotImplemented(_)) => {}
+                        result => return result,
+                    }
+                }
+            }
+        }
+        if let [Statement::Query(query)] = ast.as_slice() {
+            let functions = self.functions.read().unwrap();
+            if let Some(result) = eval::evaluate_query(query, &functions) {
+                return result.map_err(|e| DataFusionError::External(Box::new(e)));
+            }
+        }
+
         let logical_plan = self.create_logical_plan(&ast)?;
+        let execute_subquery = |plan: LogicalPlan| -> BoxFuture<'_, Result<QueryResult>> {
+            Box::pin(async move {
+                let optimized_plan = self.optimize_plan(&plan)?;
+                self.execute_optimized_plan(&optimized_plan).await
+            })
+        };
+        let logical_plan =
+            subquery::evaluate_scalar_subqueries(&logical_plan, &execute_subquery).await?;
         let optimized_plan = self.optimize_plan(&logical_plan)?;
         self.execute_optimized_plan(&optimized_plan).await
     }
 
+    /// Plans `statement` without executing it, returning the indented logical plan before
+    /// and after optimization as `(plan_type, plan)` rows. With `analyze`, the optimized plan
+    /// is also executed, and the number of rows it produced, the time it took and the physical
+    /// plan annotated with the metrics of each of its nodes are added.
+    ///
+    /// Queries over external files never get here, as DataFusion explains those itself.
+    async fn explain(&self, statement: &Statement, analyze: bool) -> Result<QueryResult> {
+        let logical_plan = self.create_logical_plan(std::slice::from_ref(statement))?;
+        let optimized_plan = self.optimize_plan(&logical_plan)?;
+
+        let row = |plan_type: &str, plan: String| {
+            vec![
+                ty::DataType::Text(plan_type.to_string()),
+                ty::DataType::Text(plan),
+            ]
+        };
+        let mut rows = vec![
+            row("logical_plan", logical_plan.display_indent().to_string()),
+            row(
+                "optimized_logical_plan",
+                optimized_plan.display_indent().to_string(),
+            ),
+        ];
+
+        if analyze {
+            let started = Instant::now();
+            let physical_plan = self.create_physical_plan(&optimized_plan).await?;
+            let result = self.execute_physical_plan(physical_plan.clone()).await?;
+            rows.push(row(
+                "analyze",
+                format!(
+                    "output_rows={}, elapsed={:?}",
+                    result.row_count(),
+                    started.elapsed()
+                ),
+            ));
+            rows.push(row(
+                "plan_with_metrics",
+                DisplayableExecutionPlan::with_metrics(physical_plan.as_ref())
+                    .indent(true)
+                    .to_string(),
+            ));
+        }
+
+        Ok(QueryResult::new(
+            vec!["plan_type".to_string(), "plan".to_string()],
+            rows,
+        ))
+    }
+
     fn create_logical_plan(&self, ast: &[Statement]) -> Result<LogicalPlan> {
-        // Implement logic to create a logical plan from the AST
-        todo!()
+        planner::create_logical_plan(ast, &self.catalog)
     }
 
     fn optimize_plan(&self, logical_plan: &LogicalPlan) -> Result<LogicalPlan> {
-        // Implement optimization logic
-        todo!()
+        self.context.state().optimize(logical_plan)
     }
 
-    async fn execute_optimized_plan(&self, optimized_plan: &LogicalPlan) -> Result<()> {
-        // Implement execution logic for the optimized plan
-        todo!()
+    async fn execute_optimized_plan(&self, optimized_plan: &LogicalPlan) -> Result<QueryResult> {
+        let physical_plan = self.create_physical_plan(optimized_plan).await?;
+        self.execute_physical_plan(physical_plan).await
+    }
+
+    /// Plans the execution of a plan over catalog tables, whose rows are read into memory
+    /// first (see [`scan::load_tables`]).
+    async fn create_physical_plan(
+        &self,
+        optimized_plan: &LogicalPlan,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let plan = scan::load_tables(self, optimized_plan).await?;
+        self.context.state().create_physical_plan(&plan).await
+    }
+
+    async fn execute_physical_plan(&self, plan: Arc<dyn ExecutionPlan>) -> Result<QueryResult> {
+        let schema = plan.schema();
+        let batches = datafusion::physical_plan::collect(plan, self.context.task_ctx()).await?;
+        QueryResult::from_batches(&schema, &batches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, StringArray};
+
+    #[tokio::test]
+    async fn test_registered_udf_is_callable_from_sql() {
+        let engine = QueryEngine::new();
+        engine.register_udf("flip", vec![DataType::Utf8], DataType::Utf8, |args| {
+            let input = args[0]
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .ok_or_else(|| DataFusionError::Execution("flip expects text".to_string()))?;
+            let flipped: StringArray = input
+                .iter()
+                .map(|value| value.map(|s| s.chars().rev().collect::<String>()))
+                .collect();
+            Ok(Arc::new(flipped) as ArrayRef)
+        });
+
+        let batches = engine
+            .context
+            .sql("SELECT flip(column1) AS flipped FROM (VALUES ('hello'), ('r2db2'), (NULL))")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+
+        let flipped = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(flipped.len(), 3);
+        assert_eq!(flipped.value(0), "olleh");
+        assert_eq!(flipped.value(1), "2bd2r");
+        assert!(flipped.is_null(2));
+    }
+
+    #[tokio::test]
+    async fn test_registered_function_is_callable_without_from() {
+        let engine = QueryEngine::new();
+        engine.register_function("repeat", Arity::Exact(2), |args| {
+            match (&args[0], &args[1]) {
+                (ty::DataType::Text(s), ty::DataType::BigInt(n)) => {
+                    Ok(ty::DataType::Text(s.repeat(*n as usize)))
+                }
+                (a, _) => Err(ty::TypeError::IncompatibleType {
+                    expected: "TEXT".to_string(),
+                    found: a.data_type_kind().metadata().name().to_string(),
+                }),
+            }
+        });
+
+        let result = engine
+            .execute_query("SELECT repeat('ab', 3) AS repeated")
+            .await
+            .unwrap();
+        assert_eq!(result.columns(), &["repeated"]);
+        assert_eq!(
+            result.rows()[0],
+            vec![ty::DataType::Text("ababab".to_string())]
+        );
+
+        let err = engine
+            .execute_query("SELECT repeat('ab')")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            &err,
+            DataFusionError::External(e) if matches!(
+                e.downcast_ref::<EvalError>(),
+                Some(EvalError::ArgumentCount { found: 1, .. })
+            )
+        ));
+        assert_eq!(
+            err.to_string(),
+            "External error: REPEAT takes 2 arguments, but was given 1"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_cancelled_query_returns_promptly() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("numbers.csv");
+        let rows = (0..100_000).map(|i| i.to_string()).collect::<Vec<_>>();
+        std::fs::write(&csv_path, format!("n\n{}\n", rows.join("\n"))).unwrap();
+
+        let engine = Arc::new(QueryEngine::new());
+        // Takes a second per batch, so scanning the whole file would take over ten seconds
+        engine.register_udf("crawl", vec![DataType::Int64], DataType::Int64, |args| {
+            std::thread::sleep(std::time::Duration::from_secs(1));
+            Ok(args[0].clone())
+        });
+
+        let token = CancellationToken::new();
+        let query = tokio::spawn({
+            let (engine, token) = (engine.clone(), token.clone());
+            let sql = format!("SELECT crawl(n) FROM {}", csv_path.display());
+            async move { engine.execute_query_with_cancellation(&sql, &token).await }
+        });
+
+        // Cancel from another thread, as the query may keep the runtime's workers busy
+        let canceller = std::thread::spawn({
+            let token = token.clone();
+            move || {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                token.cancel();
+                std::time::Instant::now()
+            }
+        });
+
+        let result = query.await.unwrap();
+        let cancelled_at = canceller.join().unwrap();
+        assert!(is_cancelled(&result.unwrap_err()));
+        assert!(cancelled_at.elapsed() < std::time::Duration::from_secs(3));
+    }
+
+    #[tokio::test]
+    async fn test_external_file_can_be_queried_repeatedly() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("numbers.csv");
+        std::fs::write(&csv_path, "n\n1\n2\n").unwrap();
+
+        let engine = QueryEngine::new();
+        let sql = format!("SELECT n FROM {}", csv_path.display());
+        engine.execute_query(&sql).await.unwrap();
+        engine.execute_query(&sql).await.unwrap();
+
+        // Every query's table is dropped once it completes
+        assert!(engine.context.catalog_names().iter().all(|catalog| {
+            let catalog = engine.context.catalog(catalog).unwrap();
+            catalog.schema_names().iter().all(|schema| {
+                let tables = catalog.schema(schema).unwrap().table_names();
+                tables.iter().all(|table| !table.starts_with("csv_table"))
+            })
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_explain_returns_plan_without_executing() {
+        let engine = QueryEngine::new();
+        let schema = catalog::schema::Schema::new(vec![
+            catalog::Column::new_fixed("id", ty::DataTypeKind::Integer).unwrap(),
+            catalog::Column::new_varlen("name", ty::DataTypeKind::VarChar, 255).unwrap(),
+        ]);
+        engine.register_table_schema("users", Arc::new(schema));
+
+        let result = engine
+            .execute_query("EXPLAIN SELECT * FROM users WHERE id = 1")
+            .await
+            .unwrap();
+
+        assert_eq!(result.columns(), &["plan_type", "plan"]);
+        let plans = result
+            .rows()
+            .iter()
+            .map(|row| (row[0].to_string(), row[1].to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(plans[0].0, "logical_plan");
+        assert_eq!(plans[1].0, "optimized_logical_plan");
+        // Only the plans are returned, as the query itself is never executed
+        assert_eq!(plans.len(), 2);
+        for (_, plan) in &plans {
+            assert!(plan.contains("TableScan: users"), "{}", plan);
+        }
+        assert!(plans[0].1.contains("Filter: users.id = Int64(1)"));
+    }
+
+    #[tokio::test]
+    async fn test_explain_over_external_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("numbers.csv");
+        std::fs::write(&csv_path, "n\n1\n2\n3\n").unwrap();
+
+        let engine = QueryEngine::new();
+        let sql = format!("EXPLAIN SELECT n FROM {} WHERE n > 1", csv_path.display());
+        let result = engine.execute_query(&sql).await.unwrap();
+        let plan_types = result
+            .rows()
+            .iter()
+            .map(|row| row[0].to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(plan_types, ["logical_plan", "physical_plan"]);
+
+        // Analyzing executes the plan and reports the rows every operator produced
+        let sql = format!(
+            "EXPLAIN ANALYZE SELECT n FROM {} WHERE n > 1",
+            csv_path.display()
+        );
+        let result = engine.execute_query(&sql).await.unwrap();
+        let plan = result.rows()[0][1].to_string();
+        assert!(plan.contains("output_rows=2"), "{}", plan);
+        assert!(plan.contains("elapsed_compute="), "{}", plan);
+    }
+
+    /// Returns an engine whose tables store their rows in an in-memory buffer pool.
+    fn engine_with_buffer_pool() -> QueryEngine {
+        use buffer::{BufferPoolManager, ReplacementPolicy};
+        use storage::disk::{DiskManager, IN_MEMORY_PATH};
+
+        let disk_manager = Arc::new(DiskManager::new(IN_MEMORY_PATH).unwrap());
+        let bpm =
+            BufferPoolManager::new_with_size(ReplacementPolicy::LRU, disk_manager, 10).unwrap();
+        let engine = QueryEngine::new();
+        engine.set_buffer_pool(Arc::new(tokio::sync::Mutex::new(bpm)));
+        engine
+    }
+
+    #[tokio::test]
+    async fn test_explain_analyze_executes_the_plan_over_table_rows() {
+        let engine = engine_with_buffer_pool();
+        for sql in [
+            "CREATE TABLE users (id INTEGER NOT NULL, name VARCHAR(8))",
+            "INSERT INTO users VALUES (1, 'ada'), (2, 'grace'), (3, 'alan')",
+        ] {
+            engine.execute_query(sql).await.unwrap();
+        }
+
+        let result = engine
+            .execute_query("EXPLAIN ANALYZE SELECT name FROM users WHERE id > 1")
+            .await
+            .unwrap();
+        let plans = result
+            .rows()
+            .iter()
+            .map(|row| (row[0].to_string(), row[1].to_string()))
+            .collect::<Vec<_>>();
+        let plan_types = plans.iter().map(|(plan_type, _)| plan_type.as_str());
+        assert_eq!(
+            plan_types.collect::<Vec<_>>(),
+            [
+                "logical_plan",
+                "optimized_logical_plan",
+                "analyze",
+                "plan_with_metrics"
+            ]
+        );
+        assert!(plans[2].1.starts_with("output_rows=2,"), "{}", plans[2].1);
+
+        // Every operator reports the rows it produced, down to the scan of the table's rows
+        let plan = &plans[3].1;
+        assert!(plan.contains("FilterExec"), "{}", plan);
+        assert!(plan.contains("output_rows=2"), "{}", plan);
+        assert!(plan.contains("MemoryExec"), "{}", plan);
+    }
+
+    /// Returns an engine with the users 1 (ada), 2 (grace) and 3 (alan), and orders of users
+    /// 1 and 3.
+    async fn engine_with_orders() -> QueryEngine {
+        let engine = engine_with_buffer_pool();
+        for sql in [
+            "CREATE TABLE users (id INTEGER NOT NULL, name VARCHAR(8))",
+            "CREATE TABLE orders (user_id INTEGER NOT NULL, total BIGINT)",
+            "INSERT INTO users VALUES (1, 'ada'), (2, 'grace'), (3, 'alan')",
+            "INSERT INTO orders VALUES (1, 10), (3, 20), (1, 30)",
+        ] {
+            engine.execute_query(sql).await.unwrap();
+        }
+        engine
+    }
+
+    fn names(result: QueryResult) -> Vec<String> {
+        let mut names: Vec<_> = result.rows().iter().map(|row| row[0].to_string()).collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn test_exists_filters_the_rows_of_catalog_tables() {
+        let engine = engine_with_orders().await;
+        let with_orders = engine
+            .execute_query(
+                "SELECT name FROM users WHERE EXISTS (SELECT 1 FROM orders WHERE user_id = id)",
+            )
+            .await
+            .unwrap();
+        assert_eq!(names(with_orders), ["ada", "alan"]);
+        let without_orders = engine
+            .execute_query(
+                "SELECT name FROM users WHERE NOT EXISTS (SELECT 1 FROM orders WHERE user_id = id)",
+            )
+            .await
+            .unwrap();
+        assert_eq!(names(without_orders), ["grace"]);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_subqueries_are_evaluated_over_catalog_tables() {
+        let engine = engine_with_orders().await;
+        let result = engine
+            .execute_query(
+                "SELECT name FROM users WHERE id = (SELECT user_id FROM orders WHERE total = 20)",
+            )
+            .await
+            .unwrap();
+        assert_eq!(names(result), ["alan"]);
+
+        let err = engine
+            .execute_query("SELECT name FROM users WHERE id = (SELECT user_id FROM orders)")
+            .await
+            .unwrap_err();
+        assert!(is_cardinality_violation(&err), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_prepared_statement_is_planned_once() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("users.csv");
+        std::fs::write(&csv_path, "id,name\n1,ada\n2,grace\n3,barbara\n").unwrap();
+
+        let engine = QueryEngine::new();
+        let sql = format!("SELECT name FROM {} WHERE id = $1", csv_path.display());
+        let statement = engine.prepare(&sql).await.unwrap();
+        assert_eq!(engine.statements_planned(), 1);
+
+        for (id, name) in [(1, "ada"), (3, "barbara")] {
+            let result = engine
+                .execute_prepared(&statement, &[ty::DataType::Integer(id)])
+                .await
+                .unwrap();
+            assert_eq!(result.rows(), &[vec![ty::DataType::Text(name.to_string())]]);
+        }
+        assert_eq!(engine.statements_planned(), 1);
+
+        // The file stays registered until the statement is deallocated
+        let table_name = statement.external_table.clone().unwrap();
+        assert!(engine.context.table_exist(table_name.as_str()).unwrap());
+        engine.deallocate(&statement).unwrap();
+        assert!(!engine.context.table_exist(table_name.as_str()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_bound_parameters_are_substituted_into_the_plan() {
+        let engine = QueryEngine::new();
+        let schema = catalog::schema::Schema::new(vec![
+            catalog::Column::new_fixed("id", ty::DataTypeKind::Integer).unwrap(),
+            catalog::Column::new_varlen("name", ty::DataTypeKind::VarChar, 255).unwrap(),
+        ]);
+        engine.register_table_schema("users", Arc::new(schema));
+
+        let statement = engine
+            .prepare("SELECT * FROM users WHERE id = $1")
+            .await
+            .unwrap();
+        assert_eq!(
+            statement.parameter_types().unwrap(),
+            [Some(DataType::Int32)]
+        );
+
+        let bound = engine
+            .bind(&statement, &[ty::DataType::Integer(1)])
+            .unwrap();
+        let plan = bound.plan().display_indent().to_string();
+        assert!(plan.contains("Filter: users.id = Int32(1)"), "{}", plan);
+        assert!(!plan.contains("$1"), "{}", plan);
+
+        // Every parameter must be bound, and only once
+        for params in [
+            vec![],
+            vec![ty::DataType::Integer(1), ty::DataType::Integer(2)],
+        ] {
+            let err = engine.bind(&statement, &params).unwrap_err();
+            assert!(
+                err.to_string().contains(&format!(
+                    "{} parameters were bound, but the prepared statement requires 1",
+                    params.len()
+                )),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_returns_result_set() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("users.csv");
+        std::fs::write(&csv_path, "id,name\n1,ada\n2,grace\n3,barbara\n").unwrap();
+
+        let engine = QueryEngine::new();
+        let sql = format!("SELECT id, name FROM {} WHERE id > 1", csv_path.display());
+        let result = engine.execute_query(&sql).await.unwrap();
+
+        assert_eq!(result.columns(), &["id", "name"]);
+        assert_eq!(result.row_count(), 2);
+        assert_eq!(
+            result.rows()[0],
+            vec![
+                ty::DataType::BigInt(2),
+                ty::DataType::Text("grace".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_syntax_errors_are_located() {
+        let engine = QueryEngine::new();
+        let err = engine.execute_query("SELECT * users").await.unwrap_err();
+
+        let error = parse_error(&err).expect("Expected a syntax error");
+        assert_eq!(error.token(), Some("users"));
+        assert_eq!(error.span(), &(9..14));
     }
 }
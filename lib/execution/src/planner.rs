@@ -0,0 +1,577 @@
+//! # Logical Planning
+//!
+//! Translates the parsed SQL AST of queries over catalog tables into DataFusion
+//! [`LogicalPlan`]s. Column references are resolved against the catalog [`Schema`] of the
+//! table they read from, so that unknown columns are reported while planning.
+//!
+//! Only single-table `SELECT`s with an optional `WHERE` clause of simple comparisons,
+//! arithmetic, `CASE`, `[NOT] EXISTS` and scalar subqueries are supported so far; everything
+//! else is rejected with [`DataFusionError::NotImplemented`]. Parameters (`$1`) are planned as
+//! placeholders, to be bound before the plan is executed.
+//!
+//! A subquery may refer to the columns of the queries enclosing it (e.g. `WHERE EXISTS (SELECT
+//! 1 FROM orders WHERE orders.user_id = users.id)`), which are planned as outer references.
+//! The optimizer decorrelates such subqueries into semi (or, for `NOT EXISTS`, anti) joins,
+//! which stop looking for inner rows as soon as an outer row is known to match.
+
+use arrow::datatypes::{DataType, Field, Schema as ArrowSchema, TimeUnit};
+use catalog::{schema::Schema, Catalog};
+use compile::parser::{
+    BinaryOperator, Expr as SqlExpr, GroupByExpr, Ident, Query, Select, SelectItem, SetExpr,
+    Statement, TableFactor, Value,
+};
+use datafusion_common::{DataFusionError, Result, ScalarValue};
+use datafusion_expr::logical_plan::builder::LogicalTableSource;
+use datafusion_expr::{
+    binary_expr, col, exists, lit, not_exists, out_ref_col, placeholder, scalar_subquery, wildcard,
+    Case, Expr, LogicalPlan, LogicalPlanBuilder,
+};
+use datafusion_expr::{Operator, TableSource};
+use std::sync::Arc;
+use ty::DataTypeKind;
+
+fn not_implemented<T>(what: impl std::fmt::Display) -> Result<T> {
+    Err(DataFusionError::NotImplemented(what.to_string()))
+}
+
+/// Builds the logical plan of a single statement, resolving tables through `catalog`.
+pub(crate) fn create_logical_plan(ast: &[Statement], catalog: &Catalog) -> Result<LogicalPlan> {
+    match ast {
+        [Statement::Query(query)] => plan_query(query, catalog, None),
+        [statement] => not_implemented(format!("Unsupported statement: {}", statement)),
+        _ => not_implemented("Planning more than one statement at a time"),
+    }
+}
+
+/// Plans a query, or a subquery of the query `outer` resolves the columns of.
+fn plan_query(
+    query: &Query,
+    catalog: &Catalog,
+    outer: Option<&ColumnResolver>,
+) -> Result<LogicalPlan> {
+    if query.with.is_some()
+        || !query.order_by.is_empty()
+        || query.limit.is_some()
+        || query.offset.is_some()
+        || query.fetch.is_some()
+    {
+        return not_implemented(format!("Unsupported query clauses in: {}", query));
+    }
+
+    match query.body.as_ref() {
+        SetExpr::Select(select) => plan_select(select, catalog, outer),
+        body => not_implemented(format!("Unsupported query body: {}", body)),
+    }
+}
+
+fn plan_select(
+    select: &Select,
+    catalog: &Catalog,
+    outer: Option<&ColumnResolver>,
+) -> Result<LogicalPlan> {
+    let grouped = !matches!(&select.group_by, GroupByExpr::Expressions(exprs) if exprs.is_empty());
+    if select.distinct.is_some() || grouped || select.having.is_some() {
+        return not_implemented(format!("Unsupported select clauses in: {}", select));
+    }
+
+    let [from] = select.from.as_slice() else {
+        return not_implemented("Selecting from anything but a single table");
+    };
+    if !from.joins.is_empty() {
+        return not_implemented("Joins");
+    }
+    let TableFactor::Table { name, alias, .. } = &from.relation else {
+        return not_implemented(format!("Unsupported table factor: {}", from.relation));
+    };
+
+    let table_name = name.to_string();
+    let schema = catalog
+        .table_schema(&table_name)
+        .ok_or_else(|| DataFusionError::Plan(format!("Table not found: {}", table_name)))?;
+    // Columns are qualified by the alias of the table, if it has one
+    let qualifier = alias
+        .as_ref()
+        .map_or_else(|| table_name.clone(), |alias| alias.name.value.clone());
+    let resolver = ColumnResolver {
+        qualifier: &qualifier,
+        schema: &schema,
+        catalog,
+        outer,
+    };
+
+    let mut builder = LogicalPlanBuilder::scan(table_name.clone(), table_source(&schema)?, None)?;
+    if alias.is_some() {
+        builder = builder.alias(qualifier.clone())?;
+    }
+    if let Some(selection) = &select.selection {
+        // Parameters compared with columns take the type of the column
+        let predicate = resolver
+            .expr(selection)?
+            .infer_placeholder_types(builder.schema())?;
+        builder = builder.filter(predicate)?;
+    }
+
+    let projection = select
+        .projection
+        .iter()
+        .map(|item| match item {
+            SelectItem::UnnamedExpr(expr) => resolver.expr(expr),
+            SelectItem::ExprWithAlias { expr, alias } => {
+                Ok(resolver.expr(expr)?.alias(&alias.value))
+            }
+            SelectItem::Wildcard(_) => Ok(wildcard()),
+            item => not_implemented(format!("Unsupported select item: {}", item)),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    builder.project(projection)?.build()
+}
+
+/// Describes a catalog table to the planner by the Arrow schema of its rows.
+fn table_source(schema: &Schema) -> Result<Arc<dyn TableSource>> {
+    let fields = schema
+        .columns()
+        .iter()
+        .map(|column| {
+            Ok(Field::new(
+                column.column_name(),
+                arrow_type(column.column_type())?,
+                true,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Arc::new(LogicalTableSource::new(Arc::new(
+        ArrowSchema::new(fields),
+    ))))
+}
+
+/// The Arrow type that values of a column of the given kind are planned with.
+fn arrow_type(kind: &DataTypeKind) -> Result<DataType> {
+    Ok(match kind {
+        DataTypeKind::SmallInt | DataTypeKind::SmallSerial => DataType::Int16,
+        DataTypeKind::Integer | DataTypeKind::Serial => DataType::Int32,
+        DataTypeKind::BigInt | DataTypeKind::BigSerial => DataType::Int64,
+        DataTypeKind::Real => DataType::Float32,
+        DataTypeKind::DoublePrecision | DataTypeKind::Float => DataType::Float64,
+        DataTypeKind::Boolean => DataType::Boolean,
+        DataTypeKind::Text | DataTypeKind::VarChar => DataType::Utf8,
+        DataTypeKind::Blob => DataType::Binary,
+        DataTypeKind::DateTime => DataType::Timestamp(TimeUnit::Second, None),
+        DataTypeKind::TimestampTz => DataType::Timestamp(TimeUnit::Second, Some("UTC".into())),
+        kind => return not_implemented(format!("Planning columns of type {:?}", kind)),
+    })
+}
+
+/// Translates SQL expressions over the columns of a single table, and of the tables of the
+/// queries enclosing it if it is read by a subquery.
+struct ColumnResolver<'a> {
+    /// The name (or alias) the columns of the table are qualified with
+    qualifier: &'a str,
+    schema: &'a Schema,
+    catalog: &'a Catalog,
+    /// The resolver of the enclosing query
+    outer: Option<&'a ColumnResolver<'a>>,
+}
+
+impl ColumnResolver<'_> {
+    fn expr(&self, expr: &SqlExpr) -> Result<Expr> {
+        match expr {
+            SqlExpr::Identifier(ident) => self.column(None, ident),
+            SqlExpr::CompoundIdentifier(idents) => match idents.as_slice() {
+                [table, column] => self.column(Some(table), column),
+                _ => Err(DataFusionError::Plan(format!(
+                    "Invalid column reference: {}",
+                    expr
+                ))),
+            },
+            SqlExpr::Exists { subquery, negated } => {
+                let subquery = Arc::new(plan_query(subquery, self.catalog, Some(self))?);
+                Ok(if *negated {
+                    not_exists(subquery)
+                } else {
+                    exists(subquery)
+                })
+            }
+            SqlExpr::Subquery(subquery) => {
+                let plan = plan_query(subquery, self.catalog, Some(self))?;
+                if plan.schema().fields().len() != 1 {
+                    return Err(DataFusionError::Plan(format!(
+                        "Subquery used as an expression must return one column: ({})",
+                        subquery
+                    )));
+                }
+                Ok(scalar_subquery(Arc::new(plan)))
+            }
+            SqlExpr::Value(Value::Placeholder(id)) => Ok(placeholder(id)),
+            SqlExpr::Value(value) => Ok(lit(scalar(value)?)),
+            SqlExpr::Nested(expr) => self.expr(expr),
+            SqlExpr::BinaryOp { left, op, right } => Ok(binary_expr(
+                self.expr(left)?,
+                operator(op)?,
+                self.expr(right)?,
+            )),
+            SqlExpr::Case {
+                operand,
+                conditions,
+                results,
+                else_result,
+            } => {
+                let boxed = |expr: &SqlExpr| self.expr(expr).map(Box::new);
+                Ok(Expr::Case(Case::new(
+                    operand.as_deref().map(boxed).transpose()?,
+                    conditions
+                        .iter()
+                        .zip(results)
+                        .map(|(when, then)| Ok((boxed(when)?, boxed(then)?)))
+                        .collect::<Result<_>>()?,
+                    else_result.as_deref().map(boxed).transpose()?,
+                )))
+            }
+            expr => not_implemented(format!("Unsupported expression: {}", expr)),
+        }
+    }
+
+    /// Resolves a column, optionally qualified by its table. Columns the table doesn't have
+    /// are looked up in the tables of the enclosing queries, innermost first, and planned as
+    /// outer references.
+    fn column(&self, table: Option<&Ident>, name: &Ident) -> Result<Expr> {
+        let mut scope = Some(self);
+        while let Some(resolver) = scope {
+            if table.map_or(true, |table| table.value == resolver.qualifier) {
+                match resolver.schema.get_col_idx(&name.value) {
+                    Ok(index) => {
+                        let column = format!("{}.{}", resolver.qualifier, name.value);
+                        if std::ptr::eq(resolver, self) {
+                            return Ok(col(column));
+                        }
+                        let kind = resolver.schema.columns()[index].column_type();
+                        return Ok(out_ref_col(arrow_type(kind)?, column));
+                    }
+                    Err(_) if table.is_some() => {
+                        return Err(DataFusionError::Plan(format!(
+                            "Column `{}` does not exist in table `{}`",
+                            name.value, resolver.qualifier
+                        )))
+                    }
+                    Err(_) => {}
+                }
+            }
+            scope = resolver.outer;
+        }
+
+        Err(DataFusionError::Plan(match table {
+            Some(table) => format!("Invalid column reference: {}.{}", table, name),
+            None => format!(
+                "Column `{}` does not exist in table `{}`",
+                name.value, self.qualifier
+            ),
+        }))
+    }
+}
+
+fn scalar(value: &Value) -> Result<ScalarValue> {
+    Ok(match value {
+        Value::Number(n, _) => match n.parse::<i64>() {
+            Ok(n) => ScalarValue::Int64(Some(n)),
+            Err(_) => ScalarValue::Float64(Some(
+                n.parse()
+                    .map_err(|_| DataFusionError::Plan(format!("Invalid number: {}", n)))?,
+            )),
+        },
+        Value::SingleQuotedString(s) => ScalarValue::Utf8(Some(s.clone())),
+        Value::Boolean(b) => ScalarValue::Boolean(Some(*b)),
+        Value::Null => ScalarValue::Null,
+        value => return not_implemented(format!("Unsupported literal: {}", value)),
+    })
+}
+
+fn operator(op: &BinaryOperator) -> Result<Operator> {
+    Ok(match op {
+        BinaryOperator::Eq => Operator::Eq,
+        BinaryOperator::NotEq => Operator::NotEq,
+        BinaryOperator::Lt => Operator::Lt,
+        BinaryOperator::LtEq => Operator::LtEq,
+        BinaryOperator::Gt => Operator::Gt,
+        BinaryOperator::GtEq => Operator::GtEq,
+        BinaryOperator::And => Operator::And,
+        BinaryOperator::Or => Operator::Or,
+        BinaryOperator::Plus => Operator::Plus,
+        BinaryOperator::Minus => Operator::Minus,
+        BinaryOperator::Multiply => Operator::Multiply,
+        BinaryOperator::Divide => Operator::Divide,
+        BinaryOperator::Modulo => Operator::Modulo,
+        op => return not_implemented(format!("Unsupported operator: {}", op)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subquery::evaluate_scalar_subqueries;
+    use catalog::Column;
+    use compile::parser::parse_sql;
+    use datafusion::prelude::SessionContext;
+    use futures::future::BoxFuture;
+
+    fn users() -> Catalog {
+        let schema = Schema::new(vec![
+            Column::new_fixed("id", DataTypeKind::Integer).unwrap(),
+            Column::new_varlen("name", DataTypeKind::VarChar, 255).unwrap(),
+        ]);
+        let orders = Schema::new(vec![
+            Column::new_fixed("user_id", DataTypeKind::Integer).unwrap(),
+            Column::new_fixed("total", DataTypeKind::BigInt).unwrap(),
+        ]);
+        let catalog = Catalog::new();
+        catalog.register_table("users", Arc::new(schema));
+        catalog.register_table("orders", Arc::new(orders));
+        catalog
+    }
+
+    /// Gives the scans of a plan some rows to read: the users 1 (alice), 2 (bob) and 3
+    /// (carol), and orders of users 1 and 3.
+    fn with_rows(plan: &LogicalPlan) -> LogicalPlan {
+        use arrow::array::{ArrayRef, Int32Array, Int64Array, StringArray};
+        use arrow::record_batch::RecordBatch;
+        use datafusion::datasource::{provider_as_source, MemTable};
+
+        let LogicalPlan::TableScan(scan) = plan else {
+            // Rebuilt unconditionally, as scans compare equal whatever their source
+            let inputs: Vec<_> = plan.inputs().into_iter().map(with_rows).collect();
+            return plan.with_new_inputs(&inputs).unwrap();
+        };
+        let columns: Vec<ArrayRef> = match scan.table_name.table() {
+            "users" => vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec!["alice", "bob", "carol"])),
+            ],
+            _ => vec![
+                Arc::new(Int32Array::from(vec![1, 3, 1])),
+                Arc::new(Int64Array::from(vec![10, 20, 30])),
+            ],
+        };
+        let schema = scan.source.schema();
+        let batch = RecordBatch::try_new(schema.clone(), columns).unwrap();
+        let table = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+
+        // The filters pushed into the scan are applied by a filter, as memory tables ignore them
+        let mut builder = LogicalPlanBuilder::scan(
+            scan.table_name.clone(),
+            provider_as_source(Arc::new(table)),
+            None,
+        )
+        .unwrap();
+        for filter in &scan.filters {
+            builder = builder.filter(filter.clone()).unwrap();
+        }
+        let columns = scan
+            .projected_schema
+            .fields()
+            .iter()
+            .map(|field| Expr::Column(field.qualified_column()));
+        builder.project(columns).unwrap().build().unwrap()
+    }
+
+    /// Optimizes and executes a plan over the rows of [`with_rows`].
+    async fn run(plan: LogicalPlan) -> Result<crate::QueryResult> {
+        let context = SessionContext::new();
+        // Subqueries are decorrelated into joins, whose scans are part of the plan tree
+        let optimized = context.state().optimize(&plan)?;
+        let df = context.execute_logical_plan(with_rows(&optimized)).await?;
+        let schema = df.schema().into();
+        let batches = df.collect().await?;
+        crate::QueryResult::from_batches(&schema, &batches)
+    }
+
+    /// Executes a plan like the engine does, evaluating its scalar subqueries first.
+    async fn execute(plan: LogicalPlan) -> Result<crate::QueryResult> {
+        let run_subquery = |plan| -> BoxFuture<'static, _> { Box::pin(run(plan)) };
+        let plan = evaluate_scalar_subqueries(&plan, &run_subquery).await?;
+        run(plan).await
+    }
+
+    fn names(result: &crate::QueryResult) -> Vec<String> {
+        let mut names: Vec<String> = result.rows().iter().map(|row| row[0].to_string()).collect();
+        names.sort();
+        names
+    }
+
+    fn plan(sql: &str) -> Result<LogicalPlan> {
+        create_logical_plan(&parse_sql(sql).unwrap(), &users())
+    }
+
+    #[test]
+    fn test_select_plans_scan_filter_and_projection() {
+        let plan = plan("SELECT id, name FROM users WHERE id = 1").unwrap();
+
+        let LogicalPlan::Projection(projection) = &plan else {
+            panic!("Expected a projection, got:\n{}", plan.display_indent());
+        };
+        assert_eq!(projection.expr, vec![col("users.id"), col("users.name")]);
+
+        let LogicalPlan::Filter(filter) = projection.input.as_ref() else {
+            panic!("Expected a filter, got:\n{}", plan.display_indent());
+        };
+        assert_eq!(filter.predicate, col("users.id").eq(lit(1i64)));
+
+        let LogicalPlan::TableScan(scan) = filter.input.as_ref() else {
+            panic!("Expected a table scan, got:\n{}", plan.display_indent());
+        };
+        assert_eq!(scan.table_name.to_string(), "users");
+    }
+
+    #[test]
+    fn test_parameters_are_planned_as_placeholders() {
+        let plan = plan("SELECT name FROM users WHERE id = $1").unwrap();
+        assert_eq!(
+            plan.get_parameter_types().unwrap(),
+            [("$1".to_string(), Some(DataType::Int32))].into()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exists_selects_rows_with_matching_subquery_rows() {
+        let with_orders =
+            plan("SELECT name FROM users WHERE EXISTS (SELECT 1 FROM orders WHERE orders.user_id = users.id)")
+                .unwrap();
+        assert_eq!(
+            names(&execute(with_orders).await.unwrap()),
+            ["alice", "carol"]
+        );
+
+        // Unqualified columns the subquery's table doesn't have refer to the outer table
+        let without_orders = plan(
+            "SELECT name FROM users WHERE NOT EXISTS (SELECT * FROM orders WHERE user_id = id)",
+        )
+        .unwrap();
+        assert_eq!(names(&execute(without_orders).await.unwrap()), ["bob"]);
+
+        // Aliases tell apart the outer and inner scans of the same table
+        let others = plan(
+            "SELECT u.name FROM users u WHERE EXISTS (SELECT 1 FROM users v WHERE v.id > u.id) AND id > 1",
+        )
+        .unwrap();
+        assert_eq!(names(&execute(others).await.unwrap()), ["bob"]);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_subquery_feeds_a_comparison() {
+        let plan = plan(
+            "SELECT name FROM users WHERE id * 10 > (SELECT total FROM orders WHERE user_id = 3) * 0.75",
+        )
+        .unwrap();
+        assert_eq!(names(&execute(plan).await.unwrap()), ["bob", "carol"]);
+    }
+
+    #[tokio::test]
+    async fn test_case_selects_the_first_matching_branch() {
+        let plan = plan(
+            "SELECT CASE WHEN id = 1 THEN 'one' WHEN id < 3 THEN 'two' ELSE 'many' END FROM users",
+        )
+        .unwrap();
+        assert_eq!(names(&execute(plan).await.unwrap()), ["many", "one", "two"]);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_subquery_returning_many_rows_is_an_error() {
+        let plan =
+            plan("SELECT name FROM users WHERE id = (SELECT user_id FROM orders WHERE total > 10)")
+                .unwrap();
+        let error = execute(plan).await.unwrap_err();
+        assert!(crate::is_cardinality_violation(&error), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_empty_scalar_subquery_is_null() {
+        let with_total = plan(
+            "SELECT name, (SELECT total FROM orders WHERE user_id = 2) AS total FROM users WHERE id = 1",
+        )
+        .unwrap();
+        let result = execute(with_total).await.unwrap();
+        assert_eq!(
+            result.rows(),
+            &[vec![
+                ty::DataType::Text("alice".to_string()),
+                ty::DataType::Null
+            ]]
+        );
+
+        // Nothing compares equal to NULL
+        let compared =
+            plan("SELECT name FROM users WHERE id = (SELECT user_id FROM orders WHERE total = 0)")
+                .unwrap();
+        assert_eq!(execute(compared).await.unwrap().row_count(), 0);
+    }
+
+    #[test]
+    fn test_scalar_subquery_must_return_one_column() {
+        assert!(matches!(
+            plan("SELECT name FROM users WHERE id = (SELECT * FROM orders)"),
+            Err(DataFusionError::Plan(_))
+        ));
+    }
+
+    #[test]
+    fn test_not_exists_is_planned_as_an_anti_join() {
+        let plan = plan(
+            "SELECT name FROM users WHERE NOT EXISTS (SELECT 1 FROM orders WHERE orders.user_id = users.id)",
+        )
+        .unwrap();
+        let LogicalPlan::Projection(projection) = &plan else {
+            panic!("Expected a projection, got:\n{}", plan.display_indent());
+        };
+        let LogicalPlan::Filter(filter) = projection.input.as_ref() else {
+            panic!("Expected a filter, got:\n{}", plan.display_indent());
+        };
+        let Expr::Exists(exists) = &filter.predicate else {
+            panic!("Expected an EXISTS predicate, got {}", filter.predicate);
+        };
+        assert!(exists.negated);
+        assert_eq!(
+            exists.subquery.outer_ref_columns,
+            [out_ref_col(DataType::Int32, "users.id")]
+        );
+
+        let optimized = SessionContext::new().state().optimize(&plan).unwrap();
+        assert!(
+            optimized
+                .display_indent()
+                .to_string()
+                .contains("LeftAnti Join"),
+            "Expected an anti join, got:\n{}",
+            optimized.display_indent()
+        );
+    }
+
+    #[test]
+    fn test_unknown_columns_and_tables_fail_to_plan() {
+        assert!(matches!(
+            plan("SELECT age FROM users"),
+            Err(DataFusionError::Plan(_))
+        ));
+        assert!(matches!(
+            plan("SELECT id FROM products"),
+            Err(DataFusionError::Plan(_))
+        ));
+        assert!(matches!(
+            plan("SELECT id FROM users WHERE EXISTS (SELECT 1 FROM orders WHERE orders.id = 1)"),
+            Err(DataFusionError::Plan(_))
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_statements_are_not_implemented() {
+        for sql in [
+            "INSERT INTO users VALUES (1, 'a')",
+            "SELECT id FROM users ORDER BY id",
+            "SELECT u.id FROM users u JOIN users v ON u.id = v.id",
+            "SELECT id FROM users WHERE name LIKE 'a%'",
+        ] {
+            assert!(
+                matches!(plan(sql), Err(DataFusionError::NotImplemented(_))),
+                "{} should not be supported",
+                sql
+            );
+        }
+    }
+}
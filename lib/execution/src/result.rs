@@ -0,0 +1,213 @@
+//! # Query Results
+//!
+//! A [`QueryResult`] holds the rows produced by a query as [`DataType`] values, converted from
+//! the Arrow record batches DataFusion executes queries into. Arrow types without a
+//! counterpart in the type system are converted to their text representation, as are values
+//! without an Arrow counterpart when they are bound to query parameters.
+
+use arrow::array::*;
+use arrow::datatypes::*;
+use arrow::datatypes::{DataType as ArrowType, Schema};
+use arrow::record_batch::RecordBatch;
+use arrow::util::display::array_value_to_string;
+use datafusion_common::{DataFusionError, Result, ScalarValue};
+use getset::Getters;
+use ty::DataType;
+
+/// The column names and rows returned by a query.
+#[derive(Debug, Default, Clone, PartialEq, Getters)]
+#[getset(get = "pub")]
+pub struct QueryResult {
+    columns: Vec<String>,
+    rows: Vec<Vec<DataType>>,
+    /// The tag of the command completion (e.g. `INSERT 0 1`), if the command has one
+    command_tag: Option<String>,
+}
+
+impl QueryResult {
+    pub fn new(columns: Vec<String>, rows: Vec<Vec<DataType>>) -> Self {
+        QueryResult {
+            columns,
+            rows,
+            command_tag: None,
+        }
+    }
+
+    /// Tags the result with the completion of its command (e.g. `INSERT 0 1`).
+    pub fn with_command_tag(mut self, tag: impl Into<String>) -> Self {
+        self.command_tag = Some(tag.into());
+        self
+    }
+
+    /// Converts the record batches a query produced into rows, naming the columns after
+    /// the fields of `schema`.
+    pub fn from_batches(schema: &Schema, batches: &[RecordBatch]) -> Result<Self> {
+        let columns = schema
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect();
+
+        let mut rows = Vec::with_capacity(batches.iter().map(RecordBatch::num_rows).sum());
+        for batch in batches {
+            for row in 0..batch.num_rows() {
+                rows.push(
+                    batch
+                        .columns()
+                        .iter()
+                        .map(|array| arrow_value(array.as_ref(), row))
+                        .collect::<Result<_>>()?,
+                );
+            }
+        }
+
+        Ok(QueryResult::new(columns, rows))
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+}
+
+/// Converts the value at `row` of an Arrow array into a [`DataType`].
+fn arrow_value(array: &dyn Array, row: usize) -> Result<DataType> {
+    if array.is_null(row) {
+        return Ok(DataType::Null);
+    }
+
+    let datetime = |datetime: Option<_>| {
+        datetime.map(DataType::DateTime).ok_or_else(|| {
+            DataFusionError::Execution(format!(
+                "Timestamp out of range in column of {}",
+                array.data_type()
+            ))
+        })
+    };
+
+    Ok(match array.data_type() {
+        ArrowType::Boolean => DataType::Boolean(array.as_boolean().value(row)),
+        ArrowType::Int8 => DataType::SmallInt(array.as_primitive::<Int8Type>().value(row) as i16),
+        ArrowType::Int16 => DataType::SmallInt(array.as_primitive::<Int16Type>().value(row)),
+        ArrowType::Int32 => DataType::Integer(array.as_primitive::<Int32Type>().value(row)),
+        ArrowType::Int64 => DataType::BigInt(array.as_primitive::<Int64Type>().value(row)),
+        ArrowType::UInt8 => DataType::SmallInt(array.as_primitive::<UInt8Type>().value(row) as i16),
+        ArrowType::UInt16 => {
+            DataType::Integer(array.as_primitive::<UInt16Type>().value(row) as i32)
+        }
+        ArrowType::UInt32 => DataType::BigInt(array.as_primitive::<UInt32Type>().value(row) as i64),
+        ArrowType::Float32 => DataType::Real(array.as_primitive::<Float32Type>().value(row)),
+        ArrowType::Float64 => {
+            DataType::DoublePrecision(array.as_primitive::<Float64Type>().value(row))
+        }
+        ArrowType::Utf8 => DataType::Text(array.as_string::<i32>().value(row).to_string()),
+        ArrowType::LargeUtf8 => DataType::Text(array.as_string::<i64>().value(row).to_string()),
+        ArrowType::Binary => DataType::Blob(array.as_binary::<i32>().value(row).to_vec()),
+        ArrowType::LargeBinary => DataType::Blob(array.as_binary::<i64>().value(row).to_vec()),
+        ArrowType::Date32 => datetime(temporal_conversions::date32_to_datetime(
+            array.as_primitive::<Date32Type>().value(row),
+        ))?,
+        ArrowType::Date64 => datetime(temporal_conversions::date64_to_datetime(
+            array.as_primitive::<Date64Type>().value(row),
+        ))?,
+        ArrowType::Timestamp(unit, time_zone) => {
+            let value = match unit {
+                TimeUnit::Second => array.as_primitive::<TimestampSecondType>().value(row),
+                TimeUnit::Millisecond => {
+                    array.as_primitive::<TimestampMillisecondType>().value(row)
+                }
+                TimeUnit::Microsecond => {
+                    array.as_primitive::<TimestampMicrosecondType>().value(row)
+                }
+                TimeUnit::Nanosecond => array.as_primitive::<TimestampNanosecondType>().value(row),
+            };
+            let value = datetime(match unit {
+                TimeUnit::Second => temporal_conversions::timestamp_s_to_datetime(value),
+                TimeUnit::Millisecond => temporal_conversions::timestamp_ms_to_datetime(value),
+                TimeUnit::Microsecond => temporal_conversions::timestamp_us_to_datetime(value),
+                TimeUnit::Nanosecond => temporal_conversions::timestamp_ns_to_datetime(value),
+            })?;
+            // Timestamps with a time zone hold the UTC instant, whatever the zone
+            match time_zone {
+                Some(_) => value
+                    .assume_utc()
+                    .map_err(|e| DataFusionError::External(Box::new(e)))?,
+                None => value,
+            }
+        }
+        _ => DataType::Text(array_value_to_string(array, row)?),
+    })
+}
+
+/// Converts a [`DataType`] into the Arrow scalar it is bound to a query parameter as.
+pub(crate) fn scalar_value(value: &DataType) -> ScalarValue {
+    match value {
+        DataType::Null => ScalarValue::Null,
+        DataType::SmallInt(n) | DataType::SmallSerial(n) => ScalarValue::Int16(Some(*n)),
+        DataType::Integer(n) | DataType::Serial(n) => ScalarValue::Int32(Some(*n)),
+        DataType::BigInt(n) | DataType::BigSerial(n) => ScalarValue::Int64(Some(*n)),
+        DataType::Real(n) => ScalarValue::Float32(Some(*n)),
+        DataType::DoublePrecision(n) | DataType::Float(n) => ScalarValue::Float64(Some(*n)),
+        DataType::Boolean(b) => ScalarValue::Boolean(Some(*b)),
+        DataType::Text(s) | DataType::VarChar(s) => ScalarValue::Utf8(Some(s.clone())),
+        DataType::Blob(bytes) => ScalarValue::Binary(Some(bytes.clone())),
+        DataType::DateTime(datetime) => {
+            ScalarValue::TimestampSecond(Some(datetime.timestamp()), None)
+        }
+        DataType::TimestampTz(datetime) => {
+            ScalarValue::TimestampSecond(Some(datetime.timestamp()), Some("UTC".into()))
+        }
+        value => ScalarValue::Utf8(Some(value.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::Field;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_batches_are_converted_to_rows() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", ArrowType::Int64, false),
+            Field::new("name", ArrowType::Utf8, true),
+            Field::new("score", ArrowType::Decimal128(5, 2), true),
+        ]));
+        let batch = |ids: Vec<i64>, names: Vec<Option<&str>>, scores: Vec<i128>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(ids)),
+                    Arc::new(StringArray::from(names)),
+                    Arc::new(
+                        Decimal128Array::from(scores)
+                            .with_precision_and_scale(5, 2)
+                            .unwrap(),
+                    ),
+                ],
+            )
+            .unwrap()
+        };
+
+        let result = QueryResult::from_batches(
+            &schema,
+            &[
+                batch(vec![1, 2], vec![Some("a"), None], vec![150, 25]),
+                batch(vec![3], vec![Some("c")], vec![-1]),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(result.columns(), &["id", "name", "score"]);
+        assert_eq!(result.row_count(), 3);
+        assert_eq!(
+            result.rows()[1],
+            vec![
+                DataType::BigInt(2),
+                DataType::Null,
+                // Types without a counterpart are converted to text
+                DataType::Text("0.25".to_string())
+            ]
+        );
+    }
+}
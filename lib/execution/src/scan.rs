@@ -0,0 +1,564 @@
+//! # Table Scans
+//!
+//! Executes `SELECT <columns> FROM <table> [WHERE <column> <op> <value>] [ORDER BY <columns>]`
+//! against the tables created by `CREATE TABLE`. The rows of the table are read from its
+//! [`TableHeap`] through the buffer pool of the engine, decoded with the kinds of the columns
+//! of its schema (see [`decode_row`]), filtered, sorted and projected.
+//!
+//! The filter compares a single column with a value (e.g. `age >= 18` or `'ada' = name`)
+//! using `=`, `<>`, `<`, `<=`, `>` or `>=`. Comparisons with NULL are never true, so a NULL in
+//! the filtered column matches no value. Rows are sorted by columns of the table, each `ASC`
+//! (the default) or `DESC`, with NULLs last in ascending order and first in descending order
+//! unless `NULLS FIRST` or `NULLS LAST` says otherwise. Both filters and sorts order text
+//! under the collation of its column (see [`Column::compare_values`]).
+//!
+//! Queries scans don't support are planned instead, and their plans executed by DataFusion
+//! over the rows of the tables they read, loaded into memory with [`load_tables`].
+
+use crate::result::scalar_value;
+use crate::{eval, EvalError, QueryEngine, QueryResult};
+use arrow::array::{new_empty_array, ArrayRef};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType as ArrowType, SchemaRef as ArrowSchemaRef};
+use arrow::record_batch::RecordBatch;
+use buffer::TableHeap;
+use catalog::{Column, DatabaseError, Table};
+use common::PageId;
+use compile::parser::{
+    BinaryOperator, Expr, GroupByExpr, ObjectName, OrderByExpr, Query, Select, SelectItem, SetExpr,
+    TableFactor,
+};
+use datafusion::datasource::{provider_as_source, MemTable};
+use datafusion_common::{DataFusionError, Result, ScalarValue};
+use datafusion_expr::{Expr as PlanExpr, LogicalPlan, LogicalPlanBuilder};
+use futures::future::BoxFuture;
+use std::{cmp::Ordering, sync::Arc};
+use storage::table::row::decode_row;
+use tracing::info;
+use ty::DataType;
+
+fn external(error: impl std::error::Error + Send + Sync + 'static) -> DataFusionError {
+    DataFusionError::External(Box::new(error))
+}
+
+fn unsupported(what: impl std::fmt::Display) -> DataFusionError {
+    DataFusionError::NotImplemented(format!("Unsupported query on a table: {}", what))
+}
+
+/// Returns the name of the table a query selects from, if it selects from a single table.
+pub(crate) fn scanned_table(query: &Query) -> Option<&ObjectName> {
+    let SetExpr::Select(select) = query.body.as_ref() else {
+        return None;
+    };
+    match select.from.as_slice() {
+        [from] if from.joins.is_empty() => match &from.relation {
+            TableFactor::Table {
+                name, args: None, ..
+            } => Some(name),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// A filter comparing the value of a column with a value.
+struct Predicate {
+    column: Column,
+    index: usize,
+    op: BinaryOperator,
+    value: DataType,
+}
+
+impl Predicate {
+    fn matches(&self, row: &[DataType]) -> Result<bool> {
+        let holds = eval::compare_with(&self.op, &row[self.index], &self.value, |a, b| {
+            self.column.compare_values(a, b)
+        })
+        .map_err(external)?;
+        Ok(matches!(holds, DataType::Boolean(true)))
+    }
+}
+
+/// A column rows are sorted by.
+struct SortKey {
+    column: Column,
+    index: usize,
+    descending: bool,
+    nulls_first: bool,
+}
+
+impl SortKey {
+    fn compare(&self, a: &[DataType], b: &[DataType]) -> Ordering {
+        let (a, b) = (&a[self.index], &b[self.index]);
+        match (a, b) {
+            (DataType::Null, DataType::Null) => Ordering::Equal,
+            (DataType::Null, _) if self.nulls_first => Ordering::Less,
+            (DataType::Null, _) => Ordering::Greater,
+            (_, DataType::Null) if self.nulls_first => Ordering::Greater,
+            (_, DataType::Null) => Ordering::Less,
+            _ => {
+                let ordering = self.column.compare_values(a, b).unwrap_or(Ordering::Equal);
+                if self.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            }
+        }
+    }
+}
+
+/// Executes a query selecting from a single table of the catalog.
+pub(crate) async fn execute(engine: &QueryEngine, query: &Query) -> Result<QueryResult> {
+    let (SetExpr::Select(select), Some(table_name)) = (query.body.as_ref(), scanned_table(query))
+    else {
+        return Err(unsupported(query));
+    };
+    if query.with.is_some()
+        || query.limit.is_some()
+        || query.offset.is_some()
+        || query.fetch.is_some()
+    {
+        return Err(unsupported(query));
+    }
+    check_clauses(select)?;
+
+    let name = table_name.to_string();
+    let table = engine
+        .catalog
+        .get_table(&name)
+        .ok_or_else(|| external(DatabaseError::TableNotFound(name.clone())))?;
+    let schema = table.schema();
+    let projection = projection(schema.columns(), &select.projection)?;
+    let predicate = select
+        .selection
+        .as_ref()
+        .map(|selection| predicate(engine, schema.columns(), selection))
+        .transpose()?;
+    let sort_keys = query
+        .order_by
+        .iter()
+        .map(|order_by| sort_key(schema.columns(), order_by))
+        .collect::<Result<Vec<_>>>()?;
+
+    let scanned = read_rows(engine, &name, &table).await?;
+    let mut rows = Vec::new();
+    for row in &scanned {
+        if let Some(predicate) = &predicate {
+            if !predicate.matches(row)? {
+                continue;
+            }
+        }
+        rows.push(row.clone());
+    }
+    // The sort is stable, so rows equal under every key stay in heap order
+    rows.sort_by(|a, b| {
+        sort_keys
+            .iter()
+            .map(|key| key.compare(a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    let rows: Vec<Vec<DataType>> = rows
+        .into_iter()
+        .map(|row| projection.iter().map(|(_, i)| row[*i].clone()).collect())
+        .collect();
+
+    info!(
+        "Scanned {} rows of `{}`, {} matched",
+        scanned.len(),
+        name,
+        rows.len()
+    );
+    let columns = projection.into_iter().map(|(name, _)| name).collect();
+    Ok(QueryResult::new(columns, rows))
+}
+
+/// Reads the rows of a table from its heap, decoded with the kinds of its columns.
+async fn read_rows(engine: &QueryEngine, name: &str, table: &Table) -> Result<Vec<Vec<DataType>>> {
+    let heap = TableHeap::new(table.heap_pages().into_iter().map(PageId::from).collect());
+    if heap.pages().is_empty() {
+        return Ok(Vec::new());
+    }
+    let buffer_pool = engine.buffer_pool.read().unwrap().clone();
+    let Some(buffer_pool) = buffer_pool else {
+        return Err(DataFusionError::Execution(format!(
+            "Cannot scan `{}` without a buffer pool to read its rows from",
+            name
+        )));
+    };
+    let records = {
+        let mut bpm = buffer_pool.lock().await;
+        heap.scan(&mut bpm)
+            .await
+            .map_err(|e| DataFusionError::External(e.into()))?
+    };
+
+    let kinds = table
+        .schema()
+        .columns()
+        .iter()
+        .map(|column| column.column_type().clone())
+        .collect::<Vec<_>>();
+    records
+        .iter()
+        .map(|record| decode_row(&kinds, record).map_err(external))
+        .collect()
+}
+
+/// Replaces the scans of catalog tables in a plan with scans of their rows, read from their
+/// heaps into memory, so that DataFusion can execute it. The filters and projection pushed
+/// into a scan are applied on top of it, as memory tables ignore them.
+pub(crate) fn load_tables<'a>(
+    engine: &'a QueryEngine,
+    plan: &'a LogicalPlan,
+) -> BoxFuture<'a, Result<LogicalPlan>> {
+    Box::pin(async move {
+        let LogicalPlan::TableScan(scan) = plan else {
+            let mut inputs = Vec::with_capacity(plan.inputs().len());
+            for input in plan.inputs() {
+                inputs.push(load_tables(engine, input).await?);
+            }
+            return plan.with_new_inputs(&inputs);
+        };
+        let name = scan.table_name.table();
+        let table = engine
+            .catalog
+            .get_table(name)
+            .ok_or_else(|| external(DatabaseError::TableNotFound(name.to_string())))?;
+        let rows = read_rows(engine, name, &table).await?;
+        let schema = scan.source.schema();
+        let batch = record_batch(&schema, &rows)?;
+        let table = MemTable::try_new(schema, vec![vec![batch]])?;
+
+        let mut builder = LogicalPlanBuilder::scan(
+            scan.table_name.clone(),
+            provider_as_source(Arc::new(table)),
+            None,
+        )?;
+        for filter in &scan.filters {
+            builder = builder.filter(filter.clone())?;
+        }
+        let columns = scan
+            .projected_schema
+            .fields()
+            .iter()
+            .map(|field| PlanExpr::Column(field.qualified_column()));
+        builder = builder.project(columns)?;
+        if let Some(fetch) = scan.fetch {
+            builder = builder.limit(0, Some(fetch))?;
+        }
+        builder.build()
+    })
+}
+
+/// Converts rows into a record batch of the given schema, casting every value to the Arrow
+/// type of its column.
+fn record_batch(schema: &ArrowSchemaRef, rows: &[Vec<DataType>]) -> Result<RecordBatch> {
+    let columns = schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(i, field)| column_array(rows, i, field.data_type()))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+fn column_array(rows: &[Vec<DataType>], index: usize, data_type: &ArrowType) -> Result<ArrayRef> {
+    if rows.is_empty() {
+        return Ok(new_empty_array(data_type));
+    }
+    let values = rows
+        .iter()
+        .map(|row| match &row[index] {
+            DataType::Null => ScalarValue::try_from(data_type),
+            value => Ok(scalar_value(value)),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // Rows hold values of the type system, which may map to another Arrow type
+    Ok(cast(&ScalarValue::iter_to_array(values)?, data_type)?)
+}
+
+/// Fails on the clauses scans don't support (e.g. `GROUP BY`).
+fn check_clauses(select: &Select) -> Result<()> {
+    let grouped = !matches!(&select.group_by, GroupByExpr::Expressions(exprs) if exprs.is_empty());
+    if select.distinct.is_some()
+        || select.top.is_some()
+        || select.into.is_some()
+        || !select.lateral_views.is_empty()
+        || grouped
+        || select.having.is_some()
+        || select.qualify.is_some()
+    {
+        return Err(unsupported(select));
+    }
+    Ok(())
+}
+
+/// Returns the index of a column of the table in its schema.
+fn column_index(schema: &[Column], expr: &Expr) -> Result<usize> {
+    let Expr::Identifier(ident) = expr else {
+        return Err(unsupported(expr));
+    };
+    schema
+        .iter()
+        .position(|column| column.column_name() == &ident.value)
+        .ok_or_else(|| DataFusionError::Plan(format!("Column `{}` does not exist", ident.value)))
+}
+
+/// Returns the name and index in the schema of each selected column.
+fn projection(schema: &[Column], items: &[SelectItem]) -> Result<Vec<(String, usize)>> {
+    let mut projection = Vec::new();
+    for item in items {
+        match item {
+            SelectItem::Wildcard(_) => projection.extend(
+                schema
+                    .iter()
+                    .enumerate()
+                    .map(|(i, column)| (column.column_name().clone(), i)),
+            ),
+            SelectItem::UnnamedExpr(expr) => {
+                let index = column_index(schema, expr)?;
+                projection.push((schema[index].column_name().clone(), index));
+            }
+            SelectItem::ExprWithAlias { expr, alias } => {
+                projection.push((alias.value.clone(), column_index(schema, expr)?));
+            }
+            item => return Err(unsupported(item)),
+        }
+    }
+    Ok(projection)
+}
+
+/// Resolves a column of the table to sort rows by.
+fn sort_key(schema: &[Column], order_by: &OrderByExpr) -> Result<SortKey> {
+    let index = column_index(schema, &order_by.expr)?;
+    let descending = order_by.asc == Some(false);
+    Ok(SortKey {
+        column: schema[index].clone(),
+        index,
+        descending,
+        nulls_first: order_by.nulls_first.unwrap_or(descending),
+    })
+}
+
+/// Resolves a filter comparing a column with a value, on either side of the operator.
+fn predicate(engine: &QueryEngine, schema: &[Column], selection: &Expr) -> Result<Predicate> {
+    let (left, op, right) = match selection {
+        Expr::Nested(expr) => return predicate(engine, schema, expr),
+        Expr::BinaryOp { left, op, right } => (left.as_ref(), op, right.as_ref()),
+        expr => return Err(unsupported(expr)),
+    };
+    let flipped = match op {
+        BinaryOperator::Eq | BinaryOperator::NotEq => op.clone(),
+        BinaryOperator::Lt => BinaryOperator::Gt,
+        BinaryOperator::LtEq => BinaryOperator::GtEq,
+        BinaryOperator::Gt => BinaryOperator::Lt,
+        BinaryOperator::GtEq => BinaryOperator::LtEq,
+        _ => return Err(unsupported(selection)),
+    };
+    let (column, op, value) = match (left, right) {
+        (Expr::Identifier(_), value) => (left, op.clone(), value),
+        (value, Expr::Identifier(_)) => (right, flipped, value),
+        _ => return Err(unsupported(selection)),
+    };
+
+    let functions = engine.functions.read().unwrap();
+    let index = column_index(schema, column)?;
+    let value = match eval::evaluate_with(value, &functions) {
+        // Values that aren't constants (e.g. subqueries) are left to the planner
+        Err(EvalError::Unsupported(_)) => return Err(unsupported(selection)),
+        value => value.map_err(external)?,
+    };
+    Ok(Predicate {
+        column: schema[index].clone(),
+        index,
+        op,
+        value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use buffer::{BufferPoolManager, ReplacementPolicy};
+    use storage::disk::{DiskManager, IN_MEMORY_PATH};
+
+    async fn engine_with_rows() -> QueryEngine {
+        let disk_manager = Arc::new(DiskManager::new(IN_MEMORY_PATH).unwrap());
+        let bpm =
+            BufferPoolManager::new_with_size(ReplacementPolicy::LRU, disk_manager, 10).unwrap();
+        let engine = QueryEngine::new();
+        engine.set_buffer_pool(Arc::new(tokio::sync::Mutex::new(bpm)));
+        for sql in [
+            "CREATE TABLE users (id INTEGER NOT NULL, name VARCHAR(8), age INTEGER)",
+            "INSERT INTO users VALUES (1, 'ada', 36), (2, 'grace', NULL), (3, 'alan', 41)",
+        ] {
+            engine.execute_query(sql).await.unwrap();
+        }
+        engine
+    }
+
+    fn ids(result: &QueryResult) -> Vec<DataType> {
+        result.rows().iter().map(|row| row[0].clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_all_rows_are_selected_without_a_filter() {
+        let engine = engine_with_rows().await;
+        let result = engine.execute_query("SELECT * FROM users").await.unwrap();
+        assert_eq!(result.columns(), &["id", "name", "age"]);
+        assert_eq!(
+            result.rows(),
+            &[
+                vec![
+                    DataType::Integer(1),
+                    DataType::VarChar("ada".to_string()),
+                    DataType::Integer(36)
+                ],
+                vec![
+                    DataType::Integer(2),
+                    DataType::VarChar("grace".to_string()),
+                    DataType::Null
+                ],
+                vec![
+                    DataType::Integer(3),
+                    DataType::VarChar("alan".to_string()),
+                    DataType::Integer(41)
+                ],
+            ]
+        );
+
+        let result = engine
+            .execute_query("SELECT name, id AS user_id FROM users")
+            .await
+            .unwrap();
+        assert_eq!(result.columns(), &["name", "user_id"]);
+        assert_eq!(
+            result.rows()[2],
+            [DataType::VarChar("alan".to_string()), DataType::Integer(3)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rows_are_filtered_by_comparison() {
+        let engine = engine_with_rows().await;
+        let select = |sql: &'static str| {
+            let engine = &engine;
+            async move { ids(&engine.execute_query(sql).await.unwrap()) }
+        };
+
+        assert_eq!(
+            select("SELECT * FROM users WHERE name = 'grace'").await,
+            [DataType::Integer(2)]
+        );
+        assert_eq!(
+            select("SELECT id FROM users WHERE id <> 2").await,
+            [DataType::Integer(1), DataType::Integer(3)]
+        );
+        // The value may come first, flipping the comparison
+        assert_eq!(
+            select("SELECT id FROM users WHERE 2 < id").await,
+            [DataType::Integer(3)]
+        );
+        // A NULL age matches neither comparison
+        assert_eq!(
+            select("SELECT id FROM users WHERE age >= 40").await,
+            [DataType::Integer(3)]
+        );
+        assert_eq!(
+            select("SELECT id FROM users WHERE age < 40").await,
+            [DataType::Integer(1)]
+        );
+        assert!(select("SELECT id FROM users WHERE age = NULL")
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rows_are_sorted_and_compared_under_the_column_collation() {
+        let engine = engine_with_rows().await;
+        for sql in [
+            "CREATE TABLE words (word TEXT COLLATE nocase, raw TEXT)",
+            "INSERT INTO words VALUES ('banana', 'banana'), ('Cherry', 'Cherry'), \
+             ('apple', 'apple'), (NULL, NULL)",
+        ] {
+            engine.execute_query(sql).await.unwrap();
+        }
+        let select = |sql: &'static str| {
+            let engine = &engine;
+            async move {
+                let result = engine.execute_query(sql).await.unwrap();
+                result
+                    .rows()
+                    .iter()
+                    .map(|row| row[0].to_string())
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(
+            select("SELECT word FROM words ORDER BY word").await,
+            ["apple", "banana", "Cherry", "NULL"]
+        );
+        assert_eq!(
+            select("SELECT raw FROM words ORDER BY raw").await,
+            ["Cherry", "apple", "banana", "NULL"]
+        );
+        assert_eq!(
+            select("SELECT word FROM words ORDER BY word DESC").await,
+            ["NULL", "Cherry", "banana", "apple"]
+        );
+        assert_eq!(
+            select("SELECT word FROM words ORDER BY raw DESC NULLS LAST").await,
+            ["banana", "apple", "Cherry", "NULL"]
+        );
+        // 'Cherry' sorts after 'b' ignoring case, but before it byte-wise
+        assert_eq!(
+            select("SELECT word FROM words WHERE word > 'b'").await,
+            ["banana", "Cherry"]
+        );
+        assert_eq!(
+            select("SELECT raw FROM words WHERE raw > 'b'").await,
+            ["banana"]
+        );
+
+        // Later keys break the ties of earlier ones
+        assert_eq!(
+            ids(&engine
+                .execute_query("SELECT id FROM users ORDER BY age DESC, id DESC")
+                .await
+                .unwrap()),
+            [
+                DataType::Integer(2),
+                DataType::Integer(3),
+                DataType::Integer(1)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_columns_and_unsupported_filters_fail() {
+        let engine = engine_with_rows().await;
+        let err = engine
+            .execute_query("SELECT email FROM users")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DataFusionError::Plan(_)), "{}", err);
+        let err = engine
+            .execute_query("SELECT * FROM users WHERE name LIKE 'a%'")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DataFusionError::NotImplemented(_)), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_filters_scans_do_not_support_are_planned() {
+        let engine = engine_with_rows().await;
+        let result = engine
+            .execute_query("SELECT id FROM users WHERE id > 1 AND age > 40")
+            .await
+            .unwrap();
+        assert_eq!(ids(&result), [DataType::Integer(3)]);
+    }
+}
@@ -0,0 +1,110 @@
+//! # Scalar Subqueries
+//!
+//! A subquery used as an expression (e.g. `WHERE amount > (SELECT total FROM orders WHERE id =
+//! 1) * 0.75`) must produce a single value. Uncorrelated scalar subqueries are evaluated once,
+//! before the query that uses them is optimized, and replaced by the value they produced: `NULL`
+//! if they returned no rows, and a [`CardinalityViolation`] if they returned more than one.
+//!
+//! Correlated scalar subqueries are left for the optimizer to decorrelate into joins.
+
+use crate::result::scalar_value;
+use crate::QueryResult;
+use arrow::compute::cast;
+use datafusion_common::tree_node::{Transformed, TreeNode, VisitRecursion};
+use datafusion_common::{DataFusionError, Result, ScalarValue};
+use datafusion_expr::{lit, Expr, LogicalPlan, Subquery};
+use futures::future::BoxFuture;
+use thiserror::Error;
+
+/// The error a query fails with when a subquery used as an expression returns more than one
+/// row.
+#[derive(Error, Debug)]
+#[error("More than one row returned by a subquery used as an expression")]
+pub struct CardinalityViolation;
+
+/// Returns whether `error` reports a scalar subquery that returned more than one row.
+pub fn is_cardinality_violation(error: &DataFusionError) -> bool {
+    matches!(error, DataFusionError::External(e) if e.is::<CardinalityViolation>())
+}
+
+/// Executes the (unoptimized) plan of a subquery, returning the rows it produced.
+pub(crate) type Executor<'a> =
+    dyn Fn(LogicalPlan) -> BoxFuture<'a, Result<QueryResult>> + Sync + 'a;
+
+/// Evaluates the uncorrelated scalar subqueries of `plan` (and of the subqueries themselves)
+/// with `execute`, replacing each by the value it produced.
+pub(crate) fn evaluate_scalar_subqueries<'a, 'e: 'a>(
+    plan: &'a LogicalPlan,
+    execute: &'a Executor<'e>,
+) -> BoxFuture<'a, Result<LogicalPlan>> {
+    Box::pin(async move {
+        let mut inputs = Vec::with_capacity(plan.inputs().len());
+        let mut changed = false;
+        for input in plan.inputs() {
+            let evaluated = evaluate_scalar_subqueries(input, execute).await?;
+            changed |= &evaluated != input;
+            inputs.push(evaluated);
+        }
+
+        let mut exprs = plan.expressions();
+        for expr in &mut exprs {
+            let subqueries = scalar_subqueries(expr)?;
+            if subqueries.is_empty() {
+                continue;
+            }
+            let mut values = Vec::with_capacity(subqueries.len());
+            for subquery in subqueries {
+                let value = evaluate(&subquery, execute).await?;
+                values.push((subquery, value));
+            }
+            *expr = expr.clone().transform_up(&|expr| {
+                Ok(match &expr {
+                    Expr::ScalarSubquery(subquery) => values
+                        .iter()
+                        .find(|(evaluated, _)| evaluated == subquery)
+                        .map_or(Transformed::No(expr), |(_, value)| {
+                            Transformed::Yes(lit(value.clone()))
+                        }),
+                    _ => Transformed::No(expr),
+                })
+            })?;
+            changed = true;
+        }
+
+        if changed {
+            plan.with_new_exprs(exprs, &inputs)
+        } else {
+            Ok(plan.clone())
+        }
+    })
+}
+
+/// Returns the uncorrelated scalar subqueries of an expression.
+fn scalar_subqueries(expr: &Expr) -> Result<Vec<Subquery>> {
+    let mut subqueries = Vec::new();
+    expr.apply(&mut |expr| {
+        if let Expr::ScalarSubquery(subquery) = expr {
+            if subquery.outer_ref_columns.is_empty() {
+                subqueries.push(subquery.clone());
+            }
+        }
+        Ok(VisitRecursion::Continue)
+    })?;
+    Ok(subqueries)
+}
+
+/// Executes a scalar subquery, returning the single value it produced.
+async fn evaluate(subquery: &Subquery, execute: &Executor<'_>) -> Result<ScalarValue> {
+    let plan = evaluate_scalar_subqueries(&subquery.subquery, execute).await?;
+    let data_type = plan.schema().field(0).data_type().clone();
+    let result = execute(plan).await?;
+    match result.rows().as_slice() {
+        [] => ScalarValue::try_from(&data_type),
+        [row] => {
+            // Rows hold values of the type system, which may map back to another Arrow type
+            let value = cast(&scalar_value(&row[0]).to_array()?, &data_type)?;
+            ScalarValue::try_from_array(&value, 0)
+        }
+        _ => Err(DataFusionError::External(Box::new(CardinalityViolation))),
+    }
+}
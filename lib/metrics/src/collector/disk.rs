@@ -0,0 +1,107 @@
+use super::MetricCollector;
+use crate::metric::{DiskIO, Metric};
+use async_trait::async_trait;
+use getset::Getters;
+use std::sync::Arc;
+use storage::disk::DiskManager;
+use tokio::sync::Mutex;
+use tracing::trace;
+use typed_builder::TypedBuilder;
+
+/// Reports the disk I/O of a [`DiskManager`] since the previous collection.
+#[derive(Debug, Clone, Getters, TypedBuilder)]
+pub struct DiskIoCollector {
+    #[getset(get = "pub")]
+    disk_manager: Arc<DiskManager>,
+    /// The counters of the disk manager as of the previous collection.
+    #[builder(default)]
+    last: Arc<Mutex<DiskIoCounters>>,
+}
+
+/// A snapshot of the cumulative I/O counters of a [`DiskManager`].
+#[derive(Debug, Default, Clone, Copy)]
+struct DiskIoCounters {
+    writes: u64,
+    flushes: u64,
+    bytes_written: u64,
+}
+
+impl DiskIoCollector {
+    pub fn new(disk_manager: Arc<DiskManager>) -> Self {
+        DiskIoCollector::builder()
+            .disk_manager(disk_manager)
+            .build()
+    }
+}
+
+#[async_trait]
+impl MetricCollector for DiskIoCollector {
+    #[inline]
+    fn name(&self) -> String {
+        "Disk I/O Collector".to_string()
+    }
+
+    #[inline]
+    async fn collect(&self) -> Metric {
+        let mut last = self.last.lock().await;
+
+        let current = DiskIoCounters {
+            writes: self.disk_manager.num_writes() as u64,
+            flushes: self.disk_manager.num_flushes() as u64,
+            bytes_written: self.disk_manager.num_bytes_written(),
+        };
+        let prev = std::mem::replace(&mut *last, current);
+
+        trace!(
+            "Disk I/O: {} writes, {} flushes, {} bytes written since the last collection",
+            current.writes - prev.writes,
+            current.flushes - prev.flushes,
+            current.bytes_written - prev.bytes_written
+        );
+        Metric::DiskIO(
+            DiskIO::builder()
+                .writes(current.writes - prev.writes)
+                .flushes(current.flushes - prev.flushes)
+                .bytes_written(current.bytes_written - prev.bytes_written)
+                .total_writes(current.writes)
+                .total_flushes(current.flushes)
+                .total_bytes_written(current.bytes_written)
+                .build(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{PAGE_SIZE, USABLE_PAGE_SIZE};
+
+    #[tokio::test]
+    async fn test_collect_reflects_writes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db_file = temp_dir.path().join("test.db");
+        let disk_manager = Arc::new(DiskManager::new(db_file.to_str().unwrap()).unwrap());
+        let collector = DiskIoCollector::new(disk_manager.clone());
+
+        for page_id in 0..3 {
+            disk_manager
+                .write_page(page_id, &[page_id as u8 + 1; USABLE_PAGE_SIZE])
+                .unwrap();
+        }
+
+        let Metric::DiskIO(disk_io) = collector.collect().await else {
+            panic!("Expected a disk I/O metric");
+        };
+        assert_eq!(*disk_io.writes(), 3);
+        assert_eq!(*disk_io.flushes(), 3);
+        assert_eq!(*disk_io.bytes_written(), 3 * PAGE_SIZE as u64);
+
+        // Only the writes since the previous collection are reported
+        disk_manager.write_page(3, &[4; USABLE_PAGE_SIZE]).unwrap();
+        let Metric::DiskIO(disk_io) = collector.collect().await else {
+            panic!("Expected a disk I/O metric");
+        };
+        assert_eq!(*disk_io.writes(), 1);
+        assert_eq!(*disk_io.bytes_written(), PAGE_SIZE as u64);
+    }
+}
@@ -5,6 +5,7 @@ use sysinfo::System;
 use tokio::sync::Mutex;
 
 pub mod cpu;
+pub mod disk;
 pub mod memory;
 
 #[async_trait]
@@ -0,0 +1,168 @@
+use crate::metric::{LatencyBucket, QueryLatency};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// The exclusive upper bounds of the latency buckets. Latencies of at least the last bound
+/// fall into a final, unbounded bucket.
+pub const LATENCY_BUCKET_BOUNDS: [Duration; 4] = [
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(100),
+    Duration::from_secs(1),
+];
+
+/// The labels of the latency buckets, in the order of [`LatencyHistogram::bucket_counts`].
+pub const LATENCY_BUCKET_LABELS: [&str; 5] = ["<1ms", "<10ms", "<100ms", "<1s", ">=1s"];
+
+/// Counts latencies into the buckets of [`LATENCY_BUCKET_BOUNDS`].
+///
+/// Recording is lock-free, so a histogram can be shared by every connection of the server.
+/// Percentiles are estimated from the buckets: a percentile is reported as the upper bound of
+/// the bucket it falls into, capped at the slowest latency recorded.
+#[derive(Debug, Default)]
+pub struct LatencyHistogram {
+    buckets: [AtomicU64; 5],
+    count: AtomicU64,
+    total_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a latency into its bucket.
+    pub fn record(&self, latency: Duration) {
+        let bucket = LATENCY_BUCKET_BOUNDS
+            .iter()
+            .position(|bound| latency < *bound)
+            .unwrap_or(LATENCY_BUCKET_BOUNDS.len());
+        let nanos = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    /// Returns the number of latencies recorded.
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of latencies in each bucket, fastest bucket first.
+    pub fn bucket_counts(&self) -> [u64; 5] {
+        std::array::from_fn(|bucket| self.buckets[bucket].load(Ordering::Relaxed))
+    }
+
+    /// Returns the mean of the latencies recorded, or zero if none were.
+    pub fn average(&self) -> Duration {
+        match self.count() {
+            0 => Duration::ZERO,
+            count => Duration::from_nanos(self.total_nanos.load(Ordering::Relaxed) / count),
+        }
+    }
+
+    /// Returns the slowest latency recorded.
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max_nanos.load(Ordering::Relaxed))
+    }
+
+    /// Estimates the latency below which the fraction `percentile` (between 0 and 1) of the
+    /// latencies recorded fall, or zero if none were.
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        let counts = self.bucket_counts();
+        let count: u64 = counts.iter().sum();
+        if count == 0 {
+            return Duration::ZERO;
+        }
+
+        let rank = ((percentile * count as f64).ceil() as u64).clamp(1, count);
+        let mut seen = 0;
+        for (bucket, bucket_count) in counts.iter().enumerate() {
+            seen += bucket_count;
+            if seen >= rank {
+                return LATENCY_BUCKET_BOUNDS
+                    .get(bucket)
+                    .map_or(self.max(), |bound| (*bound).min(self.max()));
+            }
+        }
+        self.max()
+    }
+
+    pub fn p50(&self) -> Duration {
+        self.percentile(0.50)
+    }
+
+    pub fn p99(&self) -> Duration {
+        self.percentile(0.99)
+    }
+
+    /// Returns the current state of the histogram as a metric.
+    pub fn snapshot(&self) -> QueryLatency {
+        let buckets = LATENCY_BUCKET_LABELS
+            .iter()
+            .zip(self.bucket_counts())
+            .map(|(label, count)| {
+                LatencyBucket::builder()
+                    .label(label.to_string())
+                    .count(count)
+                    .build()
+            })
+            .collect();
+
+        QueryLatency::builder()
+            .buckets(buckets)
+            .count(self.count())
+            .sum(Duration::from_nanos(
+                self.total_nanos.load(Ordering::Relaxed),
+            ))
+            .average(self.average())
+            .p50(self.p50())
+            .p99(self.p99())
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latencies_are_bucketed_and_summarized() {
+        let histogram = LatencyHistogram::new();
+        assert_eq!(histogram.p50(), Duration::ZERO);
+
+        let latencies = [
+            (Duration::from_micros(500), 50),
+            (Duration::from_millis(5), 30),
+            (Duration::from_millis(50), 15),
+            (Duration::from_millis(500), 4),
+            (Duration::from_secs(2), 1),
+        ];
+        for (latency, times) in latencies {
+            for _ in 0..times {
+                histogram.record(latency);
+            }
+        }
+
+        assert_eq!(histogram.count(), 100);
+        assert_eq!(histogram.bucket_counts(), [50, 30, 15, 4, 1]);
+        assert_eq!(histogram.p50(), Duration::from_millis(1));
+        assert_eq!(histogram.p99(), Duration::from_secs(1));
+        // The slowest bucket is unbounded, so its percentiles are the slowest latency
+        assert_eq!(histogram.percentile(1.0), Duration::from_secs(2));
+        assert_eq!(histogram.max(), Duration::from_secs(2));
+
+        // Percentiles never exceed the slowest latency recorded
+        let histogram = LatencyHistogram::new();
+        histogram.record(Duration::from_millis(3));
+        assert_eq!(histogram.p99(), Duration::from_millis(3));
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(*snapshot.count(), 1);
+        assert_eq!(snapshot.buckets()[1].label(), "<10ms");
+        assert_eq!(*snapshot.buckets()[1].count(), 1);
+    }
+}
@@ -1,7 +1,9 @@
 #![allow(dead_code)]
 
 pub mod collector;
+pub mod histogram;
 pub mod metric;
 pub mod prof;
+pub mod prometheus;
 // pub mod stats;
 pub mod manager;
@@ -1,11 +1,14 @@
 use crate::{
     collector::MetricCollector,
+    histogram::LatencyHistogram,
     metric::{Metric, MetricKind},
+    prometheus::PrometheusWriter,
 };
 use core::fmt;
 use dashmap::DashMap;
 use serde_json::json;
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 use tracing::debug;
 
@@ -24,12 +27,14 @@ pub type MetricCollectorRef = Arc<dyn MetricCollector>;
 pub struct MetricsManager {
     metrics: Arc<DashMap<MetricKind, Metric>>,
     collectors: Vec<MetricCollectorRef>,
+    query_latency: LatencyHistogram,
 }
 
 impl fmt::Debug for MetricsManager {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("MetricsManager")
             .field("metrics", &self.metrics)
+            .field("query_latency", &self.query_latency)
             .finish()
     }
 }
@@ -39,9 +44,20 @@ impl MetricsManager {
         Self {
             metrics: Arc::new(DashMap::new()),
             collectors: vec![],
+            query_latency: LatencyHistogram::new(),
         }
     }
 
+    /// Records how long a query took to execute into the query latency histogram.
+    pub fn record_query_latency(&self, latency: Duration) {
+        self.query_latency.record(latency);
+    }
+
+    /// Returns the histogram of the latencies of the queries executed so far.
+    pub fn query_latency(&self) -> &LatencyHistogram {
+        &self.query_latency
+    }
+
     pub async fn update_metric(&self, metric_type: MetricKind, metric: Metric) {
         self.metrics.insert(metric_type, metric);
     }
@@ -55,10 +71,32 @@ impl MetricsManager {
                 serde_json::to_value(entry.value()).unwrap(),
             );
         }
+        all_metrics.insert(
+            format!("{:?}", MetricKind::QueryLatency),
+            serde_json::to_value(Metric::QueryLatency(self.query_latency.snapshot())).unwrap(),
+        );
 
         json!(all_metrics).to_string()
     }
 
+    /// Renders the latest value of every collected metric, along with the query latency
+    /// histogram, in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut metrics: Vec<_> = self
+            .metrics
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        metrics.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut writer = PrometheusWriter::new();
+        for (_, metric) in metrics {
+            metric.write_prometheus(&mut writer);
+        }
+        Metric::QueryLatency(self.query_latency.snapshot()).write_prometheus(&mut writer);
+        writer.finish()
+    }
+
     pub fn register_collector(&mut self, collector: impl MetricCollector + 'static) {
         self.collectors.push(Arc::new(collector));
     }
@@ -76,3 +114,106 @@ impl MetricsManager {
         collected_metrics
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collector::disk::DiskIoCollector;
+    use common::USABLE_PAGE_SIZE;
+    use std::collections::{HashMap, HashSet};
+    use storage::disk::DiskManager;
+
+    /// Parses a Prometheus text exposition into its samples (keyed by name and labels),
+    /// checking that each family is declared once, before its samples, and that names are
+    /// valid.
+    fn parse_exposition(exposition: &str) -> HashMap<String, f64> {
+        let valid_name = |name: &str| {
+            name.chars().enumerate().all(|(i, c)| {
+                c.is_ascii_alphabetic() || c == '_' || c == ':' || (i > 0 && c.is_ascii_digit())
+            })
+        };
+        let mut helps = HashSet::new();
+        let mut types = HashMap::new();
+        let mut samples = HashMap::new();
+
+        for line in exposition.lines() {
+            if let Some(help) = line.strip_prefix("# HELP ") {
+                let name = help.split(' ').next().unwrap();
+                assert!(
+                    helps.insert(name.to_string()),
+                    "Duplicate HELP for {}",
+                    name
+                );
+            } else if let Some(declaration) = line.strip_prefix("# TYPE ") {
+                let (name, metric_type) = declaration.split_once(' ').unwrap();
+                assert!(["counter", "gauge", "histogram"].contains(&metric_type));
+                assert!(types.insert(name.to_string(), metric_type).is_none());
+            } else {
+                let (series, value) = line.rsplit_once(' ').unwrap();
+                let name = series.split('{').next().unwrap();
+                assert!(valid_name(name), "Invalid metric name {}", name);
+                assert!(name.starts_with("r2db2_"), "{}", name);
+
+                let family = ["_bucket", "_sum", "_count"]
+                    .iter()
+                    .find_map(|suffix| {
+                        let family = name.strip_suffix(suffix)?;
+                        (types.get(family) == Some(&"histogram")).then_some(family)
+                    })
+                    .unwrap_or(name);
+                match types.get(family) {
+                    Some(&"counter") => assert!(name.ends_with("_total"), "{}", name),
+                    Some(_) => {}
+                    None => panic!("{} was not declared before its samples", name),
+                }
+                samples.insert(series.to_string(), value.parse().unwrap());
+            }
+        }
+        samples
+    }
+
+    #[tokio::test]
+    async fn test_prometheus_exposition() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db_file = temp_dir.path().join("test.db");
+        let disk_manager = Arc::new(DiskManager::new(db_file.to_str().unwrap()).unwrap());
+        let mut metrics_manager = MetricsManager::new();
+        metrics_manager.register_collector(DiskIoCollector::new(disk_manager.clone()));
+
+        disk_manager.write_page(0, &[1; USABLE_PAGE_SIZE]).unwrap();
+        metrics_manager.record_query_latency(Duration::from_micros(200));
+        metrics_manager.record_query_latency(Duration::from_millis(20));
+        metrics_manager.collect_metrics().await;
+        let before = parse_exposition(&metrics_manager.to_prometheus());
+
+        assert_eq!(before["r2db2_disk_writes_total"], 1.0);
+        assert_eq!(
+            before["r2db2_query_latency_seconds_bucket{le=\"0.001\"}"],
+            1.0
+        );
+        assert_eq!(
+            before["r2db2_query_latency_seconds_bucket{le=\"0.01\"}"],
+            1.0
+        );
+        assert_eq!(
+            before["r2db2_query_latency_seconds_bucket{le=\"+Inf\"}"],
+            2.0
+        );
+        assert_eq!(before["r2db2_query_latency_seconds_count"], 2.0);
+
+        // Counters keep counting across collections, although the collector reports the
+        // writes of each interval
+        disk_manager.write_page(1, &[2; USABLE_PAGE_SIZE]).unwrap();
+        metrics_manager.record_query_latency(Duration::from_secs(3));
+        metrics_manager.collect_metrics().await;
+        let after = parse_exposition(&metrics_manager.to_prometheus());
+
+        assert_eq!(after["r2db2_disk_writes_total"], 2.0);
+        for (series, value) in &before {
+            if series.contains("_total") || series.contains("_bucket") || series.ends_with("_count")
+            {
+                assert!(after[series] >= *value, "{} decreased", series);
+            }
+        }
+    }
+}
@@ -5,6 +5,8 @@ use tracing::info;
 use typed_builder::TypedBuilder;
 
 use crate::collector::memory::format_memory_usage;
+use crate::histogram::LATENCY_BUCKET_BOUNDS;
+use crate::prometheus::{MetricType, PrometheusWriter};
 
 /// Represents different kinds of metrics that can be collected.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
@@ -18,6 +20,7 @@ pub enum MetricKind {
     TransactionRate,
     GarbageCollection,
     QueryExecutionTime,
+    QueryLatency,
 
     // Network metrics
     NetworkIO,
@@ -59,6 +62,7 @@ pub enum Metric {
     TransactionRate(TransactionRate),
     GarbageCollection(GarbageCollection),
     QueryExecutionTime(QueryExecutionTime),
+    QueryLatency(QueryLatency),
 
     // Network metrics
     NetworkIO(NetworkIO),
@@ -93,6 +97,7 @@ impl Metric {
             Metric::TransactionRate(_) => MetricKind::TransactionRate,
             Metric::GarbageCollection(_) => MetricKind::GarbageCollection,
             Metric::QueryExecutionTime(_) => MetricKind::QueryExecutionTime,
+            Metric::QueryLatency(_) => MetricKind::QueryLatency,
             Metric::NetworkIO(_) => MetricKind::NetworkIO,
             Metric::DiskIO(_) => MetricKind::DiskIO,
             Metric::TableSpaceUsage(_) => MetricKind::TableSpaceUsage,
@@ -161,6 +166,37 @@ pub struct QueryExecutionTime {
     average_duration: Duration,
 }
 
+/// Metric for tracking the distribution of query latencies.
+///
+/// Latencies are counted into buckets (see [`LatencyHistogram`](crate::histogram::LatencyHistogram)),
+/// from which the percentiles are estimated.
+#[derive(Debug, Clone, Getters, Setters, TypedBuilder, Serialize, Deserialize)]
+#[getset(get = "pub")]
+pub struct QueryLatency {
+    /// Number of queries in each latency bucket, fastest bucket first.
+    buckets: Vec<LatencyBucket>,
+    /// Total number of queries.
+    count: u64,
+    /// Total latency of the queries.
+    sum: Duration,
+    /// Average latency of the queries.
+    average: Duration,
+    /// Median latency of the queries.
+    p50: Duration,
+    /// 99th percentile latency of the queries.
+    p99: Duration,
+}
+
+/// The number of queries whose latency falls into a bucket of a [`QueryLatency`] histogram.
+#[derive(Debug, Clone, Getters, Setters, TypedBuilder, Serialize, Deserialize)]
+#[getset(get = "pub")]
+pub struct LatencyBucket {
+    /// The range of latencies in the bucket, e.g. `<10ms`.
+    label: String,
+    /// Number of queries in the bucket.
+    count: u64,
+}
+
 /// Metric for tracking network I/O.
 ///
 /// This includes the total number of bytes sent and received.
@@ -174,17 +210,23 @@ pub struct NetworkIO {
 
 /// Metric for tracking disk I/O.
 ///
-/// This metric covers both read and write operations, including the total bytes and time spent.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Counts the page writes, flushes and bytes written since the previous collection, along with
+/// their totals since the database was opened.
+#[derive(Debug, Clone, Getters, Setters, TypedBuilder, Serialize, Deserialize)]
+#[getset(get = "pub")]
 pub struct DiskIO {
-    /// Total bytes read from disk.
-    read_bytes: u64,
-    /// Total bytes written to disk.
-    write_bytes: u64,
-    /// Total time spent in read operations.
-    read_time: Duration,
-    /// Total time spent in write operations.
-    write_time: Duration,
+    /// Number of pages written to disk during the interval.
+    writes: u64,
+    /// Number of flushes of the database file during the interval.
+    flushes: u64,
+    /// Number of bytes written to disk during the interval.
+    bytes_written: u64,
+    /// Total number of pages written to disk.
+    total_writes: u64,
+    /// Total number of flushes of the database file.
+    total_flushes: u64,
+    /// Total number of bytes written to disk.
+    total_bytes_written: u64,
 }
 
 /// Metric for tracking cache hit rate.
@@ -284,13 +326,17 @@ impl Metric {
             Metric::QueryExecutionTime(data) => {
                 info!("Average Query Execution Time: {:?}", data.average_duration)
             }
+            Metric::QueryLatency(data) => info!(
+                "Query Latency - Count: {}, Average: {:?}, p50: {:?}, p99: {:?}",
+                data.count, data.average, data.p50, data.p99
+            ),
             Metric::NetworkIO(data) => info!(
                 "Network I/O - Sent: {} bytes, Received: {} bytes",
                 data.bytes_sent, data.bytes_received
             ),
             Metric::DiskIO(data) => info!(
-                "Disk I/O - Read: {} bytes, Write: {} bytes, Read Time: {:?}, Write Time: {:?}",
-                data.read_bytes, data.write_bytes, data.read_time, data.write_time
+                "Disk I/O - Writes: {}, Flushes: {}, Written: {} bytes",
+                data.writes, data.flushes, data.bytes_written
             ),
             Metric::CacheHitRate(data) => info!("Cache Hit Rate: {}%", data.hit_rate_percentage),
             Metric::ReplicationDelay(data) => {
@@ -318,3 +364,241 @@ impl Metric {
         }
     }
 }
+
+impl Metric {
+    /// Writes the metric in the Prometheus text exposition format: cumulative values as
+    /// counters, latency distributions as histograms and everything else as gauges.
+    pub fn write_prometheus(&self, writer: &mut PrometheusWriter) {
+        match self {
+            Metric::CpuUsage(data) => {
+                writer
+                    .declare(
+                        "cpu_usage_percent",
+                        MetricType::Gauge,
+                        "CPU usage (0-100%).",
+                    )
+                    .sample("cpu_usage_percent", &[], data.usage_percentage as f64);
+            }
+            Metric::MemoryUsage(data) => {
+                // The collector reports the used memory in bytes (see `format_memory_usage`)
+                writer
+                    .declare("memory_usage_bytes", MetricType::Gauge, "Used memory.")
+                    .sample("memory_usage_bytes", &[], data.usage_mb as f64);
+            }
+            Metric::ActiveConnections(data) => {
+                writer
+                    .declare(
+                        "active_connections",
+                        MetricType::Gauge,
+                        "Active client connections.",
+                    )
+                    .sample("active_connections", &[], data.count as f64);
+            }
+            Metric::TransactionRate(data) => {
+                writer
+                    .declare(
+                        "transactions_per_second",
+                        MetricType::Gauge,
+                        "Transactions executed per second.",
+                    )
+                    .sample("transactions_per_second", &[], data.per_second as f64);
+            }
+            Metric::GarbageCollection(data) => {
+                writer
+                    .declare(
+                        "garbage_collections_total",
+                        MetricType::Counter,
+                        "Garbage collection events.",
+                    )
+                    .sample("garbage_collections_total", &[], data.count as f64)
+                    .declare(
+                        "garbage_collection_seconds_total",
+                        MetricType::Counter,
+                        "Time spent in garbage collection.",
+                    )
+                    .sample(
+                        "garbage_collection_seconds_total",
+                        &[],
+                        data.total_duration.as_secs_f64(),
+                    );
+            }
+            Metric::QueryExecutionTime(data) => {
+                writer
+                    .declare(
+                        "query_execution_seconds_average",
+                        MetricType::Gauge,
+                        "Average query execution time.",
+                    )
+                    .sample(
+                        "query_execution_seconds_average",
+                        &[],
+                        data.average_duration.as_secs_f64(),
+                    );
+            }
+            Metric::QueryLatency(data) => {
+                writer.declare(
+                    "query_latency_seconds",
+                    MetricType::Histogram,
+                    "Latency of the queries executed.",
+                );
+                let mut cumulative = 0;
+                for (bucket, latency_bucket) in data.buckets.iter().enumerate() {
+                    cumulative += latency_bucket.count;
+                    let le = LATENCY_BUCKET_BOUNDS
+                        .get(bucket)
+                        .map_or("+Inf".to_string(), |bound| bound.as_secs_f64().to_string());
+                    writer.sample(
+                        "query_latency_seconds_bucket",
+                        &[("le", &le)],
+                        cumulative as f64,
+                    );
+                }
+                writer
+                    .sample("query_latency_seconds_sum", &[], data.sum.as_secs_f64())
+                    .sample("query_latency_seconds_count", &[], data.count as f64);
+            }
+            Metric::NetworkIO(data) => {
+                writer
+                    .declare(
+                        "network_sent_bytes_total",
+                        MetricType::Counter,
+                        "Bytes sent over the network.",
+                    )
+                    .sample("network_sent_bytes_total", &[], data.bytes_sent as f64)
+                    .declare(
+                        "network_received_bytes_total",
+                        MetricType::Counter,
+                        "Bytes received over the network.",
+                    )
+                    .sample(
+                        "network_received_bytes_total",
+                        &[],
+                        data.bytes_received as f64,
+                    );
+            }
+            Metric::DiskIO(data) => {
+                writer
+                    .declare(
+                        "disk_writes_total",
+                        MetricType::Counter,
+                        "Pages written to disk.",
+                    )
+                    .sample("disk_writes_total", &[], data.total_writes as f64)
+                    .declare(
+                        "disk_flushes_total",
+                        MetricType::Counter,
+                        "Flushes of the database file.",
+                    )
+                    .sample("disk_flushes_total", &[], data.total_flushes as f64)
+                    .declare(
+                        "disk_written_bytes_total",
+                        MetricType::Counter,
+                        "Bytes written to disk.",
+                    )
+                    .sample(
+                        "disk_written_bytes_total",
+                        &[],
+                        data.total_bytes_written as f64,
+                    );
+            }
+            Metric::TableSpaceUsage(data) => {
+                writer
+                    .declare(
+                        "tablespace_used_megabytes",
+                        MetricType::Gauge,
+                        "Space used in the tablespace.",
+                    )
+                    .sample("tablespace_used_megabytes", &[], data.space_used_mb as f64)
+                    .declare(
+                        "tablespace_free_megabytes",
+                        MetricType::Gauge,
+                        "Space free in the tablespace.",
+                    )
+                    .sample("tablespace_free_megabytes", &[], data.space_free_mb as f64);
+            }
+            Metric::CacheHitRate(data) => {
+                writer
+                    .declare(
+                        "cache_hit_rate_percent",
+                        MetricType::Gauge,
+                        "Cache hits out of all cache accesses (0-100%).",
+                    )
+                    .sample(
+                        "cache_hit_rate_percent",
+                        &[],
+                        data.hit_rate_percentage as f64,
+                    );
+            }
+            Metric::ReplicationDelay(data) => {
+                writer
+                    .declare(
+                        "replication_delay_seconds",
+                        MetricType::Gauge,
+                        "Delay of data replication.",
+                    )
+                    .sample("replication_delay_seconds", &[], data.delay_seconds as f64);
+            }
+            Metric::LockWaitTime(data) => {
+                writer
+                    .declare(
+                        "lock_wait_seconds_average",
+                        MetricType::Gauge,
+                        "Average time spent waiting for locks.",
+                    )
+                    .sample(
+                        "lock_wait_seconds_average",
+                        &[],
+                        data.average_wait_time.as_secs_f64(),
+                    );
+            }
+            Metric::RowOperations(data) => {
+                writer.declare(
+                    "row_operations_total",
+                    MetricType::Counter,
+                    "Row operations by kind.",
+                );
+                for (operation, count) in [
+                    ("read", data.reads),
+                    ("insert", data.inserts),
+                    ("update", data.updates),
+                    ("delete", data.deletes),
+                ] {
+                    writer.sample(
+                        "row_operations_total",
+                        &[("operation", operation)],
+                        count as f64,
+                    );
+                }
+            }
+            Metric::IndexUsage(data) => {
+                writer.declare(
+                    "index_operations_total",
+                    MetricType::Counter,
+                    "Index operations by index and kind.",
+                );
+                for (operation, count) in [
+                    ("scan", data.scans),
+                    ("read", data.reads),
+                    ("write", data.writes),
+                ] {
+                    writer.sample(
+                        "index_operations_total",
+                        &[("index", &data.index_name), ("operation", operation)],
+                        count as f64,
+                    );
+                }
+            }
+            Metric::QueryTypeStats(data) => {
+                writer.declare("queries_total", MetricType::Counter, "Queries by type.");
+                for (query_type, count) in [
+                    ("select", data.select_count),
+                    ("insert", data.insert_count),
+                    ("update", data.update_count),
+                    ("delete", data.delete_count),
+                ] {
+                    writer.sample("queries_total", &[("type", query_type)], count as f64);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! # Prometheus Exposition
+//!
+//! Renders metrics in the Prometheus text exposition format (version 0.0.4), so that the
+//! `/metrics` endpoint can be scraped by Prometheus and compatible tools.
+
+use core::fmt;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// The `Content-Type` of the Prometheus text exposition format.
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// The prefix of the names of every metric exposed.
+pub const METRIC_NAME_PREFIX: &str = "r2db2_";
+
+/// The type of a metric family, as declared by its `# TYPE` line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricType {
+    /// A cumulative value that only increases (names end in `_total`)
+    Counter,
+    /// A value that can go up and down
+    Gauge,
+    /// Observations counted into cumulative `le` buckets, with a `_sum` and a `_count`
+    Histogram,
+}
+
+impl fmt::Display for MetricType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetricType::Counter => write!(f, "counter"),
+            MetricType::Gauge => write!(f, "gauge"),
+            MetricType::Histogram => write!(f, "histogram"),
+        }
+    }
+}
+
+/// Builds a Prometheus exposition, declaring each metric family once however many samples
+/// are written for it.
+#[derive(Debug, Default)]
+pub struct PrometheusWriter {
+    output: String,
+    declared: HashSet<String>,
+}
+
+impl PrometheusWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes the `# HELP` and `# TYPE` lines of a metric family, unless it was already
+    /// declared. `name` is given without the [`METRIC_NAME_PREFIX`].
+    pub fn declare(&mut self, name: &str, metric_type: MetricType, help: &str) -> &mut Self {
+        if self.declared.insert(name.to_string()) {
+            let help = help.replace('\\', "\\\\").replace('\n', "\\n");
+            let _ = writeln!(
+                self.output,
+                "# HELP {}{} {}",
+                METRIC_NAME_PREFIX, name, help
+            );
+            let _ = writeln!(
+                self.output,
+                "# TYPE {}{} {}",
+                METRIC_NAME_PREFIX, name, metric_type
+            );
+        }
+        self
+    }
+
+    /// Writes a sample of a metric family. `name` is given without the [`METRIC_NAME_PREFIX`]
+    /// and includes any suffix (e.g. `_bucket` for histograms).
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) -> &mut Self {
+        let _ = write!(self.output, "{}{}", METRIC_NAME_PREFIX, name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(label, value)| format!("{}=\"{}\"", label, escape_label_value(value)))
+                .collect();
+            let _ = write!(self.output, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.output, " {}", format_value(value));
+        self
+    }
+
+    /// Returns the exposition written so far.
+    pub fn finish(self) -> String {
+        self.output
+    }
+}
+
+/// Escapes backslashes, quotes and line feeds in a label value.
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Formats a sample value, spelling out the special values as Prometheus expects.
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
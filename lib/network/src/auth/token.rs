@@ -4,6 +4,9 @@ use jsonwebtoken::{
 };
 use serde::{Deserialize, Serialize};
 
+/// The secret tokens are signed with unless the server is configured with another one.
+pub(crate) const DEFAULT_TOKEN_SECRET: &str = "my_secret_key";
+
 #[derive(Debug, Serialize, Deserialize)]
 struct Claims {
     sub: String,
@@ -1,6 +1,7 @@
 use anyhow::{anyhow, Context, Result};
 use cli::{ClientArgs, NetworkProtocol};
 use getset::{Getters, Setters};
+use std::future::Future;
 use thiserror::Error;
 use tokio::time::{sleep, Duration};
 use tokio::{
@@ -22,6 +23,32 @@ pub enum ClientError {
 
     #[error("Response error: {0}")]
     ResponseError(String),
+
+    #[error("Timed out after {0:?}")]
+    Timeout(Duration),
+}
+
+/// Runs a network operation, failing with [`ClientError::Timeout`] if it takes longer than
+/// `timeout` milliseconds. Without a timeout, the operation may block forever.
+async fn with_timeout<T>(
+    timeout: Option<u64>,
+    operation: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let Some(timeout) = timeout.map(Duration::from_millis) else {
+        return operation.await;
+    };
+    tokio::time::timeout(timeout, operation)
+        .await
+        .map_err(|_| ClientError::Timeout(timeout))?
+}
+
+/// The response to a query: the rows of its result set (if any) and the tag the server
+/// completed the command with.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Getters, TypedBuilder)]
+#[getset(get = "pub")]
+pub struct QueryResult {
+    rows: Vec<Vec<String>>,
+    tag: String,
 }
 
 #[derive(Debug, Getters, Setters, TypedBuilder)]
@@ -29,6 +56,7 @@ pub enum ClientError {
 pub struct DbClient {
     server_address: String,
     protocol: NetworkProtocol,
+    /// How long, in milliseconds, every network operation may take (unbounded if unset)
     timeout: Option<u64>,
     ssl: bool,
     stream: Option<TcpStream>,
@@ -56,9 +84,12 @@ impl DbClient {
 
         trace!("Connecting to server at {}", &self.server_address);
         // Otherwise, create a new stream
-        let stream = TcpStream::connect(&self.server_address)
-            .await
-            .context("Failed to connect to server")?;
+        let stream = with_timeout(self.timeout, async {
+            TcpStream::connect(&self.server_address)
+                .await
+                .context("Failed to connect to server")
+        })
+        .await?;
 
         // Set the stream
         self.set_stream(Some(stream));
@@ -97,6 +128,18 @@ impl DbClient {
                 MessageKind::ErrorResponse => todo!(),
                 MessageKind::AuthenticationRequest => todo!(),
                 MessageKind::ReadyForQuery => todo!(),
+                // Neither cancel requests nor the extended query protocol are handled here
+                kind @ (MessageKind::Cancel
+                | MessageKind::Parse
+                | MessageKind::Bind
+                | MessageKind::Execute
+                | MessageKind::ParameterDescription) => {
+                    return Err(ClientError::ResponseError(format!(
+                        "Unexpected {} from server",
+                        kind
+                    ))
+                    .into())
+                }
             }
         } else {
             error!("Failed to parse incoming message");
@@ -107,24 +150,53 @@ impl DbClient {
         Ok(())
     }
 
-    async fn receive_and_process_response(stream: &mut TcpStream) -> Result<()> {
-        let response = DbClient::receive_response(stream).await?;
-
-        // TODO: handle reponse: parse it, log it, handle errors, etc.
-        info!("Received response from server: {}", response);
-
-        Ok(())
+    // Receive the response to a request, accumulating the rows of a result set until the
+    // server reports that the command completed
+    async fn receive_and_process_response<S: AsyncReadExt + Unpin>(
+        stream: &mut S,
+    ) -> Result<QueryResult> {
+        let mut rows = Vec::new();
+
+        loop {
+            match DbClient::receive_response(stream).await? {
+                Message::DataRowMessage(row) => {
+                    trace!("Received row with {} columns", row.columns().len());
+                    rows.push(row.columns().clone());
+                }
+                Message::CommandCompleteMessage(complete) => {
+                    info!(
+                        "Received response from server: {} ({} rows)",
+                        complete.tag(),
+                        rows.len()
+                    );
+                    return Ok(QueryResult::builder()
+                        .rows(rows)
+                        .tag(complete.tag().clone())
+                        .build());
+                }
+                Message::ErrorResponse(response) => {
+                    error!("Server responded with an error: {}", response.error());
+                    return Err(ClientError::ResponseError(response.error().clone()).into());
+                }
+                Message::ReadyForQuery(_) => trace!("Server is ready for query"),
+                message => {
+                    return Err(ClientError::ResponseError(format!(
+                        "Unexpected {} in response",
+                        message.kind()
+                    ))
+                    .into())
+                }
+            }
+        }
     }
 
-    async fn receive_response(stream: &mut TcpStream) -> Result<String> {
-        let mut buffer = [0; 1024];
-        let n = stream
-            .read(&mut buffer)
+    async fn receive_response<S: AsyncReadExt + Unpin>(stream: &mut S) -> Result<Message> {
+        Protocol::parse_incoming(stream)
             .await
-            .context("Failed to read response from server")?;
-
-        let response = String::from_utf8_lossy(&buffer[..n]).to_string();
-        Ok(response)
+            .context("Failed to read response from server")?
+            .ok_or_else(|| {
+                ClientError::ResponseError("Connection closed by server".to_string()).into()
+            })
     }
 
     // Send a startup message to the server and process the response
@@ -134,37 +206,127 @@ impl DbClient {
         let startup_message = Message::serialize_startup_message();
 
         trace!("Sending startup message");
+        let timeout = self.timeout;
         if let Some(stream) = &mut self.stream {
-            stream
-                .write_all(&startup_message)
-                .await
-                .context("Failed to send startup message to the server")?;
+            let result = async {
+                with_timeout(timeout, async {
+                    stream
+                        .write_all(&startup_message)
+                        .await
+                        .context("Failed to send startup message to the server")
+                })
+                .await?;
 
-            DbClient::receive_and_process_response(stream).await?;
+                with_timeout(timeout, DbClient::receive_and_process_response(stream)).await
+            }
+            .await;
+            self.drop_stream_on_timeout(result)?;
         }
 
         Ok(())
     }
 
-    // Send a SQL query to the server and process the response
-    pub async fn send_sql_query(&mut self, query: &str) -> Result<()> {
+    // Send a SQL query to the server and process the response. Queries that do not fit in
+    // a single frame are split into chunks, which the server reassembles.
+    pub async fn send_sql_query(&mut self, query: &str) -> Result<QueryResult> {
         // self.connect().await?;
-        let query_message = Message::serialize_query(query);
+        let query_message = Protocol::encode_chunked(
+            &Message::query_message(query.to_string()),
+            Protocol::MAX_FRAME_LENGTH as usize,
+        );
 
         trace!("Sending query message");
 
+        let timeout = self.timeout;
+        let Some(stream) = &mut self.stream else {
+            return Err(
+                ClientError::ResponseError("Not connected to the server".to_string()).into(),
+            );
+        };
+        let result = async {
+            with_timeout(timeout, async {
+                stream.write_all(&query_message).await.context(format!(
+                    "Failed to send query message '{}' to the server",
+                    query
+                ))
+            })
+            .await?;
+
+            with_timeout(timeout, DbClient::receive_and_process_response(stream)).await
+        }
+        .await;
+
+        self.drop_stream_on_timeout(result)
+    }
+
+    // A request that timed out may still be answered later, and its response would be taken
+    // for the response to the next request, so the connection is dropped instead of reused
+    fn drop_stream_on_timeout<T>(&mut self, result: Result<T>) -> Result<T> {
+        if let Err(err) = &result {
+            if let Some(ClientError::Timeout(_)) = err.downcast_ref::<ClientError>() {
+                warn!(
+                    "Dropping the connection to {} after a timeout",
+                    self.server_address
+                );
+                self.stream = None;
+            }
+        }
+        result
+    }
+
+    // Ask the server to cancel the query with the given id, which may be running on another
+    // connection, and process the response
+    pub async fn cancel_query(&mut self, query_id: u64) -> Result<()> {
+        let cancel_message = Protocol::encode_message(&Message::cancel_message(query_id));
+
+        trace!("Sending cancel message for query {}", query_id);
+
+        let timeout = self.timeout;
         if let Some(stream) = &mut self.stream {
-            stream.write_all(&query_message).await.context(format!(
-                "Failed to send query message '{}' to the server",
-                query
-            ))?;
+            let result = async {
+                with_timeout(timeout, async {
+                    stream
+                        .write_all(&cancel_message)
+                        .await
+                        .context(format!("Failed to cancel query {}", query_id))
+                })
+                .await?;
 
-            DbClient::receive_and_process_response(stream).await?;
+                with_timeout(timeout, DbClient::receive_and_process_response(stream)).await
+            }
+            .await;
+            self.drop_stream_on_timeout(result)?;
         }
 
         Ok(())
     }
 
+    // Tell the server the connection is closing with a termination message, then shut the
+    // connection down. Does nothing if the client isn't connected.
+    pub async fn disconnect(&mut self) -> Result<()> {
+        let Some(mut stream) = self.stream.take() else {
+            trace!("Not connected to server");
+            return Ok(());
+        };
+
+        trace!("Sending termination message");
+        let termination_message = Protocol::encode_message(&Message::termination_message());
+        with_timeout(self.timeout, async {
+            stream
+                .write_all(&termination_message)
+                .await
+                .context("Failed to send termination message to the server")?;
+            stream
+                .shutdown()
+                .await
+                .context("Failed to shut down the connection")
+        })
+        .await?;
+
+        info!("Disconnected from server at {}", self.server_address);
+        Ok(())
+    }
+
     pub async fn connect_with_retry(
         &mut self,
         max_retries: u32,
@@ -225,5 +387,149 @@ pub async fn start_client(args: &ClientArgs) -> Result<()> {
         error!("Failed to send query: {:?}", e);
     }
 
-    Ok(())
+    client.disconnect().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::MiddlewareStack;
+    use crate::protocol::handler::SharedQueryState;
+    use crate::server::tcp::DbServer;
+    use dashmap::DashMap;
+    use driver::Driver;
+    use metrics::manager::MetricsManager;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_response_rows_are_collected_until_command_complete() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let long_column = "x".repeat(4096);
+
+        let rows = vec![
+            vec!["1".to_string(), "alice".to_string()],
+            vec!["2".to_string(), long_column],
+        ];
+        let expected = rows.clone();
+        tokio::spawn(async move {
+            for row in rows {
+                Protocol::send_message(&mut server, Message::data_row_message(row))
+                    .await
+                    .unwrap();
+            }
+            let complete = Message::command_complete_message("SELECT 2".to_string());
+            Protocol::send_message(&mut server, complete).await.unwrap();
+            let error = Message::error_response("relation does not exist".to_string());
+            Protocol::send_message(&mut server, error).await.unwrap();
+        });
+
+        let result = DbClient::receive_and_process_response(&mut client)
+            .await
+            .unwrap();
+        assert_eq!(result.rows(), &expected);
+        assert_eq!(result.tag(), "SELECT 2");
+
+        let err = DbClient::receive_and_process_response(&mut client)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("relation does not exist"));
+    }
+
+    #[tokio::test]
+    async fn test_large_query_is_reassembled_by_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let query = format!("SELECT '{}'", "q".repeat(50_000 - 9));
+        assert_eq!(query.len(), 50_000);
+
+        let expected = query.clone();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let message = Protocol::parse_incoming(&mut stream)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(message.query(), expected);
+            let response = Message::command_complete_message("QUERY EXECUTED".to_string());
+            Protocol::send_message(&mut stream, response).await.unwrap();
+        });
+
+        let mut client = DbClient::new(address.to_string());
+        client.connect().await.unwrap();
+        client.send_sql_query(&query).await.unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_disconnect_closes_the_connection_on_the_server() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Arc::new(Driver::new(db_path.to_str().unwrap()).await.unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let connections = Arc::new(DashMap::new());
+        let mut server = DbServer::builder()
+            .server_address(address)
+            .driver(driver)
+            .middleware_stack(Arc::new(MiddlewareStack::new()))
+            .metrics_manager(Arc::new(MetricsManager::new()))
+            .connections(connections.clone())
+            .max_connections(4)
+            .queries(SharedQueryState::new(4))
+            .build();
+        tokio::spawn(async move { server.serve_until(listener, std::future::pending()).await });
+
+        let mut client = DbClient::new(address.to_string());
+        client.connect().await.unwrap();
+        client.send_sql_query("SELECT 1").await.unwrap();
+        assert_eq!(connections.len(), 1);
+
+        client.disconnect().await.unwrap();
+        assert!(client.stream().is_none());
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !connections.is_empty() {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("The server should drop the connection");
+        // Disconnecting twice is harmless
+        client.disconnect().await.unwrap();
+
+        // The server closes its end of the connection once it is terminated
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        Protocol::send_message(&mut stream, Message::termination_message())
+            .await
+            .unwrap();
+        assert_eq!(Protocol::parse_incoming(&mut stream).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_unresponsive_server_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        // Accepts the connection but never responds
+        let server = tokio::spawn(async move { listener.accept().await.unwrap() });
+
+        let mut client = DbClient::new(address.to_string());
+        client.set_timeout(Some(50));
+        client.connect().await.unwrap();
+        let err = tokio::time::timeout(Duration::from_secs(5), client.send_sql_query("SELECT 1"))
+            .await
+            .expect("The query should time out rather than hang")
+            .unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref::<ClientError>(),
+                Some(ClientError::Timeout(timeout)) if *timeout == Duration::from_millis(50)
+            ),
+            "unexpected error: {}",
+            err
+        );
+        // The late response must not be taken for the response to the next query
+        assert!(client.stream().is_none());
+        assert!(client.send_sql_query("SELECT 1").await.is_err());
+        drop(server);
+    }
 }
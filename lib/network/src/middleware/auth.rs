@@ -0,0 +1,117 @@
+use super::{Middleware, RequestRejected};
+use crate::auth::password::PasswordAuthenticator;
+use crate::auth::token::{TokenAuthenticator, DEFAULT_TOKEN_SECRET};
+use crate::protocol::message::Message;
+use crate::protocol::stream::ClientStream;
+use crate::server::tcp::{generate_connection_id, ConnectionId};
+use async_trait::async_trait;
+use dashmap::DashMap;
+use tracing::{debug, warn};
+
+/// The `auth_type` of the `AuthenticationRequest` sent to clients that query before
+/// authenticating, asking for a username and password (or a token).
+pub const PASSWORD_AUTHENTICATION: u8 = 3;
+
+/// Middleware enforcing the startup handshake.
+///
+/// Queries (simple or extended) are rejected with an `AuthenticationRequest` until the
+/// connection has sent a `StartupMessage` with valid credentials, checked by the
+/// [`PasswordAuthenticator`] or, for token credentials, the [`TokenAuthenticator`]. A startup
+/// message with invalid credentials is answered with an `ErrorResponse`. Other requests (e.g.
+/// cancel requests, which may be sent from a fresh connection) are let through.
+pub struct AuthMiddleware {
+    password_authenticator: PasswordAuthenticator,
+    token_authenticator: TokenAuthenticator,
+    // Whether each open connection has authenticated
+    connections: DashMap<ConnectionId, bool>,
+}
+
+impl AuthMiddleware {
+    pub fn new() -> Self {
+        Self::with_token_secret(DEFAULT_TOKEN_SECRET.to_string())
+    }
+
+    /// Creates a middleware accepting tokens signed with `secret`.
+    pub fn with_token_secret(secret: String) -> Self {
+        AuthMiddleware {
+            password_authenticator: PasswordAuthenticator::new(),
+            token_authenticator: TokenAuthenticator::new(secret),
+            connections: DashMap::new(),
+        }
+    }
+
+    fn is_authenticated(&self, connection_id: &ConnectionId) -> bool {
+        self.connections
+            .get(connection_id)
+            .is_some_and(|authenticated| *authenticated)
+    }
+}
+
+impl Default for AuthMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Middleware for AuthMiddleware {
+    #[inline]
+    fn name(&self) -> String {
+        "AuthMiddleware".to_string()
+    }
+
+    async fn on_connect(&self, stream: &dyn ClientStream) -> anyhow::Result<()> {
+        let connection_id = generate_connection_id(&stream.peer_addr()?);
+        self.connections.insert(connection_id, false);
+        Ok(())
+    }
+
+    async fn before_request(
+        &self,
+        stream: &mut dyn ClientStream,
+        message: &Message,
+    ) -> anyhow::Result<()> {
+        let connection_id = generate_connection_id(&stream.peer_addr()?);
+        match message {
+            Message::StartupMessage(startup) => {
+                let response = startup
+                    .authenticate_with(&self.password_authenticator, &self.token_authenticator);
+                let authenticated = matches!(response, Message::ReadyForQuery(_));
+                self.connections
+                    .insert(connection_id.clone(), authenticated);
+                if !authenticated {
+                    warn!("Connection {} failed to authenticate", connection_id);
+                    return Err(RequestRejected(response).into());
+                }
+                debug!("Connection {} authenticated", connection_id);
+            }
+            Message::QueryMessage(_)
+            | Message::Parse(_)
+            | Message::Bind(_)
+            | Message::Execute(_)
+                if !self.is_authenticated(&connection_id) =>
+            {
+                debug!(
+                    "Rejecting query of unauthenticated connection {}",
+                    connection_id
+                );
+                return Err(RequestRejected(Message::authentication_request(
+                    PASSWORD_AUTHENTICATION,
+                ))
+                .into());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn after_request(&self, _stream: &mut dyn ClientStream) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn on_disconnect(&self, stream: &dyn ClientStream) -> anyhow::Result<()> {
+        let connection_id = generate_connection_id(&stream.peer_addr()?);
+        self.connections.remove(&connection_id);
+        Ok(())
+    }
+}
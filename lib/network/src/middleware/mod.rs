@@ -1,13 +1,16 @@
+use crate::protocol::message::Message;
+use crate::protocol::stream::ClientStream;
 use anyhow::Result;
 use async_trait::async_trait;
 use core::fmt;
 use getset::{Getters, Setters};
 use std::sync::Arc;
 use std::time::Instant;
-use tokio::net::TcpStream;
+use thiserror::Error;
 use tracing::trace;
 use typed_builder::TypedBuilder;
 
+pub mod auth;
 pub mod trace;
 
 /// The `Middleware` trait defines the interface for middleware components in the server.
@@ -19,10 +22,10 @@ pub mod trace;
 ///
 /// # Lifecycle Hooks
 ///
-/// - `on_connect`: Called when a new TCP connection is established.
+/// - `on_connect`: Called when a new connection is established.
 /// - `before_request`: Invoked before a request is processed.
 /// - `after_request`: Invoked after a request has been processed.
-/// - `on_disconnect`: Called when a TCP connection is terminated.
+/// - `on_disconnect`: Called when a connection is terminated.
 ///
 /// Implementors of this trait should ensure that operations are non-blocking and asynchronous,
 /// as the server operates in an async runtime environment, with the overarching goal of minimizing
@@ -46,20 +49,20 @@ pub trait Middleware: Send + Sync {
     /// ```
     fn name(&self) -> String;
 
-    /// Hook that is called when a new TCP connection is established.
+    /// Hook that is called when a new connection is established.
     ///
     /// This method is invoked when a new client connects to the server. It can be used for initializing
     /// per-connection resources, logging connection information, performing initial authentication, etc.
     ///
     /// # Arguments
     ///
-    /// * `stream` - A reference to the TCP stream representing the client connection.
+    /// * `stream` - A reference to the stream representing the client connection (e.g. a TCP stream).
     ///
     /// # Errors
     ///
     /// Implementors should return an error if any operation in this hook fails. This will generally result in
     /// the termination of the connection.
-    async fn on_connect(&self, stream: &TcpStream) -> Result<()>;
+    async fn on_connect(&self, stream: &dyn ClientStream) -> Result<()>;
 
     /// Hook that is called before a request is processed by the server.
     ///
@@ -69,13 +72,15 @@ pub trait Middleware: Send + Sync {
     ///
     /// # Arguments
     ///
-    /// * `stream` - A mutable reference to the TCP stream, allowing middleware to modify the incoming data.
+    /// * `stream` - A mutable reference to the client stream, allowing middleware to modify the incoming data.
+    /// * `message` - The request about to be processed.
     ///
     /// # Errors
     ///
     /// Implementors should return an error if any operation in this hook fails. Depending on the server's
-    /// implementation, this may halt further processing of the request.
-    async fn before_request(&self, stream: &mut TcpStream) -> Result<()>;
+    /// implementation, this may halt further processing of the request. Returning a [`RequestRejected`]
+    /// error skips the request, sending its response to the client, and keeps the connection open.
+    async fn before_request(&self, stream: &mut dyn ClientStream, message: &Message) -> Result<()>;
 
     /// Hook that is called after a request has been processed by the server.
     ///
@@ -84,30 +89,36 @@ pub trait Middleware: Send + Sync {
     ///
     /// # Arguments
     ///
-    /// * `stream` - A mutable reference to the TCP stream, allowing middleware to modify the outgoing data.
+    /// * `stream` - A mutable reference to the client stream, allowing middleware to modify the outgoing data.
     ///
     /// # Errors
     ///
     /// Implementors should return an error if any operation in this hook fails. Depending on the server's
     /// implementation, this may affect the final response sent to the client.
-    async fn after_request(&self, stream: &mut TcpStream) -> Result<()>;
+    async fn after_request(&self, stream: &mut dyn ClientStream) -> Result<()>;
 
-    /// Hook that is called when a TCP connection is terminated.
+    /// Hook that is called when a connection is terminated.
     ///
     /// This method is invoked when a client disconnects or the connection is otherwise terminated.
     /// Useful for cleanup tasks, logging disconnection events, releasing resources, etc.
     ///
     /// # Arguments
     ///
-    /// * `stream` - A reference to the TCP stream that is being disconnected.
+    /// * `stream` - A reference to the client stream that is being disconnected.
     ///
     /// # Errors
     ///
     /// Implementors should return an error if any operation in this hook fails, although since the connection
     /// is closing, the impact of such errors is typically limited.
-    async fn on_disconnect(&self, stream: &TcpStream) -> Result<()>;
+    async fn on_disconnect(&self, stream: &dyn ClientStream) -> Result<()>;
 }
 
+/// The error a middleware returns from [`Middleware::before_request`] to refuse a request,
+/// carrying the response sent to the client in place of processing the request.
+#[derive(Error, Debug)]
+#[error("Request rejected by middleware")]
+pub struct RequestRejected(pub Message);
+
 /// A reference-counted reference to a [`MiddlewareStack`].
 pub type MiddlewareStackRef = Arc<MiddlewareStack>;
 
@@ -144,28 +155,32 @@ impl MiddlewareStack {
         );
     }
 
-    pub async fn handle_connect(&self, stream: &TcpStream) -> anyhow::Result<()> {
+    pub async fn handle_connect(&self, stream: &dyn ClientStream) -> anyhow::Result<()> {
         for middleware in &self.middlewares {
             middleware.on_connect(stream).await?;
         }
         Ok(())
     }
 
-    pub async fn handle_before_request(&self, stream: &mut TcpStream) -> anyhow::Result<()> {
+    pub async fn handle_before_request(
+        &self,
+        stream: &mut dyn ClientStream,
+        message: &Message,
+    ) -> anyhow::Result<()> {
         for middleware in &self.middlewares {
-            middleware.before_request(stream).await?;
+            middleware.before_request(stream, message).await?;
         }
         Ok(())
     }
 
-    pub async fn handle_after_request(&self, stream: &mut TcpStream) -> anyhow::Result<()> {
+    pub async fn handle_after_request(&self, stream: &mut dyn ClientStream) -> anyhow::Result<()> {
         for middleware in &self.middlewares {
             middleware.after_request(stream).await?;
         }
         Ok(())
     }
 
-    pub async fn handle_disconnect(&self, stream: &TcpStream) -> anyhow::Result<()> {
+    pub async fn handle_disconnect(&self, stream: &dyn ClientStream) -> anyhow::Result<()> {
         for middleware in &self.middlewares {
             middleware.on_disconnect(stream).await?;
         }
@@ -1,9 +1,10 @@
 use super::Middleware;
+use crate::protocol::message::Message;
+use crate::protocol::stream::ClientStream;
 use async_trait::async_trait;
 use common::util::time::{elapsed_duration_since, format_duration, now_as_u64};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::net::TcpStream;
 use tracing::{info, trace, warn};
 
 /// Middleware for logging various stages of the connection lifecycle.
@@ -36,7 +37,7 @@ impl Middleware for LoggingMiddleware {
     }
 
     #[inline]
-    async fn on_connect(&self, stream: &TcpStream) -> anyhow::Result<()> {
+    async fn on_connect(&self, stream: &dyn ClientStream) -> anyhow::Result<()> {
         self.connection_start_time
             .store(now_as_u64(), Ordering::SeqCst);
         trace!("Connection established with {}", stream.peer_addr()?);
@@ -44,7 +45,11 @@ impl Middleware for LoggingMiddleware {
     }
 
     #[inline]
-    async fn before_request(&self, stream: &mut TcpStream) -> anyhow::Result<()> {
+    async fn before_request(
+        &self,
+        stream: &mut dyn ClientStream,
+        _message: &Message,
+    ) -> anyhow::Result<()> {
         self.request_start_time
             .store(now_as_u64(), Ordering::SeqCst);
         trace!("Handling request from {}", stream.peer_addr()?);
@@ -52,7 +57,7 @@ impl Middleware for LoggingMiddleware {
     }
 
     #[inline]
-    async fn after_request(&self, stream: &mut TcpStream) -> anyhow::Result<()> {
+    async fn after_request(&self, stream: &mut dyn ClientStream) -> anyhow::Result<()> {
         let request_duration =
             elapsed_duration_since(self.request_start_time.load(Ordering::SeqCst));
         trace!(
@@ -64,7 +69,7 @@ impl Middleware for LoggingMiddleware {
     }
 
     #[inline]
-    async fn on_disconnect(&self, stream: &TcpStream) -> anyhow::Result<()> {
+    async fn on_disconnect(&self, stream: &dyn ClientStream) -> anyhow::Result<()> {
         let connection_duration =
             elapsed_duration_since(self.connection_start_time.load(Ordering::SeqCst));
         trace!(
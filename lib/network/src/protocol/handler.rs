@@ -1,83 +1,216 @@
-use super::message::Message;
-use crate::middleware::MiddlewareStackRef;
+use super::message::{ErrorResponse, Message};
+use crate::middleware::{MiddlewareStackRef, RequestRejected};
 use crate::protocol::message::MessageKind;
-use crate::protocol::Protocol;
-use crate::server::tcp::{generate_connection_id, ConnectionId, SemaphoreRef};
+use crate::protocol::stream::ClientStream;
+use crate::protocol::{sqlstate, Protocol};
+use crate::server::tcp::{
+    generate_connection_id, ConnectionId, QueryId, RunningQueriesRef, SemaphoreRef,
+};
 use anyhow::{anyhow, Result};
+use bytes::BytesMut;
 use dashmap::DashMap;
-use driver::DriverRef;
+use driver::{BoundStatement, Driver, DriverRef, PreparedStatements, QueryCancelled, QueryResult};
+use metrics::manager::{MetricsManager, MetricsManagerRef};
+use std::collections::HashMap;
 use std::io::{self};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
 use tokio::net::TcpStream;
 use tokio::sync::mpsc::{self, Receiver, Sender};
-use tokio::sync::SemaphorePermit;
-use tracing::{debug, error, info, trace};
+use tokio::sync::{broadcast, Semaphore};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, trace, warn};
+use ty::DataType;
 use typed_builder::TypedBuilder;
 
+/// The tag of the `CommandCompleteMessage` sent once a command succeeded: the tag of its
+/// result (e.g. `INSERT 0 1`), or `QUERY EXECUTED` for results without one.
+fn command_tag(result: &QueryResult) -> String {
+    result
+        .command_tag()
+        .clone()
+        .unwrap_or_else(|| "QUERY EXECUTED".to_string())
+}
+
+/// Limits a [`ConnectionHandler`] enforces on the requests of its client.
+#[derive(Debug, Clone, Copy, TypedBuilder)]
+pub struct ConnectionSettings {
+    /// Upper bound on the payload of a (chunked) client message.
+    #[builder(default = common::MAX_MESSAGE_LENGTH)]
+    pub max_message_length: usize,
+    /// Queries running longer than this are aborted.
+    #[builder(default)]
+    pub statement_timeout: Option<Duration>,
+    /// Upper bound on the number of result rows that are ready at once written to the client
+    /// in a single write.
+    #[builder(default = common::ROW_FLUSH_BATCH_SIZE)]
+    pub row_flush_batch_size: usize,
+}
+
+impl Default for ConnectionSettings {
+    fn default() -> Self {
+        ConnectionSettings::builder().build()
+    }
+}
+
+/// A command a client asked the server to execute.
+#[derive(Debug)]
+enum Command {
+    /// The SQL of a `QueryMessage`.
+    Query(String),
+    /// The statement bound to the portal of an `Execute` message.
+    Portal(Box<BoundStatement>),
+}
+
+/// Server-wide state the handlers of every connection share to coordinate their queries.
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct SharedQueryState {
+    /// Cancellation tokens of the queries running on every connection of the server.
+    pub running_queries: RunningQueriesRef,
+    /// Bounds the number of queries executing at once; further queries wait for a permit.
+    pub query_throttle: SemaphoreRef,
+    /// Tells handlers to close their connection once their current request is complete.
+    pub shutdown: broadcast::Sender<()>,
+    /// Records the latency of every query.
+    #[builder(default = Arc::new(MetricsManager::new()))]
+    pub metrics_manager: MetricsManagerRef,
+}
+
+impl SharedQueryState {
+    /// Creates the state for a server running at most `max_transactions` queries at once.
+    pub fn new(max_transactions: usize) -> Self {
+        SharedQueryState::builder()
+            .running_queries(Arc::new(DashMap::new()))
+            .query_throttle(Arc::new(Semaphore::new(max_transactions)))
+            .shutdown(broadcast::channel(1).0)
+            .build()
+    }
+}
+
 #[derive(Debug, TypedBuilder)]
 pub struct ConnectionHandler {
-    stream: TcpStream,
+    stream: Box<dyn ClientStream>,
     receiver: Receiver<TcpStream>,
     driver: DriverRef,
     connections: Arc<DashMap<ConnectionId, bool>>,
     middleware_stack: MiddlewareStackRef,
+    settings: ConnectionSettings,
+    // The secret key identifying the query running on this connection in cancel requests
+    query_id: QueryId,
+    // Running queries and the query throttle, shared with every connection of the server
+    queries: SharedQueryState,
+    // Subscribed on creation, so that no shutdown signal is missed before the handler runs
+    shutdown: broadcast::Receiver<()>,
+    // Statements prepared by the client, released when it disconnects
+    #[builder(default)]
+    prepared_statements: PreparedStatements,
+    // Statements bound by the client, by portal name
+    #[builder(default)]
+    portals: HashMap<String, BoundStatement>,
     // conn_pool_sender: mpsc::Sender<()>, // Sender to release connection pool permit
-    // query_throttle_sender: mpsc::Sender<()>, // Sender to release query throttle permit
 }
 
 impl ConnectionHandler {
     pub fn new(
-        stream: TcpStream,
+        stream: impl ClientStream + 'static,
         receiver: Receiver<TcpStream>,
         driver: DriverRef,
         connections: Arc<DashMap<ConnectionId, bool>>,
         middleware_stack: MiddlewareStackRef,
+        settings: ConnectionSettings,
+        queries: SharedQueryState,
         // conn_pool_sender: mpsc::Sender<()>,
-        // query_throttle_sender: mpsc::Sender<()>,
     ) -> Self {
         ConnectionHandler::builder()
-            .stream(stream)
+            .stream(Box::new(stream))
             .receiver(receiver)
             .driver(driver)
             .connections(connections)
             .middleware_stack(middleware_stack)
+            .settings(settings)
+            .query_id(rand::random())
+            .shutdown(queries.shutdown.subscribe())
+            .queries(queries)
             // .conn_pool_sender(conn_pool_sender)
-            // .query_throttle_sender(query_throttle_sender)
             .build()
     }
 
+    /// Replaces the receiver of shutdown signals, for handlers created after their connection
+    /// was accepted (e.g. once it was upgraded to a WebSocket) that must not miss a signal sent
+    /// in the meantime.
+    pub fn with_shutdown(mut self, shutdown: broadcast::Receiver<()>) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
     pub async fn handle_connection(&mut self) -> Result<()> {
         // Invoke middleware's on_connect method
-        if let Err(e) = self.middleware_stack.handle_connect(&self.stream).await {
+        if let Err(e) = self
+            .middleware_stack
+            .handle_connect(self.stream.as_ref())
+            .await
+        {
             error!("Error in middleware on_connect: {:?}", e);
             return Err(anyhow!("Error in middleware on_connect"));
         }
 
         // Main loop for handling client requests
         loop {
-            match Protocol::parse_incoming(&mut self.stream).await? {
+            let message = tokio::select! {
+                biased;
+                _ = self.shutdown.recv() => {
+                    info!("Server is shutting down, closing connection");
+                    self.handle_disconnect().await?;
+                    break;
+                }
+                message = Protocol::parse_incoming_with_limit(
+                    &mut self.stream,
+                    self.settings.max_message_length,
+                ) => message?,
+            };
+
+            match message {
                 Some(message) => {
                     // Invoke middleware's before_request method
                     if let Err(e) = self
                         .middleware_stack
-                        .handle_before_request(&mut self.stream)
+                        .handle_before_request(self.stream.as_mut(), &message)
                         .await
                     {
-                        error!("Error in middleware before_request: {:?}", e);
-                        return Err(anyhow!("Error in handling before_request lifecycle hook within middleware stack."));
+                        match e.downcast::<RequestRejected>() {
+                            Ok(RequestRejected(response)) => {
+                                debug!("Request rejected by middleware: {}", message);
+                                Protocol::send_message(&mut self.stream, response).await?;
+                                continue;
+                            }
+                            Err(e) => {
+                                error!("Error in middleware before_request: {:?}", e);
+                                return Err(anyhow!("Error in handling before_request lifecycle hook within middleware stack."));
+                            }
+                        }
                     }
 
+                    let terminating = matches!(message, Message::TerminationMessage(_));
                     self.process_message(message).await?;
 
                     // Invoke middleware's after_request method
                     if let Err(e) = self
                         .middleware_stack
-                        .handle_after_request(&mut self.stream)
+                        .handle_after_request(self.stream.as_mut())
                         .await
                     {
                         error!("Error in middleware after_request: {:?}", e);
                         return Err(anyhow!("Error in handling after_request lifecycle hook within middleware stack."));
                     }
+
+                    // The client asked to close the connection, which was already disconnected
+                    if terminating {
+                        if let Err(e) = self.stream.shutdown().await {
+                            debug!("Failed to shut down the terminated connection: {}", e);
+                        }
+                        break;
+                    }
                 }
                 None => {
                     self.handle_disconnect().await?;
@@ -93,8 +226,21 @@ impl ConnectionHandler {
         let connection_id = generate_connection_id(&self.stream.peer_addr()?);
         self.connections.remove(&connection_id);
 
+        // The statements of the client go away with its connection
+        self.portals.clear();
+        if let Err(e) = self.driver.deallocate_all(&mut self.prepared_statements) {
+            error!(
+                "Failed to release the prepared statements of the client: {}",
+                e
+            );
+        }
+
         // Invoke middleware's on_disconnect method
-        if let Err(e) = self.middleware_stack.handle_disconnect(&self.stream).await {
+        if let Err(e) = self
+            .middleware_stack
+            .handle_disconnect(self.stream.as_ref())
+            .await
+        {
             error!("Error in middleware on_disconnect: {:?}", e);
             return Err(anyhow!("Error in middleware on_disconnect"));
         }
@@ -134,6 +280,18 @@ impl ConnectionHandler {
             MessageKind::QueryMessage => {
                 self.process_query_message(message).await?;
             }
+            MessageKind::Cancel => {
+                self.process_cancel_message(message).await?;
+            }
+            MessageKind::Parse => {
+                self.process_parse_message(message).await?;
+            }
+            MessageKind::Bind => {
+                self.process_bind_message(message).await?;
+            }
+            MessageKind::Execute => {
+                self.process_execute_message(message).await?;
+            }
             MessageKind::TerminationMessage => {
                 self.handle_disconnect().await?;
             }
@@ -157,8 +315,10 @@ impl ConnectionHandler {
         // TODO: Perform initialization or setup required for a new client (e.g. authentication)
         // ...
 
-        // Send back a CommandCompleteMessage as a placeholder
-        let response = Message::command_complete_message("STARTUP COMPLETE".to_string());
+        // Send back a CommandCompleteMessage as a placeholder. The tag carries the id that
+        // cancel requests (from any connection) use to refer to this connection's query.
+        let response =
+            Message::command_complete_message(format!("STARTUP COMPLETE {}", self.query_id));
         Protocol::send_message(&mut self.stream, response).await?;
         Ok(())
     }
@@ -168,24 +328,696 @@ impl ConnectionHandler {
         let query = query_message.query();
 
         info!("Received query: `{}`", query);
+        self.execute_command(Command::Query(query)).await
+    }
+
+    /// Executes a command like any query of the client: it can be cancelled, is aborted once
+    /// it exceeds the statement timeout, waits for a free execution slot, and streams its rows
+    /// to the client before the message completing it.
+    async fn execute_command(&mut self, command: Command) -> io::Result<()> {
+        // Register the query so that it can be cancelled while it runs
+        let token = CancellationToken::new();
+        self.queries
+            .running_queries
+            .insert(self.query_id, token.clone());
+
+        // Execute on a separate task so that a panicking query does not take down the connection
+        let driver = self.driver.clone();
+        let statement_timeout = self.settings.statement_timeout;
+        let query_throttle = self.queries.query_throttle.clone();
+        let (row_sender, mut row_receiver) =
+            mpsc::channel(self.settings.row_flush_batch_size.max(1));
+        let started = Instant::now();
+        let execution = tokio::spawn(async move {
+            // Wait for a free execution slot; a queued query can still be cancelled
+            let _permit = tokio::select! {
+                permit = query_throttle.acquire_owned() => permit?,
+                _ = token.cancelled() => return Err(QueryCancelled.into()),
+            };
 
-        // TODO: execute query on db here
+            let execution = async {
+                match &command {
+                    Command::Query(query) => {
+                        driver
+                            .execute_sql_command_streaming(query, &token, &row_sender)
+                            .await
+                    }
+                    Command::Portal(statement) => {
+                        driver
+                            .execute_bound_streaming(statement, &token, &row_sender)
+                            .await
+                    }
+                }
+            };
+            match statement_timeout {
+                Some(timeout) => Driver::with_statement_timeout(execution, &token, timeout).await,
+                None => execution.await,
+            }
+        });
 
-        // Send back a CommandCompleteMessage as a placeholder
-        // let response = Message::CommandCompleteMessage {
-        //     tag: "QUERY EXECUTED".to_string(),
-        // };
+        // Stream the result set as it is produced, ahead of the completion of the command
+        let streamed = self.stream_rows(&mut row_receiver).await;
+        // A query still producing rows for a client that went away is cancelled
+        drop(row_receiver);
+        let result = execution.await;
+        self.queries.running_queries.remove(&self.query_id);
+        self.queries
+            .metrics_manager
+            .record_query_latency(started.elapsed());
+        streamed?;
 
-        let response = Message::command_complete_message("QUERY EXECUTED".to_string());
+        let response = match result {
+            Ok(Ok(result)) => Message::command_complete_message(command_tag(&result)),
+            Ok(Err(e)) if e.is::<QueryCancelled>() => {
+                info!("Query {} was cancelled", self.query_id);
+                Message::error_response_with_code(
+                    sqlstate::QUERY_CANCELED,
+                    "QUERY CANCELLED".to_string(),
+                )
+            }
+            Ok(Err(e)) => Message::ErrorResponse(ErrorResponse::from(&e)),
+            Err(e) => {
+                error!("Query execution failed: {}", e);
+                Message::error_response("Query execution failed".to_string())
+            }
+        };
         Protocol::send_message(&mut self.stream, response).await?;
         Ok(())
     }
 
+    /// Writes the rows of a query to the client as they are received, until the query drops
+    /// its sender. Up to [`ConnectionSettings::row_flush_batch_size`] rows that are ready at
+    /// once are coalesced into a single write, and every write is flushed right away.
+    async fn stream_rows(&mut self, rows: &mut Receiver<Vec<DataType>>) -> io::Result<()> {
+        let mut buffer = BytesMut::new();
+        while let Some(row) = rows.recv().await {
+            Self::encode_row(&mut buffer, row);
+            for _ in 1..self.settings.row_flush_batch_size {
+                match rows.try_recv() {
+                    Ok(row) => Self::encode_row(&mut buffer, row),
+                    Err(_) => break,
+                }
+            }
+
+            self.stream.write_all(&buffer).await?;
+            self.stream.flush().await?;
+            buffer.clear();
+        }
+        Ok(())
+    }
+
+    fn encode_row(buffer: &mut BytesMut, row: Vec<DataType>) {
+        let columns = row.iter().map(ToString::to_string).collect();
+        buffer.extend_from_slice(&Protocol::encode_chunked(
+            &Message::data_row_message(columns),
+            Protocol::MAX_FRAME_LENGTH as usize,
+        ));
+    }
+
+    async fn process_cancel_message(&mut self, message: Message) -> io::Result<()> {
+        let Message::Cancel(cancel) = message else {
+            unreachable!("Message is not a Cancel message");
+        };
+        let query_id = *cancel.query_id();
+
+        let response = match self.queries.running_queries.get(&query_id) {
+            Some(token) => {
+                info!("Cancelling query {}", query_id);
+                token.cancel();
+                Message::command_complete_message("CANCEL REQUESTED".to_string())
+            }
+            None => {
+                warn!("Request to cancel query {}, which is not running", query_id);
+                Message::error_response(format!("No running query with id {}", query_id))
+            }
+        };
+        Protocol::send_message(&mut self.stream, response).await?;
+        Ok(())
+    }
+
+    /// Prepares the statement of a `Parse` message, replying with the types of its parameters.
+    async fn process_parse_message(&mut self, message: Message) -> io::Result<()> {
+        let Message::Parse(parse) = message else {
+            unreachable!("Message is not a Parse message");
+        };
+
+        info!(
+            "Preparing statement \"{}\": `{}`",
+            parse.statement(),
+            parse.query()
+        );
+        let prepared = match self
+            .driver
+            .prepare(
+                &mut self.prepared_statements,
+                parse.statement(),
+                parse.query(),
+            )
+            .await
+        {
+            Ok(()) => self
+                .driver
+                .parameter_types(&self.prepared_statements, parse.statement()),
+            Err(e) => Err(e),
+        };
+        let response = match prepared {
+            Ok(types) => Message::parameter_description_message(types),
+            Err(e) => Message::ErrorResponse(ErrorResponse::from(&e)),
+        };
+        Protocol::send_message(&mut self.stream, response).await?;
+        Ok(())
+    }
+
+    /// Binds the parameters of a `Bind` message to its prepared statement, storing the bound
+    /// statement under the portal name of the message.
+    async fn process_bind_message(&mut self, message: Message) -> io::Result<()> {
+        let Message::Bind(bind) = message else {
+            unreachable!("Message is not a Bind message");
+        };
+
+        let bound = bind
+            .values()
+            .map_err(anyhow::Error::from)
+            .and_then(|params| {
+                self.driver
+                    .bind(&self.prepared_statements, bind.statement(), &params)
+            });
+        let response = match bound {
+            Ok(statement) => {
+                debug!(
+                    "Bound statement \"{}\" to portal \"{}\"",
+                    bind.statement(),
+                    bind.portal()
+                );
+                self.portals.insert(bind.portal().clone(), statement);
+                Message::command_complete_message("BIND COMPLETE".to_string())
+            }
+            Err(e) => Message::ErrorResponse(ErrorResponse::from(&e)),
+        };
+        Protocol::send_message(&mut self.stream, response).await?;
+        Ok(())
+    }
+
+    /// Executes the portal of an `Execute` message, replying like to a `QueryMessage`.
+    async fn process_execute_message(&mut self, message: Message) -> io::Result<()> {
+        let Message::Execute(execute) = message else {
+            unreachable!("Message is not an Execute message");
+        };
+        let Some(statement) = self.portals.get(execute.portal()) else {
+            let response = Message::error_response_with_code(
+                sqlstate::INVALID_CURSOR_NAME,
+                format!("Portal \"{}\" does not exist", execute.portal()),
+            );
+            return Protocol::send_message(&mut self.stream, response).await;
+        };
+
+        info!("Executing portal \"{}\"", execute.portal());
+        self.execute_command(Command::Portal(Box::new(statement.clone())))
+            .await
+    }
+
     async fn handle_unknown_message(&mut self, message: Message) -> io::Result<()> {
-        let error_response = Message::error_response(
+        let error_response = Message::error_response_with_code(
+            sqlstate::PROTOCOL_VIOLATION,
             "Unsupported message type: ".to_string() + &message.to_string(),
         );
         Protocol::send_message(&mut self.stream, error_response).await?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::auth::{AuthMiddleware, PASSWORD_AUTHENTICATION};
+    use crate::middleware::MiddlewareStack;
+    use crate::protocol::message::StartupMessage;
+    use arrow::datatypes::DataType;
+    use driver::Driver;
+    use std::net::SocketAddr;
+    use std::time::Instant;
+    use tokio::net::TcpListener;
+
+    /// Serves every connection accepted on `listener` with its own handler.
+    async fn serve(listener: TcpListener, driver: DriverRef, queries: SharedQueryState) {
+        serve_with(listener, driver, queries, MiddlewareStack::new()).await
+    }
+
+    /// Like [`serve`], running the requests through `middleware_stack`.
+    async fn serve_with(
+        listener: TcpListener,
+        driver: DriverRef,
+        queries: SharedQueryState,
+        middleware_stack: MiddlewareStack,
+    ) {
+        let connections = Arc::new(DashMap::new());
+        let middleware_stack = Arc::new(middleware_stack);
+        while let Ok((stream, _)) = listener.accept().await {
+            let (_, rx) = mpsc::channel(1);
+            let mut handler = ConnectionHandler::new(
+                stream,
+                rx,
+                driver.clone(),
+                connections.clone(),
+                middleware_stack.clone(),
+                ConnectionSettings::default(),
+                queries.clone(),
+            );
+            tokio::spawn(async move { handler.handle_connection().await });
+        }
+    }
+
+    /// Sends a request and returns the message completing it, skipping any result rows.
+    async fn request(stream: &mut TcpStream, message: Message) -> Message {
+        Protocol::send_message(stream, message).await.unwrap();
+        loop {
+            match Protocol::parse_incoming(stream).await.unwrap().unwrap() {
+                Message::DataRowMessage(_) => continue,
+                response => return response,
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_cancel_from_another_connection_aborts_query() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("numbers.csv");
+        let rows = (0..100_000).map(|i| i.to_string()).collect::<Vec<_>>();
+        std::fs::write(&csv_path, format!("n\n{}\n", rows.join("\n"))).unwrap();
+
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Arc::new(Driver::new(db_path.to_str().unwrap()).await.unwrap());
+        // Takes a second per batch, so scanning the whole file would take over ten seconds
+        driver.query_engine().register_udf(
+            "crawl",
+            vec![DataType::Int64],
+            DataType::Int64,
+            |args| {
+                std::thread::sleep(Duration::from_secs(1));
+                Ok(args[0].clone())
+            },
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let queries = SharedQueryState::new(4);
+        let running_queries = queries.running_queries.clone();
+        tokio::spawn(serve(listener, driver, queries));
+
+        let mut conn = TcpStream::connect(address).await.unwrap();
+        let Message::CommandCompleteMessage(startup) =
+            request(&mut conn, Message::startup_message(1)).await
+        else {
+            panic!("Startup should complete");
+        };
+        let query_id: QueryId = startup.tag().rsplit(' ').next().unwrap().parse().unwrap();
+
+        let sql = format!("SELECT crawl(n) FROM {}", csv_path.display());
+        let query =
+            tokio::spawn(async move { request(&mut conn, Message::query_message(sql)).await });
+        while !running_queries.contains_key(&query_id) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Cancel the query from a second connection
+        let mut other = TcpStream::connect(address).await.unwrap();
+        let cancelled_at = Instant::now();
+        assert_eq!(
+            request(&mut other, Message::cancel_message(query_id)).await,
+            Message::command_complete_message("CANCEL REQUESTED".to_string())
+        );
+
+        let response = tokio::time::timeout(Duration::from_secs(5), query)
+            .await
+            .expect("Cancelled query should return promptly")
+            .unwrap();
+        assert_eq!(
+            response,
+            Message::error_response_with_code(
+                sqlstate::QUERY_CANCELED,
+                "QUERY CANCELLED".to_string()
+            )
+        );
+        assert!(cancelled_at.elapsed() < Duration::from_secs(3));
+
+        // The query is no longer running, so it cannot be cancelled again
+        assert!(matches!(
+            request(&mut other, Message::cancel_message(query_id)).await,
+            Message::ErrorResponse(_)
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_rows_reach_the_client_before_the_query_completes() {
+        const BATCH_SIZE: usize = 8192;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("numbers.csv");
+        let rows = (0..4 * BATCH_SIZE)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>();
+        std::fs::write(&csv_path, format!("n\n{}\n", rows.join("\n"))).unwrap();
+
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Arc::new(Driver::new(db_path.to_str().unwrap()).await.unwrap());
+        // Produces a batch every half a second, so the whole file takes two seconds
+        driver.query_engine().register_udf(
+            "trickle",
+            vec![DataType::Int64],
+            DataType::Int64,
+            |args| {
+                std::thread::sleep(Duration::from_millis(500));
+                Ok(args[0].clone())
+            },
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, driver, SharedQueryState::new(4)));
+
+        let mut conn = TcpStream::connect(address).await.unwrap();
+        let sql = format!("SELECT trickle(n) FROM {}", csv_path.display());
+        let sent_at = Instant::now();
+        Protocol::send_message(&mut conn, Message::query_message(sql))
+            .await
+            .unwrap();
+
+        let mut first_row_at = None;
+        let mut num_rows = 0;
+        let response = loop {
+            match Protocol::parse_incoming(&mut conn).await.unwrap().unwrap() {
+                Message::DataRowMessage(_) => {
+                    first_row_at.get_or_insert_with(|| sent_at.elapsed());
+                    num_rows += 1;
+                }
+                response => break response,
+            }
+        };
+        let completed_at = sent_at.elapsed();
+
+        assert_eq!(
+            response,
+            Message::command_complete_message("QUERY EXECUTED".to_string())
+        );
+        assert_eq!(num_rows, 4 * BATCH_SIZE);
+        // The first batch arrives while the remaining batches are still being produced
+        let first_row_at = first_row_at.unwrap();
+        assert!(
+            first_row_at + Duration::from_secs(1) < completed_at,
+            "First row after {:?}, query completed after {:?}",
+            first_row_at,
+            completed_at
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_waits_for_a_free_execution_slot() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Arc::new(Driver::new(db_path.to_str().unwrap()).await.unwrap());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let shared = SharedQueryState::new(1);
+        let metrics_manager = shared.metrics_manager.clone();
+        // The only execution slot is taken, as if by a query running on another connection
+        let permit = shared.query_throttle.clone().acquire_owned().await.unwrap();
+        tokio::spawn(serve(listener, driver, shared));
+
+        let mut conn = TcpStream::connect(address).await.unwrap();
+        let query = tokio::spawn(async move {
+            request(&mut conn, Message::query_message("SELECT 1".to_string())).await
+        });
+
+        // The query waits for the slot instead of being rejected
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!query.is_finished());
+        drop(permit);
+        assert_eq!(
+            query.await.unwrap(),
+            Message::command_complete_message("QUERY EXECUTED".to_string())
+        );
+
+        // The time spent waiting counts towards the latency of the query
+        let latency = metrics_manager.query_latency();
+        assert_eq!(latency.count(), 1);
+        assert!(latency.p50() >= Duration::from_millis(200));
+    }
+
+    /// Starts a server authenticating its connections, returning its address and a query
+    /// over a file in `temp_dir`.
+    async fn serve_authenticated(temp_dir: &tempfile::TempDir) -> (SocketAddr, String) {
+        let csv_path = temp_dir.path().join("one.csv");
+        std::fs::write(&csv_path, "n\n1\n").unwrap();
+
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Arc::new(Driver::new(db_path.to_str().unwrap()).await.unwrap());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut middleware_stack = MiddlewareStack::new();
+        middleware_stack.add_middleware(AuthMiddleware::new());
+        tokio::spawn(serve_with(
+            listener,
+            driver,
+            SharedQueryState::new(4),
+            middleware_stack,
+        ));
+
+        (address, format!("SELECT n FROM {}", csv_path.display()))
+    }
+
+    fn password_startup(username: &str, password: &str) -> Message {
+        Message::StartupMessage(
+            StartupMessage::builder()
+                .protocol_version(Message::PROTOCOL_VERSION)
+                .username(username.to_string())
+                .password(password.to_string())
+                .build(),
+        )
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_insert_completes_with_the_number_of_rows_inserted() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Arc::new(Driver::new(db_path.to_str().unwrap()).await.unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, driver, SharedQueryState::new(1)));
+
+        let mut conn = TcpStream::connect(address).await.unwrap();
+        let sql = "CREATE TABLE t (id INTEGER)".to_string();
+        assert_eq!(
+            request(&mut conn, Message::query_message(sql)).await,
+            Message::command_complete_message("QUERY EXECUTED".to_string())
+        );
+        let sql = "INSERT INTO t VALUES (1), (2)".to_string();
+        assert_eq!(
+            request(&mut conn, Message::query_message(sql)).await,
+            Message::command_complete_message("INSERT 0 2".to_string())
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_query_before_authentication_is_rejected() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (address, sql) = serve_authenticated(&temp_dir).await;
+
+        let mut conn = TcpStream::connect(address).await.unwrap();
+        assert_eq!(
+            request(&mut conn, Message::query_message(sql.clone())).await,
+            Message::authentication_request(PASSWORD_AUTHENTICATION)
+        );
+        // The connection stays open for the client to authenticate
+        assert_eq!(
+            request(&mut conn, Message::query_message(sql)).await,
+            Message::authentication_request(PASSWORD_AUTHENTICATION)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_query_after_password_authentication_is_allowed() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (address, sql) = serve_authenticated(&temp_dir).await;
+
+        let mut conn = TcpStream::connect(address).await.unwrap();
+        assert!(matches!(
+            request(&mut conn, password_startup("test", "test")).await,
+            Message::CommandCompleteMessage(message) if message.tag().starts_with("STARTUP COMPLETE")
+        ));
+        assert_eq!(
+            request(&mut conn, Message::query_message(sql.clone())).await,
+            Message::command_complete_message("QUERY EXECUTED".to_string())
+        );
+
+        // Authentication is tracked per connection
+        let mut other = TcpStream::connect(address).await.unwrap();
+        assert_eq!(
+            request(&mut other, Message::query_message(sql)).await,
+            Message::authentication_request(PASSWORD_AUTHENTICATION)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_bad_credentials_are_an_error() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (address, sql) = serve_authenticated(&temp_dir).await;
+
+        let mut conn = TcpStream::connect(address).await.unwrap();
+        assert_eq!(
+            request(&mut conn, password_startup("test", "wrong")).await,
+            Message::error_response_with_code(
+                sqlstate::INVALID_AUTHORIZATION_SPECIFICATION,
+                "Invalid authentication credentials".to_string()
+            )
+        );
+        assert_eq!(
+            request(&mut conn, Message::query_message(sql)).await,
+            Message::authentication_request(PASSWORD_AUTHENTICATION)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_prepared_statement_is_executed_through_the_extended_protocol() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("users.csv");
+        std::fs::write(&csv_path, "id,name\n1,ada\n2,grace\n").unwrap();
+
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Arc::new(Driver::new(db_path.to_str().unwrap()).await.unwrap());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, driver, SharedQueryState::new(4)));
+
+        let mut conn = TcpStream::connect(address).await.unwrap();
+        let sql = format!("SELECT name FROM {} WHERE id = $1", csv_path.display());
+        assert_eq!(
+            request(&mut conn, Message::parse_message("by_id".to_string(), sql)).await,
+            Message::parameter_description_message(vec!["Int64".to_string()])
+        );
+
+        let bind = |portal: &str, params: &[ty::DataType]| {
+            Message::bind_message(portal.to_string(), "by_id".to_string(), params).unwrap()
+        };
+        assert_eq!(
+            request(&mut conn, bind("ada", &[ty::DataType::Integer(1)])).await,
+            Message::command_complete_message("BIND COMPLETE".to_string())
+        );
+
+        // The rows of the bound statement precede the completion of the command
+        Protocol::send_message(&mut conn, Message::execute_message("ada".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(
+            Protocol::parse_incoming(&mut conn).await.unwrap().unwrap(),
+            Message::data_row_message(vec!["ada".to_string()])
+        );
+        assert_eq!(
+            Protocol::parse_incoming(&mut conn).await.unwrap().unwrap(),
+            Message::command_complete_message("QUERY EXECUTED".to_string())
+        );
+
+        // Every parameter must be bound, and only once
+        for params in [
+            vec![],
+            vec![ty::DataType::Integer(1), ty::DataType::Integer(2)],
+        ] {
+            let response = request(&mut conn, bind("wrong", &params)).await;
+            let Message::ErrorResponse(error) = response else {
+                panic!("Binding {} parameters should fail", params.len());
+            };
+            assert!(
+                error.error().contains("the prepared statement requires 1"),
+                "{}",
+                error.error()
+            );
+        }
+        assert_eq!(
+            request(&mut conn, Message::execute_message("wrong".to_string())).await,
+            Message::error_response_with_code(
+                sqlstate::INVALID_CURSOR_NAME,
+                "Portal \"wrong\" does not exist".to_string()
+            )
+        );
+
+        // Prepared statements belong to the connection that prepared them
+        let mut other = TcpStream::connect(address).await.unwrap();
+        let Message::ErrorResponse(error) =
+            request(&mut other, bind("ada", &[ty::DataType::Integer(1)])).await
+        else {
+            panic!("The statement of another connection should not be bound");
+        };
+        assert_eq!(error.code(), sqlstate::INVALID_SQL_STATEMENT_NAME);
+        let sql = format!("SELECT id FROM {} WHERE name = $1", csv_path.display());
+        assert_eq!(
+            request(&mut other, Message::parse_message("by_id".to_string(), sql)).await,
+            Message::parameter_description_message(vec!["Utf8".to_string()])
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_executed_portal_can_be_cancelled() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("numbers.csv");
+        let rows = (0..100_000).map(|i| i.to_string()).collect::<Vec<_>>();
+        std::fs::write(&csv_path, format!("n\n{}\n", rows.join("\n"))).unwrap();
+
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Arc::new(Driver::new(db_path.to_str().unwrap()).await.unwrap());
+        // Takes a second per batch, so scanning the whole file would take over ten seconds
+        driver.query_engine().register_udf(
+            "crawl",
+            vec![DataType::Int64],
+            DataType::Int64,
+            |args| {
+                std::thread::sleep(Duration::from_secs(1));
+                Ok(args[0].clone())
+            },
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let queries = SharedQueryState::new(4);
+        let running_queries = queries.running_queries.clone();
+        tokio::spawn(serve(listener, driver, queries));
+
+        let mut conn = TcpStream::connect(address).await.unwrap();
+        let Message::CommandCompleteMessage(startup) =
+            request(&mut conn, Message::startup_message(1)).await
+        else {
+            panic!("Startup should complete");
+        };
+        let query_id: QueryId = startup.tag().rsplit(' ').next().unwrap().parse().unwrap();
+        let sql = format!("SELECT crawl(n) FROM {} WHERE n >= $1", csv_path.display());
+        request(&mut conn, Message::parse_message("slow".to_string(), sql)).await;
+        let bind = Message::bind_message(
+            "slow".to_string(),
+            "slow".to_string(),
+            &[ty::DataType::Integer(0)],
+        )
+        .unwrap();
+        request(&mut conn, bind).await;
+
+        let execute = Message::execute_message("slow".to_string());
+        let query = tokio::spawn(async move { request(&mut conn, execute).await });
+        while !running_queries.contains_key(&query_id) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let mut other = TcpStream::connect(address).await.unwrap();
+        request(&mut other, Message::cancel_message(query_id)).await;
+        let response = tokio::time::timeout(Duration::from_secs(5), query)
+            .await
+            .expect("Cancelled portal should return promptly")
+            .unwrap();
+        assert_eq!(
+            response,
+            Message::error_response_with_code(
+                sqlstate::QUERY_CANCELED,
+                "QUERY CANCELLED".to_string()
+            )
+        );
+    }
+}
@@ -31,18 +31,36 @@
 //! | 6    | ErrorResponse          | An error response                     | Server -> Client        |
 //! | 7    | AuthenticationRequest  | Authentication request                | Server -> Client        |
 //! | 8    | ReadyForQuery          | Ready for query                       | Server -> Client        |
-
-use crate::auth::{password::PasswordAuthenticator, token::TokenAuthenticator};
+//! | 9    | Cancel                 | Cancels a running query               | Client -> Server        |
+//! | 10   | Parse                  | Prepares a statement                  | Client -> Server        |
+//! | 11   | Bind                   | Binds parameters to a statement       | Client -> Server        |
+//! | 12   | Execute                | Executes a bound statement            | Client -> Server        |
+//! | 13   | ParameterDescription   | The parameter types of a statement    | Server -> Client        |
+//!
+//! ## Extended query protocol
+//!
+//! Instead of sending the SQL of a query each time it runs, a client can prepare it once
+//! under a name with a `Parse` message, its parameters written as `$1`, `$2`, ... The server
+//! replies with a `ParameterDescription` of the types the parameters were inferred to have.
+//! A `Bind` message then binds values to the parameters of the statement, creating a named
+//! portal, which an `Execute` message runs, replied to like a `QueryMessage`.
+
+use crate::auth::password::PasswordAuthenticator;
+use crate::auth::token::{TokenAuthenticator, DEFAULT_TOKEN_SECRET};
+use crate::protocol::sqlstate;
 use anyhow::Result;
 use bytes::{BufMut, BytesMut};
+use common::traits::encode::{Encodable, EncodingError};
 use core::fmt;
 use getset::{Getters, Setters};
 use std::mem;
+use std::str::FromStr;
 use tracing::{error, warn};
+use ty::{DataType, DataTypeKind};
 use typed_builder::TypedBuilder;
 
 /// Represents the different kinds of messages in the protocol.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MessageKind {
     /// Message sent by the client to initiate a connection.
     StartupMessage = 0x01,
@@ -60,6 +78,16 @@ pub enum MessageKind {
     AuthenticationRequest = 0x07,
     /// Message sent by the server indicating it is ready for a new query.
     ReadyForQuery = 0x08,
+    /// Message sent by the client to cancel a query running on any connection.
+    Cancel = 0x09,
+    /// Message sent by the client to prepare a statement.
+    Parse = 0x0A,
+    /// Message sent by the client to bind parameters to a prepared statement.
+    Bind = 0x0B,
+    /// Message sent by the client to execute a bound statement.
+    Execute = 0x0C,
+    /// Message sent by the server describing the parameters of a prepared statement.
+    ParameterDescription = 0x0D,
 }
 
 /// Common functionality shared by all messages.
@@ -86,17 +114,29 @@ pub trait MessageFormat {
 
 impl MessageKind {
     pub fn from_u8(byte: u8) -> MessageKind {
+        Self::try_from_u8(byte).unwrap_or_else(|| {
+            warn!("Unknown message type: {}", byte);
+            MessageKind::ErrorResponse
+        })
+    }
+
+    /// Returns the kind identified by `byte`, or `None` if no message has that type.
+    pub fn try_from_u8(byte: u8) -> Option<MessageKind> {
         match byte {
-            0x01 => MessageKind::StartupMessage,
-            0x02 => MessageKind::QueryMessage,
-            0x03 => MessageKind::DataRowMessage,
-            0x04 => MessageKind::CommandCompleteMessage,
-            0x05 => MessageKind::TerminationMessage,
-            0x06 => MessageKind::ErrorResponse,
-            _ => {
-                warn!("Unknown message type: {}", byte);
-                MessageKind::ErrorResponse
-            }
+            0x01 => Some(MessageKind::StartupMessage),
+            0x02 => Some(MessageKind::QueryMessage),
+            0x03 => Some(MessageKind::DataRowMessage),
+            0x04 => Some(MessageKind::CommandCompleteMessage),
+            0x05 => Some(MessageKind::TerminationMessage),
+            0x06 => Some(MessageKind::ErrorResponse),
+            0x07 => Some(MessageKind::AuthenticationRequest),
+            0x08 => Some(MessageKind::ReadyForQuery),
+            0x09 => Some(MessageKind::Cancel),
+            0x0A => Some(MessageKind::Parse),
+            0x0B => Some(MessageKind::Bind),
+            0x0C => Some(MessageKind::Execute),
+            0x0D => Some(MessageKind::ParameterDescription),
+            _ => None,
         }
     }
 
@@ -110,6 +150,11 @@ impl MessageKind {
             MessageKind::ErrorResponse => 0x06,
             MessageKind::AuthenticationRequest => 0x07,
             MessageKind::ReadyForQuery => 0x08,
+            MessageKind::Cancel => 0x09,
+            MessageKind::Parse => 0x0A,
+            MessageKind::Bind => 0x0B,
+            MessageKind::Execute => 0x0C,
+            MessageKind::ParameterDescription => 0x0D,
         }
     }
 }
@@ -125,13 +170,18 @@ impl fmt::Display for MessageKind {
             MessageKind::ErrorResponse => "ErrorResponse",
             MessageKind::AuthenticationRequest => "AuthenticationRequest",
             MessageKind::ReadyForQuery => "ReadyForQuery",
+            MessageKind::Cancel => "Cancel",
+            MessageKind::Parse => "Parse",
+            MessageKind::Bind => "Bind",
+            MessageKind::Execute => "Execute",
+            MessageKind::ParameterDescription => "ParameterDescription",
         };
 
         write!(f, "{}", kind)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum Message {
     StartupMessage(StartupMessage),
     QueryMessage(QueryMessage),
@@ -141,6 +191,11 @@ pub enum Message {
     ErrorResponse(ErrorResponse),
     ReadyForQuery(ReadyForQueryMessage),
     AuthenticationRequest(AuthenticationRequestMessage),
+    Cancel(CancelMessage),
+    Parse(ParseMessage),
+    Bind(BindMessage),
+    Execute(ExecuteMessage),
+    ParameterDescription(ParameterDescriptionMessage),
 }
 
 impl MessageFormat for Message {
@@ -154,6 +209,11 @@ impl MessageFormat for Message {
             Message::ErrorResponse(_) => MessageKind::ErrorResponse,
             Message::ReadyForQuery(_) => MessageKind::ReadyForQuery,
             Message::AuthenticationRequest(_) => MessageKind::AuthenticationRequest,
+            Message::Cancel(_) => MessageKind::Cancel,
+            Message::Parse(_) => MessageKind::Parse,
+            Message::Bind(_) => MessageKind::Bind,
+            Message::Execute(_) => MessageKind::Execute,
+            Message::ParameterDescription(_) => MessageKind::ParameterDescription,
         }
     }
 
@@ -167,6 +227,11 @@ impl MessageFormat for Message {
             Message::ErrorResponse(message) => message.payload(),
             Message::ReadyForQuery(message) => message.payload(),
             Message::AuthenticationRequest(message) => message.payload(),
+            Message::Cancel(message) => message.payload(),
+            Message::Parse(message) => message.payload(),
+            Message::Bind(message) => message.payload(),
+            Message::Execute(message) => message.payload(),
+            Message::ParameterDescription(message) => message.payload(),
         }
     }
 }
@@ -189,6 +254,11 @@ impl Message {
             Message::ErrorResponse(_) => MessageKind::ErrorResponse,
             Message::ReadyForQuery(_) => MessageKind::ReadyForQuery,
             Message::AuthenticationRequest(_) => MessageKind::AuthenticationRequest,
+            Message::Cancel(_) => MessageKind::Cancel,
+            Message::Parse(_) => MessageKind::Parse,
+            Message::Bind(_) => MessageKind::Bind,
+            Message::Execute(_) => MessageKind::Execute,
+            Message::ParameterDescription(_) => MessageKind::ParameterDescription,
         }
     }
 
@@ -265,6 +335,17 @@ impl Message {
         Message::ErrorResponse(ErrorResponse::builder().error(error).build())
     }
 
+    /// An `ErrorResponse` reporting an error with the given SQLSTATE code (see
+    /// [`sqlstate`]).
+    pub fn error_response_with_code(code: &str, error: String) -> Message {
+        Message::ErrorResponse(
+            ErrorResponse::builder()
+                .code(code.to_string())
+                .error(error)
+                .build(),
+        )
+    }
+
     pub fn command_complete_message(tag: String) -> Message {
         Message::CommandCompleteMessage(CommandCompleteMessage::builder().tag(tag).build())
     }
@@ -273,6 +354,53 @@ impl Message {
         Message::StartupMessage(StartupMessage::builder().protocol_version(0).build())
     }
 
+    pub fn data_row_message(columns: Vec<String>) -> Message {
+        Message::DataRowMessage(DataRowMessage::builder().columns(columns).build())
+    }
+
+    pub fn authentication_request(auth_type: u8) -> Message {
+        Message::AuthenticationRequest(
+            AuthenticationRequestMessage::builder()
+                .auth_type(auth_type)
+                .build(),
+        )
+    }
+
+    pub fn ready_for_query() -> Message {
+        Message::ReadyForQuery(ReadyForQueryMessage)
+    }
+
+    pub fn cancel_message(query_id: u64) -> Message {
+        Message::Cancel(CancelMessage::builder().query_id(query_id).build())
+    }
+
+    pub fn parse_message(statement: String, query: String) -> Message {
+        Message::Parse(
+            ParseMessage::builder()
+                .statement(statement)
+                .query(query)
+                .build(),
+        )
+    }
+
+    /// Returns a `Bind` message binding `params` to the parameters of `statement`, failing if
+    /// a value can't be encoded.
+    pub fn bind_message(
+        portal: String,
+        statement: String,
+        params: &[DataType],
+    ) -> Result<Message, EncodingError> {
+        Ok(Message::Bind(BindMessage::new(portal, statement, params)?))
+    }
+
+    pub fn execute_message(portal: String) -> Message {
+        Message::Execute(ExecuteMessage::builder().portal(portal).build())
+    }
+
+    pub fn parameter_description_message(types: Vec<String>) -> Message {
+        Message::ParameterDescription(ParameterDescriptionMessage::builder().types(types).build())
+    }
+
     pub fn termination_message() -> Message {
         Message::TerminationMessage(TerminationMessage::builder().status(0).build())
     }
@@ -315,7 +443,11 @@ impl Message {
 ///
 /// The `StartupMessage` is the first message sent after establishing a connection,
 /// carrying information about the protocol version and optionally, authentication credentials.
-#[derive(Debug, Getters, Setters, TypedBuilder)]
+///
+/// Credentials follow the protocol version as `name\0value\0` pairs (`user`, `password` and
+/// `token`), terminated by a zero byte. A message without credentials carries no pairs and no
+/// terminator.
+#[derive(Debug, PartialEq, Eq, Getters, Setters, TypedBuilder)]
 #[getset(get = "pub", set = "pub")]
 pub struct StartupMessage {
     /// Protocol version number.
@@ -335,7 +467,7 @@ pub struct StartupMessage {
 ///
 /// `QueryMessage` carries the SQL query text which the server is expected to execute.
 /// The query can be any valid SQL statement.
-#[derive(Debug, Getters, Setters, TypedBuilder)]
+#[derive(Debug, PartialEq, Eq, Getters, Setters, TypedBuilder)]
 #[getset(get = "pub", set = "pub")]
 pub struct QueryMessage {
     /// The SQL query to be executed.
@@ -345,8 +477,9 @@ pub struct QueryMessage {
 /// Represents a message sent by the server containing a row of data from a query result.
 ///
 /// `DataRowMessage` is used in response to a `QueryMessage` when the query yields a result set.
-/// Each `DataRowMessage` contains data for a single row, structured into columns.
-#[derive(Debug, Getters, Setters, TypedBuilder)]
+/// Each `DataRowMessage` contains data for a single row, structured into columns. The payload
+/// holds the number of columns followed by each column as a length-prefixed string.
+#[derive(Debug, PartialEq, Eq, Getters, Setters, TypedBuilder)]
 #[getset(get = "pub", set = "pub")]
 pub struct DataRowMessage {
     /// The data for each column in the row.
@@ -358,7 +491,7 @@ pub struct DataRowMessage {
 /// `CommandCompleteMessage` is used to signal the successful execution of a command
 /// such as an SQL query. It includes a tag (e.g., "INSERT 0 1") indicating the type and
 /// outcome of the command.
-#[derive(Debug, Getters, Setters, TypedBuilder)]
+#[derive(Debug, PartialEq, Eq, Getters, Setters, TypedBuilder)]
 #[getset(get = "pub", set = "pub")]
 pub struct CommandCompleteMessage {
     /// A tag representing the status and result of the command.
@@ -369,20 +502,65 @@ pub struct CommandCompleteMessage {
 ///
 /// `TerminationMessage` is used to gracefully close the connection between the client and the server.
 /// It contains a status code indicating the reason or manner of the termination.
-#[derive(Debug, Getters, Setters, TypedBuilder)]
+#[derive(Debug, PartialEq, Eq, Getters, Setters, TypedBuilder)]
 #[getset(get = "pub", set = "pub")]
 pub struct TerminationMessage {
     /// Status code indicating the termination reason or type.
     pub status: u8,
 }
 
+/// How severe the error reported by an `ErrorResponse` is.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The request failed, but the connection can be used for further requests
+    #[default]
+    Error,
+    /// The connection is closed (e.g. it is refused)
+    Fatal,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "ERROR",
+            Severity::Fatal => "FATAL",
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ERROR" => Ok(Severity::Error),
+            "FATAL" => Ok(Severity::Fatal),
+            _ => Err(format!("Unknown error severity: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// Represents a message sent by the server in response to an error.
 ///
 /// `ErrorResponse` is used by the server to notify the client about an error occurred during
-/// processing a request. It includes a descriptive error message.
-#[derive(Debug, Getters, Setters, TypedBuilder)]
+/// processing a request. It includes the SQLSTATE code of the error (see [`sqlstate`]), its
+/// severity and a descriptive error message. The payload holds the code and the severity as
+/// zero-terminated strings, followed by the message.
+#[derive(Debug, PartialEq, Eq, Getters, Setters, TypedBuilder)]
 #[getset(get = "pub", set = "pub")]
 pub struct ErrorResponse {
+    /// How severe the error is.
+    #[builder(default)]
+    pub severity: Severity,
+    /// The five-character SQLSTATE code of the error.
+    #[builder(default = sqlstate::INTERNAL_ERROR.to_string())]
+    pub code: String,
     /// The error message describing what went wrong.
     pub error: String,
 }
@@ -391,7 +569,7 @@ pub struct ErrorResponse {
 ///
 /// `AuthenticationRequestMessage` is sent as part of the connection establishment process,
 /// prompting the client to provide necessary authentication details, such as a password or token.
-#[derive(Debug, Getters, Setters, TypedBuilder)]
+#[derive(Debug, PartialEq, Eq, Getters, Setters, TypedBuilder)]
 #[getset(get = "pub", set = "pub")]
 pub struct AuthenticationRequestMessage {
     /// Type of authentication being requested (e.g., password, token).
@@ -402,19 +580,149 @@ pub struct AuthenticationRequestMessage {
 ///
 /// `ReadyForQueryMessage` signals to the client that the server has completed processing
 /// the previous command and is ready to receive the next query.
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct ReadyForQueryMessage;
 
+/// Represents a message sent by the client to cancel a running query.
+///
+/// `CancelMessage` may be sent on any connection, so a client can abort a query that is
+/// keeping its own connection busy. The query id is the one the server reported in reply
+/// to the startup message of the connection running the query.
+#[derive(Debug, PartialEq, Eq, Getters, Setters, TypedBuilder)]
+#[getset(get = "pub", set = "pub")]
+pub struct CancelMessage {
+    /// Id of the query to cancel.
+    pub query_id: u64,
+}
+
+/// Represents a message sent by the client to prepare a statement.
+///
+/// `ParseMessage` plans the query once and stores it under the statement name, its parameters
+/// written as `$1`, `$2`, ... The payload holds the name and the query as zero-terminated
+/// strings.
+#[derive(Debug, PartialEq, Eq, Getters, Setters, TypedBuilder)]
+#[getset(get = "pub", set = "pub")]
+pub struct ParseMessage {
+    /// Name the statement is prepared under.
+    pub statement: String,
+    /// The SQL query, with placeholders for its parameters.
+    pub query: String,
+}
+
+/// Represents a message sent by the client to bind values to the parameters of a prepared
+/// statement, creating a portal that can be executed.
+///
+/// The payload holds the portal and statement names as zero-terminated strings, followed by
+/// the number of parameters (`u16`) and each parameter as the zero-terminated name of its
+/// kind and its length-prefixed encoding.
+#[derive(Debug, PartialEq, Eq, Getters, Setters, TypedBuilder)]
+#[getset(get = "pub", set = "pub")]
+pub struct BindMessage {
+    /// Name of the portal the bound statement is stored under.
+    pub portal: String,
+    /// Name of the prepared statement.
+    pub statement: String,
+    /// The kind and encoding of each parameter value, in order.
+    pub params: Vec<(DataTypeKind, Vec<u8>)>,
+}
+
+/// Represents a message sent by the client to execute a portal created by a `BindMessage`.
+#[derive(Debug, PartialEq, Eq, Getters, Setters, TypedBuilder)]
+#[getset(get = "pub", set = "pub")]
+pub struct ExecuteMessage {
+    /// Name of the portal to execute.
+    pub portal: String,
+}
+
+/// Represents a message sent by the server in reply to a `ParseMessage`, describing the
+/// parameters of the prepared statement.
+///
+/// The payload holds the number of parameters followed by the name of each parameter's type
+/// as a length-prefixed string.
+#[derive(Debug, PartialEq, Eq, Getters, Setters, TypedBuilder)]
+#[getset(get = "pub", set = "pub")]
+pub struct ParameterDescriptionMessage {
+    /// The type each parameter was inferred to have, in order.
+    pub types: Vec<String>,
+}
+
+impl BindMessage {
+    pub fn new(
+        portal: String,
+        statement: String,
+        params: &[DataType],
+    ) -> Result<Self, EncodingError> {
+        let params = params
+            .iter()
+            .map(|param| Ok((param.data_type_kind(), param.encode()?)))
+            .collect::<Result<_, EncodingError>>()?;
+        Ok(BindMessage {
+            portal,
+            statement,
+            params,
+        })
+    }
+
+    /// Decodes the values of the parameters.
+    pub fn values(&self) -> Result<Vec<DataType>, EncodingError> {
+        self.params
+            .iter()
+            .map(|(kind, bytes)| DataType::decode(kind, bytes))
+            .collect()
+    }
+
+    /// Returns the name a kind is encoded by.
+    pub(crate) fn kind_name(kind: &DataTypeKind) -> String {
+        match serde_json::to_value(kind) {
+            Ok(serde_json::Value::String(name)) => name,
+            _ => unreachable!("kinds serialize to their name"),
+        }
+    }
+
+    /// Returns the kind encoded by `name`.
+    pub(crate) fn kind_from_name(name: &str) -> Option<DataTypeKind> {
+        serde_json::from_value(serde_json::Value::String(name.to_string())).ok()
+    }
+}
+
 impl StartupMessage {
+    /// Names of the credential parameters, in the order they are encoded.
+    pub const USER_PARAMETER: &'static str = "user";
+    pub const PASSWORD_PARAMETER: &'static str = "password";
+    pub const TOKEN_PARAMETER: &'static str = "token";
+
+    /// Returns the credentials the message carries, as `(name, value)` pairs.
+    pub fn parameters(&self) -> Vec<(&'static str, &str)> {
+        [
+            (Self::USER_PARAMETER, &self.username),
+            (Self::PASSWORD_PARAMETER, &self.password),
+            (Self::TOKEN_PARAMETER, &self.token),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.as_deref().map(|value| (name, value)))
+        .collect()
+    }
+
     pub fn authenticate(&self) -> Message {
+        self.authenticate_with(
+            &PasswordAuthenticator::new(),
+            &TokenAuthenticator::new(DEFAULT_TOKEN_SECRET.to_string()),
+        )
+    }
+
+    /// Checks the credentials of the message against the given authenticators, returning
+    /// `ReadyForQuery` if they are valid and an `ErrorResponse` otherwise.
+    pub(crate) fn authenticate_with(
+        &self,
+        password_authenticator: &PasswordAuthenticator,
+        token_authenticator: &TokenAuthenticator,
+    ) -> Message {
         match self {
             StartupMessage {
                 username: Some(username),
                 password: Some(password),
                 ..
             } => {
-                let password_authenticator = PasswordAuthenticator::new();
-
                 if password_authenticator.authenticate(&username, &password) {
                     // Proceed with connection
                     Message::ReadyForQuery(ReadyForQueryMessage)
@@ -423,27 +731,34 @@ impl StartupMessage {
                         "Request to authenticate with invalid credentials: {} / {}",
                         username, password
                     );
-                    Message::error_response("Invalid authentication credentials".to_string())
+                    Message::error_response_with_code(
+                        sqlstate::INVALID_AUTHORIZATION_SPECIFICATION,
+                        "Invalid authentication credentials".to_string(),
+                    )
                 }
             }
             StartupMessage {
                 token: Some(token), ..
             } => {
-                let token_authenticator = TokenAuthenticator::new("my_secret_key".to_string());
-
                 if token_authenticator.authenticate(&token).unwrap_or(false) {
                     // Proceed with connection
                     Message::ReadyForQuery(ReadyForQueryMessage)
                 } else {
                     error!("Request to authenticate with invalid token: {}", token);
-                    Message::error_response("Invalid authentication credentials".to_string())
+                    Message::error_response_with_code(
+                        sqlstate::INVALID_AUTHORIZATION_SPECIFICATION,
+                        "Invalid authentication credentials".to_string(),
+                    )
                 }
             }
             _ => {
                 error!(
                     "Invalid authentication credentials (no username/password or token provided)"
                 );
-                Message::error_response("Invalid authentication credentials".to_string())
+                Message::error_response_with_code(
+                    sqlstate::INVALID_AUTHORIZATION_SPECIFICATION,
+                    "Invalid authentication credentials".to_string(),
+                )
             }
         }
     }
@@ -458,6 +773,15 @@ impl MessageFormat for StartupMessage {
         let mut payload = BytesMut::new();
         payload.put_u32(self.protocol_version); // Protocol version
 
+        let parameters = self.parameters();
+        if !parameters.is_empty() {
+            for (name, value) in parameters {
+                put_cstring(&mut payload, name);
+                put_cstring(&mut payload, value);
+            }
+            payload.put_u8(0); // Terminates the credentials
+        }
+
         payload
     }
 }
@@ -495,6 +819,7 @@ impl MessageFormat for DataRowMessage {
 
         payload.put_u32(self.columns.len() as u32); // Number of columns
         for column in &self.columns {
+            payload.put_u32(column.len() as u32); // Column length
             payload.put(column.as_bytes()); // The actual partial result set
         }
 
@@ -538,6 +863,10 @@ impl MessageFormat for ErrorResponse {
     fn payload(&self) -> BytesMut {
         let mut payload = BytesMut::new();
 
+        payload.put(self.code.as_bytes()); // SQLSTATE code
+        payload.put_u8(0);
+        payload.put(self.severity.as_str().as_bytes()); // Severity
+        payload.put_u8(0);
         payload.put(self.error.as_bytes()); // Actual error message
 
         payload
@@ -556,6 +885,92 @@ impl MessageFormat for AuthenticationRequestMessage {
     }
 }
 
+impl MessageFormat for CancelMessage {
+    fn kind(&self) -> MessageKind {
+        MessageKind::Cancel
+    }
+
+    fn payload(&self) -> BytesMut {
+        let mut payload = BytesMut::new();
+        payload.put_u64(self.query_id);
+        payload
+    }
+}
+
+impl MessageFormat for ParseMessage {
+    fn kind(&self) -> MessageKind {
+        MessageKind::Parse
+    }
+
+    fn payload(&self) -> BytesMut {
+        let mut payload = BytesMut::new();
+
+        put_cstring(&mut payload, &self.statement); // Statement name
+        put_cstring(&mut payload, &self.query); // The SQL query
+
+        payload
+    }
+}
+
+impl MessageFormat for BindMessage {
+    fn kind(&self) -> MessageKind {
+        MessageKind::Bind
+    }
+
+    fn payload(&self) -> BytesMut {
+        let mut payload = BytesMut::new();
+
+        put_cstring(&mut payload, &self.portal); // Portal name
+        put_cstring(&mut payload, &self.statement); // Statement name
+        payload.put_u16(self.params.len() as u16); // Number of parameters
+        for (kind, bytes) in &self.params {
+            put_cstring(&mut payload, &Self::kind_name(kind)); // Parameter kind
+            payload.put_u32(bytes.len() as u32); // Parameter length
+            payload.put(bytes.as_slice()); // The encoded parameter
+        }
+
+        payload
+    }
+}
+
+impl MessageFormat for ExecuteMessage {
+    fn kind(&self) -> MessageKind {
+        MessageKind::Execute
+    }
+
+    fn payload(&self) -> BytesMut {
+        let mut payload = BytesMut::new();
+
+        put_cstring(&mut payload, &self.portal); // Portal name
+
+        payload
+    }
+}
+
+impl MessageFormat for ParameterDescriptionMessage {
+    fn kind(&self) -> MessageKind {
+        MessageKind::ParameterDescription
+    }
+
+    fn payload(&self) -> BytesMut {
+        let mut payload = BytesMut::new();
+
+        payload.put_u32(self.types.len() as u32); // Number of parameters
+        for data_type in &self.types {
+            payload.put_u32(data_type.len() as u32); // Type name length
+            payload.put(data_type.as_bytes()); // Type name
+        }
+
+        payload
+    }
+}
+
+/// Appends a zero-terminated string.
+fn put_cstring(payload: &mut BytesMut, string: &str) {
+    payload.put(string.as_bytes());
+    payload.put_u8(0);
+}
+
 impl MessageFormat for ReadyForQueryMessage {
     fn kind(&self) -> MessageKind {
         MessageKind::ReadyForQuery
@@ -1,27 +1,153 @@
-use self::message::Message;
+use self::message::{
+    BindMessage, ErrorResponse, Message, Severity, StartupMessage, TerminationMessage,
+};
 use crate::protocol::message::{MessageFormat, MessageKind};
-use bytes::{BufMut, BytesMut};
+use bytes::{Buf, BufMut, BytesMut};
+use common::MAX_MESSAGE_LENGTH;
 use std::io::Result as IoResult;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tracing::{error, trace};
 
 pub mod handler;
 pub mod message;
+pub mod sqlstate;
+pub mod stream;
 
 pub struct Protocol;
 
 impl Protocol {
+    /// The largest frame (header included) accepted by the server. Messages that do not fit
+    /// in a single frame are split into chunks (see [`Protocol::CONTINUATION_FLAG`]).
+    pub const MAX_FRAME_LENGTH: i32 = 10_000;
+
+    /// Set in the type byte of every frame of a chunked message except the last. The frames
+    /// of a chunked message carry consecutive slices of its payload and are reassembled
+    /// before the message is decoded.
+    pub const CONTINUATION_FLAG: u8 = 0x80;
+
     // Parses incoming data from the client
     // return a type which implements the MessageFormat trait (e.g. Message)
     pub async fn parse_incoming<R: AsyncReadExt + Unpin>(
         stream: &mut R,
+    ) -> IoResult<Option<Message>> {
+        Self::parse_incoming_with_limit(stream, MAX_MESSAGE_LENGTH).await
+    }
+
+    /// Like [`Protocol::parse_incoming`], but rejects chunked messages whose reassembled
+    /// payload exceeds `max_message_length` bytes.
+    pub async fn parse_incoming_with_limit<R: AsyncReadExt + Unpin>(
+        stream: &mut R,
+        max_message_length: usize,
     ) -> IoResult<Option<Message>> {
         let mut header = [0_u8; 5];
         if stream.read_exact(&mut header).await.is_err() {
             return Ok(None); // Handle connection close, return None
         }
 
-        let message_kind = header[0];
+        let (message_kind, mut continued) = Self::split_type_byte(header[0]);
+        let length = Self::validate_length(message_kind, &header)?;
+
+        let mut buffer = vec![0; length - Message::HEADER_LENGTH as usize];
+        stream.read_exact(&mut buffer).await?;
+
+        // Reassemble the payload of a chunked message from its remaining frames
+        while continued {
+            if stream.read_exact(&mut header).await.is_err() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "Connection closed in the middle of a chunked message",
+                ));
+            }
+
+            let (chunk_kind, more) = Self::split_type_byte(header[0]);
+            if chunk_kind != message_kind {
+                return Err(Self::invalid_data(format!(
+                    "Chunk of {} interleaved with a chunked {}",
+                    Message::kind_to_string(chunk_kind),
+                    Message::kind_to_string(message_kind)
+                )));
+            }
+
+            let chunk_length =
+                Self::validate_length(chunk_kind, &header)? - Message::HEADER_LENGTH as usize;
+            if buffer.len() + chunk_length > max_message_length {
+                error!(
+                    "Chunked message exceeds the maximum of {} bytes. Closing connection.",
+                    max_message_length
+                );
+                return Err(Self::invalid_data(format!(
+                    "Chunked message exceeds the maximum of {} bytes",
+                    max_message_length
+                )));
+            }
+
+            let start = buffer.len();
+            buffer.resize(start + chunk_length, 0);
+            stream.read_exact(&mut buffer[start..]).await?;
+            continued = more;
+            trace!("Reassembled {} bytes of a chunked message", buffer.len());
+        }
+
+        Self::decode_message(message_kind, &buffer)
+            .map(Some)
+            .map_err(|e| {
+                error!("{}", e);
+                e
+            })
+    }
+
+    /// Parses a message from a single datagram. Unlike [`Protocol::parse_incoming`], there is
+    /// no stream to read the rest of a message from, so the datagram must contain the whole
+    /// message (header + payload) and nothing else.
+    pub fn parse_datagram(datagram: &[u8]) -> IoResult<Message> {
+        if datagram.len() < Message::HEADER_LENGTH as usize {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Datagram is shorter than the message header",
+            ));
+        }
+
+        let (message_kind, continued) = Self::split_type_byte(datagram[0]);
+        if continued {
+            return Err(Self::invalid_data(
+                "Chunked messages are not supported in datagrams".to_string(),
+            ));
+        }
+        let length = Self::validate_length(message_kind, &datagram[..5])?;
+
+        if length > datagram.len() {
+            error!(
+                "Message of {} bytes spans multiple datagrams (received {} bytes)",
+                length,
+                datagram.len()
+            );
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Message is larger than a single datagram",
+            ));
+        }
+        if length < datagram.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Datagram contains trailing bytes after the message",
+            ));
+        }
+
+        Self::decode_message(message_kind, &datagram[Message::HEADER_LENGTH as usize..])
+    }
+
+    /// Splits the type byte of a frame header into the message kind and whether the frame
+    /// is followed by another chunk of the same message.
+    fn split_type_byte(byte: u8) -> (u8, bool) {
+        (
+            byte & !Self::CONTINUATION_FLAG,
+            byte & Self::CONTINUATION_FLAG != 0,
+        )
+    }
+
+    /// Reads the length field of a message header, rejecting unreasonable lengths to prevent
+    /// capacity overflow. Returns the total message length (header included).
+    fn validate_length(message_kind: u8, header: &[u8]) -> IoResult<usize> {
         let length = i32::from_be_bytes([header[1], header[2], header[3], header[4]]);
         trace!(
             "Received message: `{}` ({} bytes including header)",
@@ -29,8 +155,8 @@ impl Protocol {
             length
         );
 
-        // Check for a reasonable message length to prevent capacity overflow
-        if length <= 5 || length > 10_000 {
+        // Messages without a payload (e.g. `ReadyForQuery`) consist of the header alone
+        if length < Message::HEADER_LENGTH as i32 || length > Self::MAX_FRAME_LENGTH {
             error!("Invalid message length: {}. Closing connection.", length);
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
@@ -38,28 +164,197 @@ impl Protocol {
             ));
         }
 
-        let mut buffer = vec![0; (length - Message::HEADER_LENGTH as i32) as usize];
-        stream.read_exact(&mut buffer).await?;
-
-        match MessageKind::from_u8(message_kind) {
-            MessageKind::QueryMessage => {
-                let query = String::from_utf8_lossy(&buffer).to_string();
+        Ok(length as usize)
+    }
 
-                let message = Message::query_message(query);
+    /// Decodes the payload of a message of the given kind. Unknown kinds and malformed
+    /// payloads are reported as [`std::io::ErrorKind::InvalidData`].
+    fn decode_message(message_kind: u8, buffer: &[u8]) -> IoResult<Message> {
+        let Some(kind) = MessageKind::try_from_u8(message_kind) else {
+            return Err(Self::invalid_data(format!(
+                "Unknown message type: {}",
+                message_kind
+            )));
+        };
 
-                Ok(Some(message))
+        let mut payload = buffer;
+        let message = match kind {
+            MessageKind::QueryMessage => {
+                let query = String::from_utf8_lossy(payload).to_string();
+                Message::query_message(query)
             }
             MessageKind::StartupMessage => {
-                let protocol_version =
-                    i32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
-                Ok(Some(Message::startup_message(protocol_version)))
+                let protocol_version = Self::read_u32(&mut payload, kind)?;
+                let mut startup = StartupMessage::builder()
+                    .protocol_version(protocol_version)
+                    .build();
+                if payload.has_remaining() {
+                    loop {
+                        let name = Self::read_cstring(&mut payload, kind)?;
+                        if name.is_empty() {
+                            break;
+                        }
+                        let value = Some(Self::read_cstring(&mut payload, kind)?);
+                        match name.as_str() {
+                            StartupMessage::USER_PARAMETER => startup.username = value,
+                            StartupMessage::PASSWORD_PARAMETER => startup.password = value,
+                            StartupMessage::TOKEN_PARAMETER => startup.token = value,
+                            _ => {
+                                return Err(Self::invalid_data(format!(
+                                    "Unknown {} parameter: {}",
+                                    kind, name
+                                )))
+                            }
+                        }
+                    }
+                    if payload.has_remaining() {
+                        return Err(Self::invalid_data(format!(
+                            "{} contains trailing bytes after its parameters",
+                            kind
+                        )));
+                    }
+                }
+                Message::StartupMessage(startup)
+            }
+            MessageKind::DataRowMessage => {
+                let num_columns = Self::read_u32(&mut payload, kind)?;
+                let columns = (0..num_columns)
+                    .map(|_| {
+                        let len = Self::read_u32(&mut payload, kind)? as usize;
+                        if payload.remaining() < len {
+                            return Err(Self::invalid_data(format!("{} is truncated", kind)));
+                        }
+                        let column = String::from_utf8_lossy(&payload[..len]).to_string();
+                        payload.advance(len);
+                        Ok(column)
+                    })
+                    .collect::<IoResult<Vec<_>>>()?;
+                if payload.has_remaining() {
+                    return Err(Self::invalid_data(format!(
+                        "{} contains trailing bytes after its columns",
+                        kind
+                    )));
+                }
+                Message::data_row_message(columns)
             }
             MessageKind::CommandCompleteMessage => {
-                let tag = String::from_utf8_lossy(&buffer).to_string();
-                Ok(Some(Message::command_complete_message(tag)))
+                let tag = String::from_utf8_lossy(payload).to_string();
+                Message::command_complete_message(tag)
+            }
+            MessageKind::TerminationMessage => {
+                let status = Self::read_u8(&mut payload, kind)?;
+                Message::TerminationMessage(TerminationMessage::builder().status(status).build())
+            }
+            MessageKind::ErrorResponse => {
+                let code = Self::read_cstring(&mut payload, kind)?;
+                let severity = Self::read_cstring(&mut payload, kind)?;
+                let severity = severity.parse::<Severity>().map_err(Self::invalid_data)?;
+                let error = String::from_utf8_lossy(payload).to_string();
+                Message::ErrorResponse(
+                    ErrorResponse::builder()
+                        .severity(severity)
+                        .code(code)
+                        .error(error)
+                        .build(),
+                )
+            }
+            MessageKind::AuthenticationRequest => {
+                let auth_type = Self::read_u8(&mut payload, kind)?;
+                Message::authentication_request(auth_type)
+            }
+            MessageKind::ReadyForQuery => Message::ready_for_query(),
+            MessageKind::Cancel => {
+                if payload.remaining() < 8 {
+                    return Err(Self::invalid_data(format!("{} is truncated", kind)));
+                }
+                Message::cancel_message(payload.get_u64())
+            }
+            MessageKind::Parse => {
+                let statement = Self::read_cstring(&mut payload, kind)?;
+                let query = Self::read_cstring(&mut payload, kind)?;
+                Message::parse_message(statement, query)
+            }
+            MessageKind::Bind => {
+                let portal = Self::read_cstring(&mut payload, kind)?;
+                let statement = Self::read_cstring(&mut payload, kind)?;
+                if payload.remaining() < 2 {
+                    return Err(Self::invalid_data(format!("{} is truncated", kind)));
+                }
+                let num_params = payload.get_u16();
+                let params = (0..num_params)
+                    .map(|_| {
+                        let name = Self::read_cstring(&mut payload, kind)?;
+                        let param_kind = BindMessage::kind_from_name(&name).ok_or_else(|| {
+                            Self::invalid_data(format!("Unknown parameter kind: {}", name))
+                        })?;
+                        let len = Self::read_u32(&mut payload, kind)? as usize;
+                        let bytes = Self::read_bytes(&mut payload, len, kind)?;
+                        Ok((param_kind, bytes))
+                    })
+                    .collect::<IoResult<Vec<_>>>()?;
+                Message::Bind(
+                    BindMessage::builder()
+                        .portal(portal)
+                        .statement(statement)
+                        .params(params)
+                        .build(),
+                )
             }
-            _ => unimplemented!("Message type not yet implemented: {}", message_kind),
+            MessageKind::Execute => {
+                let portal = Self::read_cstring(&mut payload, kind)?;
+                Message::execute_message(portal)
+            }
+            MessageKind::ParameterDescription => {
+                let num_params = Self::read_u32(&mut payload, kind)?;
+                let types = (0..num_params)
+                    .map(|_| {
+                        let len = Self::read_u32(&mut payload, kind)? as usize;
+                        let bytes = Self::read_bytes(&mut payload, len, kind)?;
+                        Ok(String::from_utf8_lossy(&bytes).to_string())
+                    })
+                    .collect::<IoResult<Vec<_>>>()?;
+                Message::parameter_description_message(types)
+            }
+        };
+
+        Ok(message)
+    }
+
+    fn read_u8(payload: &mut &[u8], kind: MessageKind) -> IoResult<u8> {
+        if !payload.has_remaining() {
+            return Err(Self::invalid_data(format!("{} is truncated", kind)));
         }
+        Ok(payload.get_u8())
+    }
+
+    fn read_u32(payload: &mut &[u8], kind: MessageKind) -> IoResult<u32> {
+        if payload.remaining() < 4 {
+            return Err(Self::invalid_data(format!("{} is truncated", kind)));
+        }
+        Ok(payload.get_u32())
+    }
+
+    fn read_bytes(payload: &mut &[u8], len: usize, kind: MessageKind) -> IoResult<Vec<u8>> {
+        if payload.remaining() < len {
+            return Err(Self::invalid_data(format!("{} is truncated", kind)));
+        }
+        let bytes = payload[..len].to_vec();
+        payload.advance(len);
+        Ok(bytes)
+    }
+
+    /// Reads a zero-terminated string.
+    fn read_cstring(payload: &mut &[u8], kind: MessageKind) -> IoResult<String> {
+        let Some(len) = payload.iter().position(|&byte| byte == 0) else {
+            return Err(Self::invalid_data(format!("{} is truncated", kind)));
+        };
+        let string = String::from_utf8_lossy(&payload[..len]).to_string();
+        payload.advance(len + 1);
+        Ok(string)
+    }
+
+    fn invalid_data(message: String) -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, message)
     }
 
     // Serializes and sends a message to the client
@@ -67,6 +362,48 @@ impl Protocol {
         stream: &mut W,
         message: Message,
     ) -> IoResult<()> {
+        let buffer = Self::encode_chunked(&message, Self::MAX_FRAME_LENGTH as usize);
+        stream.write_all(&buffer).await
+    }
+
+    /// Serializes a message into frames of at most `max_frame_length` bytes (header
+    /// included). Messages that fit in a single frame are encoded as by
+    /// [`Protocol::encode_message`]; larger ones are split into chunks, all but the last
+    /// of which carry the [`Protocol::CONTINUATION_FLAG`].
+    pub fn encode_chunked(message: &Message, max_frame_length: usize) -> BytesMut {
+        if message.len() <= max_frame_length {
+            return Self::encode_message(message);
+        }
+
+        let payload = message.payload();
+        let chunk_size = max_frame_length
+            .saturating_sub(Message::HEADER_LENGTH as usize)
+            .max(1);
+        let num_chunks = payload.len().div_ceil(chunk_size);
+        trace!(
+            "Splitting {} ({} bytes) into {} chunks",
+            message.kind(),
+            message.len(),
+            num_chunks
+        );
+
+        let mut buffer =
+            BytesMut::with_capacity(payload.len() + num_chunks * Message::HEADER_LENGTH as usize);
+        for (idx, chunk) in payload.chunks(chunk_size).enumerate() {
+            let mut message_kind = message.kind().to_u8();
+            if idx + 1 < num_chunks {
+                message_kind |= Self::CONTINUATION_FLAG;
+            }
+            buffer.put_u8(message_kind);
+            buffer.put_i32((chunk.len() + Message::HEADER_LENGTH as usize) as i32);
+            buffer.extend_from_slice(chunk);
+        }
+
+        buffer
+    }
+
+    /// Serializes a message (header + payload) into a buffer, as sent over the wire.
+    pub fn encode_message(message: &Message) -> BytesMut {
         let mut buffer = BytesMut::new();
 
         trace!(
@@ -89,6 +426,154 @@ impl Protocol {
 
         trace!("Sending payload: {:?}", String::from_utf8_lossy(&payload));
 
-        stream.write_all(&buffer).await
+        buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ty::DataType;
+
+    async fn round_trip(message: Message) {
+        let bytes = message.serialize();
+        let parsed = Protocol::parse_incoming(&mut &bytes[..])
+            .await
+            .unwrap()
+            .expect("a complete message should be parsed");
+        assert_eq!(parsed, message);
+    }
+
+    #[tokio::test]
+    async fn test_every_message_kind_round_trips() {
+        round_trip(Message::startup_message(Message::PROTOCOL_VERSION as i32)).await;
+        round_trip(Message::StartupMessage(
+            StartupMessage::builder()
+                .protocol_version(Message::PROTOCOL_VERSION)
+                .username("test".to_string())
+                .password("test".to_string())
+                .build(),
+        ))
+        .await;
+        round_trip(Message::query_message("SELECT 1".to_string())).await;
+        round_trip(Message::data_row_message(vec![
+            "1".to_string(),
+            String::new(),
+            "héllo".to_string(),
+        ]))
+        .await;
+        round_trip(Message::command_complete_message("INSERT 0 1".to_string())).await;
+        round_trip(Message::TerminationMessage(
+            TerminationMessage::builder().status(3).build(),
+        ))
+        .await;
+        round_trip(Message::error_response(
+            "relation does not exist".to_string(),
+        ))
+        .await;
+        round_trip(Message::ErrorResponse(
+            ErrorResponse::builder()
+                .severity(Severity::Fatal)
+                .code(sqlstate::TOO_MANY_CONNECTIONS.to_string())
+                .error("too many connections".to_string())
+                .build(),
+        ))
+        .await;
+        round_trip(Message::authentication_request(1)).await;
+        round_trip(Message::ready_for_query()).await;
+        round_trip(Message::cancel_message(42)).await;
+        round_trip(Message::parse_message(
+            "by_id".to_string(),
+            "SELECT * FROM users WHERE id = $1".to_string(),
+        ))
+        .await;
+        round_trip(
+            Message::bind_message(
+                "portal".to_string(),
+                "by_id".to_string(),
+                &[DataType::Integer(1), DataType::Text("héllo".to_string())],
+            )
+            .unwrap(),
+        )
+        .await;
+        round_trip(Message::execute_message("portal".to_string())).await;
+        round_trip(Message::parameter_description_message(vec![
+            "Int32".to_string(),
+            "unknown".to_string(),
+        ]))
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_malformed_messages_are_errors() {
+        // Unknown message type
+        let bytes = [0x7f, 0, 0, 0, 5];
+        let err = Protocol::parse_incoming(&mut &bytes[..]).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        // A data row whose column is longer than the payload
+        let mut bytes = BytesMut::new();
+        bytes.put_u8(MessageKind::DataRowMessage.to_u8());
+        bytes.put_u32(Message::HEADER_LENGTH + 10);
+        bytes.put_u32(1);
+        bytes.put_u32(100);
+        bytes.put_slice(b"ab");
+        let err = Protocol::parse_incoming(&mut &bytes[..]).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        // A chunked message without its final frame
+        let message = Message::query_message("x".repeat(100));
+        let bytes = Protocol::encode_chunked(&message, 40);
+        let err = Protocol::parse_incoming(&mut &bytes[..bytes.len() - 10])
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+
+        // A termination message without its status byte
+        let bytes = [MessageKind::TerminationMessage.to_u8(), 0, 0, 0, 5];
+        assert!(Protocol::parse_datagram(&bytes).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_large_query_is_chunked_and_reassembled() {
+        let query = format!(
+            "INSERT INTO t VALUES {}",
+            (0..5_000)
+                .map(|i| format!("({})", i))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let query = format!("{}{}", query, " ".repeat(50_000 - query.len()));
+        assert_eq!(query.len(), 50_000);
+
+        let message = Message::query_message(query.clone());
+        let bytes = Protocol::encode_chunked(&message, Protocol::MAX_FRAME_LENGTH as usize);
+        assert!(bytes.len() > 50_000);
+        assert_ne!(bytes[0] & Protocol::CONTINUATION_FLAG, 0);
+
+        let parsed = Protocol::parse_incoming(&mut &bytes[..])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(parsed.query(), query);
+
+        // Small messages keep the single-frame encoding
+        let small = Message::query_message("SELECT 1".to_string());
+        assert_eq!(
+            Protocol::encode_chunked(&small, Protocol::MAX_FRAME_LENGTH as usize),
+            Protocol::encode_message(&small)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chunked_message_over_limit_is_rejected() {
+        let message = Message::query_message("x".repeat(30_000));
+        let bytes = Protocol::encode_chunked(&message, Protocol::MAX_FRAME_LENGTH as usize);
+
+        let err = Protocol::parse_incoming_with_limit(&mut &bytes[..], 20_000)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(Protocol::parse_datagram(&bytes[..Protocol::MAX_FRAME_LENGTH as usize]).is_err());
     }
 }
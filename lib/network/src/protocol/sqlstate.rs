@@ -0,0 +1,213 @@
+//! # SQLSTATE Codes
+//!
+//! Every `ErrorResponse` carries the five-character SQLSTATE code of its error, so that clients
+//! can tell errors apart without parsing their messages. The codes are those of PostgreSQL
+//! (see <https://www.postgresql.org/docs/current/errcodes-appendix.html>): the first two
+//! characters are the class of the error (e.g. `42` for syntax errors and access rule
+//! violations), the last three the condition within the class.
+//!
+//! Errors are mapped to their code by the `From` impls of [`ErrorResponse`] for the error
+//! types of the engine and the server. The errors of queries, which are reported through
+//! `anyhow`, are mapped after the first error of their chain that has a code.
+
+use crate::protocol::message::{ErrorResponse, Severity};
+use crate::server::tcp::ServerError;
+use driver::{DatabaseError, ParseError, PreparedStatementError, QueryCancelled, StatementTimeout};
+use ty::TypeError;
+
+pub const STRING_DATA_RIGHT_TRUNCATION: &str = "22001";
+pub const NUMERIC_VALUE_OUT_OF_RANGE: &str = "22003";
+pub const DIVISION_BY_ZERO: &str = "22012";
+pub const INVALID_PARAMETER_VALUE: &str = "22023";
+pub const NOT_NULL_VIOLATION: &str = "23502";
+pub const INVALID_AUTHORIZATION_SPECIFICATION: &str = "28000";
+pub const INVALID_CURSOR_NAME: &str = "34000";
+pub const INVALID_SQL_STATEMENT_NAME: &str = "26000";
+pub const DUPLICATE_PREPARED_STATEMENT: &str = "42P05";
+pub const SYNTAX_ERROR: &str = "42601";
+pub const DATATYPE_MISMATCH: &str = "42804";
+pub const CANNOT_COERCE: &str = "42846";
+pub const UNDEFINED_OBJECT: &str = "42704";
+pub const UNDEFINED_TABLE: &str = "42P01";
+pub const DUPLICATE_TABLE: &str = "42P07";
+pub const TOO_MANY_CONNECTIONS: &str = "53300";
+pub const QUERY_CANCELED: &str = "57014";
+pub const PROTOCOL_VIOLATION: &str = "08P01";
+pub const INTERNAL_ERROR: &str = "XX000";
+
+fn error_response(severity: Severity, code: &str, error: String) -> ErrorResponse {
+    ErrorResponse::builder()
+        .severity(severity)
+        .code(code.to_string())
+        .error(error)
+        .build()
+}
+
+impl From<&TypeError> for ErrorResponse {
+    fn from(error: &TypeError) -> Self {
+        let code = match error {
+            TypeError::IncompatibleType { .. } => DATATYPE_MISMATCH,
+            TypeError::InvalidCast { .. } => CANNOT_COERCE,
+            TypeError::OverflowError { .. } | TypeError::PrecisionError { .. } => {
+                NUMERIC_VALUE_OUT_OF_RANGE
+            }
+            TypeError::NullViolation { .. } => NOT_NULL_VIOLATION,
+            TypeError::DivisionByZero => DIVISION_BY_ZERO,
+            TypeError::UnknownType { .. } => UNDEFINED_OBJECT,
+            TypeError::InvalidArgument { .. } => INVALID_PARAMETER_VALUE,
+            TypeError::StringTooLong { .. } => STRING_DATA_RIGHT_TRUNCATION,
+        };
+        error_response(Severity::Error, code, error.to_string())
+    }
+}
+
+impl From<&ParseError> for ErrorResponse {
+    fn from(error: &ParseError) -> Self {
+        error_response(Severity::Error, SYNTAX_ERROR, error.to_string())
+    }
+}
+
+impl From<&PreparedStatementError> for ErrorResponse {
+    fn from(error: &PreparedStatementError) -> Self {
+        let code = match error {
+            PreparedStatementError::AlreadyExists(_) => DUPLICATE_PREPARED_STATEMENT,
+            PreparedStatementError::DoesNotExist(_) => INVALID_SQL_STATEMENT_NAME,
+        };
+        error_response(Severity::Error, code, error.to_string())
+    }
+}
+
+impl From<&DatabaseError> for ErrorResponse {
+    fn from(error: &DatabaseError) -> Self {
+        let code = match error {
+            DatabaseError::TableNotFound(_) => UNDEFINED_TABLE,
+            DatabaseError::TableAlreadyExists(_) => DUPLICATE_TABLE,
+            DatabaseError::InvalidEncoding(_) => INTERNAL_ERROR,
+        };
+        error_response(Severity::Error, code, error.to_string())
+    }
+}
+
+impl From<&ServerError> for ErrorResponse {
+    fn from(error: &ServerError) -> Self {
+        match error {
+            // The connection is refused
+            ServerError::ConnectionPoolFull(_) => {
+                error_response(Severity::Fatal, TOO_MANY_CONNECTIONS, error.to_string())
+            }
+        }
+    }
+}
+
+impl From<&anyhow::Error> for ErrorResponse {
+    fn from(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            let response = if let Some(e) = cause.downcast_ref::<ParseError>() {
+                ErrorResponse::from(e)
+            } else if let Some(e) = cause.downcast_ref::<TypeError>() {
+                ErrorResponse::from(e)
+            } else if let Some(e) = cause.downcast_ref::<PreparedStatementError>() {
+                ErrorResponse::from(e)
+            } else if let Some(e) = cause.downcast_ref::<DatabaseError>() {
+                ErrorResponse::from(e)
+            } else if let Some(e) = cause.downcast_ref::<ServerError>() {
+                ErrorResponse::from(e)
+            } else if cause.is::<QueryCancelled>() || cause.is::<StatementTimeout>() {
+                error_response(Severity::Error, QUERY_CANCELED, cause.to_string())
+            } else {
+                continue;
+            };
+            // The message of the whole error, which may add context to the cause
+            return ErrorResponse {
+                error: error.to_string(),
+                ..response
+            };
+        }
+        error_response(Severity::Error, INTERNAL_ERROR, error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::message::Message;
+    use crate::protocol::Protocol;
+    use driver::Driver;
+    use tokio_util::sync::CancellationToken;
+
+    async fn round_trip(response: ErrorResponse) -> ErrorResponse {
+        let encoded = Protocol::encode_message(&Message::ErrorResponse(response));
+        match Protocol::parse_incoming(&mut encoded.as_ref()).await {
+            Ok(Some(Message::ErrorResponse(response))) => response,
+            message => panic!("Expected an ErrorResponse, got {:?}", message),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_syntax_error_is_reported_with_its_code() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Driver::new(db_path.to_str().unwrap()).await.unwrap();
+        let error = driver
+            .execute_sql_command("SELECT * users", &CancellationToken::new())
+            .await
+            .unwrap_err();
+
+        let response = round_trip(ErrorResponse::from(&error)).await;
+        assert_eq!(response.code(), SYNTAX_ERROR);
+        assert_eq!(*response.severity(), Severity::Error);
+        assert_eq!(response.error(), &error.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_missing_and_duplicate_tables_are_reported_with_their_codes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Driver::new(db_path.to_str().unwrap()).await.unwrap();
+        let token = CancellationToken::new();
+
+        let error = driver
+            .execute_sql_command("DROP TABLE users", &token)
+            .await
+            .unwrap_err();
+        assert_eq!(ErrorResponse::from(&error).code(), UNDEFINED_TABLE);
+
+        let create = "CREATE TABLE users (id BIGINT)";
+        driver.execute_sql_command(create, &token).await.unwrap();
+        let error = driver
+            .execute_sql_command(create, &token)
+            .await
+            .unwrap_err();
+        assert_eq!(ErrorResponse::from(&error).code(), DUPLICATE_TABLE);
+    }
+
+    #[tokio::test]
+    async fn test_full_connection_pool_is_reported_with_its_code() {
+        let error = ServerError::ConnectionPoolFull(8);
+
+        let response = round_trip(ErrorResponse::from(&error)).await;
+        assert_eq!(response.code(), TOO_MANY_CONNECTIONS);
+        assert_eq!(*response.severity(), Severity::Fatal);
+        assert_eq!(
+            response.error(),
+            "Connection pool is full. Max connections: 8"
+        );
+        // Wrapped in an `anyhow` error with context
+        let error = anyhow::Error::from(error).context("Failed to accept connection");
+        assert_eq!(ErrorResponse::from(&error).code(), TOO_MANY_CONNECTIONS);
+    }
+
+    #[test]
+    fn test_type_errors_are_mapped_to_data_exceptions() {
+        let error = TypeError::NullViolation {
+            column: "id".to_string(),
+        };
+        assert_eq!(ErrorResponse::from(&error).code(), NOT_NULL_VIOLATION);
+        let error = anyhow::Error::from(TypeError::DivisionByZero);
+        assert_eq!(ErrorResponse::from(&error).code(), DIVISION_BY_ZERO);
+        assert_eq!(
+            ErrorResponse::from(&anyhow::anyhow!("unexpected")).code(),
+            INTERNAL_ERROR
+        );
+    }
+}
@@ -0,0 +1,25 @@
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+
+/// The byte stream a client is connected over, e.g. a [`TcpStream`] or a WebSocket (see
+/// [`crate::server::ws::WebSocketConnection`]). Protocol messages are read from and written
+/// to it as they would be to a TCP connection.
+pub trait ClientStream: AsyncRead + AsyncWrite + Unpin + Send + Sync + fmt::Debug {
+    /// Returns the address of the client.
+    fn peer_addr(&self) -> io::Result<SocketAddr>;
+}
+
+impl ClientStream for TcpStream {
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        TcpStream::peer_addr(self)
+    }
+}
+
+impl<S: ClientStream + ?Sized> ClientStream for Box<S> {
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        (**self).peer_addr()
+    }
+}
@@ -1,21 +1,41 @@
 use cli::{NetworkProtocol, ServeArgs};
-use common::{TCP_PORT, UDP_PORT};
+use common::{TCP_PORT, UDP_PORT, WS_PORT};
 use get_if_addrs::get_if_addrs;
 use std::net::SocketAddr;
+use std::time::Duration;
 use tracing::{info, warn};
 
 pub mod tcp;
 pub mod udp;
+pub mod ws;
 
-pub use udp::run_udp_server;
+pub use udp::{run_udp_server, UdpServer};
 
-use crate::middleware;
+use crate::middleware::auth::AuthMiddleware;
+use crate::middleware::MiddlewareStack;
+
+/// The database file served when none is given.
+pub const DEFAULT_DB_FILE: &str = "test.db";
+
+/// Builds the middleware stack the requests of every connection go through, according to
+/// the `serve` flags (e.g. authentication with `--auth`).
+pub fn middleware_stack(args: &ServeArgs) -> MiddlewareStack {
+    let mut middleware_stack = MiddlewareStack::new();
+    if *args.auth() {
+        middleware_stack.add_middleware(AuthMiddleware::new());
+    }
+    middleware_stack
+}
 
 pub async fn start_server(args: &ServeArgs) {
     let protocol = args.protocol().clone();
     info!(port = args.port(), db_file = ?args.db_file(), verbose = args.verbose(), protocol = ?protocol, "Starting SQL server");
 
-    let tcp_addr = format!("127.0.0.1:{}", TCP_PORT)
+    let tcp_port = match protocol {
+        NetworkProtocol::WebSocket => WS_PORT,
+        _ => TCP_PORT,
+    };
+    let tcp_addr = format!("127.0.0.1:{}", tcp_port)
         .parse::<SocketAddr>()
         .expect("Failed to parse TCP address");
     let udp_addr = format!("127.0.0.1:{}", UDP_PORT);
@@ -23,12 +43,29 @@ pub async fn start_server(args: &ServeArgs) {
     let public_ip = get_public_ip().expect("Failed to get public IP address");
     info!(public_ip = ?public_ip, "Listening at IP address");
 
-    let middleware_stack = middleware::MiddlewareStack::new();
+    let middleware_stack = middleware_stack(args);
+    let db_file = args
+        .db_file()
+        .as_ref()
+        .map_or(DEFAULT_DB_FILE.to_string(), |path| {
+            path.display().to_string()
+        });
     let max_txns = args.max_txns().clone();
     let max_connections = args.max_connections().clone();
 
-    if protocol == NetworkProtocol::TCP {
-        let mut server = tcp::DbServer::new(tcp_addr, middleware_stack, max_txns, max_connections);
+    if matches!(protocol, NetworkProtocol::TCP | NetworkProtocol::WebSocket) {
+        let mut server = tcp::DbServer::new(
+            tcp_addr,
+            &db_file,
+            middleware_stack,
+            max_txns,
+            max_connections,
+        )
+        .await;
+        server.set_protocol(protocol);
+        server.set_max_message_length(*args.max_message_length());
+        server.set_statement_timeout(args.statement_timeout_ms().map(Duration::from_millis));
+        server.set_metrics_port(*args.metrics_port());
 
         // Start background tasks
         // server.start_background_tasks();
@@ -46,12 +83,12 @@ pub async fn start_server(args: &ServeArgs) {
             server.start_noop_metrics_server();
         }
 
-        info!("We're ready to rumble! (TCP server started)");
+        info!("We're ready to rumble! ({} server started)", protocol);
 
         // Run the SQL server
         server.run().await.expect("TCP server failed to run");
     } else if protocol == NetworkProtocol::UDP {
-        warn!("UDP server not implemented yet");
+        info!("We're ready to rumble! (UDP server started)");
         run_udp_server(&udp_addr)
             .await
             .expect("UDP server failed to run");
@@ -73,3 +110,74 @@ fn get_public_ip() -> Option<String> {
 
     public_ip
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::auth::PASSWORD_AUTHENTICATION;
+    use crate::protocol::message::{Message, StartupMessage};
+    use crate::protocol::Protocol;
+    use clap::Parser;
+    use cli::{Cli, Commands};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Serves the database in `temp_dir` as `serve` would with the given flags, returning the
+    /// address of the server.
+    async fn serve(temp_dir: &tempfile::TempDir, flags: &[&str]) -> SocketAddr {
+        let cli = Cli::try_parse_from(["r2db2", "serve"].iter().chain(flags)).unwrap();
+        let Some(Commands::Serve(args)) = cli.command() else {
+            panic!("Expected the serve command");
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let mut server = tcp::DbServer::new(
+            address,
+            db_path.to_str().unwrap(),
+            middleware_stack(args),
+            *args.max_txns(),
+            *args.max_connections(),
+        )
+        .await;
+        tokio::spawn(async move { server.serve_until(listener, std::future::pending()).await });
+        address
+    }
+
+    async fn request(stream: &mut TcpStream, message: Message) -> Message {
+        Protocol::send_message(stream, message).await.unwrap();
+        loop {
+            match Protocol::parse_incoming(stream).await.unwrap().unwrap() {
+                Message::DataRowMessage(_) => continue,
+                response => return response,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auth_flag_requires_clients_to_authenticate() {
+        let query = || Message::query_message("SELECT 1".to_string());
+        let executed = Message::command_complete_message("QUERY EXECUTED".to_string());
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let address = serve(&temp_dir, &["--auth"]).await;
+        let mut conn = TcpStream::connect(address).await.unwrap();
+        assert_eq!(
+            request(&mut conn, query()).await,
+            Message::authentication_request(PASSWORD_AUTHENTICATION)
+        );
+        let startup = StartupMessage::builder()
+            .protocol_version(Message::PROTOCOL_VERSION)
+            .username("test".to_string())
+            .password("test".to_string())
+            .build();
+        request(&mut conn, Message::StartupMessage(startup)).await;
+        assert_eq!(request(&mut conn, query()).await, executed);
+
+        // Without the flag, clients may query right away
+        let temp_dir = tempfile::tempdir().unwrap();
+        let address = serve(&temp_dir, &[]).await;
+        let mut conn = TcpStream::connect(address).await.unwrap();
+        assert_eq!(request(&mut conn, query()).await, executed);
+    }
+}
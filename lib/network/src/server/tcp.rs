@@ -1,30 +1,39 @@
 use crate::middleware::trace::LoggingMiddleware;
 use crate::middleware::{MiddlewareStack, MiddlewareStackRef};
-use crate::protocol::handler::ConnectionHandler;
+use crate::protocol::handler::{ConnectionHandler, ConnectionSettings, SharedQueryState};
 // use crate::protocol::message::{Message, MessageKind};
-use crate::protocol::message::{Message, MessageKind};
+use crate::protocol::message::{ErrorResponse, Message};
+use crate::protocol::stream::ClientStream;
 use crate::protocol::Protocol;
+use crate::server::ws::WebSocketConnection;
 use anyhow::{anyhow, Context, Result};
-use axum::{routing::get, Router};
+use axum::{http::header, routing::get, Router};
+use cli::NetworkProtocol;
+use common::METRICS_PORT;
 use dashmap::DashMap;
 use driver::{Driver, DriverRef};
 use metrics::collector::cpu::CpuUsageCollector;
+use metrics::collector::disk::DiskIoCollector;
 use metrics::collector::memory::MemoryUsageCollector;
 use rustc_hash::FxHasher;
 use std::env;
+use std::future::Future;
 use std::hash::{Hash, Hasher};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::time::Duration;
 use sysinfo::System;
 use thiserror::Error;
+use tokio::io::AsyncWriteExt;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::signal;
 use tokio::sync::{mpsc, Mutex, Semaphore};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, trace, warn};
 use typed_builder::TypedBuilder;
 // use metrics::{counter, gauge, register_counter, register_gauge, register_histogram, Histogram, HistogramOpts, HistogramTimer, HistogramVec, Opts, Registry};
 use metrics::manager::{MetricsManager, MetricsManagerRef, MetricsServerError};
+use metrics::prometheus::PROMETHEUS_CONTENT_TYPE;
 
 /// Unique identifier for each connection
 pub type ConnectionId = String;
@@ -32,6 +41,20 @@ pub type ConnectionId = String;
 /// A reference-counted [`Semaphore`] handle that can be shared across threads.
 pub type SemaphoreRef = Arc<Semaphore>;
 
+/// Identifies the query running on a connection in cancel requests. Query ids are random
+/// secret keys, handed only to the client of the connection, so that other clients cannot
+/// guess them to cancel its queries.
+pub type QueryId = u64;
+
+/// The cancellation tokens of the queries currently running on the server, by query id.
+pub type RunningQueriesRef = Arc<DashMap<QueryId, CancellationToken>>;
+
+/// How many ports, starting from the configured one, the metrics server tries to bind.
+pub const METRICS_PORT_WALK: u16 = 100;
+
+/// How long a shutdown waits for active connections to finish by default.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[derive(Error, Debug)]
 pub enum ServerError {
     #[error("Connection pool is full. Max connections: {0}")]
@@ -48,7 +71,18 @@ pub struct DbServer {
     middleware_stack: MiddlewareStackRef,
     metrics_manager: MetricsManagerRef,
     connections: Arc<DashMap<ConnectionId, bool>>, //  Stores a flag indicating whether the connection is active
-    conn_pool: SemaphoreRef,                       // Semaphore to limit active connections
+    max_connections: usize,                        // Connections beyond this many are refused
+    #[builder(setter(skip), default = Arc::new(Semaphore::new(max_connections)))]
+    conn_pool: SemaphoreRef, // Semaphore to limit active connections
+    queries: SharedQueryState, // Lets any connection cancel a running query and throttles execution
+    #[builder(default)]
+    connection_settings: ConnectionSettings, // Limits enforced on every connection
+    #[builder(default = DEFAULT_SHUTDOWN_TIMEOUT)]
+    shutdown_timeout: Duration, // How long a shutdown waits for active connections to finish
+    #[builder(default = METRICS_PORT)]
+    metrics_port: u16, // The first port the metrics server tries to bind
+    #[builder(default = NetworkProtocol::TCP)]
+    protocol: NetworkProtocol, // How clients speak over their TCP connections (e.g. framed in WebSockets)
 }
 
 impl DbServer {
@@ -56,13 +90,15 @@ impl DbServer {
     ///
     /// Arguments:
     /// - `server_address`: The IP address and port for the server to listen on.
+    /// - `db_path`: The database file the server hosts.
     /// - `middleware_stack`: Middleware components for processing requests.
     /// - `max_transactions`: Maximum number of concurrent transactions the server can handle.
     /// - `max_connections`: Maximum number of concurrent connections the server can handle.
     ///
     /// Returns a new `DbServer` instance.
-    pub fn new(
+    pub async fn new(
         server_address: SocketAddr,
+        db_path: &str,
         mut middleware_stack: MiddlewareStack,
         max_transactions: usize,
         max_connections: usize,
@@ -70,6 +106,8 @@ impl DbServer {
         // By default, we use the logging middleware
         middleware_stack.add_middleware(LoggingMiddleware::new());
 
+        let driver = Arc::new(Driver::new(db_path).await.expect("Failed to create driver"));
+
         let mut metrics_manager = MetricsManager::new();
 
         metrics_manager.register_collector(
@@ -82,16 +120,23 @@ impl DbServer {
                 .system(Arc::new(Mutex::new(System::new_all())))
                 .build(),
         );
+        metrics_manager.register_collector(DiskIoCollector::new(driver.disk_manager().clone()));
+        let metrics_manager = Arc::new(metrics_manager);
+
+        // Queries record their latency into the server's metrics
+        let queries = SharedQueryState {
+            metrics_manager: metrics_manager.clone(),
+            ..SharedQueryState::new(max_transactions)
+        };
 
         DbServer::builder()
             .server_address(server_address)
             .connections(Arc::new(DashMap::new()))
-            .driver(Arc::new(
-                Driver::new("test.db").expect("Failed to create driver"),
-            ))
+            .driver(driver)
             .middleware_stack(Arc::new(middleware_stack))
-            .metrics_manager(Arc::new(metrics_manager))
-            .conn_pool(Arc::new(Semaphore::new(max_connections)))
+            .metrics_manager(metrics_manager)
+            .max_connections(max_connections)
+            .queries(queries)
             .build()
     }
 
@@ -102,12 +147,17 @@ impl DbServer {
             // Update connection tracking
             let conn_id = generate_connection_id(&addr);
 
-            let permit = self
-                .conn_pool
-                .clone()
-                .acquire_owned()
-                .await
-                .expect("Failed to acquire semaphore permit");
+            let Ok(permit) = self.conn_pool.clone().try_acquire_owned() else {
+                let error = ServerError::ConnectionPoolFull(self.max_connections);
+                warn!("Refusing connection from {}: {}", addr, error);
+                let protocol = self.protocol;
+                tokio::spawn(async move {
+                    if let Err(e) = refuse_connection(socket, protocol, &error).await {
+                        debug!("Failed to tell {} its connection is refused: {}", addr, e);
+                    }
+                });
+                continue;
+            };
 
             // store a flag indicating the connection is active
             self.connections.insert(conn_id.clone(), true);
@@ -122,16 +172,38 @@ impl DbServer {
 
             let (_, rx) = mpsc::channel(1); // Create a channel for communication with the connection handler
 
-            // Successfully acquired a permit, proceed with handling the connection
-            let mut connection_handler = ConnectionHandler::new(
-                socket,
-                rx,
-                self.driver.clone(),
-                self.connections.clone(),
-                self.middleware_stack.clone(),
-            );
+            // Subscribed before the connection is upgraded, so that no shutdown signal is missed
+            let shutdown = self.queries.shutdown.subscribe();
+            let protocol = self.protocol;
+            let driver = self.driver.clone();
+            let middleware_stack = self.middleware_stack.clone();
+            let settings = self.connection_settings;
+            let queries = self.queries.clone();
 
             tokio::spawn(async move {
+                let stream = match upgrade_connection(socket, protocol).await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        warn!(
+                            "Failed to upgrade the connection of {} to a WebSocket: {}",
+                            addr, e
+                        );
+                        connections.remove(&conn_id);
+                        return;
+                    }
+                };
+
+                // Successfully acquired a permit, proceed with handling the connection
+                let mut connection_handler = ConnectionHandler::new(
+                    stream,
+                    rx,
+                    driver,
+                    connections.clone(),
+                    middleware_stack,
+                    settings,
+                    queries,
+                )
+                .with_shutdown(shutdown);
                 if let Err(e) = connection_handler.handle_connection().await {
                     error!("Error handling connection: {:?}", e);
                 }
@@ -151,6 +223,59 @@ impl DbServer {
         Ok(())
     }
 
+    /// Accepts connections on `listener` until `shutdown` completes, then stops accepting
+    /// new connections and drains the active ones (see [`DbServer::drain_connections`]).
+    pub async fn serve_until(
+        &mut self,
+        listener: TcpListener,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<()> {
+        tokio::select! {
+            result = self.accept_connections(listener) => {
+                if let Err(e) = result {
+                    error!("Error accepting connections: {}", e);
+                }
+            }
+            _ = shutdown => {
+                info!("Shutdown signal received, terminating server...");
+            }
+        }
+
+        self.drain_connections().await
+    }
+
+    /// Tells every connection to close once its current request is complete and waits up to
+    /// the shutdown timeout for them to do so, then flushes the buffer pool. Connections still
+    /// active after the timeout are abandoned.
+    pub async fn drain_connections(&self) -> Result<()> {
+        // Sending only fails if no connection is subscribed, i.e. none is active
+        let _ = self.queries.shutdown.send(());
+
+        info!(
+            "Waiting up to {:?} for {} active connections to finish",
+            self.shutdown_timeout,
+            self.connections.len()
+        );
+        let drained = tokio::time::timeout(self.shutdown_timeout, async {
+            while !self.connections.is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await;
+        if drained.is_err() {
+            warn!(
+                "Abandoning {} connections still active after {:?}",
+                self.connections.len(),
+                self.shutdown_timeout
+            );
+        }
+
+        self.driver
+            .checkpoint()
+            .await
+            .context("Failed to flush the buffer pool on shutdown")
+    }
+
     pub async fn run(&mut self) -> Result<()> {
         // TODO: Make this configurable via CLI args
         let mut current_port = self.server_address.port();
@@ -169,17 +294,12 @@ impl DbServer {
             match TcpListener::bind(&address).await {
                 Ok(listener) => {
                     info!("Server successfully running on {}", &address);
-                    tokio::select! {
-                        result = self.accept_connections(listener) => {
-                            if let Err(e) = result {
-                                error!("Error accepting connections: {}", e);
-                            }
+                    self.serve_until(listener, async {
+                        if let Err(e) = signal::ctrl_c().await {
+                            error!("Failed to listen for the shutdown signal: {}", e);
                         }
-                        _ = signal::ctrl_c() => {
-                            info!("Shutdown signal received, terminating server...");
-                            break;
-                        }
-                    }
+                    })
+                    .await?;
                     break;
                 }
                 Err(e) => {
@@ -204,24 +324,7 @@ impl DbServer {
     /// Start the metrics server on a background thread with retry logic for port allocation.
     pub async fn start_metrics_server(&self) -> Result<()> {
         let server_ip = self.server_address.ip();
-        let default_port = 8080;
-        let max_port_walk = 100; // Maximum number of ports to try before giving up
-
-        let mut port = default_port;
-        while port < default_port + max_port_walk {
-            match TcpListener::bind(SocketAddr::new(server_ip, port)).await {
-                Ok(_) => {
-                    break;
-                }
-                Err(_) => {
-                    port += 1;
-
-                    if port == default_port + max_port_walk {
-                        return Err(MetricsServerError::PortAllocationError(max_port_walk).into());
-                    }
-                }
-            }
-        }
+        let port = find_metrics_port(server_ip, self.metrics_port).await?;
 
         let metrics_manager = self.metrics_manager.clone();
 
@@ -246,11 +349,17 @@ impl DbServer {
 
         let metrics_manager = self.metrics_manager.clone();
         let metrics_address = SocketAddr::new(server_ip, port);
+        info!("Metrics server listening on {}", metrics_address);
 
         tokio::spawn(async move {
             let app = Router::new().route(
                 "/metrics",
-                get(move || async move { metrics_manager.get_metrics().await }),
+                get(move || async move {
+                    (
+                        [(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)],
+                        metrics_manager.to_prometheus(),
+                    )
+                }),
             );
 
             // Run the axum server
@@ -265,7 +374,8 @@ impl DbServer {
 
     /// Start a no-op metrics server which tells the client that metrics are disabled on any request.
     pub fn start_noop_metrics_server(&self) {
-        let metrics_address = SocketAddr::new(self.server_address.ip(), 8080);
+        let metrics_address = SocketAddr::new(self.server_address.ip(), self.metrics_port);
+        info!("No-op metrics server listening on {}", metrics_address);
 
         tokio::spawn(async move {
             let app = Router::new().route(
@@ -283,8 +393,34 @@ impl DbServer {
         });
     }
 
+    /// Sets the largest (possibly chunked) message accepted from clients, in bytes.
+    pub fn set_max_message_length(&mut self, max_message_length: usize) {
+        self.connection_settings.max_message_length = max_message_length;
+    }
+
+    /// Sets how long a query may run before it is aborted (`None` disables the timeout).
+    pub fn set_statement_timeout(&mut self, statement_timeout: Option<Duration>) {
+        self.connection_settings.statement_timeout = statement_timeout;
+    }
+
+    /// Sets how clients speak over their connections: plain TCP, or framed in WebSocket messages
+    /// once their connection is upgraded (see [`crate::server::ws`]).
+    pub fn set_protocol(&mut self, protocol: NetworkProtocol) {
+        self.protocol = protocol;
+    }
+
+    /// Sets the first port the metrics server tries to bind.
+    pub fn set_metrics_port(&mut self, metrics_port: u16) {
+        self.metrics_port = metrics_port;
+    }
+
+    /// Sets how long a shutdown waits for active connections to finish.
+    pub fn set_shutdown_timeout(&mut self, shutdown_timeout: Duration) {
+        self.shutdown_timeout = shutdown_timeout;
+    }
+
     pub async fn start_metrics_logging(&mut self) {
-        // let active_connections = self.active_connections.clone();
+        let connections = self.connections.clone();
 
         let wait = 15;
         trace!("Starting metrics logging every {} seconds", wait);
@@ -293,11 +429,8 @@ impl DbServer {
             let mut delay = tokio::time::interval(Duration::from_secs(wait));
 
             loop {
-                // Current connection pool size
-                // let pool_size = active_connections.load(std::sync::atomic::Ordering::SeqCst);
-                info!("Current active connections in pool: TODO:");
-                // info!("Current active connections in pool: TODO:{}", pool_size);
-                // info!(%pool_size, "Current active connections in pool");
+                let pool_size = connections.len();
+                info!(%pool_size, "Current active connections in pool");
 
                 delay.tick().await;
             }
@@ -305,9 +438,198 @@ impl DbServer {
     }
 }
 
+/// Returns the first port, starting from `start_port`, that the metrics server can bind,
+/// trying at most [`METRICS_PORT_WALK`] ports.
+async fn find_metrics_port(ip: IpAddr, start_port: u16) -> Result<u16> {
+    for port in (start_port..=u16::MAX).take(METRICS_PORT_WALK as usize) {
+        match TcpListener::bind(SocketAddr::new(ip, port)).await {
+            Ok(_) => return Ok(port),
+            Err(e) => debug!("Metrics port {} is unavailable: {}", port, e),
+        }
+    }
+    Err(MetricsServerError::PortAllocationError(METRICS_PORT_WALK).into())
+}
+
+/// Wraps a freshly accepted connection in the stream its client speaks `protocol` over,
+/// upgrading it to a WebSocket if need be.
+async fn upgrade_connection(
+    socket: TcpStream,
+    protocol: NetworkProtocol,
+) -> std::io::Result<Box<dyn ClientStream>> {
+    Ok(match protocol {
+        NetworkProtocol::WebSocket => Box::new(WebSocketConnection::accept(socket).await?),
+        _ => Box::new(socket),
+    })
+}
+
+/// Answers a connection the server cannot serve with an `ErrorResponse` carrying the reason,
+/// then closes it.
+async fn refuse_connection(
+    socket: TcpStream,
+    protocol: NetworkProtocol,
+    error: &ServerError,
+) -> std::io::Result<()> {
+    let mut stream = upgrade_connection(socket, protocol).await?;
+    Protocol::send_message(
+        &mut stream,
+        Message::ErrorResponse(ErrorResponse::from(error)),
+    )
+    .await?;
+    stream.shutdown().await
+}
+
 pub fn generate_connection_id(addr: &SocketAddr) -> ConnectionId {
     // Generate a unique connection ID based on the client's address
     let mut hasher = FxHasher::default();
     format!("{}:{}", addr.ip(), addr.port()).hash(&mut hasher);
     hasher.finish().to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::sqlstate;
+    use arrow::datatypes::DataType;
+    use tokio::sync::oneshot;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn test_metrics_server_binds_configured_port_first() {
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        let port = TcpListener::bind(SocketAddr::new(ip, 0))
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        assert_eq!(find_metrics_port(ip, port).await.unwrap(), port);
+
+        // Walks past the configured port while it is taken
+        let _taken = TcpListener::bind(SocketAddr::new(ip, port)).await.unwrap();
+        assert!(find_metrics_port(ip, port).await.unwrap() > port);
+    }
+
+    #[tokio::test]
+    async fn test_connections_beyond_the_pool_are_refused() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Arc::new(Driver::new(db_path.to_str().unwrap()).await.unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut server = DbServer::builder()
+            .server_address(address)
+            .driver(driver)
+            .middleware_stack(Arc::new(MiddlewareStack::new()))
+            .metrics_manager(Arc::new(MetricsManager::new()))
+            .connections(Arc::new(DashMap::new()))
+            .max_connections(1)
+            .queries(SharedQueryState::new(4))
+            .build();
+        tokio::spawn(async move { server.serve_until(listener, std::future::pending()).await });
+
+        let mut first = TcpStream::connect(address).await.unwrap();
+        Protocol::send_message(&mut first, Message::query_message("SELECT 1".to_string()))
+            .await
+            .unwrap();
+        assert!(matches!(
+            Protocol::parse_incoming(&mut first).await.unwrap(),
+            Some(Message::DataRowMessage(_))
+        ));
+
+        // The second connection is told why it is refused, then closed
+        let mut second = TcpStream::connect(address).await.unwrap();
+        let response = timeout(
+            Duration::from_secs(5),
+            Protocol::parse_incoming(&mut second),
+        )
+        .await
+        .expect("The connection should be refused rather than queued")
+        .unwrap();
+        match response {
+            Some(Message::ErrorResponse(response)) => {
+                assert_eq!(response.code(), sqlstate::TOO_MANY_CONNECTIONS);
+                assert_eq!(
+                    response.error(),
+                    "Connection pool is full. Max connections: 1"
+                );
+            }
+            message => panic!("Expected an ErrorResponse, got {:?}", message),
+        }
+        assert_eq!(Protocol::parse_incoming(&mut second).await.unwrap(), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_shutdown_waits_for_running_query() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let csv_path = temp_dir.path().join("numbers.csv");
+        let rows = (0..100_000).map(|i| i.to_string()).collect::<Vec<_>>();
+        std::fs::write(&csv_path, format!("n\n{}\n", rows.join("\n"))).unwrap();
+
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Arc::new(Driver::new(db_path.to_str().unwrap()).await.unwrap());
+        // Takes a tenth of a second per batch, so the whole file takes over a second
+        driver.query_engine().register_udf(
+            "crawl",
+            vec![DataType::Int64],
+            DataType::Int64,
+            |args| {
+                std::thread::sleep(Duration::from_millis(100));
+                Ok(args[0].clone())
+            },
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let queries = SharedQueryState::new(4);
+        let running_queries = queries.running_queries.clone();
+        let mut server = DbServer::builder()
+            .server_address(address)
+            .driver(driver)
+            .middleware_stack(Arc::new(MiddlewareStack::new()))
+            .metrics_manager(Arc::new(MetricsManager::new()))
+            .connections(Arc::new(DashMap::new()))
+            .max_connections(4)
+            .queries(queries)
+            .build();
+        let (trigger, shutdown) = oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            server
+                .serve_until(listener, async {
+                    let _ = shutdown.await;
+                })
+                .await
+        });
+
+        let mut conn = TcpStream::connect(address).await.unwrap();
+        let sql = format!("SELECT count(crawl(n)) FROM {}", csv_path.display());
+        Protocol::send_message(&mut conn, Message::query_message(sql))
+            .await
+            .unwrap();
+        while running_queries.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        trigger.send(()).unwrap();
+        timeout(Duration::from_secs(10), server)
+            .await
+            .expect("Server should finish draining")
+            .unwrap()
+            .unwrap();
+
+        // The query ran to completion before the server returned, then the connection closed
+        assert_eq!(
+            Protocol::parse_incoming(&mut conn).await.unwrap(),
+            Some(Message::data_row_message(vec!["100000".to_string()]))
+        );
+        assert_eq!(
+            Protocol::parse_incoming(&mut conn).await.unwrap(),
+            Some(Message::command_complete_message(
+                "QUERY EXECUTED".to_string()
+            ))
+        );
+        assert_eq!(Protocol::parse_incoming(&mut conn).await.unwrap(), None);
+
+        // No new connections are accepted
+        assert!(TcpStream::connect(address).await.is_err());
+    }
+}
@@ -1,29 +1,168 @@
+use crate::protocol::message::{Message, MessageKind};
+use crate::protocol::{sqlstate, Protocol};
+use driver::{Driver, DriverRef};
+use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::net::UdpSocket;
 use tracing::{debug, error, info, warn};
 
-pub async fn run_udp_server(addr: &str) -> tokio::io::Result<()> {
-    let socket = UdpSocket::bind(addr).await?;
-    info!("UDP Server running on {}", addr);
+/// The largest payload a UDP datagram can carry over IPv4.
+pub const MAX_DATAGRAM_SIZE: usize = 65_507;
 
-    let mut buf = [0; 1024];
+/// `UdpServer` serves the wire protocol over UDP. Every datagram carries exactly one
+/// message, and every message is answered with a single datagram sent back to its sender.
+/// Messages larger than one datagram are rejected with an `ErrorResponse`.
+#[derive(Debug)]
+pub struct UdpServer {
+    socket: UdpSocket,
+    driver: DriverRef,
+}
 
-    loop {
-        match socket.recv_from(&mut buf).await {
-            Ok((n, peer)) => {
-                debug!("Received {} bytes from {}", n, peer);
+impl UdpServer {
+    /// Binds a new UDP server to `addr`, dispatching queries through `driver`.
+    pub async fn bind(addr: &str, driver: DriverRef) -> tokio::io::Result<Self> {
+        let socket = UdpSocket::bind(addr).await?;
+        Ok(UdpServer { socket, driver })
+    }
+
+    /// Returns the address the server is bound to.
+    pub fn local_addr(&self) -> tokio::io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
 
-                // TODO: add support for parsing the data according to custom UDP-based protocol.
+    pub async fn run(&self) -> tokio::io::Result<()> {
+        info!("UDP Server running on {}", self.local_addr()?);
 
-                // For simplicity, our placeholder echoes the received message back to the sender.
-                match socket.send_to(&buf[..n], &peer).await {
-                    Ok(_) => debug!("Response sent to {}", peer),
-                    Err(e) => warn!("Failed to send response to {}: {:?}", peer, e),
+        let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+
+        loop {
+            match self.socket.recv_from(&mut buf).await {
+                Ok((n, peer)) => {
+                    debug!("Received {} bytes from {}", n, peer);
+
+                    let response = match Protocol::parse_datagram(&buf[..n]) {
+                        Ok(message) => self.process_message(message).await,
+                        Err(e) => {
+                            warn!("Failed to parse datagram from {}: {}", peer, e);
+                            Message::error_response_with_code(
+                                sqlstate::PROTOCOL_VIOLATION,
+                                e.to_string(),
+                            )
+                        }
+                    };
+
+                    let response = Protocol::encode_message(&response);
+                    match self.socket.send_to(&response, &peer).await {
+                        Ok(_) => debug!("Response sent to {}", peer),
+                        Err(e) => warn!("Failed to send response to {}: {:?}", peer, e),
+                    }
+                }
+                Err(e) => {
+                    error!("Error receiving data: {:?}", e);
+                    // TODO: add support for handling errors (e.g. retrying, reporting, etc.)
                 }
             }
-            Err(e) => {
-                error!("Error receiving data: {:?}", e);
-                // TODO: add support for handling errors (e.g. retrying, reporting, etc.)
+        }
+    }
+
+    async fn process_message(&self, message: Message) -> Message {
+        debug!("Processing message: {}", message);
+
+        match message.kind() {
+            MessageKind::StartupMessage => {
+                info!(
+                    "Startup request with protocol version: {}",
+                    message.protocol_version()
+                );
+                Message::command_complete_message("STARTUP COMPLETE".to_string())
             }
+            MessageKind::QueryMessage => {
+                let query = message.query();
+                info!("Received query: `{}`", query);
+
+                // Run the query in its own task so that a panicking query can't take the server down
+                let driver = self.driver.clone();
+                match tokio::spawn(async move { driver.process_sql_command(&query).await }).await {
+                    Ok(()) => Message::command_complete_message("QUERY EXECUTED".to_string()),
+                    Err(e) => {
+                        error!("Query execution failed: {:?}", e);
+                        Message::error_response("Query execution failed".to_string())
+                    }
+                }
+            }
+            _ => Message::error_response_with_code(
+                sqlstate::PROTOCOL_VIOLATION,
+                "Unsupported message type: ".to_string() + &message.to_string(),
+            ),
         }
     }
 }
+
+pub async fn run_udp_server(addr: &str) -> tokio::io::Result<()> {
+    let driver = Arc::new(
+        Driver::new("test.db")
+            .await
+            .expect("Failed to create driver"),
+    );
+    UdpServer::bind(addr, driver).await?.run().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BufMut;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    async fn spawn_server() -> (SocketAddr, UdpSocket, tempfile::TempDir) {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("udp.db");
+        let driver = Arc::new(Driver::new(db_path.to_str().unwrap()).await.unwrap());
+
+        let server = UdpServer::bind("127.0.0.1:0", driver).await.unwrap();
+        let server_addr = server.local_addr().unwrap();
+        tokio::spawn(async move { server.run().await });
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        (server_addr, client, temp_dir)
+    }
+
+    async fn round_trip(client: &UdpSocket, server_addr: SocketAddr, datagram: &[u8]) -> Vec<u8> {
+        client.send_to(datagram, server_addr).await.unwrap();
+        let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+        let (n, _) = timeout(Duration::from_secs(5), client.recv_from(&mut buf))
+            .await
+            .expect("No response datagram arrived")
+            .unwrap();
+        buf.truncate(n);
+        buf
+    }
+
+    #[tokio::test]
+    async fn test_query_message_gets_response_datagram() {
+        let (server_addr, client, _temp_dir) = spawn_server().await;
+        let query = Protocol::encode_message(&Message::query_message(
+            "SELECT * FROM missing.csv".to_string(),
+        ));
+
+        let response = round_trip(&client, server_addr, &query).await;
+        match Protocol::parse_datagram(&response).unwrap() {
+            Message::CommandCompleteMessage(message) => assert_eq!(message.tag(), "QUERY EXECUTED"),
+            message => panic!("Expected CommandCompleteMessage, got {}", message),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_message_larger_than_datagram_is_rejected() {
+        let (server_addr, client, _temp_dir) = spawn_server().await;
+
+        // The header claims a longer message than the datagram actually carries
+        let mut datagram = bytes::BytesMut::new();
+        datagram.put_u8(MessageKind::QueryMessage as u8);
+        datagram.put_i32(1_000);
+        datagram.extend_from_slice(b"SELECT");
+
+        let response = round_trip(&client, server_addr, &datagram).await;
+        assert_eq!(response[0], MessageKind::ErrorResponse as u8);
+    }
+}
@@ -0,0 +1,225 @@
+//! # WebSocket Transport
+//!
+//! Lets clients that can't open raw TCP connections (e.g. browsers) connect over WebSockets.
+//! The server accepts the WebSocket upgrade of every TCP connection (see
+//! [`DbServer::set_protocol`]) and hands the resulting [`WebSocketConnection`] to the same
+//! [`ConnectionHandler`](crate::protocol::handler::ConnectionHandler) as TCP connections.
+//!
+//! WebSockets are already framed, so a [`WebSocketConnection`] turns them back into the byte
+//! stream protocol messages are parsed from:
+//!
+//! - The payloads of binary messages are read back to back, so a binary message may hold
+//!   several protocol messages (or part of one).
+//! - A text message is read as a `QueryMessage` of its text.
+//! - Everything the handler writes at once (e.g. a response, or a batch of rows) is sent as a
+//!   single binary message.
+//!
+//! [`DbServer::set_protocol`]: super::tcp::DbServer::set_protocol
+
+use crate::protocol::message::Message;
+use crate::protocol::stream::ClientStream;
+use crate::protocol::Protocol;
+use bytes::BytesMut;
+use futures_util::{SinkExt, StreamExt};
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::WebSocketStream;
+use tracing::debug;
+
+fn io_error(error: tokio_tungstenite::tungstenite::Error) -> io::Error {
+    match error {
+        tokio_tungstenite::tungstenite::Error::Io(e) => e,
+        e => io::Error::other(e),
+    }
+}
+
+/// A WebSocket connection, read and written as the byte stream of the protocol.
+#[derive(Debug)]
+pub struct WebSocketConnection {
+    peer_addr: SocketAddr,
+    socket: WebSocketStream<TcpStream>,
+    /// The bytes received but not read yet
+    read_buffer: BytesMut,
+    /// Whether a message was queued on the socket but not flushed yet
+    sending: bool,
+}
+
+impl WebSocketConnection {
+    /// Accepts the WebSocket upgrade of a TCP connection.
+    pub async fn accept(stream: TcpStream) -> io::Result<Self> {
+        let peer_addr = stream.peer_addr()?;
+        let socket = tokio_tungstenite::accept_async(stream)
+            .await
+            .map_err(io_error)?;
+        debug!("Upgraded the connection of {} to a WebSocket", peer_addr);
+
+        Ok(WebSocketConnection {
+            peer_addr,
+            socket,
+            read_buffer: BytesMut::new(),
+            sending: false,
+        })
+    }
+}
+
+impl ClientStream for WebSocketConnection {
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.peer_addr)
+    }
+}
+
+impl AsyncRead for WebSocketConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        // Keep sending the last message written while waiting for the next one
+        if self.sending {
+            if let Poll::Ready(flushed) = self.socket.poll_flush_unpin(cx) {
+                self.sending = false;
+                flushed.map_err(io_error)?;
+            }
+        }
+        while self.read_buffer.is_empty() {
+            match ready!(self.socket.poll_next_unpin(cx)) {
+                Some(Ok(WsMessage::Binary(data))) => self.read_buffer.extend_from_slice(&data),
+                Some(Ok(WsMessage::Text(sql))) => {
+                    let query = Message::query_message(sql);
+                    let encoded =
+                        Protocol::encode_chunked(&query, Protocol::MAX_FRAME_LENGTH as usize);
+                    self.read_buffer.extend_from_slice(&encoded);
+                }
+                // Pings are answered by the socket itself
+                Some(Ok(WsMessage::Ping(_) | WsMessage::Pong(_) | WsMessage::Frame(_))) => {}
+                // The end of the stream
+                Some(Ok(WsMessage::Close(_))) | None => return Poll::Ready(Ok(())),
+                Some(Err(e)) => return Poll::Ready(Err(io_error(e))),
+            }
+        }
+
+        let len = buf.remaining().min(self.read_buffer.len());
+        buf.put_slice(&self.read_buffer.split_to(len));
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for WebSocketConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        // The previous message is sent before the next one is accepted, so that a `Pending`
+        // write never leaves a message queued on the socket
+        if self.sending {
+            ready!(self.socket.poll_flush_unpin(cx)).map_err(io_error)?;
+            self.sending = false;
+        }
+        ready!(self.socket.poll_ready_unpin(cx)).map_err(io_error)?;
+        self.socket
+            .start_send_unpin(WsMessage::Binary(buf.to_vec()))
+            .map_err(io_error)?;
+
+        // The message now belongs to the socket. Responses aren't flushed by the handler, so
+        // it is flushed right away, and otherwise by the next write, flush or read.
+        self.sending = true;
+        if let Poll::Ready(flushed) = self.socket.poll_flush_unpin(cx) {
+            self.sending = false;
+            flushed.map_err(io_error)?;
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.socket.poll_flush_unpin(cx)).map_err(io_error)?;
+        self.sending = false;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.socket.poll_close_unpin(cx).map_err(io_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::MiddlewareStack;
+    use crate::protocol::handler::SharedQueryState;
+    use crate::server::tcp::DbServer;
+    use cli::NetworkProtocol;
+    use dashmap::DashMap;
+    use driver::Driver;
+    use metrics::manager::MetricsManager;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::{connect_async, MaybeTlsStream};
+
+    type ClientSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    /// Receives a binary message holding a single protocol message.
+    async fn receive(socket: &mut ClientSocket) -> Message {
+        match socket.next().await.unwrap().unwrap() {
+            WsMessage::Binary(data) => Protocol::parse_incoming(&mut data.as_slice())
+                .await
+                .unwrap()
+                .unwrap(),
+            message => panic!("Expected a binary message, got {:?}", message),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_query_over_websocket() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let driver = Arc::new(Driver::new(db_path.to_str().unwrap()).await.unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut server = DbServer::builder()
+            .server_address(address)
+            .driver(driver)
+            .middleware_stack(Arc::new(MiddlewareStack::new()))
+            .metrics_manager(Arc::new(MetricsManager::new()))
+            .connections(Arc::new(DashMap::new()))
+            .max_connections(4)
+            .queries(SharedQueryState::new(4))
+            .protocol(NetworkProtocol::WebSocket)
+            .build();
+        tokio::spawn(async move { server.serve_until(listener, std::future::pending()).await });
+
+        let (mut socket, _) = connect_async(format!("ws://{}", address)).await.unwrap();
+        // A serialized protocol message in a binary message
+        let query = Protocol::encode_message(&Message::query_message("SELECT 1".to_string()));
+        socket
+            .send(WsMessage::Binary(query.to_vec()))
+            .await
+            .unwrap();
+        assert_eq!(
+            receive(&mut socket).await,
+            Message::data_row_message(vec!["1".to_string()])
+        );
+        assert_eq!(
+            receive(&mut socket).await,
+            Message::command_complete_message("QUERY EXECUTED".to_string())
+        );
+
+        // The text of a text message is the query
+        socket
+            .send(WsMessage::Text("SELECT 'ws'".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(
+            receive(&mut socket).await,
+            Message::data_row_message(vec!["ws".to_string()])
+        );
+    }
+}
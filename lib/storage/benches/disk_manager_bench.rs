@@ -1,11 +1,11 @@
-use common::PAGE_SIZE;
+use common::USABLE_PAGE_SIZE;
 use criterion::{criterion_group, criterion_main, Criterion};
 use std::fs;
 use storage::disk::DiskManager;
 
 fn write_benchmark(c: &mut Criterion) {
     let dm = DiskManager::new("testdata/bench.db").unwrap();
-    let data = vec![0u8; PAGE_SIZE];
+    let data = vec![0u8; USABLE_PAGE_SIZE];
 
     c.bench_function("write_page", |b| {
         b.iter(|| {
@@ -0,0 +1,455 @@
+//! Where a [`DiskManager`](super::DiskManager) keeps its pages, log and free list: in files, or
+//! in memory for `:memory:` databases. Tests can also wrap a backend in one whose page I/O fails
+//! with injected errors.
+
+use common::PAGE_SIZE;
+#[cfg(test)]
+use parking_lot::Mutex;
+use parking_lot::RwLock;
+#[cfg(test)]
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use tokio::fs::File as AsyncFile;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tracing::error;
+
+/// The database path that selects an in-memory database, which is never written to disk and
+/// is gone once its [`DiskManager`](super::DiskManager) is dropped.
+pub const IN_MEMORY_PATH: &str = ":memory:";
+
+fn page_offset(page_id: u32) -> u64 {
+    page_id as u64 * PAGE_SIZE as u64
+}
+
+#[derive(Debug)]
+pub(crate) enum Backend {
+    File(FileBackend),
+    Memory(MemoryBackend),
+    #[cfg(test)]
+    Faulty(FaultyBackend),
+}
+
+#[derive(Debug)]
+pub(crate) struct FileBackend {
+    // Synchronous file handle for the database.
+    db_io: RwLock<File>,
+    // Synchronous file handle for the log.
+    log_io: RwLock<File>,
+    // Synchronous file handle for the persisted free page list.
+    free_io: RwLock<File>,
+    // File path for the database.
+    db_file: String,
+    // File path for the log.
+    log_file: String,
+}
+
+/// Growable byte buffers standing in for the database, log and free list files. Reads beyond
+/// their end behave like reads beyond the end of a file.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryBackend {
+    db: RwLock<Vec<u8>>,
+    log: RwLock<Vec<u8>>,
+    free: RwLock<Vec<u8>>,
+}
+
+/// A backend whose page reads and writes first fail with the injected errors, in order, and
+/// then go to the wrapped backend. The wrapped backend is always accessed synchronously.
+#[cfg(test)]
+#[derive(Debug)]
+pub(crate) struct FaultyBackend {
+    inner: Box<Backend>,
+    faults: Mutex<VecDeque<io::ErrorKind>>,
+}
+
+#[cfg(test)]
+impl FaultyBackend {
+    pub(crate) fn new(inner: Backend) -> Self {
+        Self {
+            inner: Box::new(inner),
+            faults: Mutex::default(),
+        }
+    }
+
+    /// Makes the next page reads and writes fail with errors of the given kinds, in order.
+    pub(crate) fn inject(&self, kinds: impl IntoIterator<Item = io::ErrorKind>) {
+        self.faults.lock().extend(kinds);
+    }
+
+    /// Returns the number of injected errors that are still to be returned.
+    pub(crate) fn pending_faults(&self) -> usize {
+        self.faults.lock().len()
+    }
+
+    /// Fails with the next injected error, if any.
+    fn next_fault(&self) -> io::Result<()> {
+        match self.faults.lock().pop_front() {
+            Some(kind) => Err(io::Error::new(kind, "injected I/O error")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Copies the bytes of `bytes` from `offset` into `buf`, returning how many there were.
+fn read_at(bytes: &[u8], offset: u64, buf: &mut [u8]) -> usize {
+    let start = usize::try_from(offset)
+        .unwrap_or(usize::MAX)
+        .min(bytes.len());
+    let len = buf.len().min(bytes.len() - start);
+    buf[..len].copy_from_slice(&bytes[start..start + len]);
+    len
+}
+
+/// Copies `data` into `bytes` at `offset`, zero-filling any gap before it.
+fn write_at(bytes: &mut Vec<u8>, offset: u64, data: &[u8]) {
+    let start = offset as usize;
+    if bytes.len() < start + data.len() {
+        bytes.resize(start + data.len(), 0);
+    }
+    bytes[start..start + data.len()].copy_from_slice(data);
+}
+
+impl Backend {
+    /// Opens (creating them if needed) the database file at `db_file` and its log and free
+    /// list files, or creates an empty in-memory database if `db_file` is
+    /// [`IN_MEMORY_PATH`].
+    pub(crate) fn open(db_file: &str, log_file: &str) -> io::Result<Self> {
+        if db_file == IN_MEMORY_PATH {
+            return Ok(Backend::Memory(MemoryBackend::default()));
+        }
+
+        let open = |path: &str| {
+            File::options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)
+        };
+        Ok(Backend::File(FileBackend {
+            db_io: RwLock::new(open(db_file)?),
+            log_io: RwLock::new(open(log_file)?),
+            free_io: RwLock::new(open(&format!("{}.free", db_file))?),
+            db_file: db_file.to_string(),
+            log_file: log_file.to_string(),
+        }))
+    }
+
+    /// Returns the size of the database in bytes.
+    pub(crate) fn len(&self) -> u64 {
+        match self {
+            Backend::File(file) => file
+                .db_io
+                .read()
+                .metadata()
+                .expect("Failed to read metadata")
+                .len(),
+            Backend::Memory(memory) => memory.db.read().len() as u64,
+            #[cfg(test)]
+            Backend::Faulty(faulty) => faulty.inner.len(),
+        }
+    }
+
+    /// Resizes the database to `len` bytes, durably.
+    pub(crate) fn set_len(&self, len: u64) -> io::Result<()> {
+        match self {
+            Backend::File(file) => {
+                let db_io = file.db_io.write();
+                db_io.set_len(len)?;
+                db_io.sync_all()
+            }
+            Backend::Memory(memory) => {
+                memory.db.write().resize(len as usize, 0);
+                Ok(())
+            }
+            #[cfg(test)]
+            Backend::Faulty(faulty) => faulty.inner.set_len(len),
+        }
+    }
+
+    /// Writes a page and flushes it.
+    pub(crate) fn write_page(&self, page_id: u32, page_data: &[u8]) -> io::Result<()> {
+        let file = match self {
+            Backend::File(file) => file,
+            Backend::Memory(memory) => {
+                write_at(&mut memory.db.write(), page_offset(page_id), page_data);
+                return Ok(());
+            }
+            #[cfg(test)]
+            Backend::Faulty(faulty) => {
+                faulty.next_fault()?;
+                return faulty.inner.write_page(page_id, page_data);
+            }
+        };
+
+        let mut db_io = file.db_io.write();
+        db_io
+            .seek(SeekFrom::Start(page_offset(page_id)))
+            .map_err(|e| {
+                error!("Failed to seek to page {}: {}", page_id, e);
+                e
+            })?;
+
+        db_io.write_all(page_data).map_err(|e| {
+            error!("Failed to write page {}: {}", page_id, e);
+            e
+        })?;
+        db_io.flush().map_err(|e| {
+            error!("Failed to flush page {}: {}", page_id, e);
+            e
+        })
+    }
+
+    pub(crate) async fn write_page_async(&self, page_id: u32, page_data: &[u8]) -> io::Result<()> {
+        let file = match self {
+            Backend::File(file) => file,
+            Backend::Memory(_) => return self.write_page(page_id, page_data),
+            #[cfg(test)]
+            Backend::Faulty(_) => return self.write_page(page_id, page_data),
+        };
+
+        let mut db_io = AsyncFile::options()
+            .write(true)
+            .create(true)
+            .open(&file.db_file)
+            .await
+            .map_err(|e| {
+                error!("Failed to open db file {}: {}", file.db_file, e);
+                e
+            })?;
+
+        db_io
+            .seek(SeekFrom::Start(page_offset(page_id)))
+            .await
+            .map_err(|e| {
+                error!("Failed to seek to page {}: {}", page_id, e);
+                e
+            })?;
+        db_io.write_all(page_data).await.map_err(|e| {
+            error!("Failed to write page {}: {}", page_id, e);
+            e
+        })?;
+        db_io.flush().await // Explicitly flush the data to disk
+    }
+
+    /// Reads a page into `page_data`, returning the number of bytes read, which is less than
+    /// its length if the page lies (partly) beyond the end of the database.
+    pub(crate) fn read_page(&self, page_id: u32, page_data: &mut [u8]) -> io::Result<usize> {
+        let file = match self {
+            Backend::File(file) => file,
+            Backend::Memory(memory) => {
+                return Ok(read_at(&memory.db.read(), page_offset(page_id), page_data))
+            }
+            #[cfg(test)]
+            Backend::Faulty(faulty) => {
+                faulty.next_fault()?;
+                return faulty.inner.read_page(page_id, page_data);
+            }
+        };
+
+        let mut db_io = File::options()
+            .read(true)
+            .open(&file.db_file)
+            .map_err(|e| {
+                error!("Failed to open db file {}: {}", file.db_file, e);
+                e
+            })?;
+
+        db_io
+            .seek(SeekFrom::Start(page_offset(page_id)))
+            .map_err(|e| {
+                error!("Failed to seek to page {}: {}", page_id, e);
+                e
+            })?;
+        db_io.read(page_data).map_err(|e| {
+            error!("Failed to read page {}: {}", page_id, e);
+            e
+        })
+    }
+
+    /// Like [`Backend::read_page`], for a page of `len` bytes, which are returned zero-filled
+    /// beyond the end of the database.
+    pub(crate) async fn read_page_async(&self, page_id: u32, len: usize) -> io::Result<Vec<u8>> {
+        let file = match self {
+            Backend::File(file) => file,
+            Backend::Memory(memory) => {
+                let mut read_data = vec![0; len];
+                read_at(&memory.db.read(), page_offset(page_id), &mut read_data);
+                return Ok(read_data);
+            }
+            #[cfg(test)]
+            Backend::Faulty(_) => {
+                let mut read_data = vec![0; len];
+                self.read_page(page_id, &mut read_data)?;
+                return Ok(read_data);
+            }
+        };
+
+        let mut db_io = AsyncFile::open(&file.db_file).await.map_err(|e| {
+            error!("Failed to open db file {}: {}", file.db_file, e);
+            e
+        })?;
+
+        db_io
+            .seek(SeekFrom::Start(page_offset(page_id)))
+            .await
+            .map_err(|e| {
+                error!("Failed to seek to page {}: {}", page_id, e);
+                e
+            })?;
+
+        let mut read_data = vec![0; len];
+        let mut read_size = 0;
+        while read_size < len {
+            let n = db_io.read(&mut read_data[read_size..]).await.map_err(|e| {
+                error!("Failed to read page {}: {}", page_id, e);
+                e
+            })?;
+            if n == 0 {
+                break;
+            }
+            read_size += n;
+        }
+        Ok(read_data)
+    }
+
+    /// Appends to the log and syncs it to disk, so the appended bytes survive a power loss.
+    pub(crate) fn append_log(&self, log_data: &[u8]) -> io::Result<()> {
+        let file = match self {
+            Backend::File(file) => file,
+            Backend::Memory(memory) => {
+                memory.log.write().extend_from_slice(log_data);
+                return Ok(());
+            }
+            #[cfg(test)]
+            Backend::Faulty(faulty) => return faulty.inner.append_log(log_data),
+        };
+
+        let mut log_io = file.log_io.write();
+        // The log is append-only, including across reopens of the database
+        log_io.seek(SeekFrom::End(0)).map_err(|e| {
+            error!("Failed to seek to the end of the log: {}", e);
+            e
+        })?;
+        log_io.write_all(log_data).map_err(|e| {
+            error!("Failed to write log: {}", e);
+            e
+        })?;
+        log_io.flush().map_err(|e| {
+            error!("Failed to flush log: {}", e);
+            e
+        })?;
+        log_io.sync_data().map_err(|e| {
+            error!("Failed to sync log: {}", e);
+            e
+        })
+    }
+
+    /// Reads the log from `offset` into `log_data`, returning the number of bytes read.
+    pub(crate) fn read_log(&self, offset: u64, log_data: &mut [u8]) -> io::Result<usize> {
+        let file = match self {
+            Backend::File(file) => file,
+            Backend::Memory(memory) => return Ok(read_at(&memory.log.read(), offset, log_data)),
+            #[cfg(test)]
+            Backend::Faulty(faulty) => return faulty.inner.read_log(offset, log_data),
+        };
+
+        let mut log_io = File::options()
+            .read(true)
+            .open(&file.log_file)
+            .map_err(|e| {
+                error!("Failed to open log file {}: {}", file.log_file, e);
+                e
+            })?;
+
+        log_io.seek(SeekFrom::Start(offset)).map_err(|e| {
+            error!("Failed to seek to offset {}: {}", offset, e);
+            e
+        })?;
+        log_io.read(log_data).map_err(|e| {
+            error!("Failed to read log: {}", e);
+            e
+        })
+    }
+
+    /// Returns the size of the log in bytes.
+    pub(crate) fn log_len(&self) -> u64 {
+        match self {
+            Backend::File(file) => file
+                .log_io
+                .read()
+                .metadata()
+                .expect("Failed to read log metadata")
+                .len(),
+            Backend::Memory(memory) => memory.log.read().len() as u64,
+            #[cfg(test)]
+            Backend::Faulty(faulty) => faulty.inner.log_len(),
+        }
+    }
+
+    /// Truncates the log to `len` bytes, durably.
+    pub(crate) fn truncate_log(&self, len: u64) -> io::Result<()> {
+        match self {
+            Backend::File(file) => {
+                let log_io = file.log_io.write();
+                log_io.set_len(len)?;
+                log_io.sync_data()
+            }
+            Backend::Memory(memory) => {
+                memory.log.write().truncate(len as usize);
+                Ok(())
+            }
+            #[cfg(test)]
+            Backend::Faulty(faulty) => faulty.inner.truncate_log(len),
+        }
+    }
+
+    /// Returns the persisted free list.
+    pub(crate) fn read_free_list(&self) -> io::Result<Vec<u8>> {
+        match self {
+            Backend::File(file) => {
+                let mut free_io = file.free_io.write();
+                let mut bytes = Vec::new();
+                free_io.seek(SeekFrom::Start(0))?;
+                free_io.read_to_end(&mut bytes)?;
+                Ok(bytes)
+            }
+            Backend::Memory(memory) => Ok(memory.free.read().clone()),
+            #[cfg(test)]
+            Backend::Faulty(faulty) => faulty.inner.read_free_list(),
+        }
+    }
+
+    /// Replaces the persisted free list, durably.
+    pub(crate) fn write_free_list(&self, bytes: &[u8]) -> io::Result<()> {
+        match self {
+            Backend::File(file) => {
+                let mut free_io = file.free_io.write();
+                free_io.set_len(0)?;
+                free_io.seek(SeekFrom::Start(0))?;
+                free_io.write_all(bytes)?;
+                free_io.sync_data()
+            }
+            Backend::Memory(memory) => {
+                *memory.free.write() = bytes.to_vec();
+                Ok(())
+            }
+            #[cfg(test)]
+            Backend::Faulty(faulty) => faulty.inner.write_free_list(bytes),
+        }
+    }
+
+    /// Flushes the database, log and free list files.
+    pub(crate) fn flush(&self) -> io::Result<()> {
+        match self {
+            Backend::File(file) => {
+                file.db_io.write().flush()?;
+                file.log_io.write().flush()?;
+                file.free_io.write().flush()
+            }
+            Backend::Memory(_) => Ok(()),
+            #[cfg(test)]
+            Backend::Faulty(faulty) => faulty.inner.flush(),
+        }
+    }
+}
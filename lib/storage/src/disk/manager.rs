@@ -1,17 +1,22 @@
+use super::backend::{Backend, IN_MEMORY_PATH};
 #[allow(unused_imports)]
 use crate::disk::setup_dm;
 use anyhow::Result;
-use common::PAGE_SIZE;
-use parking_lot::RwLock;
+use common::{PAGE_CHECKSUM_SIZE, PAGE_HEADER_SIZE, PAGE_SIZE, USABLE_PAGE_SIZE};
+use parking_lot::Mutex;
+use std::collections::BTreeSet;
 use std::fmt::Debug;
+#[cfg(test)]
 use std::fs::File;
+use std::future::Future;
+use std::io::{self, ErrorKind};
+#[cfg(test)]
 use std::io::{Read, Seek, SeekFrom, Write};
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
-use tokio::fs::File as AsyncFile;
-use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
-use tracing::{debug, error, info, instrument};
+use tracing::{debug, error, info, instrument, warn};
 
 #[derive(Error, Debug)]
 pub enum DiskManagerError {
@@ -21,16 +26,75 @@ pub enum DiskManagerError {
     #[error("Failed to perform async I/O operation")]
     AsyncIoError(#[from] tokio::io::Error),
 
-    #[error("Page exceeds maximum allowed size of 4KB")]
+    #[error("Page data exceeds the payload size of a page")]
     PageSizeError,
+
+    #[error("Checksum mismatch for page {page_id}")]
+    ChecksumMismatch { page_id: u32 },
+
+    #[error("Invalid header for page {page_id}")]
+    InvalidPageHeader { page_id: u32 },
     // TODO: future other error types ...
     // TODO: more semantic error types (e.g. PageNotFound, etc.)
     // read/write errors
 }
 
+/// The magic number at the start of the header of every written page.
+const PAGE_MAGIC: [u8; 4] = *b"R2DB";
+
+/// The kind byte of the header of a data page.
+const DATA_PAGE: u8 = 1;
+
+/// What a page read from disk holds, according to its header.
+///
+/// The header (see [`PAGE_HEADER_SIZE`]) leads every page written while checksums are enabled,
+/// followed by the payload and the checksum covering both. A page without one was never
+/// written: reading it yields zeros, which must not be mistaken for a page that was written all
+/// zeros.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageKind {
+    /// The page has never been written (e.g. it lies beyond the end of the file)
+    Uninitialized,
+    /// The page holds data, written with the given LSN
+    Data { lsn: u64 },
+}
+
 /// A reference-counted [`DiskManager`] handle that can be shared across threads.
 pub type DiskManagerRef = Arc<DiskManager>;
 
+/// How page reads and writes are retried when they fail with a transient I/O error (see
+/// [`is_transient`]). Other errors are returned straight away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The number of times an operation is retried before its error is returned
+    pub max_retries: u32,
+    /// The delay before the first retry, doubled before each further retry
+    pub initial_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay before the given retry (counting from zero).
+    fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+    }
+}
+
+/// Returns whether an I/O error is transient, i.e. whether the operation may succeed if it is
+/// simply tried again. Errors such as `NotFound` or `PermissionDenied` are permanent.
+pub fn is_transient(error: &io::Error) -> bool {
+    matches!(error.kind(), ErrorKind::Interrupted | ErrorKind::WouldBlock)
+}
+
 /// DiskManager handles disk-based storage operations.
 /// It provides synchronous and asynchronous methods to read and write pages of data.
 ///
@@ -38,24 +102,38 @@ pub type DiskManagerRef = Arc<DiskManager>;
 /// - Page-Based I/O: Operates at the granularity of pages.
 /// - Async I/O Support: Incorporates async I/O operations using Tokio.
 /// - Logging: Facilitates logging of operations using the `tracing` crate.
-/// - Atomic Counters: Maintains counters for flushes and writes.
+/// - Atomic Counters: Maintains counters for flushes, writes and reads.
+/// - Page Allocation: Hands out page ids, reusing deallocated pages before growing the file.
+///   The free list is persisted in a `<db_file>.free` file alongside the database.
+/// - In-Memory Databases: Opening the path `:memory:` keeps the database, log and free list
+///   in memory instead of files. Every such manager holds a database of its own.
 ///
 /// # Usage Scenarios
 /// Ideal for high-throughput and low-latency disk access
 #[derive(Debug)]
 pub struct DiskManager {
-    // Synchronous file handle for the database.
-    db_io: Arc<RwLock<File>>,
-    // Synchronous file handle for the log.
-    log_io: Arc<RwLock<File>>,
+    // The files (or memory) holding the database, log and free page list.
+    backend: Backend,
     // File path for the database.
     db_file: String,
-    // File path for the log.
-    log_file: String,
+    // Deallocated page ids available for reuse (lowest ids are reused first)
+    free_pages: Mutex<BTreeSet<u32>>,
+    // The page id handed out once the free list is exhausted
+    next_page_id: AtomicU32,
     // Counter for the number of flushes to disk (used for statistics)
     num_flushes: AtomicU32,
     // Counter for the number of writes to disk (used for statistics)
     num_writes: AtomicU32,
+    // Counter for the number of bytes written to disk (used for statistics)
+    num_bytes_written: AtomicU64,
+    // Counter for the number of page reads from disk (used for statistics)
+    num_reads: AtomicU32,
+    // Whether pages are stamped with (and verified against) a trailing CRC32 checksum
+    checksums_enabled: AtomicBool,
+    // Whether all-zero pages beyond the end of the file are left unwritten
+    sparse_writes_enabled: AtomicBool,
+    // How page reads and writes are retried on transient I/O errors
+    retry_policy: Mutex<RetryPolicy>,
 }
 
 impl DiskManager {
@@ -73,7 +151,9 @@ impl DiskManager {
             .into());
         }
 
-        if !std::path::Path::new(db_file).exists() {
+        if db_file == IN_MEMORY_PATH {
+            debug!("Creating a new in-memory database");
+        } else if !std::path::Path::new(db_file).exists() {
             debug!(
                 "Database file {} does not exist. Creating a new database file",
                 db_file
@@ -85,25 +165,307 @@ impl DiskManager {
             );
         }
 
-        let db_io = File::options()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(db_file)?;
-        let log_io = File::options()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(&log_file)?;
-
-        Ok(Self {
-            db_io: Arc::new(RwLock::new(db_io)),
-            log_io: Arc::new(RwLock::new(log_io)),
+        Self::with_backend(db_file, Backend::open(db_file, &log_file)?)
+    }
+
+    /// Creates a manager of the database at `db_file`, kept in the given backend.
+    fn with_backend(db_file: &str, backend: Backend) -> Result<Self> {
+        let free_pages = Self::load_free_pages(&backend)?;
+        debug!("Loaded {} free pages for {}", free_pages.len(), db_file);
+
+        let dm = Self {
+            backend,
             db_file: db_file.to_string(),
-            log_file,
+            free_pages: Mutex::new(free_pages),
+            next_page_id: AtomicU32::new(0),
             num_flushes: AtomicU32::new(0),
             num_writes: AtomicU32::new(0),
-        })
+            num_bytes_written: AtomicU64::new(0),
+            num_reads: AtomicU32::new(0),
+            checksums_enabled: AtomicBool::new(true),
+            sparse_writes_enabled: AtomicBool::new(false),
+            retry_policy: Mutex::default(),
+        };
+        dm.next_page_id.store(dm.num_pages(), Ordering::SeqCst);
+
+        Ok(dm)
+    }
+
+    /// Reads the persisted free list, stored as a sequence of big-endian page ids.
+    fn load_free_pages(backend: &Backend) -> Result<BTreeSet<u32>> {
+        let bytes = backend.read_free_list()?;
+
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|id| u32::from_be_bytes(id.try_into().expect("chunk is 4 bytes")))
+            .collect())
+    }
+
+    /// Rewrites the persisted free list. Called with the free list lock held so that
+    /// concurrent allocations cannot persist out of order.
+    fn persist_free_pages(&self, free_pages: &BTreeSet<u32>) -> Result<()> {
+        let bytes = free_pages
+            .iter()
+            .flat_map(|id| id.to_be_bytes())
+            .collect::<Vec<u8>>();
+
+        self.backend.write_free_list(&bytes)?;
+        Ok(())
+    }
+
+    /// Allocates a page id, reusing the lowest deallocated page before extending the file.
+    #[instrument(skip(self))]
+    pub fn allocate_page(&self) -> Result<u32> {
+        let mut free_pages = self.free_pages.lock();
+        if let Some(page_id) = free_pages.pop_first() {
+            self.persist_free_pages(&free_pages)?;
+            debug!("Reusing deallocated page {}", page_id);
+            return Ok(page_id);
+        }
+
+        let page_id = self.next_page_id.fetch_add(1, Ordering::SeqCst);
+        debug!("Allocated new page {}", page_id);
+        Ok(page_id)
+    }
+
+    /// Returns a page to the free list so that a later [`DiskManager::allocate_page`] can reuse it.
+    /// The free list is persisted before returning.
+    #[instrument(skip(self))]
+    pub fn deallocate_page(&self, page_id: u32) -> Result<()> {
+        if page_id >= self.next_page_id.load(Ordering::SeqCst) {
+            warn!("Ignoring deallocation of unallocated page {}", page_id);
+            return Ok(());
+        }
+
+        let mut free_pages = self.free_pages.lock();
+        if !free_pages.insert(page_id) {
+            warn!("Page {} is already deallocated", page_id);
+            return Ok(());
+        }
+        self.persist_free_pages(&free_pages)
+    }
+
+    /// Shrinks the database file to `num_pages` pages. Only call this once every page at or
+    /// beyond `num_pages` is confirmed free: those pages are dropped from the free list and
+    /// the allocator resumes at `num_pages`. Reads beyond the new end return zeroed pages.
+    #[instrument(skip(self))]
+    pub fn truncate_to(&self, num_pages: u32) -> Result<()> {
+        let current = self.num_pages();
+        if num_pages > current {
+            return Err(DiskManagerError::IoError(format!(
+                "Cannot truncate {} pages to a larger size of {} pages",
+                current, num_pages
+            ))
+            .into());
+        }
+
+        let mut free_pages = self.free_pages.lock();
+        self.backend.set_len(num_pages as u64 * PAGE_SIZE as u64)?;
+
+        let dropped = free_pages.split_off(&num_pages);
+        if !dropped.is_empty() {
+            self.persist_free_pages(&free_pages)?;
+        }
+        self.next_page_id.store(num_pages, Ordering::SeqCst);
+
+        info!(
+            "Truncated {} from {} to {} pages",
+            self.db_file, current, num_pages
+        );
+        Ok(())
+    }
+
+    /// Returns the number of deallocated pages awaiting reuse.
+    pub fn num_free_pages(&self) -> usize {
+        self.free_pages.lock().len()
+    }
+
+    /// Records that `page_id` is in use, so it is never handed out by the allocator.
+    fn mark_allocated(&self, page_id: u32) {
+        self.next_page_id
+            .fetch_max(page_id.saturating_add(1), Ordering::SeqCst);
+    }
+
+    /// Enables or disables page checksums. When disabled, pages are written and read back
+    /// byte-for-byte, including the trailing bytes normally reserved for the checksum.
+    pub fn set_checksums_enabled(&self, enabled: bool) {
+        self.checksums_enabled.store(enabled, Ordering::SeqCst);
+    }
+
+    pub fn checksums_enabled(&self) -> bool {
+        self.checksums_enabled.load(Ordering::SeqCst)
+    }
+
+    /// Enables or disables sparse writes. When enabled, writing an all-zero page beyond the
+    /// end of the file is skipped: reads past the end already return zeroed pages, and the
+    /// page is materialized once non-zero data is written to it (or to a later page).
+    ///
+    /// A skipped page does not grow the file, so after reopening the database it counts
+    /// as unallocated again.
+    pub fn set_sparse_writes_enabled(&self, enabled: bool) {
+        self.sparse_writes_enabled.store(enabled, Ordering::SeqCst);
+    }
+
+    pub fn sparse_writes_enabled(&self) -> bool {
+        self.sparse_writes_enabled.load(Ordering::SeqCst)
+    }
+
+    pub fn set_retry_policy(&self, retry_policy: RetryPolicy) {
+        *self.retry_policy.lock() = retry_policy;
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        *self.retry_policy.lock()
+    }
+
+    /// Performs an I/O operation on a page, retrying it according to the [`RetryPolicy`] while
+    /// it fails with a transient error. The thread sleeps through the backoff before each
+    /// retry, as it already blocks on the operation itself.
+    fn with_retries<T>(
+        &self,
+        page_id: u32,
+        mut operation: impl FnMut() -> io::Result<T>,
+    ) -> io::Result<T> {
+        let policy = self.retry_policy();
+        let mut retry = 0;
+        loop {
+            match operation() {
+                Err(e) if is_transient(&e) && retry < policy.max_retries => {
+                    let backoff = policy.backoff(retry);
+                    warn!(
+                        "Transient I/O error on page {} ({}), retrying in {:?}",
+                        page_id, e, backoff
+                    );
+                    std::thread::sleep(backoff);
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Like [`DiskManager::with_retries`], for asynchronous I/O operations, which sleep through
+    /// the backoff without blocking the thread.
+    async fn with_retries_async<T, F>(
+        &self,
+        page_id: u32,
+        mut operation: impl FnMut() -> F,
+    ) -> io::Result<T>
+    where
+        F: Future<Output = io::Result<T>>,
+    {
+        let policy = self.retry_policy();
+        let mut retry = 0;
+        loop {
+            match operation().await {
+                Err(e) if is_transient(&e) && retry < policy.max_retries => {
+                    let backoff = policy.backoff(retry);
+                    warn!(
+                        "Transient I/O error on page {} ({}), retrying in {:?}",
+                        page_id, e, backoff
+                    );
+                    tokio::time::sleep(backoff).await;
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Whether writing `page_data` to `page_id` can be skipped under sparse writes. Pages
+    /// within the file must always be written, as they may hold non-zero data.
+    fn can_skip_write(&self, page_id: u32, page_data: &[u8]) -> bool {
+        self.sparse_writes_enabled()
+            && page_id >= self.num_pages()
+            && page_data.iter().all(|&b| b == 0)
+    }
+
+    /// Returns the number of bytes of a page available to callers: [`USABLE_PAGE_SIZE`] while
+    /// checksums are enabled, as the page header and checksum take up the rest of the page,
+    /// and the whole [`PAGE_SIZE`] otherwise.
+    pub fn payload_size(&self) -> usize {
+        if self.checksums_enabled() {
+            USABLE_PAGE_SIZE
+        } else {
+            PAGE_SIZE
+        }
+    }
+
+    /// Lays out the given payload as it is stored on disk. With checksums enabled, the page
+    /// starts with its header (recording the given LSN), followed by the payload padded to
+    /// [`USABLE_PAGE_SIZE`] bytes and the CRC32 of both. Without them, the payload is the page.
+    ///
+    /// Payloads longer than [`DiskManager::payload_size`] are rejected rather than truncated.
+    fn prepare_page(&self, page_data: &[u8], lsn: u64) -> Result<Vec<u8>> {
+        if page_data.len() > self.payload_size() {
+            error!(
+                "Page data of {} bytes exceeds the payload size of {} bytes",
+                page_data.len(),
+                self.payload_size()
+            );
+            return Err(DiskManagerError::PageSizeError.into());
+        }
+
+        if !self.checksums_enabled() {
+            return Ok(page_data.to_vec());
+        }
+
+        let mut page = vec![0; PAGE_SIZE];
+        let (header, rest) = page.split_at_mut(PAGE_HEADER_SIZE);
+        header[..4].copy_from_slice(&PAGE_MAGIC);
+        header[4] = DATA_PAGE;
+        header[8..].copy_from_slice(&lsn.to_be_bytes());
+        rest[..page_data.len()].copy_from_slice(page_data);
+
+        let checksummed = PAGE_SIZE - PAGE_CHECKSUM_SIZE;
+        let checksum = crc32fast::hash(&page[..checksummed]);
+        page[checksummed..].copy_from_slice(&checksum.to_be_bytes());
+        Ok(page)
+    }
+
+    /// Verifies the checksum and header of a full page read from disk, returning what it holds.
+    /// All-zero pages have never been written (e.g. reads past the end of the file) and are
+    /// reported as [`PageKind::Uninitialized`].
+    ///
+    /// Without checksums, pages carry no header, so only all-zero pages can be told apart.
+    fn verify_page(&self, page_id: u32, page: &[u8]) -> Result<PageKind> {
+        if page.iter().all(|&b| b == 0) {
+            return Ok(PageKind::Uninitialized);
+        }
+        if !self.checksums_enabled() {
+            return Ok(PageKind::Data { lsn: 0 });
+        }
+
+        let (checksummed, stored) = page.split_at(PAGE_SIZE - PAGE_CHECKSUM_SIZE);
+        let stored = u32::from_be_bytes(stored.try_into().expect("checksum is 4 bytes"));
+        if crc32fast::hash(checksummed) != stored {
+            error!("Checksum mismatch for page {}", page_id);
+            return Err(DiskManagerError::ChecksumMismatch { page_id }.into());
+        }
+
+        let header = &checksummed[..PAGE_HEADER_SIZE];
+        if header[..4] != PAGE_MAGIC || header[4] != DATA_PAGE {
+            error!("Invalid header for page {}", page_id);
+            return Err(DiskManagerError::InvalidPageHeader { page_id }.into());
+        }
+        let lsn = u64::from_be_bytes(header[8..].try_into().expect("LSN is 8 bytes"));
+        Ok(PageKind::Data { lsn })
+    }
+
+    /// Verifies a full page read from disk and copies its payload into `page_data`, zero-filling
+    /// whatever of the buffer lies beyond the payload.
+    fn unpack_page(&self, page_id: u32, page: &[u8], page_data: &mut [u8]) -> Result<PageKind> {
+        let kind = self.verify_page(page_id, page)?;
+        let payload = if self.checksums_enabled() {
+            &page[PAGE_HEADER_SIZE..PAGE_HEADER_SIZE + USABLE_PAGE_SIZE]
+        } else {
+            page
+        };
+
+        let len = payload.len().min(page_data.len());
+        page_data[..len].copy_from_slice(&payload[..len]);
+        page_data[len..].fill(0);
+        Ok(kind)
     }
 
     #[instrument(skip(self))]
@@ -112,15 +474,32 @@ impl DiskManager {
             "[DiskManager::shut_down] Shutting down storage manager for {}",
             self.db_file
         );
-        self.db_io.write().flush()?;
-        self.log_io.write().flush()?;
+        self.backend.flush()?;
         Ok(())
     }
 
+    /// Returns the number of pages read from disk so far.
+    pub fn num_reads(&self) -> u32 {
+        self.num_reads.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of pages written to disk so far.
+    pub fn num_writes(&self) -> u32 {
+        self.num_writes.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of times the database file has been flushed so far.
+    pub fn num_flushes(&self) -> u32 {
+        self.num_flushes.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of bytes written to disk so far.
+    pub fn num_bytes_written(&self) -> u64 {
+        self.num_bytes_written.load(Ordering::SeqCst)
+    }
+
     pub fn num_pages(&self) -> u32 {
-        let db_io = self.db_io.read();
-        let metadata = db_io.metadata().expect("Failed to read metadata");
-        let file_size = metadata.len();
+        let file_size = self.backend.len();
         debug!(
             "[DiskManager::num_pages] File size for {} is {} bytes",
             self.db_file, file_size
@@ -134,154 +513,139 @@ impl DiskManager {
         ((file_size + PAGE_SIZE as u64 - 1) / PAGE_SIZE as u64) as u32
     }
 
-    #[instrument(skip(self))]
     pub fn write_page(&self, page_id: u32, page_data: &[u8]) -> Result<()> {
+        self.write_page_with_lsn(page_id, page_data, 0)
+    }
+
+    /// Writes a page, recording `lsn` in its header (see [`PageKind::Data`]).
+    #[instrument(skip(self))]
+    pub fn write_page_with_lsn(&self, page_id: u32, page_data: &[u8], lsn: u64) -> Result<()> {
         debug!(
             "[DiskManager::write_page] Writing page {} with {} bytes",
             page_id,
             page_data.len()
         );
+        if self.can_skip_write(page_id, page_data) {
+            debug!(
+                "Skipping write of zero page {} beyond the end of the file",
+                page_id
+            );
+            self.mark_allocated(page_id);
+            return Ok(());
+        }
+        let page_data = self.prepare_page(page_data, lsn)?;
+        self.mark_allocated(page_id);
 
-        let mut db_io = self.db_io.write();
-        db_io
-            .seek(SeekFrom::Start((page_id as u64) * PAGE_SIZE as u64))
-            .map_err(|e| {
-                error!("Failed to seek to page {}: {}", page_id, e);
-                e
-            })?;
-
-        db_io.write_all(page_data).map_err(|e| {
-            error!("Failed to write page {}: {}", page_id, e);
-            e
-        })?;
-        db_io.flush().map_err(|e| {
-            error!("Failed to flush page {}: {}", page_id, e);
-            e
-        })?;
+        self.with_retries(page_id, || self.backend.write_page(page_id, &page_data))?;
         info!("Page {} written successfully", page_id);
 
         self.num_flushes.fetch_add(1, Ordering::SeqCst);
         self.num_writes.fetch_add(1, Ordering::SeqCst);
+        self.num_bytes_written
+            .fetch_add(page_data.len() as u64, Ordering::SeqCst);
 
         Ok(())
     }
 
-    #[instrument(skip(self))]
     pub async fn write_page_async(&self, page_id: u32, page_data: &[u8]) -> Result<()> {
-        if page_data.len() > PAGE_SIZE {
-            return Err(DiskManagerError::PageSizeError.into());
-        }
+        self.write_page_with_lsn_async(page_id, page_data, 0).await
+    }
 
+    /// Writes a page asynchronously, recording `lsn` in its header (see [`PageKind::Data`]).
+    #[instrument(skip(self))]
+    pub async fn write_page_with_lsn_async(
+        &self,
+        page_id: u32,
+        page_data: &[u8],
+        lsn: u64,
+    ) -> Result<()> {
         debug!(
             "[DiskManager::write_page_async] Writing page {} (async) with {} bytes",
             page_id,
             page_data.len()
         );
 
+        if self.can_skip_write(page_id, page_data) {
+            debug!(
+                "Skipping write of zero page {} beyond the end of the file",
+                page_id
+            );
+            self.mark_allocated(page_id);
+            return Ok(());
+        }
+
         // If data itself is less than PAGE_SIZE, we need to pad it with zeros
-        let mut page_data = page_data.to_vec();
+        let mut page_data = self.prepare_page(page_data, lsn)?;
+        self.mark_allocated(page_id);
         if page_data.len() < PAGE_SIZE {
             page_data.resize(PAGE_SIZE, 0);
         }
 
-        let mut db_io = AsyncFile::options()
-            .write(true)
-            .create(true)
-            .open(&self.db_file)
-            .await
-            .map_err(|e| {
-                error!("Failed to open db file {}: {}", self.db_file, e);
-                e
-            })?;
+        self.with_retries_async(page_id, || {
+            self.backend.write_page_async(page_id, &page_data)
+        })
+        .await?;
 
-        db_io
-            .seek(SeekFrom::Start((page_id as u64) * PAGE_SIZE as u64))
-            .await
-            .map_err(|e| {
-                error!("Failed to seek to page {}: {}", page_id, e);
-                e
-            })?;
-        db_io.write_all(page_data.as_slice()).await.map_err(|e| {
-            error!("Failed to write page {}: {}", page_id, e);
-            e
-        })?;
-        db_io.flush().await?; // Explicitly flush the data to disk
+        self.num_flushes.fetch_add(1, Ordering::SeqCst);
+        self.num_writes.fetch_add(1, Ordering::SeqCst);
+        self.num_bytes_written
+            .fetch_add(page_data.len() as u64, Ordering::SeqCst);
 
         info!("Page {} written successfully (async)", page_id);
         Ok(())
     }
 
+    /// Reads the payload of a page into `page_data`, returning whether the page was ever written
+    /// and with which LSN. Pages beyond the end of the file read as zeros, and so does any part
+    /// of the buffer beyond [`DiskManager::payload_size`].
     #[instrument(skip(self))]
-    pub fn read_page(&self, page_id: u32, page_data: &mut [u8]) -> Result<()> {
+    pub fn read_page(&self, page_id: u32, page_data: &mut [u8]) -> Result<PageKind> {
         debug!(
             "[DiskManager::read_page] Reading page {} with {} bytes",
             page_id,
             page_data.len()
         );
-        let mut db_io = File::options()
-            .read(true)
-            .open(&self.db_file)
-            .map_err(|e| {
-                error!("Failed to open db file {}: {}", self.db_file, e);
-                e
-            })?;
-
-        db_io
-            .seek(SeekFrom::Start((page_id as u64) * PAGE_SIZE as u64))
-            .map_err(|e| {
-                error!("Failed to seek to page {}: {}", page_id, e);
-                e
-            })?;
-        let read_size = db_io.read(page_data).map_err(|e| {
-            error!("Failed to read page {}: {}", page_id, e);
-            e
-        })?;
-
-        if read_size < page_data.len() {
-            page_data[read_size..].fill(0); // Fill the rest of the buffer with zeros
+        let mut page = vec![0; PAGE_SIZE];
+        let read_size =
+            self.with_retries(page_id, || self.backend.read_page(page_id, &mut page))?;
+
+        if read_size < page.len() {
+            page[read_size..].fill(0); // Fill the rest of the page with zeros
         }
+        self.num_reads.fetch_add(1, Ordering::SeqCst);
+        let kind = self.unpack_page(page_id, &page, page_data)?;
         info!("Page {} read successfully", page_id);
 
-        Ok(())
+        Ok(kind)
     }
 
     #[instrument(skip(self))]
-    pub async fn read_page_async(&self, page_id: u32, page_data: &mut [u8]) -> Result<()> {
+    pub async fn read_page_async(&self, page_id: u32, page_data: &mut [u8]) -> Result<PageKind> {
         debug!(
             "[DiskManager::read_page_async] Reading page {} (async) with {} bytes",
             page_id,
             page_data.len()
         );
 
-        let mut db_io = AsyncFile::open(&self.db_file).await.map_err(|e| {
-            error!("Failed to open db file {}: {}", self.db_file, e);
-            e
-        })?;
-
-        db_io
-            .seek(SeekFrom::Start((page_id as u64) * PAGE_SIZE as u64))
-            .await
-            .map_err(|e| {
-                error!("Failed to seek to page {}: {}", page_id, e);
-                e
-            })?;
-
-        db_io.read_exact(page_data).await.map_err(|e| {
-            error!("Failed to read page {}: {}", page_id, e);
-            e
-        })?;
+        // Each attempt reads into its own buffer, as a failed attempt may have filled part of it.
+        // Like the synchronous read, pages beyond the end of the file read as zeros
+        let page = self
+            .with_retries_async(page_id, || self.backend.read_page_async(page_id, PAGE_SIZE))
+            .await?;
+        self.num_reads.fetch_add(1, Ordering::SeqCst);
+        let kind = self.unpack_page(page_id, &page, page_data)?;
 
         info!("Page {} read successfully (async)", page_id);
-        Ok(())
+        Ok(kind)
     }
 
     #[instrument(skip(self))]
     pub fn write_data(&self, page_id: u32, data: &[u8]) -> anyhow::Result<()> {
-        if data.len() > PAGE_SIZE {
+        if data.len() > self.payload_size() {
             return Err(DiskManagerError::PageSizeError.into());
         }
 
-        let mut page_data = vec![0; PAGE_SIZE];
+        let mut page_data = vec![0; self.payload_size()];
         page_data[..data.len()].copy_from_slice(data);
         self.write_page(page_id, &page_data)?;
         Ok(())
@@ -289,11 +653,11 @@ impl DiskManager {
 
     #[instrument(skip(self))]
     pub async fn write_data_async(&self, page_id: u32, data: &[u8]) -> anyhow::Result<()> {
-        if data.len() > PAGE_SIZE {
+        if data.len() > self.payload_size() {
             return Err(DiskManagerError::PageSizeError.into());
         }
 
-        let mut page_data = vec![0; PAGE_SIZE];
+        let mut page_data = vec![0; self.payload_size()];
         page_data[..data.len()].copy_from_slice(data);
         self.write_page_async(page_id, &page_data).await?;
         Ok(())
@@ -301,55 +665,42 @@ impl DiskManager {
 
     #[instrument(skip(self))]
     pub fn read_data(&self, page_id: u32) -> anyhow::Result<Vec<u8>> {
-        let mut page_data = vec![0; PAGE_SIZE];
+        let mut page_data = vec![0; self.payload_size()];
         self.read_page(page_id, &mut page_data)?;
         Ok(page_data)
     }
 
     #[instrument(skip(self))]
     pub async fn read_data_async(&self, page_id: u32) -> anyhow::Result<Vec<u8>> {
-        let mut page_data = vec![0; PAGE_SIZE];
+        let mut page_data = vec![0; self.payload_size()];
         self.read_page_async(page_id, &mut page_data).await?;
         Ok(page_data)
     }
 
     #[instrument(skip(self))]
     pub fn write_log(&self, log_data: &[u8]) -> Result<()> {
-        let mut log_io = self.log_io.write();
-
         info!("Writing log ({} bytes)", log_data.len());
-        log_io.write_all(log_data).map_err(|e| {
-            error!("Failed to write log: {}", e);
-            e
-        })?;
-        log_io.flush().map_err(|e| {
-            error!("Failed to flush log: {}", e);
-            e
-        })?;
+        self.backend.append_log(log_data)?;
 
         Ok(())
     }
 
+    /// Returns the size of the log file in bytes.
+    pub fn log_size(&self) -> u64 {
+        self.backend.log_len()
+    }
+
+    /// Truncates the log file to `len` bytes, discarding everything after it.
     #[instrument(skip(self))]
-    pub fn read_log(&self, offset: u64, log_data: &mut [u8]) -> Result<()> {
-        let mut log_io = File::options()
-            .read(true)
-            .open(&self.log_file)
-            .map_err(|e| {
-                error!("Failed to open log file {}: {}", self.log_file, e);
-                e
-            })?;
+    pub fn truncate_log(&self, len: u64) -> Result<()> {
+        self.backend.truncate_log(len)?;
+        Ok(())
+    }
 
+    #[instrument(skip(self))]
+    pub fn read_log(&self, offset: u64, log_data: &mut [u8]) -> Result<()> {
         info!("Reading log at offset {}", offset);
-        log_io.seek(SeekFrom::Start(offset)).map_err(|e| {
-            error!("Failed to seek to offset {}: {}", offset, e);
-            e
-        })?;
-
-        let read_size = log_io.read(log_data).map_err(|e| {
-            error!("Failed to read log: {}", e);
-            e
-        })?;
+        let read_size = self.backend.read_log(offset, log_data)?;
 
         if read_size < log_data.len() {
             log_data[read_size..].fill(0); // Fill the rest of the buffer with zeros
@@ -368,6 +719,7 @@ mod single_thread_tests {
     #[test]
     fn read_write_page_test() {
         let (dm, _temp_dir) = setup_dm();
+        dm.set_checksums_enabled(false);
         let mut buf = [0u8; PAGE_SIZE];
         let mut data = [0u8; PAGE_SIZE];
         data[..14].copy_from_slice(b"A test string.");
@@ -385,6 +737,214 @@ mod single_thread_tests {
         assert_eq!(buf, data);
     }
 
+    #[test]
+    fn checksum_round_trip_test() {
+        let (dm, _temp_dir) = setup_dm();
+        let data = b"A checksummed string.";
+        dm.write_page(0, data).expect("Failed to write page");
+
+        let mut buf = [0u8; PAGE_SIZE];
+        dm.read_page(0, &mut buf).expect("Failed to read page");
+        assert_eq!(&buf[..data.len()], data);
+        assert!(buf[data.len()..USABLE_PAGE_SIZE].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn checksum_mismatch_test() {
+        let (dm, _temp_dir) = setup_dm();
+        dm.write_page(1, b"Soon to be corrupted.")
+            .expect("Failed to write page");
+
+        // Flip a byte of the payload directly in the database file
+        {
+            let mut file = File::options()
+                .read(true)
+                .write(true)
+                .open(&dm.db_file)
+                .unwrap();
+            let offset = (PAGE_SIZE + PAGE_HEADER_SIZE) as u64 + 3;
+            let mut byte = [0u8; 1];
+            file.seek(SeekFrom::Start(offset)).unwrap();
+            file.read_exact(&mut byte).unwrap();
+            file.seek(SeekFrom::Start(offset)).unwrap();
+            file.write_all(&[!byte[0]]).unwrap();
+        }
+
+        let mut buf = [0u8; PAGE_SIZE];
+        let err = dm
+            .read_page(1, &mut buf)
+            .expect_err("Expected checksum error");
+        assert!(matches!(
+            err.downcast_ref::<DiskManagerError>(),
+            Some(DiskManagerError::ChecksumMismatch { page_id: 1 })
+        ));
+
+        // Unwritten pages read back as zeros and are not treated as corrupt
+        dm.read_page(7, &mut buf)
+            .expect("Failed to read empty page");
+    }
+
+    #[test]
+    fn page_header_test() {
+        let (dm, _temp_dir) = setup_dm();
+        let mut buf = [0u8; PAGE_SIZE];
+
+        // A never-written page reads as zeros, but is reported as such
+        assert_eq!(dm.read_page(3, &mut buf).unwrap(), PageKind::Uninitialized);
+        assert_eq!(buf, [0u8; PAGE_SIZE]);
+
+        // Whereas a page written all zeros holds data
+        dm.write_page(3, &[0u8; USABLE_PAGE_SIZE]).unwrap();
+        assert_eq!(
+            dm.read_page(3, &mut buf).unwrap(),
+            PageKind::Data { lsn: 0 }
+        );
+        assert!(buf[..USABLE_PAGE_SIZE].iter().all(|&b| b == 0));
+        // Pages before it are holes in the file
+        assert_eq!(dm.read_page(2, &mut buf).unwrap(), PageKind::Uninitialized);
+
+        dm.write_page_with_lsn(3, b"Logged.", 42).unwrap();
+        assert_eq!(
+            dm.read_page(3, &mut buf).unwrap(),
+            PageKind::Data { lsn: 42 }
+        );
+        assert_eq!(&buf[..7], b"Logged.");
+
+        // The header leads the page on disk, followed by the payload
+        dm.set_checksums_enabled(false);
+        dm.read_page(3, &mut buf).unwrap();
+        assert_eq!(&buf[..4], b"R2DB");
+        assert_eq!(buf[8..PAGE_HEADER_SIZE], 42u64.to_be_bytes());
+        assert_eq!(&buf[PAGE_HEADER_SIZE..PAGE_HEADER_SIZE + 7], b"Logged.");
+    }
+
+    #[tokio::test]
+    async fn async_page_header_test() {
+        let (dm, _temp_dir) = setup_dm();
+        dm.write_page_with_lsn_async(0, b"Logged.", 7)
+            .await
+            .unwrap();
+
+        let mut buf = vec![0u8; USABLE_PAGE_SIZE];
+        assert_eq!(
+            dm.read_page_async(0, &mut buf).await.unwrap(),
+            PageKind::Data { lsn: 7 }
+        );
+        assert_eq!(&buf[..7], b"Logged.");
+        assert_eq!(
+            dm.read_page_async(1, &mut buf).await.unwrap(),
+            PageKind::Uninitialized
+        );
+    }
+
+    #[tokio::test]
+    async fn oversized_page_is_rejected_test() {
+        let (dm, _temp_dir) = setup_dm();
+        dm.write_page(0, b"Intact.").unwrap();
+
+        // A full page would leave no room for the header and checksum
+        for result in [
+            dm.write_page(0, &[1u8; PAGE_SIZE]),
+            dm.write_page_async(0, &[1u8; PAGE_SIZE]).await,
+            dm.write_data(0, &[1u8; USABLE_PAGE_SIZE + 1]),
+        ] {
+            assert!(matches!(
+                result.unwrap_err().downcast_ref::<DiskManagerError>(),
+                Some(DiskManagerError::PageSizeError)
+            ));
+        }
+        assert_eq!(&dm.read_data(0).unwrap()[..7], b"Intact.");
+
+        // Without checksums, the whole page is available
+        dm.set_checksums_enabled(false);
+        dm.write_page(1, &[1u8; PAGE_SIZE]).unwrap();
+    }
+
+    #[test]
+    fn missing_page_header_test() {
+        let (dm, _temp_dir) = setup_dm();
+
+        // A page whose checksum is valid, but whose header was overwritten
+        let mut page = vec![0u8; PAGE_SIZE];
+        page[..4].copy_from_slice(b"XXXX");
+        page[PAGE_HEADER_SIZE] = 1;
+        let checksum = crc32fast::hash(&page[..PAGE_SIZE - PAGE_CHECKSUM_SIZE]);
+        page[PAGE_SIZE - PAGE_CHECKSUM_SIZE..].copy_from_slice(&checksum.to_be_bytes());
+        dm.set_checksums_enabled(false);
+        dm.write_page(0, &page).unwrap();
+        dm.set_checksums_enabled(true);
+
+        let err = dm.read_data(0).expect_err("Expected header error");
+        assert!(matches!(
+            err.downcast_ref::<DiskManagerError>(),
+            Some(DiskManagerError::InvalidPageHeader { page_id: 0 })
+        ));
+    }
+
+    #[test]
+    fn allocate_reuses_deallocated_pages_test() {
+        let (dm, _temp_dir) = setup_dm();
+        let allocated = (0..5)
+            .map(|_| dm.allocate_page().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(allocated, vec![0, 1, 2, 3, 4]);
+
+        dm.deallocate_page(3).unwrap();
+        dm.deallocate_page(1).unwrap();
+        assert_eq!(dm.num_free_pages(), 2);
+
+        assert_eq!(dm.allocate_page().unwrap(), 1);
+        assert_eq!(dm.allocate_page().unwrap(), 3);
+        assert_eq!(dm.allocate_page().unwrap(), 5);
+        assert_eq!(dm.num_free_pages(), 0);
+    }
+
+    #[test]
+    fn free_list_survives_reopen_test() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let db_path = db_path.to_str().unwrap();
+
+        {
+            let dm = DiskManager::new(db_path).unwrap();
+            for page_id in 0..5 {
+                dm.write_data(page_id, b"page").unwrap();
+            }
+            dm.deallocate_page(2).unwrap();
+            dm.deallocate_page(4).unwrap();
+            dm.shut_down().unwrap();
+        }
+
+        let dm = DiskManager::new(db_path).unwrap();
+        assert_eq!(dm.num_free_pages(), 2);
+        assert_eq!(dm.allocate_page().unwrap(), 2);
+        assert_eq!(dm.allocate_page().unwrap(), 4);
+        assert_eq!(dm.allocate_page().unwrap(), 5);
+    }
+
+    #[tokio::test]
+    async fn truncate_to_test() {
+        let (dm, _temp_dir) = setup_dm();
+        for page_id in 0..10 {
+            dm.write_data(page_id, &[page_id as u8 + 1; 16]).unwrap();
+        }
+        dm.deallocate_page(8).unwrap();
+        assert_eq!(dm.num_pages(), 10);
+
+        dm.truncate_to(5).unwrap();
+        assert_eq!(dm.num_pages(), 5);
+        assert_eq!(dm.num_free_pages(), 0);
+        assert_eq!(dm.read_data(4).unwrap()[..16], [5; 16]);
+
+        // Reads beyond the new end return zeros, both synchronously and asynchronously
+        assert!(dm.read_data(7).unwrap().iter().all(|&b| b == 0));
+        assert!(dm.read_data_async(7).await.unwrap().iter().all(|&b| b == 0));
+
+        // The allocator resumes right after the retained pages, and growing is rejected
+        assert_eq!(dm.allocate_page().unwrap(), 5);
+        assert!(dm.truncate_to(6).is_err());
+    }
+
     #[test]
     fn read_write_log_test() {
         let (dm, _temp_dir) = setup_dm();
@@ -398,6 +958,54 @@ mod single_thread_tests {
         assert_eq!(buf[..log_string.len()], data[..log_string.len()]);
     }
 
+    #[tokio::test]
+    async fn in_memory_read_write_log_test() {
+        let dm = DiskManager::new(IN_MEMORY_PATH).unwrap();
+        assert_eq!(dm.num_pages(), 0);
+
+        dm.write_data(0, b"page zero").unwrap();
+        dm.write_data_async(3, b"page three").await.unwrap();
+        assert_eq!(dm.num_pages(), 4);
+        assert_eq!(&dm.read_data(0).unwrap()[..9], b"page zero");
+        assert_eq!(&dm.read_data_async(3).await.unwrap()[..10], b"page three");
+        let mut buf = [0u8; PAGE_SIZE];
+        assert_eq!(dm.read_page(2, &mut buf).unwrap(), PageKind::Uninitialized);
+        assert_eq!(dm.read_page(7, &mut buf).unwrap(), PageKind::Uninitialized);
+
+        dm.write_log(b"first ").unwrap();
+        dm.write_log(b"second").unwrap();
+        assert_eq!(dm.log_size(), 12);
+        let mut log = [0u8; 16];
+        dm.read_log(0, &mut log).unwrap();
+        assert_eq!(&log, b"first second\0\0\0\0");
+        dm.truncate_log(5).unwrap();
+        assert_eq!(dm.log_size(), 5);
+
+        dm.deallocate_page(0).unwrap();
+        assert_eq!(dm.allocate_page().unwrap(), 0);
+        dm.truncate_to(1).unwrap();
+        assert_eq!(dm.num_pages(), 1);
+        dm.shut_down().unwrap();
+
+        // Nothing was written to a file named after the path
+        assert!(!std::path::Path::new(IN_MEMORY_PATH).exists());
+    }
+
+    #[test]
+    fn in_memory_managers_are_independent_test() {
+        let first = DiskManager::new(IN_MEMORY_PATH).unwrap();
+        let second = DiskManager::new(IN_MEMORY_PATH).unwrap();
+
+        first.write_data(0, b"first").unwrap();
+        first.write_log(b"first").unwrap();
+        assert_eq!(second.num_pages(), 0);
+        assert_eq!(second.log_size(), 0);
+        assert!(second.read_data(0).unwrap().iter().all(|&b| b == 0));
+
+        second.write_data(0, b"second").unwrap();
+        assert_eq!(&first.read_data(0).unwrap()[..5], b"first");
+    }
+
     #[test]
     fn throw_bad_file_test() {
         let result = DiskManager::new("dev/null\\/foo/bar/baz/test.db");
@@ -427,6 +1035,7 @@ mod concurrent_tests {
     #[test]
     fn concurrent_read_write_test() {
         let (dm, _temp_dir) = setup_dm();
+        dm.set_checksums_enabled(false);
         let barrier = Arc::new(Barrier::new(NUM_THREADS));
 
         let mut handles = vec![];
@@ -456,10 +1065,12 @@ mod concurrent_tests {
 #[cfg(test)]
 mod async_tests {
     use super::*;
+    use crate::disk::backend::FaultyBackend;
 
     #[tokio::test]
     async fn async_read_write_page_test() {
         let (dm, _temp_dir) = setup_dm();
+        dm.set_checksums_enabled(false);
         let data = vec![1u8; PAGE_SIZE];
         let page_id: u32 = 0;
 
@@ -472,6 +1083,111 @@ mod async_tests {
             .expect("Failed to read page async");
         assert_eq!(buf, data, "Async read data does not match written data");
     }
+
+    #[tokio::test]
+    async fn test_sparse_writes_skip_zero_pages() {
+        let (dm, _temp_dir) = setup_dm();
+        dm.set_sparse_writes_enabled(true);
+        let file_len = || std::fs::metadata(&dm.db_file).unwrap().len();
+
+        dm.write_page_async(0, &vec![0u8; USABLE_PAGE_SIZE])
+            .await
+            .unwrap();
+        assert_eq!(file_len(), 0, "Zero page should not be written");
+
+        // Writing real data materializes the page, leaving the skipped page as a hole
+        let data = vec![7u8; USABLE_PAGE_SIZE];
+        dm.write_page_async(1, &data).await.unwrap();
+        assert_eq!(file_len(), 2 * PAGE_SIZE as u64);
+        assert_eq!(
+            dm.read_data_async(1).await.unwrap()[..USABLE_PAGE_SIZE],
+            data[..]
+        );
+        assert_eq!(
+            dm.read_data_async(0).await.unwrap(),
+            vec![0u8; USABLE_PAGE_SIZE]
+        );
+
+        // Zero pages within the file still overwrite what is there
+        dm.write_page_async(1, &vec![0u8; USABLE_PAGE_SIZE])
+            .await
+            .unwrap();
+        let page = dm.read_data_async(1).await.unwrap();
+        assert!(page[..USABLE_PAGE_SIZE].iter().all(|&b| b == 0));
+    }
+
+    /// Returns a manager of an in-memory database whose page I/O fails with the errors
+    /// injected into the returned backend.
+    fn faulty_dm() -> DiskManager {
+        let backend = Backend::Faulty(FaultyBackend::new(Backend::Memory(Default::default())));
+        let dm = DiskManager::with_backend(IN_MEMORY_PATH, backend).unwrap();
+        dm.set_retry_policy(RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(1),
+        });
+        dm
+    }
+
+    fn faults(dm: &DiskManager) -> &FaultyBackend {
+        match &dm.backend {
+            Backend::Faulty(faulty) => faulty,
+            backend => panic!("Expected a faulty backend, got {:?}", backend),
+        }
+    }
+
+    fn io_error_kind(error: &anyhow::Error) -> Option<ErrorKind> {
+        error.downcast_ref::<io::Error>().map(io::Error::kind)
+    }
+
+    #[test]
+    fn test_transient_io_errors_are_retried() {
+        let dm = faulty_dm();
+        let data = vec![3u8; USABLE_PAGE_SIZE];
+
+        faults(&dm).inject([ErrorKind::Interrupted, ErrorKind::WouldBlock]);
+        dm.write_page(0, &data).unwrap();
+        faults(&dm).inject([ErrorKind::Interrupted]);
+        assert_eq!(dm.read_data(0).unwrap()[..USABLE_PAGE_SIZE], data[..]);
+        assert_eq!(faults(&dm).pending_faults(), 0);
+
+        // An operation failing more often than the policy allows gives up with the error,
+        // having backed off for 1, 2 and 4 ms before its retries
+        faults(&dm).inject([ErrorKind::Interrupted; 4]);
+        let start = std::time::Instant::now();
+        let error = dm.write_page(0, &data).unwrap_err();
+        assert_eq!(io_error_kind(&error), Some(ErrorKind::Interrupted));
+        assert!(start.elapsed() >= Duration::from_millis(7));
+    }
+
+    #[test]
+    fn test_permanent_io_errors_fail_immediately() {
+        let dm = faulty_dm();
+
+        faults(&dm).inject([ErrorKind::PermissionDenied, ErrorKind::Interrupted]);
+        let error = dm.write_page(0, &[1; 16]).unwrap_err();
+        assert_eq!(io_error_kind(&error), Some(ErrorKind::PermissionDenied));
+        // The operation was attempted only once
+        assert_eq!(faults(&dm).pending_faults(), 1);
+        assert_eq!(dm.num_writes(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_transient_io_errors_are_retried_async() {
+        let dm = faulty_dm();
+        let data = vec![5u8; USABLE_PAGE_SIZE];
+
+        faults(&dm).inject([ErrorKind::WouldBlock]);
+        dm.write_page_async(0, &data).await.unwrap();
+        faults(&dm).inject([ErrorKind::Interrupted, ErrorKind::Interrupted]);
+        assert_eq!(
+            dm.read_data_async(0).await.unwrap()[..USABLE_PAGE_SIZE],
+            data[..]
+        );
+
+        faults(&dm).inject([ErrorKind::NotFound]);
+        let error = dm.read_data_async(0).await.unwrap_err();
+        assert_eq!(io_error_kind(&error), Some(ErrorKind::NotFound));
+    }
 }
 
 #[cfg(test)]
@@ -1,7 +1,11 @@
+mod backend;
 mod manager;
 mod scheduler;
 
-pub use manager::DiskManager;
+pub use backend::IN_MEMORY_PATH;
+pub use manager::{
+    is_transient, DiskManager, DiskManagerError, DiskManagerRef, PageKind, RetryPolicy,
+};
 pub use scheduler::*;
 
 use std::sync::Arc;
@@ -1,14 +1,18 @@
 #![allow(dead_code)]
 
-use super::DiskManager;
+use super::{DiskManager, PageKind};
 #[allow(unused_imports)]
 use crate::disk::setup_dm;
+use crate::wal::Lsn;
 use anyhow::Result;
-use common::{PageId, PAGE_SIZE};
+use common::PageId;
 use getset::{Getters, Setters};
 use parking_lot::Mutex;
 use std::cmp::Ordering;
-use std::sync::Arc;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
 use thiserror::Error;
 use tokio::sync::{mpsc, oneshot};
@@ -26,6 +30,12 @@ pub enum DiskSchedulerError {
 
     #[error("Disk Manager Error: {0}")]
     DiskManagerError(String),
+
+    #[error("Failed to read page {0}")]
+    ReadError(u32),
+
+    #[error("Invalid disk scheduler configuration: {0}")]
+    InvalidConfig(&'static str),
     // ... TODO: future other error types ...
 }
 
@@ -42,10 +52,21 @@ pub struct DiskRequest {
     /// The callback to be invoked when the request is complete
     /// (i.e. the data has been written or read)
     completion_signal: Option<oneshot::Sender<()>>,
-    /// Channel to send back read data
-    read_data_sender: Option<mpsc::Sender<Vec<u8>>>,
+    /// Channel to send back read data, along with what the header of the page says it holds
+    read_data_sender: Option<mpsc::Sender<(Vec<u8>, PageKind)>>,
     /// The priority of the request
     priority: u8, // Lower number means higher priority
+    /// The order in which the request was scheduled, which breaks ties between requests of
+    /// the same priority
+    #[builder(default)]
+    sequence: u64,
+    /// Further callers waiting for the request, e.g. those of the buffered writes of the page
+    /// it superseded. They are signalled along with the completion signal.
+    #[builder(default)]
+    completion_waiters: Vec<oneshot::Sender<()>>,
+    /// The LSN to record in the header of the written page (see [`PageKind::Data`])
+    #[builder(default)]
+    lsn: Lsn,
 }
 
 impl Clone for DiskRequest {
@@ -57,17 +78,28 @@ impl Clone for DiskRequest {
             .completion_signal(None) // NOTE: Reset the completion signal (if any)
             .read_data_sender(self.read_data_sender.clone())
             .priority(self.priority)
+            .sequence(self.sequence)
+            .lsn(self.lsn)
             .build()
     }
 }
 
 impl DiskRequest {
+    /// The priority of reads, which callers are blocked on.
+    pub const READ_PRIORITY: u8 = 0;
+
+    /// The priority of writes that callers wait for, e.g. flushes of dirty pages.
+    pub const WRITE_PRIORITY: u8 = 1;
+
+    /// The priority of buffered writes, which no caller is waiting for.
+    pub const BUFFERED_WRITE_PRIORITY: u8 = 2;
+
     pub fn new(
         is_write: bool,
         data: Vec<u8>,
         page_id: u32,
         completion_signal: Option<oneshot::Sender<()>>,
-        read_data_sender: Option<mpsc::Sender<Vec<u8>>>,
+        read_data_sender: Option<mpsc::Sender<(Vec<u8>, PageKind)>>,
         priority: u8,
     ) -> Self {
         DiskRequest::builder()
@@ -81,15 +113,41 @@ impl DiskRequest {
     }
 
     pub async fn complete(&mut self) {
-        if let Some(sender) = self.completion_signal.take() {
+        let waiters = self.completion_signal.take().into_iter();
+        for sender in waiters.chain(self.completion_waiters.drain(..)) {
             let _ = sender.send(()); // Ignoring the result as receiver may be dropped
         }
     }
+
+    /// Replaces the data of this write with that of a later write of the same page, taking over
+    /// the callers waiting for it (see [`DiskRequest::adopt_waiters`]).
+    pub fn supersede_with(&mut self, mut newer: DiskRequest) {
+        debug_assert_eq!(self.page_id, newer.page_id);
+        self.data = std::mem::take(&mut newer.data);
+        self.lsn = newer.lsn;
+        self.adopt_waiters(&mut newer);
+    }
+
+    /// Takes over the callers waiting for another request of the same page, which is dropped
+    /// in favor of this one. They are signalled along with this request's completion signal.
+    pub fn adopt_waiters(&mut self, other: &mut DiskRequest) {
+        debug_assert_eq!(self.page_id, other.page_id);
+        self.completion_waiters
+            .extend(other.completion_signal.take());
+        self.completion_waiters
+            .append(&mut other.completion_waiters);
+    }
 }
 
+/// Requests are ordered by urgency: a request is greater than another if it has a lower
+/// priority number or, at the same priority, was scheduled earlier. A [`BinaryHeap`] of
+/// requests therefore pops the request to service next.
 impl Ord for DiskRequest {
     fn cmp(&self, other: &Self) -> Ordering {
-        other.priority.cmp(&self.priority)
+        other
+            .priority
+            .cmp(&self.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
     }
 }
 
@@ -101,7 +159,7 @@ impl PartialOrd for DiskRequest {
 
 impl PartialEq for DiskRequest {
     fn eq(&self, other: &Self) -> bool {
-        self.priority == other.priority
+        self.priority == other.priority && self.sequence == other.sequence
     }
 }
 
@@ -121,6 +179,24 @@ mod tests {
 
         assert!(rx.await.is_ok(), "Completion signal should have been sent");
     }
+
+    #[test]
+    fn test_heap_pops_lowest_priority_number_first() {
+        let request = |page_id, priority, sequence| {
+            let mut request = DiskRequest::new(false, vec![], page_id, None, None, priority);
+            request.sequence = sequence;
+            request
+        };
+
+        let mut heap = BinaryHeap::new();
+        heap.push(request(0, DiskRequest::BUFFERED_WRITE_PRIORITY, 0));
+        heap.push(request(1, DiskRequest::WRITE_PRIORITY, 1));
+        heap.push(request(2, DiskRequest::READ_PRIORITY, 2));
+        heap.push(request(3, DiskRequest::WRITE_PRIORITY, 3));
+
+        let order: Vec<u32> = std::iter::from_fn(|| heap.pop().map(|r| r.page_id)).collect();
+        assert_eq!(order, [2, 1, 3, 0]);
+    }
 }
 
 pub enum WriteStrategy {
@@ -128,119 +204,358 @@ pub enum WriteStrategy {
     Buffered,
 }
 
+/// The requests waiting for the worker, which pops the most urgent one first (see the ordering
+/// of [`DiskRequest`]). Requests are indexed by page as well, so that the earlier requests a
+/// request conflicts with are found and taken out without scanning the whole queue.
+#[derive(Debug, Default)]
+struct RequestQueue {
+    /// The queued requests, by sequence number
+    requests: HashMap<u64, DiskRequest>,
+    /// The (priority, sequence) of the queued requests, most urgent first. Requests taken out
+    /// of turn leave their entry behind, which is skipped once it is popped.
+    urgency: BinaryHeap<Reverse<(u8, u64)>>,
+    /// The sequence numbers of the queued requests of each page
+    pages: HashMap<u32, BTreeSet<u64>>,
+    /// The sequence numbers of the queued writes of each page
+    writes: HashMap<u32, BTreeSet<u64>>,
+}
+
+impl RequestQueue {
+    fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    fn push(&mut self, request: DiskRequest) {
+        let (page_id, sequence) = (request.page_id, request.sequence);
+        self.urgency.push(Reverse((request.priority, sequence)));
+        self.pages.entry(page_id).or_default().insert(sequence);
+        if request.is_write {
+            self.writes.entry(page_id).or_default().insert(sequence);
+        }
+        self.requests.insert(sequence, request);
+    }
+
+    /// Removes the most urgent request.
+    fn pop(&mut self) -> Option<DiskRequest> {
+        while let Some(Reverse((_, sequence))) = self.urgency.pop() {
+            if let Some(request) = self.take(sequence) {
+                return Some(request);
+            }
+        }
+        None
+    }
+
+    /// Removes the request with the given sequence number, if it is queued.
+    fn take(&mut self, sequence: u64) -> Option<DiskRequest> {
+        let request = self.requests.remove(&sequence)?;
+        for index in [&mut self.pages, &mut self.writes] {
+            if let Some(sequences) = index.get_mut(&request.page_id) {
+                sequences.remove(&sequence);
+                if sequences.is_empty() {
+                    index.remove(&request.page_id);
+                }
+            }
+        }
+        Some(request)
+    }
+
+    /// Removes the earliest request that must be serviced before `request`: one of the same
+    /// page, scheduled before it, of which at least one of the two is a write. A read therefore
+    /// never sees the page from before an earlier write, nor a write lands before an earlier
+    /// read, while reads of a page may still be reordered among themselves.
+    fn take_earlier_conflict(&mut self, request: &DiskRequest) -> Option<DiskRequest> {
+        let conflicts = if request.is_write {
+            &self.pages
+        } else {
+            &self.writes
+        };
+        let sequence = *conflicts.get(&request.page_id)?.first()?;
+        if sequence > request.sequence {
+            return None;
+        }
+        self.take(sequence)
+    }
+}
+
+/// The callers waiting for the result of a read that another caller is performing.
+type ReadWaiters = Arc<Mutex<Vec<oneshot::Sender<Result<(Vec<u8>, PageKind), String>>>>>;
+
+/// Registration of a read that is being performed, which later reads of the same page attach
+/// to. The read is unregistered once the reader finishes or is dropped, so that subsequent
+/// reads of the page go to disk again.
+struct InFlightRead<'a> {
+    in_flight_reads: &'a Mutex<HashMap<u32, ReadWaiters>>,
+    page_id: u32,
+    waiters: ReadWaiters,
+}
+
+impl InFlightRead<'_> {
+    fn unregister(&self) {
+        let mut in_flight_reads = self.in_flight_reads.lock();
+        // The read may have been detached by a write and replaced by a newer one
+        if in_flight_reads
+            .get(&self.page_id)
+            .is_some_and(|waiters| Arc::ptr_eq(waiters, &self.waiters))
+        {
+            in_flight_reads.remove(&self.page_id);
+        }
+    }
+
+    /// Unregisters the read, returning the callers that attached to it.
+    fn finish(self) -> Vec<oneshot::Sender<Result<(Vec<u8>, PageKind), String>>> {
+        self.unregister();
+        let waiters = std::mem::take(&mut *self.waiters.lock());
+        waiters
+    }
+}
+
+impl Drop for InFlightRead<'_> {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
+
+/// Tuning knobs of a [`DiskScheduler`]. The defaults suit a mixed workload: latency-sensitive
+/// workloads may want a shorter flush interval, throughput-oriented ones a larger write buffer.
+#[derive(Debug, Clone, PartialEq, Eq, TypedBuilder)]
+pub struct DiskSchedulerConfig {
+    /// The interval at which the write buffer is flushed to disk (non-zero).
+    #[builder(default = Duration::from_secs(DiskScheduler::FLUSH_INTERVAL))]
+    pub flush_interval: Duration,
+    /// The maximum number of write requests that can be buffered before the write buffer is
+    /// flushed to disk.
+    #[builder(default = DiskScheduler::MAX_BUFFER_SIZE)]
+    pub max_buffer_size: usize,
+    /// The maximum number of pending requests that can be queued up before the scheduler
+    /// blocks the caller (at least 1).
+    #[builder(default = DiskScheduler::MAX_PENDING_REQUESTS)]
+    pub max_pending_requests: usize,
+}
+
+impl Default for DiskSchedulerConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl DiskSchedulerConfig {
+    /// Checks that the scheduler can run with this configuration: the request queue needs room
+    /// for at least one request, and the flush task a non-zero interval to wait between flushes.
+    pub fn validate(&self) -> Result<(), DiskSchedulerError> {
+        if self.max_pending_requests == 0 {
+            return Err(DiskSchedulerError::InvalidConfig(
+                "max_pending_requests must be at least 1",
+            ));
+        }
+        if self.flush_interval.is_zero() {
+            return Err(DiskSchedulerError::InvalidConfig(
+                "flush_interval must be non-zero",
+            ));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct DiskScheduler {
     disk_manager: Arc<DiskManager>,
     sender: mpsc::Sender<DiskRequest>,
+    /// The sequence number of the next request scheduled
+    next_sequence: AtomicU64,
     write_buffer: Arc<Mutex<Vec<DiskRequest>>>,
+    /// Held while buffered writes are written to disk, so that writes superseding them can
+    /// wait for them to land first
+    flush_lock: Arc<tokio::sync::Mutex<()>>,
     last_flush: Mutex<Instant>,
-    flush_interval: Duration,
+    config: DiskSchedulerConfig,
+    /// Reads that are being performed, by page id
+    in_flight_reads: Mutex<HashMap<u32, ReadWaiters>>,
+    /// The number of batch reads scheduled (used for statistics)
+    num_batch_reads: AtomicU64,
 }
 
 impl DiskScheduler {
-    /// The maximum number of pending requests that can be queued up
+    /// The default maximum number of pending requests that can be queued up
     /// before the scheduler blocks the caller.
     const MAX_PENDING_REQUESTS: usize = 32;
 
-    /// The interval at which the write buffer is flushed to disk.
+    /// The default interval at which the write buffer is flushed to disk.
     const FLUSH_INTERVAL: u64 = 5; // seconds
 
-    /// The maximum number of write requests that can be buffered
+    /// The default maximum number of write requests that can be buffered
     /// before the write buffer is flushed to disk.
     const MAX_BUFFER_SIZE: usize = 32;
 
-    #[instrument(skip(disk_manager))]
+    /// The number of requests the worker services, or a flush schedules, in a row before
+    /// yielding to the runtime, so that a burst of requests doesn't starve other tasks.
+    const YIELD_INTERVAL: usize = 8;
+
+    /// Creates a scheduler with the default [`DiskSchedulerConfig`].
     pub fn new(disk_manager: Arc<DiskManager>) -> Arc<Self> {
-        let (sender, mut receiver) = mpsc::channel::<DiskRequest>(Self::MAX_PENDING_REQUESTS);
+        Self::new_with_config(disk_manager, DiskSchedulerConfig::default())
+            .expect("The default configuration is valid")
+    }
+
+    /// Creates a scheduler with the given configuration.
+    ///
+    /// Fails with [`DiskSchedulerError::InvalidConfig`] if the configuration doesn't pass
+    /// [`DiskSchedulerConfig::validate`].
+    #[instrument(skip(disk_manager))]
+    pub fn new_with_config(
+        disk_manager: Arc<DiskManager>,
+        config: DiskSchedulerConfig,
+    ) -> Result<Arc<Self>, DiskSchedulerError> {
+        config.validate()?;
+        let (sender, mut receiver) = mpsc::channel::<DiskRequest>(config.max_pending_requests);
         let disk_manager_clone = disk_manager.clone();
 
         debug!("Spawning DiskScheduler worker task");
 
         tokio::spawn(async move {
             trace!("DiskScheduler worker started");
-            while let Some(mut request) = receiver.recv().await {
-                trace!(
-                    page_id = request.page_id,
-                    is_write = request.is_write,
-                    "Processing disk request"
-                );
-                let disk_manager_clone = disk_manager_clone.clone();
-                task::spawn(async move {
-                    if request.is_write {
-                        trace!(page_id = request.page_id, "Writing to disk");
-                        if let Err(e) = disk_manager_clone
-                            .write_page_async(request.page_id, &request.data)
-                            .await
-                        {
-                            error!(error = %e, "Failed to write to disk");
-                        }
-                    } else {
-                        trace!(page_id = request.page_id, "Reading from disk");
-                        let mut read_data = vec![0; PAGE_SIZE];
-                        if let Err(e) = disk_manager_clone
-                            .read_page_async(request.page_id, &mut read_data)
-                            .await
-                        {
-                            error!(error = %e, "Failed to read from disk");
-                        }
-
-                        if let Some(sender) = request.read_data_sender {
-                            trace!("Sending back read data");
-                            let _ = sender.send(read_data).await;
-                        }
-                    }
-                    if let Some(sender) = request.completion_signal.take() {
-                        trace!("Sending completion signal");
-                        let _ = sender.send(());
+            // Requests are serviced one at a time, most urgent first, except that a request
+            // never overtakes a conflicting request of the same page. Whatever arrived while
+            // the previous request was serviced competes for the next turn.
+            let mut queue = RequestQueue::default();
+            let mut serviced = 0;
+            loop {
+                if queue.is_empty() {
+                    match receiver.recv().await {
+                        Some(request) => queue.push(request),
+                        None => break,
                     }
-                });
+                }
+                while let Ok(request) = receiver.try_recv() {
+                    queue.push(request);
+                }
+
+                let request =
+                    Self::next_request(&mut queue).expect("Queue holds at least one request");
+                Self::process(&disk_manager_clone, request).await;
+
+                Self::yield_periodically(&mut serviced).await;
             }
             trace!("DiskScheduler worker loop ended");
         });
 
         // Initialize the write buffer and flush interval
-        let write_buffer = Arc::new(Mutex::new(Vec::with_capacity(Self::MAX_BUFFER_SIZE)));
-        let flush_interval = Duration::from_secs(Self::FLUSH_INTERVAL);
+        let write_buffer = Arc::new(Mutex::new(Vec::with_capacity(config.max_buffer_size)));
         let last_flush = Mutex::new(Instant::now());
 
         let scheduler = Arc::new(Self {
             disk_manager,
+            next_sequence: AtomicU64::new(0),
             sender,
             write_buffer,
-            flush_interval,
+            flush_lock: Arc::default(),
             last_flush,
+            config,
+            in_flight_reads: Mutex::new(HashMap::new()),
+            num_batch_reads: AtomicU64::new(0),
         });
 
         // Start the flush task
         scheduler.start_flush_task();
 
-        scheduler
+        Ok(scheduler)
     }
 
-    async fn flush_write_buffer(&self) {
-        // Scope for the lock
-        {
-            let mut buffer = self.write_buffer.lock();
-
-            if !buffer.is_empty() {
-                // Clone data or prepare a list of operations to be performed outside the lock
-                let requests = buffer.drain(..).collect::<Vec<_>>();
-
-                // Explicitly drop the buffer to release the lock
-                drop(buffer);
-
-                // Process the requests outside the lock
-                for mut request in requests {
-                    trace!(page_id = request.page_id, "Writing to disk");
-                    if let Err(e) = self
-                        .disk_manager
-                        .write_page_async(request.page_id, &request.data)
-                        .await
-                    {
-                        error!(error = %e, "Failed to write to disk");
+    pub fn config(&self) -> &DiskSchedulerConfig {
+        &self.config
+    }
+
+    /// Returns the number of batch reads scheduled so far (see [`DiskScheduler::batch_read`]).
+    pub fn num_batch_reads(&self) -> u64 {
+        self.num_batch_reads.load(AtomicOrdering::SeqCst)
+    }
+
+    /// Returns when the write buffer was last flushed, whether by the flush task, because the
+    /// buffer filled up, or on request.
+    pub fn last_flush(&self) -> Instant {
+        *self.last_flush.lock()
+    }
+
+    /// Counts an iteration of a loop, yielding to the runtime every
+    /// [`YIELD_INTERVAL`](Self::YIELD_INTERVAL) iterations.
+    async fn yield_periodically(iterations: &mut usize) {
+        *iterations += 1;
+        if *iterations == Self::YIELD_INTERVAL {
+            *iterations = 0;
+            task::yield_now().await;
+        }
+    }
+
+    /// Removes the request to service next from the queue: the most urgent one, unless a
+    /// conflicting request of the same page was scheduled before it (see
+    /// [`RequestQueue::take_earlier_conflict`]).
+    fn next_request(queue: &mut RequestQueue) -> Option<DiskRequest> {
+        let mut request = queue.pop()?;
+        while let Some(earlier) = queue.take_earlier_conflict(&request) {
+            queue.push(std::mem::replace(&mut request, earlier));
+        }
+        Some(request)
+    }
+
+    /// Services a request on behalf of the worker, signalling its completion.
+    async fn process(disk_manager: &DiskManager, mut request: DiskRequest) {
+        trace!(
+            page_id = request.page_id,
+            is_write = request.is_write,
+            priority = request.priority,
+            "Processing disk request"
+        );
+        if request.is_write {
+            trace!(page_id = request.page_id, "Writing to disk");
+            if let Err(e) = disk_manager
+                .write_page_with_lsn_async(request.page_id, &request.data, request.lsn)
+                .await
+            {
+                error!(error = %e, "Failed to write to disk");
+            }
+        } else {
+            trace!(page_id = request.page_id, "Reading from disk");
+            let mut read_data = vec![0; disk_manager.payload_size()];
+            match disk_manager
+                .read_page_async(request.page_id, &mut read_data)
+                .await
+            {
+                Ok(kind) => {
+                    if let Some(sender) = request.read_data_sender.take() {
+                        trace!(?kind, "Sending back read data");
+                        let _ = sender.send((read_data, kind)).await;
                     }
-                    request.complete().await;
                 }
+                // The read data sender is dropped unused, which tells the reader the read failed
+                Err(e) => error!(error = %e, "Failed to read from disk"),
+            }
+        }
+        trace!("Sending completion signal");
+        request.complete().await;
+    }
+
+    /// Writes every buffered write to disk, queued behind more urgent requests.
+    pub async fn flush_write_buffer(&self) {
+        {
+            let _flush = self.flush_lock.lock().await;
+            let requests = std::mem::take(&mut *self.write_buffer.lock());
+
+            let mut completions = Vec::with_capacity(requests.len());
+            let mut scheduled = 0;
+            for mut request in requests {
+                Self::yield_periodically(&mut scheduled).await;
+                let (tx, rx) = oneshot::channel();
+                if let Some(signal) = request.completion_signal.replace(tx) {
+                    request.completion_waiters.push(signal);
+                }
+                request.priority = DiskRequest::BUFFERED_WRITE_PRIORITY;
+                if let Err(e) = self.schedule(request).await {
+                    error!(error = %e, "Failed to schedule buffered write");
+                    continue;
+                }
+                completions.push(rx);
+            }
+            for completion in completions {
+                let _ = completion.await;
             }
         }
 
@@ -250,66 +565,104 @@ impl DiskScheduler {
 
     pub async fn schedule(
         &self,
-        request: DiskRequest,
+        mut request: DiskRequest,
     ) -> Result<(), mpsc::error::SendError<DiskRequest>> {
+        if request.is_write {
+            self.detach_in_flight_read(request.page_id);
+        }
+        request.sequence = self.next_sequence.fetch_add(1, AtomicOrdering::SeqCst);
         self.sender.send(request).await
     }
 
-    pub async fn batch_write(&self, batch: Vec<(PageId, Vec<u8>)>) -> Result<()> {
-        let mut requests = Vec::with_capacity(batch.len());
+    /// Stops later reads of a page from attaching to a read that started before it was
+    /// written. Callers that already attached still get the result of that read.
+    fn detach_in_flight_read(&self, page_id: u32) {
+        self.in_flight_reads.lock().remove(&page_id);
+    }
+
+    /// Drops the buffered writes of pages that are about to be written again, so that they
+    /// can't land on disk after (and overwrite) the newer writes. A flush of the buffer that
+    /// is already in progress is waited for instead, as its writes can no longer be dropped.
+    ///
+    /// Returns the dropped writes, whose callers are to be signalled along with the newer
+    /// writes.
+    async fn supersede_buffered_writes(&self, page_ids: &[u32]) -> Vec<DiskRequest> {
+        let _flush = self.flush_lock.lock().await;
+        let mut buffer = self.write_buffer.lock();
+        let (superseded, kept) = std::mem::take(&mut *buffer)
+            .into_iter()
+            .partition::<Vec<_>, _>(|request| page_ids.contains(&request.page_id));
+        *buffer = kept;
+        if !superseded.is_empty() {
+            debug!(
+                superseded = superseded.len(),
+                "Dropped buffered writes superseded by immediate writes"
+            );
+        }
+        superseded
+    }
 
-        for (page_id, data) in batch {
-            let (tx, _rx) = oneshot::channel();
-            let request = DiskRequest::new(true, data, page_id.into(), Some(tx), None, 0);
+    /// Writes a batch of pages, each with the LSN to record in its header, returning once all
+    /// of them are on disk.
+    pub async fn batch_write(&self, batch: Vec<(PageId, Vec<u8>, Lsn)>) -> Result<()> {
+        let page_ids: Vec<u32> = batch
+            .iter()
+            .map(|(page_id, _, _)| (*page_id).into())
+            .collect();
+        let mut superseded = self.supersede_buffered_writes(&page_ids).await;
+
+        let mut requests = Vec::with_capacity(batch.len());
+        let mut completions = Vec::with_capacity(batch.len());
+
+        for (page_id, data, lsn) in batch {
+            let (tx, rx) = oneshot::channel();
+            let mut request = DiskRequest::new(
+                true,
+                data,
+                page_id.into(),
+                Some(tx),
+                None,
+                DiskRequest::WRITE_PRIORITY,
+            );
+            request.lsn = lsn;
+            for older in superseded
+                .iter_mut()
+                .filter(|older| older.page_id == u32::from(page_id))
+            {
+                request.adopt_waiters(older);
+            }
             requests.push(request);
+            completions.push(rx);
         }
 
-        for request in &requests {
-            self.schedule(request.clone()).await?;
+        let mut scheduled = 0;
+        for request in requests {
+            Self::yield_periodically(&mut scheduled).await;
+            self.schedule(request)
+                .await
+                .map_err(DiskSchedulerError::from)?;
         }
 
-        for mut request in requests {
-            request.complete().await;
+        for completion in completions {
+            completion.await.map_err(DiskSchedulerError::from)?;
         }
 
         Ok(())
     }
 
+    /// Spawns a task flushing the write buffer every
+    /// [`flush_interval`](DiskSchedulerConfig::flush_interval), until the scheduler is dropped.
     pub fn start_flush_task(self: &Arc<Self>) {
-        let flush_interval = self.flush_interval;
-        let write_buffer = self.write_buffer.clone();
-        let disk_manager = self.disk_manager.clone();
-        let mut requests = Vec::<DiskRequest>::new();
+        let flush_interval = self.config.flush_interval;
+        let scheduler: Weak<Self> = Arc::downgrade(self);
 
         tokio::spawn(async move {
             loop {
                 tokio::time::sleep(flush_interval).await;
-
-                // Scope to hold the lock
-                {
-                    let mut buffer = write_buffer.lock();
-
-                    if !buffer.is_empty() {
-                        // Move the requests out of the buffer into a local variable
-                        requests = std::mem::replace(&mut *buffer, Vec::new());
-                        // MutexGuard is dropped here when `buffer` goes out of scope
-                    }
-                }
-
-                // Now we can process the requests outside of the lock
-                for request in &mut requests {
-                    if request.is_write {
-                        if let Err(e) = disk_manager
-                            .write_page_async(request.page_id, &request.data)
-                            .await
-                        {
-                            error!(error = %e, "Failed to write to disk");
-                        }
-                        request.complete().await;
-                    } else {
-                        warn!("Read requests should not be buffered");
-                    }
-                }
+                let Some(scheduler) = scheduler.upgrade() else {
+                    break;
+                };
+                scheduler.flush_write_buffer().await;
             }
         });
     }
@@ -325,40 +678,91 @@ impl DiskScheduler {
     //     rx.await.map_err(DiskSchedulerError::from)?;
     //     Ok(())
     // }
-    #[instrument(name = "Scheduler::schedule_write", skip(self, data, strategy))]
     pub async fn schedule_write(
         &self,
         page_id: PageId,
         data: Vec<u8>,
         strategy: WriteStrategy,
+    ) -> anyhow::Result<()> {
+        self.schedule_write_with_lsn(page_id, data, 0, strategy)
+            .await
+    }
+
+    /// Writes a page with the given strategy, recording `lsn` in its header (see
+    /// [`PageKind::Data`]).
+    #[instrument(name = "Scheduler::schedule_write", skip(self, data, strategy))]
+    pub async fn schedule_write_with_lsn(
+        &self,
+        page_id: PageId,
+        data: Vec<u8>,
+        lsn: Lsn,
+        strategy: WriteStrategy,
     ) -> anyhow::Result<()> {
         match strategy {
-            WriteStrategy::Immediate => self.immediate_write(page_id, data).await,
-            WriteStrategy::Buffered => self.buffered_write(page_id, data).await,
+            WriteStrategy::Immediate => self.immediate_write(page_id, data, lsn).await,
+            WriteStrategy::Buffered => self.buffered_write(page_id, data, lsn).await,
         }
     }
 
+    /// Buffers a write of a page, to be written by the next flush of the write buffer.
+    ///
+    /// Repeated writes of a page are coalesced: a write of a page that is already buffered
+    /// replaces the data of the buffered write (see [`DiskRequest::supersede_with`]), so that a
+    /// flush writes each page once, with its latest data. The buffered write keeps its place in
+    /// the buffer, so pages are flushed in the order they were first buffered in, and a page
+    /// never ends up with the data of an earlier write than the last one buffered.
     #[instrument(name = "Scheduler::buffered_write", skip(self, data))]
-    pub async fn buffered_write(&self, page_id: PageId, data: Vec<u8>) -> anyhow::Result<()> {
+    pub async fn buffered_write(
+        &self,
+        page_id: PageId,
+        data: Vec<u8>,
+        lsn: Lsn,
+    ) -> anyhow::Result<()> {
         let page_id = page_id.into();
         info!(page_id, data_len = data.len(), "Buffering write request");
 
-        let request = DiskRequest::new(true, data, page_id, None, None, 0); // No completion signal
-
-        let mut buffer = self.write_buffer.lock();
-
-        buffer.push(request);
-
-        if buffer.len() >= Self::MAX_BUFFER_SIZE {
-            drop(buffer); // Explicitly drop the lock before flush
+        let mut request = DiskRequest::new(
+            true,
+            data,
+            page_id,
+            None, // No completion signal
+            None,
+            DiskRequest::BUFFERED_WRITE_PRIORITY,
+        );
+        request.lsn = lsn;
+        if self.buffer(request) >= self.config.max_buffer_size {
             self.flush_write_buffer().await;
         }
 
         Ok(())
     }
 
+    /// Adds a write to the write buffer, coalescing it with a buffered write of the same page.
+    /// Returns the number of buffered writes.
+    fn buffer(&self, request: DiskRequest) -> usize {
+        self.detach_in_flight_read(request.page_id);
+
+        let mut buffer = self.write_buffer.lock();
+        match buffer
+            .iter_mut()
+            .find(|buffered| buffered.page_id == request.page_id)
+        {
+            Some(buffered) => {
+                trace!(page_id = request.page_id, "Coalescing buffered write");
+                buffered.supersede_with(request);
+            }
+            None => buffer.push(request),
+        }
+        buffer.len()
+    }
+
     #[instrument(name = "Scheduler::immediate_write", skip(self, data))]
-    pub async fn immediate_write(&self, page_id: PageId, data: Vec<u8>) -> anyhow::Result<()> {
+    pub async fn immediate_write(
+        &self,
+        page_id: PageId,
+        data: Vec<u8>,
+        lsn: Lsn,
+    ) -> anyhow::Result<()> {
         let page_id = page_id.into();
 
         info!(
@@ -368,7 +772,20 @@ impl DiskScheduler {
         );
 
         let (tx, rx) = oneshot::channel();
-        let request = DiskRequest::new(true, data, page_id, Some(tx), None, 0);
+        let mut request = DiskRequest::new(
+            true,
+            data,
+            page_id,
+            Some(tx),
+            None,
+            DiskRequest::WRITE_PRIORITY,
+        );
+        request.lsn = lsn;
+
+        // The write supersedes any buffered write of the page
+        for mut older in self.supersede_buffered_writes(&[page_id]).await {
+            request.adopt_waiters(&mut older);
+        }
 
         self.schedule(request)
             .await
@@ -379,32 +796,142 @@ impl DiskScheduler {
         Ok(())
     }
 
-    #[instrument(name = "Scheduler::schedule_read", skip(self))]
+    /// Reads the payload of a page from disk (see [`DiskScheduler::schedule_read_page`]).
     pub async fn schedule_read(&self, page_id: u32) -> anyhow::Result<Vec<u8>> {
+        let (data, _) = self.schedule_read_page(page_id).await?;
+        Ok(data)
+    }
+
+    /// Reads a page from disk, returning its payload along with what its header says it holds.
+    /// A read of a page that is already being read attaches to the pending read and returns its
+    /// result, rather than reading the page again. Should the caller performing the pending read
+    /// be dropped before it finishes, the attached reads start over.
+    #[instrument(name = "Scheduler::schedule_read", skip(self))]
+    pub async fn schedule_read_page(&self, page_id: u32) -> anyhow::Result<(Vec<u8>, PageKind)> {
+        let waiters = loop {
+            let attached = {
+                let mut in_flight_reads = self.in_flight_reads.lock();
+                match in_flight_reads.get(&page_id) {
+                    Some(waiters) => {
+                        let (tx, rx) = oneshot::channel();
+                        waiters.lock().push(tx);
+                        Err(rx)
+                    }
+                    None => {
+                        let waiters = ReadWaiters::default();
+                        in_flight_reads.insert(page_id, waiters.clone());
+                        Ok(waiters)
+                    }
+                }
+            };
+
+            match attached {
+                Ok(waiters) => break waiters,
+                Err(rx) => {
+                    info!(page_id, "Attaching to in-flight read request");
+                    match rx.await {
+                        Ok(result) => {
+                            return result
+                                .map_err(|e| DiskSchedulerError::DiskManagerError(e).into())
+                        }
+                        // The caller performing the read was dropped before it finished, having
+                        // unregistered the read, so the next attempt reads the page itself or
+                        // attaches to another caller's read
+                        Err(_) => debug!(page_id, "In-flight read was abandoned, retrying"),
+                    }
+                }
+            }
+        };
+
+        let in_flight_read = InFlightRead {
+            in_flight_reads: &self.in_flight_reads,
+            page_id,
+            waiters,
+        };
+        let result = self.read_page(page_id).await;
+        for waiter in in_flight_read.finish() {
+            let _ = waiter.send(
+                result
+                    .as_ref()
+                    .map(Clone::clone)
+                    .map_err(ToString::to_string),
+            );
+        }
+        result
+    }
+
+    async fn read_page(&self, page_id: u32) -> anyhow::Result<(Vec<u8>, PageKind)> {
         info!(page_id, "Scheduling read request");
         let (tx, rx) = oneshot::channel();
         let (read_tx, mut read_rx) = mpsc::channel(1);
         let request = DiskRequest::new(
             false,
-            vec![0; PAGE_SIZE],
+            Vec::new(),
             page_id,
             Some(tx),
             Some(read_tx),
-            0,
+            DiskRequest::READ_PRIORITY,
         );
         self.schedule(request)
             .await
             .map_err(DiskSchedulerError::from)?;
         rx.await.map_err(DiskSchedulerError::from)?;
-        read_rx.recv().await.ok_or_else(|| {
-            DiskSchedulerError::DiskManagerError("Failed to receive read data".to_string()).into()
-        })
+        read_rx
+            .recv()
+            .await
+            .ok_or_else(|| DiskSchedulerError::ReadError(page_id).into())
+    }
+
+    /// Reads a batch of pages, returning their payloads and kinds in the order the pages were
+    /// requested in. Every read is scheduled before any of them is waited for, so that the
+    /// worker services them back to back rather than one round trip at a time.
+    ///
+    /// Fails with [`DiskSchedulerError::ReadError`], naming the page, if a page can't be read.
+    #[instrument(name = "Scheduler::batch_read", skip(self, page_ids))]
+    pub async fn batch_read(
+        &self,
+        page_ids: Vec<PageId>,
+    ) -> Result<Vec<(PageId, Vec<u8>, PageKind)>> {
+        info!(num_pages = page_ids.len(), "Scheduling batch read request");
+        self.num_batch_reads.fetch_add(1, AtomicOrdering::SeqCst);
+
+        let mut pending = Vec::with_capacity(page_ids.len());
+        let mut scheduled = 0;
+        for page_id in page_ids {
+            Self::yield_periodically(&mut scheduled).await;
+            let (tx, rx) = oneshot::channel();
+            let (read_tx, read_rx) = mpsc::channel(1);
+            let request = DiskRequest::new(
+                false,
+                Vec::new(),
+                page_id.into(),
+                Some(tx),
+                Some(read_tx),
+                DiskRequest::READ_PRIORITY,
+            );
+            self.schedule(request)
+                .await
+                .map_err(DiskSchedulerError::from)?;
+            pending.push((page_id, rx, read_rx));
+        }
+
+        let mut pages = Vec::with_capacity(pending.len());
+        for (page_id, rx, mut read_rx) in pending {
+            rx.await.map_err(DiskSchedulerError::from)?;
+            let (data, kind) = read_rx
+                .recv()
+                .await
+                .ok_or_else(|| DiskSchedulerError::ReadError(page_id.into()))?;
+            pages.push((page_id, data, kind));
+        }
+        Ok(pages)
     }
 }
 
 #[cfg(test)]
 mod scheduler_tests {
     use super::*;
+    use common::PAGE_SIZE;
 
     #[tokio::test]
     async fn test_schedule_write_request() {
@@ -461,7 +988,7 @@ mod scheduler_tests {
         let (tx, rx) = oneshot::channel();
         let (read_tx, mut read_rx) = mpsc::channel(1);
 
-        let request = DiskRequest::new(false, vec![0; PAGE_SIZE], 0, Some(tx), Some(read_tx), 0);
+        let request = DiskRequest::new(false, Vec::new(), 0, Some(tx), Some(read_tx), 0);
 
         eprintln!("Scheduling read request");
         scheduler
@@ -477,12 +1004,13 @@ mod scheduler_tests {
         );
 
         // Receive the read data
-        if let Some(read_data) = read_rx.recv().await {
+        if let Some((read_data, kind)) = read_rx.recv().await {
             assert_eq!(
                 &read_data[0..data.len()],
                 &data[..],
                 "Data should be read from disk"
             );
+            assert_eq!(kind, PageKind::Data { lsn: 0 });
         } else {
             panic!("Failed to receive read data");
         }
@@ -536,6 +1064,60 @@ mod scheduler_tests {
         assert_eq!(buf[0..4], [1, 2, 3, 4], "Data should be written to disk");
     }
 
+    #[tokio::test]
+    async fn test_invalid_configs_are_rejected() {
+        let (dm, _temp_dir) = setup_dm();
+        for config in [
+            DiskSchedulerConfig::builder()
+                .max_pending_requests(0)
+                .build(),
+            DiskSchedulerConfig::builder()
+                .flush_interval(Duration::ZERO)
+                .build(),
+        ] {
+            assert!(matches!(
+                DiskScheduler::new_with_config(dm.clone(), config),
+                Err(DiskSchedulerError::InvalidConfig(_))
+            ));
+        }
+
+        let config = DiskSchedulerConfig::builder()
+            .max_pending_requests(1)
+            .build();
+        let scheduler = DiskScheduler::new_with_config(dm, config).unwrap();
+        scheduler
+            .schedule_write(PageId::from(0), vec![1; 4], WriteStrategy::Immediate)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_flush_task_honors_the_configured_interval() {
+        let (dm, _temp_dir) = setup_dm();
+        let config = DiskSchedulerConfig::builder()
+            .flush_interval(Duration::from_millis(50))
+            .build();
+        assert_eq!(config.max_buffer_size, DiskScheduler::MAX_BUFFER_SIZE);
+        let scheduler = DiskScheduler::new_with_config(dm.clone(), config).unwrap();
+        let created_at = scheduler.last_flush();
+
+        scheduler
+            .schedule_write(PageId::from(0), vec![1, 2, 3, 4], WriteStrategy::Buffered)
+            .await
+            .unwrap();
+        // A single write is well below the buffer size, so only the timer can flush it
+        assert_eq!(scheduler.write_buffer.lock().len(), 1);
+        assert_eq!(scheduler.last_flush(), created_at);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert!(scheduler.write_buffer.lock().is_empty());
+        assert!(scheduler.last_flush() > created_at);
+        let mut buf = vec![0; PAGE_SIZE];
+        dm.read_page(0, &mut buf).expect("Failed to read page");
+        assert_eq!(buf[0..4], [1, 2, 3, 4], "Data should be written to disk");
+    }
+
     #[tokio::test]
     async fn test_schedule_write_coalescing() {
         let (dm, _temp_dir) = setup_dm();
@@ -544,33 +1126,103 @@ mod scheduler_tests {
         let data1 = vec![1, 2, 3, 4];
         let data2 = vec![5, 6, 7, 8];
 
-        // Schedule two write requests
+        // Schedule two writes of the same page
         scheduler
             .schedule_write(PageId::from(0), data1.clone(), WriteStrategy::Buffered)
             .await
             .unwrap();
         scheduler
-            .schedule_write(PageId::from(1), data2.clone(), WriteStrategy::Buffered)
+            .schedule_write(PageId::from(0), data2.clone(), WriteStrategy::Buffered)
             .await
             .unwrap();
+        {
+            let buffer = scheduler.write_buffer.lock();
+            assert_eq!(buffer.len(), 1, "The writes should be coalesced");
+            assert_eq!(buffer[0].data(), &data2);
+        }
 
         // Force flush to ensure that requests are processed
         scheduler.flush_write_buffer().await;
 
-        // Verify that data is written to disk...
+        // Only the last write lands on disk, in a single physical write
         let mut buf = vec![0; PAGE_SIZE];
-        let _ = dm.read_page(0, &mut buf).expect("Failed to read page");
-        assert_eq!(
-            buf[0..data1.len()],
-            data1[..],
-            "Data should be written to disk"
-        );
-        let _ = dm.read_page(1, &mut buf).expect("Failed to read page");
+        dm.read_page(0, &mut buf).expect("Failed to read page");
         assert_eq!(
             buf[0..data2.len()],
             data2[..],
-            "Data should be written to disk"
+            "The latest data should be written to disk"
         );
+        assert_eq!(dm.num_writes(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_other_tasks_progress_during_a_large_flush() {
+        let (dm, _temp_dir) = setup_dm();
+        dm.write_data(1000, &[9; 16]).unwrap();
+        let scheduler = DiskScheduler::new(dm.clone());
+        for page_id in 0..256 {
+            let request = DiskRequest::new(
+                true,
+                vec![page_id as u8; 16],
+                page_id,
+                None,
+                None,
+                DiskRequest::BUFFERED_WRITE_PRIORITY,
+            );
+            scheduler.buffer(request);
+        }
+
+        let flush = {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move {
+                scheduler.flush_write_buffer().await;
+                Instant::now()
+            })
+        };
+        let read = {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move {
+                // Let the flush get going first
+                task::yield_now().await;
+                let data = scheduler.schedule_read(1000).await.unwrap();
+                (data, Instant::now())
+            })
+        };
+
+        let (flushed_at, (data, read_at)) = (flush.await.unwrap(), read.await.unwrap());
+        assert_eq!(data[..16], [9; 16]);
+        assert!(read_at < flushed_at, "The read should complete mid-flush");
+        assert_eq!(dm.read_data(255).unwrap()[..16], [255; 16]);
+    }
+
+    #[tokio::test]
+    async fn test_coalesced_writes_signal_their_completion() {
+        let (dm, _temp_dir) = setup_dm();
+        let scheduler = DiskScheduler::new(dm.clone());
+
+        let mut completions = Vec::new();
+        for value in 1..=3 {
+            let (tx, rx) = oneshot::channel();
+            let request = DiskRequest::new(
+                true,
+                vec![value; 4],
+                0,
+                Some(tx),
+                None,
+                DiskRequest::BUFFERED_WRITE_PRIORITY,
+            );
+            assert_eq!(scheduler.buffer(request), 1);
+            completions.push(rx);
+        }
+
+        scheduler.flush_write_buffer().await;
+        for completion in completions {
+            assert!(
+                completion.await.is_ok(),
+                "Superseded writes should be signalled once the page is written"
+            );
+        }
+        assert_eq!(dm.read_data(0).unwrap()[0..4], [3; 4]);
     }
 
     #[tokio::test]
@@ -580,7 +1232,7 @@ mod scheduler_tests {
 
         // Schedule a buffered write
         scheduler
-            .buffered_write(PageId::from(0), vec![1, 2, 3, 4])
+            .buffered_write(PageId::from(0), vec![1, 2, 3, 4], 0)
             .await
             .unwrap();
 
@@ -601,11 +1253,41 @@ mod scheduler_tests {
         let _ = dm.read_page(0, &mut buf).expect("Failed to read page");
         assert_eq!(buf[0..4], [1, 2, 3, 4], "Data should be written to disk");
     }
+
+    #[tokio::test]
+    async fn test_immediate_write_supersedes_buffered_write() {
+        let (dm, _temp_dir) = setup_dm();
+        let scheduler = DiskScheduler::new(dm.clone());
+
+        scheduler
+            .buffered_write(PageId::from(0), vec![1, 2, 3, 4], 0)
+            .await
+            .unwrap();
+        scheduler
+            .buffered_write(PageId::from(1), vec![9, 9, 9, 9], 0)
+            .await
+            .unwrap();
+        scheduler
+            .immediate_write(PageId::from(0), vec![5, 6, 7, 8], 0)
+            .await
+            .unwrap();
+
+        // Only the buffered write of the other page is left to flush
+        assert_eq!(scheduler.write_buffer.lock().len(), 1);
+        scheduler.flush_write_buffer().await;
+
+        let mut buf = vec![0; PAGE_SIZE];
+        dm.read_page(0, &mut buf).expect("Failed to read page");
+        assert_eq!(buf[0..4], [5, 6, 7, 8], "The immediate write should win");
+        dm.read_page(1, &mut buf).expect("Failed to read page");
+        assert_eq!(buf[0..4], [9, 9, 9, 9]);
+    }
 }
 
 #[cfg(test)]
 mod high_level_api_tests {
     use super::*;
+    use common::{PAGE_SIZE, USABLE_PAGE_SIZE};
 
     #[tokio::test]
     async fn test_high_level_write_api() {
@@ -648,13 +1330,151 @@ mod high_level_api_tests {
             .await
             .expect("Failed to read page");
 
-        assert_eq!(read_data.len(), PAGE_SIZE, "Read data should be a page");
+        assert_eq!(
+            read_data.len(),
+            USABLE_PAGE_SIZE,
+            "Read data should be the payload of a page"
+        );
         assert_eq!(
             &read_data[0..data.len()],
             &data[..],
             "Data should be read from disk"
         );
     }
+
+    #[tokio::test]
+    async fn test_concurrent_reads_of_a_page_are_deduplicated() {
+        let (dm, _temp_dir) = setup_dm();
+        let data = vec![1, 2, 3, 4];
+        dm.write_page(0, &data).expect("Failed to write page");
+
+        let scheduler = DiskScheduler::new(dm.clone());
+        let (first, second) = tokio::join!(scheduler.schedule_read(0), scheduler.schedule_read(0));
+
+        assert_eq!(dm.num_reads(), 1, "Only one read should reach the disk");
+        for read_data in [first.unwrap(), second.unwrap()] {
+            assert_eq!(&read_data[0..data.len()], &data[..]);
+        }
+
+        // Once the read completes, the page is read from disk again
+        scheduler.schedule_read(0).await.unwrap();
+        assert_eq!(dm.num_reads(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_attached_reads_survive_the_reader_being_dropped() {
+        use std::future::Future;
+
+        let (dm, _temp_dir) = setup_dm();
+        dm.write_page(0, &[1, 2, 3, 4]).unwrap();
+        let scheduler = DiskScheduler::new(dm);
+
+        // Start a read and attach a second one to it, polling each just once
+        let mut reader = Box::pin(scheduler.schedule_read_page(0));
+        let mut attached = Box::pin(scheduler.schedule_read_page(0));
+        std::future::poll_fn(|cx| {
+            assert!(reader.as_mut().poll(cx).is_pending());
+            assert!(attached.as_mut().poll(cx).is_pending());
+            std::task::Poll::Ready(())
+        })
+        .await;
+
+        drop(reader);
+        let (data, kind) = attached.await.expect("The attached read should start over");
+        assert_eq!(data[..4], [1, 2, 3, 4]);
+        assert_eq!(kind, PageKind::Data { lsn: 0 });
+        assert!(scheduler.in_flight_reads.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_batch_read_returns_pages_in_requested_order() {
+        let (dm, _temp_dir) = setup_dm();
+        let scheduler = DiskScheduler::new(dm.clone());
+        for page_id in 0..3u32 {
+            scheduler
+                .schedule_write(
+                    PageId::from(page_id),
+                    vec![page_id as u8 + 1; 16],
+                    WriteStrategy::Immediate,
+                )
+                .await
+                .unwrap();
+        }
+
+        let order = [2, 0, 1].map(PageId::from);
+        let pages = scheduler.batch_read(order.to_vec()).await.unwrap();
+        assert_eq!(scheduler.num_batch_reads(), 1);
+
+        assert_eq!(pages.len(), 3);
+        for ((page_id, data, kind), expected) in pages.iter().zip(order) {
+            assert_eq!(*page_id, expected);
+            assert_eq!(data.len(), USABLE_PAGE_SIZE);
+            assert_eq!(data[..16], [u32::from(expected) as u8 + 1; 16]);
+            assert_eq!(*kind, PageKind::Data { lsn: 0 });
+        }
+    }
+
+    #[tokio::test]
+    async fn test_writes_record_their_lsn() {
+        let (dm, _temp_dir) = setup_dm();
+        let scheduler = DiskScheduler::new(dm.clone());
+
+        scheduler
+            .schedule_write_with_lsn(PageId::from(0), vec![1; 4], 5, WriteStrategy::Immediate)
+            .await
+            .unwrap();
+        // Coalesced buffered writes keep the LSN of the latest one
+        for lsn in [6, 7] {
+            scheduler
+                .schedule_write_with_lsn(PageId::from(1), vec![2; 4], lsn, WriteStrategy::Buffered)
+                .await
+                .unwrap();
+        }
+        scheduler.flush_write_buffer().await;
+        scheduler
+            .batch_write(vec![(PageId::from(2), vec![3; 4], 8)])
+            .await
+            .unwrap();
+
+        let kinds = [0, 1, 2, 3].map(|page_id| {
+            let mut buf = vec![0; PAGE_SIZE];
+            dm.read_page(page_id, &mut buf).unwrap()
+        });
+        assert_eq!(
+            kinds,
+            [
+                PageKind::Data { lsn: 5 },
+                PageKind::Data { lsn: 7 },
+                PageKind::Data { lsn: 8 },
+                PageKind::Uninitialized,
+            ]
+        );
+        let (data, kind) = scheduler.schedule_read_page(1).await.unwrap();
+        assert_eq!(data[..4], [2; 4]);
+        assert_eq!(kind, PageKind::Data { lsn: 7 });
+    }
+
+    #[tokio::test]
+    async fn test_batch_read_names_the_page_that_failed() {
+        let (dm, _temp_dir) = setup_dm();
+        for page_id in 0..3 {
+            dm.write_page(page_id, &[1; 16]).unwrap();
+        }
+        // Overwrite page 1 without a checksum, so that reading it fails verification
+        dm.set_checksums_enabled(false);
+        dm.write_page(1, &[2; 16]).unwrap();
+        dm.set_checksums_enabled(true);
+
+        let scheduler = DiskScheduler::new(dm);
+        let error = scheduler
+            .batch_read([0, 1, 2].map(PageId::from).to_vec())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<DiskSchedulerError>(),
+            Some(DiskSchedulerError::ReadError(1))
+        ));
+    }
 }
 
 // #[tokio::test]
@@ -671,20 +1491,161 @@ mod high_level_api_tests {
 //     // ...
 // }
 
-// #[cfg(test)]
-// mod priority_tests {
-//     use super::*;
-//     use tokio::sync::oneshot;
+#[cfg(test)]
+mod priority_tests {
+    use super::*;
+    use common::USABLE_PAGE_SIZE;
+
+    /// Waits for the completion of a request, returning when it was signalled.
+    async fn completed_at(rx: oneshot::Receiver<()>) -> Instant {
+        rx.await.expect("Request should complete");
+        Instant::now()
+    }
+
+    #[tokio::test]
+    async fn test_request_prioritization() {
+        let (dm, _temp_dir) = setup_dm();
+        dm.write_data(1, &[7; 16]).unwrap();
+        let scheduler = DiskScheduler::new(dm);
 
-//     #[tokio::test]
-//     #[ignore = "TODO: Implement priority scheduling"]
-//     async fn test_request_prioritization() {
-//         let dm = Arc::new(DiskManager::new("testdata/test_schedule.db").unwrap());
-//         let scheduler = DiskScheduler::new(dm);
+        // Both requests are queued before the worker gets to run, so the read, scheduled
+        // last but more urgent, is serviced first
+        let (write_tx, write_rx) = oneshot::channel();
+        let write = DiskRequest::new(
+            true,
+            vec![1; USABLE_PAGE_SIZE],
+            0,
+            Some(write_tx),
+            None,
+            DiskRequest::BUFFERED_WRITE_PRIORITY,
+        );
+        let (read_tx, read_rx) = oneshot::channel();
+        let (data_tx, mut data_rx) = mpsc::channel(1);
+        let read = DiskRequest::new(
+            false,
+            Vec::new(),
+            1,
+            Some(read_tx),
+            Some(data_tx),
+            DiskRequest::READ_PRIORITY,
+        );
+        scheduler.schedule(write).await.unwrap();
+        scheduler.schedule(read).await.unwrap();
+
+        let (write_done, read_done) = tokio::join!(completed_at(write_rx), completed_at(read_rx));
+        assert!(
+            read_done < write_done,
+            "The read should complete before the write"
+        );
+        assert_eq!(data_rx.recv().await.unwrap().0[..16], [7; 16]);
+    }
 
-//         // Priorities for the requests
-//         let priorities = vec![1, 3, 2];
+    #[tokio::test]
+    async fn test_read_does_not_overtake_an_earlier_write_of_its_page() {
+        let (dm, _temp_dir) = setup_dm();
+        dm.write_data(0, &[1; 16]).unwrap();
+        let scheduler = DiskScheduler::new(dm);
 
-//         // TODO: Further flesh out priority tests
-//     }
-// }
+        // Both requests are queued before the worker gets to run, but the read, although more
+        // urgent, must see the write
+        let (write_tx, mut write_rx) = oneshot::channel();
+        let write = DiskRequest::new(
+            true,
+            vec![2; 16],
+            0,
+            Some(write_tx),
+            None,
+            DiskRequest::BUFFERED_WRITE_PRIORITY,
+        );
+        let (read_tx, read_rx) = oneshot::channel();
+        let (data_tx, mut data_rx) = mpsc::channel(1);
+        let read = DiskRequest::new(
+            false,
+            Vec::new(),
+            0,
+            Some(read_tx),
+            Some(data_tx),
+            DiskRequest::READ_PRIORITY,
+        );
+        scheduler.schedule(write).await.unwrap();
+        scheduler.schedule(read).await.unwrap();
+
+        read_rx.await.expect("Request should complete");
+        assert!(
+            write_rx.try_recv().is_ok(),
+            "The write should complete first"
+        );
+        assert_eq!(data_rx.recv().await.unwrap().0[..16], [2; 16]);
+    }
+
+    #[test]
+    fn test_conflicting_requests_keep_their_order() {
+        let request = |is_write, page_id, priority, sequence| {
+            let mut request = DiskRequest::new(is_write, vec![], page_id, None, None, priority);
+            request.sequence = sequence;
+            request
+        };
+
+        let mut queue = RequestQueue::default();
+        queue.push(request(false, 0, DiskRequest::READ_PRIORITY, 0));
+        queue.push(request(true, 0, DiskRequest::BUFFERED_WRITE_PRIORITY, 1));
+        queue.push(request(false, 1, DiskRequest::READ_PRIORITY, 2));
+        queue.push(request(false, 0, DiskRequest::READ_PRIORITY, 3));
+        queue.push(request(true, 1, DiskRequest::WRITE_PRIORITY, 4));
+
+        let order: Vec<u64> =
+            std::iter::from_fn(|| DiskScheduler::next_request(&mut queue).map(|r| r.sequence))
+                .collect();
+        // Reads go first, except the one of page 0 scheduled after its write
+        assert_eq!(order, [0, 2, 1, 3, 4]);
+    }
+
+    #[test]
+    fn test_requests_taken_out_of_turn_are_serviced_once() {
+        let mut queue = RequestQueue::default();
+        for (sequence, is_write, priority) in [
+            (0, true, DiskRequest::BUFFERED_WRITE_PRIORITY),
+            (1, true, DiskRequest::BUFFERED_WRITE_PRIORITY),
+            (2, false, DiskRequest::READ_PRIORITY),
+        ] {
+            let mut request = DiskRequest::new(is_write, vec![], 0, None, None, priority);
+            request.sequence = sequence;
+            queue.push(request);
+        }
+
+        // The read is popped first, but waits for both writes, which are taken out of turn
+        let order: Vec<u64> =
+            std::iter::from_fn(|| DiskScheduler::next_request(&mut queue).map(|r| r.sequence))
+                .collect();
+        assert_eq!(order, [0, 1, 2]);
+        assert!(queue.is_empty());
+        assert!(queue.pages.is_empty() && queue.writes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_equal_priorities_are_serviced_in_order() {
+        let (dm, _temp_dir) = setup_dm();
+        let scheduler = DiskScheduler::new(dm.clone());
+
+        // Later writes of a page must land after earlier ones
+        let mut completions = Vec::new();
+        for value in 1..=3 {
+            let (tx, rx) = oneshot::channel();
+            let request = DiskRequest::new(
+                true,
+                vec![value; USABLE_PAGE_SIZE],
+                0,
+                Some(tx),
+                None,
+                DiskRequest::WRITE_PRIORITY,
+            );
+            scheduler.schedule(request).await.unwrap();
+            completions.push(rx);
+        }
+        for completion in completions {
+            completion.await.unwrap();
+        }
+
+        assert_eq!(dm.read_data(0).unwrap()[0], 3);
+    }
+}
@@ -5,9 +5,13 @@ use crate::tuple::Tuple;
 use super::{
     index::{Index, IndexMetadataRef},
     iterator::IndexIterator,
+    IndexError,
 };
 use anyhow::Result;
+use common::rid::RID;
 use getset::{Getters, Setters};
+use std::cmp::Ordering;
+use ty::DataType;
 // use serde::{Deserialize, Serialize};
 use typed_builder::TypedBuilder;
 
@@ -43,6 +47,467 @@ impl Index for BPlusTreeIndex {
     // Implement the trait methods for B+ Tree...
 }
 
+/// Identifies a node of a [`BPlusTree`] by its position in the tree's node arena.
+type NodeId = usize;
+
+#[derive(Debug, Clone)]
+enum Node {
+    /// `children[i]` holds the keys below `keys[i]` (and at or above `keys[i - 1]`), so there
+    /// is one more child than there are keys.
+    Internal {
+        keys: Vec<DataType>,
+        children: Vec<NodeId>,
+    },
+    /// Leaves hold the entries of the tree, and are linked in key order for range scans.
+    Leaf {
+        keys: Vec<DataType>,
+        rids: Vec<RID>,
+        next: Option<NodeId>,
+    },
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Node::Leaf {
+            keys: Vec::new(),
+            rids: Vec::new(),
+            next: None,
+        }
+    }
+}
+
+impl Node {
+    fn keys(&self) -> &[DataType] {
+        match self {
+            Node::Internal { keys, .. } | Node::Leaf { keys, .. } => keys,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.keys().len()
+    }
+}
+
+/// An in-memory B+ tree mapping unique keys to the records holding them, ordered by the
+/// [`PartialOrd`] of [`DataType`].
+///
+/// Nodes hold at most `max_size` keys and, except for the root, at least half as many:
+/// overflowing nodes are split in two, and underflowing nodes borrow a key from a sibling or
+/// are merged with it, so every leaf stays at the same depth.
+#[derive(Debug, Clone)]
+pub struct BPlusTree {
+    nodes: Vec<Node>,
+    /// Nodes merged into their siblings, whose slots are reused by the next nodes created
+    free: Vec<NodeId>,
+    root: NodeId,
+    max_size: usize,
+    len: usize,
+}
+
+impl BPlusTree {
+    /// The smallest `max_size` for which splitting a node leaves both halves non-empty.
+    pub const MIN_MAX_SIZE: usize = 3;
+
+    /// Creates an empty tree whose nodes hold up to `max_size` keys.
+    pub fn new(max_size: usize) -> Result<Self, IndexError> {
+        if max_size < Self::MIN_MAX_SIZE {
+            return Err(IndexError::CreationError(format!(
+                "B+ tree nodes must hold at least {} keys, not {}",
+                Self::MIN_MAX_SIZE,
+                max_size
+            )));
+        }
+
+        Ok(Self {
+            nodes: vec![Node::default()],
+            free: Vec::new(),
+            root: 0,
+            max_size,
+            len: 0,
+        })
+    }
+
+    /// Returns the number of entries in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn min_size(&self) -> usize {
+        self.max_size / 2
+    }
+
+    fn allocate(&mut self, node: Node) -> NodeId {
+        match self.free.pop() {
+            Some(node_id) => {
+                self.nodes[node_id] = node;
+                node_id
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Returns the record stored under `key`.
+    pub fn search(&self, key: &DataType) -> Option<RID> {
+        let (leaf, position) = self.find_leaf(key).ok()?;
+        let Node::Leaf { keys, rids, .. } = &self.nodes[leaf] else {
+            unreachable!("Searches end at a leaf");
+        };
+        match keys.get(position)?.partial_cmp(key) {
+            Some(Ordering::Equal) => Some(rids[position]),
+            _ => None,
+        }
+    }
+
+    /// Returns the leaf `key` belongs in, and the position of the first key of the leaf at
+    /// or above it.
+    fn find_leaf(&self, key: &DataType) -> Result<(NodeId, usize), IndexError> {
+        let mut node_id = self.root;
+        loop {
+            match &self.nodes[node_id] {
+                Node::Internal { keys, children } => node_id = children[child_index(keys, key)?],
+                Node::Leaf { keys, .. } => {
+                    let position = search_keys(keys, key)?.unwrap_or_else(|position| position);
+                    return Ok((node_id, position));
+                }
+            }
+        }
+    }
+
+    /// Inserts an entry, failing if the tree already holds `key` or if `key` can't be ordered
+    /// against the keys of the tree.
+    pub fn insert(&mut self, key: DataType, rid: RID) -> Result<(), IndexError> {
+        if let Some((separator, right)) = self.insert_into(self.root, key, rid)? {
+            let left = self.root;
+            self.root = self.allocate(Node::Internal {
+                keys: vec![separator],
+                children: vec![left, right],
+            });
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Inserts an entry into the subtree of `node_id`. If the node overflows, it is split and
+    /// the key separating the halves is returned along with the new right half.
+    fn insert_into(
+        &mut self,
+        node_id: NodeId,
+        key: DataType,
+        rid: RID,
+    ) -> Result<Option<(DataType, NodeId)>, IndexError> {
+        match &mut self.nodes[node_id] {
+            Node::Leaf { keys, rids, .. } => match search_keys(keys, &key)? {
+                Ok(_) => {
+                    return Err(IndexError::CreationError(format!(
+                        "Duplicate key {} in B+ tree",
+                        key
+                    )))
+                }
+                Err(position) => {
+                    keys.insert(position, key);
+                    rids.insert(position, rid);
+                }
+            },
+            Node::Internal { keys, children } => {
+                let child_idx = child_index(keys, &key)?;
+                let child = children[child_idx];
+                if let Some((separator, right)) = self.insert_into(child, key, rid)? {
+                    let Node::Internal { keys, children } = &mut self.nodes[node_id] else {
+                        unreachable!("Node changed kind during insert");
+                    };
+                    keys.insert(child_idx, separator);
+                    children.insert(child_idx + 1, right);
+                }
+            }
+        }
+
+        if self.nodes[node_id].len() > self.max_size {
+            Ok(Some(self.split(node_id)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Moves the upper half of a node to a new node, returning the key separating the halves
+    /// and the new node.
+    fn split(&mut self, node_id: NodeId) -> (DataType, NodeId) {
+        let (separator, right) = match &mut self.nodes[node_id] {
+            Node::Leaf { keys, rids, next } => {
+                let mid = keys.len() / 2;
+                let right_keys = keys.split_off(mid);
+                let separator = right_keys[0].clone();
+                let right = Node::Leaf {
+                    keys: right_keys,
+                    rids: rids.split_off(mid),
+                    next: *next,
+                };
+                (separator, right)
+            }
+            Node::Internal { keys, children } => {
+                let mid = keys.len() / 2;
+                let mut right_keys = keys.split_off(mid);
+                // The middle key moves up rather than to the right half
+                let separator = right_keys.remove(0);
+                let right = Node::Internal {
+                    keys: right_keys,
+                    children: children.split_off(mid + 1),
+                };
+                (separator, right)
+            }
+        };
+
+        let right = self.allocate(right);
+        if let Node::Leaf { next, .. } = &mut self.nodes[node_id] {
+            *next = Some(right);
+        }
+        (separator, right)
+    }
+
+    /// Deletes the entry stored under `key`, returning its record.
+    pub fn delete(&mut self, key: &DataType) -> Result<RID, IndexError> {
+        let rid = self.delete_from(self.root, key)?;
+
+        // A root left with a single child is replaced by the child
+        if let Node::Internal { keys, children } = &self.nodes[self.root] {
+            if keys.is_empty() {
+                let child = children[0];
+                self.free.push(self.root);
+                self.root = child;
+            }
+        }
+        self.len -= 1;
+        Ok(rid)
+    }
+
+    fn delete_from(&mut self, node_id: NodeId, key: &DataType) -> Result<RID, IndexError> {
+        match &mut self.nodes[node_id] {
+            Node::Leaf { keys, rids, .. } => match search_keys(keys, key)? {
+                Ok(position) => {
+                    keys.remove(position);
+                    Ok(rids.remove(position))
+                }
+                Err(_) => Err(IndexError::NotFoundError(format!(
+                    "Key {} is not in the B+ tree",
+                    key
+                ))),
+            },
+            Node::Internal { keys, children } => {
+                let child_idx = child_index(keys, key)?;
+                let child = children[child_idx];
+                let rid = self.delete_from(child, key)?;
+                if self.nodes[child].len() < self.min_size() {
+                    self.rebalance(node_id, child_idx);
+                }
+                Ok(rid)
+            }
+        }
+    }
+
+    /// Restores the minimum size of the underflowing child `child_idx` of `parent`, by
+    /// borrowing a key from a sibling that can spare one or merging with a sibling otherwise.
+    fn rebalance(&mut self, parent: NodeId, child_idx: usize) {
+        let Node::Internal { children, .. } = &self.nodes[parent] else {
+            unreachable!("Only internal nodes have children");
+        };
+        // The underflowing child and the sibling it is rebalanced with, in key order
+        let left_idx = child_idx.saturating_sub(1);
+        let (left, right) = (children[left_idx], children[left_idx + 1]);
+        let sibling = if child_idx == left_idx { right } else { left };
+        let can_borrow = self.nodes[sibling].len() > self.min_size();
+
+        let mut left_node = std::mem::take(&mut self.nodes[left]);
+        let mut right_node = std::mem::take(&mut self.nodes[right]);
+        let Node::Internal { keys, children } = &mut self.nodes[parent] else {
+            unreachable!("Only internal nodes have children");
+        };
+        let separator = &mut keys[left_idx];
+
+        match (&mut left_node, &mut right_node) {
+            (
+                Node::Leaf {
+                    keys: left_keys,
+                    rids: left_rids,
+                    next,
+                },
+                Node::Leaf {
+                    keys: right_keys,
+                    rids: right_rids,
+                    next: right_next,
+                },
+            ) => {
+                if !can_borrow {
+                    left_keys.append(right_keys);
+                    left_rids.append(right_rids);
+                    *next = *right_next;
+                } else if sibling == left {
+                    right_keys.insert(0, left_keys.pop().expect("Sibling can spare a key"));
+                    right_rids.insert(0, left_rids.pop().expect("Sibling can spare a key"));
+                    *separator = right_keys[0].clone();
+                } else {
+                    left_keys.push(right_keys.remove(0));
+                    left_rids.push(right_rids.remove(0));
+                    *separator = right_keys[0].clone();
+                }
+            }
+            (
+                Node::Internal {
+                    keys: left_keys,
+                    children: left_children,
+                },
+                Node::Internal {
+                    keys: right_keys,
+                    children: right_children,
+                },
+            ) => {
+                // Keys rotate through the separator in the parent
+                if !can_borrow {
+                    left_keys.push(separator.clone());
+                    left_keys.append(right_keys);
+                    left_children.append(right_children);
+                } else if sibling == left {
+                    let key = left_keys.pop().expect("Sibling can spare a key");
+                    right_keys.insert(0, std::mem::replace(separator, key));
+                    right_children.insert(0, left_children.pop().expect("Sibling has children"));
+                } else {
+                    let key = right_keys.remove(0);
+                    left_keys.push(std::mem::replace(separator, key));
+                    left_children.push(right_children.remove(0));
+                }
+            }
+            _ => unreachable!("Siblings are at the same depth"),
+        }
+
+        if !can_borrow {
+            keys.remove(left_idx);
+            children.remove(left_idx + 1);
+            self.free.push(right);
+        }
+        self.nodes[left] = left_node;
+        self.nodes[right] = right_node;
+    }
+
+    /// Returns an iterator over the records of the entries with keys between `lo` and `hi`
+    /// (inclusive), in key order.
+    pub fn range(&self, lo: &DataType, hi: &DataType) -> Result<BPlusTreeIterator<'_>, IndexError> {
+        let (leaf, position) = self.find_leaf(lo)?;
+        Ok(BPlusTreeIterator::new(
+            self,
+            leaf,
+            position,
+            Some(hi.clone()),
+        ))
+    }
+
+    /// Returns an iterator over the records of every entry, in key order.
+    pub fn iter(&self) -> BPlusTreeIterator<'_> {
+        let mut node_id = self.root;
+        while let Node::Internal { children, .. } = &self.nodes[node_id] {
+            node_id = children[0];
+        }
+        BPlusTreeIterator::new(self, node_id, 0, None)
+    }
+}
+
+/// Binary searches the sorted keys of a node, failing if `key` can't be ordered against them
+/// (e.g. text looked up in a tree of integers).
+fn search_keys(keys: &[DataType], key: &DataType) -> Result<Result<usize, usize>, IndexError> {
+    let mut comparable = true;
+    let position = keys.binary_search_by(|probe| {
+        probe.partial_cmp(key).unwrap_or_else(|| {
+            comparable = false;
+            Ordering::Equal
+        })
+    });
+
+    if comparable {
+        Ok(position)
+    } else {
+        Err(IndexError::CreationError(format!(
+            "Key {} can't be ordered against the keys of the B+ tree",
+            key
+        )))
+    }
+}
+
+/// Returns the index of the child of an internal node whose subtree `key` belongs in.
+fn child_index(keys: &[DataType], key: &DataType) -> Result<usize, IndexError> {
+    Ok(match search_keys(keys, key)? {
+        Ok(position) => position + 1,
+        Err(position) => position,
+    })
+}
+
+/// Walks the linked leaves of a [`BPlusTree`] from a starting entry, up to an optional upper
+/// bound.
+#[derive(Debug)]
+pub struct BPlusTreeIterator<'a> {
+    tree: &'a BPlusTree,
+    /// The leaf and position of the next entry, or `None` once past the last entry in range
+    cursor: Option<(NodeId, usize)>,
+    hi: Option<DataType>,
+}
+
+impl<'a> BPlusTreeIterator<'a> {
+    fn new(tree: &'a BPlusTree, leaf: NodeId, position: usize, hi: Option<DataType>) -> Self {
+        let mut iterator = Self {
+            tree,
+            cursor: Some((leaf, position)),
+            hi,
+        };
+        iterator.settle();
+        iterator
+    }
+
+    /// Moves the cursor past the end of exhausted leaves, and clears it once past `hi`.
+    fn settle(&mut self) {
+        while let Some((leaf, position)) = self.cursor {
+            let Node::Leaf { keys, next, .. } = &self.tree.nodes[leaf] else {
+                unreachable!("Iterators only visit leaves");
+            };
+            match keys.get(position) {
+                Some(key) => {
+                    let past_hi = self.hi.as_ref().is_some_and(|hi| {
+                        !matches!(key.partial_cmp(hi), Some(Ordering::Less | Ordering::Equal))
+                    });
+                    if past_hi {
+                        self.cursor = None;
+                    }
+                    return;
+                }
+                None => self.cursor = next.map(|next| (next, 0)),
+            }
+        }
+    }
+
+    /// Returns the next entry in key order.
+    pub fn next_entry(&mut self) -> Option<(&'a DataType, RID)> {
+        let (leaf, position) = self.cursor?;
+        let Node::Leaf { keys, rids, .. } = &self.tree.nodes[leaf] else {
+            unreachable!("Iterators only visit leaves");
+        };
+        self.cursor = Some((leaf, position + 1));
+        self.settle();
+        Some((&keys[position], rids[position]))
+    }
+}
+
+impl IndexIterator for BPlusTreeIterator<'_> {
+    fn next(&mut self) -> Option<RID> {
+        self.next_entry().map(|(_, rid)| rid)
+    }
+
+    fn has_next(&self) -> bool {
+        self.cursor.is_some()
+    }
+}
+
 // Hash Index
 pub struct HashIndex {
     metadata: IndexMetadataRef,
@@ -63,3 +528,174 @@ impl Index for HashIndex {
     }
     // Implement the trait methods for Hash Index...
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    const NUM_KEYS: i32 = 1000;
+
+    fn rid(key: i32) -> RID {
+        RID::new(key as u32, key as u32 % 7)
+    }
+
+    /// Builds a tree of small nodes from the keys `0..NUM_KEYS`, inserted in random order.
+    fn random_tree(rng: &mut StdRng) -> BPlusTree {
+        let mut keys: Vec<i32> = (0..NUM_KEYS).collect();
+        keys.shuffle(rng);
+
+        let mut tree = BPlusTree::new(4).unwrap();
+        for key in keys {
+            tree.insert(DataType::Integer(key), rid(key)).unwrap();
+        }
+        tree
+    }
+
+    /// Checks that every leaf is at the same depth, that every node but the root is at least
+    /// half full and that the linked leaves hold every entry in key order.
+    fn check_invariants(tree: &BPlusTree) {
+        fn check_node(
+            tree: &BPlusTree,
+            node_id: NodeId,
+            depth: usize,
+            leaf_depths: &mut Vec<usize>,
+        ) {
+            let node = &tree.nodes[node_id];
+            assert!(node.len() <= tree.max_size);
+            if node_id != tree.root {
+                assert!(node.len() >= tree.min_size(), "Node {} underflows", node_id);
+            }
+            assert!(node.keys().windows(2).all(|pair| pair[0] < pair[1]));
+            match node {
+                Node::Internal { keys, children } => {
+                    assert_eq!(children.len(), keys.len() + 1);
+                    for child in children {
+                        check_node(tree, *child, depth + 1, leaf_depths);
+                    }
+                }
+                Node::Leaf { .. } => leaf_depths.push(depth),
+            }
+        }
+
+        let mut leaf_depths = Vec::new();
+        check_node(tree, tree.root, 0, &mut leaf_depths);
+        assert!(leaf_depths.windows(2).all(|pair| pair[0] == pair[1]));
+
+        let mut iterator = tree.iter();
+        let mut keys = Vec::new();
+        while let Some((key, _)) = iterator.next_entry() {
+            keys.push(key.clone());
+        }
+        assert_eq!(keys.len(), tree.len());
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_random_inserts_are_iterated_in_order() {
+        let mut rng = StdRng::seed_from_u64(2294);
+        let tree = random_tree(&mut rng);
+        assert_eq!(tree.len(), NUM_KEYS as usize);
+        check_invariants(&tree);
+
+        let mut iterator = tree.iter();
+        for key in 0..NUM_KEYS {
+            assert!(iterator.has_next());
+            assert_eq!(IndexIterator::next(&mut iterator), Some(rid(key)));
+        }
+        assert!(!iterator.has_next());
+        assert_eq!(IndexIterator::next(&mut iterator), None);
+    }
+
+    #[test]
+    fn test_point_lookups() {
+        let mut rng = StdRng::seed_from_u64(2294);
+        let mut tree = random_tree(&mut rng);
+
+        for key in 0..NUM_KEYS {
+            assert_eq!(tree.search(&DataType::Integer(key)), Some(rid(key)));
+        }
+        assert_eq!(tree.search(&DataType::Integer(NUM_KEYS)), None);
+        assert_eq!(tree.search(&DataType::Integer(-1)), None);
+
+        // Duplicate and incomparable keys are rejected rather than inserted
+        assert!(matches!(
+            tree.insert(DataType::Integer(7), rid(7)),
+            Err(IndexError::CreationError(_))
+        ));
+        assert!(matches!(
+            tree.insert(DataType::Text("seven".to_string()), rid(7)),
+            Err(IndexError::CreationError(_))
+        ));
+        assert_eq!(tree.search(&DataType::Text("seven".to_string())), None);
+        assert_eq!(tree.len(), NUM_KEYS as usize);
+        assert!(matches!(
+            BPlusTree::new(2),
+            Err(IndexError::CreationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_deletions_merge_underflowing_nodes() {
+        let mut rng = StdRng::seed_from_u64(2294);
+        let mut tree = random_tree(&mut rng);
+        let nodes_before = tree.nodes.len() - tree.free.len();
+
+        let mut keys: Vec<i32> = (0..NUM_KEYS).filter(|key| key % 2 == 0).collect();
+        keys.shuffle(&mut rng);
+        for key in keys {
+            assert_eq!(tree.delete(&DataType::Integer(key)).unwrap(), rid(key));
+            check_invariants(&tree);
+        }
+
+        assert_eq!(tree.len(), NUM_KEYS as usize / 2);
+        assert!(tree.nodes.len() - tree.free.len() < nodes_before);
+        for key in 0..NUM_KEYS {
+            let expected = (key % 2 == 1).then(|| rid(key));
+            assert_eq!(tree.search(&DataType::Integer(key)), expected);
+        }
+        assert!(matches!(
+            tree.delete(&DataType::Integer(0)),
+            Err(IndexError::NotFoundError(_))
+        ));
+
+        // Emptying the tree collapses it back to a single leaf
+        for key in (0..NUM_KEYS).filter(|key| key % 2 == 1) {
+            tree.delete(&DataType::Integer(key)).unwrap();
+        }
+        assert!(tree.is_empty());
+        assert!(matches!(tree.nodes[tree.root], Node::Leaf { .. }));
+        assert!(!tree.iter().has_next());
+    }
+
+    #[test]
+    fn test_range_queries() {
+        let mut rng = StdRng::seed_from_u64(2294);
+        let tree = random_tree(&mut rng);
+        let collect = |lo: i32, hi: i32| {
+            let mut iterator = tree
+                .range(&DataType::Integer(lo), &DataType::Integer(hi))
+                .unwrap();
+            let mut rids = Vec::new();
+            while let Some(rid) = IndexIterator::next(&mut iterator) {
+                rids.push(rid);
+            }
+            rids
+        };
+
+        assert_eq!(collect(100, 199), (100..200).map(rid).collect::<Vec<_>>());
+        assert_eq!(collect(-50, 2), [rid(0), rid(1), rid(2)]);
+        assert_eq!(collect(998, 5000), [rid(998), rid(999)]);
+        assert_eq!(collect(500, 500), [rid(500)]);
+
+        // Empty ranges
+        assert!(collect(500, 400).is_empty());
+        assert!(collect(NUM_KEYS, NUM_KEYS + 10).is_empty());
+        assert!(tree
+            .range(
+                &DataType::Text("a".to_string()),
+                &DataType::Text("z".to_string())
+            )
+            .is_err());
+    }
+}
@@ -7,11 +7,3 @@ pub trait IndexIterator {
     /// Checks if the iterator has more elements.
     fn has_next(&self) -> bool;
 }
-
-// pub struct BTreeIndexIterator {
-//     // Implementation-specific fields...
-// }
-
-// impl IndexIterator for BTreeIndexIterator {
-//     // Implement the methods...
-// }
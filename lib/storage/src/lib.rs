@@ -6,7 +6,9 @@ pub mod lsm;
 pub mod page;
 pub mod slotted_page;
 pub mod table;
+pub mod wal;
 
 pub use disk::*;
 pub use page::*;
 pub use table::*;
+pub use wal::*;
@@ -0,0 +1,60 @@
+use super::{search_keys, sstable, Entry, LsmError};
+use ty::DataType;
+
+/// The in-memory write buffer of an [`LsmTree`](super::LsmTree), holding the latest entry of
+/// each key in key order until it is flushed to an [`SsTable`](super::SsTable).
+#[derive(Debug, Default)]
+pub struct MemTable {
+    keys: Vec<DataType>,
+    entries: Vec<Entry>,
+    /// The encoded size of the entries, i.e. roughly the size of the SSTable they flush to
+    size: usize,
+}
+
+impl MemTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts an entry, replacing any previous entry of the key.
+    pub fn insert(&mut self, key: DataType, entry: Entry) -> Result<(), LsmError> {
+        let size = sstable::encoded_size(&key, &entry)?;
+        match search_keys(&self.keys, &key)? {
+            Ok(position) => {
+                let previous = std::mem::replace(&mut self.entries[position], entry);
+                self.size -= sstable::encoded_size(&key, &previous)?;
+            }
+            Err(position) => {
+                self.keys.insert(position, key);
+                self.entries.insert(position, entry);
+            }
+        }
+        self.size += size;
+        Ok(())
+    }
+
+    /// Returns the entry of `key`, which is `Some(None)` if the key was deleted.
+    pub fn get(&self, key: &DataType) -> Result<Option<&Entry>, LsmError> {
+        Ok(search_keys(&self.keys, key)?
+            .ok()
+            .map(|position| &self.entries[position]))
+    }
+
+    /// Returns the entries in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&DataType, &Entry)> {
+        self.keys.iter().zip(&self.entries)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns the encoded size of the entries in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
@@ -1 +1,539 @@
+//! # LSM Tree
+//!
+//! A log-structured merge tree buffers writes in a sorted, in-memory [`MemTable`] and flushes
+//! it to an immutable, on-disk [`SsTable`] once it grows past a size threshold. Deletes are
+//! recorded as tombstones, so that they shadow older entries of the key in earlier segments.
+//!
+//! Reads check the memtable first, then the SSTables from newest to oldest, stopping at the
+//! first entry of the key. Compaction merges the newest segments into one, keeping only the
+//! newest entry of each key and dropping tombstones once no older segment may hold their key.
+//!
+//! The segments are listed in a manifest page, rewritten whenever a flush or a compaction
+//! changes them, so that a tree can be reopened from its manifest after a restart. The
+//! manifest holds a `u16` count of the segments followed by the `u32` id of the first page of
+//! each segment, newest first. Entries still in the memtable are lost unless flushed.
+
 pub mod block;
+mod memtable;
+mod sstable;
+
+pub use memtable::MemTable;
+pub use sstable::{SsTable, MAX_ENTRY_SIZE};
+
+use crate::disk::DiskManagerRef;
+use common::USABLE_PAGE_SIZE;
+use parking_lot::{Mutex, RwLock};
+use std::cmp::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{debug, error, instrument};
+use ty::DataType;
+use typed_builder::TypedBuilder;
+
+/// The entry of a key: its value, or `None` for a tombstone marking the key as deleted.
+pub type Entry = Option<Vec<u8>>;
+
+#[derive(Error, Debug)]
+pub enum LsmError {
+    #[error("Key {0} can't be ordered against the keys of the tree")]
+    IncomparableKey(String),
+
+    #[error("Entry of {size} bytes exceeds the maximum of {max} bytes")]
+    EntryTooLarge { size: usize, max: usize },
+
+    #[error("Failed to encode the entry: {0}")]
+    EncodingError(String),
+
+    #[error("Corrupted SSTable page {page_id}: {reason}")]
+    CorruptedPage { page_id: u32, reason: String },
+
+    #[error("Disk Manager Error: {0}")]
+    DiskManagerError(String),
+
+    #[error("The manifest can't list more than {max} segments")]
+    TooManySegments { max: usize },
+
+    #[error("The LSM tree must be opened before it is written to")]
+    NotOpened,
+}
+
+/// Binary searches sorted keys, failing if `key` can't be ordered against them.
+fn search_keys(keys: &[DataType], key: &DataType) -> Result<Result<usize, usize>, LsmError> {
+    let mut comparable = true;
+    let position = keys.binary_search_by(|probe| {
+        probe.partial_cmp(key).unwrap_or_else(|| {
+            comparable = false;
+            Ordering::Equal
+        })
+    });
+
+    if comparable {
+        Ok(position)
+    } else {
+        Err(LsmError::IncomparableKey(key.to_string()))
+    }
+}
+
+/// A key-value store of [`DataType`] keys and byte values, backed by a [`DiskManager`].
+///
+/// [`DiskManager`]: crate::disk::DiskManager
+#[derive(Debug, TypedBuilder)]
+pub struct LsmTree {
+    disk_manager: DiskManagerRef,
+    /// The encoded size in bytes at which the memtable is flushed to an SSTable
+    #[builder(default = LsmTree::DEFAULT_MEMTABLE_CAPACITY)]
+    memtable_capacity: usize,
+    /// The number of segments a compaction merges, once there are at least as many
+    #[builder(default = LsmTree::DEFAULT_COMPACTION_FAN_IN)]
+    compaction_fan_in: usize,
+    #[builder(default, setter(skip))]
+    memtable: RwLock<MemTable>,
+    /// The flushed segments, newest first
+    #[builder(default, setter(skip))]
+    segments: RwLock<Vec<Arc<SsTable>>>,
+    /// Held for the duration of a compaction, so that compactions don't merge the same
+    /// segments concurrently
+    #[builder(default, setter(skip))]
+    compaction_lock: Mutex<()>,
+    /// The page listing the segments, set to reopen an existing tree
+    #[builder(default, setter(strip_option))]
+    manifest_page_id: Option<u32>,
+}
+
+impl LsmTree {
+    pub const DEFAULT_MEMTABLE_CAPACITY: usize = 64 * 1024;
+
+    pub const DEFAULT_COMPACTION_FAN_IN: usize = 4;
+
+    /// The maximum number of segments the manifest page can list.
+    pub const MAX_SEGMENTS: usize = (USABLE_PAGE_SIZE - 2) / 4;
+
+    /// Creates a new, empty tree.
+    pub fn new(disk_manager: DiskManagerRef) -> Result<Self, LsmError> {
+        LsmTree::builder().disk_manager(disk_manager).build().open()
+    }
+
+    /// Opens the tree: loads the segments listed in its manifest if a manifest page was set,
+    /// or writes the empty manifest of a new tree otherwise.
+    pub fn open(mut self) -> Result<Self, LsmError> {
+        let segments = match self.manifest_page_id {
+            Some(manifest_page_id) => self.read_manifest(manifest_page_id)?,
+            None => {
+                let manifest_page_id = self
+                    .disk_manager
+                    .allocate_page()
+                    .map_err(|e| LsmError::DiskManagerError(e.to_string()))?;
+                self.manifest_page_id = Some(manifest_page_id);
+                self.write_manifest(&[])?;
+                Vec::new()
+            }
+        };
+        debug!(
+            "Opened LSM tree of {} segments from manifest page {:?}",
+            segments.len(),
+            self.manifest_page_id
+        );
+        *self.segments.get_mut() = segments;
+        Ok(self)
+    }
+
+    /// Returns the page listing the segments of the tree, from which it can be reopened.
+    pub fn manifest_page_id(&self) -> Option<u32> {
+        self.manifest_page_id
+    }
+
+    fn read_manifest(&self, manifest_page_id: u32) -> Result<Vec<Arc<SsTable>>, LsmError> {
+        let page = self
+            .disk_manager
+            .read_data(manifest_page_id)
+            .map_err(|e| LsmError::DiskManagerError(e.to_string()))?;
+        let num_segments = u16::from_be_bytes([page[0], page[1]]) as usize;
+        if num_segments > Self::MAX_SEGMENTS {
+            return Err(LsmError::CorruptedPage {
+                page_id: manifest_page_id,
+                reason: format!("manifest of {} segments", num_segments),
+            });
+        }
+
+        page[2..2 + 4 * num_segments]
+            .chunks_exact(4)
+            .map(|id| {
+                let first_page_id = u32::from_be_bytes([id[0], id[1], id[2], id[3]]);
+                SsTable::open(self.disk_manager.clone(), first_page_id).map(Arc::new)
+            })
+            .collect()
+    }
+
+    /// Rewrites the manifest to list `segments`, which must be called with the segments
+    /// locked so that concurrent rewrites can't be reordered.
+    fn write_manifest(&self, segments: &[Arc<SsTable>]) -> Result<(), LsmError> {
+        let manifest_page_id = self.manifest_page_id.ok_or(LsmError::NotOpened)?;
+        if segments.len() > Self::MAX_SEGMENTS {
+            return Err(LsmError::TooManySegments {
+                max: Self::MAX_SEGMENTS,
+            });
+        }
+
+        let mut page = Vec::with_capacity(2 + 4 * segments.len());
+        page.extend_from_slice(&(segments.len() as u16).to_be_bytes());
+        for segment in segments {
+            page.extend_from_slice(&segment.first_page_id().to_be_bytes());
+        }
+        self.disk_manager
+            .write_data(manifest_page_id, &page)
+            .map_err(|e| LsmError::DiskManagerError(e.to_string()))
+    }
+
+    /// Sets the value of `key`.
+    pub fn put(&self, key: DataType, value: Vec<u8>) -> Result<(), LsmError> {
+        self.write(key, Some(value))
+    }
+
+    /// Deletes `key` by writing a tombstone for it.
+    pub fn delete(&self, key: DataType) -> Result<(), LsmError> {
+        self.write(key, None)
+    }
+
+    fn write(&self, key: DataType, entry: Entry) -> Result<(), LsmError> {
+        let mut memtable = self.memtable.write();
+        memtable.insert(key, entry)?;
+        if memtable.size() >= self.memtable_capacity {
+            self.flush_memtable(&mut memtable)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the value of `key`, or `None` if it was never set or was deleted.
+    pub fn get(&self, key: &DataType) -> Result<Option<Vec<u8>>, LsmError> {
+        if let Some(entry) = self.memtable.read().get(key)? {
+            return Ok(entry.clone());
+        }
+
+        // A flush moves entries to a segment before clearing them from the memtable, so an
+        // entry missed above is found here
+        let segments = self.segments.read().clone();
+        for segment in segments {
+            if let Some(entry) = segment.get(key)? {
+                return Ok(entry);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Flushes the memtable to a new SSTable, regardless of its size.
+    pub fn flush(&self) -> Result<(), LsmError> {
+        let mut memtable = self.memtable.write();
+        self.flush_memtable(&mut memtable)
+    }
+
+    #[instrument(skip_all)]
+    fn flush_memtable(&self, memtable: &mut MemTable) -> Result<(), LsmError> {
+        if memtable.is_empty() {
+            return Ok(());
+        }
+
+        let segment = SsTable::write(self.disk_manager.clone(), memtable.iter())?;
+        debug!(
+            "Flushed {} memtable entries to an SSTable of {} pages",
+            memtable.len(),
+            segment.num_pages()
+        );
+        let segment = Arc::new(segment);
+        let mut segments = self.segments.write();
+        segments.insert(0, segment.clone());
+        if let Err(e) = self.write_manifest(&segments) {
+            // The entries stay in the memtable, and the pages of the segment are freed
+            segments.remove(0);
+            segment.mark_obsolete();
+            return Err(e);
+        }
+        drop(segments);
+        *memtable = MemTable::new();
+        Ok(())
+    }
+
+    /// Returns the number of flushed segments.
+    pub fn num_segments(&self) -> usize {
+        self.segments.read().len()
+    }
+
+    /// Merges the newest `compaction_fan_in` segments into one, if there are at least as
+    /// many. The newest entry of each key is kept, and tombstones are dropped unless a segment
+    /// older than the merged ones may still hold their key. Returns whether segments were
+    /// merged.
+    #[instrument(skip_all)]
+    pub fn compact(&self) -> Result<bool, LsmError> {
+        let _compaction = self.compaction_lock.lock();
+        let fan_in = self.compaction_fan_in.max(2);
+
+        // Flushes only ever add segments in front, so the merged segments stay contiguous
+        let (merged, older) = {
+            let segments = self.segments.read();
+            if segments.len() < fan_in {
+                return Ok(false);
+            }
+            (segments[..fan_in].to_vec(), segments[fan_in..].to_vec())
+        };
+
+        // Entries sorted by key and then by age, so that the newest entry of a key is first
+        let mut entries = Vec::new();
+        for (age, segment) in merged.iter().enumerate() {
+            entries.extend(
+                segment
+                    .entries()?
+                    .into_iter()
+                    .map(|(key, entry)| (key, age, entry)),
+            );
+        }
+        let mut incomparable = None;
+        entries.sort_by(|(a, a_age, _), (b, b_age, _)| match a.partial_cmp(b) {
+            Some(Ordering::Equal) => a_age.cmp(b_age),
+            Some(ordering) => ordering,
+            None => {
+                incomparable.get_or_insert_with(|| b.clone());
+                Ordering::Equal
+            }
+        });
+        if let Some(key) = incomparable {
+            return Err(LsmError::IncomparableKey(key.to_string()));
+        }
+        entries.dedup_by(|(a, ..), (b, ..)| (*a).partial_cmp(&*b) == Some(Ordering::Equal));
+
+        let num_entries = entries.len();
+        let entries: Vec<(DataType, Entry)> = entries
+            .into_iter()
+            .filter(|(key, _, entry)| {
+                entry.is_some() || older.iter().any(|segment| segment.may_contain(key))
+            })
+            .map(|(key, _, entry)| (key, entry))
+            .collect();
+        debug!(
+            "Merging {} segments, dropping {} tombstones",
+            fan_in,
+            num_entries - entries.len()
+        );
+
+        let compacted = if entries.is_empty() {
+            None
+        } else {
+            let segment = SsTable::write(
+                self.disk_manager.clone(),
+                entries.iter().map(|(key, entry)| (key, entry)),
+            )?;
+            Some(Arc::new(segment))
+        };
+
+        let mut segments = self.segments.write();
+        let start = segments
+            .iter()
+            .position(|segment| Arc::ptr_eq(segment, &merged[0]))
+            .expect("Merged segments are only removed by compaction");
+        let replaced: Vec<_> = segments
+            .splice(start..start + fan_in, compacted.clone())
+            .collect();
+        if let Err(e) = self.write_manifest(&segments) {
+            // The manifest still lists the merged segments, so keep them in place
+            segments.splice(start..start + compacted.iter().len(), replaced);
+            if let Some(compacted) = compacted {
+                compacted.mark_obsolete();
+            }
+            return Err(e);
+        }
+        drop(segments);
+
+        for segment in merged {
+            segment.mark_obsolete();
+        }
+        Ok(true)
+    }
+
+    /// Spawns a task compacting the tree every `interval`, until the tree is dropped.
+    pub fn start_compaction_task(self: &Arc<Self>, interval: Duration) {
+        let tree = Arc::downgrade(self);
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let Some(tree) = tree.upgrade() else {
+                    break;
+                };
+
+                // Compactions read and write whole segments, so keep them off the runtime
+                match tokio::task::spawn_blocking(move || tree.compact()).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => error!("Failed to compact the LSM tree: {}", e),
+                    Err(e) => error!("Compaction task panicked: {}", e),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::setup_dm;
+
+    fn value(key: i32) -> Vec<u8> {
+        key.to_be_bytes().repeat(8)
+    }
+
+    #[test]
+    fn test_reads_are_served_from_the_memtable() {
+        let (dm, _temp_dir) = setup_dm();
+        let tree = LsmTree::new(dm).unwrap();
+
+        tree.put(DataType::Integer(1), value(1)).unwrap();
+        tree.put(DataType::Integer(2), value(2)).unwrap();
+        tree.put(DataType::Integer(1), value(10)).unwrap();
+        tree.delete(DataType::Integer(2)).unwrap();
+
+        assert_eq!(tree.num_segments(), 0);
+        assert_eq!(tree.get(&DataType::Integer(1)).unwrap(), Some(value(10)));
+        assert_eq!(tree.get(&DataType::Integer(2)).unwrap(), None);
+        assert_eq!(tree.get(&DataType::Integer(3)).unwrap(), None);
+        assert!(matches!(
+            tree.put(DataType::Text("one".to_string()), value(1)),
+            Err(LsmError::IncomparableKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_reads_are_served_from_flushed_sstables() {
+        let (dm, _temp_dir) = setup_dm();
+        let tree = LsmTree::builder()
+            .disk_manager(dm)
+            .memtable_capacity(4096)
+            .build()
+            .open()
+            .unwrap();
+
+        for key in 0..500 {
+            tree.put(DataType::Integer(key), value(key)).unwrap();
+        }
+        assert!(tree.num_segments() > 1);
+        assert!(tree.memtable.read().size() < 4096);
+        for key in 0..500 {
+            assert_eq!(tree.get(&DataType::Integer(key)).unwrap(), Some(value(key)));
+        }
+
+        // Newer entries in the memtable shadow the flushed ones
+        tree.put(DataType::Integer(7), value(70)).unwrap();
+        tree.delete(DataType::Integer(8)).unwrap();
+        assert_eq!(tree.get(&DataType::Integer(7)).unwrap(), Some(value(70)));
+        assert_eq!(tree.get(&DataType::Integer(8)).unwrap(), None);
+        tree.flush().unwrap();
+        assert!(tree.memtable.read().is_empty());
+        assert_eq!(tree.get(&DataType::Integer(7)).unwrap(), Some(value(70)));
+        assert_eq!(tree.get(&DataType::Integer(8)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_compaction_drops_tombstones_once_no_older_segment_holds_the_key() {
+        let (dm, _temp_dir) = setup_dm();
+        let tree = LsmTree::builder()
+            .disk_manager(dm.clone())
+            .compaction_fan_in(2)
+            .build()
+            .open()
+            .unwrap();
+
+        for key in 0..20 {
+            tree.put(DataType::Integer(key), value(key)).unwrap();
+        }
+        tree.flush().unwrap();
+        tree.delete(DataType::Integer(7)).unwrap();
+        tree.flush().unwrap();
+        tree.put(DataType::Integer(100), value(100)).unwrap();
+        tree.flush().unwrap();
+        assert_eq!(tree.num_segments(), 3);
+
+        // The oldest segment still holds the key, so its tombstone must be kept
+        assert!(tree.compact().unwrap());
+        assert_eq!(tree.num_segments(), 2);
+        assert_eq!(tree.segments.read()[0].len(), 2);
+        assert_eq!(tree.get(&DataType::Integer(7)).unwrap(), None);
+
+        assert!(tree.compact().unwrap());
+        assert_eq!(tree.num_segments(), 1);
+        assert_eq!(tree.segments.read()[0].len(), 20);
+        assert!(!tree.compact().unwrap());
+
+        assert_eq!(tree.get(&DataType::Integer(7)).unwrap(), None);
+        assert_eq!(tree.get(&DataType::Integer(8)).unwrap(), Some(value(8)));
+        assert_eq!(tree.get(&DataType::Integer(100)).unwrap(), Some(value(100)));
+        // The pages of the merged segments are freed, and the second compaction reused one
+        // freed by the first
+        assert_eq!(dm.num_free_pages(), 3);
+    }
+
+    #[test]
+    fn test_flushed_segments_are_found_after_reopening_the_tree() {
+        let (dm, _temp_dir) = setup_dm();
+        let tree = LsmTree::builder()
+            .disk_manager(dm.clone())
+            .memtable_capacity(4096)
+            .compaction_fan_in(2)
+            .build()
+            .open()
+            .unwrap();
+        for key in 0..500 {
+            tree.put(DataType::Integer(key), value(key)).unwrap();
+        }
+        tree.delete(DataType::Integer(8)).unwrap();
+        tree.flush().unwrap();
+        let manifest_page_id = tree.manifest_page_id().unwrap();
+
+        let reopen = || {
+            LsmTree::builder()
+                .disk_manager(dm.clone())
+                .manifest_page_id(manifest_page_id)
+                .build()
+                .open()
+                .unwrap()
+        };
+        let reopened = reopen();
+        assert_eq!(reopened.num_segments(), tree.num_segments());
+        assert_eq!(reopened.get(&DataType::Integer(7)).unwrap(), Some(value(7)));
+        assert_eq!(reopened.get(&DataType::Integer(8)).unwrap(), None);
+        assert_eq!(
+            reopened.get(&DataType::Integer(499)).unwrap(),
+            Some(value(499))
+        );
+
+        // Compactions rewrite the manifest as well
+        while tree.compact().unwrap() {}
+        let reopened = reopen();
+        assert_eq!(reopened.num_segments(), 1);
+        for key in (0..500).filter(|&key| key != 8) {
+            assert_eq!(
+                reopened.get(&DataType::Integer(key)).unwrap(),
+                Some(value(key))
+            );
+        }
+        assert_eq!(reopened.get(&DataType::Integer(8)).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_background_compaction() {
+        let (dm, _temp_dir) = setup_dm();
+        let tree = Arc::new(
+            LsmTree::builder()
+                .disk_manager(dm)
+                .compaction_fan_in(2)
+                .build()
+                .open()
+                .unwrap(),
+        );
+        for key in 0..2 {
+            tree.put(DataType::Integer(key), value(key)).unwrap();
+            tree.flush().unwrap();
+        }
+
+        tree.start_compaction_task(Duration::from_millis(10));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(tree.num_segments(), 1);
+        assert_eq!(tree.get(&DataType::Integer(1)).unwrap(), Some(value(1)));
+    }
+}
@@ -0,0 +1,357 @@
+//! # SSTable
+//!
+//! An [`SsTable`] is an immutable, sorted segment of an [`LsmTree`](super::LsmTree), written to
+//! pages of the [`DiskManager`](crate::disk::DiskManager) when the memtable is flushed or when
+//! segments are compacted.
+//!
+//! Entries never span pages, so that a lookup reads a single page. Each page holds a `u16`
+//! count of its entries and the `u32` id of the next page of the segment (`u32::MAX` on the
+//! last page), followed by the entries, encoded as:
+//!
+//! ```text
+//! | key len (u16) | key (JSON) | tag (u8) | value len (u16) | value |
+//! ```
+//!
+//! where the tag is `1` for a value and `0` for a tombstone (whose value is empty).
+
+use super::{search_keys, Entry, LsmError};
+use crate::disk::DiskManagerRef;
+use common::USABLE_PAGE_SIZE;
+use std::cmp::Ordering;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use tracing::{debug, warn};
+use ty::DataType;
+
+/// The size of the entry count and next page id at the start of each page.
+const PAGE_HEADER_SIZE: usize = 6;
+
+/// The next page id of the last page of a segment.
+const NO_NEXT_PAGE: u32 = u32::MAX;
+
+/// The size of the key length, tag and value length of an encoded entry.
+const ENTRY_HEADER_SIZE: usize = 5;
+
+/// The largest encoded entry a page can hold.
+pub const MAX_ENTRY_SIZE: usize = USABLE_PAGE_SIZE - PAGE_HEADER_SIZE;
+
+const TOMBSTONE_TAG: u8 = 0;
+const VALUE_TAG: u8 = 1;
+
+fn encode_key(key: &DataType) -> Result<Vec<u8>, LsmError> {
+    serde_json::to_vec(key).map_err(|e| LsmError::EncodingError(e.to_string()))
+}
+
+/// Returns the encoded size of an entry, failing if it can't fit in a page.
+pub fn encoded_size(key: &DataType, entry: &Entry) -> Result<usize, LsmError> {
+    let size = ENTRY_HEADER_SIZE + encode_key(key)?.len() + entry.as_ref().map_or(0, Vec::len);
+    if size > MAX_ENTRY_SIZE {
+        return Err(LsmError::EntryTooLarge {
+            size,
+            max: MAX_ENTRY_SIZE,
+        });
+    }
+    Ok(size)
+}
+
+fn encode_entry(page: &mut Vec<u8>, key: &[u8], entry: &Entry) {
+    page.extend_from_slice(&(key.len() as u16).to_be_bytes());
+    page.extend_from_slice(key);
+    match entry {
+        Some(value) => {
+            page.push(VALUE_TAG);
+            page.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            page.extend_from_slice(value);
+        }
+        None => {
+            page.push(TOMBSTONE_TAG);
+            page.extend_from_slice(&0u16.to_be_bytes());
+        }
+    }
+}
+
+/// The entries of a page, in key order, and the id of the next page of the segment (if any).
+type DecodedPage = (Vec<(DataType, Entry)>, Option<u32>);
+
+/// Decodes the entries and the next page id of a page.
+fn decode_page(page_id: u32, page: &[u8]) -> Result<DecodedPage, LsmError> {
+    let corrupted = |reason: &str| LsmError::CorruptedPage {
+        page_id,
+        reason: reason.to_string(),
+    };
+    let mut offset = 0;
+    let mut take = |len: usize| -> Result<&[u8], LsmError> {
+        let bytes = page
+            .get(offset..offset + len)
+            .ok_or_else(|| corrupted("entry extends past the end of the page"))?;
+        offset += len;
+        Ok(bytes)
+    };
+    let read_u16 = |bytes: &[u8]| u16::from_be_bytes([bytes[0], bytes[1]]) as usize;
+
+    let num_entries = read_u16(take(2)?);
+    let next_page = take(4)?;
+    let next_page = u32::from_be_bytes([next_page[0], next_page[1], next_page[2], next_page[3]]);
+    let mut entries = Vec::with_capacity(num_entries);
+    for _ in 0..num_entries {
+        let key_len = read_u16(take(2)?);
+        let key = serde_json::from_slice(take(key_len)?)
+            .map_err(|e| corrupted(&format!("undecodable key ({})", e)))?;
+        let tag = take(1)?[0];
+        let value_len = read_u16(take(2)?);
+        let value = take(value_len)?;
+        let entry = match tag {
+            VALUE_TAG => Some(value.to_vec()),
+            TOMBSTONE_TAG => None,
+            _ => return Err(corrupted(&format!("unknown entry tag {}", tag))),
+        };
+        entries.push((key, entry));
+    }
+    Ok((entries, (next_page != NO_NEXT_PAGE).then_some(next_page)))
+}
+
+/// An immutable, sorted run of entries stored on disk.
+#[derive(Debug)]
+pub struct SsTable {
+    disk_manager: DiskManagerRef,
+    /// The first key of each page
+    first_keys: Vec<DataType>,
+    page_ids: Vec<u32>,
+    last_key: DataType,
+    len: usize,
+    /// Set once the segment is compacted away, so that its pages are deallocated when the
+    /// last reader drops it
+    obsolete: AtomicBool,
+}
+
+impl SsTable {
+    /// Writes entries, which must be sorted by key and non-empty, to newly allocated pages.
+    pub fn write<'a>(
+        disk_manager: DiskManagerRef,
+        entries: impl IntoIterator<Item = (&'a DataType, &'a Entry)>,
+    ) -> Result<Self, LsmError> {
+        let mut pages: Vec<(DataType, Vec<u8>, u16)> = Vec::new();
+        let mut last_key = None;
+        let mut len = 0;
+
+        // Pack the entries into pages before allocating any of them
+        for (key, entry) in entries {
+            let encoded_key = encode_key(key)?;
+            let size = encoded_size(key, entry)?;
+            match pages.last_mut() {
+                Some((_, page, count)) if page.len() + size <= USABLE_PAGE_SIZE => {
+                    encode_entry(page, &encoded_key, entry);
+                    *count += 1;
+                }
+                _ => {
+                    let mut page = vec![0; PAGE_HEADER_SIZE];
+                    encode_entry(&mut page, &encoded_key, entry);
+                    pages.push((key.clone(), page, 1));
+                }
+            }
+            last_key = Some(key.clone());
+            len += 1;
+        }
+        let last_key = last_key.ok_or_else(|| {
+            LsmError::EncodingError("SSTables must hold at least one entry".to_string())
+        })?;
+
+        // Each page links to the next one, so the segment can be reopened from its first page
+        let page_ids = (0..pages.len())
+            .map(|_| disk_manager.allocate_page())
+            .collect::<anyhow::Result<Vec<u32>>>()
+            .map_err(|e| LsmError::DiskManagerError(e.to_string()))?;
+        let mut first_keys = Vec::with_capacity(pages.len());
+        for (index, (first_key, mut page, count)) in pages.into_iter().enumerate() {
+            let next_page = page_ids.get(index + 1).copied().unwrap_or(NO_NEXT_PAGE);
+            page[..2].copy_from_slice(&count.to_be_bytes());
+            page[2..PAGE_HEADER_SIZE].copy_from_slice(&next_page.to_be_bytes());
+            disk_manager
+                .write_data(page_ids[index], &page)
+                .map_err(|e| LsmError::DiskManagerError(e.to_string()))?;
+            first_keys.push(first_key);
+        }
+
+        debug!("Wrote SSTable of {} entries to pages {:?}", len, page_ids);
+        Ok(Self {
+            disk_manager,
+            first_keys,
+            page_ids,
+            last_key,
+            len,
+            obsolete: AtomicBool::new(false),
+        })
+    }
+
+    /// Reopens the segment whose first page is `first_page_id`, following the links between
+    /// its pages.
+    pub fn open(disk_manager: DiskManagerRef, first_page_id: u32) -> Result<Self, LsmError> {
+        let mut first_keys = Vec::new();
+        let mut page_ids = Vec::new();
+        let mut last_key = None;
+        let mut len = 0;
+
+        let mut next_page = Some(first_page_id);
+        while let Some(page_id) = next_page {
+            let page = disk_manager
+                .read_data(page_id)
+                .map_err(|e| LsmError::DiskManagerError(e.to_string()))?;
+            let (entries, next) = decode_page(page_id, &page)?;
+            let (Some((first_key, _)), Some((key, _))) = (entries.first(), entries.last()) else {
+                return Err(LsmError::CorruptedPage {
+                    page_id,
+                    reason: "SSTable page without entries".to_string(),
+                });
+            };
+            first_keys.push(first_key.clone());
+            last_key = Some(key.clone());
+            page_ids.push(page_id);
+            len += entries.len();
+            next_page = next;
+        }
+
+        debug!("Opened SSTable of {} entries on pages {:?}", len, page_ids);
+        Ok(Self {
+            disk_manager,
+            first_keys,
+            page_ids,
+            last_key: last_key.expect("SSTables have at least one page"),
+            len,
+            obsolete: AtomicBool::new(false),
+        })
+    }
+
+    /// Returns the id of the first page of the segment, from which it can be reopened.
+    pub fn first_page_id(&self) -> u32 {
+        self.page_ids[0]
+    }
+
+    /// Returns the number of entries, including tombstones.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn num_pages(&self) -> usize {
+        self.page_ids.len()
+    }
+
+    /// Returns whether `key` is within the key range of the segment.
+    pub fn may_contain(&self, key: &DataType) -> bool {
+        matches!(
+            self.first_keys[0].partial_cmp(key),
+            Some(Ordering::Less | Ordering::Equal)
+        ) && matches!(
+            key.partial_cmp(&self.last_key),
+            Some(Ordering::Less | Ordering::Equal)
+        )
+    }
+
+    fn read_page(&self, index: usize) -> Result<Vec<(DataType, Entry)>, LsmError> {
+        let page_id = self.page_ids[index];
+        let page = self
+            .disk_manager
+            .read_data(page_id)
+            .map_err(|e| LsmError::DiskManagerError(e.to_string()))?;
+        Ok(decode_page(page_id, &page)?.0)
+    }
+
+    /// Returns the entry of `key`, which is `Some(None)` if the segment holds a tombstone for
+    /// it. Only the page that would hold the key is read.
+    pub fn get(&self, key: &DataType) -> Result<Option<Entry>, LsmError> {
+        if !self.may_contain(key) {
+            return Ok(None);
+        }
+
+        // The key can only be on the last page starting at or before it
+        let index = match search_keys(&self.first_keys, key)? {
+            Ok(index) => index,
+            Err(index) => index - 1,
+        };
+        let mut entries = self.read_page(index)?;
+        let keys: Vec<DataType> = entries.iter().map(|(key, _)| key.clone()).collect();
+        Ok(search_keys(&keys, key)?
+            .ok()
+            .map(|position| entries.swap_remove(position).1))
+    }
+
+    /// Reads every entry, in key order.
+    pub fn entries(&self) -> Result<Vec<(DataType, Entry)>, LsmError> {
+        let mut entries = Vec::with_capacity(self.len);
+        for index in 0..self.page_ids.len() {
+            entries.extend(self.read_page(index)?);
+        }
+        Ok(entries)
+    }
+
+    /// Marks the segment as replaced by a compaction (or as never having been added to the
+    /// tree), so that its pages are deallocated once it is dropped.
+    pub(super) fn mark_obsolete(&self) {
+        self.obsolete.store(true, AtomicOrdering::SeqCst);
+    }
+}
+
+impl Drop for SsTable {
+    fn drop(&mut self) {
+        if !self.obsolete.load(AtomicOrdering::SeqCst) {
+            return;
+        }
+        for page_id in &self.page_ids {
+            if let Err(e) = self.disk_manager.deallocate_page(*page_id) {
+                warn!("Failed to deallocate SSTable page {}: {}", page_id, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::setup_dm;
+
+    #[test]
+    fn test_entries_round_trip_across_pages() {
+        let (dm, _temp_dir) = setup_dm();
+        let entries: Vec<(DataType, Entry)> = (0..200)
+            .map(|key| {
+                let entry = (key % 10 != 0).then(|| vec![key as u8; 100]);
+                (DataType::Integer(key), entry)
+            })
+            .collect();
+
+        let sstable = SsTable::write(dm, entries.iter().map(|(key, entry)| (key, entry))).unwrap();
+        assert_eq!(sstable.len(), 200);
+        assert!(sstable.num_pages() > 1);
+        assert_eq!(sstable.entries().unwrap(), entries);
+
+        assert_eq!(
+            sstable.get(&DataType::Integer(123)).unwrap(),
+            Some(Some(vec![123; 100]))
+        );
+        assert_eq!(sstable.get(&DataType::Integer(120)).unwrap(), Some(None));
+        assert_eq!(sstable.get(&DataType::Integer(200)).unwrap(), None);
+        assert!(sstable.may_contain(&DataType::Integer(0)));
+        assert!(!sstable.may_contain(&DataType::Integer(-1)));
+
+        // The segment is found again from its first page
+        let reopened =
+            SsTable::open(sstable.disk_manager.clone(), sstable.first_page_id()).unwrap();
+        assert_eq!(reopened.len(), 200);
+        assert_eq!(reopened.page_ids, sstable.page_ids);
+        assert_eq!(reopened.first_keys, sstable.first_keys);
+        assert_eq!(reopened.last_key, DataType::Integer(199));
+        assert_eq!(reopened.entries().unwrap(), entries);
+    }
+
+    #[test]
+    fn test_oversized_entries_are_rejected() {
+        let key = DataType::Integer(1);
+        assert!(encoded_size(&key, &Some(vec![0; 64])).is_ok());
+        assert!(matches!(
+            encoded_size(&key, &Some(vec![0; MAX_ENTRY_SIZE])),
+            Err(LsmError::EntryTooLarge { .. })
+        ));
+    }
+}
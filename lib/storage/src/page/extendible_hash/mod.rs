@@ -1,5 +1,20 @@
+//! # Extendible Hash Table
+//!
+//! A hash index made of three kinds of pages:
+//!
+//! - A [`HeaderPage`] routes the most significant bits of a hash to a directory page.
+//! - A [`DirectoryPage`] maps the least significant `global_depth` bits of a hash to a bucket
+//!   page. Every slot also records the local depth of its bucket: the number of low bits all
+//!   the keys in the bucket share, so `2^(global_depth - local_depth)` slots point to it.
+//! - A [`BucketPage`] stores up to `max_size` `(key, value)` entries.
+//!
+//! A full bucket is split in two on insert, on the bit after its local depth. If its local
+//! depth already equals the global depth, the directory first doubles in size.
+
 use common::PageId;
 use parking_lot::RwLock;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
 const HTABLE_HEADER_PAGE_METADATA_SIZE: usize = std::mem::size_of::<u32>();
 const HTABLE_HEADER_MAX_DEPTH: usize = 9;
@@ -7,28 +22,52 @@ const HTABLE_HEADER_ARRAY_SIZE: usize = 1 << HTABLE_HEADER_MAX_DEPTH;
 const HTABLE_DIRECTORY_MAX_DEPTH: usize = 9;
 const HTABLE_DIRECTORY_ARRAY_SIZE: usize = 1 << HTABLE_DIRECTORY_MAX_DEPTH;
 
-// Assuming MappingType is defined somewhere
-#[derive(Clone, Copy)]
-struct MappingType {
-    // Your key-value pair structure
-}
-
+/// Routes hashes to directory pages by their `max_depth` most significant bits.
 #[derive(Debug, Default, Clone)]
-struct HeaderPage {
-    directory_page_ids: Vec<PageId>,
+pub struct HeaderPage {
+    directory_page_ids: Vec<Option<PageId>>,
     max_depth: u32,
 }
 
 impl HeaderPage {
-    fn new(max_depth: u32) -> Self {
-        // Initialize the header page with default values.
-        todo!()
+    pub fn new(max_depth: u32) -> Self {
+        assert!(
+            max_depth as usize <= HTABLE_HEADER_MAX_DEPTH,
+            "Header depth {} exceeds the maximum of {}",
+            max_depth,
+            HTABLE_HEADER_MAX_DEPTH
+        );
+        Self {
+            directory_page_ids: vec![None; 1 << max_depth],
+            max_depth,
+        }
+    }
+
+    /// Returns the index of the directory the hash belongs to.
+    pub fn hash_to_directory_index(&self, hash: u32) -> usize {
+        match self.max_depth {
+            0 => 0,
+            depth => (hash >> (u32::BITS - depth)) as usize,
+        }
     }
 
-    // ... other methods ...
+    pub fn directory_page_id(&self, directory_idx: usize) -> Option<PageId> {
+        self.directory_page_ids[directory_idx]
+    }
+
+    pub fn set_directory_page_id(&mut self, directory_idx: usize, page_id: PageId) {
+        self.directory_page_ids[directory_idx] = Some(page_id);
+    }
+
+    /// Returns the number of directories the header can route to.
+    pub fn max_size(&self) -> usize {
+        self.directory_page_ids.len()
+    }
 }
 
-struct DirectoryPage {
+/// Maps the `global_depth` least significant bits of hashes to bucket pages.
+#[derive(Debug, Clone)]
+pub struct DirectoryPage {
     max_depth: u32,
     global_depth: u32,
     local_depths: Vec<u8>,
@@ -36,75 +75,353 @@ struct DirectoryPage {
 }
 
 impl DirectoryPage {
-    fn new(max_depth: u32) -> Self {
-        // Initialize the directory page with default values.
-        todo!()
+    /// Creates a directory of global depth 0, whose single slot points to `bucket_page_id`.
+    pub fn new(max_depth: u32, bucket_page_id: PageId) -> Self {
+        assert!(
+            max_depth as usize <= HTABLE_DIRECTORY_MAX_DEPTH,
+            "Directory depth {} exceeds the maximum of {}",
+            max_depth,
+            HTABLE_DIRECTORY_MAX_DEPTH
+        );
+        Self {
+            max_depth,
+            global_depth: 0,
+            local_depths: vec![0],
+            bucket_page_ids: vec![bucket_page_id],
+        }
     }
 
-    // ... other methods ...
+    pub fn hash_to_bucket_index(&self, hash: u32) -> usize {
+        (hash & self.global_depth_mask()) as usize
+    }
+
+    pub fn global_depth_mask(&self) -> u32 {
+        (1u32 << self.global_depth) - 1
+    }
+
+    pub fn bucket_page_id(&self, bucket_idx: usize) -> PageId {
+        self.bucket_page_ids[bucket_idx]
+    }
+
+    pub fn set_bucket_page_id(&mut self, bucket_idx: usize, page_id: PageId) {
+        self.bucket_page_ids[bucket_idx] = page_id;
+    }
+
+    pub fn local_depth(&self, bucket_idx: usize) -> u32 {
+        self.local_depths[bucket_idx] as u32
+    }
+
+    pub fn set_local_depth(&mut self, bucket_idx: usize, local_depth: u32) {
+        self.local_depths[bucket_idx] = local_depth as u8;
+    }
+
+    pub fn global_depth(&self) -> u32 {
+        self.global_depth
+    }
+
+    pub fn max_depth(&self) -> u32 {
+        self.max_depth
+    }
+
+    /// Returns the number of slots in the directory, `2^global_depth`.
+    pub fn size(&self) -> usize {
+        self.bucket_page_ids.len()
+    }
+
+    /// Returns the slot that differs from `bucket_idx` only in the highest bit of its local
+    /// depth, which pointed to the same bucket before the bucket was last split.
+    pub fn split_image_index(&self, bucket_idx: usize) -> usize {
+        match self.local_depth(bucket_idx) {
+            0 => bucket_idx,
+            depth => bucket_idx ^ (1 << (depth - 1)),
+        }
+    }
+
+    /// Doubles the directory, pointing every new slot to the bucket of the slot it mirrors.
+    /// Returns `false` if the directory is already at its maximum depth.
+    pub fn incr_global_depth(&mut self) -> bool {
+        if self.global_depth >= self.max_depth {
+            return false;
+        }
+
+        self.bucket_page_ids.extend_from_within(..);
+        self.local_depths.extend_from_within(..);
+        self.global_depth += 1;
+        true
+    }
+
+    /// Halves the directory, if no bucket is split on the highest bit of the global depth.
+    pub fn decr_global_depth(&mut self) -> bool {
+        if !self.can_shrink() {
+            return false;
+        }
+
+        self.global_depth -= 1;
+        self.bucket_page_ids.truncate(1 << self.global_depth);
+        self.local_depths.truncate(1 << self.global_depth);
+        true
+    }
+
+    pub fn can_shrink(&self) -> bool {
+        self.global_depth > 0
+            && self
+                .local_depths
+                .iter()
+                .all(|depth| (*depth as u32) < self.global_depth)
+    }
 }
 
+/// Stores up to `max_size` `(key, value)` entries with distinct keys.
 #[derive(Debug, Default, Clone)]
-struct BucketPage<KeyType, ValueType> {
+pub struct BucketPage<KeyType, ValueType> {
     size: u32,
     max_size: u32,
     entries: Vec<(KeyType, ValueType)>,
 }
 
-impl<KeyType, ValueType> BucketPage<KeyType, ValueType> {
-    fn new(max_size: u32) -> Self {
-        // Initialize the bucket page with default values.
-        todo!()
+impl<KeyType, ValueType> BucketPage<KeyType, ValueType>
+where
+    KeyType: PartialEq + Clone,
+    ValueType: Clone,
+{
+    pub fn new(max_size: u32) -> Self {
+        Self {
+            size: 0,
+            max_size,
+            entries: Vec::with_capacity(max_size as usize),
+        }
     }
 
-    fn init(&mut self, max_size: u32) {
-        // Initialize the bucket page with default values.
-        todo!()
+    /// Empties the bucket page and sets the number of entries it can hold.
+    pub fn init(&mut self, max_size: u32) {
+        *self = Self::new(max_size);
     }
 
-    fn is_full(&self) -> bool {
-        // Check if the bucket page is full.
-        todo!()
+    pub fn is_full(&self) -> bool {
+        self.size >= self.max_size
     }
 
-    fn is_empty(&self) -> bool {
-        // Check if the bucket page is empty.
-        todo!()
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
     }
 
-    fn insert(&mut self, key: &KeyType, value: &ValueType) -> bool {
-        // Insert a (key, value) pair into the bucket page.
-        todo!()
+    pub fn size(&self) -> u32 {
+        self.size
     }
 
-    fn lookup(&self, key: &KeyType) -> Option<&ValueType> {
-        // Lookup a key in the bucket page.
-        todo!()
+    /// Inserts a `(key, value)` pair, unless the bucket is full or already holds `key`.
+    pub fn insert(&mut self, key: &KeyType, value: &ValueType) -> bool {
+        if self.is_full() || self.lookup(key).is_some() {
+            return false;
+        }
+
+        self.entries.push((key.clone(), value.clone()));
+        self.size += 1;
+        true
+    }
+
+    pub fn lookup(&self, key: &KeyType) -> Option<&ValueType> {
+        self.entries
+            .iter()
+            .find(|(entry_key, _)| entry_key == key)
+            .map(|(_, value)| value)
     }
 
-    fn remove(&mut self, key: &KeyType) -> bool {
-        // Remove a key from the bucket page.
-        todo!()
+    pub fn remove(&mut self, key: &KeyType) -> bool {
+        match self
+            .entries
+            .iter()
+            .position(|(entry_key, _)| entry_key == key)
+        {
+            Some(position) => {
+                self.entries.swap_remove(position);
+                self.size -= 1;
+                true
+            }
+            None => false,
+        }
     }
 
-    // ... other methods ...
+    /// Removes and returns every entry of the bucket.
+    fn drain(&mut self) -> Vec<(KeyType, ValueType)> {
+        self.size = 0;
+        std::mem::take(&mut self.entries)
+    }
 }
 
-// We would also need a struct to represent the entire extendible hash table,
-// which would contain instances of the header, directory, and bucket pages.
-struct ExtendibleHashTable<KeyType, ValueType> {
+/// An in-memory extendible hash table. Pages are identified by their position in the
+/// directory and bucket page lists of the table.
+#[derive(Debug)]
+pub struct ExtendibleHashTable<KeyType, ValueType> {
     header_page: RwLock<HeaderPage>,
     directory_pages: RwLock<Vec<DirectoryPage>>,
     bucket_pages: RwLock<Vec<BucketPage<KeyType, ValueType>>>,
+    directory_max_depth: u32,
+    bucket_max_size: u32,
 }
 
-impl<KeyType, ValueType> ExtendibleHashTable<KeyType, ValueType> {
+impl<KeyType, ValueType> Default for ExtendibleHashTable<KeyType, ValueType>
+where
+    KeyType: Hash + PartialEq + Clone,
+    ValueType: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<KeyType, ValueType> ExtendibleHashTable<KeyType, ValueType>
+where
+    KeyType: Hash + PartialEq + Clone,
+    ValueType: Clone,
+{
+    /// The number of entries in a bucket of a table created with [`ExtendibleHashTable::new`].
+    pub const DEFAULT_BUCKET_MAX_SIZE: u32 = 64;
+
     pub fn new() -> Self {
-        // Initialize the extendible hash table
-        todo!()
+        Self::with_depths(
+            HTABLE_HEADER_MAX_DEPTH as u32,
+            HTABLE_DIRECTORY_MAX_DEPTH as u32,
+            Self::DEFAULT_BUCKET_MAX_SIZE,
+        )
+    }
+
+    /// Creates a table whose header routes to `2^header_max_depth` directories of up to
+    /// `2^directory_max_depth` slots each, pointing to buckets of `bucket_max_size` entries.
+    pub fn with_depths(
+        header_max_depth: u32,
+        directory_max_depth: u32,
+        bucket_max_size: u32,
+    ) -> Self {
+        Self {
+            header_page: RwLock::new(HeaderPage::new(header_max_depth)),
+            directory_pages: RwLock::new(Vec::new()),
+            bucket_pages: RwLock::new(Vec::new()),
+            directory_max_depth,
+            bucket_max_size,
+        }
+    }
+
+    fn hash(key: &KeyType) -> u32 {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish() as u32
+    }
+
+    /// Returns the value stored under `key`.
+    pub fn get_value(&self, key: &KeyType) -> Option<ValueType> {
+        let hash = Self::hash(key);
+        let header = self.header_page.read();
+        let directory_page_id = header.directory_page_id(header.hash_to_directory_index(hash))?;
+
+        let directories = self.directory_pages.read();
+        let directory = &directories[directory_page_id.as_usize()];
+        let bucket_page_id = directory.bucket_page_id(directory.hash_to_bucket_index(hash));
+
+        self.bucket_pages.read()[bucket_page_id.as_usize()]
+            .lookup(key)
+            .cloned()
     }
 
-    // ... other methods for insertion, deletion, search, etc. ...
+    /// Inserts a `(key, value)` pair, splitting the bucket of the key as many times as needed
+    /// to make room for it. Returns `false` if the table already holds `key`, or if the
+    /// bucket can't be split further because the directory is at its maximum depth.
+    pub fn insert(&self, key: &KeyType, value: &ValueType) -> bool {
+        let hash = Self::hash(key);
+        let mut header = self.header_page.write();
+        let mut directories = self.directory_pages.write();
+        let mut buckets = self.bucket_pages.write();
+
+        let directory_idx = header.hash_to_directory_index(hash);
+        let directory_page_id = match header.directory_page_id(directory_idx) {
+            Some(page_id) => page_id,
+            None => {
+                let bucket_page_id = PageId::from(buckets.len());
+                buckets.push(BucketPage::new(self.bucket_max_size));
+                let page_id = PageId::from(directories.len());
+                directories.push(DirectoryPage::new(self.directory_max_depth, bucket_page_id));
+                header.set_directory_page_id(directory_idx, page_id);
+                page_id
+            }
+        };
+        let directory = &mut directories[directory_page_id.as_usize()];
+
+        loop {
+            let bucket_idx = directory.hash_to_bucket_index(hash);
+            let bucket = &mut buckets[directory.bucket_page_id(bucket_idx).as_usize()];
+            if bucket.lookup(key).is_some() {
+                return false;
+            }
+            if !bucket.is_full() {
+                return bucket.insert(key, value);
+            }
+
+            if directory.local_depth(bucket_idx) == directory.global_depth()
+                && !directory.incr_global_depth()
+            {
+                return false;
+            }
+            Self::split_bucket(directory, &mut buckets, bucket_idx, self.bucket_max_size);
+        }
+    }
+
+    /// Splits the bucket at `bucket_idx` (whose local depth must be below the global depth)
+    /// on the bit after its local depth, moving the entries with that bit set to a new bucket.
+    fn split_bucket(
+        directory: &mut DirectoryPage,
+        buckets: &mut Vec<BucketPage<KeyType, ValueType>>,
+        bucket_idx: usize,
+        bucket_max_size: u32,
+    ) {
+        let bucket_page_id = directory.bucket_page_id(bucket_idx);
+        let local_depth = directory.local_depth(bucket_idx);
+        let split_page_id = PageId::from(buckets.len());
+        buckets.push(BucketPage::new(bucket_max_size));
+
+        for idx in 0..directory.size() {
+            if directory.bucket_page_id(idx) == bucket_page_id {
+                directory.set_local_depth(idx, local_depth + 1);
+                if (idx >> local_depth) & 1 == 1 {
+                    directory.set_bucket_page_id(idx, split_page_id);
+                }
+            }
+        }
+
+        for (key, value) in buckets[bucket_page_id.as_usize()].drain() {
+            let target = directory.bucket_page_id(directory.hash_to_bucket_index(Self::hash(&key)));
+            buckets[target.as_usize()].insert(&key, &value);
+        }
+    }
+
+    /// Removes `key` from the table. Buckets are never merged, so the table does not shrink.
+    pub fn remove(&self, key: &KeyType) -> bool {
+        let hash = Self::hash(key);
+        let header = self.header_page.read();
+        let Some(directory_page_id) =
+            header.directory_page_id(header.hash_to_directory_index(hash))
+        else {
+            return false;
+        };
+
+        let directories = self.directory_pages.read();
+        let directory = &directories[directory_page_id.as_usize()];
+        let bucket_page_id = directory.bucket_page_id(directory.hash_to_bucket_index(hash));
+
+        self.bucket_pages.write()[bucket_page_id.as_usize()].remove(key)
+    }
+
+    /// Returns the global depth of the directory `key` is routed to.
+    pub fn global_depth(&self, key: &KeyType) -> Option<u32> {
+        let header = self.header_page.read();
+        let directory_page_id =
+            header.directory_page_id(header.hash_to_directory_index(Self::hash(key)))?;
+        Some(self.directory_pages.read()[directory_page_id.as_usize()].global_depth())
+    }
+
+    /// Returns the number of bucket pages in the table.
+    pub fn num_buckets(&self) -> usize {
+        self.bucket_pages.read().len()
+    }
 }
 
 #[cfg(test)]
@@ -121,7 +438,6 @@ mod tests {
     }
 
     #[test]
-    #[ignore = "Not yet implemented"]
     fn bucket_page_sample_test() {
         let bucket_page = create_page::<BucketPage<i64, i64>>();
 
@@ -139,8 +455,9 @@ mod tests {
 
         // Check if the bucket page is full.
         {
-            let bucket_page = bucket_page.lock().unwrap();
+            let mut bucket_page = bucket_page.lock().unwrap();
             assert!(bucket_page.is_full());
+            assert!(!bucket_page.insert(&10, &1000));
         }
 
         // Check for the inserted pairs.
@@ -167,6 +484,13 @@ mod tests {
             }
         }
 
+        // Remove the remaining pairs.
+        for i in (0..10).step_by(2) {
+            let mut bucket_page = bucket_page.lock().unwrap();
+            assert!(bucket_page.remove(&i));
+            assert!(!bucket_page.remove(&i));
+        }
+
         // Finally, check if the bucket page is empty.
         {
             let bucket_page = bucket_page.lock().unwrap();
@@ -176,8 +500,73 @@ mod tests {
 
     #[test]
     fn header_directory_page_sample_test() {
-        // Test setup for `HeaderPage` and `DirectoryPage`.
+        let header = HeaderPage::new(2);
+        assert_eq!(header.max_size(), 4);
+        assert_eq!(header.hash_to_directory_index(0b01 << 30), 1);
+        assert_eq!(header.hash_to_directory_index((0b11 << 30) | 0b1), 3);
+
+        let mut directory = DirectoryPage::new(3, PageId(0));
+        assert_eq!((directory.size(), directory.global_depth()), (1, 0));
+        assert_eq!(directory.hash_to_bucket_index(0b111), 0);
+
+        // Doubling mirrors the existing slots into the new half
+        assert!(directory.incr_global_depth());
+        assert_eq!(directory.size(), 2);
+        assert_eq!(directory.bucket_page_id(1), PageId(0));
+        directory.set_bucket_page_id(1, PageId(1));
+        directory.set_local_depth(0, 1);
+        directory.set_local_depth(1, 1);
+        assert_eq!(directory.hash_to_bucket_index(0b111), 1);
+        assert_eq!(directory.split_image_index(1), 0);
+        assert!(!directory.can_shrink());
+
+        assert!(directory.incr_global_depth());
+        assert!(directory.incr_global_depth());
+        assert!(
+            !directory.incr_global_depth(),
+            "Directory is at its maximum depth"
+        );
+        assert_eq!(directory.size(), 8);
+        assert!(directory.decr_global_depth());
+        assert!(directory.decr_global_depth());
+        assert!(!directory.decr_global_depth());
     }
 
-    // TODO: Additional tests here to cover more scenarios.
+    #[test]
+    fn test_inserts_split_buckets_and_double_the_directory() {
+        const NUM_KEYS: i64 = 100;
+
+        let table = ExtendibleHashTable::<i64, String>::with_depths(0, 9, 4);
+        assert_eq!(table.get_value(&0), None);
+
+        for key in 0..NUM_KEYS {
+            assert!(table.insert(&key, &format!("value-{}", key)), "{}", key);
+        }
+        assert!(!table.insert(&0, &"duplicate".to_string()));
+
+        // 100 keys can't fit in fewer than 25 buckets of 4, which takes a directory of at
+        // least 32 slots
+        assert!(table.num_buckets() >= 25, "{} buckets", table.num_buckets());
+        assert!(table.global_depth(&0).unwrap() >= 5);
+
+        for key in 0..NUM_KEYS {
+            assert_eq!(table.get_value(&key), Some(format!("value-{}", key)));
+        }
+        assert_eq!(table.get_value(&NUM_KEYS), None);
+
+        assert!(table.remove(&42));
+        assert!(!table.remove(&42));
+        assert_eq!(table.get_value(&42), None);
+        assert_eq!(table.get_value(&43), Some("value-43".to_string()));
+    }
+
+    #[test]
+    fn test_insert_fails_once_the_directory_is_full() {
+        // A single directory slot with a bucket of two entries
+        let table = ExtendibleHashTable::<i64, i64>::with_depths(0, 0, 2);
+        assert!(table.insert(&1, &1));
+        assert!(table.insert(&2, &2));
+        assert!(!table.insert(&3, &3));
+        assert_eq!(table.num_buckets(), 1);
+    }
 }
@@ -1,6 +1,7 @@
 use std::time::Instant;
 
-use common::{PageId, PAGE_SIZE};
+use crate::wal::Lsn;
+use common::{PageId, USABLE_PAGE_SIZE};
 use getset::{CopyGetters, Getters, Setters};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -17,6 +18,9 @@ pub enum PageError {
 
     #[error("Pin count overflow for page {0}")]
     PinCountOverflow(PageId),
+
+    #[error("{len} bytes exceeds the {USABLE_PAGE_SIZE} bytes available in page {page_id}")]
+    DataTooLarge { page_id: PageId, len: usize },
 }
 
 /// Represents a memory page in the system.
@@ -35,7 +39,7 @@ pub enum PageError {
 ///
 /// let mut page = Page::new(PageId::new(1), vec![1, 2, 3]).expect("Failed to create page");
 ///
-/// page.write_data(&[4, 5, 6]); // Overwrites existing data
+/// page.write_data(&[4, 5, 6]).expect("Failed to write data"); // Overwrites existing data
 /// assert_eq!(page.read_data(), vec![4, 5, 6]); // Read newly written data
 /// ```
 #[derive(
@@ -67,19 +71,32 @@ pub struct Page {
     last_accessed: Option<Instant>,
     #[getset(get_copy = "pub")]
     access_count: u64,
+    /// LSN of the log record describing the latest change to the page (0 if none was logged),
+    /// which must be durable before the page is written to disk.
+    #[getset(get_copy = "pub", set = "pub")]
+    #[builder(default)]
+    page_lsn: Lsn,
 }
 
 impl Page {
     /// Creates a new `Page` with the specified `id` and `data`.
     ///
     /// Initializes a new page with given data, setting `is_dirty`, `pin_count`,
-    /// `last_accessed`, and `access_count` to their default values.
+    /// `last_accessed`, and `access_count` to their default values. The data may take up at
+    /// most [`USABLE_PAGE_SIZE`] bytes, the rest of the page on disk being reserved for its
+    /// header and checksum.
     pub fn new(id: PageId, data: Vec<u8>) -> Result<Self, PageError> {
         debug!("Creating new page {} with {} bytes", id, data.len());
 
         if data.is_empty() {
             Err(PageError::DataAccessError("Empty data provided".into()))?
         }
+        if data.len() > USABLE_PAGE_SIZE {
+            Err(PageError::DataTooLarge {
+                page_id: id,
+                len: data.len(),
+            })?
+        }
 
         Ok(Page::builder()
             .id(id)
@@ -168,12 +185,32 @@ impl Page {
         self.data.truncate(new_len);
     }
 
-    pub fn write_data(&mut self, data: &[u8]) {
+    /// Replaces the data of the page.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PageError::DataTooLarge`, leaving the page untouched, if the data exceeds the
+    /// [`USABLE_PAGE_SIZE`] bytes that fit in a page on disk.
+    pub fn write_data(&mut self, data: &[u8]) -> Result<(), PageError> {
+        if data.len() > USABLE_PAGE_SIZE {
+            warn!(
+                "Rejecting write of {} bytes to page {}",
+                data.len(),
+                self.id
+            );
+            return Err(PageError::DataTooLarge {
+                page_id: self.id,
+                len: data.len(),
+            });
+        }
+
         self.update_access_stats();
         self.data.clear();
         self.data.extend_from_slice(data);
+        Ok(())
     }
 
+    /// Returns a copy of the data of the page, which is at most [`USABLE_PAGE_SIZE`] bytes.
     pub fn read_data(&mut self) -> Vec<u8> {
         self.update_access_stats();
         self.data.clone()
@@ -203,11 +240,12 @@ impl Default for Page {
     fn default() -> Self {
         Self {
             id: PageId::default(),
-            data: vec![0; PAGE_SIZE],
+            data: vec![0; USABLE_PAGE_SIZE],
             is_dirty: false,
             pin_count: 0,
             last_accessed: None,
             access_count: 0,
+            page_lsn: 0,
         }
     }
 }
@@ -234,19 +272,31 @@ mod tests {
         let mut page = Page::default();
         let data = vec![4, 5, 6];
 
-        page.write_data(&data);
+        page.write_data(&data).unwrap();
         assert_eq!(page.read_data(), data);
+        assert_eq!(page.page_lsn(), 0);
         assert!(page.last_accessed().is_some());
         assert_eq!(page.access_count(), 2); // One for write, one for read
     }
 
+    #[test]
+    fn test_data_must_fit_in_a_page() {
+        let mut page = Page::default();
+        let err = page.write_data(&[1; USABLE_PAGE_SIZE + 1]).unwrap_err();
+        assert!(matches!(err, PageError::DataTooLarge { len, .. } if len == USABLE_PAGE_SIZE + 1));
+        assert_eq!(page.read_data(), vec![0; USABLE_PAGE_SIZE]);
+
+        page.write_data(&[1; USABLE_PAGE_SIZE]).unwrap();
+        assert!(Page::new(PageId::new(1), vec![1; USABLE_PAGE_SIZE + 1]).is_err());
+    }
+
     #[test]
     fn test_page_access_stats() {
         let mut page = Page::default();
         let data = vec![4, 5, 6];
 
         let before = Instant::now();
-        page.write_data(&data);
+        page.write_data(&data).unwrap();
         assert!(page.get_access_stats().0 >= Some(before));
         assert_eq!(page.get_access_stats().1, 1); // One for write
     }
@@ -281,11 +331,11 @@ mod tests {
     fn test_page_data_methods() {
         let mut page = Page::default();
         assert!(page.is_empty());
-        assert_eq!(page.len(), PAGE_SIZE);
-        assert_eq!(page.capacity(), PAGE_SIZE);
+        assert_eq!(page.len(), USABLE_PAGE_SIZE);
+        assert_eq!(page.capacity(), USABLE_PAGE_SIZE);
 
         let data = vec![1, 2, 3];
-        page.write_data(&data);
+        page.write_data(&data).unwrap();
         assert_eq!(page.as_slice(), data.as_slice());
 
         let data_mut = page.as_mut_slice();
@@ -306,7 +356,7 @@ mod tests {
     fn test_clear() {
         let mut page = Page::default();
         let data = vec![1, 2, 3];
-        page.write_data(&data);
+        page.write_data(&data).unwrap();
         page.clear();
         assert!(page.is_empty());
     }
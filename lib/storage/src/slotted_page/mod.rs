@@ -1,32 +1,24 @@
 #![allow(dead_code)]
 
-use std::collections::HashMap;
 use thiserror::Error;
 
-#[derive(Error, Debug)]
-enum SlottedPageError {
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SlottedPageError {
     #[error("not enough space to store the record")]
     InsufficientSpace,
     #[error("record not found")]
     RecordNotFound,
     #[error("page split required")]
     PageSplitRequired,
+    #[error("corrupted slotted page: {0}")]
+    Corrupted(String),
     // ...
 }
 
-/// Header of the slotted page, which keeps track of the record slots and the free space.
-#[derive(Debug, Clone)]
-struct SlottedPageHeader {
-    free_space_offset: usize,
-    slots: HashMap<usize, RecordSlot>, // Maps slot index to the record slot
-}
-
-/// Represents a slot in the slotted page, which could either point to a fixed or variable length record.
-#[derive(Debug, Clone)]
-struct RecordSlot {
-    offset: usize,
-    length: usize,
-}
+/// The size of the header: the number of slots, then the offset of the end of the free space.
+const HEADER_SIZE: usize = 8;
+/// The size of a slot: the offset of its record, then its length.
+const SLOT_SIZE: usize = 8;
 
 /// A `SlottedPage` represents a single page in a slotted page storage system.
 /// It is designed to store both fixed and variable-length records.
@@ -34,11 +26,14 @@ struct RecordSlot {
 /// ## Data layout:
 ///
 /// ```ignore
-/// | Header | ...Free Space... | ...Records... |
+/// | Header | Slots | ...Free Space... | ...Records... |
 /// ```
-/// The header contains metadata such as the offset to the beginning of the free space
-/// and a map of record slots. Records are stored at the end of the page, growing
-/// towards the beginning, while free space is managed from the beginning of the page.
+/// The header holds the number of slots and the offset to the end of the free space, and is
+/// followed by a slot per record holding its offset and length (all big-endian `u32`s). Records
+/// are stored at the end of the page, growing towards the beginning, while the slots grow from
+/// the beginning of the page. An offset of 0 for the end of the free space stands for the end
+/// of the page, so that a zeroed page (e.g. one read beyond the end of the file) is an empty
+/// slotted page.
 ///
 /// ## Example:
 ///
@@ -52,66 +47,191 @@ struct RecordSlot {
 ///
 /// println!("Retrieved record: {:?}", std::str::from_utf8(retrieved_record).unwrap());
 /// ```
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SlottedPage {
-    header: SlottedPageHeader,
-    data: Vec<u8>, // A vector of bytes to store record data
+    data: Vec<u8>, // The bytes of the page, header included
+}
+
+fn read_u32(data: &[u8], offset: usize) -> usize {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().expect("4 bytes")) as usize
+}
+
+fn write_u32(data: &mut [u8], offset: usize, val: usize) {
+    data[offset..offset + 4].copy_from_slice(&(val as u32).to_be_bytes());
 }
 
 impl SlottedPage {
     /// Initializes a new slotted page with a given size.
     pub fn new(page_size: usize) -> Self {
         Self {
-            header: SlottedPageHeader {
-                free_space_offset: 0,
-                slots: HashMap::new(),
-            },
             data: vec![0; page_size],
         }
     }
 
-    /// Adds a new record to the slotted page, returning the slot index.
-    pub fn add_record(&mut self, record: &[u8]) -> usize {
-        let slot_index = self.header.slots.len();
-        let offset = self.header.free_space_offset;
-        let length = record.len();
+    /// Wraps the bytes of a slotted page (e.g. the data of a page read from disk), failing if
+    /// its slots don't lie within it.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, SlottedPageError> {
+        if data.len() < HEADER_SIZE {
+            return Err(SlottedPageError::Corrupted(format!(
+                "{} bytes is too small for the header",
+                data.len()
+            )));
+        }
 
-        // Check if there is enough space to store the record.
-        if offset + length > self.data.len() {
-            panic!("Not enough space to store the record");
+        let page = Self { data };
+        let slots_end = HEADER_SIZE + page.slot_count() * SLOT_SIZE;
+        if slots_end > page.free_space_end() || page.free_space_end() > page.data.len() {
+            return Err(SlottedPageError::Corrupted(format!(
+                "{} slots and free space ending at {} in a page of {} bytes",
+                page.slot_count(),
+                page.free_space_end(),
+                page.data.len()
+            )));
+        }
+        for slot_index in 0..page.slot_count() {
+            let (offset, length) = page.slot(slot_index);
+            if offset < page.free_space_end() || offset + length > page.data.len() {
+                return Err(SlottedPageError::Corrupted(format!(
+                    "record {} lies outside of the record space",
+                    slot_index
+                )));
+            }
+        }
+        Ok(page)
+    }
+
+    /// Returns the bytes of the page, header included.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the number of records in the page.
+    pub fn slot_count(&self) -> usize {
+        read_u32(&self.data, 0)
+    }
+
+    fn free_space_end(&self) -> usize {
+        match read_u32(&self.data, 4) {
+            0 => self.data.len(),
+            offset => offset,
+        }
+    }
+
+    fn slot(&self, slot_index: usize) -> (usize, usize) {
+        let slot_offset = HEADER_SIZE + slot_index * SLOT_SIZE;
+        (
+            read_u32(&self.data, slot_offset),
+            read_u32(&self.data, slot_offset + 4),
+        )
+    }
+
+    /// Returns the number of bytes left for records and their slots.
+    pub fn free_space(&self) -> usize {
+        self.free_space_end() - HEADER_SIZE - self.slot_count() * SLOT_SIZE
+    }
+
+    /// Returns whether a record of `length` bytes fits in the page.
+    pub fn fits(&self, length: usize) -> bool {
+        length + SLOT_SIZE <= self.free_space()
+    }
+
+    /// Returns the length of the largest record an empty page of `page_size` bytes can hold.
+    pub fn max_record_len(page_size: usize) -> usize {
+        page_size.saturating_sub(HEADER_SIZE + SLOT_SIZE)
+    }
+
+    /// Adds a new record to the slotted page, returning the slot index, or
+    /// [`SlottedPageError::InsufficientSpace`] if the record doesn't fit.
+    pub fn try_add_record(&mut self, record: &[u8]) -> Result<usize, SlottedPageError> {
+        if !self.fits(record.len()) {
+            return Err(SlottedPageError::InsufficientSpace);
         }
 
         // Store the record data.
-        self.data[offset..offset + length].copy_from_slice(record);
+        let slot_index = self.slot_count();
+        let offset = self.free_space_end() - record.len();
+        self.data[offset..offset + record.len()].copy_from_slice(record);
         // Update the header.
-        self.header.free_space_offset += length;
-        self.header
-            .slots
-            .insert(slot_index, RecordSlot { offset, length });
+        let slot_offset = HEADER_SIZE + slot_index * SLOT_SIZE;
+        write_u32(&mut self.data, slot_offset, offset);
+        write_u32(&mut self.data, slot_offset + 4, record.len());
+        write_u32(&mut self.data, 0, slot_index + 1);
+        write_u32(&mut self.data, 4, offset);
 
-        slot_index
+        Ok(slot_index)
+    }
+
+    /// Adds a new record to the slotted page, returning the slot index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the record doesn't fit in the page.
+    pub fn add_record(&mut self, record: &[u8]) -> usize {
+        self.try_add_record(record)
+            .expect("Not enough space to store the record")
     }
 
     /// Retrieves a record from the slotted page by its slot index.
     pub fn get_record(&self, slot_index: usize) -> Option<&[u8]> {
-        self.header
-            .slots
-            .get(&slot_index)
-            .map(|slot| &self.data[slot.offset..slot.offset + slot.length])
+        if slot_index >= self.slot_count() {
+            return None;
+        }
+        let (offset, length) = self.slot(slot_index);
+        Some(&self.data[offset..offset + length])
+    }
+
+    /// Returns the records of the page, in slot order.
+    pub fn records(&self) -> impl Iterator<Item = &[u8]> {
+        (0..self.slot_count()).filter_map(|slot_index| self.get_record(slot_index))
     }
 
     // Additional methods like delete_record, update_record, etc. could be implemented.
 }
 
-// fn usage() {
-//     // Example usage:
-//     let mut page = SlottedPage::new(1024); // Initialize a new slotted page of size 1024 bytes.
-//     let record = b"Example record";
-//     let slot_index = page.add_record(record); // Add a record and get its slot index.
-//     let retrieved_record = page.get_record(slot_index).unwrap(); // Retrieve the record by its slot index.
-
-//     println!(
-//         "Retrieved record: {:?}",
-//         std::str::from_utf8(retrieved_record).unwrap()
-//     );
-// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_survive_a_round_trip_through_bytes() {
+        let mut page = SlottedPage::new(64);
+        assert_eq!(page.add_record(b"first"), 0);
+        assert_eq!(page.add_record(b""), 1);
+        assert_eq!(page.add_record(b"third"), 2);
+
+        let page = SlottedPage::from_bytes(page.as_bytes().to_vec()).unwrap();
+        assert_eq!(
+            page.records().collect::<Vec<_>>(),
+            [&b"first"[..], b"", b"third"]
+        );
+        assert_eq!(page.get_record(3), None);
+    }
+
+    #[test]
+    fn test_full_page_rejects_records() {
+        let mut page = SlottedPage::new(32);
+        let record = [7; 16];
+        // The header takes 8 bytes, and each record 8 more for its slot
+        assert_eq!(SlottedPage::max_record_len(32), 16);
+        assert!(page.try_add_record(&record).is_ok());
+        assert_eq!(page.free_space(), 0);
+        assert_eq!(
+            page.try_add_record(b""),
+            Err(SlottedPageError::InsufficientSpace)
+        );
+    }
+
+    #[test]
+    fn test_zeroed_page_is_empty() {
+        let page = SlottedPage::from_bytes(vec![0; 64]).unwrap();
+        assert_eq!(page.slot_count(), 0);
+        assert_eq!(page.free_space(), 56);
+
+        let mut corrupted = vec![0; 64];
+        corrupted[3] = 100;
+        assert!(matches!(
+            SlottedPage::from_bytes(corrupted),
+            Err(SlottedPageError::Corrupted(_))
+        ));
+    }
+}
@@ -1 +1,3 @@
+pub mod row;
+pub mod stats;
 pub mod tuple;
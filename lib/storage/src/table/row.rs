@@ -0,0 +1,108 @@
+//! # Rows
+//!
+//! The records rows are stored as in table heaps: the values of the row, in column order,
+//! each as its big-endian `u32` length followed by its [`Encodable`] encoding. A NULL is
+//! written as the length [`NULL_LENGTH`] without any bytes, so that it can be told apart from
+//! values whose encoding is empty (e.g. empty text).
+//!
+//! The encoding doesn't record the kinds of the values, so rows are decoded with the kinds of
+//! the columns of the table (see [`decode_row`]).
+
+use common::traits::encode::{Encodable, EncodingError};
+use ty::{DataType, DataTypeKind};
+
+/// The length standing for a NULL value in an encoded row.
+pub const NULL_LENGTH: u32 = u32::MAX;
+
+/// Encodes the values of a row.
+pub fn encode_row(values: &[DataType]) -> Result<Vec<u8>, EncodingError> {
+    let mut bytes = Vec::new();
+    for value in values {
+        if let DataType::Null = value {
+            bytes.extend_from_slice(&NULL_LENGTH.to_be_bytes());
+            continue;
+        }
+        let encoded = value.encode()?;
+        let len = u32::try_from(encoded.len())
+            .ok()
+            .filter(|&len| len != NULL_LENGTH)
+            .ok_or(EncodingError::InvalidDataType)?;
+        bytes.extend_from_slice(&len.to_be_bytes());
+        bytes.extend(encoded);
+    }
+    Ok(bytes)
+}
+
+/// Decodes a row encoded by [`encode_row`] holding a value of each of the given kinds.
+pub fn decode_row(kinds: &[DataTypeKind], bytes: &[u8]) -> Result<Vec<DataType>, EncodingError> {
+    let mut values = Vec::with_capacity(kinds.len());
+    let mut rest = bytes;
+    for kind in kinds {
+        if rest.len() < 4 {
+            return Err(EncodingError::InvalidDataType);
+        }
+        let (len, tail) = rest.split_at(4);
+        let len = u32::from_be_bytes(len.try_into().expect("4 bytes"));
+        if len == NULL_LENGTH {
+            values.push(DataType::Null);
+            rest = tail;
+            continue;
+        }
+        let len = len as usize;
+        if tail.len() < len {
+            return Err(EncodingError::InvalidDataType);
+        }
+        let (value, tail) = tail.split_at(len);
+        values.push(DataType::decode(kind, value)?);
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        return Err(EncodingError::InvalidDataType);
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_are_length_prefixed() {
+        let row = [
+            DataType::Integer(7),
+            DataType::Null,
+            DataType::Text(String::new()),
+            DataType::Text("ab".to_string()),
+        ];
+        assert_eq!(
+            encode_row(&row).unwrap(),
+            [
+                &[0, 0, 0, 4, 0, 0, 0, 7][..],
+                &[0xFF, 0xFF, 0xFF, 0xFF],
+                &[0, 0, 0, 0],
+                &[0, 0, 0, 2, b'a', b'b'],
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn test_rows_are_decoded_with_the_kinds_of_their_columns() {
+        let row = [
+            DataType::Integer(7),
+            DataType::Null,
+            DataType::Text("ab".to_string()),
+        ];
+        let kinds = [
+            DataTypeKind::Integer,
+            DataTypeKind::Boolean,
+            DataTypeKind::Text,
+        ];
+        let bytes = encode_row(&row).unwrap();
+        assert_eq!(decode_row(&kinds, &bytes).unwrap(), row);
+
+        // Too few or too many bytes for the columns
+        assert!(decode_row(&kinds, &bytes[..bytes.len() - 1]).is_err());
+        assert!(decode_row(&kinds[..2], &bytes).is_err());
+    }
+}
@@ -0,0 +1,317 @@
+//! # Sampled Table Statistics
+//!
+//! The [`StatisticsSampler`] keeps approximate [`TableStatistics`] fresh without running
+//! `ANALYZE`: on every tick it reads a random fraction of each registered table's pages
+//! and extrapolates row counts and per-column statistics from the sample. Only sampled
+//! pages are read, so the cost of a tick is proportional to the sample fraction rather
+//! than to the size of the table.
+
+use anyhow::Result;
+use dashmap::DashMap;
+use getset::{CopyGetters, Getters};
+use parking_lot::RwLock;
+use rand::seq::index::sample;
+use std::{collections::HashSet, sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use ty::value::Value;
+
+/// A reference-counted [`StatisticsSampler`] handle that can be shared across threads.
+pub type StatisticsSamplerRef = Arc<StatisticsSampler>;
+
+/// The default fraction of a table's pages read on every sampling tick.
+pub const DEFAULT_SAMPLE_FRACTION: f64 = 0.1;
+
+/// A table whose pages can be sampled for statistics.
+pub trait SampleSource: Send + Sync {
+    /// The name the table's statistics are published under.
+    fn table_name(&self) -> String;
+
+    /// The number of pages the table occupies.
+    fn num_pages(&self) -> usize;
+
+    /// Reads the rows stored on the page at `page_index` (in `0..num_pages()`).
+    fn read_page_rows(&self, page_index: usize) -> Result<Vec<Vec<Value>>>;
+}
+
+/// Approximate statistics of a single column, extrapolated from a sample.
+#[derive(Debug, Clone, PartialEq, Getters, CopyGetters)]
+pub struct ColumnStatistics {
+    /// Fraction of sampled values that were `NULL`.
+    #[getset(get_copy = "pub")]
+    null_fraction: f64,
+    /// Estimated number of distinct non-null values in the whole table.
+    #[getset(get_copy = "pub")]
+    distinct_estimate: usize,
+    /// Smallest sampled non-null value.
+    #[getset(get = "pub")]
+    min: Option<Value>,
+    /// Largest sampled non-null value.
+    #[getset(get = "pub")]
+    max: Option<Value>,
+}
+
+/// Approximate statistics of a table, extrapolated from a sample of its pages.
+#[derive(Debug, Clone, PartialEq, Getters, CopyGetters)]
+pub struct TableStatistics {
+    #[getset(get_copy = "pub")]
+    row_count_estimate: usize,
+    #[getset(get_copy = "pub")]
+    pages_sampled: usize,
+    #[getset(get_copy = "pub")]
+    total_pages: usize,
+    #[getset(get = "pub")]
+    columns: Vec<ColumnStatistics>,
+}
+
+impl TableStatistics {
+    /// Builds statistics from the rows of `pages_sampled` out of `total_pages` pages.
+    fn from_sample(rows: &[Vec<Value>], pages_sampled: usize, total_pages: usize) -> Self {
+        if pages_sampled == 0 {
+            return TableStatistics {
+                row_count_estimate: 0,
+                pages_sampled,
+                total_pages,
+                columns: Vec::new(),
+            };
+        }
+
+        let scale = total_pages as f64 / pages_sampled as f64;
+        let row_count_estimate = (rows.len() as f64 * scale).round() as usize;
+        let num_columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+
+        let columns = (0..num_columns)
+            .map(|idx| {
+                let values: Vec<&Value> = rows.iter().filter_map(|row| row.get(idx)).collect();
+                let non_null: Vec<&Value> =
+                    values.iter().copied().filter(|v| !v.is_null()).collect();
+                let distinct = non_null
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<HashSet<_>>()
+                    .len();
+
+                // A column that is (nearly) unique within the sample is assumed to stay
+                // unique across the table; otherwise the sample has likely seen every value.
+                let distinct_estimate = if distinct as f64 >= 0.9 * non_null.len() as f64 {
+                    (distinct as f64 * scale).round() as usize
+                } else {
+                    distinct
+                };
+
+                let pick = |keep: fn(std::cmp::Ordering) -> bool| {
+                    non_null
+                        .iter()
+                        .copied()
+                        .reduce(|a, b| match b.partial_cmp(a) {
+                            Some(ordering) if keep(ordering) => b,
+                            _ => a,
+                        })
+                        .cloned()
+                };
+
+                ColumnStatistics {
+                    null_fraction: if values.is_empty() {
+                        0.0
+                    } else {
+                        (values.len() - non_null.len()) as f64 / values.len() as f64
+                    },
+                    distinct_estimate,
+                    min: pick(|ordering| ordering.is_lt()),
+                    max: pick(|ordering| ordering.is_gt()),
+                }
+            })
+            .collect();
+
+        TableStatistics {
+            row_count_estimate,
+            pages_sampled,
+            total_pages,
+            columns,
+        }
+    }
+}
+
+/// `StatisticsSampler` periodically samples registered tables and publishes their
+/// approximate statistics for the planner.
+pub struct StatisticsSampler {
+    sources: RwLock<Vec<Arc<dyn SampleSource>>>,
+    statistics: DashMap<String, TableStatistics>,
+    sample_fraction: f64,
+}
+
+impl std::fmt::Debug for StatisticsSampler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StatisticsSampler")
+            .field("sources", &self.sources.read().len())
+            .field("sample_fraction", &self.sample_fraction)
+            .finish()
+    }
+}
+
+impl StatisticsSampler {
+    /// Creates a sampler that reads `sample_fraction` (clamped to `(0, 1]`) of each table's
+    /// pages per tick.
+    pub fn new(sample_fraction: f64) -> Self {
+        StatisticsSampler {
+            sources: RwLock::new(Vec::new()),
+            statistics: DashMap::new(),
+            sample_fraction: sample_fraction.clamp(f64::MIN_POSITIVE, 1.0),
+        }
+    }
+
+    /// Registers a table to be sampled on every tick.
+    pub fn register(&self, source: Arc<dyn SampleSource>) {
+        debug!("Registering table `{}` for sampling", source.table_name());
+        self.sources.write().push(source);
+    }
+
+    /// Returns the latest statistics of a table, if it has been sampled.
+    pub fn table_statistics(&self, table_name: &str) -> Option<TableStatistics> {
+        self.statistics
+            .get(table_name)
+            .map(|stats| stats.value().clone())
+    }
+
+    /// Samples every registered table once. Tables that fail to sample keep their
+    /// previous statistics.
+    pub fn sample_once(&self) {
+        let sources = self.sources.read().clone();
+        for source in sources {
+            let table_name = source.table_name();
+            match self.sample_table(source.as_ref()) {
+                Ok(stats) => {
+                    debug!(
+                        "Sampled {} of {} pages of `{}`: ~{} rows",
+                        stats.pages_sampled(),
+                        stats.total_pages(),
+                        table_name,
+                        stats.row_count_estimate()
+                    );
+                    self.statistics.insert(table_name, stats);
+                }
+                Err(e) => warn!("Failed to sample table `{}`: {}", table_name, e),
+            }
+        }
+    }
+
+    fn sample_table(&self, source: &dyn SampleSource) -> Result<TableStatistics> {
+        let total_pages = source.num_pages();
+        let sample_size = ((total_pages as f64 * self.sample_fraction).ceil() as usize)
+            .clamp(total_pages.min(1), total_pages);
+
+        let mut rows = Vec::new();
+        for page_index in sample(&mut rand::thread_rng(), total_pages, sample_size) {
+            rows.extend(source.read_page_rows(page_index)?);
+        }
+
+        Ok(TableStatistics::from_sample(
+            &rows,
+            sample_size,
+            total_pages,
+        ))
+    }
+
+    /// Spawns a background task that samples every registered table each `interval`.
+    pub fn spawn(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        info!(
+            "Starting background statistics sampling every {:?}",
+            interval
+        );
+        let sampler = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                sampler.sample_once();
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ty::DataType;
+
+    /// An in-memory table where page `i` holds `45 + i % 11` rows.
+    struct InMemoryTable {
+        pages: Vec<Vec<Vec<Value>>>,
+    }
+
+    impl InMemoryTable {
+        fn new(num_pages: usize) -> Self {
+            let mut next_id = 0;
+            let pages = (0..num_pages)
+                .map(|i| {
+                    (0..45 + i % 11)
+                        .map(|_| {
+                            next_id += 1;
+                            vec![
+                                Value::new(DataType::Integer(next_id)),
+                                Value::new(DataType::Boolean(next_id % 2 == 0)),
+                            ]
+                        })
+                        .collect()
+                })
+                .collect();
+            InMemoryTable { pages }
+        }
+
+        fn row_count(&self) -> usize {
+            self.pages.iter().map(Vec::len).sum()
+        }
+    }
+
+    impl SampleSource for InMemoryTable {
+        fn table_name(&self) -> String {
+            "t".to_string()
+        }
+
+        fn num_pages(&self) -> usize {
+            self.pages.len()
+        }
+
+        fn read_page_rows(&self, page_index: usize) -> Result<Vec<Vec<Value>>> {
+            Ok(self.pages[page_index].clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_background_sampling_estimates_row_count() {
+        let table = Arc::new(InMemoryTable::new(200));
+        let true_count = table.row_count();
+
+        let sampler = Arc::new(StatisticsSampler::new(DEFAULT_SAMPLE_FRACTION));
+        sampler.register(table);
+        assert!(sampler.table_statistics("t").is_none());
+
+        let handle = sampler.spawn(Duration::from_millis(10));
+        let stats = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(stats) = sampler.table_statistics("t") {
+                    break stats;
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("No sampling tick completed");
+        handle.abort();
+
+        assert_eq!(stats.pages_sampled(), 20);
+        let error = (stats.row_count_estimate() as f64 - true_count as f64).abs();
+        assert!(
+            error <= 0.1 * true_count as f64,
+            "estimate {} too far from {}",
+            stats.row_count_estimate(),
+            true_count
+        );
+
+        // The id column is unique, the flag column only has two values
+        let (id, flag) = (&stats.columns()[0], &stats.columns()[1]);
+        assert!(id.distinct_estimate() as f64 >= 0.9 * stats.row_count_estimate() as f64);
+        assert_eq!(flag.distinct_estimate(), 2);
+        assert_eq!(flag.null_fraction(), 0.0);
+    }
+}
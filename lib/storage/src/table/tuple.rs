@@ -6,13 +6,15 @@ use getset::{Getters, Setters};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use thiserror::Error;
-use ty::value::Value;
+use ty::{value::Value, TypeError};
 use typed_builder::TypedBuilder;
 
 #[derive(Error, Debug)]
 pub enum TupleError {
     #[error("Invalid operation")]
     InvalidOperation,
+    #[error("Invalid value: {0}")]
+    InvalidValue(#[from] TypeError),
 }
 
 /// Represents a tuple (or record) in a database.
@@ -136,6 +138,7 @@ impl Tuple {
 
         for (idx, value) in values.iter().enumerate() {
             let column = &schema.columns()[idx];
+            let value = column.coerce_value(value)?;
             let column_offset = *column.column_offset() as usize;
 
             if column.is_inlined() {
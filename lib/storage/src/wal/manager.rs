@@ -0,0 +1,299 @@
+use super::{LogRecord, LogRecordKind, Lsn};
+use crate::disk::{DiskManagerError, DiskManagerRef, PageKind};
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use tracing::{debug, info, instrument, warn};
+
+/// A reference-counted [`WalManager`] handle that can be shared across threads.
+pub type WalManagerRef = Arc<WalManager>;
+
+/// `WalManager` maintains the write-ahead log stored in the disk manager's `.log` file.
+///
+/// Records are appended to an in-memory buffer and assigned monotonically increasing
+/// LSNs (starting at 1). The buffer is written out by [`WalManager::flush`] or
+/// [`WalManager::flush_to`], which callers must invoke before writing a page whose
+/// changes are described by unflushed records (log before data).
+///
+/// On startup [`WalManager::recover`] redoes every update whose LSN exceeds the last
+/// checkpoint, restoring changes that never made it into the database file.
+#[derive(Debug)]
+pub struct WalManager {
+    disk_manager: DiskManagerRef,
+    // Encoded records that have not been written to the log file yet
+    buffer: Mutex<Vec<u8>>,
+    // The LSN assigned to the next appended record
+    next_lsn: AtomicU64,
+    // The highest LSN that is durable in the log file
+    flushed_lsn: AtomicU64,
+    // The LSN of the most recent checkpoint (0 if there is none)
+    checkpoint_lsn: AtomicU64,
+}
+
+impl WalManager {
+    /// Opens the write-ahead log of the given disk manager, continuing after the last
+    /// valid record. A torn record at the tail of the log (from a crash mid-append) is
+    /// discarded.
+    pub fn new(disk_manager: DiskManagerRef) -> Result<Self> {
+        let (records, valid_len) = Self::read_records(&disk_manager)?;
+        if valid_len < disk_manager.log_size() {
+            warn!(
+                "Discarding {} bytes of torn records at the tail of the log",
+                disk_manager.log_size() - valid_len
+            );
+            disk_manager.truncate_log(valid_len)?;
+        }
+
+        let last_lsn = records.last().map_or(0, |record| record.lsn());
+        let checkpoint_lsn = Self::last_checkpoint(&records);
+        debug!(
+            "Opened write-ahead log with {} records (last LSN {}, checkpoint LSN {})",
+            records.len(),
+            last_lsn,
+            checkpoint_lsn
+        );
+
+        Ok(Self {
+            disk_manager,
+            buffer: Mutex::new(Vec::new()),
+            next_lsn: AtomicU64::new(last_lsn + 1),
+            flushed_lsn: AtomicU64::new(last_lsn),
+            checkpoint_lsn: AtomicU64::new(checkpoint_lsn),
+        })
+    }
+
+    /// Appends an update record describing the change of `page_id`'s bytes at `offset`
+    /// from `before` to `after`, returning its LSN. The record is buffered until the
+    /// next flush.
+    pub fn log_update(
+        &self,
+        page_id: u32,
+        offset: u32,
+        before: Vec<u8>,
+        after: Vec<u8>,
+    ) -> Result<Lsn> {
+        if offset as usize + after.len() > self.disk_manager.payload_size() {
+            return Err(DiskManagerError::PageSizeError.into());
+        }
+
+        let mut buffer = self.buffer.lock();
+        let lsn = self.next_lsn.fetch_add(1, Ordering::SeqCst);
+        buffer.extend_from_slice(&LogRecord::update(lsn, page_id, offset, before, after).encode());
+        debug!("Appended update record {} for page {}", lsn, page_id);
+        Ok(lsn)
+    }
+
+    /// Appends a checkpoint record and flushes the log. Callers must have written every
+    /// dirty page to the database file beforehand.
+    #[instrument(skip(self))]
+    pub fn checkpoint(&self) -> Result<Lsn> {
+        let lsn = {
+            let mut buffer = self.buffer.lock();
+            let lsn = self.next_lsn.fetch_add(1, Ordering::SeqCst);
+            buffer.extend_from_slice(&LogRecord::checkpoint(lsn).encode());
+            lsn
+        };
+        self.flush()?;
+        self.checkpoint_lsn.store(lsn, Ordering::SeqCst);
+        info!("Wrote checkpoint {}", lsn);
+        Ok(lsn)
+    }
+
+    /// Writes every buffered record to the log file and syncs it to disk.
+    pub fn flush(&self) -> Result<()> {
+        let mut buffer = self.buffer.lock();
+        if buffer.is_empty() {
+            return Ok(());
+        }
+
+        self.disk_manager.write_log(&buffer)?;
+        buffer.clear();
+        // The log is synced, and no record can be appended while the buffer is locked, so every
+        // assigned LSN is durable
+        self.flushed_lsn
+            .store(self.next_lsn.load(Ordering::SeqCst) - 1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Ensures that every record up to and including `lsn` is durable in the log file.
+    pub fn flush_to(&self, lsn: Lsn) -> Result<()> {
+        if self.flushed_lsn() >= lsn {
+            return Ok(());
+        }
+        self.flush()
+    }
+
+    /// Returns the highest LSN that is durable in the log file.
+    pub fn flushed_lsn(&self) -> Lsn {
+        self.flushed_lsn.load(Ordering::SeqCst)
+    }
+
+    /// Returns the LSN of the most recent checkpoint (0 if there is none).
+    pub fn checkpoint_lsn(&self) -> Lsn {
+        self.checkpoint_lsn.load(Ordering::SeqCst)
+    }
+
+    /// Redoes every update record whose LSN exceeds the last checkpoint by applying its
+    /// after image to the page in the database file, then writes a new checkpoint. Records
+    /// already reflected in a page, as told by the LSN in its header, are skipped.
+    /// Must run before any page is cached in the buffer pool.
+    ///
+    /// Returns the number of records that were replayed.
+    #[instrument(skip(self))]
+    pub fn recover(&self) -> Result<usize> {
+        self.flush()?;
+        let (records, _) = Self::read_records(&self.disk_manager)?;
+        let checkpoint_lsn = Self::last_checkpoint(&records);
+
+        let mut replayed = 0;
+        for record in records.iter().filter(|record| {
+            record.kind() == LogRecordKind::Update && record.lsn() > checkpoint_lsn
+        }) {
+            let page_id = record.page_id();
+            let mut page = vec![0; self.disk_manager.payload_size()];
+            match self.disk_manager.read_page(page_id, &mut page) {
+                Ok(PageKind::Data { lsn }) if lsn >= record.lsn() => {
+                    debug!("Page {} already reflects record {}", page_id, record.lsn());
+                    continue;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    // Updates carry partial images, so a torn page can only be redone onto zeros
+                    warn!("Failed to read page {} during recovery: {}", page_id, e);
+                    page.fill(0);
+                }
+            }
+
+            let start = record.offset() as usize;
+            page[start..start + record.after().len()].copy_from_slice(record.after());
+            self.disk_manager
+                .write_page_with_lsn(page_id, &page, record.lsn())?;
+            replayed += 1;
+        }
+
+        info!(
+            "Recovery replayed {} records after checkpoint {}",
+            replayed, checkpoint_lsn
+        );
+        if replayed > 0 {
+            self.checkpoint()?;
+        }
+        Ok(replayed)
+    }
+
+    /// Reads every valid record from the log, returning them along with the length of
+    /// the valid prefix of the log.
+    fn read_records(disk_manager: &DiskManagerRef) -> Result<(Vec<LogRecord>, u64)> {
+        let mut log = vec![0; disk_manager.log_size() as usize];
+        disk_manager.read_log(0, &mut log)?;
+
+        let mut records = Vec::new();
+        let mut offset = 0;
+        while offset < log.len() {
+            match LogRecord::decode(&log, offset) {
+                Ok((record, len)) => {
+                    records.push(record);
+                    offset += len;
+                }
+                Err(e) => {
+                    warn!("Stopping log scan: {}", e);
+                    break;
+                }
+            }
+        }
+
+        Ok((records, offset as u64))
+    }
+
+    fn last_checkpoint(records: &[LogRecord]) -> Lsn {
+        records
+            .iter()
+            .rev()
+            .find(|record| record.kind() == LogRecordKind::Checkpoint)
+            .map_or(0, |record| record.lsn())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::setup_dm;
+    use common::USABLE_PAGE_SIZE;
+
+    #[test]
+    fn test_lsns_continue_after_reopen() {
+        let (dm, _temp_dir) = setup_dm();
+        let wal = WalManager::new(dm.clone()).unwrap();
+        assert_eq!(wal.log_update(0, 0, vec![0], vec![1]).unwrap(), 1);
+        assert_eq!(wal.log_update(0, 0, vec![1], vec![2]).unwrap(), 2);
+        assert_eq!(wal.flushed_lsn(), 0);
+        wal.flush_to(2).unwrap();
+        assert_eq!(wal.flushed_lsn(), 2);
+
+        let wal = WalManager::new(dm).unwrap();
+        assert_eq!(wal.flushed_lsn(), 2);
+        assert_eq!(wal.log_update(0, 0, vec![2], vec![3]).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_recover_replays_updates_after_checkpoint() {
+        let (dm, _temp_dir) = setup_dm();
+        let wal = WalManager::new(dm.clone()).unwrap();
+        wal.log_update(1, 0, vec![0; 2], vec![1, 1]).unwrap();
+        wal.checkpoint().unwrap();
+        wal.log_update(1, 2, vec![0; 2], vec![2, 2]).unwrap();
+        wal.flush().unwrap();
+
+        // Only the update after the checkpoint is redone
+        assert_eq!(wal.recover().unwrap(), 1);
+        let data = dm.read_data(1).unwrap();
+        assert_eq!(&data[..4], &[0, 0, 2, 2]);
+        assert!(data[4..USABLE_PAGE_SIZE].iter().all(|&b| b == 0));
+
+        // Recovery checkpoints, so a second pass has nothing to do
+        assert_eq!(wal.recover().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_recover_skips_updates_already_on_the_page() {
+        let (dm, _temp_dir) = setup_dm();
+        let wal = WalManager::new(dm.clone()).unwrap();
+        wal.log_update(1, 0, vec![0; 2], vec![1, 1]).unwrap();
+        let lsn = wal.log_update(1, 0, vec![1, 1], vec![2, 2]).unwrap();
+        wal.flush().unwrap();
+
+        // The page was written after the second update, which the first must not undo
+        dm.write_page_with_lsn(1, &[2, 2, 3], lsn).unwrap();
+        assert_eq!(wal.recover().unwrap(), 0);
+        assert_eq!(&dm.read_data(1).unwrap()[..3], &[2, 2, 3]);
+    }
+
+    #[test]
+    fn test_torn_tail_is_discarded() {
+        let (dm, _temp_dir) = setup_dm();
+        let wal = WalManager::new(dm.clone()).unwrap();
+        wal.log_update(0, 0, vec![0], vec![1]).unwrap();
+        wal.flush().unwrap();
+        let valid_len = dm.log_size();
+
+        // Simulate a crash halfway through appending a record
+        let torn = LogRecord::update(2, 0, 0, vec![1], vec![2]).encode();
+        dm.write_log(&torn[..torn.len() / 2]).unwrap();
+
+        let wal = WalManager::new(dm.clone()).unwrap();
+        assert_eq!(dm.log_size(), valid_len);
+        assert_eq!(wal.log_update(0, 0, vec![1], vec![2]).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_rejects_updates_past_page_end() {
+        let (dm, _temp_dir) = setup_dm();
+        let wal = WalManager::new(dm).unwrap();
+        assert!(wal
+            .log_update(0, USABLE_PAGE_SIZE as u32 - 1, vec![0; 2], vec![1; 2])
+            .is_err());
+    }
+}
@@ -0,0 +1,57 @@
+//! # Write-Ahead Log
+//!
+//! Durability for buffered page writes: every change is described by a [`LogRecord`]
+//! that reaches the log file before the page itself reaches the database file, so
+//! that [`WalManager::recover`] can redo changes lost in a crash.
+
+mod manager;
+mod record;
+
+pub use manager::*;
+pub use record::*;
+
+use anyhow::Result;
+use std::fmt::Debug;
+
+/// A log sequence number, identifying a record's position in the write-ahead log.
+pub type Lsn = u64;
+
+/// The write-ahead log as used by the buffer pool, implemented by [`WalManager`].
+pub trait WriteAheadLog: Send + Sync + Debug {
+    /// Appends an update record describing a change of a page, returning its LSN.
+    fn log_update(&self, page_id: u32, offset: u32, before: Vec<u8>, after: Vec<u8>)
+        -> Result<Lsn>;
+
+    /// Forces every record up to and including `lsn` to the log file.
+    fn flush_to(&self, lsn: Lsn) -> Result<()>;
+
+    /// Appends a checkpoint record and flushes the log.
+    fn checkpoint(&self) -> Result<Lsn>;
+
+    /// Redoes the updates logged after the last checkpoint, returning how many were redone.
+    fn recover(&self) -> Result<usize>;
+}
+
+impl WriteAheadLog for WalManager {
+    fn log_update(
+        &self,
+        page_id: u32,
+        offset: u32,
+        before: Vec<u8>,
+        after: Vec<u8>,
+    ) -> Result<Lsn> {
+        WalManager::log_update(self, page_id, offset, before, after)
+    }
+
+    fn flush_to(&self, lsn: Lsn) -> Result<()> {
+        WalManager::flush_to(self, lsn)
+    }
+
+    fn checkpoint(&self) -> Result<Lsn> {
+        WalManager::checkpoint(self)
+    }
+
+    fn recover(&self) -> Result<usize> {
+        WalManager::recover(self)
+    }
+}
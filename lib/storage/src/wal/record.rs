@@ -0,0 +1,204 @@
+//! # Log Records
+//!
+//! A [`LogRecord`] is the unit of the write-ahead log. Every record is encoded with an
+//! explicit, big-endian byte layout so that the log can be replayed independently of
+//! the in-memory representation:
+//!
+//! ```text
+//! +---------+--------+-----------+-----------+---------------+--------------+
+//! | lsn: u64| kind:u8| page: u32 | offset:u32| before_len:u32| after_len:u32|
+//! +---------+--------+-----------+-----------+---------------+--------------+
+//! | before image (before_len bytes) | after image (after_len bytes) | crc: u32 |
+//! +---------------------------------+-------------------------------+----------+
+//! ```
+//!
+//! The trailing CRC32 covers every preceding byte of the record, so a record torn by a
+//! crash mid-append is detected and treated as the end of the log.
+
+use super::Lsn;
+use getset::{CopyGetters, Getters};
+use thiserror::Error;
+
+/// Size of the fixed-length record header (everything before the images).
+pub const LOG_RECORD_HEADER_SIZE: usize = 8 + 1 + 4 + 4 + 4 + 4;
+
+/// Size of the trailing record checksum.
+pub const LOG_RECORD_CHECKSUM_SIZE: usize = 4;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum WalError {
+    #[error("Log record at offset {offset} is truncated")]
+    Truncated { offset: usize },
+
+    #[error("Log record at offset {offset} has an invalid checksum")]
+    ChecksumMismatch { offset: usize },
+
+    #[error("Log record at offset {offset} has unknown kind {kind}")]
+    UnknownKind { offset: usize, kind: u8 },
+}
+
+/// The kind of change a [`LogRecord`] describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogRecordKind {
+    /// A physical update of `after.len()` bytes at `offset` within a page.
+    Update = 0,
+    /// Every change with a lower LSN has been written to the database file.
+    Checkpoint = 1,
+}
+
+impl TryFrom<u8> for LogRecordKind {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(LogRecordKind::Update),
+            1 => Ok(LogRecordKind::Checkpoint),
+            kind => Err(kind),
+        }
+    }
+}
+
+/// A single entry in the write-ahead log.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct LogRecord {
+    #[getset(get_copy = "pub")]
+    lsn: Lsn,
+    #[getset(get_copy = "pub")]
+    kind: LogRecordKind,
+    #[getset(get_copy = "pub")]
+    page_id: u32,
+    #[getset(get_copy = "pub")]
+    offset: u32,
+    #[getset(get = "pub")]
+    before: Vec<u8>,
+    #[getset(get = "pub")]
+    after: Vec<u8>,
+}
+
+impl LogRecord {
+    /// Creates a record describing a change of a page's bytes at `offset` from `before` to `after`.
+    pub fn update(lsn: Lsn, page_id: u32, offset: u32, before: Vec<u8>, after: Vec<u8>) -> Self {
+        LogRecord {
+            lsn,
+            kind: LogRecordKind::Update,
+            page_id,
+            offset,
+            before,
+            after,
+        }
+    }
+
+    /// Creates a checkpoint record.
+    pub fn checkpoint(lsn: Lsn) -> Self {
+        LogRecord {
+            lsn,
+            kind: LogRecordKind::Checkpoint,
+            page_id: 0,
+            offset: 0,
+            before: Vec::new(),
+            after: Vec::new(),
+        }
+    }
+
+    /// Returns the number of bytes the record occupies in the log.
+    pub fn encoded_len(&self) -> usize {
+        LOG_RECORD_HEADER_SIZE + self.before.len() + self.after.len() + LOG_RECORD_CHECKSUM_SIZE
+    }
+
+    /// Encodes the record using the on-disk layout described in the module docs.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        buf.extend_from_slice(&self.lsn.to_be_bytes());
+        buf.push(self.kind as u8);
+        buf.extend_from_slice(&self.page_id.to_be_bytes());
+        buf.extend_from_slice(&self.offset.to_be_bytes());
+        buf.extend_from_slice(&(self.before.len() as u32).to_be_bytes());
+        buf.extend_from_slice(&(self.after.len() as u32).to_be_bytes());
+        buf.extend_from_slice(&self.before);
+        buf.extend_from_slice(&self.after);
+        let checksum = crc32fast::hash(&buf);
+        buf.extend_from_slice(&checksum.to_be_bytes());
+        buf
+    }
+
+    /// Decodes the record starting at `offset` in `log`, returning it along with
+    /// the number of bytes it occupied.
+    pub fn decode(log: &[u8], offset: usize) -> Result<(LogRecord, usize), WalError> {
+        let buf = &log[offset..];
+        if buf.len() < LOG_RECORD_HEADER_SIZE {
+            return Err(WalError::Truncated { offset });
+        }
+
+        let u32_at = |at: usize| u32::from_be_bytes(buf[at..at + 4].try_into().unwrap());
+        let lsn = u64::from_be_bytes(buf[..8].try_into().unwrap());
+        let kind = buf[8];
+        let page_id = u32_at(9);
+        let record_offset = u32_at(13);
+        let before_len = u32_at(17) as usize;
+        let after_len = u32_at(21) as usize;
+
+        let body_end = LOG_RECORD_HEADER_SIZE + before_len + after_len;
+        let len = body_end + LOG_RECORD_CHECKSUM_SIZE;
+        if buf.len() < len {
+            return Err(WalError::Truncated { offset });
+        }
+
+        let stored = u32::from_be_bytes(buf[body_end..len].try_into().unwrap());
+        if crc32fast::hash(&buf[..body_end]) != stored {
+            return Err(WalError::ChecksumMismatch { offset });
+        }
+
+        let kind =
+            LogRecordKind::try_from(kind).map_err(|kind| WalError::UnknownKind { offset, kind })?;
+        let before_end = LOG_RECORD_HEADER_SIZE + before_len;
+        let record = LogRecord {
+            lsn,
+            kind,
+            page_id,
+            offset: record_offset,
+            before: buf[LOG_RECORD_HEADER_SIZE..before_end].to_vec(),
+            after: buf[before_end..body_end].to_vec(),
+        };
+
+        Ok((record, len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let record = LogRecord::update(7, 3, 16, vec![1, 2, 3], vec![4, 5, 6, 7]);
+        let encoded = record.encode();
+        assert_eq!(encoded.len(), record.encoded_len());
+        assert_eq!(&encoded[..8], &7u64.to_be_bytes());
+        assert_eq!(encoded[8], LogRecordKind::Update as u8);
+
+        let (decoded, len) = LogRecord::decode(&encoded, 0).unwrap();
+        assert_eq!(decoded, record);
+        assert_eq!(len, encoded.len());
+    }
+
+    #[test]
+    fn test_decode_detects_torn_records() {
+        let mut log = LogRecord::checkpoint(1).encode();
+        let second = LogRecord::update(2, 0, 0, vec![0], vec![9]).encode();
+        log.extend_from_slice(&second[..second.len() - 1]);
+
+        let (first, len) = LogRecord::decode(&log, 0).unwrap();
+        assert_eq!(first.kind(), LogRecordKind::Checkpoint);
+        assert_eq!(
+            LogRecord::decode(&log, len),
+            Err(WalError::Truncated { offset: len })
+        );
+
+        let mut corrupt = second.clone();
+        corrupt[LOG_RECORD_HEADER_SIZE] ^= 0xff;
+        assert_eq!(
+            LogRecord::decode(&corrupt, 0),
+            Err(WalError::ChecksumMismatch { offset: 0 })
+        );
+    }
+}
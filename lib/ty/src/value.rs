@@ -98,6 +98,12 @@ impl Value {
         self.data.coerce_to(&target_type).map(Value::new)
     }
 
+    /// Coerces a [`Value`] of [`DataType::Decimal`] to `NUMERIC(precision, scale)` (see
+    /// [`DataType::coerce_decimal`]).
+    pub fn coerce_decimal(&self, precision: u32, scale: u32) -> Result<Self, TypeError> {
+        self.data.coerce_decimal(precision, scale).map(Value::new)
+    }
+
     /// Returns the minimum of two [`Value`] instances, respecting SQL `Null` semantics.
     pub fn min(self, other: Self) -> Self {
         if self.data <= other.data {